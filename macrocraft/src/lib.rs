@@ -11,7 +11,10 @@ use quote::{
     quote_spanned, ToTokens,
 };
 
+mod schema;
+
 use syn::{
+    DeriveInput,
     parse::{
         Parse,
         ParseStream,
@@ -83,3 +86,12 @@ pub fn nbt(input: TokenStream) -> TokenStream {
     
     input
 }
+
+/// Derives `DecodeNbt` and `EncodeNbt` for a struct with named fields, mapping each field to the
+/// compound tag of the same name. See `mcutil::nbt::schema` for the `#[nbt(...)]` field options.
+#[proc_macro_derive(NbtSchema, attributes(nbt))]
+pub fn derive_nbt_schema(input: TokenStream) -> TokenStream {
+    schema::derive(parse_macro_input!(input as DeriveInput))
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
@@ -0,0 +1,189 @@
+//! The `NbtSchema` derive, which generates `DecodeNbt` and `EncodeNbt` implementations
+//! that map the fields of a struct to the tags of a compound.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    spanned::Spanned,
+    Data,
+    DeriveInput,
+    Error,
+    Expr,
+    Fields,
+    GenericArgument,
+    Lit,
+    Meta,
+    NestedMeta,
+    PathArguments,
+    Result,
+    Type,
+};
+
+/// How a missing tag is handled.
+enum Missing {
+    /// Decoding fails with `McError::NotFoundInCompound`.
+    Required,
+    /// The field is `Default::default()`.
+    Default,
+    /// The field is the value of an expression.
+    Expr(Box<Expr>),
+}
+
+/// The `#[nbt(...)]` options of a field.
+struct FieldOptions {
+    name: String,
+    aliases: Vec<String>,
+    missing: Missing,
+    other: bool,
+}
+
+impl FieldOptions {
+    fn parse(field: &syn::Field) -> Result<Self> {
+        let mut options = FieldOptions {
+            name: field.ident.as_ref().map(ToString::to_string).unwrap_or_default(),
+            aliases: Vec::new(),
+            missing: Missing::Required,
+            other: false,
+        };
+        for attr in field.attrs.iter().filter(|attr| attr.path.is_ident("nbt")) {
+            let Meta::List(list) = attr.parse_meta()? else {
+                return Err(Error::new(attr.span(), "expected #[nbt(...)]"));
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => options.missing = Missing::Default,
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("other") => options.other = true,
+                    NestedMeta::Meta(Meta::NameValue(pair)) => {
+                        let Lit::Str(value) = &pair.lit else {
+                            return Err(Error::new(pair.lit.span(), "expected a string literal"));
+                        };
+                        if pair.path.is_ident("rename") {
+                            options.name = value.value();
+                        } else if pair.path.is_ident("alias") {
+                            options.aliases.push(value.value());
+                        } else if pair.path.is_ident("default") {
+                            options.missing = Missing::Expr(Box::new(value.parse()?));
+                        } else {
+                            return Err(Error::new(pair.path.span(), "unknown nbt option"));
+                        }
+                    }
+                    other => return Err(Error::new(other.span(), "unknown nbt option")),
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The `T` of an `Option<T>` field.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+pub(crate) fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(input.span(), "NbtSchema can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new(input.span(), "NbtSchema requires named fields"));
+    };
+    let mut decoders = Vec::new();
+    let mut encoders = Vec::new();
+    let mut other_field = None;
+    for field in &fields.named {
+        let ident = field.ident.as_ref().unwrap();
+        let options = FieldOptions::parse(field)?;
+        if options.other {
+            if other_field.is_some() {
+                return Err(Error::new(field.span(), "only one field can be #[nbt(other)]"));
+            }
+            other_field = Some(ident);
+            continue;
+        }
+        let name = &options.name;
+        let aliases = &options.aliases;
+        let tag = quote! {
+            __map.remove(#name)#(.or_else(|| __map.remove(#aliases)))*
+        };
+        let decoder = match (option_inner(&field.ty), &options.missing) {
+            (Some(inner), Missing::Required) => quote! {
+                match #tag {
+                    ::core::option::Option::Some(tag) => ::core::option::Option::Some(<#inner as ::mcutil::nbt::tag::DecodeNbt>::decode_nbt(tag)?),
+                    ::core::option::Option::None => ::core::option::Option::None,
+                }
+            },
+            (_, missing) => {
+                let ty = &field.ty;
+                let fallback = match missing {
+                    Missing::Required => quote! {
+                        return ::core::result::Result::Err(::mcutil::McError::NotFoundInCompound(#name.to_owned()))
+                    },
+                    Missing::Default => quote! { ::core::default::Default::default() },
+                    Missing::Expr(expr) => quote! { #expr },
+                };
+                quote! {
+                    match #tag {
+                        ::core::option::Option::Some(tag) => <#ty as ::mcutil::nbt::tag::DecodeNbt>::decode_nbt(tag)?,
+                        ::core::option::Option::None => #fallback,
+                    }
+                }
+            }
+        };
+        decoders.push(quote! { let #ident = #decoder; });
+        encoders.push(match option_inner(&field.ty) {
+            Some(_) => quote! {
+                if let ::core::option::Option::Some(value) = self.#ident {
+                    __map.insert(#name.to_owned(), value.encode_nbt());
+                }
+            },
+            None => quote! {
+                __map.insert(#name.to_owned(), self.#ident.encode_nbt());
+            },
+        });
+    }
+    let idents = fields.named.iter().map(|field| field.ident.as_ref().unwrap());
+    let (other_decoder, other_encoder) = match other_field {
+        Some(ident) => (quote! { let #ident = __map; }, quote! { __map.extend(self.#ident); }),
+        None => (quote! {}, quote! {}),
+    };
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mcutil::nbt::tag::DecodeNbt for #ident #ty_generics #where_clause {
+            fn decode_nbt(nbt: ::mcutil::nbt::tag::Tag) -> ::mcutil::McResult<Self> {
+                let ::mcutil::nbt::tag::Tag::Compound(mut __map) = nbt else {
+                    return ::core::result::Result::Err(::mcutil::McError::NbtDecodeError);
+                };
+                #(#decoders)*
+                #other_decoder
+                ::core::result::Result::Ok(Self {
+                    #(#idents,)*
+                })
+            }
+        }
+
+        impl #impl_generics ::mcutil::nbt::tag::EncodeNbt for #ident #ty_generics #where_clause {
+            fn encode_nbt(self) -> ::mcutil::nbt::tag::Tag {
+                #[allow(unused_imports)]
+                use ::mcutil::nbt::tag::EncodeNbt as _;
+                let mut __map = ::mcutil::nbt::Map::new();
+                #(#encoders)*
+                #other_encoder
+                ::mcutil::nbt::tag::Tag::Compound(__map)
+            }
+        }
+    })
+}
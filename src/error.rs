@@ -1,10 +1,37 @@
 
 
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use thiserror::Error;
 
+use crate::math::coord::WorldCoord;
+use crate::world::io::region::RegionCoord;
+
+/// Where an error happened, attached to an error with [McError::with_context].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorContext {
+    /// The file that was being read or written.
+    Path(PathBuf),
+    /// The chunk that was being processed.
+    Chunk(WorldCoord),
+    /// The chunk within a region file that was being read or written.
+    RegionChunk(RegionCoord),
+    /// The NBT path of the tag that was being decoded.
+    NbtPath(String),
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorContext::Path(path) => write!(f, "{}", path.display()),
+            ErrorContext::Chunk(coord) => write!(f, "chunk ({}, {}) in {:?}", coord.x, coord.z, coord.dimension),
+            ErrorContext::RegionChunk(coord) => write!(f, "chunk {coord}"),
+            ErrorContext::NbtPath(path) => write!(f, "at {path}"),
+        }
+    }
+}
+
 /// The master error type.
 #[derive(Debug, Error)]
 pub enum McError {
@@ -18,6 +45,10 @@ pub enum McError {
     RegionDataTooLarge,
     #[error("Invalid Compression value: {0}")]
     InvalidCompressionScheme(u8),
+    #[error("Unsupported custom compression scheme: \"{0}\"")]
+    UnsupportedCustomCompression(String),
+    #[error("Chunk at {0} is stored in an external file, but its path can't be determined from the region file name.")]
+    ExternalChunkPathUnknown(crate::world::io::region::RegionCoord),
     #[error("Out of range error.")]
     OutOfRange,
     #[error("Failed to convert to UTF-8 string.")]
@@ -42,6 +73,40 @@ pub enum McError {
     WorldDirectoryNotFound(PathBuf),
     #[error("Failed to save chunk.")]
     FailedToSaveChunk,
+    #[error("Chunk at {0} has already been written.")]
+    DuplicateRegionCoord(crate::world::io::region::RegionCoord),
+    #[error("Chunk at {coord_hint} is truncated. Expected {expected} bytes, but only {got} were available.")]
+    TruncatedChunk {
+        coord_hint: crate::world::io::region::RegionCoord,
+        expected: u64,
+        got: u64,
+    },
+    #[error("Unsupported DataVersion: {0}")]
+    UnsupportedDataVersion(i32),
+    #[error("Invalid block state: {0}")]
+    InvalidBlockState(String),
+    #[error("The world at {0} is being used by another process.")]
+    WorldLocked(PathBuf),
+    #[error("Invalid NBT path: {0}")]
+    InvalidNbtPath(String),
+    #[error("Unsupported archive format: {0}")]
+    UnsupportedArchiveFormat(PathBuf),
+    #[error("The operation was cancelled.")]
+    Cancelled,
+    #[error("Unknown dimension: {0:?}")]
+    UnknownDimension(crate::math::coord::Dimension),
+    #[cfg(feature = "backup")]
+    #[error("Zip Error: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+    #[cfg(feature = "json")]
+    #[error("JSON Error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    /// An error with the location where it happened. See [McError::with_context].
+    #[error("{context}: {source}")]
+    WithContext {
+        context: ErrorContext,
+        source: Box<McError>,
+    },
 }
 
 impl McError {
@@ -61,6 +126,79 @@ impl McError {
     pub fn custom<T, S: AsRef<str>>(msg: S) -> Result<T,Self> {
         Err(McError::Custom(msg.as_ref().to_owned()))
     }
+
+    /// Wraps the error with the location where it happened.
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        McError::WithContext {
+            context,
+            source: Box::new(self),
+        }
+    }
+
+    /// The error without any [context](McError::with_context).
+    /// Match on this rather than on the error itself when the error may have context.
+    pub fn root(&self) -> &McError {
+        match self {
+            McError::WithContext { source, .. } => source.root(),
+            err => err,
+        }
+    }
+
+    /// The context of the error, from the outermost to the innermost.
+    pub fn contexts(&self) -> Vec<&ErrorContext> {
+        let mut contexts = Vec::new();
+        let mut err = self;
+        while let McError::WithContext { context, source } = err {
+            contexts.push(context);
+            err = source;
+        }
+        contexts
+    }
+}
+
+pub type McResult<T> = Result<T,McError>;
+
+/// Adds [ErrorContext] to the error of a result.
+pub trait ResultExt<T> {
+    /// Wraps the error with the context returned by `context`, which is only called if there is an error.
+    fn with_context<F: FnOnce() -> ErrorContext>(self, context: F) -> McResult<T>;
+
+    /// Wraps the error with the path of the file that was being read or written.
+    fn with_path<P: AsRef<Path>>(self, path: P) -> McResult<T>;
+
+    /// Wraps the error with the coordinate of the chunk that was being processed.
+    fn with_chunk(self, coord: WorldCoord) -> McResult<T>;
 }
 
-pub type McResult<T> = Result<T,McError>;
\ No newline at end of file
+impl<T, E: Into<McError>> ResultExt<T> for Result<T, E> {
+    fn with_context<F: FnOnce() -> ErrorContext>(self, context: F) -> McResult<T> {
+        self.map_err(|err| err.into().with_context(context()))
+    }
+
+    fn with_path<P: AsRef<Path>>(self, path: P) -> McResult<T> {
+        self.with_context(|| ErrorContext::Path(path.as_ref().to_owned()))
+    }
+
+    fn with_chunk(self, coord: WorldCoord) -> McResult<T> {
+        self.with_context(|| ErrorContext::Chunk(coord))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_context_test() {
+        let result: McResult<()> = Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+            .with_context(|| ErrorContext::RegionChunk(RegionCoord::new(3, 4)))
+            .with_path("world/region/r.0.0.mca");
+        let err = result.unwrap_err();
+        assert!(matches!(err.root(), McError::IoError(_)));
+        assert_eq!(err.contexts(), vec![
+            &ErrorContext::Path(PathBuf::from("world/region/r.0.0.mca")),
+            &ErrorContext::RegionChunk(RegionCoord::new(3, 4)),
+        ]);
+        assert_eq!(err.to_string(), "world/region/r.0.0.mca: chunk (3, 4): IO Error: unexpected end of file");
+    }
+}
\ No newline at end of file
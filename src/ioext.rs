@@ -9,6 +9,11 @@ pub const BUFFERSIZE: usize = 8192;
 /// For types that can be written to a writer.
 pub trait Writable {
     fn write_to<W: Write>(&self, writer: &mut W) -> McResult<usize>;
+
+    /// The exact number of bytes that [Writable::write_to] will write, if it can be known without writing.
+    fn write_size(&self) -> Option<usize> {
+        None
+    }
 }
 
 /// For types that can be read from a reader.
@@ -1,3 +1,6 @@
+// Lets the code generated by the macrocraft derives refer to this crate as `::mcutil` from within it.
+extern crate self as mcutil;
+
 pub mod nbt;
 pub mod world;
 pub mod ioext;
@@ -11,4 +14,8 @@ pub mod meshing;
 pub use flate2;
 
 pub use error::McError;
-pub use error::McResult;
\ No newline at end of file
+pub use error::McResult;
+pub use error::{ErrorContext, ResultExt};
+pub use util::compression::compression_from_level;
+pub use util::compression::level_of;
+pub use util::cancel::CancellationToken;
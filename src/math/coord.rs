@@ -1,6 +1,6 @@
 use glam::I64Vec3;
 
-use crate::world::block::CubeDirection;
+use crate::world::{block::CubeDirection, io::region::{filename::RegionFileName, RegionCoord}};
 
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Dimension {
@@ -10,6 +10,7 @@ pub enum Dimension {
     Other(u32),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Cardinal {
     East,	// +X
     West,	// -X
@@ -192,6 +193,11 @@ impl Default for Dimension {
     }
 }
 
+/// A chunk coordinate in world space (chunk x and z, as in `xPos` and `zPos`).
+/// [WorldCoord] is used for both chunk and region coordinates; this alias makes
+/// it clear when a function expects a chunk.
+pub type ChunkCoord = WorldCoord;
+
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
 pub struct WorldCoord {
     pub x: i64,
@@ -242,6 +248,44 @@ impl WorldCoord {
         }
     }
 
+    /// Converts a chunk coordinate into its slot within the region file that contains it.
+    #[inline(always)]
+    pub fn region_local(self) -> RegionCoord {
+        RegionCoord::new(self.x.rem_euclid(32) as u16, self.z.rem_euclid(32) as u16)
+    }
+
+    /// Converts a chunk coordinate into the coordinate of the region that contains it
+    /// and its slot within that region.
+    #[inline(always)]
+    pub fn region_and_local(self) -> (Self, RegionCoord) {
+        (self.region_coord(), self.region_local())
+    }
+
+    /// Converts a region coordinate and a slot within the region into a chunk coordinate.
+    /// This is the inverse of [WorldCoord::region_and_local].
+    #[inline(always)]
+    pub fn chunk_in_region(self, local: RegionCoord) -> Self {
+        Self::new(self.x * 32 + local.x() as i64, self.z * 32 + local.z() as i64, self.dimension)
+    }
+
+    /// Converts a chunk coordinate into the block coordinate of a block within that chunk,
+    /// where `x` and `z` are relative to the chunk (`0..16`) and `y` is the world height.
+    #[inline(always)]
+    pub fn block_in_chunk(self, x: i64, y: i64, z: i64) -> BlockCoord {
+        BlockCoord::new(self.x * 16 + x, y, self.z * 16 + z, self.dimension)
+    }
+
+    /// The name of the region file for a region coordinate, such as `r.-3.7.mca`.
+    pub fn region_file_name(self) -> String {
+        RegionFileName::new(self.x, self.z).to_string()
+    }
+
+    /// Parses the region coordinate from a region file name such as `r.-3.7.mca`.
+    /// Returns `None` if the name isn't a region file name.
+    pub fn from_region_file_name(name: &str, dimension: Dimension) -> Option<Self> {
+        RegionFileName::parse(name).map(|name| Self::new(name.x, name.z, dimension))
+    }
+
     #[inline(always)]
     pub fn neighbor(self, direction: Cardinal) -> Self {
         self + direction
@@ -319,6 +363,33 @@ impl BlockCoord {
         }
     }
 
+    /// The y coordinate of the section that contains the block (the `Y` of the section).
+    #[inline(always)]
+    pub fn section_y(self) -> i64 {
+        self.y.div_euclid(16)
+    }
+
+    /// The index of the section that contains the block within a chunk's sections,
+    /// where `bottom_section_y` is the `Y` of the chunk's lowest section.
+    /// Returns `None` if the block is below the lowest section.
+    #[inline(always)]
+    pub fn section_index(self, bottom_section_y: i64) -> Option<usize> {
+        usize::try_from(self.section_y() - bottom_section_y).ok()
+    }
+
+    /// The coordinate of the block within its chunk: `x` and `z` are relative to the chunk (`0..16`),
+    /// while `y` is unchanged.
+    #[inline(always)]
+    pub fn chunk_local(self) -> (i64, i64, i64) {
+        (self.x.rem_euclid(16), self.y, self.z.rem_euclid(16))
+    }
+
+    /// The slot within its region file of the chunk that contains the block.
+    #[inline(always)]
+    pub fn region_local(self) -> RegionCoord {
+        self.chunk_coord().region_local()
+    }
+
     #[inline(always)]
     pub fn neighbor(self, direction: CubeDirection) -> Self {
         let (x,y,z) = direction.coord();
@@ -444,4 +515,28 @@ impl std::ops::Sub<CubeDirection> for BlockCoord {
         let (x,y,z) = rhs.coord();
         Self::new(self.x - x, self.y - y, self.z - z, self.dimension)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coord_conversion_test() {
+        let block = BlockCoord::overworld(-1, -17, 513);
+        assert_eq!(block.chunk_coord(), WorldCoord::overworld(-1, 32));
+        assert_eq!(block.region_coord(), WorldCoord::overworld(-1, 1));
+        assert_eq!(block.section_y(), -2);
+        assert_eq!(block.section_index(-4), Some(2));
+        assert_eq!(block.section_index(-1), None);
+        assert_eq!(block.chunk_local(), (15, -17, 1));
+        assert_eq!(block.region_local(), RegionCoord::new(31, 0));
+        let chunk = block.chunk_coord();
+        let (region, local) = chunk.region_and_local();
+        assert_eq!(region.chunk_in_region(local), chunk);
+        assert_eq!(chunk.block_in_chunk(15, -17, 1), block);
+        assert_eq!(region.region_file_name(), "r.-1.1.mca");
+        assert_eq!(WorldCoord::from_region_file_name("r.-3.7.mca", Dimension::Nether), Some(WorldCoord::nether(-3, 7)));
+        assert_eq!(WorldCoord::from_region_file_name("r.-3.mca", Dimension::Nether), None);
+    }
+}
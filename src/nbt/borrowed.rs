@@ -0,0 +1,536 @@
+//! Zero-copy NBT parsing for read-only workloads.
+//!
+//! [TagRef] borrows from the bytes that it was parsed from, so strings and arrays are never copied and compounds
+//! and lists aren't collected into maps and vectors. The whole tag is validated when it's parsed, after which
+//! compounds and lists are walked lazily as they're accessed. Arrays are stored big-endian, so their elements are
+//! decoded as they're read.
+//!
+//! Combined with [RegionFile::read_decompressed_into](crate::world::io::region::regionfile::RegionFile::read_decompressed_into),
+//! which reuses a single buffer, scanning every chunk of a region only allocates when the buffer has to grow.
+
+use std::marker::PhantomData;
+
+use crate::{McError, McResult};
+
+use super::{
+    tag::{ListTag, NamedTag, Tag, TagID},
+    Map,
+};
+
+/// Minecraft refuses to read NBT that is nested deeper than this.
+const MAX_DEPTH: usize = 512;
+
+/// A borrowed NBT tag. See the [module documentation](self).
+#[derive(Debug, Clone, Copy)]
+pub enum TagRef<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'a [i8]),
+    String(&'a str),
+    List(ListRef<'a>),
+    Compound(CompoundRef<'a>),
+    IntArray(ArrayRef<'a, i32>),
+    LongArray(ArrayRef<'a, i64>),
+}
+
+/// A borrowed compound. Entries are read in the order that they were written.
+#[derive(Debug, Clone, Copy)]
+pub struct CompoundRef<'a> {
+    /// The entries, without the End tag.
+    bytes: &'a [u8],
+}
+
+/// A borrowed list.
+#[derive(Debug, Clone, Copy)]
+pub struct ListRef<'a> {
+    /// `None` for lists with the End tag as their element type.
+    id: Option<TagID>,
+    len: usize,
+    /// The elements, without the element type and length.
+    bytes: &'a [u8],
+}
+
+/// A borrowed `IntArray` or `LongArray`.
+#[derive(Debug, Clone, Copy)]
+pub struct ArrayRef<'a, T> {
+    bytes: &'a [u8],
+    _element: PhantomData<T>,
+}
+
+/// Array elements, which are stored big-endian.
+pub trait ArrayElement: Copy + 'static {
+    const SIZE: usize;
+    fn from_be_slice(bytes: &[u8]) -> Self;
+}
+
+impl ArrayElement for i32 {
+    const SIZE: usize = 4;
+    fn from_be_slice(bytes: &[u8]) -> Self {
+        i32::from_be_bytes(bytes.try_into().expect("Slice is the size of an i32."))
+    }
+}
+
+impl ArrayElement for i64 {
+    const SIZE: usize = 8;
+    fn from_be_slice(bytes: &[u8]) -> Self {
+        i64::from_be_bytes(bytes.try_into().expect("Slice is the size of an i64."))
+    }
+}
+
+/// Reads validated bytes. Every read returns [McError::NbtDecodeError] if there aren't enough bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn take(&mut self, count: usize) -> McResult<&'a [u8]> {
+        let end = self.position.checked_add(count).filter(|&end| end <= self.bytes.len()).ok_or(McError::NbtDecodeError)?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> McResult<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("Slice has N bytes."))
+    }
+
+    fn u8(&mut self) -> McResult<u8> {
+        Ok(self.take_array::<1>()?[0])
+    }
+
+    /// Reads a length prefix, which can't be negative.
+    fn length(&mut self) -> McResult<usize> {
+        usize::try_from(i32::from_be_bytes(self.take_array()?)).map_err(|_| McError::NbtDecodeError)
+    }
+
+    fn str(&mut self) -> McResult<&'a str> {
+        let length = u16::from_be_bytes(self.take_array()?) as usize;
+        std::str::from_utf8(self.take(length)?).map_err(|_| McError::NbtDecodeError)
+    }
+
+    /// Reads the payload of a tag with the given type, validating everything within it.
+    fn payload(&mut self, id: TagID, depth: usize) -> McResult<TagRef<'a>> {
+        if depth > MAX_DEPTH {
+            return Err(McError::NbtDecodeError);
+        }
+        Ok(match id {
+            TagID::Byte => TagRef::Byte(self.u8()? as i8),
+            TagID::Short => TagRef::Short(i16::from_be_bytes(self.take_array()?)),
+            TagID::Int => TagRef::Int(i32::from_be_bytes(self.take_array()?)),
+            TagID::Long => TagRef::Long(i64::from_be_bytes(self.take_array()?)),
+            TagID::Float => TagRef::Float(f32::from_be_bytes(self.take_array()?)),
+            TagID::Double => TagRef::Double(f64::from_be_bytes(self.take_array()?)),
+            TagID::ByteArray => {
+                let length = self.length()?;
+                TagRef::ByteArray(bytemuck::cast_slice(self.take(length)?))
+            }
+            TagID::String => TagRef::String(self.str()?),
+            TagID::List => {
+                let id = match TagID::try_from(self.u8()?) {
+                    Ok(id) => Some(id),
+                    Err(McError::EndTagMarker) => None,
+                    Err(err) => return Err(err),
+                };
+                let len = self.length()?;
+                let start = self.position;
+                match id {
+                    Some(id) => for _ in 0..len {
+                        self.payload(id, depth + 1)?;
+                    },
+                    // Minecraft writes empty lists with the End type, but a non-empty list of End tags can't be read.
+                    None if len > 0 => return Err(McError::NbtDecodeError),
+                    None => (),
+                }
+                TagRef::List(ListRef { id, len, bytes: &self.bytes[start..self.position] })
+            }
+            TagID::Compound => {
+                let start = self.position;
+                loop {
+                    let end = self.position;
+                    match TagID::try_from(self.u8()?) {
+                        Ok(id) => {
+                            self.str()?;
+                            self.payload(id, depth + 1)?;
+                        }
+                        Err(McError::EndTagMarker) => break TagRef::Compound(CompoundRef { bytes: &self.bytes[start..end] }),
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+            TagID::IntArray => {
+                let length = self.length()?;
+                TagRef::IntArray(ArrayRef::new(self.take(length.checked_mul(4).ok_or(McError::NbtDecodeError)?)?))
+            }
+            TagID::LongArray => {
+                let length = self.length()?;
+                TagRef::LongArray(ArrayRef::new(self.take(length.checked_mul(8).ok_or(McError::NbtDecodeError)?)?))
+            }
+        })
+    }
+}
+
+impl<'a> TagRef<'a> {
+    /// Parses a named tag (such as the root of a chunk or an NBT file) from uncompressed NBT,
+    /// returning the name and the tag. Bytes after the tag are ignored.
+    pub fn read_named(bytes: &'a [u8]) -> McResult<(&'a str, TagRef<'a>)> {
+        let mut reader = Reader::new(bytes);
+        let id = TagID::try_from(reader.u8()?)?;
+        let name = reader.str()?;
+        Ok((name, reader.payload(id, 0)?))
+    }
+
+    /// Parses an unnamed root tag, as used by the network protocol since 1.20.2.
+    /// See [NbtOptions::network](crate::nbt::io::NbtOptions::network).
+    pub fn read_unnamed(bytes: &'a [u8]) -> McResult<TagRef<'a>> {
+        let mut reader = Reader::new(bytes);
+        let id = TagID::try_from(reader.u8()?)?;
+        reader.payload(id, 0)
+    }
+
+    /// Parses the payload of a tag with the type `id`, without the type and name that precede it in a named tag.
+    pub fn read_payload(id: TagID, bytes: &'a [u8]) -> McResult<TagRef<'a>> {
+        Reader::new(bytes).payload(id, 0)
+    }
+
+    pub fn id(&self) -> TagID {
+        match self {
+            TagRef::Byte(_) => TagID::Byte,
+            TagRef::Short(_) => TagID::Short,
+            TagRef::Int(_) => TagID::Int,
+            TagRef::Long(_) => TagID::Long,
+            TagRef::Float(_) => TagID::Float,
+            TagRef::Double(_) => TagID::Double,
+            TagRef::ByteArray(_) => TagID::ByteArray,
+            TagRef::String(_) => TagID::String,
+            TagRef::List(_) => TagID::List,
+            TagRef::Compound(_) => TagID::Compound,
+            TagRef::IntArray(_) => TagID::IntArray,
+            TagRef::LongArray(_) => TagID::LongArray,
+        }
+    }
+
+    /// Gets a child of this tag if it's a compound.
+    pub fn get(&self, key: &str) -> Option<TagRef<'a>> {
+        match self {
+            TagRef::Compound(compound) => compound.get(key),
+            _ => None,
+        }
+    }
+
+    /// Follows a sequence of compound keys, such as `["Level", "Sections"]`.
+    pub fn get_path(&self, keys: &[&str]) -> Option<TagRef<'a>> {
+        keys.iter().try_fold(*self, |tag, key| tag.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            TagRef::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of a numeric tag as an `i64`. Floating point values are truncated.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            TagRef::Byte(value) => Some(value as i64),
+            TagRef::Short(value) => Some(value as i64),
+            TagRef::Int(value) => Some(value as i64),
+            TagRef::Long(value) => Some(value),
+            TagRef::Float(value) => Some(value as i64),
+            TagRef::Double(value) => Some(value as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<CompoundRef<'a>> {
+        match self {
+            TagRef::Compound(compound) => Some(*compound),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<ListRef<'a>> {
+        match self {
+            TagRef::List(list) => Some(*list),
+            _ => None,
+        }
+    }
+
+    /// Copies this tag into an owned [Tag].
+    pub fn to_tag(&self) -> Tag {
+        match *self {
+            TagRef::Byte(value) => Tag::Byte(value),
+            TagRef::Short(value) => Tag::Short(value),
+            TagRef::Int(value) => Tag::Int(value),
+            TagRef::Long(value) => Tag::Long(value),
+            TagRef::Float(value) => Tag::Float(value),
+            TagRef::Double(value) => Tag::Double(value),
+            TagRef::ByteArray(value) => Tag::ByteArray(value.to_vec()),
+            TagRef::String(value) => Tag::String(value.to_owned()),
+            TagRef::List(list) => Tag::List(list.to_list_tag()),
+            TagRef::Compound(compound) => Tag::Compound(compound.to_map()),
+            TagRef::IntArray(array) => Tag::IntArray(array.to_vec()),
+            TagRef::LongArray(array) => Tag::LongArray(array.to_vec()),
+        }
+    }
+}
+
+impl<'a> CompoundRef<'a> {
+    pub fn iter(&self) -> CompoundIter<'a> {
+        CompoundIter { reader: Reader::new(self.bytes) }
+    }
+
+    /// Finds the entry with the given key. This walks the entries in order, so it's linear in the size of the compound.
+    pub fn get(&self, key: &str) -> Option<TagRef<'a>> {
+        self.iter().find(|(name, _)| *name == key).map(|(_, tag)| tag)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Counts the entries. Like [CompoundRef::get], this walks the compound.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn to_map(&self) -> Map {
+        self.iter().map(|(name, tag)| (name.to_owned(), tag.to_tag())).collect()
+    }
+}
+
+impl<'a> IntoIterator for CompoundRef<'a> {
+    type Item = (&'a str, TagRef<'a>);
+    type IntoIter = CompoundIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterates the entries of a [CompoundRef].
+pub struct CompoundIter<'a> {
+    reader: Reader<'a>,
+}
+
+impl<'a> Iterator for CompoundIter<'a> {
+    type Item = (&'a str, TagRef<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.is_empty() {
+            return None;
+        }
+        // The compound was validated when it was parsed, so reading an entry can't fail.
+        let id = TagID::try_from(self.reader.u8().ok()?).ok()?;
+        let name = self.reader.str().ok()?;
+        Some((name, self.reader.payload(id, 0).ok()?))
+    }
+}
+
+impl<'a> ListRef<'a> {
+    /// The type of the elements, or `None` if the list is empty and has no type.
+    pub fn element_id(&self) -> Option<TagID> {
+        self.id
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> ListIter<'a> {
+        ListIter {
+            id: self.id,
+            remaining: self.len,
+            reader: Reader::new(self.bytes),
+        }
+    }
+
+    /// Gets an element. This is constant time for lists of numbers, and linear for other lists.
+    pub fn get(&self, index: usize) -> Option<TagRef<'a>> {
+        if index >= self.len {
+            return None;
+        }
+        let size = match self.id? {
+            TagID::Byte => 1,
+            TagID::Short => 2,
+            TagID::Int | TagID::Float => 4,
+            TagID::Long | TagID::Double => 8,
+            _ => return self.iter().nth(index),
+        };
+        TagRef::read_payload(self.id?, &self.bytes[index * size..]).ok()
+    }
+
+    pub fn to_list_tag(&self) -> ListTag {
+        macro_rules! collect {
+            ($variant:ident) => {
+                ListTag::$variant(self.iter().filter_map(|tag| match tag.to_tag() {
+                    Tag::$variant(value) => Some(value),
+                    _ => None,
+                }).collect())
+            };
+        }
+        match self.id {
+            None => ListTag::Empty,
+            Some(TagID::Byte) => collect!(Byte),
+            Some(TagID::Short) => collect!(Short),
+            Some(TagID::Int) => collect!(Int),
+            Some(TagID::Long) => collect!(Long),
+            Some(TagID::Float) => collect!(Float),
+            Some(TagID::Double) => collect!(Double),
+            Some(TagID::ByteArray) => collect!(ByteArray),
+            Some(TagID::String) => collect!(String),
+            Some(TagID::List) => collect!(List),
+            Some(TagID::Compound) => collect!(Compound),
+            Some(TagID::IntArray) => collect!(IntArray),
+            Some(TagID::LongArray) => collect!(LongArray),
+        }
+    }
+}
+
+impl<'a> IntoIterator for ListRef<'a> {
+    type Item = TagRef<'a>;
+    type IntoIter = ListIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterates the elements of a [ListRef].
+pub struct ListIter<'a> {
+    id: Option<TagID>,
+    remaining: usize,
+    reader: Reader<'a>,
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = TagRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        // The list was validated when it was parsed, so reading an element can't fail.
+        self.reader.payload(self.id?, 0).ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T: ArrayElement> ArrayRef<'a, T> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, _element: PhantomData }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len() / T::SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.bytes.get(index * T::SIZE..(index + 1) * T::SIZE).map(T::from_be_slice)
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = T> + 'a {
+        self.bytes.chunks_exact(T::SIZE).map(T::from_be_slice)
+    }
+
+    /// The big-endian bytes of the array.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+}
+
+impl NamedTag {
+    /// Parses a [NamedTag] from uncompressed NBT with [TagRef::read_named], then copies it.
+    pub fn from_borrowed(bytes: &[u8]) -> McResult<NamedTag> {
+        let (name, tag) = TagRef::read_named(bytes)?;
+        Ok(NamedTag::with_name(name, tag.to_tag()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::io::{write_named_tag, NbtOptions, WriteNbt};
+
+    #[test]
+    fn borrowed_test() {
+        let tag = Tag::parse(r#"{
+            DataVersion: 3465,
+            Status: "minecraft:full",
+            sections: [
+                { Y: -4b, block_states: { palette: [{ Name: "minecraft:stone" }], data: [L; 1L, -2L] } },
+                { Y: -3b, biomes: { palette: ["minecraft:plains"] } }
+            ],
+            Heightmaps: { WORLD_SURFACE: [I; 7, 8, 9] },
+            empty: [],
+            bytes: [B; 1b, 2b],
+            doubles: [0.5d, 1.5d]
+        }"#).unwrap();
+        let mut bytes = Vec::new();
+        write_named_tag(&mut bytes, &tag, "root").unwrap();
+
+        let (name, root) = TagRef::read_named(&bytes).unwrap();
+        assert_eq!(name, "root");
+        assert_eq!(root.get("DataVersion").and_then(|tag| tag.as_i64()), Some(3465));
+        assert_eq!(root.get("Status").and_then(|tag| tag.as_str()), Some("minecraft:full"));
+        let sections = root.get("sections").and_then(|tag| tag.as_list()).unwrap();
+        assert_eq!(sections.len(), 2);
+        let data = sections.get(0).unwrap().get_path(&["block_states", "data"]).unwrap();
+        assert!(matches!(data, TagRef::LongArray(array) if array.to_vec() == [1, -2]));
+        assert!(sections.get(1).unwrap().get_path(&["block_states", "data"]).is_none());
+        assert!(matches!(root.get_path(&["Heightmaps", "WORLD_SURFACE"]), Some(TagRef::IntArray(array)) if array.get(2) == Some(9)));
+        assert!(matches!(root.get("empty"), Some(TagRef::List(list)) if list.is_empty() && list.iter().next().is_none()));
+        assert!(matches!(root.get("bytes"), Some(TagRef::ByteArray([1, 2]))));
+        assert!(matches!(root.get("doubles").and_then(|tag| tag.as_list()?.get(1)), Some(TagRef::Double(value)) if value == 1.5));
+        assert_eq!(root.as_compound().unwrap().len(), 7);
+        assert!(crate::nbt::diff(&tag, &root.to_tag()).is_empty());
+
+        let mut region = crate::world::io::region::regionfile::RegionFile::new_in_memory();
+        region.write_data((1u16, 2u16), &NamedTag::with_name("root", tag.clone())).unwrap();
+        let mut buffer = Vec::new();
+        assert_eq!(region.read_decompressed_into((1u16, 2u16), &mut buffer).unwrap(), bytes.len());
+        assert_eq!(buffer, bytes);
+
+        let mut network = Vec::new();
+        network.write_root_tag(&NamedTag::new(tag.clone()), NbtOptions::network()).unwrap();
+        assert!(TagRef::read_unnamed(&network).unwrap().get("DataVersion").is_some());
+
+        // Truncated data is rejected up front.
+        for end in [1, bytes.len() / 2, bytes.len() - 1] {
+            assert!(TagRef::read_named(&bytes[..end]).is_err());
+        }
+    }
+}
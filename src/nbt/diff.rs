@@ -0,0 +1,148 @@
+//! Structural differences between two [Tag]s.
+//!
+//! When an edit produces a broken chunk, comparing the NBT from before and after the edit is usually the quickest
+//! way to see what went wrong. Compounds are compared key by key, and lists and arrays are compared element by
+//! element, so each difference is reported at the deepest path where the values differ.
+
+use std::fmt::Display;
+
+use super::{
+    format::write_tag,
+    format::Indentation,
+    path::{NbtPath, NbtPathNode},
+    tag::Tag,
+    tagpath::TagPathPart,
+    tagref::ValueRef,
+};
+
+/// A difference between two [Tag]s, at the path where it was found. An empty path is the root.
+#[derive(Debug, Clone)]
+pub enum NbtDiff {
+    /// The value is only in the second tag.
+    Added { path: NbtPath, value: Tag },
+    /// The value is only in the first tag.
+    Removed { path: NbtPath, value: Tag },
+    /// The value is in both tags, but isn't the same.
+    Changed { path: NbtPath, old: Tag, new: Tag },
+}
+
+impl NbtDiff {
+    pub fn path(&self) -> &NbtPath {
+        match self {
+            NbtDiff::Added { path, .. } | NbtDiff::Removed { path, .. } | NbtDiff::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl Display for NbtDiff {
+    /// Writes the difference on a single line, prefixed with `+`, `-`, or `~`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (sign, path) = match self {
+            NbtDiff::Added { path, .. } => ('+', path),
+            NbtDiff::Removed { path, .. } => ('-', path),
+            NbtDiff::Changed { path, .. } => ('~', path),
+        };
+        if path.nodes().is_empty() {
+            write!(f, "{sign} <root>: ")?;
+        } else {
+            write!(f, "{sign} {path}: ")?;
+        }
+        match self {
+            NbtDiff::Added { value, .. } | NbtDiff::Removed { value, .. } => write_tag(f, value, true, Indentation::space(), true),
+            NbtDiff::Changed { old, new, .. } => {
+                write_tag(f, old, true, Indentation::space(), true)?;
+                write!(f, " -> ")?;
+                write_tag(f, new, true, Indentation::space(), true)
+            }
+        }
+    }
+}
+
+/// Finds the differences between `old` and `new`. Compound keys are visited in sorted order, so the result is
+/// the same regardless of the order that the keys are stored in.
+pub fn diff(old: &Tag, new: &Tag) -> Vec<NbtDiff> {
+    let mut differences = Vec::new();
+    diff_values(&mut Vec::new(), ValueRef::from(old), ValueRef::from(new), &mut differences);
+    differences
+}
+
+fn diff_values(path: &mut Vec<NbtPathNode>, old: ValueRef<'_>, new: ValueRef<'_>, differences: &mut Vec<NbtDiff>) {
+    let current = |path: &[NbtPathNode]| NbtPath::new(path.to_vec());
+    match (old, new) {
+        (ValueRef::Compound(old), ValueRef::Compound(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys().filter(|key| !old.contains_key(*key))).collect();
+            keys.sort();
+            for key in keys {
+                path.push(NbtPathNode::Key(key.clone()));
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => diff_values(path, ValueRef::from(old), ValueRef::from(new), differences),
+                    (Some(old), None) => differences.push(NbtDiff::Removed { path: current(path), value: old.clone() }),
+                    (None, Some(new)) => differences.push(NbtDiff::Added { path: current(path), value: new.clone() }),
+                    (None, None) => unreachable!("The key came from one of the compounds."),
+                }
+                path.pop();
+            }
+        }
+        (ValueRef::List(_), ValueRef::List(_))
+        | (ValueRef::ByteArray(_), ValueRef::ByteArray(_))
+        | (ValueRef::IntArray(_), ValueRef::IntArray(_))
+        | (ValueRef::LongArray(_), ValueRef::LongArray(_)) => {
+            let old_count = old.element_count().unwrap_or_default();
+            let new_count = new.element_count().unwrap_or_default();
+            for index in 0..old_count.max(new_count) {
+                let at = TagPathPart::AtIndex(index as i64);
+                path.push(NbtPathNode::Index(index as i64));
+                match (old.get_child(&at), new.get_child(&at)) {
+                    (Some(old), Some(new)) => diff_values(path, old, new, differences),
+                    (Some(old), None) => differences.push(NbtDiff::Removed { path: current(path), value: Tag::from(old) }),
+                    (None, Some(new)) => differences.push(NbtDiff::Added { path: current(path), value: Tag::from(new) }),
+                    (None, None) => (),
+                }
+                path.pop();
+            }
+        }
+        _ if !primitives_equal(old, new) => differences.push(NbtDiff::Changed {
+            path: current(path),
+            old: Tag::from(old),
+            new: Tag::from(new),
+        }),
+        _ => (),
+    }
+}
+
+/// Compares values that aren't compounds, lists, or arrays. Floating point values are compared by their bits so that
+/// `NaN` equals itself and `-0.0` doesn't equal `0.0`.
+fn primitives_equal(old: ValueRef<'_>, new: ValueRef<'_>) -> bool {
+    match (old, new) {
+        (ValueRef::Byte(a), ValueRef::Byte(b)) => a == b,
+        (ValueRef::Short(a), ValueRef::Short(b)) => a == b,
+        (ValueRef::Int(a), ValueRef::Int(b)) => a == b,
+        (ValueRef::Long(a), ValueRef::Long(b)) => a == b,
+        (ValueRef::Float(a), ValueRef::Float(b)) => a.to_bits() == b.to_bits(),
+        (ValueRef::Double(a), ValueRef::Double(b)) => a.to_bits() == b.to_bits(),
+        (ValueRef::String(a), ValueRef::String(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_test() {
+        let old = Tag::parse(r#"{ a: 1, b: { c: "x", d: [1b, 2b, 3b] }, e: [L; 1L], removed: 0.5f, same: [{ x: 1 }] }"#).unwrap();
+        let new = Tag::parse(r#"{ added: 2s, a: 1, b: { c: "y", d: [1b, 5b] }, e: [L; 1L, 2L], same: [{ x: 1 }] }"#).unwrap();
+        let lines: Vec<String> = diff(&old, &new).iter().map(ToString::to_string).collect();
+        assert_eq!(lines, [
+            "+ added: 2S",
+            "~ b.c: \"x\" -> \"y\"",
+            "~ b.d[1]: 2B -> 5B",
+            "- b.d[2]: 3B",
+            "+ e[1]: 2L",
+            "- removed: 0.5F",
+        ]);
+        assert!(diff(&old, &old).is_empty());
+        assert!(matches!(&diff(&Tag::Int(1), &Tag::Long(1))[..], [NbtDiff::Changed { path, .. }] if path.nodes().is_empty()));
+    }
+}
@@ -0,0 +1,165 @@
+//! Content hashing for NBT.
+//!
+//! The hash of a [Tag] only depends on its content, so two tags that are
+//! equal will hash equally regardless of compound key order, compression,
+//! or the platform that the hash was computed on. This makes the hash
+//! suitable for storing alongside backups and comparing later.
+
+use std::hash::Hasher;
+
+use super::Map;
+use super::tag::*;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// A 64-bit FNV-1a [Hasher].
+/// Unlike [std::collections::hash_map::DefaultHasher], the output of this
+/// hasher is stable across Rust versions and platforms.
+#[derive(Debug, Clone, Copy)]
+pub struct ContentHasher(u64);
+
+impl ContentHasher {
+    pub const fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for ContentHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+impl Tag {
+    /// Computes a canonical hash of the content of this tag.
+    /// Compound keys are hashed in sorted order and floating point values
+    /// are hashed by their bits, so the result is stable across
+    /// re-encoding, recompression, and reordering of compound entries.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = ContentHasher::new();
+        hash_tag(self, &mut hasher);
+        hasher.finish()
+    }
+}
+
+impl NamedTag {
+    /// Computes a canonical hash of the name and content of this tag.
+    /// See [Tag::content_hash].
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = ContentHasher::new();
+        hash_str(&self.name, &mut hasher);
+        hash_tag(&self.tag, &mut hasher);
+        hasher.finish()
+    }
+}
+
+fn hash_str<H: Hasher>(value: &str, hasher: &mut H) {
+    hasher.write_u64(value.len() as u64);
+    hasher.write(value.as_bytes());
+}
+
+fn hash_compound<H: Hasher>(map: &Map, hasher: &mut H) {
+    let mut keys = map.keys().collect::<Vec<&String>>();
+    keys.sort();
+    hasher.write_u64(keys.len() as u64);
+    for key in keys {
+        hash_str(key, hasher);
+        hash_tag(&map[key], hasher);
+    }
+}
+
+fn hash_bytes<H: Hasher>(values: &[i8], hasher: &mut H) {
+    hasher.write_u64(values.len() as u64);
+    values.iter().for_each(|value| hasher.write_i8(*value));
+}
+
+fn hash_ints<H: Hasher>(values: &[i32], hasher: &mut H) {
+    hasher.write_u64(values.len() as u64);
+    values.iter().for_each(|value| hasher.write(&value.to_be_bytes()));
+}
+
+fn hash_longs<H: Hasher>(values: &[i64], hasher: &mut H) {
+    hasher.write_u64(values.len() as u64);
+    values.iter().for_each(|value| hasher.write(&value.to_be_bytes()));
+}
+
+fn hash_tag<H: Hasher>(tag: &Tag, hasher: &mut H) {
+    hasher.write_u8(tag.id() as u8);
+    match tag {
+        Tag::Byte(value) => hasher.write_i8(*value),
+        Tag::Short(value) => hasher.write(&value.to_be_bytes()),
+        Tag::Int(value) => hasher.write(&value.to_be_bytes()),
+        Tag::Long(value) => hasher.write(&value.to_be_bytes()),
+        Tag::Float(value) => hasher.write(&value.to_bits().to_be_bytes()),
+        Tag::Double(value) => hasher.write(&value.to_bits().to_be_bytes()),
+        Tag::ByteArray(values) => hash_bytes(values, hasher),
+        Tag::String(value) => hash_str(value, hasher),
+        Tag::List(list) => hash_list(list, hasher),
+        Tag::Compound(map) => hash_compound(map, hasher),
+        Tag::IntArray(values) => hash_ints(values, hasher),
+        Tag::LongArray(values) => hash_longs(values, hasher),
+    }
+}
+
+fn hash_list<H: Hasher>(list: &ListTag, hasher: &mut H) {
+    // The element type of an empty list is meaningless, so all empty lists hash the same.
+    if list.len() == 0 {
+        hasher.write_u64(0);
+        return;
+    }
+    hasher.write_u64(list.len() as u64);
+    hasher.write_u8(list.id() as u8);
+    match list {
+        ListTag::Empty => (),
+        ListTag::Byte(items) => items.iter().for_each(|item| hasher.write_i8(*item)),
+        ListTag::Short(items) => items.iter().for_each(|item| hasher.write(&item.to_be_bytes())),
+        ListTag::Int(items) => items.iter().for_each(|item| hasher.write(&item.to_be_bytes())),
+        ListTag::Long(items) => items.iter().for_each(|item| hasher.write(&item.to_be_bytes())),
+        ListTag::Float(items) => items.iter().for_each(|item| hasher.write(&item.to_bits().to_be_bytes())),
+        ListTag::Double(items) => items.iter().for_each(|item| hasher.write(&item.to_bits().to_be_bytes())),
+        ListTag::ByteArray(items) => items.iter().for_each(|item| hash_bytes(item, hasher)),
+        ListTag::String(items) => items.iter().for_each(|item| hash_str(item, hasher)),
+        ListTag::List(items) => items.iter().for_each(|item| hash_list(item, hasher)),
+        ListTag::Compound(items) => items.iter().for_each(|item| hash_compound(item, hasher)),
+        ListTag::IntArray(items) => items.iter().for_each(|item| hash_ints(item, hasher)),
+        ListTag::LongArray(items) => items.iter().for_each(|item| hash_longs(item, hasher)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nbt::*;
+    use crate::nbt::io::*;
+    use crate::nbt::tag::*;
+
+    #[test]
+    fn content_hash_test() {
+        let tag = crate::compound!{
+            ("xPos", 3i32),
+            ("zPos", -7i32),
+            ("Status", "minecraft:full"),
+            ("Empty", Tag::List(ListTag::Empty)),
+        };
+        let mut buffer = Vec::new();
+        write_named_tag(&mut buffer, &tag, "").expect("Failed to write.");
+        let (_, read) = read_named_tag(&mut buffer.as_slice()).expect("Failed to read.");
+        assert_eq!(tag.content_hash(), read.content_hash());
+        let Tag::Compound(mut map) = read else { panic!() };
+        map.insert("xPos".to_owned(), Tag::Int(4));
+        assert_ne!(tag.content_hash(), Tag::Compound(map).content_hash());
+    }
+}
@@ -17,7 +17,7 @@ use crate::{
     ioext::*,
     McError,
 };
-use std::io::{ Read, Write };
+use std::io::{ Read, Seek, SeekFrom, Write };
 
 /// Trait that gives the serialization size in bytes of various values.
 /// This size may include a 2 or 4 byte length, or a single byte end marker in addition to the payload.
@@ -30,6 +30,9 @@ pub trait NbtSize {
 pub trait ReadNbt: Read {
     /// Read NBT (anything that implements NbtRead).
     fn read_nbt<T: NbtRead>(&mut self) -> Result<T, McError>;
+    /// Read a root tag framed as described by `options`.
+    /// The network protocol writes a lone End tag when there is no NBT, which returns [McError::EndTagMarker].
+    fn read_root_tag(&mut self, options: NbtOptions) -> Result<NamedTag, McError>;
 }
 
 // std::io::Read extension method read_nbt implementation.
@@ -38,12 +41,23 @@ impl<Reader: Read> ReadNbt for Reader {
     fn read_nbt<T: NbtRead>(&mut self) -> Result<T, McError> {
         T::nbt_read(self)
     }
+
+    fn read_root_tag(&mut self, options: NbtOptions) -> Result<NamedTag, McError> {
+        use flate2::read::{GzDecoder, ZlibDecoder};
+        match options.compression {
+            NbtCompression::None => read_root(self, options),
+            NbtCompression::GZip => read_root(&mut GzDecoder::new(self), options),
+            NbtCompression::ZLib => read_root(&mut ZlibDecoder::new(self), options),
+        }
+    }
 }
 
 /// Trait applied to all writers for NBT extensions.
 pub trait WriteNbt: Write {
     /// Write NBT (anything that implements NbtWrite).
     fn write_nbt<T: NbtWrite>(&mut self, value: &T) -> Result<usize, McError>;
+    /// Write a root tag framed as described by `options`, returning the size of the NBT before compression.
+    fn write_root_tag(&mut self, tag: &NamedTag, options: NbtOptions) -> Result<usize, McError>;
 }
 
 // std::io::Write extension method write_nbt implementation.
@@ -52,6 +66,25 @@ impl<Writer: Write> WriteNbt for Writer {
     fn write_nbt<T: NbtWrite>(&mut self, value: &T) -> Result<usize, McError> {
         value.nbt_write(self)
     }
+
+    fn write_root_tag(&mut self, tag: &NamedTag, options: NbtOptions) -> Result<usize, McError> {
+        use flate2::{Compression, write::{GzEncoder, ZlibEncoder}};
+        match options.compression {
+            NbtCompression::None => write_root(self, tag, options),
+            NbtCompression::GZip => {
+                let mut encoder = GzEncoder::new(self, Compression::default());
+                let size = write_root(&mut encoder, tag, options)?;
+                encoder.finish()?;
+                Ok(size)
+            }
+            NbtCompression::ZLib => {
+                let mut encoder = ZlibEncoder::new(self, Compression::default());
+                let size = write_root(&mut encoder, tag, options)?;
+                encoder.finish()?;
+                Ok(size)
+            }
+        }
+    }
 }
 
 /// A trait for reading values from readers.
@@ -81,6 +114,11 @@ impl<T: NbtRead> Readable for T {
 pub trait NbtWrite {
     /// Write a value to a writer.
     fn nbt_write<W: Write>(&self, writer: &mut W) -> Result<usize, McError>;
+
+    /// The number of bytes that [NbtWrite::nbt_write] will write, for values that implement [NbtSize].
+    fn nbt_write_size(&self) -> Option<usize> {
+        None
+    }
 }
 
 impl<T: NbtWrite> Writable for T {
@@ -88,6 +126,10 @@ impl<T: NbtWrite> Writable for T {
         use crate::nbt::io::*;
         Ok(writer.write_nbt(self)?)
     }
+
+    fn write_size(&self) -> Option<usize> {
+        self.nbt_write_size()
+    }
 }
 
 macro_rules! tag_io {
@@ -240,6 +282,10 @@ macro_rules! tag_io {
                     )+
                 }
             }
+
+            fn nbt_write_size(&self) -> Option<usize> {
+                Some(self.nbt_size())
+            }
         }
     };
 }
@@ -449,6 +495,476 @@ impl NbtRead for NamedTag {
     }
 }
 
+/// Reads a [NamedTag] from a reader that may or may not be compressed.
+/// The first two bytes are peeked to determine the compression:
+/// the GZip magic number (`0x1F 0x8B`) is read with a [GzDecoder](flate2::read::GzDecoder),
+/// a valid ZLib header is read with a [ZlibDecoder](flate2::read::ZlibDecoder),
+/// and anything else is read as raw NBT.
+/// The reader is returned to its original position before reading.
+pub fn read_nbt_auto<R: Read + Seek>(reader: &mut R) -> Result<NamedTag, McError> {
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    let start = reader.stream_position()?;
+    let mut magic = [0u8; 2];
+    let peeked = read_up_to(reader, &mut magic)?;
+    reader.seek(SeekFrom::Start(start))?;
+    let magic = &magic[..peeked];
+    match magic {
+        [0x1F, 0x8B] => NamedTag::nbt_read(&mut GzDecoder::new(reader)),
+        // ZLib header: CM must be 8 (deflate) and CMF*256 + FLG must be a multiple of 31.
+        [cmf, flg] if cmf & 0x0F == 8 && ((*cmf as u16) << 8 | *flg as u16).is_multiple_of(31) => {
+            NamedTag::nbt_read(&mut ZlibDecoder::new(reader))
+        }
+        _ => NamedTag::nbt_read(reader),
+    }
+}
+
+/// The compression that wraps an NBT document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NbtCompression {
+    /// Raw NBT, as used by the network protocol and within region files (after the chunk is decompressed).
+    #[default]
+    None,
+    /// GZip, as used by `level.dat`, player data, and structure files.
+    GZip,
+    ZLib,
+}
+
+/// How numbers and lengths are encoded in NBT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NbtFlavor {
+    /// Java Edition's NBT, where everything is big-endian.
+    #[default]
+    BigEndian,
+    /// Bedrock Edition's NBT, where everything is little-endian. This is used by `.mcstructure` files,
+    /// `level.dat` (after its 8 byte header), and the NBT in Bedrock's LevelDB world storage.
+    LittleEndian,
+    /// Bedrock Edition's little-endian NBT, except that `Int`s, `Long`s, and lengths are VarInts.
+    /// `Int`s, `Long`s, and the lengths of lists and arrays are ZigZag encoded so that negative values stay small,
+    /// while string lengths are unsigned. This is used by Bedrock's network protocol.
+    VarInt,
+}
+
+/// Options for how an NBT document is framed and encoded, for use with [ReadNbt::read_root_tag] and
+/// [WriteNbt::write_root_tag].
+///
+/// NBT files give the root tag a name (usually empty) after its type. Since 1.20.2, the network protocol leaves out
+/// the name, so the type is followed directly by the payload, and nothing is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NbtOptions {
+    unnamed_root: bool,
+    compression: NbtCompression,
+    flavor: NbtFlavor,
+}
+
+impl NbtOptions {
+    /// A named root without compression, which is how [NamedTag] is read and written by default.
+    pub const fn new() -> Self {
+        Self {
+            unnamed_root: false,
+            compression: NbtCompression::None,
+            flavor: NbtFlavor::BigEndian,
+        }
+    }
+
+    /// An unnamed root without compression, as used by the network protocol since 1.20.2.
+    pub const fn network() -> Self {
+        Self {
+            unnamed_root: true,
+            compression: NbtCompression::None,
+            flavor: NbtFlavor::BigEndian,
+        }
+    }
+
+    /// A named root with GZip compression, as used by `level.dat` and other NBT files.
+    pub const fn file() -> Self {
+        Self {
+            unnamed_root: false,
+            compression: NbtCompression::GZip,
+            flavor: NbtFlavor::BigEndian,
+        }
+    }
+
+    /// A named root with little-endian numbers and without compression, as used by Bedrock Edition's `.mcstructure` files.
+    pub const fn bedrock() -> Self {
+        Self {
+            unnamed_root: false,
+            compression: NbtCompression::None,
+            flavor: NbtFlavor::LittleEndian,
+        }
+    }
+
+    /// Whether the root tag is written without a name. Unnamed roots are read with an empty name.
+    pub const fn with_unnamed_root(mut self, unnamed_root: bool) -> Self {
+        self.unnamed_root = unnamed_root;
+        self
+    }
+
+    pub const fn with_compression(mut self, compression: NbtCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub const fn with_flavor(mut self, flavor: NbtFlavor) -> Self {
+        self.flavor = flavor;
+        self
+    }
+
+    pub const fn unnamed_root(&self) -> bool {
+        self.unnamed_root
+    }
+
+    pub const fn compression(&self) -> NbtCompression {
+        self.compression
+    }
+
+    pub const fn flavor(&self) -> NbtFlavor {
+        self.flavor
+    }
+}
+
+/// Reads a root tag without compression. An unnamed root is read with an empty name.
+fn read_root<R: Read>(reader: &mut R, options: NbtOptions) -> Result<NamedTag, McError> {
+    if options.flavor == NbtFlavor::BigEndian && !options.unnamed_root {
+        return NamedTag::nbt_read(reader);
+    }
+    let mut reader = FlavoredReader { reader, flavor: options.flavor };
+    let id = TagID::try_from(reader.u8()?)?;
+    let name = if options.unnamed_root { String::new() } else { reader.string()? };
+    let tag = reader.tag(id)?;
+    Ok(NamedTag::with_name(name, tag))
+}
+
+/// Writes a root tag without compression. The name of an unnamed root isn't written.
+fn write_root<W: Write>(writer: &mut W, tag: &NamedTag, options: NbtOptions) -> Result<usize, McError> {
+    if options.flavor == NbtFlavor::BigEndian && !options.unnamed_root {
+        return tag.nbt_write(writer);
+    }
+    let mut writer = FlavoredWriter { writer, flavor: options.flavor };
+    let mut size = writer.bytes(&[tag.tag().id() as u8])?;
+    if !options.unnamed_root {
+        size += writer.string(tag.name())?;
+    }
+    Ok(size + writer.tag(tag.tag())?)
+}
+
+/// Reads NBT of any [NbtFlavor].
+struct FlavoredReader<'a, R> {
+    reader: &'a mut R,
+    flavor: NbtFlavor,
+}
+
+impl<R: Read> FlavoredReader<'_, R> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], McError> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, McError> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    /// Reads an unsigned VarInt of up to `max_bytes` bytes.
+    fn varint(&mut self, max_bytes: u32) -> Result<u64, McError> {
+        let mut value = 0u64;
+        for index in 0..max_bytes {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7F) as u64) << (7 * index);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(McError::NbtDecodeError)
+    }
+
+    fn i16(&mut self) -> Result<i16, McError> {
+        let bytes = self.bytes()?;
+        Ok(match self.flavor {
+            NbtFlavor::BigEndian => i16::from_be_bytes(bytes),
+            NbtFlavor::LittleEndian | NbtFlavor::VarInt => i16::from_le_bytes(bytes),
+        })
+    }
+
+    fn i32(&mut self) -> Result<i32, McError> {
+        Ok(match self.flavor {
+            NbtFlavor::BigEndian => i32::from_be_bytes(self.bytes()?),
+            NbtFlavor::LittleEndian => i32::from_le_bytes(self.bytes()?),
+            NbtFlavor::VarInt => {
+                let value = self.varint(5)? as u32;
+                (value >> 1) as i32 ^ -((value & 1) as i32)
+            }
+        })
+    }
+
+    fn i64(&mut self) -> Result<i64, McError> {
+        Ok(match self.flavor {
+            NbtFlavor::BigEndian => i64::from_be_bytes(self.bytes()?),
+            NbtFlavor::LittleEndian => i64::from_le_bytes(self.bytes()?),
+            NbtFlavor::VarInt => {
+                let value = self.varint(10)?;
+                (value >> 1) as i64 ^ -((value & 1) as i64)
+            }
+        })
+    }
+
+    fn f32(&mut self) -> Result<f32, McError> {
+        let bytes = self.bytes()?;
+        Ok(match self.flavor {
+            NbtFlavor::BigEndian => f32::from_be_bytes(bytes),
+            NbtFlavor::LittleEndian | NbtFlavor::VarInt => f32::from_le_bytes(bytes),
+        })
+    }
+
+    fn f64(&mut self) -> Result<f64, McError> {
+        let bytes = self.bytes()?;
+        Ok(match self.flavor {
+            NbtFlavor::BigEndian => f64::from_be_bytes(bytes),
+            NbtFlavor::LittleEndian | NbtFlavor::VarInt => f64::from_le_bytes(bytes),
+        })
+    }
+
+    /// Reads the length of a list or array.
+    fn length(&mut self) -> Result<usize, McError> {
+        usize::try_from(self.i32()?).map_err(|_| McError::NbtDecodeError)
+    }
+
+    /// Reads `length` bytes without trusting `length` enough to allocate it all up front.
+    fn byte_vec(&mut self, length: usize) -> Result<Vec<u8>, McError> {
+        let mut bytes = Vec::new();
+        (&mut *self.reader).take(length as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != length {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String, McError> {
+        let length = match self.flavor {
+            NbtFlavor::BigEndian => u16::from_be_bytes(self.bytes()?) as usize,
+            NbtFlavor::LittleEndian => u16::from_le_bytes(self.bytes()?) as usize,
+            NbtFlavor::VarInt => self.varint(5)? as usize,
+        };
+        Ok(String::from_utf8(self.byte_vec(length)?)?)
+    }
+
+    fn repeat<T>(&mut self, length: usize, mut read: impl FnMut(&mut Self) -> Result<T, McError>) -> Result<Vec<T>, McError> {
+        (0..length).map(|_| read(self)).collect()
+    }
+
+    fn tag(&mut self, id: TagID) -> Result<Tag, McError> {
+        Ok(match id {
+            TagID::Byte => Tag::Byte(self.u8()? as i8),
+            TagID::Short => Tag::Short(self.i16()?),
+            TagID::Int => Tag::Int(self.i32()?),
+            TagID::Long => Tag::Long(self.i64()?),
+            TagID::Float => Tag::Float(self.f32()?),
+            TagID::Double => Tag::Double(self.f64()?),
+            TagID::ByteArray => Tag::ByteArray(self.byte_array()?),
+            TagID::String => Tag::String(self.string()?),
+            TagID::List => Tag::List(self.list()?),
+            TagID::Compound => Tag::Compound(self.compound()?),
+            TagID::IntArray => Tag::IntArray(self.int_array()?),
+            TagID::LongArray => Tag::LongArray(self.long_array()?),
+        })
+    }
+
+    fn byte_array(&mut self) -> Result<Vec<i8>, McError> {
+        let length = self.length()?;
+        Ok(self.byte_vec(length)?.into_iter().map(|byte| byte as i8).collect())
+    }
+
+    fn int_array(&mut self) -> Result<Vec<i32>, McError> {
+        let length = self.length()?;
+        self.repeat(length, Self::i32)
+    }
+
+    fn long_array(&mut self) -> Result<Vec<i64>, McError> {
+        let length = self.length()?;
+        self.repeat(length, Self::i64)
+    }
+
+    fn list(&mut self) -> Result<ListTag, McError> {
+        let id = TagID::try_from(self.u8()?);
+        let length = self.length()?;
+        Ok(match id {
+            Err(McError::EndTagMarker) => ListTag::Empty,
+            Err(err) => return Err(err),
+            Ok(TagID::Byte) => ListTag::Byte(self.repeat(length, |reader| reader.u8().map(|byte| byte as i8))?),
+            Ok(TagID::Short) => ListTag::Short(self.repeat(length, Self::i16)?),
+            Ok(TagID::Int) => ListTag::Int(self.repeat(length, Self::i32)?),
+            Ok(TagID::Long) => ListTag::Long(self.repeat(length, Self::i64)?),
+            Ok(TagID::Float) => ListTag::Float(self.repeat(length, Self::f32)?),
+            Ok(TagID::Double) => ListTag::Double(self.repeat(length, Self::f64)?),
+            Ok(TagID::ByteArray) => ListTag::ByteArray(self.repeat(length, Self::byte_array)?),
+            Ok(TagID::String) => ListTag::String(self.repeat(length, Self::string)?),
+            Ok(TagID::List) => ListTag::List(self.repeat(length, Self::list)?),
+            Ok(TagID::Compound) => ListTag::Compound(self.repeat(length, Self::compound)?),
+            Ok(TagID::IntArray) => ListTag::IntArray(self.repeat(length, Self::int_array)?),
+            Ok(TagID::LongArray) => ListTag::LongArray(self.repeat(length, Self::long_array)?),
+        })
+    }
+
+    fn compound(&mut self) -> Result<Map, McError> {
+        let mut map = Map::new();
+        loop {
+            match TagID::try_from(self.u8()?) {
+                Ok(id) => {
+                    let name = self.string()?;
+                    let tag = self.tag(id)?;
+                    map.insert(name, tag);
+                }
+                Err(McError::EndTagMarker) => return Ok(map),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Writes NBT of any [NbtFlavor]. Each method returns the number of bytes that were written.
+struct FlavoredWriter<'a, W> {
+    writer: &'a mut W,
+    flavor: NbtFlavor,
+}
+
+impl<W: Write> FlavoredWriter<'_, W> {
+    fn bytes(&mut self, bytes: &[u8]) -> Result<usize, McError> {
+        self.writer.write_all(bytes)?;
+        Ok(bytes.len())
+    }
+
+    fn varint(&mut self, mut value: u64) -> Result<usize, McError> {
+        let mut size = 0;
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                return Ok(size + self.bytes(&[byte])?);
+            }
+            size += self.bytes(&[byte | 0x80])?;
+        }
+    }
+
+    fn i16(&mut self, value: i16) -> Result<usize, McError> {
+        match self.flavor {
+            NbtFlavor::BigEndian => self.bytes(&value.to_be_bytes()),
+            NbtFlavor::LittleEndian | NbtFlavor::VarInt => self.bytes(&value.to_le_bytes()),
+        }
+    }
+
+    fn i32(&mut self, value: i32) -> Result<usize, McError> {
+        match self.flavor {
+            NbtFlavor::BigEndian => self.bytes(&value.to_be_bytes()),
+            NbtFlavor::LittleEndian => self.bytes(&value.to_le_bytes()),
+            NbtFlavor::VarInt => self.varint(((value << 1) ^ (value >> 31)) as u32 as u64),
+        }
+    }
+
+    fn i64(&mut self, value: i64) -> Result<usize, McError> {
+        match self.flavor {
+            NbtFlavor::BigEndian => self.bytes(&value.to_be_bytes()),
+            NbtFlavor::LittleEndian => self.bytes(&value.to_le_bytes()),
+            NbtFlavor::VarInt => self.varint(((value << 1) ^ (value >> 63)) as u64),
+        }
+    }
+
+    fn f32(&mut self, value: f32) -> Result<usize, McError> {
+        match self.flavor {
+            NbtFlavor::BigEndian => self.bytes(&value.to_be_bytes()),
+            NbtFlavor::LittleEndian | NbtFlavor::VarInt => self.bytes(&value.to_le_bytes()),
+        }
+    }
+
+    fn f64(&mut self, value: f64) -> Result<usize, McError> {
+        match self.flavor {
+            NbtFlavor::BigEndian => self.bytes(&value.to_be_bytes()),
+            NbtFlavor::LittleEndian | NbtFlavor::VarInt => self.bytes(&value.to_le_bytes()),
+        }
+    }
+
+    fn length(&mut self, length: usize) -> Result<usize, McError> {
+        self.i32(i32::try_from(length).map_err(|_| McError::OutOfRange)?)
+    }
+
+    fn string(&mut self, value: &str) -> Result<usize, McError> {
+        let size = match self.flavor {
+            NbtFlavor::BigEndian => self.bytes(&u16::try_from(value.len()).map_err(|_| McError::OutOfRange)?.to_be_bytes())?,
+            NbtFlavor::LittleEndian => self.bytes(&u16::try_from(value.len()).map_err(|_| McError::OutOfRange)?.to_le_bytes())?,
+            NbtFlavor::VarInt => self.varint(value.len() as u64)?,
+        };
+        Ok(size + self.bytes(value.as_bytes())?)
+    }
+
+    fn each<T>(&mut self, items: &[T], mut write: impl FnMut(&mut Self, &T) -> Result<usize, McError>) -> Result<usize, McError> {
+        let size = self.length(items.len())?;
+        items.iter().try_fold(size, |size, item| Ok(size + write(self, item)?))
+    }
+
+    fn tag(&mut self, tag: &Tag) -> Result<usize, McError> {
+        match tag {
+            Tag::Byte(value) => self.bytes(&[*value as u8]),
+            Tag::Short(value) => self.i16(*value),
+            Tag::Int(value) => self.i32(*value),
+            Tag::Long(value) => self.i64(*value),
+            Tag::Float(value) => self.f32(*value),
+            Tag::Double(value) => self.f64(*value),
+            Tag::ByteArray(array) => self.byte_array(array),
+            Tag::String(value) => self.string(value),
+            Tag::List(list) => self.list(list),
+            Tag::Compound(map) => self.compound(map),
+            Tag::IntArray(array) => self.each(array, |writer, value| writer.i32(*value)),
+            Tag::LongArray(array) => self.each(array, |writer, value| writer.i64(*value)),
+        }
+    }
+
+    fn byte_array(&mut self, array: &[i8]) -> Result<usize, McError> {
+        Ok(self.length(array.len())? + self.bytes(bytemuck::cast_slice(array))?)
+    }
+
+    fn list(&mut self, list: &ListTag) -> Result<usize, McError> {
+        let id = match list {
+            ListTag::Empty => 0,
+            list => list.id() as u8,
+        };
+        let size = self.bytes(&[id])?;
+        Ok(size + match list {
+            ListTag::Empty => self.length(0)?,
+            ListTag::Byte(items) => self.each(items, |writer, value| writer.bytes(&[*value as u8]))?,
+            ListTag::Short(items) => self.each(items, |writer, value| writer.i16(*value))?,
+            ListTag::Int(items) => self.each(items, |writer, value| writer.i32(*value))?,
+            ListTag::Long(items) => self.each(items, |writer, value| writer.i64(*value))?,
+            ListTag::Float(items) => self.each(items, |writer, value| writer.f32(*value))?,
+            ListTag::Double(items) => self.each(items, |writer, value| writer.f64(*value))?,
+            ListTag::ByteArray(items) => self.each(items, |writer, value| writer.byte_array(value))?,
+            ListTag::String(items) => self.each(items, |writer, value| writer.string(value))?,
+            ListTag::List(items) => self.each(items, Self::list)?,
+            ListTag::Compound(items) => self.each(items, Self::compound)?,
+            ListTag::IntArray(items) => self.each(items, |writer, array| writer.each(array, |writer, value| writer.i32(*value)))?,
+            ListTag::LongArray(items) => self.each(items, |writer, array| writer.each(array, |writer, value| writer.i64(*value)))?,
+        })
+    }
+
+    fn compound(&mut self, map: &Map) -> Result<usize, McError> {
+        let size = map.iter().try_fold(0, |size, (key, tag)| {
+            Ok::<_, McError>(size + self.bytes(&[tag.id() as u8])? + self.string(key)? + self.tag(tag)?)
+        })?;
+        Ok(size + self.bytes(&[0])?)
+    }
+}
+
+/// Reads as many bytes as possible into `buf`, stopping early at EOF.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        match reader.read(&mut buf[total..]) {
+            Ok(0) => break,
+            Ok(count) => total += count,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(total)
+}
+
 impl NbtWrite for &str {
     /// Write a string to a writer.
     fn nbt_write<W: Write>(&self, writer: &mut W) -> Result<usize, McError> {
@@ -502,6 +1018,10 @@ impl NbtWrite for Map {
         })?;
         0u8.nbt_write(writer).map(|size| write_size + size)
     }
+
+    fn nbt_write_size(&self) -> Option<usize> {
+        Some(self.nbt_size())
+    }
 }
 
 impl NbtWrite for NamedTag {
@@ -509,6 +1029,10 @@ impl NbtWrite for NamedTag {
     fn nbt_write<W: Write>(&self, writer: &mut W) -> Result<usize, McError> {
         write_named_tag(writer, &self.tag, &self.name)
     }
+
+    fn nbt_write_size(&self) -> Option<usize> {
+        Some(self.nbt_size())
+    }
 }
 
 
@@ -524,6 +1048,10 @@ where T: NbtWrite {
     fn nbt_write<W: Write>(&self, writer: &mut W) -> Result<usize, McError> {
         (*self).write_to(writer)
     }
+
+    fn nbt_write_size(&self) -> Option<usize> {
+        (*self).nbt_write_size()
+    }
 }
 
 impl<T> NbtRead for &T
@@ -569,4 +1097,97 @@ mod tests {
         compound.insert("Compound".to_owned(), Tag::Compound(mapclone));
         Tag::Compound(compound)
     }
+
+    #[test]
+    fn root_framing_test() {
+        use std::io::Cursor;
+        let root = NamedTag::with_name("root", test_tag());
+        let mut network = Vec::new();
+        let size = network.write_root_tag(&root, NbtOptions::network()).unwrap();
+        assert_eq!(size, network.len());
+        // The type of the root is followed directly by the type of its first entry.
+        assert_eq!(network[0], TagID::Compound as u8);
+        assert!(TagID::try_from(network[1]).is_ok());
+        let mut named = Vec::new();
+        write_named_tag(&mut named, root.tag(), "root").unwrap();
+        assert_eq!(network.len() + "root".len() + 2, named.len());
+        let read = Cursor::new(&network).read_root_tag(NbtOptions::network()).unwrap();
+        assert_eq!(read.name(), "");
+        assert!(matches!(read.tag(), Tag::Compound(map) if map.len() == 13));
+
+        for options in [NbtOptions::file(), NbtOptions::network().with_compression(NbtCompression::ZLib)] {
+            let mut data = Vec::new();
+            data.write_root_tag(&root, options).unwrap();
+            let read = Cursor::new(data).read_root_tag(options).unwrap();
+            assert_eq!(read.name(), if options.unnamed_root() { "" } else { "root" });
+            assert!(matches!(read.tag(), Tag::Compound(map) if map.len() == 13));
+        }
+        let mut data = Vec::new();
+        data.write_root_tag(&NamedTag::new(Tag::String("text".to_owned())), NbtOptions::network()).unwrap();
+        assert_eq!(data, [8, 0, 4, b't', b'e', b'x', b't']);
+        assert!(matches!(Cursor::new([0u8]).read_root_tag(NbtOptions::network()), Err(crate::McError::EndTagMarker)));
+    }
+
+    #[test]
+    fn nbt_flavor_test() {
+        use std::io::Cursor;
+        let small = NamedTag::new(Tag::Compound(Map::from([("a".to_owned(), Tag::Int(300))])));
+        let expected: [(NbtFlavor, &[u8]); 3] = [
+            (NbtFlavor::BigEndian, &[10, 0, 0, 3, 0, 1, b'a', 0, 0, 1, 44, 0]),
+            (NbtFlavor::LittleEndian, &[10, 0, 0, 3, 1, 0, b'a', 44, 1, 0, 0, 0]),
+            (NbtFlavor::VarInt, &[10, 0, 3, 1, b'a', 0xD8, 0x04, 0]),
+        ];
+        for (flavor, bytes) in expected {
+            let options = NbtOptions::new().with_flavor(flavor);
+            let mut data = Vec::new();
+            assert_eq!(data.write_root_tag(&small, options).unwrap(), bytes.len());
+            assert_eq!(data, bytes, "{flavor:?}");
+
+            let root = NamedTag::with_name("root", test_tag());
+            let mut data = Vec::new();
+            data.write_root_tag(&root, options).unwrap();
+            let read = Cursor::new(data).read_root_tag(options).unwrap();
+            assert_eq!(read.name(), "root");
+            assert_eq!(read.tag().content_hash(), root.tag().content_hash());
+        }
+        let mut data = Vec::new();
+        data.write_root_tag(&NamedTag::new(Tag::LongArray(vec![-1, i64::MIN])), NbtOptions::network().with_flavor(NbtFlavor::VarInt)).unwrap();
+        assert_eq!(&data[..3], [12, 4, 1]);
+        assert_eq!(data.len(), 13);
+    }
+
+    #[test]
+    fn byte_size_test() {
+        let Tag::Compound(mut compound) = test_tag() else {
+            unreachable!();
+        };
+        compound.insert("Compounds".to_owned(), Tag::List(ListTag::Compound(vec![Map::new(), compound.clone()])));
+        compound.insert("Lists".to_owned(), Tag::List(ListTag::List(vec![ListTag::Empty, ListTag::from(vec![1i8, 2])])));
+        let root = NamedTag::with_name("root", Tag::Compound(compound));
+        let mut data = Vec::new();
+        root.write_to(&mut data).unwrap();
+        assert_eq!(root.byte_size(), data.len());
+        assert_eq!(root.write_size(), Some(data.len()));
+        // The type ID and the name aren't part of the payload.
+        assert_eq!(root.tag().byte_size(), data.len() - 1 - 2 - "root".len());
+    }
+
+    #[test]
+    fn read_nbt_auto_test() {
+        use std::io::{Cursor, Write};
+        use flate2::{Compression, write::{GzEncoder, ZlibEncoder}};
+        let mut raw = Vec::new();
+        write_named_tag(&mut raw, &test_tag(), "root").expect("Failed to write.");
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&raw).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(&raw).unwrap();
+        let zlib = zlib.finish().unwrap();
+        for data in [raw, gzip, zlib] {
+            let tag = read_nbt_auto(&mut Cursor::new(data)).expect("Failed to read.");
+            assert_eq!(tag.name(), "root");
+            assert!(matches!(tag.tag(), Tag::Compound(map) if map.len() == 13));
+        }
+    }
 }
\ No newline at end of file
@@ -9,8 +9,16 @@ pub mod tagtype;
 // format is incomplete, and I have no need to finish it, so it will remain incomplete until it is needed.
 pub mod format;
 pub mod tagpath;
+pub mod path;
+pub mod pretty;
+pub mod diff;
+pub mod borrowed;
 pub mod tagref;
 pub mod editable;
+pub mod hash;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod serde;
 
 // /// This is the Error type returned from NbtRead and NbtWrite operations that fail.
 // #[derive(thiserror::Error, Debug)]
@@ -43,4 +51,6 @@ pub type MapType<V> = IndexMap<String, V>;
 #[cfg(not(feature = "preserve_order"))]
 pub type MapType<V> = std::collections::HashMap<String, V>;
 
-pub type Map = MapType<tag::Tag>;
\ No newline at end of file
+pub type Map = MapType<tag::Tag>;
+
+pub use diff::diff;
\ No newline at end of file
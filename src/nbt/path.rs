@@ -0,0 +1,470 @@
+//! NBT paths, using the syntax of Minecraft's `/data` command.
+//!
+//! A path is a sequence of nodes, such as `Level.Sections[0].Palette[{Name:"minecraft:stone"}]`:
+//! - `name` or `"quoted name"` selects the child of a compound.
+//! - `name{...}` selects the child if it's a compound that matches the SNBT compound.
+//! - `{...}` (only at the start of a path) selects the root if it matches.
+//! - `[index]` selects an element of a list or array. Negative indices count from the end.
+//! - `[]` selects every element of a list or array.
+//! - `[{...}]` selects the compounds in a list that match.
+//!
+//! A compound matches if each of its tags is in the other compound and matches.
+//! Lists match if each of their elements matches an element of the other list, and other tags must be equal.
+//!
+//! Since a path may select several values, [Tag::query] and [Tag::query_mut] return every value that was selected.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{McError, McResult};
+
+use super::{
+    format::{is_identifier, write_compound, write_string, Indentation},
+    tag::{ListTag, Tag},
+    tagpath::TagPathPart,
+    tagref::{ValueRef, ValueRefMut},
+    Map,
+};
+
+/// A node of an [NbtPath].
+#[derive(Debug, Clone)]
+pub enum NbtPathNode {
+    /// `{...}`: The root, if it matches the compound.
+    MatchRootObject(Map),
+    /// `name`: The child of a compound.
+    Key(String),
+    /// `name{...}`: The child of a compound, if it's a compound that matches.
+    MatchObject(String, Map),
+    /// `[index]`: An element of a list or array.
+    Index(i64),
+    /// `[]`: Every element of a list or array.
+    AllElements,
+    /// `[{...}]`: The compounds in a list that match.
+    MatchElement(Map),
+}
+
+/// A path to values within a [Tag]. See the [module documentation](self) for the syntax.
+#[derive(Debug, Clone)]
+pub struct NbtPath(Vec<NbtPathNode>);
+
+impl NbtPath {
+    /// Parses a path. Returns [McError::InvalidNbtPath] if the syntax is invalid,
+    /// or [McError::ParseError] if a compound to match isn't valid SNBT.
+    pub fn parse<S: AsRef<str>>(source: S) -> McResult<Self> {
+        let source = source.as_ref();
+        let mut rest = source;
+        let mut nodes = Vec::new();
+        while !rest.is_empty() {
+            let (node, remaining) = parse_node(rest, nodes.is_empty())
+                .map_err(|message| McError::InvalidNbtPath(format!("{message} at \"{rest}\"")))?;
+            nodes.push(node);
+            rest = remaining;
+            if let Some(remaining) = rest.strip_prefix('.') {
+                if remaining.is_empty() {
+                    return Err(McError::InvalidNbtPath(format!("Expected a name after the last '.' in \"{source}\"")));
+                }
+                rest = remaining;
+            } else if !rest.is_empty() && !rest.starts_with('[') {
+                return Err(McError::InvalidNbtPath(format!("Expected '.' or '[' at \"{rest}\"")));
+            }
+        }
+        if nodes.is_empty() {
+            return Err(McError::InvalidNbtPath("The path is empty".to_owned()));
+        }
+        Ok(Self(nodes))
+    }
+
+    /// Creates a path from its nodes. An empty path selects the root.
+    pub fn new(nodes: Vec<NbtPathNode>) -> Self {
+        Self(nodes)
+    }
+
+    pub fn nodes(&self) -> &[NbtPathNode] {
+        &self.0
+    }
+}
+
+impl FromStr for NbtPath {
+    type Err = McError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NbtPath::parse(s)
+    }
+}
+
+impl Display for NbtPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let write_key = |f: &mut std::fmt::Formatter<'_>, index: usize, key: &str| {
+            if index > 0 {
+                write!(f, ".")?;
+            }
+            if is_identifier(key) {
+                write!(f, "{key}")
+            } else {
+                write_string(f, key)
+            }
+        };
+        for (index, node) in self.0.iter().enumerate() {
+            match node {
+                NbtPathNode::MatchRootObject(pattern) => write_compound(f, pattern, true, Indentation::space(), true)?,
+                NbtPathNode::Key(key) => write_key(f, index, key)?,
+                NbtPathNode::MatchObject(key, pattern) => {
+                    write_key(f, index, key)?;
+                    write_compound(f, pattern, true, Indentation::space(), true)?;
+                }
+                NbtPathNode::Index(element) => write!(f, "[{element}]")?,
+                NbtPathNode::AllElements => write!(f, "[]")?,
+                NbtPathNode::MatchElement(pattern) => {
+                    write!(f, "[")?;
+                    write_compound(f, pattern, true, Indentation::space(), true)?;
+                    write!(f, "]")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the node at the start of `source`, returning it and the rest of the source.
+fn parse_node(source: &str, first: bool) -> Result<(NbtPathNode, &str), String> {
+    if source.starts_with('{') {
+        if !first {
+            return Err("A compound to match can only be at the start of the path or after a name".to_owned());
+        }
+        let (pattern, rest) = take_compound(source)?;
+        return Ok((NbtPathNode::MatchRootObject(pattern), rest));
+    }
+    if let Some(inner) = source.strip_prefix('[') {
+        let (node, rest) = if let Some(rest) = inner.strip_prefix(']') {
+            return Ok((NbtPathNode::AllElements, rest));
+        } else if inner.starts_with('{') {
+            let (pattern, rest) = take_compound(inner)?;
+            (NbtPathNode::MatchElement(pattern), rest)
+        } else {
+            let end = inner.find(']').ok_or("Expected ']'")?;
+            let index = inner[..end].parse::<i64>().map_err(|_| format!("Invalid index \"{}\"", &inner[..end]))?;
+            (NbtPathNode::Index(index), &inner[end..])
+        };
+        return rest.strip_prefix(']').map(|rest| (node, rest)).ok_or_else(|| "Expected ']'".to_owned());
+    }
+    let (key, rest) = if source.starts_with(['"', '\'']) {
+        take_quoted(source)?
+    } else {
+        let end = source.find(|c: char| c.is_whitespace() || "\"'[]{}.".contains(c)).unwrap_or(source.len());
+        if end == 0 {
+            return Err("Expected a name".to_owned());
+        }
+        (source[..end].to_owned(), &source[end..])
+    };
+    if rest.starts_with('{') {
+        let (pattern, rest) = take_compound(rest)?;
+        Ok((NbtPathNode::MatchObject(key, pattern), rest))
+    } else {
+        Ok((NbtPathNode::Key(key), rest))
+    }
+}
+
+/// Takes a quoted string from the start of `source`. Backslashes escape the next character.
+fn take_quoted(source: &str) -> Result<(String, &str), String> {
+    let mut chars = source.char_indices();
+    let Some((_, quote)) = chars.next() else {
+        return Err("Expected a quoted name".to_owned());
+    };
+    let mut text = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => text.push(chars.next().ok_or("Unterminated escape sequence")?.1),
+            c if c == quote => return Ok((text, &source[index + 1..])),
+            c => text.push(c),
+        }
+    }
+    Err("Unterminated quoted name".to_owned())
+}
+
+/// Takes an SNBT compound from the start of `source`, finding its end by matching braces outside of strings.
+fn take_compound(source: &str) -> Result<(Map, &str), String> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in source.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return match Tag::parse(&source[..=index]) {
+                        Ok(Tag::Compound(pattern)) => Ok((pattern, &source[index + 1..])),
+                        Ok(_) => Err("Expected a compound".to_owned()),
+                        Err(err) => Err(format!("Invalid SNBT compound: {err}")),
+                    };
+                }
+            }
+            (None, _) => (),
+        }
+    }
+    Err("Unterminated compound".to_owned())
+}
+
+/// Returns true if each tag of `pattern` is in `target` and matches.
+pub fn compound_matches(pattern: &Map, target: &Map) -> bool {
+    pattern.iter().all(|(key, pattern)| target.get(key).is_some_and(|target| tag_matches(pattern, target)))
+}
+
+/// Returns true if `target` matches `pattern`. See the [module documentation](self).
+pub fn tag_matches(pattern: &Tag, target: &Tag) -> bool {
+    match (pattern, target) {
+        (Tag::Byte(a), Tag::Byte(b)) => a == b,
+        (Tag::Short(a), Tag::Short(b)) => a == b,
+        (Tag::Int(a), Tag::Int(b)) => a == b,
+        (Tag::Long(a), Tag::Long(b)) => a == b,
+        (Tag::Float(a), Tag::Float(b)) => a == b,
+        (Tag::Double(a), Tag::Double(b)) => a == b,
+        (Tag::ByteArray(a), Tag::ByteArray(b)) => a == b,
+        (Tag::String(a), Tag::String(b)) => a == b,
+        (Tag::List(a), Tag::List(b)) => list_matches(a, b),
+        (Tag::Compound(a), Tag::Compound(b)) => compound_matches(a, b),
+        (Tag::IntArray(a), Tag::IntArray(b)) => a == b,
+        (Tag::LongArray(a), Tag::LongArray(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// An empty pattern only matches an empty list. Otherwise, each element of `pattern` has to match an element of `target`.
+fn list_matches(pattern: &ListTag, target: &ListTag) -> bool {
+    macro_rules! each_in {
+        ($pattern:ident, $target:ident, $matches:expr) => {
+            $pattern.iter().all(|pattern| $target.iter().any(|target| $matches(pattern, target)))
+        };
+    }
+    if pattern.len() == 0 {
+        return target.len() == 0;
+    }
+    match (pattern, target) {
+        (ListTag::Byte(a), ListTag::Byte(b)) => each_in!(a, b, PartialEq::eq),
+        (ListTag::Short(a), ListTag::Short(b)) => each_in!(a, b, PartialEq::eq),
+        (ListTag::Int(a), ListTag::Int(b)) => each_in!(a, b, PartialEq::eq),
+        (ListTag::Long(a), ListTag::Long(b)) => each_in!(a, b, PartialEq::eq),
+        (ListTag::Float(a), ListTag::Float(b)) => each_in!(a, b, PartialEq::eq),
+        (ListTag::Double(a), ListTag::Double(b)) => each_in!(a, b, PartialEq::eq),
+        (ListTag::ByteArray(a), ListTag::ByteArray(b)) => each_in!(a, b, PartialEq::eq),
+        (ListTag::String(a), ListTag::String(b)) => each_in!(a, b, PartialEq::eq),
+        (ListTag::List(a), ListTag::List(b)) => each_in!(a, b, list_matches),
+        (ListTag::Compound(a), ListTag::Compound(b)) => each_in!(a, b, compound_matches),
+        (ListTag::IntArray(a), ListTag::IntArray(b)) => each_in!(a, b, PartialEq::eq),
+        (ListTag::LongArray(a), ListTag::LongArray(b)) => each_in!(a, b, PartialEq::eq),
+        _ => false,
+    }
+}
+
+/// Applies the `$each` macro to the elements of a list or array, or returns `$other` for other values.
+macro_rules! match_elements {
+    ($value:expr, $reftype:ident, $each:ident, $other:expr) => {
+        match $value {
+            $reftype::List(list) => match list {
+                ListTag::Empty => $other,
+                ListTag::Byte(items) => $each!(items, Byte),
+                ListTag::Short(items) => $each!(items, Short),
+                ListTag::Int(items) => $each!(items, Int),
+                ListTag::Long(items) => $each!(items, Long),
+                ListTag::Float(items) => $each!(items, Float),
+                ListTag::Double(items) => $each!(items, Double),
+                ListTag::ByteArray(items) => $each!(items, ByteArray),
+                ListTag::String(items) => $each!(items, String),
+                ListTag::List(items) => $each!(items, List),
+                ListTag::Compound(items) => $each!(items, Compound),
+                ListTag::IntArray(items) => $each!(items, IntArray),
+                ListTag::LongArray(items) => $each!(items, LongArray),
+            },
+            $reftype::ByteArray(items) => $each!(items, Byte),
+            $reftype::IntArray(items) => $each!(items, Int),
+            $reftype::LongArray(items) => $each!(items, Long),
+            _ => $other,
+        }
+    };
+}
+
+/// Converts a negative index (counting from the end) to an index from the start.
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+fn select<'a>(node: &NbtPathNode, value: ValueRef<'a>, selected: &mut Vec<ValueRef<'a>>) {
+    macro_rules! all {
+        ($items:ident, $variant:ident) => {
+            selected.extend($items.iter().map(ValueRef::$variant))
+        };
+    }
+    match (node, value) {
+        (NbtPathNode::MatchRootObject(pattern), ValueRef::Compound(map)) if compound_matches(pattern, map) => selected.push(value),
+        (NbtPathNode::Key(key), ValueRef::Compound(map)) => selected.extend(map.get(key).map(ValueRef::from)),
+        (NbtPathNode::MatchObject(key, pattern), ValueRef::Compound(map)) => match map.get(key) {
+            Some(Tag::Compound(child)) if compound_matches(pattern, child) => selected.push(ValueRef::Compound(child)),
+            _ => (),
+        },
+        (&NbtPathNode::Index(index), value) => selected.extend(value.get_child(&TagPathPart::AtIndex(index))),
+        (NbtPathNode::AllElements, value) => match_elements!(value, ValueRef, all, ()),
+        (NbtPathNode::MatchElement(pattern), ValueRef::List(ListTag::Compound(items))) => {
+            selected.extend(items.iter().filter(|item| compound_matches(pattern, item)).map(ValueRef::Compound));
+        }
+        _ => (),
+    }
+}
+
+fn select_mut<'a>(node: &NbtPathNode, value: ValueRefMut<'a>, selected: &mut Vec<ValueRefMut<'a>>) {
+    macro_rules! all {
+        ($items:ident, $variant:ident) => {
+            selected.extend($items.iter_mut().map(ValueRefMut::$variant))
+        };
+    }
+    match (node, value) {
+        (NbtPathNode::MatchRootObject(pattern), ValueRefMut::Compound(map)) if compound_matches(pattern, map) => {
+            selected.push(ValueRefMut::Compound(map));
+        }
+        (NbtPathNode::Key(key), ValueRefMut::Compound(map)) => selected.extend(map.get_mut(key).map(ValueRefMut::from)),
+        (NbtPathNode::MatchObject(key, pattern), ValueRefMut::Compound(map)) => match map.get_mut(key) {
+            Some(Tag::Compound(child)) if compound_matches(pattern, child) => selected.push(ValueRefMut::Compound(child)),
+            _ => (),
+        },
+        (&NbtPathNode::Index(index), value) => {
+            macro_rules! at {
+                ($items:ident, $variant:ident) => {
+                    if let Some(index) = resolve_index(index, $items.len()) {
+                        selected.push(ValueRefMut::$variant(&mut $items[index]));
+                    }
+                };
+            }
+            match_elements!(value, ValueRefMut, at, ())
+        }
+        (NbtPathNode::AllElements, value) => match_elements!(value, ValueRefMut, all, ()),
+        (NbtPathNode::MatchElement(pattern), ValueRefMut::List(ListTag::Compound(items))) => {
+            selected.extend(items.iter_mut().filter(|item| compound_matches(pattern, item)).map(ValueRefMut::Compound));
+        }
+        _ => (),
+    }
+}
+
+/// Removes the values that the last node of a path selects from `parent`, returning how many were removed.
+fn remove_from(node: &NbtPathNode, parent: ValueRefMut<'_>) -> usize {
+    macro_rules! clear {
+        ($items:ident, $variant:ident) => {{
+            let count = $items.len();
+            $items.clear();
+            count
+        }};
+    }
+    match (node, parent) {
+        (NbtPathNode::Key(key), ValueRefMut::Compound(map)) => map.remove(key).is_some() as usize,
+        (NbtPathNode::MatchObject(key, pattern), ValueRefMut::Compound(map)) => {
+            if matches!(map.get(key), Some(Tag::Compound(child)) if compound_matches(pattern, child)) {
+                map.remove(key);
+                1
+            } else {
+                0
+            }
+        }
+        (&NbtPathNode::Index(index), parent) => {
+            macro_rules! remove_at {
+                ($items:ident, $variant:ident) => {
+                    match resolve_index(index, $items.len()) {
+                        Some(index) => {
+                            $items.remove(index);
+                            1
+                        }
+                        None => 0,
+                    }
+                };
+            }
+            match_elements!(parent, ValueRefMut, remove_at, 0)
+        }
+        (NbtPathNode::AllElements, parent) => match_elements!(parent, ValueRefMut, clear, 0),
+        (NbtPathNode::MatchElement(pattern), ValueRefMut::List(ListTag::Compound(items))) => {
+            let count = items.len();
+            items.retain(|item| !compound_matches(pattern, item));
+            count - items.len()
+        }
+        _ => 0,
+    }
+}
+
+impl Tag {
+    /// Finds the values that `path` selects.
+    pub fn query(&self, path: &NbtPath) -> Vec<ValueRef<'_>> {
+        path.0.iter().fold(vec![ValueRef::from(self)], |values, node| {
+            let mut selected = Vec::new();
+            values.into_iter().for_each(|value| select(node, value, &mut selected));
+            selected
+        })
+    }
+
+    /// Finds the values that `path` selects, so that they can be modified.
+    pub fn query_mut(&mut self, path: &NbtPath) -> Vec<ValueRefMut<'_>> {
+        query_mut_nodes(self, &path.0)
+    }
+
+    /// Removes the values that `path` selects, returning how many were removed.
+    /// The root can't be removed, so nothing is removed if the path is only a `{...}` node.
+    pub fn remove_path(&mut self, path: &NbtPath) -> usize {
+        let Some((last, parents)) = path.0.split_last() else {
+            return 0;
+        };
+        query_mut_nodes(self, parents).into_iter()
+            .map(|parent| remove_from(last, parent))
+            .sum()
+    }
+}
+
+fn query_mut_nodes<'a>(tag: &'a mut Tag, nodes: &[NbtPathNode]) -> Vec<ValueRefMut<'a>> {
+    nodes.iter().fold(vec![ValueRefMut::from(tag)], |values, node| {
+        let mut selected = Vec::new();
+        values.into_iter().for_each(|value| select_mut(node, value, &mut selected));
+        selected
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nbt_path_test() {
+        let mut tag = Tag::parse(r#"{
+            Level: {
+                Sections: [
+                    { Y: 0b, Palette: [{ Name: "minecraft:air" }, { Name: "minecraft:stone" }] },
+                    { Y: 1b, Palette: [{ Name: "minecraft:stone", Properties: { a: "b" } }] }
+                ],
+                "odd key": [I; 1, 2, 3]
+            }
+        }"#).unwrap();
+        let stone = NbtPath::parse(r#"Level.Sections[].Palette[{Name:"minecraft:stone"}]"#).unwrap();
+        assert_eq!(tag.query(&stone).len(), 2);
+        let path = NbtPath::parse(r#"Level."odd key"[-1]"#).unwrap();
+        assert!(matches!(tag.query(&path)[..], [ValueRef::Int(3)]));
+        assert_eq!(path.to_string(), r#"Level."odd key"[-1]"#);
+        let path = NbtPath::parse("{Level:{}}.Level.Sections[1].Palette[0].Properties{a:\"b\"}").unwrap();
+        assert_eq!(tag.query(&path).len(), 1);
+        assert!(NbtPath::parse(path.to_string()).is_ok());
+
+        for value in tag.query_mut(&NbtPath::parse("Level.Sections[].Y").unwrap()) {
+            if let ValueRefMut::Byte(y) = value {
+                *y += 10;
+            }
+        }
+        assert!(matches!(tag.query(&NbtPath::parse("Level.Sections[0].Y").unwrap())[..], [ValueRef::Byte(10)]));
+
+        assert_eq!(tag.remove_path(&stone), 2);
+        assert_eq!(tag.query(&NbtPath::parse("Level.Sections[].Palette[]").unwrap()).len(), 1);
+        assert_eq!(tag.remove_path(&NbtPath::parse("Level.\"odd key\"[0]").unwrap()), 1);
+        assert_eq!(tag.remove_path(&NbtPath::parse("Level.Sections").unwrap()), 1);
+        assert!(tag.query(&NbtPath::parse("Level.Sections").unwrap()).is_empty());
+
+        for invalid in ["", "a.", "a..b", "a[", "a[x]", "a.{b:1}", "a{b:1", "\"a"] {
+            assert!(matches!(NbtPath::parse(invalid), Err(McError::InvalidNbtPath(_))), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Human-readable output for [Tag]s, for inspecting NBT while debugging.
+//!
+//! The output is SNBT-like: compounds, and lists of compounds, lists, or arrays, are written over multiple lines,
+//! while other lists and arrays are written on a single line. Long arrays (such as a section's block states) can be
+//! truncated so that they don't bury everything else, and the output can be colored with ANSI escape codes.
+
+use std::fmt::Write;
+
+use super::{
+    format::{write_identifier, write_string, Indentation},
+    tag::{ListTag, Tag},
+    tagpath::TagPathPart,
+    tagref::ValueRef,
+};
+
+const RESET: &str = "\x1b[0m";
+const KEY_COLOR: &str = "\x1b[36m";
+const STRING_COLOR: &str = "\x1b[32m";
+const NUMBER_COLOR: &str = "\x1b[33m";
+const ARRAY_COLOR: &str = "\x1b[35m";
+const ELLIPSIS_COLOR: &str = "\x1b[90m";
+
+/// Options for pretty printing [Tag]s.
+#[derive(Debug, Clone, Copy)]
+pub struct PrettyPrinter {
+    indentation: Indentation,
+    color: bool,
+    sort_keys: bool,
+    array_limit: Option<usize>,
+}
+
+impl Default for PrettyPrinter {
+    /// Indents with four spaces and sorts keys, without color or truncation.
+    fn default() -> Self {
+        Self {
+            indentation: Indentation::four_spaces(),
+            color: false,
+            sort_keys: true,
+            array_limit: None,
+        }
+    }
+}
+
+impl PrettyPrinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_indentation(mut self, indentation: Indentation) -> Self {
+        self.indentation = indentation;
+        self
+    }
+
+    /// Colors keys, strings, numbers, and array prefixes with ANSI escape codes.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Whether compound keys are sorted. Otherwise they are written in the compound's order.
+    pub fn with_sorted_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Writes at most `limit` elements of each single-line list or array, followed by the number of elements left out.
+    pub fn with_array_limit(mut self, limit: usize) -> Self {
+        self.array_limit = Some(limit);
+        self
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, tag: &Tag) -> std::fmt::Result {
+        self.write_value(writer, ValueRef::from(tag), self.indentation)
+    }
+
+    pub fn print(&self, tag: &Tag) -> String {
+        let mut output = String::new();
+        // Writing to a String can't fail.
+        let _ = self.write(&mut output, tag);
+        output
+    }
+
+    fn paint<W: Write>(&self, writer: &mut W, color: &str, text: std::fmt::Arguments) -> std::fmt::Result {
+        if self.color {
+            write!(writer, "{color}{text}{RESET}")
+        } else {
+            writer.write_fmt(text)
+        }
+    }
+
+    fn write_value<W: Write>(&self, writer: &mut W, value: ValueRef<'_>, indentation: Indentation) -> std::fmt::Result {
+        match value {
+            ValueRef::Byte(value) => self.paint(writer, NUMBER_COLOR, format_args!("{value}b")),
+            ValueRef::Short(value) => self.paint(writer, NUMBER_COLOR, format_args!("{value}s")),
+            ValueRef::Int(value) => self.paint(writer, NUMBER_COLOR, format_args!("{value}")),
+            ValueRef::Long(value) => self.paint(writer, NUMBER_COLOR, format_args!("{value}L")),
+            ValueRef::Float(value) => self.paint(writer, NUMBER_COLOR, format_args!("{value}f")),
+            ValueRef::Double(value) => self.paint(writer, NUMBER_COLOR, format_args!("{value}d")),
+            ValueRef::String(value) => {
+                let mut quoted = String::new();
+                write_string(&mut quoted, value)?;
+                self.paint(writer, STRING_COLOR, format_args!("{quoted}"))
+            }
+            ValueRef::Compound(compound) => {
+                if compound.is_empty() {
+                    return write!(writer, "{{}}");
+                }
+                let mut entries: Vec<_> = compound.iter().collect();
+                if self.sort_keys {
+                    entries.sort_by_key(|(key, _)| *key);
+                }
+                let inner = indentation.indent();
+                writeln!(writer, "{{")?;
+                for (index, (key, tag)) in entries.iter().enumerate() {
+                    write!(writer, "{inner}")?;
+                    let mut identifier = String::new();
+                    write_identifier(&mut identifier, key)?;
+                    self.paint(writer, KEY_COLOR, format_args!("{identifier}"))?;
+                    write!(writer, ": ")?;
+                    self.write_value(writer, ValueRef::from(*tag), inner)?;
+                    writeln!(writer, "{}", if index + 1 < entries.len() { "," } else { "" })?;
+                }
+                write!(writer, "{indentation}}}")
+            }
+            ValueRef::List(ListTag::List(_) | ListTag::Compound(_) | ListTag::ByteArray(_) | ListTag::IntArray(_) | ListTag::LongArray(_)) => {
+                let count = value.element_count().unwrap_or_default();
+                let inner = indentation.indent();
+                writeln!(writer, "[")?;
+                for index in 0..count {
+                    write!(writer, "{inner}")?;
+                    if let Some(element) = value.get_child(&TagPathPart::AtIndex(index as i64)) {
+                        self.write_value(writer, element, inner)?;
+                    }
+                    writeln!(writer, "{}", if index + 1 < count { "," } else { "" })?;
+                }
+                write!(writer, "{indentation}]")
+            }
+            ValueRef::List(_) => self.write_inline(writer, value, None),
+            ValueRef::ByteArray(_) => self.write_inline(writer, value, Some("B")),
+            ValueRef::IntArray(_) => self.write_inline(writer, value, Some("I")),
+            ValueRef::LongArray(_) => self.write_inline(writer, value, Some("L")),
+        }
+    }
+
+    /// Writes a list or array on a single line.
+    fn write_inline<W: Write>(&self, writer: &mut W, value: ValueRef<'_>, prefix: Option<&str>) -> std::fmt::Result {
+        let count = value.element_count().unwrap_or_default();
+        let shown = self.array_limit.map_or(count, |limit| count.min(limit));
+        write!(writer, "[")?;
+        if let Some(prefix) = prefix {
+            self.paint(writer, ARRAY_COLOR, format_args!("{prefix};"))?;
+            if count > 0 {
+                write!(writer, " ")?;
+            }
+        }
+        for index in 0..shown {
+            if index > 0 {
+                write!(writer, ", ")?;
+            }
+            if let Some(element) = value.get_child(&TagPathPart::AtIndex(index as i64)) {
+                self.write_value(writer, element, self.indentation)?;
+            }
+        }
+        if shown < count {
+            if shown > 0 {
+                write!(writer, ", ")?;
+            }
+            self.paint(writer, ELLIPSIS_COLOR, format_args!("... {} more", count - shown))?;
+        }
+        write!(writer, "]")
+    }
+}
+
+impl Tag {
+    /// Formats this tag as indented SNBT-like text with [PrettyPrinter::default].
+    pub fn pretty_print(&self) -> String {
+        PrettyPrinter::default().print(self)
+    }
+
+    /// Formats this tag with the given options.
+    pub fn pretty_print_with(&self, printer: &PrettyPrinter) -> String {
+        printer.print(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pretty_print_test() {
+        let tag = Tag::parse(r#"{ b: [I; 1, 2, 3], a: { "odd key": "text", list: [1b, 2b] }, sections: [{ Y: 0b }, {}] }"#).unwrap();
+        assert_eq!(tag.pretty_print(), [
+            "{",
+            "    a: {",
+            "        list: [1b, 2b],",
+            "        \"odd key\": \"text\"",
+            "    },",
+            "    b: [I; 1, 2, 3],",
+            "    sections: [",
+            "        {",
+            "            Y: 0b",
+            "        },",
+            "        {}",
+            "    ]",
+            "}",
+        ].join("\n"));
+        let printer = PrettyPrinter::new().with_array_limit(2).with_color(true);
+        let colored = tag.pretty_print_with(&printer);
+        assert!(colored.contains("[\x1b[35mI;\x1b[0m \x1b[33m1\x1b[0m, \x1b[33m2\x1b[0m, \x1b[90m... 1 more\x1b[0m]"));
+        assert!(colored.contains("\x1b[36ma\x1b[0m: {"));
+    }
+}
@@ -0,0 +1,87 @@
+//! Mapping compound tags to structs with `#[derive(NbtSchema)]`, which implements
+//! [DecodeNbt](super::tag::DecodeNbt) and [EncodeNbt](super::tag::EncodeNbt) for a struct with named fields.
+//!
+//! Each field is stored in the tag with the same name as the field, and is decoded and encoded with
+//! the field type's own `DecodeNbt` and `EncodeNbt` implementations, so fields may be primitives,
+//! [Tag](super::tag::Tag)s, [Map](super::Map)s, or other schemas. Fields can be configured with `#[nbt(...)]`:
+//!
+//! - `rename = "xPos"` uses a different tag name.
+//! - `alias = "TileEntities"` is a name that is read if the tag is missing, such as the name used by older versions.
+//!   A field may have several aliases, which are tried in order. Fields are always written with their own name.
+//! - `default` uses `Default::default()` if the tag is missing, and `default = "expr"` uses an expression instead.
+//!   Otherwise, a missing tag is an [McError::NotFoundInCompound](crate::McError::NotFoundInCompound).
+//! - `other` collects the tags that no other field uses into a [Map](super::Map), and writes them back when encoding.
+//!
+//! `Option` fields are `None` if the tag is missing (unless they have a default), and aren't written when they're `None`.
+//!
+//! ```no_run
+//! use mcutil::nbt::{schema::NbtSchema, Map};
+//!
+//! #[derive(NbtSchema)]
+//! struct BlockEntityHeader {
+//!     id: String,
+//!     #[nbt(rename = "keepPacked", default)]
+//!     keep_packed: i8,
+//!     x: i32,
+//!     y: i32,
+//!     z: i32,
+//!     #[nbt(rename = "CustomName")]
+//!     custom_name: Option<String>,
+//!     #[nbt(other)]
+//!     data: Map,
+//! }
+//! ```
+
+pub use macrocraft::NbtSchema;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{McError, nbt::{Map, tag::{DecodeNbt, EncodeNbt, Tag}}};
+
+    #[derive(NbtSchema, Debug, PartialEq)]
+    struct Position {
+        x: i32,
+        z: i32,
+    }
+
+    #[derive(NbtSchema, Debug)]
+    struct ChunkHeader {
+        #[nbt(rename = "DataVersion", default = "100")]
+        data_version: i32,
+        #[nbt(rename = "Status", alias = "TerrainPopulated")]
+        status: String,
+        position: Position,
+        #[nbt(rename = "LastUpdate")]
+        last_update: Option<i64>,
+        #[nbt(other)]
+        other: Map,
+    }
+
+    #[test]
+    fn schema_test() {
+        let nbt = Tag::Compound(Map::from([
+            ("TerrainPopulated".to_owned(), Tag::string("full")),
+            ("position".to_owned(), Tag::Compound(Map::from([("x".to_owned(), Tag::Int(3)), ("z".to_owned(), Tag::Int(-4))]))),
+            ("InhabitedTime".to_owned(), Tag::Long(9)),
+        ]));
+        let header = ChunkHeader::decode_nbt(nbt).unwrap();
+        assert_eq!(header.data_version, 100);
+        assert_eq!(header.status, "full");
+        assert_eq!(header.position, Position { x: 3, z: -4 });
+        assert_eq!(header.last_update, None);
+        assert!(header.other.contains_key("InhabitedTime"));
+
+        let Tag::Compound(encoded) = header.encode_nbt() else {
+            panic!("expected a compound");
+        };
+        assert!(matches!(encoded.get("Status"), Some(Tag::String(status)) if status == "full"));
+        assert!(!encoded.contains_key("TerrainPopulated") && !encoded.contains_key("LastUpdate"));
+        assert!(encoded.contains_key("InhabitedTime"));
+        let mut missing = encoded.clone();
+        missing.remove("position");
+        assert!(matches!(ChunkHeader::decode_nbt(Tag::Compound(missing)), Err(McError::NotFoundInCompound(name)) if name == "position"));
+        assert!(matches!(ChunkHeader::decode_nbt(Tag::Int(0)), Err(McError::NbtDecodeError)));
+        assert!(ChunkHeader::decode_nbt(Tag::Compound(encoded)).is_ok());
+    }
+}
@@ -0,0 +1,680 @@
+//! [serde](::serde) support for NBT.
+//! This module is only available with the `serde` feature.
+//!
+//! Any type that implements [Serialize] can be converted to a [Tag] with [to_tag],
+//! and any type that implements [Deserialize] can be converted from a [Tag] with [from_tag].
+//! [to_writer] and [from_reader] do the same for (uncompressed) NBT streams with a root [NamedTag].
+//!
+//! Types are mapped like so:
+//! - `bool` is a [Tag::Byte] (`0` or `1`).
+//! - `i8`, `i16`, `i32`, and `i64` are [Tag::Byte], [Tag::Short], [Tag::Int], and [Tag::Long].
+//!   Unsigned integers are stored in the signed tag of the same width with the same bits.
+//! - `f32` and `f64` are [Tag::Float] and [Tag::Double].
+//! - `char` and strings are [Tag::String].
+//! - Sequences and tuples are [Tag::List]. All elements must serialize to the same tag type.
+//!   Use [byte_array], [int_array], or [long_array] to store a sequence as an array tag instead.
+//! - Structs and maps are [Tag::Compound]. Map keys must be strings, chars, or integers.
+//! - `None` is left out of compounds. `Some` is the inner value.
+//! - Unit variants are the name of the variant as a [Tag::String]. Other variants are
+//!   a [Tag::Compound] with the name of the variant as the only key.
+//!
+//! When deserializing, array tags can be read into sequences as well as lists.
+
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
+
+use ::serde::{
+    de::{
+        self,
+        value::{MapDeserializer, SeqDeserializer},
+        DeserializeOwned,
+        DeserializeSeed,
+        EnumAccess,
+        IntoDeserializer,
+        VariantAccess,
+        Visitor,
+    },
+    ser::{self, Serialize},
+    forward_to_deserialize_any,
+    Deserialize,
+};
+
+use crate::{
+    McError, McResult,
+    ioext::*,
+    nbt::{
+        Map,
+        tag::{ListTag, NamedTag, Tag, TagID},
+        tag_info_table,
+    },
+};
+
+const BYTE_ARRAY_TOKEN: &str = "$mcutil::nbt::ByteArray";
+const INT_ARRAY_TOKEN: &str = "$mcutil::nbt::IntArray";
+const LONG_ARRAY_TOKEN: &str = "$mcutil::nbt::LongArray";
+
+impl ser::Error for McError {
+    fn custom<T: Display>(msg: T) -> Self {
+        McError::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for McError {
+    fn custom<T: Display>(msg: T) -> Self {
+        McError::Custom(msg.to_string())
+    }
+}
+
+/// Serializes a value to a [Tag].
+/// Returns an error if the value has no NBT representation (such as `None`).
+pub fn to_tag<T: Serialize + ?Sized>(value: &T) -> McResult<Tag> {
+    match value.serialize(Serializer)? {
+        Some(tag) => Ok(tag),
+        None => McError::custom("Value has no NBT representation."),
+    }
+}
+
+/// Deserializes a value from a [Tag].
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> McResult<T> {
+    T::deserialize(Deserializer::new(tag))
+}
+
+/// Serializes a value as the root tag of an NBT stream (with an empty name).
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(writer: &mut W, value: &T) -> McResult<usize> {
+    NamedTag::new(to_tag(value)?).write_to(writer)
+}
+
+/// Deserializes a value from the root tag of an NBT stream.
+pub fn from_reader<R: Read, T: DeserializeOwned>(reader: &mut R) -> McResult<T> {
+    from_tag(NamedTag::read_from(reader)?.take_tag())
+}
+
+macro_rules! list_conversions {
+    ($($id:literal $title:ident $type:path [$($impl:path)?])+) => {
+        /// Creates a [ListTag] from tags that all have the same type.
+        fn list_from_tags(tags: Vec<Tag>) -> McResult<ListTag> {
+            let Some(first) = tags.first() else {
+                return Ok(ListTag::Empty);
+            };
+            match first.id() {
+                $(
+                    TagID::$title => tags.into_iter().map(|tag| match tag {
+                        Tag::$title(value) => Ok(value),
+                        other => McError::custom(format!(
+                            "List elements must have the same type. Expected {}, found {}.",
+                            TagID::$title.title(),
+                            other.title(),
+                        )),
+                    }).collect::<McResult<Vec<_>>>().map(ListTag::$title),
+                )+
+            }
+        }
+
+        fn tags_from_list(list: ListTag) -> Vec<Tag> {
+            match list {
+                ListTag::Empty => Vec::new(),
+                $(
+                    ListTag::$title(values) => values.into_iter().map(Tag::$title).collect(),
+                )+
+            }
+        }
+    };
+}
+
+tag_info_table!(list_conversions);
+
+macro_rules! array_modules {
+    ($($module:ident $token:ident $title:literal;)+) => {
+        $(
+            #[doc = concat!("Serializes a sequence as a [Tag::", $title, "] instead of a [Tag::List].")]
+            #[doc = ""]
+            #[doc = concat!("Use with `#[serde(with = \"mcutil::nbt::serde::", stringify!($module), "\")]`.")]
+            /// Other formats see the sequence unchanged.
+            pub mod $module {
+                use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+                pub fn serialize<T: Serialize + ?Sized, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_newtype_struct(super::$token, value)
+                }
+
+                pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+                    T::deserialize(deserializer)
+                }
+            }
+        )+
+    };
+}
+
+array_modules! {
+    byte_array BYTE_ARRAY_TOKEN "ByteArray";
+    int_array INT_ARRAY_TOKEN "IntArray";
+    long_array LONG_ARRAY_TOKEN "LongArray";
+}
+
+/// Converts a serialized sequence into an array tag for the [byte_array], [int_array], and [long_array] modules.
+fn sequence_to_array(token: &'static str, tag: Tag) -> McResult<Tag> {
+    match (token, tag) {
+        (BYTE_ARRAY_TOKEN, tag @ Tag::ByteArray(_))
+        | (INT_ARRAY_TOKEN, tag @ Tag::IntArray(_))
+        | (LONG_ARRAY_TOKEN, tag @ Tag::LongArray(_)) => Ok(tag),
+        (BYTE_ARRAY_TOKEN, Tag::List(ListTag::Byte(values))) => Ok(Tag::ByteArray(values)),
+        (INT_ARRAY_TOKEN, Tag::List(ListTag::Int(values))) => Ok(Tag::IntArray(values)),
+        (LONG_ARRAY_TOKEN, Tag::List(ListTag::Long(values))) => Ok(Tag::LongArray(values)),
+        (BYTE_ARRAY_TOKEN, Tag::List(ListTag::Empty)) => Ok(Tag::ByteArray(Vec::new())),
+        (INT_ARRAY_TOKEN, Tag::List(ListTag::Empty)) => Ok(Tag::IntArray(Vec::new())),
+        (LONG_ARRAY_TOKEN, Tag::List(ListTag::Empty)) => Ok(Tag::LongArray(Vec::new())),
+        (_, tag) => McError::custom(format!("Expected a sequence of array elements, found {}.", tag.title())),
+    }
+}
+
+/// A [serde::Serializer](ser::Serializer) that produces a [Tag].
+/// Values without an NBT representation (`None`) serialize to `None`.
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Tag>;
+    type Error = McError;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Long(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Byte(v as i8)))
+    }
+
+    fn serialize_u16(self, v: u16) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Short(v as i16)))
+    }
+
+    fn serialize_u32(self, v: u32) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Int(v as i32)))
+    }
+
+    fn serialize_u64(self, v: u64) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Long(v as i64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::String(v.to_owned())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::ByteArray(v.iter().map(|&b| b as i8).collect())))
+    }
+
+    fn serialize_none(self) -> McResult<Option<Tag>> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> McResult<Option<Tag>> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Compound(Map::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> McResult<Option<Tag>> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> McResult<Option<Tag>> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> McResult<Option<Tag>> {
+        match (name, value.serialize(self)?) {
+            (BYTE_ARRAY_TOKEN | INT_ARRAY_TOKEN | LONG_ARRAY_TOKEN, Some(tag)) => sequence_to_array(name, tag).map(Some),
+            (_, tag) => Ok(tag),
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _variant_index: u32, variant: &'static str, value: &T) -> McResult<Option<Tag>> {
+        let mut map = Map::new();
+        if let Some(tag) = value.serialize(self)? {
+            map.insert(variant.to_owned(), tag);
+        }
+        Ok(Some(Tag::Compound(map)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> McResult<SerializeList> {
+        Ok(SerializeList {
+            tags: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> McResult<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> McResult<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, len: usize) -> McResult<SerializeTupleVariant> {
+        Ok(SerializeTupleVariant {
+            variant,
+            list: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> McResult<SerializeCompound> {
+        Ok(SerializeCompound {
+            map: Map::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> McResult<SerializeCompound> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, len: usize) -> McResult<SerializeStructVariant> {
+        Ok(SerializeStructVariant {
+            variant,
+            compound: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+/// Serializes sequences and tuples as a [Tag::List].
+pub struct SerializeList {
+    tags: Vec<Tag>,
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = McError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> McResult<()> {
+        match value.serialize(Serializer)? {
+            Some(tag) => self.tags.push(tag),
+            None => return McError::custom("Lists can't contain values without an NBT representation."),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::List(list_from_tags(self.tags)?)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = McError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> McResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> McResult<Option<Tag>> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = McError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> McResult<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> McResult<Option<Tag>> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Serializes a tuple variant as a [Tag::Compound] containing a [Tag::List].
+pub struct SerializeTupleVariant {
+    variant: &'static str,
+    list: SerializeList,
+}
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = Option<Tag>;
+    type Error = McError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> McResult<()> {
+        ser::SerializeSeq::serialize_element(&mut self.list, value)
+    }
+
+    fn end(self) -> McResult<Option<Tag>> {
+        let mut map = Map::new();
+        if let Some(tag) = ser::SerializeSeq::end(self.list)? {
+            map.insert(self.variant.to_owned(), tag);
+        }
+        Ok(Some(Tag::Compound(map)))
+    }
+}
+
+/// Serializes maps and structs as a [Tag::Compound].
+pub struct SerializeCompound {
+    map: Map,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for SerializeCompound {
+    type Ok = Option<Tag>;
+    type Error = McError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> McResult<()> {
+        self.key = Some(match key.serialize(Serializer)? {
+            Some(Tag::String(key)) => key,
+            Some(Tag::Byte(key)) => key.to_string(),
+            Some(Tag::Short(key)) => key.to_string(),
+            Some(Tag::Int(key)) => key.to_string(),
+            Some(Tag::Long(key)) => key.to_string(),
+            _ => return McError::custom("Compound keys must be strings, chars, or integers."),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> McResult<()> {
+        let Some(key) = self.key.take() else {
+            return McError::custom("serialize_value was called before serialize_key.");
+        };
+        if let Some(tag) = value.serialize(Serializer)? {
+            self.map.insert(key, tag);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Compound(self.map)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeCompound {
+    type Ok = Option<Tag>;
+    type Error = McError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> McResult<()> {
+        if let Some(tag) = value.serialize(Serializer)? {
+            self.map.insert(key.to_owned(), tag);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> McResult<Option<Tag>> {
+        Ok(Some(Tag::Compound(self.map)))
+    }
+}
+
+/// Serializes a struct variant as a [Tag::Compound] containing a [Tag::Compound].
+pub struct SerializeStructVariant {
+    variant: &'static str,
+    compound: SerializeCompound,
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Option<Tag>;
+    type Error = McError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> McResult<()> {
+        ser::SerializeStruct::serialize_field(&mut self.compound, key, value)
+    }
+
+    fn end(self) -> McResult<Option<Tag>> {
+        let mut map = Map::new();
+        map.insert(self.variant.to_owned(), Tag::Compound(self.compound.map));
+        Ok(Some(Tag::Compound(map)))
+    }
+}
+
+/// A [serde::Deserializer](de::Deserializer) that consumes a [Tag].
+pub struct Deserializer {
+    tag: Tag,
+}
+
+impl Deserializer {
+    pub fn new(tag: Tag) -> Self {
+        Self { tag }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, McError> for Tag {
+    type Deserializer = Deserializer;
+
+    fn into_deserializer(self) -> Deserializer {
+        Deserializer::new(self)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = McError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        match self.tag {
+            Tag::Byte(v) => visitor.visit_i8(v),
+            Tag::Short(v) => visitor.visit_i16(v),
+            Tag::Int(v) => visitor.visit_i32(v),
+            Tag::Long(v) => visitor.visit_i64(v),
+            Tag::Float(v) => visitor.visit_f32(v),
+            Tag::Double(v) => visitor.visit_f64(v),
+            Tag::String(v) => visitor.visit_string(v),
+            // Array elements are deserialized as tags so that unsigned integers are handled the same as in lists.
+            Tag::ByteArray(v) => visitor.visit_seq(SeqDeserializer::new(v.into_iter().map(Tag::Byte))),
+            Tag::IntArray(v) => visitor.visit_seq(SeqDeserializer::new(v.into_iter().map(Tag::Int))),
+            Tag::LongArray(v) => visitor.visit_seq(SeqDeserializer::new(v.into_iter().map(Tag::Long))),
+            Tag::List(list) => visitor.visit_seq(SeqDeserializer::new(tags_from_list(list).into_iter())),
+            Tag::Compound(map) => visitor.visit_map(MapDeserializer::new(map.into_iter())),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        match self.tag {
+            Tag::Byte(v) => visitor.visit_bool(v != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    // Unsigned integers are stored with the same bits as the signed tag of the same width.
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        match self.tag {
+            Tag::Byte(v) => visitor.visit_u8(v as u8),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        match self.tag {
+            Tag::Short(v) => visitor.visit_u16(v as u16),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        match self.tag {
+            Tag::Int(v) => visitor.visit_u32(v as u32),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        match self.tag {
+            Tag::Long(v) => visitor.visit_u64(v as u64),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        match self.tag {
+            Tag::ByteArray(v) => visitor.visit_byte_buf(v.into_iter().map(|b| b as u8).collect()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        // Missing values are None, so anything that is present is Some.
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> McResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> McResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> McResult<V::Value> {
+        match self.tag {
+            Tag::String(variant) => visitor.visit_enum(IntoDeserializer::<McError>::into_deserializer(variant)),
+            Tag::Compound(map) if map.len() == 1 => {
+                let (variant, value) = map.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            },
+            tag => McError::custom(format!("Expected a String or a Compound with a single entry for an enum, found {}.", tag.title())),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> McResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        seq tuple tuple_struct map struct identifier
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Tag,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = McError;
+    type Variant = Deserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> McResult<(V::Value, Deserializer)> {
+        let variant = seed.deserialize(IntoDeserializer::<McError>::into_deserializer(self.variant))?;
+        Ok((variant, Deserializer::new(self.value)))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Deserializer {
+    type Error = McError;
+
+    fn unit_variant(self) -> McResult<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> McResult<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> McResult<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> McResult<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::serde::Serialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Mode {
+        Survival,
+        Custom { speed: f32 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: String,
+        count: u8,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Player {
+        name: String,
+        health: f32,
+        on_ground: bool,
+        #[serde(with = "int_array")]
+        uuid: Vec<u32>,
+        inventory: Vec<Item>,
+        mode: Mode,
+        previous_mode: Mode,
+        spawn: Option<(i32, i32, i32)>,
+    }
+
+    #[test]
+    fn serde_round_trip_test() {
+        let player = Player {
+            name: String::from("Steve"),
+            health: 20.0,
+            on_ground: true,
+            uuid: vec![1, 2, 3, u32::MAX],
+            inventory: vec![Item { id: String::from("minecraft:stone"), count: 200 }],
+            mode: Mode::Survival,
+            previous_mode: Mode::Custom { speed: 0.5 },
+            spawn: None,
+        };
+        let tag = to_tag(&player).unwrap();
+        let Tag::Compound(map) = &tag else {
+            panic!("Expected a Compound.");
+        };
+        assert!(matches!(map.get("uuid"), Some(Tag::IntArray(uuid)) if uuid[3] == -1));
+        assert!(matches!(map.get("on_ground"), Some(Tag::Byte(1))));
+        assert!(matches!(map.get("mode"), Some(Tag::String(mode)) if mode == "Survival"));
+        assert!(!map.contains_key("spawn"));
+        let mut buffer = Vec::new();
+        to_writer(&mut buffer, &player).unwrap();
+        let read: Player = from_reader(&mut buffer.as_slice()).unwrap();
+        assert_eq!(read, player);
+        // List elements must all have the same type.
+        assert!(to_tag(&(1i32, 1i64)).is_err());
+    }
+}
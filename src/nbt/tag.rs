@@ -160,6 +160,12 @@ impl Tag {
             Tag::LongArray(_) => TagID::LongArray,
         }
     }
+
+    /// Returns the exact number of bytes that the tag's payload is written as, without writing it.
+    /// This doesn't include the type ID or name that precede the payload in a compound; see [NamedTag::byte_size].
+    pub fn byte_size(&self) -> usize {
+        crate::nbt::io::NbtSize::nbt_size(self)
+    }
 }
 
 impl ListTag {
@@ -422,6 +428,12 @@ impl NamedTag {
     pub fn set_name<T: Into<String>>(&mut self, name: T) {
         self.name = name.into();
     }
+
+    /// Returns the exact number of bytes that the NamedTag is written as (uncompressed), including the
+    /// type ID and the name, without writing it. This is the size of a chunk's NBT before it's compressed.
+    pub fn byte_size(&self) -> usize {
+        crate::nbt::io::NbtSize::nbt_size(self)
+    }
 }
 
 /// Creates a NamedTag from (Into<String>, Into<Tag>)
@@ -160,6 +160,17 @@ impl<'a> ValueRef<'a> {
         get_child_dry!(self:ValueRef at => ValueRef)
     }
 
+    /// The number of elements if this is a list or array, which can be accessed with [ValueRef::get_child].
+    pub fn element_count(self) -> Option<usize> {
+        match self {
+            ValueRef::ByteArray(array) => Some(array.len()),
+            ValueRef::List(list) => Some(list.len()),
+            ValueRef::IntArray(array) => Some(array.len()),
+            ValueRef::LongArray(array) => Some(array.len()),
+            _ => None,
+        }
+    }
+
     pub fn find_child(self, path: &[TagPathPart]) -> Option<ValueRef<'a>> {
         if path.is_empty() {
             return None;
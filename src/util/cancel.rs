@@ -0,0 +1,57 @@
+//! Cancelling long-running operations from another thread.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::{McError, McResult};
+
+/// A flag that can be set from any thread to ask a long-running operation to stop.
+/// Clones share the same flag, so one clone can be handed to the operation and another kept by a UI.
+///
+/// Operations that accept a token check it between units of work (such as chunks or region files)
+/// and return [McError::Cancelled] once it's set, after undoing any partially written file.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks the operations using this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns [McError::Cancelled] if the token has been cancelled.
+    pub fn check(&self) -> McResult<()> {
+        if self.is_cancelled() {
+            Err(McError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancellation_token_test() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+        std::thread::spawn(move || clone.cancel()).join().unwrap();
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(McError::Cancelled)));
+    }
+}
@@ -0,0 +1,18 @@
+//! Conversions between [Compression] levels and plain integers.
+
+use flate2::Compression;
+
+use crate::{McError, McResult};
+
+/// Creates a [Compression] from a compression level in the range `0..=9`,
+/// where `0` is no compression and `9` is the best compression.
+/// Returns [McError::OutOfRange] if the level is greater than `9`.
+pub fn compression_from_level(level: u32) -> McResult<Compression> {
+    McError::range_check(level, 0..=9)?;
+    Ok(Compression::new(level))
+}
+
+/// Returns the compression level (`0..=9`) of a [Compression].
+pub fn level_of(compression: Compression) -> u32 {
+    compression.level()
+}
@@ -1,2 +1,4 @@
 pub mod traits;
-pub mod coreext;
\ No newline at end of file
+pub mod coreext;
+pub mod compression;
+pub mod cancel;
\ No newline at end of file
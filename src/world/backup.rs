@@ -0,0 +1,306 @@
+//! Backing up a world directory into an archive, and restoring a world from one.
+//!
+//! Archives are zip files or gzipped tarballs, with paths relative to the world directory.
+//! `session.lock` and temporary files are never backed up or restored.
+//!
+//! This module is behind the `backup` feature.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{
+    math::{bounds::Bounds2, coord::Dimension},
+    McError, McResult,
+};
+
+use super::io::region::filename::RegionFileName;
+
+/// File names that are never backed up or restored.
+const SKIPPED_NAMES: [&str; 1] = ["session.lock"];
+
+/// Extensions of files that are never backed up or restored, such as partially written files.
+const SKIPPED_EXTENSIONS: [&str; 2] = ["tmp", "cache"];
+
+/// The folders of a dimension that hold region files.
+const REGION_FOLDERS: [&str; 3] = ["region", "entities", "poi"];
+
+/// The kind of archive that a backup is written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveFormat {
+    /// A `.zip` file.
+    Zip,
+    /// A `.tar.gz` file.
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// The file extension of the format, without a leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+        }
+    }
+
+    /// Guesses the format of an archive from its file name.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let name = path.as_ref().file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Selects the files of a world that are backed up or restored.
+///
+/// By default, every file is selected. Once the filter is restricted to dimensions or regions,
+/// only the region files (in the `region`, `entities`, and `poi` folders) that match are selected,
+/// and other files such as `level.dat` are left out.
+/// Dimensions other than the Overworld, the Nether, and the End can't be selected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupFilter {
+    dimensions: Option<Vec<Dimension>>,
+    regions: Option<Bounds2>,
+}
+
+impl BackupFilter {
+    /// A filter that selects every file.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Restricts the filter to the region files of the given dimensions.
+    pub fn with_dimensions<I: IntoIterator<Item = Dimension>>(mut self, dimensions: I) -> Self {
+        self.dimensions = Some(dimensions.into_iter().collect());
+        self
+    }
+
+    /// Restricts the filter to the region files within `regions` (inclusive), in region coordinates.
+    pub fn with_regions<T: Into<Bounds2>>(mut self, regions: T) -> Self {
+        self.regions = Some(regions.into());
+        self
+    }
+
+    /// Returns true if the file at `path` (relative to the world directory) is selected.
+    pub fn includes<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        if is_skipped(path) {
+            return false;
+        }
+        if self.dimensions.is_none() && self.regions.is_none() {
+            return true;
+        }
+        let Some(components) = path.components()
+            .map(|component| component.as_os_str().to_str())
+            .collect::<Option<Vec<&str>>>() else {
+            return false;
+        };
+        let (dimension, rest) = match components.as_slice() {
+            ["DIM-1", rest @ ..] => (Dimension::Nether, rest),
+            ["DIM1", rest @ ..] => (Dimension::TheEnd, rest),
+            rest => (Dimension::Overworld, rest),
+        };
+        let [folder, file] = rest else {
+            return false;
+        };
+        if !REGION_FOLDERS.contains(folder) {
+            return false;
+        }
+        if self.dimensions.as_ref().is_some_and(|dimensions| !dimensions.contains(&dimension)) {
+            return false;
+        }
+        match (&self.regions, region_of_file(file)) {
+            (Some(regions), Some((x, z))) => (regions.min.x..=regions.max.x).contains(&x) && (regions.min.y..=regions.max.y).contains(&z),
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+/// Returns true if the file is never backed up or restored.
+fn is_skipped(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| SKIPPED_NAMES.contains(&name))
+        || path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| SKIPPED_EXTENSIONS.contains(&extension))
+}
+
+/// The region coordinate of a region file (`r.x.z.mca`) or an external chunk file (`c.x.z.mcc`).
+fn region_of_file(name: &str) -> Option<(i64, i64)> {
+    if let Some(region) = RegionFileName::parse(name) {
+        return Some((region.x, region.z));
+    }
+    let (x, z) = name.strip_prefix("c.")?.strip_suffix(".mcc")?.split_once('.')?;
+    Some((x.parse::<i64>().ok()?.div_euclid(32), z.parse::<i64>().ok()?.div_euclid(32)))
+}
+
+/// The files in `directory` and its subdirectories, relative to `root`.
+fn collect_files(root: &Path, directory: &Path, files: &mut Vec<PathBuf>) -> McResult<()> {
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(root, &entry.path(), files)?;
+        } else if file_type.is_file() {
+            let path = entry.path();
+            files.push(path.strip_prefix(root).map_err(|_| McError::Custom(format!("{} is outside of the world directory.", path.display())))?.to_owned());
+        }
+    }
+    Ok(())
+}
+
+/// The name of an archive entry, which always uses `/` as the separator.
+fn entry_name(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Writes the files of the world at `world_dir` that `filter` selects into a new archive at `archive`.
+/// Returns the number of files that were written.
+pub fn write_archive<P1: AsRef<Path>, P2: AsRef<Path>>(world_dir: P1, archive: P2, format: ArchiveFormat, filter: &BackupFilter) -> McResult<usize> {
+    let world_dir = world_dir.as_ref();
+    if !world_dir.is_dir() {
+        return Err(McError::WorldDirectoryNotFound(world_dir.to_owned()));
+    }
+    let mut files = Vec::new();
+    collect_files(world_dir, world_dir, &mut files)?;
+    files.retain(|path| filter.includes(path));
+    files.sort();
+    let writer = BufWriter::new(File::options().write(true).create_new(true).open(archive)?);
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipWriter::new(writer);
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .large_file(true);
+            for path in files.iter() {
+                zip.start_file(entry_name(path), options)?;
+                std::io::copy(&mut File::open(world_dir.join(path))?, &mut zip)?;
+            }
+            zip.finish()?.flush()?;
+        }
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+            for path in files.iter() {
+                tar.append_path_with_name(world_dir.join(path), entry_name(path))?;
+            }
+            tar.into_inner()?.finish()?.flush()?;
+        }
+    }
+    Ok(files.len())
+}
+
+/// Backs up the world at `world_dir` into a new archive in `backup_dir`, which is created if it doesn't exist.
+/// The archive is named after the world directory and the current local time, such as `New World_2023-06-07_18-30-00.zip`.
+/// Returns the path of the archive.
+pub fn backup_world<P1: AsRef<Path>, P2: AsRef<Path>>(world_dir: P1, backup_dir: P2, format: ArchiveFormat, filter: &BackupFilter) -> McResult<PathBuf> {
+    let world_dir = world_dir.as_ref();
+    let world_name = world_dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "world".to_owned());
+    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+    std::fs::create_dir_all(backup_dir.as_ref())?;
+    let archive = backup_dir.as_ref().join(format!("{world_name}_{timestamp}.{}", format.extension()));
+    write_archive(world_dir, &archive, format, filter)?;
+    Ok(archive)
+}
+
+/// Extracts the files that `filter` selects from `archive` into `world_dir`, replacing existing files.
+/// Files in the world that aren't in the archive are left alone.
+/// The format is detected from the archive's file name, and [McError::UnsupportedArchiveFormat] is returned if it isn't recognized.
+/// Entries with paths that would be outside of `world_dir` are skipped.
+///
+/// Returns the number of files that were restored.
+pub fn restore_world<P1: AsRef<Path>, P2: AsRef<Path>>(archive: P1, world_dir: P2, filter: &BackupFilter) -> McResult<usize> {
+    let archive = archive.as_ref();
+    let world_dir = world_dir.as_ref();
+    let format = ArchiveFormat::from_path(archive).ok_or_else(|| McError::UnsupportedArchiveFormat(archive.to_owned()))?;
+    let reader = BufReader::new(File::open(archive)?);
+    let mut count = 0;
+    let mut restore = |path: &Path, reader: &mut dyn std::io::Read| -> McResult<()> {
+        if path.components().any(|component| !matches!(component, Component::Normal(_))) || !filter.includes(path) {
+            return Ok(());
+        }
+        let output = world_dir.join(path);
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::io::copy(reader, &mut BufWriter::new(File::create(output)?))?;
+        count += 1;
+        Ok(())
+    };
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipArchive::new(reader)?;
+            for index in 0..zip.len() {
+                let mut entry = zip.by_index(index)?;
+                if !entry.is_file() {
+                    continue;
+                }
+                let Some(path) = entry.enclosed_name() else {
+                    continue;
+                };
+                restore(&path, &mut entry)?;
+            }
+        }
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Archive::new(GzDecoder::new(reader));
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let path = entry.path()?.into_owned();
+                restore(&path, &mut entry)?;
+            }
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backup_restore_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = dir.path().join("world");
+        for path in ["level.dat", "session.lock", "region/r.0.0.mca", "region/r.3.-1.mca", "region/c.70.-5.mcc", "DIM-1/region/r.0.0.mca", "data/raids.dat"] {
+            let path = world.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "data").unwrap();
+        }
+        for format in [ArchiveFormat::Zip, ArchiveFormat::TarGz] {
+            let archive = backup_world(&world, dir.path().join("backups"), format, &BackupFilter::all()).unwrap();
+            assert_eq!(ArchiveFormat::from_path(&archive), Some(format));
+            let full = dir.path().join(format!("full_{}", format.extension()));
+            assert_eq!(restore_world(&archive, &full, &BackupFilter::all()).unwrap(), 6);
+            assert_eq!(std::fs::read_to_string(full.join("DIM-1/region/r.0.0.mca")).unwrap(), "data");
+            assert!(!full.join("session.lock").exists());
+
+            let filter = BackupFilter::all()
+                .with_dimensions([Dimension::Overworld])
+                .with_regions(((2, -2), (3, 0)));
+            let partial = dir.path().join(format!("partial_{}", format.extension()));
+            assert_eq!(restore_world(&archive, &partial, &filter).unwrap(), 2);
+            assert!(partial.join("region/r.3.-1.mca").exists());
+            assert!(partial.join("region/c.70.-5.mcc").exists());
+            assert!(!partial.join("level.dat").exists());
+        }
+        assert!(matches!(restore_world(dir.path().join("world.rar"), &world, &BackupFilter::all()), Err(McError::UnsupportedArchiveFormat(_))));
+    }
+}
@@ -186,6 +186,7 @@ impl Into<(i64, i64, i64)> for CubeDirection {
 }
 
 #[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HeightmapFlag {
     MotionBlocking = 1,
     MotionBlockingNoLeaves = 2,
@@ -8,7 +8,11 @@ use std::sync::atomic::{
 
 use std::collections::HashMap;
 
+use crate::McResult;
+
 use super::blockstate::*;
+#[cfg(feature = "vanilla-blocks")]
+use super::vanilla::VanillaBlocks;
 
 // I'm going to shelve this for another time.
 // #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -24,16 +28,16 @@ in the registry for as long as the registry exists.
 pub struct BlockRegistry {
     ids: HashMap<BlockState, u32>,
     states: Vec<BlockState>,
+    /// The blocks that [BlockRegistry::try_register] accepts, if it validates states.
+    #[cfg(feature = "vanilla-blocks")]
+    vanilla: Option<&'static VanillaBlocks>,
 }
 
 impl BlockRegistry {
     // Does it make sense for a BlockRegistry to not have
     // `minecraft:air` registered as the 0-index BlockState?
     pub fn new() -> Self {
-        Self {
-            ids: HashMap::new(),
-            states: Vec::new(),
-        }
+        Self::default()
     }
 
     pub fn len(&self) -> usize {
@@ -43,11 +47,50 @@ impl BlockRegistry {
     /// Creates a block registry with "minecraft:air" registered in
     /// the first slot (index/id 0).
     pub fn with_air() -> Self {
-        let air = BlockState::air();
-        Self {
-            ids: HashMap::from([(air.clone(), 0)]),
-            states: Vec::from([air]),
+        let mut registry = Self::new();
+        registry.register(BlockState::air());
+        registry
+    }
+
+    /// Creates a block registry with "minecraft:air" registered in the first slot
+    /// that checks the states given to [BlockRegistry::try_register] against the
+    /// vanilla blocks of the Minecraft version with the given DataVersion.
+    /// Returns [McError::UnsupportedDataVersion](crate::McError::UnsupportedDataVersion)
+    /// if there is no block list for the version.
+    #[cfg(feature = "vanilla-blocks")]
+    pub fn vanilla(data_version: i32) -> McResult<Self> {
+        Ok(Self {
+            vanilla: Some(VanillaBlocks::for_data_version(data_version)?),
+            ..Self::with_air()
+        })
+    }
+
+    /// The vanilla blocks that this registry validates against, if it was created with [BlockRegistry::vanilla].
+    #[cfg(feature = "vanilla-blocks")]
+    pub fn vanilla_blocks(&self) -> Option<&'static VanillaBlocks> {
+        self.vanilla
+    }
+
+    /// Checks that a state can be registered with [BlockRegistry::try_register].
+    /// Every state is valid unless the registry was created with `BlockRegistry::vanilla`
+    /// (with the `vanilla-blocks` feature), in which case `VanillaBlocks::validate` is used.
+    pub fn validate<T: Borrow<BlockState>>(&self, state: T) -> McResult<()> {
+        #[cfg(feature = "vanilla-blocks")]
+        if let Some(vanilla) = self.vanilla {
+            return vanilla.validate(state.borrow());
         }
+        let _ = state;
+        Ok(())
+    }
+
+    /// Registers a [BlockState] with the registry after checking it with [BlockRegistry::validate],
+    /// and returns the ID. States that are already registered aren't checked again.
+    pub fn try_register<T: Borrow<BlockState>>(&mut self, state: T) -> McResult<u32> {
+        if let Some(id) = self.find(state.borrow()) {
+            return Ok(id);
+        }
+        self.validate(state.borrow())?;
+        Ok(self.register(state))
     }
 
     /// Registers the air [BlockState].
@@ -116,4 +159,21 @@ impl BlockRegistry {
     // pub fn subset(&self) -> BlockRegistry {
     // 	todo!()
     // }
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, feature = "vanilla-blocks"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vanilla_registry_test() {
+        let mut registry = BlockRegistry::vanilla(3465).unwrap();
+        assert_eq!(registry.find(BlockState::air()), Some(0));
+        assert_eq!(registry.try_register(BlockState::from("minecraft:stone")).unwrap(), 1);
+        assert!(registry.try_register(BlockState::from("minecraft:stnoe")).is_err());
+        assert_eq!(registry.len(), 2);
+        // States registered without validation are still accepted.
+        let id = registry.register(BlockState::from("mymod:machine"));
+        assert_eq!(registry.try_register(BlockState::from("mymod:machine")).unwrap(), id);
+    }
+}
@@ -2,7 +2,9 @@ use std::fmt::Display;
 
 use sorted_vec::SortedVec;
 
-use crate::{nbt::{tag::*, Map}, McResult, McError};
+use crate::{math::coord::Cardinal, nbt::{tag::*, Map}, McResult, McError};
+
+use super::block::CubeDirection;
 
 /// Create a [BlockState].
 /// 
@@ -152,6 +154,138 @@ impl BlockProperties {
         }
         None
     }
+
+    /// Sets the value of a property, adding it if it doesn't exist. Returns the old value.
+    pub fn set<S1: AsRef<str>, S2: AsRef<str>>(&mut self, key: S1, value: S2) -> Option<String> {
+        let old = self.remove(key.as_ref());
+        self.properties.get_or_insert_with(SortedVec::new).insert(BlockProperty::new(key, value));
+        old
+    }
+
+    /// Removes a property, returning its value.
+    pub fn remove<S: AsRef<str>>(&mut self, key: S) -> Option<String> {
+        let props = self.properties.as_mut()?;
+        let index = props.as_slice().binary_search_by(|prop| prop.name.as_str().cmp(key.as_ref())).ok()?;
+        let removed = props.remove_index(index);
+        // States without properties should compare equal to states created with `BlockProperties::none`.
+        if props.is_empty() {
+            self.properties = None;
+        }
+        Some(removed.value)
+    }
+}
+
+/// A type that can be the value of a block state property, such as `bool` for `waterlogged=true`
+/// or [CubeDirection] for `facing=north`.
+pub trait PropertyValue: Sized {
+    /// Parses the value from the string stored in the block state.
+    /// Returns `None` if the string isn't a valid value.
+    fn from_property(value: &str) -> Option<Self>;
+
+    /// The string stored in the block state.
+    fn to_property(&self) -> String;
+}
+
+impl PropertyValue for String {
+    fn from_property(value: &str) -> Option<Self> {
+        Some(value.to_owned())
+    }
+
+    fn to_property(&self) -> String {
+        self.clone()
+    }
+}
+
+impl PropertyValue for &str {
+    /// Always returns `None`, since the value can't be borrowed from the block state.
+    /// Use `String` to read values.
+    fn from_property(_: &str) -> Option<Self> {
+        None
+    }
+
+    fn to_property(&self) -> String {
+        (*self).to_owned()
+    }
+}
+
+impl PropertyValue for bool {
+    fn from_property(value: &str) -> Option<Self> {
+        match value {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn to_property(&self) -> String {
+        self.to_string()
+    }
+}
+
+macro_rules! int_property_value {
+    ($($type:ty),+) => {
+        $(
+            impl PropertyValue for $type {
+                fn from_property(value: &str) -> Option<Self> {
+                    value.parse().ok()
+                }
+
+                fn to_property(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )+
+    };
+}
+
+int_property_value!(i8, u8, i16, u16, i32, u32, i64, u64);
+
+/// The values of `facing` and similar properties.
+impl PropertyValue for CubeDirection {
+    fn from_property(value: &str) -> Option<Self> {
+        match value {
+            "east" => Some(CubeDirection::East),
+            "west" => Some(CubeDirection::West),
+            "south" => Some(CubeDirection::South),
+            "north" => Some(CubeDirection::North),
+            "up" => Some(CubeDirection::Up),
+            "down" => Some(CubeDirection::Down),
+            _ => None,
+        }
+    }
+
+    fn to_property(&self) -> String {
+        match self {
+            CubeDirection::East => "east",
+            CubeDirection::West => "west",
+            CubeDirection::South => "south",
+            CubeDirection::North => "north",
+            CubeDirection::Up => "up",
+            CubeDirection::Down => "down",
+        }.to_owned()
+    }
+}
+
+/// The values of `facing` for blocks that can only face horizontally, such as stairs.
+impl PropertyValue for Cardinal {
+    fn from_property(value: &str) -> Option<Self> {
+        match value {
+            "east" => Some(Cardinal::East),
+            "west" => Some(Cardinal::West),
+            "south" => Some(Cardinal::South),
+            "north" => Some(Cardinal::North),
+            _ => None,
+        }
+    }
+
+    fn to_property(&self) -> String {
+        match self {
+            Cardinal::East => "east",
+            Cardinal::West => "west",
+            Cardinal::South => "south",
+            Cardinal::North => "north",
+        }.to_owned()
+    }
 }
 
 impl<T: Into<BlockProperty>, It: IntoIterator<Item = T>> From<It> for BlockProperties {
@@ -196,6 +330,40 @@ impl BlockState {
         self.properties.get(key)
     }
 
+    /// Gets a `true`/`false` property, such as `waterlogged`.
+    /// Returns `None` if the property doesn't exist or isn't a bool.
+    pub fn get_bool<S: AsRef<str>>(&self, key: S) -> Option<bool> {
+        self.get_enum(key)
+    }
+
+    /// Gets an integer property, such as `age`.
+    /// Returns `None` if the property doesn't exist or isn't an integer.
+    pub fn get_int<S: AsRef<str>>(&self, key: S) -> Option<i32> {
+        self.get_enum(key)
+    }
+
+    /// Gets a property as any [PropertyValue], such as `get_enum::<CubeDirection>("facing")`.
+    /// Returns `None` if the property doesn't exist or its value can't be parsed.
+    pub fn get_enum<T: PropertyValue, S: AsRef<str>>(&self, key: S) -> Option<T> {
+        self.get_property(key).and_then(T::from_property)
+    }
+
+    /// Sets a property, adding it if it doesn't exist. Returns the old value.
+    pub fn set_property<S: AsRef<str>, V: PropertyValue>(&mut self, key: S, value: V) -> Option<String> {
+        self.properties.set(key, value.to_property())
+    }
+
+    /// Removes a property, returning its value.
+    pub fn remove_property<S: AsRef<str>>(&mut self, key: S) -> Option<String> {
+        self.properties.remove(key)
+    }
+
+    /// Returns the state with a property set, for building states or making modified copies.
+    pub fn with_property<S: AsRef<str>, V: PropertyValue>(mut self, key: S, value: V) -> Self {
+        self.set_property(key, value);
+        self
+    }
+
     pub fn to_nbt(self) -> Map {
         let mut props = Map::new();
         if let Some(properties) = self.properties.properties {
@@ -209,6 +377,39 @@ impl BlockState {
         ])
     }
 
+    /// Parses a block state in the `name[property=value,...]` form used by commands and
+    /// other tools, such as `minecraft:oak_stairs[facing=east,half=top]`.
+    /// Whitespace around names and values is ignored, and the brackets may be left out
+    /// for states without properties. The name is used as is, so a missing namespace isn't added.
+    /// Returns [McError::InvalidBlockState] if the text isn't in that form or a property is repeated.
+    pub fn parse<S: AsRef<str>>(text: S) -> McResult<Self> {
+        let text = text.as_ref();
+        let invalid = || McError::InvalidBlockState(format!("\"{text}\" is not in the name[property=value,...] form"));
+        let (name, properties) = match text.split_once('[') {
+            Some((name, properties)) => (name.trim(), Some(properties.trim_end().strip_suffix(']').ok_or_else(invalid)?)),
+            None => (text.trim(), None),
+        };
+        if name.is_empty() || name.contains([']', '=', ',']) {
+            return Err(invalid());
+        }
+        let Some(properties) = properties.filter(|properties| !properties.trim().is_empty()) else {
+            return Ok(Self::new(name, BlockProperties::none()));
+        };
+        let mut parsed = Vec::<(String, String)>::new();
+        for property in properties.split(',') {
+            let (key, value) = property.split_once('=').ok_or_else(invalid)?;
+            let (key, value) = (key.trim(), value.trim());
+            if key.is_empty() || value.is_empty() || value.contains(['[', ']', '=']) {
+                return Err(invalid());
+            }
+            if parsed.iter().any(|(existing, _)| existing == key) {
+                return Err(McError::InvalidBlockState(format!("\"{text}\" has more than one value for \"{key}\"")));
+            }
+            parsed.push((key.to_owned(), value.to_owned()));
+        }
+        Ok(Self::new(name, parsed))
+    }
+
     pub fn try_from_map(map: &Map) -> McResult<Self> {
         let Some(Tag::String(name)) = map.get("Name") else {
             return Err(crate::McError::NbtDecodeError);
@@ -239,6 +440,14 @@ impl<S: AsRef<str>> From<S> for BlockState {
     }
 }
 
+impl std::str::FromStr for BlockState {
+    type Err = McError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
 impl EncodeNbt for BlockState {
     fn encode_nbt(self) -> Tag {
         let map = self.to_nbt();
@@ -256,21 +465,56 @@ impl Display for BlockState {
     }
 }
 
+/// Formats the properties as `[name=value,...]`, the syntax that [BlockState::parse] reads.
 impl Display for BlockProperties {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "[")?;
         if let Some(props) = &self.properties {
-            let last = props.len() - 1;
             props.iter()
                 .enumerate()
                 .try_for_each(|(index, prop)| {
-                    write!(f, "{}={}", &prop.name, &prop.value)?;
-                    if index < last {
-                        write!(f, ", ")?;
+                    if index > 0 {
+                        write!(f, ",")?;
                     }
-                    Ok(())
+                    write!(f, "{}={}", &prop.name, &prop.value)
                 })?;
         }
         write!(f, "]")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let stairs = BlockState::parse("minecraft:oak_stairs[ half=top, facing=east ]").unwrap();
+        assert_eq!(stairs, blockstate!(oak_stairs[facing = east, half = top]));
+        assert_eq!(stairs.to_string(), "minecraft:oak_stairs[facing=east,half=top]");
+        assert_eq!(BlockState::parse(stairs.to_string()).unwrap(), stairs);
+        assert_eq!("minecraft:stone[]".parse::<BlockState>().unwrap().to_string(), "minecraft:stone");
+        for invalid in ["", "minecraft:stone[", "minecraft:stone[facing]", "minecraft:stone[a=1,a=2]", "[a=1]"] {
+            assert!(matches!(BlockState::parse(invalid), Err(McError::InvalidBlockState(_))), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn typed_property_test() {
+        let wheat = blockstate!(wheat[age = 3]);
+        assert_eq!(wheat.get_int("age"), Some(3));
+        assert_eq!(wheat.clone().with_property("age", wheat.get_int("age").unwrap() + 1).get_int("age"), Some(4));
+        let mut stairs = BlockState::from("minecraft:oak_stairs")
+            .with_property("waterlogged", false)
+            .with_property("facing", Cardinal::East);
+        assert_eq!(stairs.get_bool("waterlogged"), Some(false));
+        assert_eq!(stairs.get_enum::<CubeDirection, _>("facing"), Some(CubeDirection::East));
+        assert_eq!(stairs.set_property("facing", CubeDirection::North), Some("east".to_owned()));
+        assert_eq!(stairs.get_enum::<Cardinal, _>("facing"), Some(Cardinal::North));
+        assert_eq!(stairs.get_int("facing"), None);
+        assert_eq!(stairs.remove_property("waterlogged"), Some("false".to_owned()));
+        assert_eq!(stairs.to_string(), "minecraft:oak_stairs[facing=north]");
+        stairs.remove_property("facing");
+        assert_eq!(stairs, BlockState::from("minecraft:oak_stairs"));
+    }
+}
@@ -14,6 +14,11 @@ use crate::nbt::*;
 use crate::nbt::tag::*;
 use crate::nbt::tagtype::*;
 use super::blockregistry::BlockRegistry;
+use super::entity::Entity;
+use super::chunkversion::{is_legacy_chunk_nbt, is_mcregion_chunk_nbt, mcregion_to_anvil_chunk_nbt, upgrade_chunk_nbt, upgrade_chunk_nbt_to, ChunkLayout, DATA_VERSION_1_18, DATA_VERSION_NON_SPANNING};
+use super::io::region::RegionFormat;
+use super::chunkstatus::ChunkStatus;
+use super::structurestart::ChunkStructures;
 // use super::world::*;
 
 /// This macro is used to remove an entry from a Map (usually HashMap or IndexMap)
@@ -61,10 +66,6 @@ macro_rules! map_encoder {
 // 	data: Box<[i8; 2048]>
 // }
 
-// pub struct Biomes {
-// 	palette: Vec<String>
-// }
-
 // pub struct TileTick {
 // 	block_id: String,
 // 	precedence: i32,
@@ -124,6 +125,15 @@ pub struct Chunk {
 }
 
 impl Chunk {
+    /// The generation status of the chunk, or `None` if [Chunk::status](Chunk#structfield.status) is unknown.
+    pub fn status(&self) -> Option<ChunkStatus> {
+        ChunkStatus::parse(&self.status)
+    }
+
+    /// Returns true if the chunk has finished generating.
+    pub fn is_fully_generated(&self) -> bool {
+        self.status().is_some_and(ChunkStatus::is_full)
+    }
 
     #[inline(always)]
     fn section_index_and_local_coord(&self, coord: (i64, i64, i64)) -> (usize, (i64, i64, i64)) {
@@ -163,6 +173,133 @@ impl Chunk {
         self.sections.sections[section_index].set_id(x, y, z, id)
     }
 
+    /// Gets the biome at a block coordinate, or `None` if the section doesn't have biomes.
+    pub fn get_biome(&self, coord: (i64, i64, i64)) -> Option<&str> {
+        let (section_index, (x, y, z)) = self.section_index_and_local_coord(coord);
+        self.sections.sections[section_index].get_biome(x, y, z)
+    }
+
+    /// Sets the biome at a block coordinate, returning the old biome.
+    /// Biomes are stored in 4x4x4 cells, so this changes the biome of the whole cell.
+    pub fn set_biome<S: AsRef<str>>(&mut self, coord: (i64, i64, i64), biome: S) -> Option<String> {
+        let (section_index, (x, y, z)) = self.section_index_and_local_coord(coord);
+        self.sections.sections[section_index].set_biome(x, y, z, biome)
+    }
+
+    /// The range of block y coordinates covered by the chunk's sections.
+    pub fn height_range(&self) -> std::ops::Range<i64> {
+        let sections = &self.sections.sections;
+        let bottom = sections.iter().map(|section| section.y as i64).min().unwrap_or(0);
+        let top = sections.iter().map(|section| section.y as i64).max().unwrap_or(-1);
+        bottom * 16..(top + 1) * 16
+    }
+
+    /// Tests if a block coordinate is within this chunk's bounds, including its height.
+    pub fn contains_coord(&self, coord: (i64, i64, i64)) -> bool {
+        let sections = &self.sections.sections;
+        coord.0.div_euclid(16) == self.x as i64
+            && coord.2.div_euclid(16) == self.z as i64
+            && sections.iter().any(|section| section.y as i64 == coord.1.div_euclid(16))
+    }
+
+    /// Gets the block entity at a block coordinate.
+    pub fn get_block_entity(&self, coord: (i64, i64, i64)) -> Option<&BlockEntity> {
+        self.block_entities.iter().find(|entity| entity.coord() == coord)
+    }
+
+    pub fn get_block_entity_mut(&mut self, coord: (i64, i64, i64)) -> Option<&mut BlockEntity> {
+        self.block_entities.iter_mut().find(|entity| entity.coord() == coord)
+    }
+
+    /// Adds a block entity, replacing the block entity at the same position and returning it.
+    /// Returns [McError::OutOfRange] if the block entity's position isn't within this chunk.
+    pub fn set_block_entity(&mut self, entity: BlockEntity) -> McResult<Option<BlockEntity>> {
+        if !self.contains_coord(entity.coord()) {
+            return Err(McError::OutOfRange);
+        }
+        if let Some(old) = self.get_block_entity_mut(entity.coord()) {
+            Ok(Some(std::mem::replace(old, entity)))
+        } else {
+            self.block_entities.push(entity);
+            Ok(None)
+        }
+    }
+
+    /// Removes the block entity at a block coordinate, returning it.
+    pub fn remove_block_entity(&mut self, coord: (i64, i64, i64)) -> Option<BlockEntity> {
+        let index = self.block_entities.iter().position(|entity| entity.coord() == coord)?;
+        Some(self.block_entities.remove(index))
+    }
+
+    /// The entities stored in the chunk's `Entities` list.
+    /// Since 1.17, entities are stored in the `entities` region folder instead (see [EntityChunk](super::entity::EntityChunk)),
+    /// so this is usually empty.
+    pub fn get_entities(&self) -> Vec<Entity> {
+        match &self.entities {
+            Some(ListTag::Compound(entities)) => entities.iter().cloned().map(Entity::from_map).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Adds an entity to the chunk's `Entities` list.
+    pub fn add_entity(&mut self, entity: Entity) {
+        match &mut self.entities {
+            Some(ListTag::Compound(entities)) => entities.push(entity.into_map()),
+            _ => self.entities = Some(ListTag::Compound(vec![entity.into_map()])),
+        }
+    }
+
+    /// Removes the entities in the chunk's `Entities` list that `keep` returns false for.
+    /// Returns the number of entities that were removed.
+    pub fn retain_entities<F: FnMut(&Entity) -> bool>(&mut self, mut keep: F) -> usize {
+        let Some(ListTag::Compound(entities)) = &mut self.entities else {
+            return 0;
+        };
+        let count = entities.len();
+        // Entities are stored as maps, so they're moved in and out of the wrapper to test them.
+        *entities = std::mem::take(entities).into_iter()
+            .map(Entity::from_map)
+            .filter(|entity| keep(entity))
+            .map(Entity::into_map)
+            .collect();
+        count - entities.len()
+    }
+
+    /// The structure starts and references in the chunk's `structures` compound.
+    pub fn structures(&self) -> ChunkStructures {
+        ChunkStructures::from_map(&self.structures)
+    }
+
+    /// The pending block ticks in the chunk's `block_ticks` list.
+    /// Entries that can't be decoded are skipped.
+    pub fn block_ticks(&self) -> Vec<ScheduledTick> {
+        decode_ticks(&self.block_ticks)
+    }
+
+    /// The pending fluid ticks in the chunk's `fluid_ticks` list.
+    /// Entries that can't be decoded are skipped.
+    pub fn fluid_ticks(&self) -> Vec<ScheduledTick> {
+        decode_ticks(&self.fluid_ticks)
+    }
+
+    /// Schedules a block update, such as for redstone components that were pasted into the chunk.
+    pub fn add_block_tick(&mut self, tick: ScheduledTick) {
+        push_tick(&mut self.block_ticks, tick);
+    }
+
+    /// Schedules a fluid update, such as for water that was placed without flowing.
+    pub fn add_fluid_tick(&mut self, tick: ScheduledTick) {
+        push_tick(&mut self.fluid_ticks, tick);
+    }
+
+    /// Removes every pending block and fluid tick. Returns the number of ticks that were removed.
+    pub fn clear_ticks(&mut self) -> usize {
+        let count = self.block_ticks.len() + self.fluid_ticks.len();
+        self.block_ticks = ListTag::Empty;
+        self.fluid_ticks = ListTag::Empty;
+        count
+    }
+
     pub fn to_nbt(&self, block_registry: &BlockRegistry) -> Tag {
         Tag::Compound(encode_chunk(block_registry, self))
     }
@@ -196,22 +333,32 @@ impl Chunk {
             HeightmapFlag::WorldSurface => self.heightmaps.world_surface.set((x, z), height),
         }
     }
+
+    /// Upgrades the chunk to a newer `data_version`.
+    /// Chunks in memory always use the 1.18+ layout, since older chunk NBT is converted when it's
+    /// decoded (see [upgrade_chunk_nbt_to]). If the chunk claims to be older than 1.18, the sections
+    /// without biomes are filled with plains, since 1.18 requires every section to have biomes.
+    ///
+    /// Returns [McError::UnsupportedDataVersion] if `data_version` is older than 1.18 or older than the chunk.
+    pub fn upgrade_to(&mut self, data_version: i32) -> McResult<()> {
+        if data_version < DATA_VERSION_1_18 || data_version < self.data_version {
+            return Err(McError::UnsupportedDataVersion(data_version));
+        }
+        if self.data_version < DATA_VERSION_1_18 {
+            for section in self.sections.sections.iter_mut().filter(|section| section.biomes.is_none()) {
+                section.biomes = Some(Biomes::filled("minecraft:plains"));
+            }
+        }
+        self.data_version = data_version;
+        Ok(())
+    }
 }
 
 impl EncodeNbt for Vec<BlockEntity> {
     fn encode_nbt(self) -> Tag {
-        let entities = self.into_iter().map(|entity| {
-            let mut map = Map::new();
-            map_encoder!(map;
-                "id" = entity.id;
-                "keepPacked" = entity.keep_packed;
-                "x" = entity.x;
-                "y" = entity.y;
-                "z" = entity.z;
-            );
-            map.extend(entity.data);
-            map
-        }).collect::<Vec<Map>>();
+        let entities = self.into_iter()
+            .map(BlockEntity::to_map)
+            .collect::<Vec<Map>>();
         Tag::List(ListTag::Compound(entities))
     }
 }
@@ -224,16 +371,9 @@ impl DecodeNbt for Vec<BlockEntity> {
         let ListTag::Compound(entities) = list else {
             return Ok(Vec::new());
         };
-        entities.into_iter().map(|mut entity| {
-            Ok(BlockEntity {
-                id: map_decoder!(entity; "id" -> String),
-                keep_packed: map_decoder!(entity; "keepPacked" -> i8),
-                x: map_decoder!(entity; "x" -> i32),
-                y: map_decoder!(entity; "y" -> i32),
-                z: map_decoder!(entity; "z" -> i32),
-                data: entity,
-            })
-        }).collect::<Result<Vec<BlockEntity>, McError>>()
+        entities.into_iter()
+            .map(BlockEntity::try_from_map)
+            .collect::<Result<Vec<BlockEntity>, McError>>()
     }
 }
 
@@ -271,6 +411,13 @@ impl Into<Vec<u8>> for Lighting {
 }
 
 impl Lighting {
+    /// Creates a section's worth of light levels (4096 nibbles) that are all zero.
+    pub fn new() -> Self {
+        Self {
+            levels: vec![0u8; 2048],
+        }
+    }
+
     pub fn get(&self, x: i64, y: i64, z: i64) -> u8 {
         let index = chunk_yzx_index(x, y, z);
         let half_index = index.div_euclid(2);
@@ -295,6 +442,12 @@ impl Lighting {
     }
 }
 
+impl Default for Lighting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DecodeNbt for Lighting {
     fn decode_nbt(nbt: Tag) -> McResult<Self> {
         if let Tag::ByteArray(light_data) = nbt {
@@ -311,11 +464,118 @@ impl EncodeNbt for Lighting {
     }
 }
 
+/// The biomes of a chunk section.
+/// Biomes are stored in 4x4x4 cells, so each biome covers 64 blocks.
+#[derive(Clone)]
+pub struct Biomes {
+    palette: Vec<String>,
+    /// Palette indices of the 64 cells in YZX order.
+    cells: Box<[u16]>,
+}
+
+#[inline(always)]
+fn biome_yzx_index(x: i64, y: i64, z: i64) -> usize {
+    let cell_x = (x & 0xf) >> 2;
+    let cell_y = (y & 0xf) >> 2;
+    let cell_z = (z & 0xf) >> 2;
+    ((cell_y<<4) | (cell_z<<2) | cell_x) as usize
+}
+
+impl Biomes {
+    /// Creates biomes where every cell is `biome`.
+    pub fn filled<S: AsRef<str>>(biome: S) -> Self {
+        Self {
+            palette: vec![biome.as_ref().to_owned()],
+            cells: vec![0u16; 64].into_boxed_slice(),
+        }
+    }
+
+    /// Gets the biome at a local block coordinate.
+    pub fn get(&self, x: i64, y: i64, z: i64) -> &str {
+        &self.palette[self.cells[biome_yzx_index(x, y, z)] as usize]
+    }
+
+    /// Sets the biome of the cell containing a local block coordinate, returning the old biome.
+    pub fn set<S: AsRef<str>>(&mut self, x: i64, y: i64, z: i64, biome: S) -> String {
+        let biome = biome.as_ref();
+        let palette_index = if let Some(index) = self.palette.iter().position(|entry| entry == biome) {
+            index
+        } else {
+            self.palette.push(biome.to_owned());
+            self.palette.len() - 1
+        };
+        let index = biome_yzx_index(x, y, z);
+        let old = self.cells[index];
+        self.cells[index] = palette_index as u16;
+        self.palette[old as usize].clone()
+    }
+}
+
+impl DecodeNbt for Biomes {
+    fn decode_nbt(nbt: Tag) -> McResult<Self> {
+        let Tag::Compound(mut map) = nbt else {
+            return Err(McError::NbtDecodeError);
+        };
+        let ListTag::String(palette) = map_decoder!(map; "palette" -> ListTag) else {
+            return Err(McError::NbtDecodeError);
+        };
+        if palette.is_empty() {
+            return Err(McError::NbtDecodeError);
+        }
+        // Unlike block states, biomes don't have a minimum bitsize, and a palette
+        // with a single biome has no data.
+        let bitsize = (palette.len() - 1).bit_length();
+        let cells = match map_decoder!(map; "data" -> Option<LongArray>) {
+            Some(data) if bitsize > 0 => {
+                let vpl = (64 / bitsize) as usize;
+                let mask = (1u64 << bitsize) - 1;
+                (0..64).map(|index| {
+                    let slot = data.get(index / vpl).copied().unwrap_or_default() as u64;
+                    let value = (slot >> ((index % vpl) as u32 * bitsize)) & mask;
+                    // Out of range indices are treated as the first biome.
+                    if (value as usize) < palette.len() { value as u16 } else { 0 }
+                }).collect::<Box<[u16]>>()
+            }
+            _ => vec![0u16; 64].into_boxed_slice(),
+        };
+        Ok(Self {
+            palette,
+            cells,
+        })
+    }
+}
+
+impl EncodeNbt for Biomes {
+    fn encode_nbt(self) -> Tag {
+        // Remove biomes that are no longer used from the palette.
+        let mut remap = HashMap::<u16, u16>::new();
+        let mut palette = Vec::<String>::new();
+        let cells = self.cells.iter().map(|&cell| {
+            *remap.entry(cell).or_insert_with(|| {
+                palette.push(self.palette[cell as usize].clone());
+                (palette.len() - 1) as u16
+            })
+        }).collect::<Vec<u16>>();
+        let bitsize = (palette.len() - 1).bit_length();
+        let mut map = Map::new();
+        // A bitsize of zero means that there is only one biome, so no data is written.
+        if let Some(vpl) = 64usize.checked_div(bitsize as usize) {
+            let mut packed = vec![0i64; 64usize.div_ceil(vpl)];
+            cells.into_iter().enumerate().for_each(|(index, cell)| {
+                packed[index / vpl] |= ((cell as u64) << ((index % vpl) as u32 * bitsize)) as i64;
+            });
+            map_encoder!(map; "data" = packed);
+        }
+        map.insert("palette".to_owned(), Tag::List(ListTag::String(palette)));
+        Tag::Compound(map)
+    }
+}
+
 #[derive(Clone)]
 pub struct ChunkSection {
     pub y: i8,
     pub blocks: Option<Box<[u32]>>,
-    pub biomes: Option<Map>,
+    pub biomes: Option<Biomes>,
     pub skylight: Option<Lighting>,
     pub blocklight: Option<Lighting>,
 }
@@ -354,6 +614,21 @@ impl ChunkSection {
         }
     }
 
+    pub fn get_biome(&self, local_x: i64, local_y: i64, local_z: i64) -> Option<&str> {
+        self.biomes.as_ref().map(|biomes| biomes.get(local_x, local_y, local_z))
+    }
+
+    /// Sets the biome, returning the old biome. If the section doesn't have biomes,
+    /// the whole section is filled with `biome` and `None` is returned.
+    pub fn set_biome<S: AsRef<str>>(&mut self, local_x: i64, local_y: i64, local_z: i64, biome: S) -> Option<String> {
+        if let Some(biomes) = &mut self.biomes {
+            Some(biomes.set(local_x, local_y, local_z, biome))
+        } else {
+            self.biomes = Some(Biomes::filled(biome));
+            None
+        }
+    }
+
     pub fn get_id(&self, local_x: i64, local_y: i64, local_z: i64) -> Option<u32> {
         if let Some(blocks) = &self.blocks {
             let index = chunk_yzx_index(local_x, local_y, local_z);
@@ -392,6 +667,129 @@ pub struct BlockEntity {
     pub data: Map,
 }
 
+impl BlockEntity {
+    /// Creates a block entity at a block coordinate.
+    /// `data` holds the rest of the block entity's tags, such as `Items` for a chest.
+    pub fn new<S: AsRef<str>>(id: S, coord: (i64, i64, i64), data: Map) -> Self {
+        Self {
+            id: id.as_ref().to_owned(),
+            keep_packed: 0,
+            x: coord.0 as i32,
+            y: coord.1 as i32,
+            z: coord.2 as i32,
+            data,
+        }
+    }
+
+    /// The block coordinate of the block entity.
+    pub fn coord(&self) -> (i64, i64, i64) {
+        (self.x as i64, self.y as i64, self.z as i64)
+    }
+
+    /// Encodes the block entity as the compound that is stored in a chunk.
+    pub fn to_map(self) -> Map {
+        let mut map = Map::new();
+        map_encoder!(map;
+            "id" = self.id;
+            "keepPacked" = self.keep_packed;
+            "x" = self.x;
+            "y" = self.y;
+            "z" = self.z;
+        );
+        map.extend(self.data);
+        map
+    }
+
+    /// Decodes a block entity from the compound that is stored in a chunk.
+    pub fn try_from_map(mut map: Map) -> McResult<Self> {
+        Ok(BlockEntity {
+            id: map_decoder!(map; "id" -> String),
+            keep_packed: map_decoder!(map; "keepPacked" -> i8),
+            x: map_decoder!(map; "x" -> i32),
+            y: map_decoder!(map; "y" -> i32),
+            z: map_decoder!(map; "z" -> i32),
+            data: map,
+        })
+    }
+}
+
+/// A pending block or fluid update in a chunk's `block_ticks` or `fluid_ticks` list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledTick {
+    /// The id of the block or fluid that is ticked, such as `minecraft:repeater` or `minecraft:water`.
+    /// The tick is ignored if the block at the coordinate has changed.
+    pub id: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    /// The number of game ticks until the update happens.
+    pub delay: i32,
+    /// The order of ticks that happen on the same game tick, where lower values are first.
+    pub priority: i32,
+}
+
+impl ScheduledTick {
+    /// Creates a tick at a block coordinate with the default priority of 0.
+    pub fn new<S: AsRef<str>>(id: S, coord: (i64, i64, i64), delay: i32) -> Self {
+        Self {
+            id: id.as_ref().to_owned(),
+            x: coord.0 as i32,
+            y: coord.1 as i32,
+            z: coord.2 as i32,
+            delay,
+            priority: 0,
+        }
+    }
+
+    /// The block coordinate of the tick.
+    pub fn coord(&self) -> (i64, i64, i64) {
+        (self.x as i64, self.y as i64, self.z as i64)
+    }
+
+    /// Encodes the tick as the compound that is stored in a chunk.
+    pub fn to_map(self) -> Map {
+        let mut map = Map::new();
+        map_encoder!(map;
+            "i" = self.id;
+            "x" = self.x;
+            "y" = self.y;
+            "z" = self.z;
+            "t" = self.delay;
+            "p" = self.priority;
+        );
+        map
+    }
+
+    /// Decodes a tick from the compound that is stored in a chunk.
+    pub fn try_from_map(mut map: Map) -> McResult<Self> {
+        Ok(ScheduledTick {
+            id: map_decoder!(map; "i" -> String),
+            x: map_decoder!(map; "x" -> i32),
+            y: map_decoder!(map; "y" -> i32),
+            z: map_decoder!(map; "z" -> i32),
+            delay: map_decoder!(map; "t" -> i32),
+            priority: map_decoder!(map; "p" -> i32),
+        })
+    }
+}
+
+fn decode_ticks(ticks: &ListTag) -> Vec<ScheduledTick> {
+    match ticks {
+        ListTag::Compound(ticks) => ticks.iter()
+            .cloned()
+            .filter_map(|tick| ScheduledTick::try_from_map(tick).ok())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn push_tick(ticks: &mut ListTag, tick: ScheduledTick) {
+    match ticks {
+        ListTag::Compound(ticks) => ticks.push(tick.to_map()),
+        _ => *ticks = ListTag::Compound(vec![tick.to_map()]),
+    }
+}
+
 #[derive(Clone)]
 pub struct Heightmap {
     pub map: Vec<i64>
@@ -590,7 +988,7 @@ pub fn decode_palette(palette: ListTag) -> Result<Vec<BlockState>, McError> {
 pub fn decode_section(block_registry: &mut BlockRegistry, mut section: Map) -> Result<ChunkSection, McError> {
     let y = map_decoder!(section; "Y" -> Byte);
     // The following three may or may not exist.
-    let biomes = map_decoder!(section; "biomes" -> Option<Map>);
+    let biomes = map_decoder!(section; "biomes" -> Option<Biomes>);
     let blocklight = map_decoder!(section; "BlockLight" -> Option<Lighting>);
     let skylight = map_decoder!(section; "SkyLight" -> Option<Lighting>);
 
@@ -603,16 +1001,20 @@ pub fn decode_section(block_registry: &mut BlockRegistry, mut section: Map) -> R
         // registry, retrieving the ID. I think the appropriate way to do this would be
         // to do an iterator map to the block_registry IDs.
         let palette = decode_palette(map_decoder!(block_states; "palette" -> ListTag))?;
+        let single_air = palette.len() == 1 && palette[0].name() == "minecraft:air";
         // Register blocks.
         let palette = palette.iter().map(|state| {
             block_registry.register(state)
         }).collect::<Vec<u32>>();
-        map_decoder!(block_states; "data" -> Option<LongArray>).map(|blocks| {
-            (0..4096).into_iter().map(|full_index| {
+        match map_decoder!(block_states; "data" -> Option<LongArray>) {
+            Some(blocks) => Some((0..4096).into_iter().map(|full_index| {
                 let index = extract_palette_index(full_index, palette.len(), &blocks);
                 palette[index]
-            }).collect::<Box<[u32]>>()
-        })
+            }).collect::<Box<[u32]>>()),
+            // Sections made of a single block state have no data. Sections of air are left empty.
+            None if single_air => None,
+            None => palette.first().map(|&id| vec![id; 4096].into_boxed_slice()),
+        }
     } else {
         None
     };
@@ -648,8 +1050,9 @@ pub fn decode_chunk(block_registry: &mut BlockRegistry, nbt: Tag) -> McResult<Ch
         last_update: map_decoder!(map; "LastUpdate" -> i64),
         block_entities: map_decoder!(map; "block_entities" -> Vec<BlockEntity>),
         heightmaps: map_decoder!(map; "Heightmaps" -> Heightmaps),
-        fluid_ticks: map_decoder!(map; "fluid_ticks" -> ListTag),
-        block_ticks: map_decoder!(map; "block_ticks" -> ListTag),
+        // Ticks are optional so that chunks written by other tools without them can still be decoded.
+        fluid_ticks: map_decoder!(map; "fluid_ticks" -> Option<ListTag>).unwrap_or(ListTag::Empty),
+        block_ticks: map_decoder!(map; "block_ticks" -> Option<ListTag>).unwrap_or(ListTag::Empty),
         post_processing: map_decoder!(map; "PostProcessing" -> ListTag),
         structures: map_decoder!(map; "structures" -> Map),
         inhabited_time: map_decoder!(map; "InhabitedTime" -> i64),
@@ -661,6 +1064,30 @@ pub fn decode_chunk(block_registry: &mut BlockRegistry, nbt: Tag) -> McResult<Ch
     })
 }
 
+/// Decodes chunk NBT from any supported Minecraft version, choosing how to decode it
+/// by the chunk's `DataVersion` (see [ChunkLayout]).
+/// Chunks from 1.13 to 1.17 are upgraded with [upgrade_chunk_nbt] before being decoded,
+/// and chunks from before 1.13 are upgraded to 1.18 with [upgrade_chunk_nbt_to].
+pub fn decode_versioned_chunk(block_registry: &mut BlockRegistry, nbt: Tag) -> McResult<Chunk> {
+    match ChunkLayout::of_chunk_nbt(&nbt)? {
+        ChunkLayout::Flattened => decode_chunk(block_registry, nbt),
+        ChunkLayout::Level | ChunkLayout::SpanningLevel => decode_chunk(block_registry, upgrade_chunk_nbt(nbt)?),
+        ChunkLayout::Numeric => decode_chunk(block_registry, upgrade_chunk_nbt_to(nbt, DATA_VERSION_1_18)?),
+    }
+}
+
+/// Decodes chunk NBT that was read from a region file of the given [RegionFormat]
+/// with [decode_versioned_chunk].
+/// McRegion chunks are converted to the Anvil layout with [mcregion_to_anvil_chunk_nbt] first,
+/// then their numeric block ids are upgraded like those of any chunk from before 1.13.
+pub fn decode_chunk_for_format(block_registry: &mut BlockRegistry, nbt: Tag, format: RegionFormat) -> McResult<Chunk> {
+    if format == RegionFormat::McRegion || is_mcregion_chunk_nbt(&nbt) {
+        decode_versioned_chunk(block_registry, mcregion_to_anvil_chunk_nbt(nbt)?)
+    } else {
+        decode_versioned_chunk(block_registry, nbt)
+    }
+}
+
 fn encode_block_states(block_registry: &BlockRegistry, blocks: &Option<Box<[u32]>>) -> Map {
     if let Some(blocks) = blocks {
         // Collect unique block-ids
@@ -795,9 +1222,448 @@ pub fn encode_chunk(block_registry: &BlockRegistry, chunk: &Chunk) -> Map {
     map
 }
 
+/// How serious a [ChunkFinding] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FindingSeverity {
+    /// The chunk can be loaded, but something is unusual (such as a duplicate palette entry).
+    Warning,
+    /// Minecraft would fail to load the chunk, or would lose data when loading it.
+    Error,
+}
+
+/// A problem found by [validate].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkFinding {
+    pub severity: FindingSeverity,
+    /// The path to the tag with the problem, such as `sections[3].block_states.data`.
+    pub path: String,
+    pub message: String,
+}
+
+impl ChunkFinding {
+    pub fn is_error(&self) -> bool {
+        self.severity == FindingSeverity::Error
+    }
+}
+
+impl std::fmt::Display for ChunkFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            FindingSeverity::Warning => "warning",
+            FindingSeverity::Error => "error",
+        };
+        write!(f, "{severity}: {}: {}", self.path, self.message)
+    }
+}
+
+/// Collects findings while walking a chunk's NBT.
+struct Validator {
+    findings: Vec<ChunkFinding>,
+}
+
+impl Validator {
+    fn push<P: AsRef<str>, M: Into<String>>(&mut self, severity: FindingSeverity, path: P, message: M) {
+        self.findings.push(ChunkFinding {
+            severity,
+            path: path.as_ref().to_owned(),
+            message: message.into(),
+        });
+    }
+
+    fn error<P: AsRef<str>, M: Into<String>>(&mut self, path: P, message: M) {
+        self.push(FindingSeverity::Error, path, message);
+    }
+
+    fn warning<P: AsRef<str>, M: Into<String>>(&mut self, path: P, message: M) {
+        self.push(FindingSeverity::Warning, path, message);
+    }
+
+    /// Gets a tag that must exist, reporting an error if it doesn't.
+    fn required<'a>(&mut self, map: &'a Map, path: &str, name: &str) -> Option<&'a Tag> {
+        let tag = map.get(name);
+        if tag.is_none() {
+            self.error(join_path(path, name), "missing required tag");
+        }
+        tag
+    }
+
+    /// Gets an Int that must exist.
+    fn required_int(&mut self, map: &Map, path: &str, name: &str) -> Option<i32> {
+        match self.required(map, path, name)? {
+            Tag::Int(value) => Some(*value),
+            tag => {
+                self.error(join_path(path, name), format!("expected Int, found {:?}", tag.id()));
+                None
+            }
+        }
+    }
+
+    /// Checks a packed array of palette indices.
+    /// `spanning` is true if values may span two longs (before 1.16).
+    fn packed_array(&mut self, path: &str, data: &[i64], count: usize, bits: u32, palette_len: usize, spanning: bool) {
+        let expected = if spanning {
+            (count * bits as usize).div_ceil(64)
+        } else {
+            count.div_ceil(64 / bits as usize)
+        };
+        if data.len() != expected {
+            self.error(path, format!("expected {expected} longs for {bits} bits per entry, found {}", data.len()));
+            return;
+        }
+        let mask = (1u64 << bits) - 1;
+        let out_of_range = (0..count).filter(|&index| {
+            let value = if spanning {
+                let start = index * bits as usize;
+                let (long, offset) = (start / 64, start % 64);
+                let mut value = (data[long] as u64) >> offset;
+                if offset + bits as usize > 64 {
+                    value |= (data[long + 1] as u64) << (64 - offset);
+                }
+                value & mask
+            } else {
+                let per_long = 64 / bits as usize;
+                ((data[index / per_long] as u64) >> ((index % per_long) as u32 * bits)) & mask
+            };
+            value as usize >= palette_len
+        }).count();
+        if out_of_range > 0 {
+            self.error(path, format!("{out_of_range} entries are outside of the palette (length {palette_len})"));
+        }
+    }
+
+    fn block_palette(&mut self, path: &str, palette: &Tag) -> Option<usize> {
+        let states = match palette {
+            Tag::List(ListTag::Compound(states)) if !states.is_empty() => states,
+            Tag::List(ListTag::Compound(_)) | Tag::List(ListTag::Empty) => {
+                self.error(path, "palette is empty");
+                return None;
+            }
+            _ => {
+                self.error(path, "expected a List of Compounds");
+                return None;
+            }
+        };
+        let mut seen = Vec::with_capacity(states.len());
+        for (index, state) in states.iter().enumerate() {
+            let state_path = format!("{path}[{index}]");
+            match BlockState::try_from_map(state) {
+                Ok(state) => {
+                    if seen.contains(&state) {
+                        self.warning(&state_path, format!("duplicate palette entry {}", state.name()));
+                    }
+                    seen.push(state);
+                }
+                Err(_) => self.error(&state_path, "expected a Name String and a Properties Compound of Strings"),
+            }
+        }
+        Some(states.len())
+    }
+
+    fn light(&mut self, path: &str, section: &Map, name: &str) {
+        match section.get(name) {
+            Some(Tag::ByteArray(light)) if light.len() == 2048 => (),
+            Some(Tag::ByteArray(light)) => self.error(join_path(path, name), format!("expected 2048 bytes, found {}", light.len())),
+            Some(tag) => self.error(join_path(path, name), format!("expected ByteArray, found {:?}", tag.id())),
+            None => (),
+        }
+    }
+
+    fn section(&mut self, path: &str, section: &Map, legacy: bool, data_version: i32) {
+        match section.get("Y") {
+            Some(Tag::Byte(_)) => (),
+            Some(tag) => self.error(join_path(path, "Y"), format!("expected Byte, found {:?}", tag.id())),
+            None => self.error(join_path(path, "Y"), "missing required tag"),
+        }
+        self.light(path, section, "BlockLight");
+        self.light(path, section, "SkyLight");
+        if legacy {
+            let palette_path = join_path(path, "Palette");
+            let palette_len = section.get("Palette").and_then(|palette| self.block_palette(&palette_path, palette));
+            match (palette_len, section.get("BlockStates")) {
+                (Some(len), Some(Tag::LongArray(data))) => {
+                    let bits = (len - 1).bit_length().max(4);
+                    self.packed_array(&join_path(path, "BlockStates"), data, 4096, bits, len, data_version < DATA_VERSION_NON_SPANNING);
+                }
+                (Some(_), Some(tag)) => self.error(join_path(path, "BlockStates"), format!("expected LongArray, found {:?}", tag.id())),
+                (Some(_), None) => self.error(join_path(path, "BlockStates"), "missing required tag"),
+                (None, Some(_)) if !section.contains_key("Palette") => self.error(palette_path, "missing required tag"),
+                _ => (),
+            }
+            return;
+        }
+        match section.get("block_states") {
+            Some(Tag::Compound(block_states)) => {
+                let block_path = join_path(path, "block_states");
+                let palette_len = self.required(block_states, &block_path, "palette")
+                    .and_then(|palette| self.block_palette(&join_path(&block_path, "palette"), palette));
+                let data_path = join_path(&block_path, "data");
+                match (palette_len, block_states.get("data")) {
+                    (Some(len), Some(Tag::LongArray(data))) => {
+                        let bits = (len - 1).bit_length().max(4);
+                        self.packed_array(&data_path, data, 4096, bits, len, false);
+                    }
+                    (Some(len), None) if len > 1 => self.error(data_path, "missing required tag"),
+                    (_, Some(tag)) if !matches!(tag, Tag::LongArray(_)) => {
+                        self.error(data_path, format!("expected LongArray, found {:?}", tag.id()));
+                    }
+                    _ => (),
+                }
+            }
+            Some(tag) => self.error(join_path(path, "block_states"), format!("expected Compound, found {:?}", tag.id())),
+            None => (),
+        }
+        match section.get("biomes") {
+            Some(Tag::Compound(biomes)) => {
+                let biome_path = join_path(path, "biomes");
+                let palette_len = match self.required(biomes, &biome_path, "palette") {
+                    Some(Tag::List(ListTag::String(palette))) if !palette.is_empty() => Some(palette.len()),
+                    Some(_) => {
+                        self.error(join_path(&biome_path, "palette"), "expected a non-empty List of Strings");
+                        None
+                    }
+                    None => None,
+                };
+                let data_path = join_path(&biome_path, "data");
+                match (palette_len, biomes.get("data")) {
+                    (Some(len), Some(Tag::LongArray(data))) if len > 1 => {
+                        self.packed_array(&data_path, data, 64, (len - 1).bit_length(), len, false);
+                    }
+                    (Some(len), None) if len > 1 => self.error(data_path, "missing required tag"),
+                    (_, Some(tag)) if !matches!(tag, Tag::LongArray(_)) => {
+                        self.error(data_path, format!("expected LongArray, found {:?}", tag.id()));
+                    }
+                    _ => (),
+                }
+            }
+            Some(tag) => self.error(join_path(path, "biomes"), format!("expected Compound, found {:?}", tag.id())),
+            None => (),
+        }
+    }
+
+    fn block_entities(&mut self, path: &str, block_entities: &Tag, position: Option<(i32, i32)>) {
+        let entities = match block_entities {
+            Tag::List(ListTag::Compound(entities)) => entities,
+            Tag::List(ListTag::Empty) => return,
+            tag => {
+                self.error(path, format!("expected a List of Compounds, found {:?}", tag.id()));
+                return;
+            }
+        };
+        for (index, entity) in entities.iter().enumerate() {
+            let entity_path = format!("{path}[{index}]");
+            match self.required(entity, &entity_path, "id") {
+                Some(Tag::String(_)) | None => (),
+                Some(tag) => self.error(join_path(&entity_path, "id"), format!("expected String, found {:?}", tag.id())),
+            }
+            let x = self.required_int(entity, &entity_path, "x");
+            let y = self.required_int(entity, &entity_path, "y");
+            let z = self.required_int(entity, &entity_path, "z");
+            if let (Some(x), Some(_), Some(z), Some((chunk_x, chunk_z))) = (x, y, z, position) {
+                if x.div_euclid(16) != chunk_x || z.div_euclid(16) != chunk_z {
+                    self.warning(&entity_path, format!("block entity at ({x}, {z}) is outside of the chunk"));
+                }
+            }
+        }
+    }
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+/// Checks the structure of chunk NBT (as it's stored in a region file) without decoding it.
+/// Both the 1.18+ layout and the pre-1.18 `Level` layout are checked, and the layout must match
+/// the chunk's `DataVersion`. The checks are:
+/// - `DataVersion`, `xPos`, and `zPos` exist and are Ints.
+/// - `sections` is a list of compounds with unique `Y` values.
+/// - Block state and biome palettes aren't empty, and the palette entries are well formed.
+/// - Packed arrays have the length required by their palette size, and every index is within the palette.
+/// - Light arrays are 2048 bytes long.
+/// - Block entities have an `id` and a position within the chunk.
+///
+/// Returns an empty list if nothing was found.
+pub fn validate(nbt: &Tag) -> Vec<ChunkFinding> {
+    let mut validator = Validator {
+        findings: Vec::new(),
+    };
+    let Tag::Compound(root) = nbt else {
+        validator.error("", "chunk root isn't a Compound");
+        return validator.findings;
+    };
+    let data_version = validator.required_int(root, "", "DataVersion");
+    let legacy = is_legacy_chunk_nbt(nbt);
+    let (map, path) = match root.get("Level") {
+        Some(Tag::Compound(level)) => (level, "Level"),
+        _ => (root, ""),
+    };
+    match (data_version, legacy) {
+        (Some(version), true) if version >= DATA_VERSION_1_18 => {
+            validator.error("Level", format!("chunks with DataVersion {version} don't use the Level compound"));
+        }
+        (Some(version), false) if version < DATA_VERSION_1_18 => {
+            validator.error("", format!("chunks with DataVersion {version} must use the Level compound"));
+        }
+        _ => (),
+    }
+    let x = validator.required_int(map, path, "xPos");
+    let z = validator.required_int(map, path, "zPos");
+    if !legacy && !map.contains_key("yPos") {
+        validator.warning("yPos", "missing, so the lowest section is assumed to be -4");
+    }
+    if !map.contains_key("Status") {
+        validator.warning(join_path(path, "Status"), "missing, so the chunk will be treated as empty");
+    }
+    let sections_name = if legacy { "Sections" } else { "sections" };
+    let sections_path = join_path(path, sections_name);
+    match validator.required(map, path, sections_name) {
+        Some(Tag::List(ListTag::Compound(sections))) => {
+            let mut ys = Vec::with_capacity(sections.len());
+            for (index, section) in sections.iter().enumerate() {
+                let section_path = format!("{sections_path}[{index}]");
+                if let Some(Tag::Byte(y)) = section.get("Y") {
+                    if ys.contains(y) {
+                        validator.error(join_path(&section_path, "Y"), format!("duplicate section Y {y}"));
+                    }
+                    ys.push(*y);
+                }
+                validator.section(&section_path, section, legacy, data_version.unwrap_or(DATA_VERSION_1_18));
+            }
+        }
+        Some(Tag::List(ListTag::Empty)) | None => (),
+        Some(tag) => validator.error(sections_path, format!("expected a List of Compounds, found {:?}", tag.id())),
+    }
+    let block_entities_name = if legacy { "TileEntities" } else { "block_entities" };
+    if let Some(block_entities) = map.get(block_entities_name) {
+        validator.block_entities(&join_path(path, block_entities_name), block_entities, x.zip(z));
+    }
+    validator.findings
+}
+
 /*
 TODO: 	Make it so that chunks can be loaded directly from memory.
         This would involve more complicated programming, but it would
         give faster load times. I also need to make it so that there
         is a World block registry to register blocks to.
-*/
\ No newline at end of file
+*/
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Creates a chunk with two empty sections (`y` from 0 to 31) for tests.
+    pub(crate) fn empty_chunk(x: i32, z: i32) -> Chunk {
+        let heightmap = || Heightmap::from(vec![0i64; 37]);
+        Chunk {
+            data_version: 3465,
+            x,
+            y: 0,
+            z,
+            last_update: 0,
+            status: "minecraft:full".to_owned(),
+            sections: ChunkSections {
+                sections: (0..2).map(|y| ChunkSection {
+                    y,
+                    blocks: None,
+                    biomes: None,
+                    skylight: None,
+                    blocklight: None,
+                }).collect(),
+            },
+            block_entities: Vec::new(),
+            heightmaps: Heightmaps {
+                motion_blocking: heightmap(),
+                motion_blocking_no_leaves: heightmap(),
+                ocean_floor: heightmap(),
+                ocean_floor_wg: None,
+                world_surface: heightmap(),
+                world_surface_wg: None,
+            },
+            fluid_ticks: ListTag::Empty,
+            block_ticks: ListTag::Empty,
+            inhabited_time: 0,
+            post_processing: ListTag::Empty,
+            structures: Map::new(),
+            carving_masks: None,
+            lights: None,
+            entities: None,
+            other: Map::new(),
+        }
+    }
+
+    #[test]
+    fn biomes_test() {
+        let mut biomes = Biomes::filled("minecraft:plains");
+        assert_eq!(biomes.set(5, 14, 3, "minecraft:desert"), "minecraft:plains");
+        biomes.set(0, 0, 0, "minecraft:forest");
+        biomes.set(0, 0, 0, "minecraft:plains");
+        let Tag::Compound(map) = biomes.encode_nbt() else {
+            panic!("Biomes should encode to a Compound.");
+        };
+        // The forest is no longer used, so it should be removed from the palette.
+        let Some(Tag::List(ListTag::String(palette))) = map.get("palette") else {
+            panic!("Palette not found.");
+        };
+        assert_eq!(palette.len(), 2);
+        let biomes = Biomes::decode_nbt(Tag::Compound(map)).unwrap();
+        assert_eq!(biomes.get(4, 12, 0), "minecraft:desert");
+        assert_eq!(biomes.get(7, 15, 3), "minecraft:desert");
+        assert_eq!(biomes.get(8, 12, 0), "minecraft:plains");
+        assert_eq!(biomes.get(0, 0, 0), "minecraft:plains");
+    }
+
+    #[test]
+    fn block_entity_test() {
+        let entity = BlockEntity::new("minecraft:chest", (-3, 70, 20), Map::from([
+            ("Items".to_owned(), Tag::List(ListTag::Empty)),
+        ]));
+        let map = entity.clone().to_map();
+        assert!(matches!(map.get("id"), Some(Tag::String(id)) if id == "minecraft:chest"));
+        assert!(map.contains_key("Items"));
+        let decoded = BlockEntity::try_from_map(map).unwrap();
+        assert_eq!(decoded.coord(), (-3, 70, 20));
+        assert!(decoded.data.contains_key("Items"));
+    }
+
+    #[test]
+    fn ticks_test() {
+        let mut registry = BlockRegistry::with_air();
+        let mut chunk = empty_chunk(0, 0);
+        chunk.add_block_tick(ScheduledTick::new("minecraft:repeater", (3, 64, 5), 2));
+        chunk.add_fluid_tick(ScheduledTick::new("minecraft:water", (4, 64, 5), 5));
+        let mut nbt = chunk.to_nbt(&registry);
+        let decoded = decode_chunk(&mut registry, nbt.clone()).unwrap();
+        assert_eq!(decoded.block_ticks(), vec![ScheduledTick::new("minecraft:repeater", (3, 64, 5), 2)]);
+        assert_eq!(decoded.fluid_ticks()[0].coord(), (4, 64, 5));
+        if let Tag::Compound(map) = &mut nbt {
+            map.remove("block_ticks");
+        }
+        let mut decoded = decode_chunk(&mut registry, nbt).unwrap();
+        assert!(decoded.block_ticks().is_empty());
+        assert_eq!(decoded.clear_ticks(), 1);
+        assert!(decoded.fluid_ticks().is_empty());
+    }
+
+    #[test]
+    fn validate_test() {
+        let mut registry = BlockRegistry::with_air();
+        let mut chunk = empty_chunk(2, -1);
+        chunk.set_id((40, 5, -3), registry.register(BlockState::from("minecraft:stone")));
+        let Tag::Compound(mut root) = chunk.to_nbt(&registry) else {
+            panic!("Chunk should encode to a Compound.");
+        };
+        assert!(validate(&Tag::Compound(root.clone())).iter().all(|finding| !finding.is_error()));
+        root.remove("xPos");
+        if let Some(Tag::List(ListTag::Compound(sections))) = root.get_mut("sections") {
+            if let Some(Tag::Compound(block_states)) = sections[0].get_mut("block_states") {
+                block_states.insert("data".to_owned(), Tag::LongArray(vec![0; 10]));
+            }
+        }
+        let findings = validate(&Tag::Compound(root));
+        assert!(findings.iter().any(|finding| finding.is_error() && finding.path == "xPos"));
+        assert!(findings.iter().any(|finding| finding.is_error() && finding.path == "sections[0].block_states.data"));
+        assert_eq!(validate(&Tag::Int(1)).len(), 1);
+    }
+}
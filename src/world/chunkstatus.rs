@@ -0,0 +1,137 @@
+//! The generation status of chunks (the `Status` tag).
+//!
+//! The game saves chunks that are still being generated (proto-chunks) as well as finished ones.
+//! A chunk's status is the last generation step that it completed, and only chunks with the
+//! [ChunkStatus::Full] status have been fully generated and played in.
+
+use crate::nbt::tag::Tag;
+
+/// A step of chunk generation. The steps are ordered from first to last, so a status that
+/// compares greater than another has finished more of the generation.
+///
+/// Some steps only exist in some versions: 1.13 had `liquid_carvers` and `heightmaps`
+/// (then named `liquid_carved` and `finalized`), and `initialize_light` was added in 1.20.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChunkStatus {
+    Empty,
+    StructureStarts,
+    StructureReferences,
+    Biomes,
+    Noise,
+    Surface,
+    Carvers,
+    LiquidCarvers,
+    Features,
+    InitializeLight,
+    Light,
+    Spawn,
+    Heightmaps,
+    Full,
+}
+
+impl ChunkStatus {
+    /// Parses a status, with or without the `minecraft:` namespace.
+    /// The names used by 1.13 (such as `base` and `postprocessed`) are accepted as well.
+    pub fn parse(status: &str) -> Option<Self> {
+        Some(match status.strip_prefix("minecraft:").unwrap_or(status) {
+            "empty" => Self::Empty,
+            "structure_starts" => Self::StructureStarts,
+            "structure_references" => Self::StructureReferences,
+            "biomes" => Self::Biomes,
+            "noise" | "base" => Self::Noise,
+            "surface" => Self::Surface,
+            "carvers" | "carved" => Self::Carvers,
+            "liquid_carvers" | "liquid_carved" => Self::LiquidCarvers,
+            "features" | "decorated" => Self::Features,
+            "initialize_light" => Self::InitializeLight,
+            "light" | "lighted" => Self::Light,
+            "spawn" | "mobs_spawned" => Self::Spawn,
+            "heightmaps" | "finalized" => Self::Heightmaps,
+            "full" | "fullchunk" | "postprocessed" => Self::Full,
+            _ => return None,
+        })
+    }
+
+    /// The namespaced name that current versions save, such as `minecraft:features`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Empty => "minecraft:empty",
+            Self::StructureStarts => "minecraft:structure_starts",
+            Self::StructureReferences => "minecraft:structure_references",
+            Self::Biomes => "minecraft:biomes",
+            Self::Noise => "minecraft:noise",
+            Self::Surface => "minecraft:surface",
+            Self::Carvers => "minecraft:carvers",
+            Self::LiquidCarvers => "minecraft:liquid_carvers",
+            Self::Features => "minecraft:features",
+            Self::InitializeLight => "minecraft:initialize_light",
+            Self::Light => "minecraft:light",
+            Self::Spawn => "minecraft:spawn",
+            Self::Heightmaps => "minecraft:heightmaps",
+            Self::Full => "minecraft:full",
+        }
+    }
+
+    /// Returns true if the chunk has finished generating.
+    pub fn is_full(self) -> bool {
+        self == Self::Full
+    }
+}
+
+/// Reads the `Status` from chunk NBT of either layout, without decoding the chunk.
+/// Returns `None` if the status is missing or unknown.
+pub fn chunk_nbt_status(nbt: &Tag) -> Option<ChunkStatus> {
+    let Tag::Compound(map) = nbt else {
+        return None;
+    };
+    let map = match map.get("Level") {
+        Some(Tag::Compound(level)) => level,
+        _ => map,
+    };
+    match map.get("Status") {
+        Some(Tag::String(status)) => ChunkStatus::parse(status),
+        _ => None,
+    }
+}
+
+/// Returns true if the chunk NBT has a status of at least `min_status`.
+/// Chunks without a known status never do.
+pub fn chunk_nbt_has_status(nbt: &Tag, min_status: ChunkStatus) -> bool {
+    chunk_nbt_status(nbt).is_some_and(|status| status >= min_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::coord::Dimension;
+    use crate::nbt::tag::NamedTag;
+    use crate::world::{chunk::tests::empty_chunk, io::region::RegionFile, iter::{ChunkIter, RegionIter}, world::VirtualJavaWorld};
+
+    #[test]
+    fn chunk_status_test() {
+        assert_eq!(ChunkStatus::parse("minecraft:liquid_carvers"), Some(ChunkStatus::LiquidCarvers));
+        assert_eq!(ChunkStatus::parse("postprocessed"), Some(ChunkStatus::Full));
+        assert_eq!(ChunkStatus::parse(ChunkStatus::Features.name()), Some(ChunkStatus::Features));
+        assert!(ChunkStatus::Noise < ChunkStatus::Features);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        let region_dir = world.get_region_directory(Dimension::Overworld).unwrap();
+        std::fs::create_dir_all(&region_dir).unwrap();
+        let mut region = RegionFile::create(region_dir.join("r.0.0.mca")).unwrap();
+        for (x, status) in [(0, "minecraft:full"), (1, "minecraft:noise"), (2, "minecraft:features")] {
+            let mut chunk = empty_chunk(x, 0);
+            chunk.status = status.to_owned();
+            region.write_data((x, 0), &NamedTag::new(chunk.to_nbt(&world.block_registry))).unwrap();
+        }
+        drop(region);
+        let iter = ChunkIter::new(RegionIter::new(&region_dir, Dimension::Overworld).unwrap());
+        assert_eq!(iter.with_min_status(ChunkStatus::Features).count(), 2);
+
+        assert_eq!(world.prune_unfinished_chunks(Dimension::Overworld).unwrap(), 2);
+        let region = RegionFile::open(region_dir.join("r.0.0.mca")).unwrap();
+        assert!(!region.get_sector((0, 0)).is_empty());
+        assert!(region.get_sector((1, 0)).is_empty());
+        assert!(region.get_sector((2, 0)).is_empty());
+    }
+}
@@ -0,0 +1,816 @@
+//! Conversion of chunk NBT between the pre-1.18 and 1.18+ layouts.
+//!
+//! Before 1.18, chunk data was nested inside of a `Level` compound and
+//! block data lived in `Sections` as a `Palette` list with a packed
+//! `BlockStates` long array. Starting with 1.18, the `Level` compound was
+//! removed, the chunk data was flattened into the root, and each section
+//! stores its blocks in a `block_states` compound with `palette` and `data`.
+//!
+//! Both formats (since 1.16) pack block indices without letting a value span
+//! two longs, so the packed arrays themselves can be moved over untouched.
+//! Chunks from 1.13 to 1.15 let values span two longs, so their arrays are
+//! repacked when they are upgraded.
+//!
+//! Pre-1.18 chunks store biomes as numeric ids in a single `Biomes` array.
+//! When upgrading, these are converted to the per-section `biomes` palettes
+//! using the 1.18 biome names (see [legacy_biome_name]).
+//!
+//! Chunks from before 1.13 (The Flattening) store numeric block ids. These can be
+//! converted to block state palettes with [flatten_chunk_nbt], and
+//! [upgrade_chunk_nbt_to] runs every conversion needed to reach a DataVersion.
+
+use crate::math::bit::BitLength;
+use crate::world::blockstate::BlockState;
+use crate::world::schematic::legacy::legacy_block_state;
+use crate::McError;
+use crate::McResult;
+use crate::nbt::Map;
+use crate::nbt::tag::*;
+
+/// DataVersion of 15w32a (a 1.9 snapshot), the first version that saved a DataVersion.
+pub const DATA_VERSION_15W32A: i32 = 100;
+/// DataVersion of 17w47a, the first 1.13 snapshot (The Flattening), where sections
+/// started storing block states in a palette. Older chunks use numeric block ids.
+pub const DATA_VERSION_FLATTENING: i32 = 1451;
+/// DataVersion of 20w17a, the first 1.16 snapshot where packed values don't span two longs.
+pub const DATA_VERSION_NON_SPANNING: i32 = 2529;
+/// DataVersion of Minecraft 1.17.1, the last version using the `Level` layout.
+pub const DATA_VERSION_1_17_1: i32 = 2730;
+/// DataVersion of Minecraft 1.18, the first version using the flattened layout.
+pub const DATA_VERSION_1_18: i32 = 2860;
+
+/// Tags that were renamed when the `Level` compound was flattened.
+/// (pre-1.18 name, 1.18+ name)
+const RENAMED_TAGS: [(&str, &str); 5] = [
+    ("Sections", "sections"),
+    ("TileEntities", "block_entities"),
+    ("TileTicks", "block_ticks"),
+    ("LiquidTicks", "fluid_ticks"),
+    ("Structures", "structures"),
+];
+
+/// Packed block data for a section with only a single palette entry.
+/// 4096 blocks at 4 bits each is 256 longs.
+const EMPTY_BLOCK_STATES_LEN: usize = 256;
+
+/// The layout of chunk NBT, which is determined by the chunk's `DataVersion`.
+/// Layouts are ordered from oldest to newest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChunkLayout {
+    /// Before 1.13. The `Level` layout, where sections store numeric block ids in `Blocks` and `Data` arrays.
+    Numeric,
+    /// 1.13 to 1.15. The `Level` layout, where packed block indices may span two longs.
+    SpanningLevel,
+    /// 1.16 to 1.17. The `Level` layout.
+    Level,
+    /// 1.18 and later. The flattened layout with `block_states` and `biomes` in each section.
+    Flattened,
+}
+
+impl ChunkLayout {
+    /// Returns the layout used by the given DataVersion, or [McError::UnsupportedDataVersion]
+    /// if it's lower than any DataVersion that Minecraft has saved.
+    pub fn for_data_version(data_version: i32) -> McResult<Self> {
+        match data_version {
+            DATA_VERSION_1_18.. => Ok(Self::Flattened),
+            DATA_VERSION_NON_SPANNING.. => Ok(Self::Level),
+            DATA_VERSION_FLATTENING.. => Ok(Self::SpanningLevel),
+            DATA_VERSION_15W32A.. => Ok(Self::Numeric),
+            _ => Err(McError::UnsupportedDataVersion(data_version)),
+        }
+    }
+
+    /// Returns the layout of the chunk NBT from its `DataVersion`.
+    /// Anvil chunks from before 1.9 don't have a `DataVersion`, so they're assumed to be [ChunkLayout::Numeric].
+    /// [McError::NotFoundInCompound] is returned for any other chunk without a `DataVersion`.
+    pub fn of_chunk_nbt(nbt: &Tag) -> McResult<Self> {
+        let Tag::Compound(map) = nbt else {
+            return Err(McError::NbtDecodeError);
+        };
+        match map.get("DataVersion") {
+            Some(Tag::Int(data_version)) => Self::for_data_version(*data_version),
+            Some(_) => Err(McError::NbtDecodeError),
+            None if is_legacy_chunk_nbt(nbt) && !is_mcregion_chunk_nbt(nbt) => Ok(Self::Numeric),
+            None => Err(McError::NotFoundInCompound("DataVersion".to_owned())),
+        }
+    }
+
+    /// Returns true if the layout nests the chunk data in a `Level` compound.
+    pub fn is_level(self) -> bool {
+        !matches!(self, Self::Flattened)
+    }
+}
+
+/// Returns true if the chunk NBT uses the pre-1.18 `Level` layout.
+pub fn is_legacy_chunk_nbt(nbt: &Tag) -> bool {
+    matches!(nbt, Tag::Compound(map) if matches!(map.get("Level"), Some(Tag::Compound(_))))
+}
+
+/// Returns true if the chunk NBT was saved in the McRegion format, which stores
+/// numeric block ids in a `Blocks` byte array within the `Level` compound rather
+/// than in `Sections`.
+pub fn is_mcregion_chunk_nbt(nbt: &Tag) -> bool {
+    let Tag::Compound(map) = nbt else {
+        return false;
+    };
+    match map.get("Level") {
+        Some(Tag::Compound(level)) => {
+            matches!(level.get("Blocks"), Some(Tag::ByteArray(_)))
+            && !level.contains_key("Sections")
+        },
+        _ => false,
+    }
+}
+
+/// Returns the `xPos` and `zPos` stored in the chunk NBT, for either layout.
+/// Returns `None` if either is missing or isn't an Int.
+pub fn chunk_nbt_position(nbt: &Tag) -> Option<(i32, i32)> {
+    let Tag::Compound(map) = nbt else {
+        return None;
+    };
+    let map = match map.get("Level") {
+        Some(Tag::Compound(level)) => level,
+        _ => map,
+    };
+    match (map.get("xPos"), map.get("zPos")) {
+        (Some(Tag::Int(x)), Some(Tag::Int(z))) => Some((*x, *z)),
+        _ => None,
+    }
+}
+
+/// Restructures the chunk NBT to the layout used by the target DataVersion
+/// and sets the `DataVersion` tag to `target_version`.
+pub fn normalize_chunk_nbt(nbt: Tag, target_version: i32) -> McResult<Tag> {
+    let nbt = if target_version >= DATA_VERSION_1_18 {
+        upgrade_chunk_nbt(nbt)?
+    } else {
+        downgrade_chunk_nbt(nbt)?
+    };
+    let Tag::Compound(mut map) = nbt else {
+        return Err(McError::NbtDecodeError);
+    };
+    map.insert("DataVersion".to_owned(), Tag::Int(target_version));
+    Ok(Tag::Compound(map))
+}
+
+/// Converts pre-1.18 chunk NBT into the 1.18+ layout.
+/// Chunks that are already in the 1.18+ layout are returned unchanged.
+/// The `DataVersion` is raised to [DATA_VERSION_1_18] if it is lower.
+///
+/// Section `Y` values are absolute in both formats, so they are kept as is.
+/// `yPos` is set to the lowest section that contains block data.
+/// Block data from before [DATA_VERSION_NON_SPANNING] is repacked so that values don't span two longs,
+/// and the numeric `Biomes` array is converted to per-section biome palettes.
+///
+/// Chunks from before 1.13 must be flattened with [flatten_chunk_nbt] first.
+pub fn upgrade_chunk_nbt(nbt: Tag) -> McResult<Tag> {
+    if !is_legacy_chunk_nbt(&nbt) {
+        return Ok(nbt);
+    }
+    let Tag::Compound(mut root) = nbt else {
+        return Err(McError::NbtDecodeError);
+    };
+    let spanning = matches!(root.get("DataVersion"), Some(Tag::Int(version)) if *version < DATA_VERSION_NON_SPANNING);
+    let Some(Tag::Compound(mut level)) = root.remove("Level") else {
+        return Err(McError::NbtDecodeError);
+    };
+    if spanning {
+        repack_level_sections(&mut level)?;
+    }
+    let biomes = level.remove("Biomes");
+    for (old, new) in RENAMED_TAGS {
+        if let Some(tag) = level.remove(old) {
+            level.insert(new.to_owned(), tag);
+        }
+    }
+    let sections = match level.remove("sections") {
+        Some(Tag::List(ListTag::Compound(sections))) => sections,
+        Some(Tag::List(ListTag::Empty)) | None => Vec::new(),
+        Some(_) => return Err(McError::NbtDecodeError),
+    };
+    let sections = sections.into_iter()
+        .map(|section| upgrade_section(section, biomes.as_ref()))
+        .collect::<McResult<Vec<Map>>>()?;
+    let lowest_y = sections.iter()
+        .filter(|section| section.contains_key("block_states"))
+        .filter_map(|section| match section.get("Y") {
+            Some(Tag::Byte(y)) => Some(*y as i32),
+            _ => None,
+        })
+        .min()
+        .unwrap_or(0);
+    level.entry("yPos".to_owned()).or_insert(Tag::Int(lowest_y));
+    level.insert("sections".to_owned(), Tag::List(ListTag::Compound(sections)));
+    let data_version = match root.remove("DataVersion") {
+        Some(Tag::Int(version)) => version.max(DATA_VERSION_1_18),
+        _ => DATA_VERSION_1_18,
+    };
+    level.insert("DataVersion".to_owned(), Tag::Int(data_version));
+    // Anything that was outside of the Level compound is kept in the root.
+    level.extend(root);
+    Ok(Tag::Compound(level))
+}
+
+/// Converts 1.18+ chunk NBT into the pre-1.18 `Level` layout.
+/// Chunks that are already in the pre-1.18 layout are returned unchanged.
+/// The `DataVersion` is lowered to [DATA_VERSION_1_17_1] if it is higher.
+///
+/// Pre-1.18 chunks only hold sections 0 through 15 (and -1 and 16 for lighting),
+/// so sections outside of that range are discarded, as are per-section biomes.
+pub fn downgrade_chunk_nbt(nbt: Tag) -> McResult<Tag> {
+    if is_legacy_chunk_nbt(&nbt) {
+        return Ok(nbt);
+    }
+    let Tag::Compound(mut level) = nbt else {
+        return Err(McError::NbtDecodeError);
+    };
+    let data_version = match level.remove("DataVersion") {
+        Some(Tag::Int(version)) => version.min(DATA_VERSION_1_17_1),
+        _ => DATA_VERSION_1_17_1,
+    };
+    level.remove("yPos");
+    let sections = match level.remove("sections") {
+        Some(Tag::List(ListTag::Compound(sections))) => sections,
+        Some(Tag::List(ListTag::Empty)) | None => Vec::new(),
+        Some(_) => return Err(McError::NbtDecodeError),
+    };
+    let sections = sections.into_iter()
+        .map(downgrade_section)
+        .collect::<McResult<Vec<Option<Map>>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<Map>>();
+    level.insert("sections".to_owned(), Tag::List(ListTag::Compound(sections)));
+    for (old, new) in RENAMED_TAGS {
+        if let Some(tag) = level.remove(new) {
+            level.insert(old.to_owned(), tag);
+        }
+    }
+    let mut root = Map::new();
+    root.insert("DataVersion".to_owned(), Tag::Int(data_version));
+    root.insert("Level".to_owned(), Tag::Compound(level));
+    Ok(Tag::Compound(root))
+}
+
+/// Converts McRegion chunk NBT (see [is_mcregion_chunk_nbt]) into the Anvil layout from before 1.13,
+/// so that it can be flattened with [flatten_chunk_nbt] like any other chunk with numeric block ids.
+/// The 128 block tall `Blocks`, `Data`, `SkyLight` and `BlockLight` arrays are split into sections,
+/// sections with only air are left out, and the `HeightMap` byte array becomes an int array.
+/// Chunks that aren't McRegion chunks are returned unchanged.
+pub fn mcregion_to_anvil_chunk_nbt(nbt: Tag) -> McResult<Tag> {
+    if !is_mcregion_chunk_nbt(&nbt) {
+        return Ok(nbt);
+    }
+    let Tag::Compound(mut root) = nbt else {
+        return Err(McError::NbtDecodeError);
+    };
+    let Some(Tag::Compound(level)) = root.get_mut("Level") else {
+        return Err(McError::NbtDecodeError);
+    };
+    let Some(Tag::ByteArray(blocks)) = level.remove("Blocks") else {
+        return Err(McError::NbtDecodeError);
+    };
+    if blocks.len() != 32768 {
+        return Err(McError::NbtDecodeError);
+    }
+    // McRegion arrays are ordered by x, then z, then y, and Anvil sections by y, then z, then x.
+    let mcregion_index = |x: usize, y: usize, z: usize| (x << 11) | (z << 7) | y;
+    let nibble_arrays = ["Data", "SkyLight", "BlockLight"].map(|name| match level.remove(name) {
+        Some(Tag::ByteArray(array)) if array.len() == 16384 => Some((name, array)),
+        _ => None,
+    });
+    let mut sections = Vec::new();
+    for section_y in 0..8usize {
+        let mut section_blocks = vec![0i8; 4096];
+        for (index, block) in section_blocks.iter_mut().enumerate() {
+            *block = blocks[mcregion_index(index & 15, section_y * 16 + (index >> 8), (index >> 4) & 15)];
+        }
+        if section_blocks.iter().all(|&block| block == 0) {
+            continue;
+        }
+        let mut section = Map::new();
+        section.insert("Y".to_owned(), Tag::Byte(section_y as i8));
+        section.insert("Blocks".to_owned(), Tag::ByteArray(section_blocks));
+        for (name, array) in nibble_arrays.iter().flatten() {
+            let mut section_array = vec![0i8; 2048];
+            for index in 0..4096 {
+                let source = mcregion_index(index & 15, section_y * 16 + (index >> 8), (index >> 4) & 15);
+                let value = (array[source / 2] as u8 >> ((source % 2) * 4)) & 15;
+                section_array[index / 2] |= (value << ((index % 2) * 4)) as i8;
+            }
+            section.insert((*name).to_owned(), Tag::ByteArray(section_array));
+        }
+        sections.push(section);
+    }
+    level.insert("Sections".to_owned(), Tag::List(ListTag::Compound(sections)));
+    if let Some(Tag::ByteArray(heights)) = level.remove("HeightMap") {
+        level.insert("HeightMap".to_owned(), Tag::IntArray(heights.into_iter().map(|height| height as u8 as i32).collect()));
+    }
+    Ok(Tag::Compound(root))
+}
+
+/// Converts chunk NBT from before 1.13, where sections store numeric block ids, into the
+/// `Level` layout with block state palettes that 1.16 and 1.17 use.
+/// The `DataVersion` is set to [DATA_VERSION_NON_SPANNING].
+/// Chunks that already have palettes are returned unchanged.
+///
+/// Block ids are converted with [legacy_block_state], and unknown ids become air.
+/// States that 1.12 derived from neighboring blocks or block entities (such as fence
+/// connections or bed colors) aren't restored, and entity and block entity ids aren't renamed.
+/// The `HeightMap` is used for all of the new heightmaps, and the chunk is given the `full`
+/// status if its terrain was populated.
+pub fn flatten_chunk_nbt(nbt: Tag) -> McResult<Tag> {
+    if ChunkLayout::of_chunk_nbt(&nbt)? != ChunkLayout::Numeric {
+        return Ok(nbt);
+    }
+    let Tag::Compound(mut root) = nbt else {
+        return Err(McError::NbtDecodeError);
+    };
+    let Some(Tag::Compound(level)) = root.get_mut("Level") else {
+        return Err(McError::NbtDecodeError);
+    };
+    if let Some(Tag::List(ListTag::Compound(sections))) = level.get_mut("Sections") {
+        for section in sections.iter_mut() {
+            flatten_section(section)?;
+        }
+    }
+    if let Some(Tag::ByteArray(biomes)) = level.remove("Biomes") {
+        level.insert("Biomes".to_owned(), Tag::IntArray(biomes.into_iter().map(|id| id as u8 as i32).collect()));
+    }
+    let heights = match level.remove("HeightMap") {
+        Some(Tag::IntArray(heights)) if heights.len() == 256 => heights,
+        _ => vec![0; 256],
+    };
+    let mut heightmap = vec![0i64; 37];
+    for (index, height) in heights.into_iter().enumerate() {
+        heightmap[index / 7] |= ((height.clamp(0, 511) as i64) & 511) << ((index % 7) * 9);
+    }
+    let mut heightmaps = Map::new();
+    for name in ["MOTION_BLOCKING", "MOTION_BLOCKING_NO_LEAVES", "OCEAN_FLOOR", "WORLD_SURFACE"] {
+        heightmaps.insert(name.to_owned(), Tag::LongArray(heightmap.clone()));
+    }
+    level.insert("Heightmaps".to_owned(), Tag::Compound(heightmaps));
+    let populated = matches!(level.remove("TerrainPopulated"), Some(Tag::Byte(1)));
+    level.remove("LightPopulated");
+    level.entry("Status".to_owned()).or_insert(Tag::string(if populated { "full" } else { "empty" }));
+    for name in ["TileTicks", "LiquidTicks", "PostProcessing", "TileEntities"] {
+        level.entry(name.to_owned()).or_insert(Tag::List(ListTag::Empty));
+    }
+    level.entry("Structures".to_owned()).or_insert(Tag::Compound(Map::new()));
+    level.entry("InhabitedTime".to_owned()).or_insert(Tag::Long(0));
+    level.entry("LastUpdate".to_owned()).or_insert(Tag::Long(0));
+    root.insert("DataVersion".to_owned(), Tag::Int(DATA_VERSION_NON_SPANNING));
+    Ok(Tag::Compound(root))
+}
+
+/// Runs every conversion needed to bring chunk NBT up to `data_version`, then sets its `DataVersion`:
+/// 1. Before 1.13, numeric block ids are flattened with [flatten_chunk_nbt].
+/// 2. Before 1.16, packed block data is repacked so that values don't span two longs.
+/// 3. Before 1.18, the `Level` compound is removed with [upgrade_chunk_nbt].
+///
+/// Numeric chunks can't be upgraded to 1.13 through 1.15, and chunks can't be upgraded to an older
+/// DataVersion (see [downgrade_chunk_nbt]), so [McError::UnsupportedDataVersion] is returned for those.
+pub fn upgrade_chunk_nbt_to(nbt: Tag, data_version: i32) -> McResult<Tag> {
+    let layout = ChunkLayout::of_chunk_nbt(&nbt)?;
+    let target = ChunkLayout::for_data_version(data_version)?;
+    let older = matches!(&nbt, Tag::Compound(map) if matches!(map.get("DataVersion"), Some(Tag::Int(version)) if *version > data_version));
+    if older || target < layout || (layout == ChunkLayout::Numeric && target == ChunkLayout::SpanningLevel) {
+        return Err(McError::UnsupportedDataVersion(data_version));
+    }
+    let nbt = match layout {
+        ChunkLayout::Numeric => flatten_chunk_nbt(nbt)?,
+        ChunkLayout::SpanningLevel if target == ChunkLayout::Level => {
+            let Tag::Compound(mut root) = nbt else {
+                return Err(McError::NbtDecodeError);
+            };
+            let Some(Tag::Compound(level)) = root.get_mut("Level") else {
+                return Err(McError::NbtDecodeError);
+            };
+            repack_level_sections(level)?;
+            Tag::Compound(root)
+        }
+        _ => nbt,
+    };
+    let nbt = if target == ChunkLayout::Flattened {
+        upgrade_chunk_nbt(nbt)?
+    } else {
+        nbt
+    };
+    let Tag::Compound(mut map) = nbt else {
+        return Err(McError::NbtDecodeError);
+    };
+    map.insert("DataVersion".to_owned(), Tag::Int(data_version));
+    Ok(Tag::Compound(map))
+}
+
+/// The 1.18 name of a biome from its numeric id in 1.13 through 1.17.
+/// Biomes that were removed in 1.18 are given the name of the biome that replaced them.
+/// Returns `None` for unused ids.
+pub fn legacy_biome_name(id: i32) -> Option<&'static str> {
+    Some(match id {
+        0 => "minecraft:ocean",
+        1 => "minecraft:plains",
+        2 | 17 | 130 => "minecraft:desert",
+        3 | 20 => "minecraft:windswept_hills",
+        4 | 18 => "minecraft:forest",
+        5 | 19 | 133 => "minecraft:taiga",
+        6 | 134 => "minecraft:swamp",
+        7 => "minecraft:river",
+        8 => "minecraft:nether_wastes",
+        9 => "minecraft:the_end",
+        10 => "minecraft:frozen_ocean",
+        11 => "minecraft:frozen_river",
+        12 | 13 => "minecraft:snowy_plains",
+        14 | 15 => "minecraft:mushroom_fields",
+        16 => "minecraft:beach",
+        21 | 22 | 149 => "minecraft:jungle",
+        23 | 151 => "minecraft:sparse_jungle",
+        24 => "minecraft:deep_ocean",
+        25 => "minecraft:stony_shore",
+        26 => "minecraft:snowy_beach",
+        27 | 28 => "minecraft:birch_forest",
+        29 | 157 => "minecraft:dark_forest",
+        30 | 31 | 158 => "minecraft:snowy_taiga",
+        32 | 33 => "minecraft:old_growth_pine_taiga",
+        34 => "minecraft:windswept_forest",
+        35 => "minecraft:savanna",
+        36 => "minecraft:savanna_plateau",
+        37 | 39 | 167 => "minecraft:badlands",
+        38 | 166 => "minecraft:wooded_badlands",
+        40 => "minecraft:small_end_islands",
+        41 => "minecraft:end_midlands",
+        42 => "minecraft:end_highlands",
+        43 => "minecraft:end_barrens",
+        44 | 47 => "minecraft:warm_ocean",
+        45 => "minecraft:lukewarm_ocean",
+        46 => "minecraft:cold_ocean",
+        48 => "minecraft:deep_lukewarm_ocean",
+        49 => "minecraft:deep_cold_ocean",
+        50 => "minecraft:deep_frozen_ocean",
+        127 => "minecraft:the_void",
+        129 => "minecraft:sunflower_plains",
+        131 | 162 => "minecraft:windswept_gravelly_hills",
+        132 => "minecraft:flower_forest",
+        140 => "minecraft:ice_spikes",
+        155 | 156 => "minecraft:old_growth_birch_forest",
+        160 | 161 => "minecraft:old_growth_spruce_taiga",
+        163 | 164 => "minecraft:windswept_savanna",
+        165 => "minecraft:eroded_badlands",
+        168 | 169 => "minecraft:bamboo_jungle",
+        170 => "minecraft:soul_sand_valley",
+        171 => "minecraft:crimson_forest",
+        172 => "minecraft:warped_forest",
+        173 => "minecraft:basalt_deltas",
+        174 => "minecraft:dripstone_caves",
+        175 => "minecraft:lush_caves",
+        _ => return None,
+    })
+}
+
+/// Packs 4096 palette indices so that values don't span two longs.
+fn pack_indices(indices: &[u32], bits: usize) -> Vec<i64> {
+    let per_long = 64 / bits;
+    let mut data = vec![0i64; indices.len().div_ceil(per_long)];
+    for (index, &value) in indices.iter().enumerate() {
+        data[index / per_long] |= (value as i64) << ((index % per_long) * bits);
+    }
+    data
+}
+
+fn flatten_section(section: &mut Map) -> McResult<()> {
+    let Some(Tag::ByteArray(blocks)) = section.remove("Blocks") else {
+        return Ok(());
+    };
+    if blocks.len() != 4096 {
+        return Err(McError::NbtDecodeError);
+    }
+    let nibble = |array: &Option<Tag>, index: usize| match array {
+        Some(Tag::ByteArray(array)) if array.len() == 2048 => (array[index / 2] as u8 >> ((index % 2) * 4)) & 15,
+        _ => 0,
+    };
+    let add = section.remove("Add");
+    let data = section.remove("Data");
+    let mut palette = Vec::<BlockState>::new();
+    let mut indices = Vec::with_capacity(4096);
+    for (index, &block) in blocks.iter().enumerate() {
+        let id = (block as u8 as u16) | ((nibble(&add, index) as u16) << 8);
+        let state = u8::try_from(id).ok()
+            .and_then(|id| legacy_block_state(id, nibble(&data, index)))
+            .unwrap_or_else(BlockState::air);
+        let palette_index = match palette.iter().position(|entry| *entry == state) {
+            Some(position) => position,
+            None => {
+                palette.push(state);
+                palette.len() - 1
+            }
+        };
+        indices.push(palette_index as u32);
+    }
+    let bits = (palette.len() - 1).bit_length().max(4) as usize;
+    let palette = palette.into_iter().map(BlockState::to_nbt).collect();
+    section.insert("Palette".to_owned(), Tag::List(ListTag::Compound(palette)));
+    section.insert("BlockStates".to_owned(), Tag::LongArray(pack_indices(&indices, bits)));
+    Ok(())
+}
+
+/// Repacks the `BlockStates` of every section in a `Level` compound so that values don't span two longs.
+fn repack_level_sections(level: &mut Map) -> McResult<()> {
+    let Some(Tag::List(ListTag::Compound(sections))) = level.get_mut("Sections") else {
+        return Ok(());
+    };
+    for section in sections.iter_mut() {
+        let palette_len = match section.get("Palette") {
+            Some(Tag::List(palette)) => palette.len(),
+            _ => continue,
+        };
+        if let Some(Tag::LongArray(data)) = section.get_mut("BlockStates") {
+            let bits = (palette_len.max(1) - 1).bit_length().max(4);
+            *data = repack_spanning(data, bits)?;
+        }
+    }
+    Ok(())
+}
+
+/// Converts a numeric `Biomes` array into the per-section biome palette of the section at `y`.
+/// 1.15 to 1.17 store 1024 ids in 4x4x4 cells, while 1.13 and 1.14 store 256 ids, one per column.
+fn upgrade_biomes(biomes: &[i32], y: i8) -> Option<Map> {
+    let cell_id = |index: usize| {
+        let (x, z, cell_y) = (index & 3, (index >> 2) & 3, index >> 4);
+        match biomes.len() {
+            1024 => biomes.get(((y as usize) * 4 + cell_y) * 16 + z * 4 + x).copied(),
+            256 => biomes.get(z * 4 * 16 + x * 4).copied(),
+            _ => None,
+        }
+    };
+    if !(0..16).contains(&y) || !matches!(biomes.len(), 256 | 1024) {
+        return None;
+    }
+    let mut palette = Vec::<&str>::new();
+    let indices = (0..64).map(|index| {
+        let name = cell_id(index).and_then(legacy_biome_name).unwrap_or("minecraft:plains");
+        match palette.iter().position(|entry| *entry == name) {
+            Some(position) => position as u32,
+            None => {
+                palette.push(name);
+                palette.len() as u32 - 1
+            }
+        }
+    }).collect::<Vec<u32>>();
+    let mut map = Map::new();
+    if palette.len() > 1 {
+        let bits = (palette.len() - 1).bit_length() as usize;
+        map.insert("data".to_owned(), Tag::LongArray(pack_indices(&indices, bits)));
+    }
+    map.insert("palette".to_owned(), Tag::List(ListTag::String(palette.into_iter().map(str::to_owned).collect())));
+    Some(map)
+}
+
+/// Repacks 4096 values that may span two longs into longs that each hold a whole number of values.
+fn repack_spanning(data: &[i64], bits: u32) -> McResult<Vec<i64>> {
+    let bits = bits as usize;
+    if data.len() != (4096 * bits).div_ceil(64) {
+        return Err(McError::NbtDecodeError);
+    }
+    let mask = (1u64 << bits) - 1;
+    let per_long = 64 / bits;
+    let mut repacked = vec![0i64; 4096usize.div_ceil(per_long)];
+    for index in 0..4096 {
+        let start = index * bits;
+        let (long, offset) = (start / 64, start % 64);
+        let mut value = (data[long] as u64) >> offset;
+        if offset + bits > 64 {
+            value |= (data[long + 1] as u64) << (64 - offset);
+        }
+        repacked[index / per_long] |= ((value & mask) << ((index % per_long) * bits)) as i64;
+    }
+    Ok(repacked)
+}
+
+fn upgrade_section(mut section: Map, biomes: Option<&Tag>) -> McResult<Map> {
+    if let (Some(Tag::IntArray(biomes)), Some(Tag::Byte(y))) = (biomes, section.get("Y")) {
+        if let Some(biomes) = upgrade_biomes(biomes, *y) {
+            section.insert("biomes".to_owned(), Tag::Compound(biomes));
+        }
+    }
+    let palette = section.remove("Palette");
+    let data = section.remove("BlockStates");
+    match (palette, data) {
+        (Some(Tag::List(palette)), data) => {
+            let single = palette.len() == 1;
+            let mut block_states = Map::new();
+            block_states.insert("palette".to_owned(), Tag::List(palette));
+            match data {
+                // A single entry palette does not have data in 1.18+.
+                Some(Tag::LongArray(_)) if single => (),
+                Some(Tag::LongArray(data)) => {
+                    block_states.insert("data".to_owned(), Tag::LongArray(data));
+                }
+                None => (),
+                Some(_) => return Err(McError::NbtDecodeError),
+            }
+            section.insert("block_states".to_owned(), Tag::Compound(block_states));
+        }
+        (None, None) => (),
+        _ => return Err(McError::NbtDecodeError),
+    }
+    Ok(section)
+}
+
+fn downgrade_section(mut section: Map) -> McResult<Option<Map>> {
+    let y = match section.get("Y") {
+        Some(Tag::Byte(y)) => *y,
+        _ => return Err(McError::NotFoundInCompound("Y".to_owned())),
+    };
+    if !(-1..=16).contains(&y) {
+        return Ok(None);
+    }
+    section.remove("biomes");
+    let block_states = section.remove("block_states");
+    // Sections -1 and 16 only hold lighting.
+    if !(0..=15).contains(&y) {
+        return Ok(Some(section));
+    }
+    match block_states {
+        Some(Tag::Compound(mut block_states)) => {
+            let Some(Tag::List(palette)) = block_states.remove("palette") else {
+                return Err(McError::NotFoundInCompound("palette".to_owned()));
+            };
+            let data = match block_states.remove("data") {
+                Some(Tag::LongArray(data)) => data,
+                None => vec![0i64; EMPTY_BLOCK_STATES_LEN],
+                Some(_) => return Err(McError::NbtDecodeError),
+            };
+            section.insert("Palette".to_owned(), Tag::List(palette));
+            section.insert("BlockStates".to_owned(), Tag::LongArray(data));
+        }
+        None => (),
+        Some(_) => return Err(McError::NbtDecodeError),
+    }
+    Ok(Some(section))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn air_palette() -> Tag {
+        let mut air = Map::new();
+        air.insert("Name".to_owned(), Tag::string("minecraft:air"));
+        Tag::List(ListTag::Compound(vec![air]))
+    }
+
+    #[test]
+    fn upgrade_downgrade_test() {
+        let mut section = Map::new();
+        section.insert("Y".to_owned(), Tag::Byte(0));
+        section.insert("Palette".to_owned(), air_palette());
+        section.insert("BlockStates".to_owned(), Tag::LongArray(vec![0; 256]));
+        let mut level = Map::new();
+        level.insert("xPos".to_owned(), Tag::Int(3));
+        level.insert("zPos".to_owned(), Tag::Int(-7));
+        level.insert("Sections".to_owned(), Tag::List(ListTag::Compound(vec![section])));
+        level.insert("TileEntities".to_owned(), Tag::List(ListTag::Empty));
+        let mut root = Map::new();
+        root.insert("DataVersion".to_owned(), Tag::Int(2730));
+        root.insert("Level".to_owned(), Tag::Compound(level));
+        let legacy = Tag::Compound(root);
+
+        let modern = upgrade_chunk_nbt(legacy).expect("Failed to upgrade.");
+        assert!(!is_legacy_chunk_nbt(&modern));
+        let Tag::Compound(map) = &modern else { panic!() };
+        assert!(matches!(map.get("DataVersion"), Some(Tag::Int(DATA_VERSION_1_18))));
+        assert!(matches!(map.get("yPos"), Some(Tag::Int(0))));
+        assert!(map.contains_key("block_entities"));
+        let Some(Tag::List(ListTag::Compound(sections))) = map.get("sections") else { panic!() };
+        let Some(Tag::Compound(block_states)) = sections[0].get("block_states") else { panic!() };
+        assert!(block_states.contains_key("palette"));
+        assert!(!block_states.contains_key("data"));
+
+        let downgraded = downgrade_chunk_nbt(modern).expect("Failed to downgrade.");
+        assert!(is_legacy_chunk_nbt(&downgraded));
+        let Tag::Compound(root) = &downgraded else { panic!() };
+        assert!(matches!(root.get("DataVersion"), Some(Tag::Int(DATA_VERSION_1_17_1))));
+        let Some(Tag::Compound(level)) = root.get("Level") else { panic!() };
+        assert!(!level.contains_key("yPos"));
+        assert!(level.contains_key("TileEntities"));
+        let Some(Tag::List(ListTag::Compound(sections))) = level.get("Sections") else { panic!() };
+        assert!(sections[0].contains_key("Palette"));
+        assert!(matches!(sections[0].get("BlockStates"), Some(Tag::LongArray(data)) if data.len() == 256));
+    }
+
+    #[test]
+    fn spanning_upgrade_test() {
+        assert_eq!(ChunkLayout::for_data_version(1976).unwrap(), ChunkLayout::SpanningLevel);
+        assert_eq!(ChunkLayout::for_data_version(2586).unwrap(), ChunkLayout::Level);
+        assert_eq!(ChunkLayout::for_data_version(3465).unwrap(), ChunkLayout::Flattened);
+        assert_eq!(ChunkLayout::for_data_version(1343).unwrap(), ChunkLayout::Numeric);
+        assert!(matches!(ChunkLayout::for_data_version(99), Err(McError::UnsupportedDataVersion(99))));
+
+        // 17 palette entries need 5 bits, so values span two longs in 1.13 to 1.15.
+        let palette = (0..17).map(|i| {
+            let mut state = Map::new();
+            state.insert("Name".to_owned(), Tag::string(format!("minecraft:block_{i}")));
+            state
+        }).collect::<Vec<_>>();
+        let mut data = vec![0i64; 320];
+        for index in 0..4096usize {
+            let start = index * 5;
+            let value = (index % 17) as u64;
+            data[start / 64] |= (value << (start % 64)) as i64;
+            if start % 64 > 59 {
+                data[start / 64 + 1] |= (value >> (64 - start % 64)) as i64;
+            }
+        }
+        let mut section = Map::new();
+        section.insert("Y".to_owned(), Tag::Byte(0));
+        section.insert("Palette".to_owned(), Tag::List(ListTag::Compound(palette)));
+        section.insert("BlockStates".to_owned(), Tag::LongArray(data));
+        let mut level = Map::new();
+        level.insert("Sections".to_owned(), Tag::List(ListTag::Compound(vec![section])));
+        let mut root = Map::new();
+        root.insert("DataVersion".to_owned(), Tag::Int(1976));
+        root.insert("Level".to_owned(), Tag::Compound(level));
+
+        let Tag::Compound(modern) = upgrade_chunk_nbt(Tag::Compound(root)).unwrap() else { panic!() };
+        let Some(Tag::List(ListTag::Compound(sections))) = modern.get("sections") else { panic!() };
+        let Some(Tag::Compound(block_states)) = sections[0].get("block_states") else { panic!() };
+        let Some(Tag::LongArray(data)) = block_states.get("data") else { panic!() };
+        // 12 values per long.
+        assert_eq!(data.len(), 342);
+        for index in [0usize, 12, 13, 100, 4095] {
+            let value = (data[index / 12] as u64 >> ((index % 12) * 5)) & 0x1f;
+            assert_eq!(value as usize, index % 17);
+        }
+    }
+
+    #[test]
+    fn flatten_upgrade_test() {
+        use crate::world::{blockregistry::BlockRegistry, chunk::decode_versioned_chunk};
+        // Stone everywhere, with a top oak slab at (1, 2, 3) in section 4.
+        let mut blocks = vec![1i8; 4096];
+        let mut data = vec![0i8; 2048];
+        let index = 2 * 256 + 3 * 16 + 1;
+        blocks[index] = 126;
+        data[index / 2] = 8 << 4;
+        let mut section = Map::new();
+        section.insert("Y".to_owned(), Tag::Byte(4));
+        section.insert("Blocks".to_owned(), Tag::ByteArray(blocks));
+        section.insert("Data".to_owned(), Tag::ByteArray(data));
+        let mut level = Map::new();
+        level.insert("xPos".to_owned(), Tag::Int(1));
+        level.insert("zPos".to_owned(), Tag::Int(2));
+        level.insert("TerrainPopulated".to_owned(), Tag::Byte(1));
+        level.insert("Biomes".to_owned(), Tag::ByteArray(vec![2; 256]));
+        level.insert("Sections".to_owned(), Tag::List(ListTag::Compound(vec![section])));
+        let mut root = Map::new();
+        root.insert("DataVersion".to_owned(), Tag::Int(1343));
+        root.insert("Level".to_owned(), Tag::Compound(level));
+        let root = Tag::Compound(root);
+        assert!(matches!(upgrade_chunk_nbt_to(root.clone(), 1976), Err(McError::UnsupportedDataVersion(1976))));
+
+        let mut registry = BlockRegistry::with_air();
+        let mut chunk = decode_versioned_chunk(&mut registry, root).unwrap();
+        assert_eq!(chunk.data_version, DATA_VERSION_1_18);
+        assert_eq!(chunk.status, "full");
+        let slab = chunk.get_id((17, 66, 35)).and_then(|id| registry.get(id)).unwrap();
+        assert_eq!(slab.name(), "minecraft:oak_slab");
+        assert_eq!(slab.get_property("type"), Some("top"));
+        assert_eq!(registry.get(chunk.get_id((16, 64, 32)).unwrap()).unwrap().name(), "minecraft:stone");
+        let biomes = chunk.sections.sections[0].biomes.as_ref().unwrap();
+        assert_eq!(biomes.get(0, 0, 0), "minecraft:desert");
+        assert!(chunk.upgrade_to(DATA_VERSION_1_17_1).is_err());
+        chunk.upgrade_to(3465).unwrap();
+        assert_eq!(chunk.data_version, 3465);
+    }
+    #[test]
+    fn mcregion_test() {
+        use crate::world::{blockregistry::BlockRegistry, chunk::decode_chunk_for_format, io::region::RegionFormat};
+        // Bedrock at y = 0 and a torch at (1, 20, 2).
+        let mut blocks = vec![0i8; 32768];
+        let mut data = vec![0i8; 16384];
+        for column in 0..256 {
+            blocks[column << 7] = 7;
+        }
+        let torch = (1 << 11) | (2 << 7) | 20;
+        blocks[torch] = 50;
+        data[torch / 2] = 5;
+        let mut level = Map::new();
+        level.insert("xPos".to_owned(), Tag::Int(-1));
+        level.insert("zPos".to_owned(), Tag::Int(3));
+        level.insert("TerrainPopulated".to_owned(), Tag::Byte(1));
+        level.insert("Blocks".to_owned(), Tag::ByteArray(blocks));
+        level.insert("Data".to_owned(), Tag::ByteArray(data));
+        level.insert("HeightMap".to_owned(), Tag::ByteArray(vec![1; 256]));
+        let root = Tag::Compound(Map::from([("Level".to_owned(), Tag::Compound(level))]));
+        assert!(is_mcregion_chunk_nbt(&root));
+        let anvil = mcregion_to_anvil_chunk_nbt(root.clone()).unwrap();
+        assert_eq!(ChunkLayout::of_chunk_nbt(&anvil).unwrap(), ChunkLayout::Numeric);
+
+        let mut registry = BlockRegistry::with_air();
+        let chunk = decode_chunk_for_format(&mut registry, root, RegionFormat::McRegion).unwrap();
+        let name = |x, y, z| registry.get(chunk.get_id((x, y, z)).unwrap()).unwrap().name().to_owned();
+        assert_eq!(name(-16, 0, 48), "minecraft:bedrock");
+        assert_eq!(name(-15, 20, 50), "minecraft:torch");
+        assert_eq!(name(-15, 21, 50), "minecraft:air");
+    }
+}
@@ -16,23 +16,28 @@ pub struct BlockContainer {
 }
 
 impl BlockContainer {
+    /// Creates a container of the given size filled with air.
     pub fn new(size: (u16, u16, u16)) -> Self {
         let blocks = vec![0u32; size.0 as usize *size.1 as usize *size.2 as usize];
         Self {
             blocks: blocks.into_boxed_slice(),
             size,
-            block_registry: BlockRegistry::new(),
+            block_registry: BlockRegistry::with_air(),
         }
     }
 
+    pub fn block_registry(&self) -> &BlockRegistry {
+        &self.block_registry
+    }
+
     pub fn size<R: From<(u16, u16, u16)>>(&self) -> R {
         R::from(self.size)
     }
 
     fn block_index(&self, x: i64, y: i64, z: i64) -> Option<usize> {
-        if x > self.size.0 as i64 || x < 0
-        || y > self.size.1 as i64 || y < 0
-        || z > self.size.2 as i64 || z < 0 {
+        if x >= self.size.0 as i64 || x < 0
+        || y >= self.size.1 as i64 || y < 0
+        || z >= self.size.2 as i64 || z < 0 {
             return None;
         }
         let (xs, zs) = (self.size.0 as usize, self.size.2 as usize);
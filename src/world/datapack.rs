@@ -0,0 +1,162 @@
+//! Data pack scaffolding: creating a pack directory and writing resources into it.
+//!
+//! A data pack is a directory with a `pack.mcmeta` and a `data` directory with a folder for each namespace.
+//! Resources are addressed by resource locations such as `mypack:towers/small`, which are written to
+//! `data/<namespace>/<kind>/<path>.<extension>`. The kind is the resource type's folder, such as
+//! `worldgen/template_pool`. Data packs go in a world's `datapacks` directory.
+
+use std::path::{Path, PathBuf};
+
+use crate::{McError, McResult};
+
+use super::structure::StructureTemplate;
+
+/// The pack format of 24w21a, where the data pack folders were renamed to their singular form
+/// (such as `structures` to `structure`).
+const SINGULAR_FOLDERS_PACK_FORMAT: i32 = 45;
+
+/// A data pack directory.
+#[derive(Debug, Clone)]
+pub struct DataPack {
+    directory: PathBuf,
+    pack_format: i32,
+}
+
+/// Writes `text` as a quoted JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Splits a resource location into its namespace and path. The namespace defaults to `minecraft`.
+/// Returns an error if either part has characters that resource locations don't allow.
+pub fn split_resource_location(location: &str) -> McResult<(&str, &str)> {
+    let (namespace, path) = location.split_once(':').unwrap_or(("minecraft", location));
+    let valid_namespace = |c: char| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-' | '.');
+    let valid = !namespace.is_empty()
+        && !path.is_empty()
+        && namespace.chars().all(valid_namespace)
+        && path.chars().all(|c| valid_namespace(c) || c == '/')
+        && !path.split('/').any(|part| part.is_empty() || part == "." || part == "..");
+    if !valid {
+        return McError::custom(format!("Invalid resource location: {location}"));
+    }
+    Ok((namespace, path))
+}
+
+impl DataPack {
+    /// Creates a data pack at `directory` with a `pack.mcmeta` and an empty `data` directory.
+    /// An existing `pack.mcmeta` is overwritten, but the rest of the directory is left alone.
+    pub fn create<P: AsRef<Path>>(directory: P, description: &str, pack_format: i32) -> McResult<Self> {
+        let directory = directory.as_ref().to_owned();
+        std::fs::create_dir_all(directory.join("data"))?;
+        let mcmeta = format!(
+            "{{\n    \"pack\": {{\n        \"pack_format\": {pack_format},\n        \"description\": {}\n    }}\n}}\n",
+            json_string(description),
+        );
+        std::fs::write(directory.join("pack.mcmeta"), mcmeta)?;
+        Ok(Self { directory, pack_format })
+    }
+
+    /// Opens an existing data pack, reading its pack format from `pack.mcmeta`.
+    #[cfg(feature = "json")]
+    pub fn open<P: AsRef<Path>>(directory: P) -> McResult<Self> {
+        let directory = directory.as_ref().to_owned();
+        let mcmeta: serde_json::Value = serde_json::from_slice(&std::fs::read(directory.join("pack.mcmeta"))?)?;
+        let Some(pack_format) = mcmeta["pack"]["pack_format"].as_i64() else {
+            return Err(McError::NotFoundInCompound("pack_format".to_owned()));
+        };
+        Ok(Self { directory, pack_format: pack_format as i32 })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn pack_format(&self) -> i32 {
+        self.pack_format
+    }
+
+    /// The folder that structure templates go in, which depends on the pack format.
+    pub fn structures_folder(&self) -> &'static str {
+        if self.pack_format >= SINGULAR_FOLDERS_PACK_FORMAT {
+            "structure"
+        } else {
+            "structures"
+        }
+    }
+
+    /// The path of a resource, such as `data/mypack/worldgen/structure/tower.json` for
+    /// `resource_path("worldgen/structure", "mypack:tower", "json")`.
+    pub fn resource_path(&self, kind: &str, location: &str, extension: &str) -> McResult<PathBuf> {
+        let (namespace, path) = split_resource_location(location)?;
+        Ok(self.directory.join("data").join(namespace).join(kind).join(format!("{path}.{extension}")))
+    }
+
+    /// Creates the directories of a resource's path and returns it.
+    fn prepare(&self, kind: &str, location: &str, extension: &str) -> McResult<PathBuf> {
+        let path = self.resource_path(kind, location, extension)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+
+    /// Writes a structure template, so that it can be used by jigsaw pools or placed with `/place template`.
+    /// Returns the path that it was written to.
+    pub fn write_structure(&self, location: &str, template: &StructureTemplate) -> McResult<PathBuf> {
+        let path = self.prepare(self.structures_folder(), location, "nbt")?;
+        template.write_to_file(&path)?;
+        Ok(path)
+    }
+
+    /// Writes a JSON resource, such as a `worldgen/structure`, `worldgen/structure_set`, or
+    /// `worldgen/template_pool`. The JSON isn't validated. Returns the path that it was written to.
+    pub fn write_json(&self, kind: &str, location: &str, json: &str) -> McResult<PathBuf> {
+        let path = self.prepare(kind, location, "json")?;
+        std::fs::write(&path, json)?;
+        Ok(path)
+    }
+
+    /// Writes a JSON resource from a value, pretty printed.
+    #[cfg(feature = "json")]
+    pub fn write_json_value(&self, kind: &str, location: &str, json: &serde_json::Value) -> McResult<PathBuf> {
+        self.write_json(kind, location, &serde_json::to_string_pretty(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn datapack_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack = DataPack::create(dir.path().join("towers"), "Towers \"v1\"", 48).unwrap();
+        let mcmeta = std::fs::read_to_string(pack.directory().join("pack.mcmeta")).unwrap();
+        assert!(mcmeta.contains("\"pack_format\": 48") && mcmeta.contains("\"Towers \\\"v1\\\"\""));
+        let path = pack.write_structure("towers:tower/small", &StructureTemplate::new((1, 1, 1))).unwrap();
+        assert_eq!(path, pack.directory().join("data/towers/structure/tower/small.nbt"));
+        assert!(StructureTemplate::read_from_file(&path).is_ok());
+        let pool = pack.write_json("worldgen/template_pool", "towers:start", "{}").unwrap();
+        assert_eq!(pool, pack.directory().join("data/towers/worldgen/template_pool/start.json"));
+        assert!(pack.resource_path("worldgen/structure", "bad:../escape", "json").is_err());
+        assert!(pack.resource_path("worldgen/structure", "Upper:case", "json").is_err());
+        assert_eq!(DataPack::create(dir.path().join("old"), "", 15).unwrap().structures_folder(), "structures");
+        #[cfg(feature = "json")]
+        assert_eq!(DataPack::open(pack.directory()).unwrap().pack_format(), 48);
+    }
+}
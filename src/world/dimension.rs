@@ -0,0 +1,189 @@
+//! Custom dimensions, which data packs add under `dimensions/<namespace>/<name>` in the world directory.
+//!
+//! The vanilla dimensions have their own [Dimension] variants. Every other dimension is a [Dimension::Other]
+//! whose id is assigned by a [DimensionRegistry], which maps it to the dimension's namespaced name
+//! (such as `mypack:sky`) and from there to its directory.
+
+use std::path::{Path, PathBuf};
+
+use crate::{McResult, math::coord::Dimension};
+
+/// The folders that hold region files. A directory under `dimensions` that has any of them is a dimension.
+const REGION_FOLDERS: [&str; 3] = ["region", "entities", "poi"];
+
+/// The vanilla dimensions along with their namespaced names.
+const VANILLA_DIMENSIONS: [(Dimension, &str); 3] = [
+    (Dimension::Overworld, "minecraft:overworld"),
+    (Dimension::Nether, "minecraft:the_nether"),
+    (Dimension::TheEnd, "minecraft:the_end"),
+];
+
+/// The directory of a custom dimension within the world at `world_dir`.
+/// The namespace and the path of the name are split at the `:`, so `mypack:sky/islands` is
+/// `dimensions/mypack/sky/islands`. A name without a namespace is in the `minecraft` namespace.
+pub fn custom_dimension_directory<P: AsRef<Path>>(world_dir: P, name: &str) -> PathBuf {
+    let (namespace, path) = name.split_once(':').unwrap_or(("minecraft", name));
+    path.split('/').fold(world_dir.as_ref().join("dimensions").join(namespace), |directory, part| directory.join(part))
+}
+
+/// Assigns [Dimension::Other] ids to the namespaced names of custom dimensions.
+/// `Dimension::Other(n)` is the `n`th registered name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DimensionRegistry {
+    names: Vec<String>,
+}
+
+impl DimensionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the custom dimensions of the world at `world_dir`: every directory under `dimensions/<namespace>`
+    /// that has a `region`, `entities`, or `poi` folder. They are registered in order of their names,
+    /// so the same world always gets the same ids. There are none if the `dimensions` directory doesn't exist.
+    pub fn discover<P: AsRef<Path>>(world_dir: P) -> McResult<Self> {
+        let mut names = Vec::new();
+        let dimensions_dir = world_dir.as_ref().join("dimensions");
+        if dimensions_dir.is_dir() {
+            for entry in std::fs::read_dir(&dimensions_dir)? {
+                let entry = entry?;
+                if let (true, Some(namespace)) = (entry.file_type()?.is_dir(), entry.file_name().to_str()) {
+                    find_dimensions(&entry.path(), &format!("{namespace}:"), &mut names)?;
+                }
+            }
+        }
+        names.sort();
+        let mut registry = Self::new();
+        for name in names {
+            registry.register(name);
+        }
+        Ok(registry)
+    }
+
+    /// Gets the [Dimension] of a namespaced name, registering it if it isn't registered already.
+    /// The names of the vanilla dimensions (such as `minecraft:the_nether`) give their own variants.
+    pub fn register<S: Into<String>>(&mut self, name: S) -> Dimension {
+        let name = name.into();
+        if let Some(dimension) = self.get(&name) {
+            return dimension;
+        }
+        self.names.push(name);
+        Dimension::Other(self.names.len() as u32 - 1)
+    }
+
+    /// Gets the [Dimension] of a namespaced name if it's a vanilla dimension or has been registered.
+    pub fn get(&self, name: &str) -> Option<Dimension> {
+        VANILLA_DIMENSIONS.iter()
+            .find(|(_, vanilla)| *vanilla == name)
+            .map(|&(dimension, _)| dimension)
+            .or_else(|| self.names.iter()
+                .position(|registered| registered == name)
+                .map(|index| Dimension::Other(index as u32)))
+    }
+
+    /// Gets the namespaced name of a dimension, or `None` for a [Dimension::Other] that isn't registered.
+    pub fn name(&self, dimension: Dimension) -> Option<&str> {
+        match dimension {
+            Dimension::Other(id) => self.names.get(id as usize).map(String::as_str),
+            _ => VANILLA_DIMENSIONS.iter()
+                .find(|&&(vanilla, _)| vanilla == dimension)
+                .map(|&(_, name)| name),
+        }
+    }
+
+    /// The registered custom dimensions and their names, in order of their ids.
+    pub fn custom(&self) -> impl Iterator<Item = (Dimension, &str)> {
+        self.names.iter()
+            .enumerate()
+            .map(|(id, name)| (Dimension::Other(id as u32), name.as_str()))
+    }
+
+    /// Every dimension: the vanilla dimensions followed by the registered custom dimensions.
+    pub fn dimensions(&self) -> Vec<Dimension> {
+        VANILLA_DIMENSIONS.iter()
+            .map(|&(dimension, _)| dimension)
+            .chain(self.custom().map(|(dimension, _)| dimension))
+            .collect()
+    }
+
+    /// The root directory of a dimension within the world at `world_dir`,
+    /// or `None` for a [Dimension::Other] that isn't registered.
+    pub fn directory<P: AsRef<Path>>(&self, world_dir: P, dimension: Dimension) -> Option<PathBuf> {
+        let world_dir = world_dir.as_ref();
+        match dimension {
+            Dimension::Overworld => Some(world_dir.to_owned()),
+            Dimension::Nether => Some(world_dir.join("DIM-1")),
+            Dimension::TheEnd => Some(world_dir.join("DIM1")),
+            Dimension::Other(_) => self.name(dimension).map(|name| custom_dimension_directory(world_dir, name)),
+        }
+    }
+}
+
+/// Adds the names of the dimensions within `directory` to `names`, where `prefix` is the name of `directory`
+/// (`namespace:` or `namespace:path`). Vanilla dimensions that newer versions store here are skipped.
+fn find_dimensions(directory: &Path, prefix: &str, names: &mut Vec<String>) -> McResult<()> {
+    let mut subdirectories = Vec::new();
+    let mut is_dimension = false;
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let Some(file_name) = entry.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+        if REGION_FOLDERS.contains(&file_name.as_str()) {
+            is_dimension = true;
+        } else {
+            subdirectories.push((entry.path(), file_name));
+        }
+    }
+    if is_dimension {
+        // A namespace directory can't be a dimension itself, since the name would have no path.
+        if !prefix.ends_with(':') && !VANILLA_DIMENSIONS.iter().any(|&(_, vanilla)| vanilla == prefix) {
+            names.push(prefix.to_owned());
+        }
+        return Ok(());
+    }
+    for (path, file_name) in subdirectories {
+        let separator = if prefix.ends_with(':') { "" } else { "/" };
+        find_dimensions(&path, &format!("{prefix}{separator}{file_name}"), names)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{McError, math::coord::WorldCoord};
+    use crate::world::{chunk::tests::empty_chunk, io::region::{RegionFile, Timestamp}, world::VirtualJavaWorld};
+    use crate::nbt::tag::NamedTag;
+
+    #[test]
+    fn custom_dimension_test() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in ["dimensions/mypack/sky/region", "dimensions/mypack/deep/caves/entities", "dimensions/mypack/empty", "dimensions/minecraft/overworld/region"] {
+            std::fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        let registry = DimensionRegistry::discover(dir.path()).unwrap();
+        assert_eq!(registry.custom().collect::<Vec<_>>(), vec![(Dimension::Other(0), "mypack:deep/caves"), (Dimension::Other(1), "mypack:sky")]);
+        assert_eq!(registry.get("minecraft:the_nether"), Some(Dimension::Nether));
+        assert_eq!(registry.directory(dir.path(), Dimension::Other(0)), Some(dir.path().join("dimensions/mypack/deep/caves")));
+        assert_eq!(registry.directory(dir.path(), Dimension::Other(2)), None);
+
+        let mut world = VirtualJavaWorld::open(dir.path());
+        let sky = world.dimensions.get("mypack:sky").unwrap();
+        let chunk = NamedTag::new(empty_chunk(3, 4).to_nbt(&world.block_registry));
+        RegionFile::create(world.get_region_directory(sky).unwrap().join("r.0.0.mca")).unwrap().write_data((3, 4), &chunk).unwrap();
+        let coord = WorldCoord::new(3, 4, sky);
+        world.load_chunk(coord).unwrap();
+        world.get_chunk(coord).unwrap().lock().unwrap().mark_dirty();
+        world.save_all().unwrap();
+        assert!(RegionFile::open(dir.path().join("dimensions/mypack/sky/region/r.0.0.mca")).unwrap().get_timestamp((3, 4)) != Timestamp::default());
+
+        let custom = world.dimensions.register("otherpack:void");
+        assert_eq!(world.get_region_directory(custom).unwrap(), dir.path().join("dimensions/otherpack/void/region"));
+        let unregistered = WorldCoord::new(0, 0, Dimension::Other(100));
+        assert!(matches!(world.load_chunk(unregistered), Err(McError::UnknownDimension(Dimension::Other(100)))));
+    }
+}
@@ -0,0 +1,181 @@
+//! Entities, such as mobs, dropped items, and minecarts.
+//!
+//! Since 1.17, entities are saved in the `entities` region folder rather than in
+//! the chunk itself. Each chunk in those region files is decoded as an [EntityChunk].
+//! Older chunks keep their entities in the chunk's `Entities` list, which can be
+//! accessed with [Chunk::get_entities](super::chunk::Chunk::get_entities).
+
+use crate::{
+    nbt::{tag::*, Map},
+    McError, McResult,
+};
+
+macro_rules! map_decoder {
+    ($map:expr; $name:literal -> Option<$type:ty>) => {
+        if let Some(tag) = $map.remove($name) {
+            Some(<$type>::decode_nbt(tag)?)
+        } else {
+            None
+        }
+    };
+    ($map:expr; $name:literal -> $type:ty) => {
+        <$type>::decode_nbt($map.remove($name).ok_or(McError::NotFoundInCompound($name.to_owned()))?)?
+    };
+}
+
+/// An entity's NBT compound with accessors for the common tags.
+/// Tags that don't have accessors can be edited through [Entity::nbt_mut].
+#[derive(Debug, Clone)]
+pub struct Entity {
+    nbt: Map,
+}
+
+impl Entity {
+    /// Creates an entity with an `id` (such as `minecraft:zombie`) at a position.
+    pub fn new<S: AsRef<str>>(id: S, pos: (f64, f64, f64)) -> Self {
+        let mut entity = Self::from_map(Map::new());
+        entity.nbt.insert("id".to_owned(), Tag::string(id.as_ref()));
+        entity.set_pos(pos);
+        entity
+    }
+
+    pub fn from_map(nbt: Map) -> Self {
+        Self {
+            nbt,
+        }
+    }
+
+    pub fn into_map(self) -> Map {
+        self.nbt
+    }
+
+    /// The raw NBT of the entity.
+    pub fn nbt(&self) -> &Map {
+        &self.nbt
+    }
+
+    pub fn nbt_mut(&mut self) -> &mut Map {
+        &mut self.nbt
+    }
+
+    /// The entity type, such as `minecraft:zombie`.
+    pub fn id(&self) -> Option<&str> {
+        match self.nbt.get("id") {
+            Some(Tag::String(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The position of the entity (`Pos`).
+    pub fn pos(&self) -> Option<(f64, f64, f64)> {
+        match self.nbt.get("Pos") {
+            Some(Tag::List(ListTag::Double(pos))) if pos.len() == 3 => Some((pos[0], pos[1], pos[2])),
+            _ => None,
+        }
+    }
+
+    pub fn set_pos(&mut self, pos: (f64, f64, f64)) {
+        self.nbt.insert("Pos".to_owned(), Tag::List(ListTag::Double(vec![pos.0, pos.1, pos.2])));
+    }
+
+    /// The coordinate of the block that contains the entity's position.
+    pub fn block_coord(&self) -> Option<(i64, i64, i64)> {
+        self.pos().map(|(x, y, z)| (x.floor() as i64, y.floor() as i64, z.floor() as i64))
+    }
+
+    /// The entity's UUID as four integers, most significant first.
+    pub fn uuid(&self) -> Option<[i32; 4]> {
+        match self.nbt.get("UUID") {
+            Some(Tag::IntArray(uuid)) => uuid.as_slice().try_into().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl From<Map> for Entity {
+    fn from(value: Map) -> Self {
+        Self::from_map(value)
+    }
+}
+
+/// A chunk from the `entities` region folder.
+#[derive(Debug, Clone)]
+pub struct EntityChunk {
+    /// DataVersion
+    pub data_version: i32,
+    /// Position[0]
+    pub x: i32,
+    /// Position[1]
+    pub z: i32,
+    /// Entities
+    pub entities: Vec<Entity>,
+    /// All other unknown tags.
+    pub other: Map,
+}
+
+impl EntityChunk {
+    pub fn new(data_version: i32, x: i32, z: i32) -> Self {
+        Self {
+            data_version,
+            x,
+            z,
+            entities: Vec::new(),
+            other: Map::new(),
+        }
+    }
+}
+
+impl DecodeNbt for EntityChunk {
+    fn decode_nbt(nbt: Tag) -> McResult<Self> {
+        let Tag::Compound(mut map) = nbt else {
+            return Err(McError::NbtDecodeError);
+        };
+        let position = map_decoder!(map; "Position" -> Vec<i32>);
+        let [x, z] = position.as_slice() else {
+            return Err(McError::NbtDecodeError);
+        };
+        let entities = match map_decoder!(map; "Entities" -> Option<ListTag>) {
+            Some(ListTag::Compound(entities)) => entities.into_iter().map(Entity::from_map).collect(),
+            Some(ListTag::Empty) | None => Vec::new(),
+            Some(_) => return Err(McError::NbtDecodeError),
+        };
+        Ok(Self {
+            data_version: map_decoder!(map; "DataVersion" -> i32),
+            x: *x,
+            z: *z,
+            entities,
+            other: map,
+        })
+    }
+}
+
+impl EncodeNbt for EntityChunk {
+    fn encode_nbt(self) -> Tag {
+        let mut map = self.other;
+        map.insert("DataVersion".to_owned(), Tag::Int(self.data_version));
+        map.insert("Position".to_owned(), Tag::IntArray(vec![self.x, self.z]));
+        let entities = self.entities.into_iter()
+            .map(Entity::into_map)
+            .collect::<Vec<Map>>();
+        map.insert("Entities".to_owned(), Tag::List(ListTag::Compound(entities)));
+        Tag::Compound(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entity_chunk_test() {
+        let mut chunk = EntityChunk::new(3465, -2, 7);
+        chunk.entities.push(Entity::new("minecraft:zombie", (-20.5, 64.0, 120.25)));
+        let decoded = EntityChunk::decode_nbt(chunk.encode_nbt()).unwrap();
+        assert_eq!((decoded.x, decoded.z), (-2, 7));
+        assert_eq!(decoded.entities.len(), 1);
+        let zombie = &decoded.entities[0];
+        assert_eq!(zombie.id(), Some("minecraft:zombie"));
+        assert_eq!(zombie.block_coord(), Some((-21, 64, 120)));
+        assert_eq!(zombie.uuid(), None);
+    }
+}
@@ -0,0 +1,264 @@
+//! Surface heightmap export for regions and block selections.
+//!
+//! Heights are in heightmap units: the number of blocks from the bottom of the world to the top of the surface,
+//! so an empty column is 0. This is what the game stores in a chunk's `Heightmaps`, and it is never negative,
+//! which makes it suitable for 16-bit grayscale images. Heights can either be read from the stored heightmaps,
+//! which is cheap, or recomputed from the blocks, which works for chunks whose heightmaps are stale or missing.
+
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    math::{bounds::Bounds2, coord::*},
+    nbt::tag::NamedTag,
+    McError, McResult,
+};
+
+use super::{
+    block::HeightmapFlag,
+    blockregistry::BlockRegistry,
+    blockstate::BlockState,
+    chunk::{decode_versioned_chunk, Chunk, Heightmap},
+    io::region::{coord::RegionCoord, RegionFile},
+    world::VirtualJavaWorld,
+};
+
+/// The number of longs in a stored heightmap with 9 bits per column.
+const HEIGHTMAP_LONGS: usize = 37;
+
+/// Where the heights of a column come from.
+#[derive(Clone, Copy)]
+pub enum HeightSource<'a> {
+    /// One of the chunk's stored heightmaps. Chunks without a usable stored heightmap have a height of 0.
+    Stored(HeightmapFlag),
+    /// Recomputed from the blocks: one above the highest block that the function returns true for.
+    Recomputed(&'a dyn Fn(&BlockState) -> bool),
+}
+
+/// Recomputes heights from the highest block that isn't air, like the `WORLD_SURFACE` heightmap.
+pub const WORLD_SURFACE: HeightSource<'static> = HeightSource::Recomputed(&is_not_air);
+
+fn is_not_air(state: &BlockState) -> bool {
+    !matches!(state.name(), "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air")
+}
+
+/// The heights of one chunk, indexed by `z * 16 + x`.
+pub fn chunk_heights(chunk: &Chunk, registry: &BlockRegistry, source: HeightSource<'_>) -> [u16; 256] {
+    let mut heights = [0u16; 256];
+    match source {
+        HeightSource::Stored(flag) => {
+            let heightmap: &Heightmap = match flag {
+                HeightmapFlag::MotionBlocking => &chunk.heightmaps.motion_blocking,
+                HeightmapFlag::MotionBlockingNoLeaves => &chunk.heightmaps.motion_blocking_no_leaves,
+                HeightmapFlag::OceanFloor => &chunk.heightmaps.ocean_floor,
+                HeightmapFlag::WorldSurface => &chunk.heightmaps.world_surface,
+            };
+            if heightmap.map.len() < HEIGHTMAP_LONGS {
+                return heights;
+            }
+            for (index, height) in heights.iter_mut().enumerate() {
+                *height = heightmap.get(((index % 16) as i64, (index / 16) as i64)) as u16;
+            }
+        }
+        HeightSource::Recomputed(is_surface) => {
+            let bottom = chunk.y as i64 * 16;
+            let mut sections = chunk.sections.sections.iter()
+                .filter(|section| section.blocks.is_some())
+                .collect::<Vec<_>>();
+            sections.sort_by_key(|section| std::cmp::Reverse(section.y));
+            for (index, height) in heights.iter_mut().enumerate() {
+                let (x, z) = ((index % 16) as i64, (index / 16) as i64);
+                let top = sections.iter().find_map(|section| (0..16).rev().find(|&y| {
+                    section.get_id(x, y, z)
+                        .and_then(|id| registry.get(id))
+                        .is_some_and(is_surface)
+                }).map(|y| section.y as i64 * 16 + y));
+                *height = top.map_or(0, |top| (top + 1 - bottom).max(0) as u16);
+            }
+        }
+    }
+    heights
+}
+
+/// A grid of surface heights over an area of block columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeightGrid {
+    /// The block x and z of the grid's north-west corner.
+    pub origin: (i64, i64),
+    pub width: usize,
+    pub depth: usize,
+    /// Heights in rows from north to south, indexed by `z * width + x`.
+    pub heights: Vec<u16>,
+}
+
+impl HeightGrid {
+    /// Creates a grid of empty columns.
+    pub fn new(origin: (i64, i64), width: usize, depth: usize) -> Self {
+        Self {
+            origin,
+            width,
+            depth,
+            heights: vec![0; width * depth],
+        }
+    }
+
+    /// The height at a block column, or `None` if it's outside of the grid.
+    pub fn get(&self, x: i64, z: i64) -> Option<u16> {
+        let index = self.index(x, z)?;
+        Some(self.heights[index])
+    }
+
+    fn index(&self, x: i64, z: i64) -> Option<usize> {
+        let (x, z) = (x - self.origin.0, z - self.origin.1);
+        if x < 0 || z < 0 || x as usize >= self.width || z as usize >= self.depth {
+            return None;
+        }
+        Some(z as usize * self.width + x as usize)
+    }
+
+    /// The lowest and highest heights, or `None` if the grid is empty.
+    pub fn range(&self) -> Option<(u16, u16)> {
+        let min = self.heights.iter().copied().min()?;
+        let max = self.heights.iter().copied().max()?;
+        Some((min, max))
+    }
+
+    /// Copies the part of a chunk's heights that overlaps the grid.
+    fn copy_chunk(&mut self, chunk_coord: (i64, i64), heights: &[u16; 256]) {
+        for (index, &height) in heights.iter().enumerate() {
+            let x = chunk_coord.0 * 16 + (index % 16) as i64;
+            let z = chunk_coord.1 * 16 + (index / 16) as i64;
+            if let Some(index) = self.index(x, z) {
+                self.heights[index] = height;
+            }
+        }
+    }
+
+    /// Converts the grid into a 16-bit grayscale image, one pixel per column, with the heights as they are.
+    #[cfg(feature = "render")]
+    pub fn to_image(&self) -> image::ImageBuffer<image::Luma<u16>, Vec<u16>> {
+        image::ImageBuffer::from_fn(self.width as u32, self.depth as u32, |x, z| {
+            image::Luma([self.heights[z as usize * self.width + x as usize]])
+        })
+    }
+
+    /// Converts the grid into a 16-bit grayscale image with the heights stretched so that the lowest
+    /// is black and the highest is white.
+    #[cfg(feature = "render")]
+    pub fn to_normalized_image(&self) -> image::ImageBuffer<image::Luma<u16>, Vec<u16>> {
+        let (min, max) = self.range().unwrap_or((0, 0));
+        let span = (max - min).max(1) as u32;
+        image::ImageBuffer::from_fn(self.width as u32, self.depth as u32, |x, z| {
+            let height = self.heights[z as usize * self.width + x as usize];
+            image::Luma([((height - min) as u32 * u16::MAX as u32 / span) as u16])
+        })
+    }
+}
+
+/// Reads the heights of every chunk in a region file into a 512x512 grid.
+/// The grid's origin is taken from the file name (`r.<x>.<z>.mca`), or `(0, 0)` if it can't be parsed.
+pub fn region_heightmap<P: AsRef<Path>>(region_path: P, source: HeightSource<'_>) -> McResult<HeightGrid> {
+    let region_path = region_path.as_ref();
+    let (region_x, region_z) = super::io::region::filename::RegionFileName::from_path(region_path)
+        .map_or((0, 0), |name| (name.x, name.z));
+    let mut region = RegionFile::open(region_path)?;
+    let mut grid = HeightGrid::new((region_x * 512, region_z * 512), 512, 512);
+    let mut registry = BlockRegistry::with_air();
+    for index in 0..1024u16 {
+        let coord = RegionCoord::from(index);
+        if region.get_sector(coord).is_empty() {
+            continue;
+        }
+        let root: NamedTag = match region.read_data(coord) {
+            Ok(root) => root,
+            Err(McError::RegionDataNotFound) => continue,
+            Err(err) => return Err(err),
+        };
+        let chunk = decode_versioned_chunk(&mut registry, root.take_tag())?;
+        let chunk_coord = (region_x * 32 + coord.x() as i64, region_z * 32 + coord.z() as i64);
+        grid.copy_chunk(chunk_coord, &chunk_heights(&chunk, &registry, source));
+    }
+    Ok(grid)
+}
+
+impl VirtualJavaWorld {
+    /// Reads the heights of the block columns within `bounds` (inclusive block x and z coordinates).
+    /// Loaded chunks are read from memory, and other chunks are read from the region files without being loaded.
+    /// Columns in chunks that haven't been generated have a height of 0.
+    pub fn heightmap<T: Into<Bounds2>>(&self, dimension: Dimension, bounds: T, source: HeightSource<'_>) -> McResult<HeightGrid> {
+        let bounds: Bounds2 = bounds.into();
+        let size = bounds.size::<glam::I64Vec2>();
+        let mut grid = HeightGrid::new((bounds.min.x, bounds.min.y), size.x as usize, size.y as usize);
+        let mut registry = BlockRegistry::with_air();
+        let mut regions: HashMap<WorldCoord, Option<RegionFile>> = HashMap::new();
+        for chunk_z in bounds.min.y.div_euclid(16)..=bounds.max.y.div_euclid(16) {
+            for chunk_x in bounds.min.x.div_euclid(16)..=bounds.max.x.div_euclid(16) {
+                let coord = WorldCoord::new(chunk_x, chunk_z, dimension);
+                let heights = if let Some(slot) = self.chunks.get(&coord) {
+                    let slot = slot.lock().map_err(|_| McError::Custom("Failed to lock chunk.".to_owned()))?;
+                    chunk_heights(&slot.chunk, &self.block_registry, source)
+                } else {
+                    let region_coord = coord.region_coord();
+                    let region = match regions.entry(region_coord) {
+                        std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                        std::collections::hash_map::Entry::Vacant(entry) => {
+                            let path = self.get_region_directory(dimension)?
+                                .join(region_coord.region_file_name());
+                            entry.insert(if path.is_file() { Some(RegionFile::open(path)?) } else { None })
+                        }
+                    };
+                    let Some(region) = region else {
+                        continue;
+                    };
+                    let root: NamedTag = match region.read_data(coord.xz()) {
+                        Ok(root) => root,
+                        Err(McError::RegionDataNotFound) => continue,
+                        Err(err) => return Err(err),
+                    };
+                    let chunk = decode_versioned_chunk(&mut registry, root.take_tag())?;
+                    chunk_heights(&chunk, &registry, source)
+                };
+                grid.copy_chunk((chunk_x, chunk_z), &heights);
+            }
+        }
+        Ok(grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{chunk::tests::empty_chunk, world::ChunkSlot};
+
+    #[test]
+    fn heightmap_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        for x in 0..2 {
+            world.chunks.insert(WorldCoord::overworld(x, 0), ChunkSlot::arc_new(empty_chunk(x as i32, 0)));
+        }
+        let bottom = world.get_chunk(WorldCoord::overworld(0, 0)).unwrap().lock().unwrap().chunk.y as i64 * 16;
+        world.set_state(BlockCoord::overworld(15, bottom + 4, 0), BlockState::from("minecraft:stone"));
+        world.set_state(BlockCoord::overworld(16, bottom, 1), BlockState::from("minecraft:stone"));
+        {
+            let slot = world.get_chunk(WorldCoord::overworld(1, 0)).unwrap();
+            slot.lock().unwrap().chunk.set_heightmap(HeightmapFlag::WorldSurface, 0, 1, 9);
+        }
+        let grid = world.heightmap(Dimension::Overworld, ((14, 0), (17, 1)), WORLD_SURFACE).unwrap();
+        assert_eq!((grid.origin, grid.width, grid.depth), ((14, 0), 4, 2));
+        assert_eq!(grid.heights, [0, 5, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(grid.range(), Some((0, 5)));
+        #[cfg(feature = "render")]
+        assert_eq!(grid.to_normalized_image().get_pixel(1, 0).0, [u16::MAX]);
+        let stored = world.heightmap(Dimension::Overworld, ((14, 0), (17, 1)), HeightSource::Stored(HeightmapFlag::WorldSurface)).unwrap();
+        assert_eq!(stored.get(16, 1), Some(9));
+        assert_eq!(stored.get(18, 1), None);
+
+        world.save_all().unwrap();
+        world.unload_chunk(WorldCoord::overworld(0, 0));
+        let region = region_heightmap(world.get_region_directory(Dimension::Overworld).unwrap().join("r.0.0.mca"), WORLD_SURFACE).unwrap();
+        assert_eq!(region.get(15, 0), Some(5));
+        assert_eq!(region.get(16, 1), Some(1));
+        assert_eq!(world.heightmap(Dimension::Overworld, ((15, 0), (15, 0)), WORLD_SURFACE).unwrap().heights, [5]);
+    }
+}
@@ -0,0 +1,319 @@
+//! An index of the chunks in a world, so that tools can find out which chunks exist without
+//! opening every region file.
+//!
+//! The index is saved in the world directory as [INDEX_FILE_NAME]. It records each region file's
+//! modification time and size, so that [WorldIndex::update] only rescans the region files that changed.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{McError, McResult, ResultExt, ioext::*, math::coord::{Dimension, WorldCoord}};
+
+use super::{
+    dimension::DimensionRegistry,
+    io::region::{RegionCoord, RegionFile, Timestamp},
+    iter::RegionIter,
+};
+
+/// The name of the index file in the world directory.
+pub const INDEX_FILE_NAME: &str = ".mcutil-index";
+
+/// Identifies an index file.
+const MAGIC: [u8; 4] = *b"MCIX";
+/// The version of the index format, which is bumped whenever the format changes.
+const VERSION: u8 = 1;
+
+/// The path of the index file of the world at `world_dir`.
+pub fn index_path<P: AsRef<Path>>(world_dir: P) -> PathBuf {
+    world_dir.as_ref().join(INDEX_FILE_NAME)
+}
+
+/// What the index knows about a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedChunk {
+    /// The time that the chunk was last saved.
+    pub timestamp: Timestamp,
+    /// The number of 4KiB sectors that the chunk takes up in its region file.
+    pub sector_count: u8,
+}
+
+impl IndexedChunk {
+    /// The number of bytes that the chunk takes up in its region file.
+    /// Chunks stored in external `.mcc` files only count the sector that is left in the region file.
+    pub fn size(self) -> u64 {
+        self.sector_count as u64 * 4096
+    }
+}
+
+/// The chunks of a region file, along with what the file looked like when it was scanned.
+#[derive(Debug, Clone)]
+struct IndexedRegion {
+    /// The modification time of the file in nanoseconds since the Unix epoch.
+    modified: u64,
+    file_size: u64,
+    /// The present chunks, sorted by their index within the region.
+    chunks: Vec<(u16, IndexedChunk)>,
+}
+
+/// The modification time and size of a file, which tell whether a region has to be rescanned.
+fn file_state(path: &Path) -> McResult<(u64, u64)> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata.modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos() as u64);
+    Ok((modified, metadata.len()))
+}
+
+/// A compact index of which chunks exist in a world's `region` folders, along with their timestamps and sizes.
+///
+/// Answering questions from the index doesn't touch the region files, so the index is only as current
+/// as the last [WorldIndex::update]. Chunks that are loaded in a [VirtualJavaWorld](super::world::VirtualJavaWorld)
+/// but haven't been saved aren't included.
+#[derive(Debug, Clone)]
+pub struct WorldIndex {
+    directory: PathBuf,
+    /// The custom dimensions that [Dimension::Other] ids refer to.
+    pub dimensions: DimensionRegistry,
+    regions: HashMap<WorldCoord, IndexedRegion>,
+}
+
+impl WorldIndex {
+    /// Creates an empty index for the world at `world_dir`.
+    pub fn new<P: AsRef<Path>>(world_dir: P) -> McResult<Self> {
+        let directory = world_dir.as_ref().to_owned();
+        Ok(Self {
+            dimensions: DimensionRegistry::discover(&directory)?,
+            regions: HashMap::new(),
+            directory,
+        })
+    }
+
+    /// Scans every region file of the world at `world_dir`. The index isn't saved.
+    pub fn build<P: AsRef<Path>>(world_dir: P) -> McResult<Self> {
+        let mut index = Self::new(world_dir)?;
+        index.update()?;
+        Ok(index)
+    }
+
+    /// Loads the world's index file and [updates](WorldIndex::update) it, then saves it if anything changed.
+    /// If the world doesn't have an index file yet, or it can't be read, the index is built from scratch.
+    pub fn open<P: AsRef<Path>>(world_dir: P) -> McResult<Self> {
+        let world_dir = world_dir.as_ref();
+        let (mut index, loaded) = match Self::load(world_dir) {
+            Ok(index) => (index, true),
+            Err(_) => (Self::new(world_dir)?, false),
+        };
+        if index.update()? > 0 || !loaded {
+            index.save()?;
+        }
+        Ok(index)
+    }
+
+    /// Reads the world's index file without updating it.
+    pub fn load<P: AsRef<Path>>(world_dir: P) -> McResult<Self> {
+        let mut index = Self::new(&world_dir)?;
+        let path = index_path(&world_dir);
+        index.read_from(&mut BufReader::new(File::open(&path)?)).with_path(path)?;
+        Ok(index)
+    }
+
+    fn read_from<R: Read>(&mut self, reader: &mut R) -> McResult<()> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(McError::Custom("Not a world index file.".to_owned()));
+        }
+        let version: u8 = reader.read_value()?;
+        if version != VERSION {
+            return Err(McError::Custom(format!("Unsupported world index version {version}.")));
+        }
+        let dimension_count: u32 = reader.read_value()?;
+        for _ in 0..dimension_count {
+            let name_len: u16 = reader.read_value()?;
+            let mut name = vec![0u8; name_len as usize];
+            reader.read_exact(&mut name)?;
+            let name = String::from_utf8(name).map_err(|_| McError::Custom("Invalid dimension name in world index.".to_owned()))?;
+            let dimension = self.dimensions.register(name);
+            let region_count: u32 = reader.read_value()?;
+            for _ in 0..region_count {
+                let x: i64 = reader.read_value()?;
+                let z: i64 = reader.read_value()?;
+                let modified: u64 = reader.read_value()?;
+                let file_size: u64 = reader.read_value()?;
+                let chunk_count: u16 = reader.read_value()?;
+                let mut chunks = Vec::with_capacity(chunk_count as usize);
+                for _ in 0..chunk_count {
+                    let index: u16 = reader.read_value()?;
+                    let timestamp: u32 = reader.read_value()?;
+                    let sector_count: u8 = reader.read_value()?;
+                    chunks.push((index, IndexedChunk { timestamp: Timestamp::from(timestamp), sector_count }));
+                }
+                self.regions.insert(WorldCoord::new(x, z, dimension), IndexedRegion { modified, file_size, chunks });
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the index to the world's index file. The file is replaced all at once, so a failed save
+    /// leaves the previous index in place.
+    pub fn save(&self) -> McResult<()> {
+        let path = index_path(&self.directory);
+        let temp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        self.write_to(&mut writer).with_path(&temp_path)?;
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> McResult<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_value(VERSION)?;
+        let mut by_dimension = HashMap::<Dimension, Vec<(&WorldCoord, &IndexedRegion)>>::new();
+        for (coord, region) in &self.regions {
+            by_dimension.entry(coord.dimension).or_default().push((coord, region));
+        }
+        writer.write_value(by_dimension.len() as u32)?;
+        for (dimension, regions) in by_dimension {
+            let Some(name) = self.dimensions.name(dimension) else {
+                return Err(McError::UnknownDimension(dimension));
+            };
+            writer.write_value(name.len() as u16)?;
+            writer.write_all(name.as_bytes())?;
+            writer.write_value(regions.len() as u32)?;
+            for (coord, region) in regions {
+                writer.write_value(coord.x)?;
+                writer.write_value(coord.z)?;
+                writer.write_value(region.modified)?;
+                writer.write_value(region.file_size)?;
+                writer.write_value(region.chunks.len() as u16)?;
+                for &(index, chunk) in &region.chunks {
+                    writer.write_value(index)?;
+                    writer.write_value(u32::from(chunk.timestamp))?;
+                    writer.write_value(chunk.sector_count)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Brings the index up to date with the region files. Region files whose modification time or size
+    /// changed since they were indexed are rescanned, new region files are scanned, and the regions of
+    /// region files that no longer exist are forgotten. Custom dimensions that were added to the world are found as well.
+    /// Returns the number of regions that were rescanned or forgotten.
+    pub fn update(&mut self) -> McResult<usize> {
+        for (_, name) in DimensionRegistry::discover(&self.directory)?.custom() {
+            self.dimensions.register(name);
+        }
+        let mut changed = 0;
+        let mut seen = HashSet::new();
+        for dimension in self.dimensions.dimensions() {
+            let Some(directory) = self.dimensions.directory(&self.directory, dimension) else {
+                continue;
+            };
+            let directory = directory.join("region");
+            for coord in RegionIter::new(&directory, dimension)? {
+                seen.insert(coord);
+                let path = directory.join(coord.region_file_name());
+                let (modified, file_size) = file_state(&path)?;
+                if self.regions.get(&coord).is_some_and(|region| region.modified == modified && region.file_size == file_size) {
+                    continue;
+                }
+                let region = RegionFile::open_read_only(&path).with_path(&path)?;
+                let chunks = (0..1024u16)
+                    .filter_map(|index| {
+                        let sector = region.get_sector(RegionCoord::from(index));
+                        (sector.sector_count() != 0).then(|| (index, IndexedChunk {
+                            timestamp: region.get_timestamp(RegionCoord::from(index)),
+                            sector_count: sector.sector_count() as u8,
+                        }))
+                    })
+                    .collect();
+                self.regions.insert(coord, IndexedRegion { modified, file_size, chunks });
+                changed += 1;
+            }
+        }
+        let count = self.regions.len();
+        self.regions.retain(|coord, _| seen.contains(coord));
+        Ok(changed + count - self.regions.len())
+    }
+
+    /// Gets what the index knows about a chunk, or `None` if the chunk doesn't exist.
+    pub fn get_chunk(&self, coord: WorldCoord) -> Option<IndexedChunk> {
+        let region = self.regions.get(&coord.region_coord())?;
+        let index = RegionCoord::from(coord.xz()).index() as u16;
+        region.chunks.binary_search_by_key(&index, |&(index, _)| index)
+            .ok()
+            .map(|position| region.chunks[position].1)
+    }
+
+    /// Returns true if the chunk exists.
+    pub fn contains_chunk(&self, coord: WorldCoord) -> bool {
+        self.get_chunk(coord).is_some()
+    }
+
+    /// The chunks that exist in a dimension, in no particular order.
+    pub fn chunks(&self, dimension: Dimension) -> impl Iterator<Item = (WorldCoord, IndexedChunk)> + '_ {
+        self.regions.iter()
+            .filter(move |(coord, _)| coord.dimension == dimension)
+            .flat_map(|(coord, region)| region.chunks.iter()
+                .map(|&(index, chunk)| (coord.chunk_in_region(RegionCoord::from(index)), chunk)))
+    }
+
+    /// The number of chunks in the index.
+    pub fn chunk_count(&self) -> usize {
+        self.regions.values().map(|region| region.chunks.len()).sum()
+    }
+
+    /// The number of region files in the index.
+    pub fn region_count(&self) -> usize {
+        self.regions.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+
+    #[test]
+    fn world_index_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let region_dir = dir.path().join("region");
+        std::fs::create_dir_all(&region_dir).unwrap();
+        let chunk = NamedTag::new(Tag::String("index".repeat(1000)));
+        let mut region = RegionFile::create(region_dir.join("r.0.0.mca")).unwrap();
+        region.write_data_timestamped((1, 2), &chunk, 1234u32).unwrap();
+        drop(region);
+        RegionFile::create(region_dir.join("r.-1.0.mca")).unwrap().write_data((31, 0), &chunk).unwrap();
+
+        let index = WorldIndex::open(dir.path()).unwrap();
+        assert!(index_path(dir.path()).is_file());
+        assert_eq!(index.chunk_count(), 2);
+        let indexed = index.get_chunk(WorldCoord::overworld(1, 2)).unwrap();
+        assert_eq!(indexed.timestamp, Timestamp::from(1234u32));
+        assert_eq!(indexed.size(), 4096);
+        assert!(index.contains_chunk(WorldCoord::overworld(-1, 0)));
+        assert!(!index.contains_chunk(WorldCoord::overworld(2, 1)));
+
+        // Only the regions that changed are rescanned.
+        RegionFile::open(region_dir.join("r.0.0.mca")).unwrap().write_data((2, 1), &chunk).unwrap();
+        std::fs::remove_file(region_dir.join("r.-1.0.mca")).unwrap();
+        let mut index = WorldIndex::load(dir.path()).unwrap();
+        assert_eq!(index.chunk_count(), 2);
+        assert_eq!(index.update().unwrap(), 2);
+        assert_eq!(index.update().unwrap(), 0);
+        assert!(index.contains_chunk(WorldCoord::overworld(2, 1)));
+        assert!(!index.contains_chunk(WorldCoord::overworld(-1, 0)));
+        let mut chunks = index.chunks(Dimension::Overworld).map(|(coord, _)| coord.xz()).collect::<Vec<_>>();
+        chunks.sort();
+        assert_eq!(chunks, vec![(1, 2), (2, 1)]);
+    }
+}
@@ -0,0 +1,297 @@
+//! An async version of [RegionFile] built on [tokio::fs::File].
+//! This module is only available with the `tokio` feature.
+
+use std::{
+    io::{Cursor, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{
+    write::ZlibEncoder,
+    Compression,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+
+use crate::{
+    McResult, McError,
+    ioext::*,
+};
+
+use super::{
+    prelude::*,
+    {required_sectors, pad_size, external_chunk_path},
+};
+
+/// Removes an external chunk file if it exists.
+async fn remove_external_chunk(path: &Path) -> McResult<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// An async counterpart to [RegionFile] for reading and writing chunks without blocking.
+/// Compression and decompression happen in memory on the calling task; only the file IO is async.
+/// Like [RegionFile], an [AsyncRegionFile] assumes that it is the only thing modifying the file.
+pub struct AsyncRegionFile {
+    header: RegionHeader,
+    sector_manager: SectorManager,
+    file_handle: File,
+    path: PathBuf,
+    pub compression: Compression,
+}
+
+impl AsyncRegionFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn header(&self) -> &RegionHeader {
+        &self.header
+    }
+
+    pub fn get_sector<C: Into<RegionCoord>>(&self, coord: C) -> RegionSector {
+        self.header.sectors[coord]
+    }
+
+    pub fn get_timestamp<C: Into<RegionCoord>>(&self, coord: C) -> Timestamp {
+        self.header.timestamps[coord]
+    }
+
+    /// Attempts to open a Minecraft region file at the given path, returning an error if it is not found.
+    pub async fn open<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let path = path.as_ref();
+        let mut file_handle = File::options()
+            .read(true).write(true)
+            .open(path).await?;
+        if file_handle.metadata().await?.len() < 8192 {
+            return Err(McError::InvalidRegionFile);
+        }
+        let mut raw_header = vec![0u8; 8192];
+        file_handle.read_exact(&mut raw_header).await?;
+        let header = RegionHeader::read_from(&mut raw_header.as_slice())?;
+        let sector_manager = SectorManager::from(header.sectors.iter());
+        Ok(Self {
+            header,
+            sector_manager,
+            file_handle,
+            path: path.to_owned(),
+            compression: Compression::best(),
+        })
+    }
+
+    /// Attempts to create a new Minecraft region file at the given path, returning an error if it already exists.
+    pub async fn create<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let path = path.as_ref();
+        let mut file_handle = File::options()
+            .read(true).write(true)
+            .create_new(true)
+            .open(path).await?;
+        file_handle.write_all(&[0u8; 8192]).await?;
+        file_handle.flush().await?;
+        Ok(Self {
+            header: RegionHeader::default(),
+            sector_manager: SectorManager::new(),
+            file_handle,
+            path: path.to_owned(),
+            compression: Compression::best(),
+        })
+    }
+
+    /// Opens the region file at the given path, or creates it if it doesn't exist.
+    pub async fn open_or_create<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let path = path.as_ref();
+        if tokio::fs::metadata(path).await.map(|meta| meta.is_file()).unwrap_or(false) {
+            Self::open(path).await
+        } else {
+            Self::create(path).await
+        }
+    }
+
+    /// Reads the data for a chunk.
+    /// Returns [McError::RegionDataNotFound] if the chunk isn't present.
+    pub async fn read_data<C: Into<RegionCoord>, T: Readable>(&mut self, coord: C) -> McResult<T> {
+        let coord: RegionCoord = coord.into();
+        let sector = self.header.sectors[coord];
+        if sector.sector_count() == 0 {
+            return Err(McError::RegionDataNotFound);
+        }
+        self.file_handle.seek(SeekFrom::Start(sector.offset())).await?;
+        let length = self.file_handle.read_u32().await?;
+        if length == 0 {
+            return Err(McError::RegionDataNotFound);
+        }
+        let mut data = vec![0u8; length as usize];
+        self.file_handle.read_exact(&mut data).await?;
+        let mut reader = data.as_slice();
+        let (scheme, external) = CompressionScheme::read_with_external_flag(&mut reader)?;
+        if external {
+            let path = external_chunk_path(&self.path, coord).ok_or(McError::ExternalChunkPathUnknown(coord))?;
+            let data = tokio::fs::read(path).await?;
+            let mut decoder = scheme.decoder(data.as_slice())?;
+            return T::read_from(&mut decoder);
+        }
+        let mut decoder = scheme.decoder(reader)?;
+        T::read_from(&mut decoder)
+    }
+
+    /// Writes data to the region file with the `utc_now` timestamp
+    /// and returns the [RegionSector] where it was written.
+    pub async fn write_data<C: Into<RegionCoord>, T: Writable>(&mut self, coord: C, value: &T) -> McResult<RegionSector> {
+        self.write_data_timestamped(coord, value, Timestamp::utc_now()).await
+    }
+
+    /// Writes data to the region file with the given timestamp
+    /// and returns the [RegionSector] where it was written.
+    /// Like [RegionFile], chunks that need more than 255 sectors are moved to an external chunk file.
+    pub async fn write_data_timestamped<C: Into<RegionCoord>, T: Writable, Ts: Into<Timestamp>>(&mut self, coord: C, value: &T, timestamp: Ts) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        let timestamp: Timestamp = timestamp.into();
+        let mut buffer = Cursor::new(Vec::with_capacity(4096*2));
+        // Room for the length and compression scheme.
+        Write::write_all(&mut buffer, &[0u8; 5])?;
+        let mut encoder = ZlibEncoder::new(&mut buffer, self.compression);
+        value.write_to(&mut encoder)?;
+        encoder.finish()?;
+        let scheme = CompressionScheme::ZLib;
+        // The length includes the compression scheme but not the length bytes.
+        let mut length = buffer.get_ref().len() - 4;
+        let external_path = external_chunk_path(&self.path, coord);
+        if required_sectors((length + 4) as u32) > 255 {
+            // If the external chunk file can't be named, there's no way to write it.
+            let Some(external_path) = external_path else {
+                return Err(McError::RegionDataTooLarge);
+            };
+            // The data goes to the external file, and only the flagged compression scheme stays in the region file.
+            tokio::fs::write(&external_path, &buffer.get_ref()[5..]).await?;
+            buffer.get_mut().truncate(4);
+            buffer.set_position(4);
+            scheme.write_external_to(&mut buffer)?;
+            length = scheme.header_len() as usize;
+        } else {
+            if let Some(external_path) = external_path {
+                // The chunk may have been stored externally before.
+                remove_external_chunk(&external_path).await?;
+            }
+            buffer.set_position(4);
+            buffer.write_value(scheme)?;
+        }
+        let required_sectors = required_sectors((length + 4) as u32);
+        buffer.set_position((length + 4) as u64);
+        buffer.write_zeroes(pad_size((length + 4) as u64))?;
+        buffer.set_position(0);
+        buffer.write_value(length as u32)?;
+        let old_sector = self.header.sectors[coord];
+        let new_sector = self.sector_manager.reallocate_err(old_sector, required_sectors as u8)?;
+        self.header.sectors[coord] = new_sector;
+        self.header.timestamps[coord] = timestamp;
+        self.file_handle.seek(SeekFrom::Start(new_sector.offset())).await?;
+        self.file_handle.write_all(buffer.get_ref()).await?;
+        self.write_table_entries(coord).await?;
+        Ok(new_sector)
+    }
+
+    /// Deletes a chunk from the region file, returning the sector that it occupied.
+    pub async fn delete_data<C: Into<RegionCoord>>(&mut self, coord: C) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        let sector = self.header.sectors[coord];
+        if sector.is_empty() {
+            return Ok(sector);
+        }
+        if !sector.is_degenerate() {
+            self.sector_manager.deallocate(sector);
+        }
+        self.header.sectors[coord] = RegionSector::default();
+        self.header.timestamps[coord] = Timestamp::default();
+        if let Some(external_path) = external_chunk_path(&self.path, coord) {
+            remove_external_chunk(&external_path).await?;
+        }
+        self.write_table_entries(coord).await?;
+        Ok(sector)
+    }
+
+    /// Writes the sector and timestamp for `coord` from the in-memory header to the file.
+    async fn write_table_entries(&mut self, coord: RegionCoord) -> McResult<()> {
+        let mut entry = Vec::with_capacity(4);
+        self.header.sectors[coord].write_to(&mut entry)?;
+        self.file_handle.seek(coord.sector_table_offset()).await?;
+        self.file_handle.write_all(&entry).await?;
+        entry.clear();
+        self.header.timestamps[coord].write_to(&mut entry)?;
+        self.file_handle.seek(coord.timestamp_table_offset()).await?;
+        self.file_handle.write_all(&entry).await?;
+        self.file_handle.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+
+    #[test]
+    fn async_region_file_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build().unwrap();
+        runtime.block_on(async {
+            let mut region = AsyncRegionFile::create(&path).await.unwrap();
+            region.write_data((1u16, 2u16), &NamedTag::new(Tag::Int(12))).await.unwrap();
+            region.write_data_timestamped((3u16, 4u16), &NamedTag::new(Tag::Int(34)), 1234).await.unwrap();
+            region.write_data((1u16, 2u16), &NamedTag::new(Tag::String("a".repeat(10000)))).await.unwrap();
+            region.delete_data((3u16, 4u16)).await.unwrap();
+            drop(region);
+
+            let mut region = AsyncRegionFile::open(&path).await.unwrap();
+            let tag: NamedTag = region.read_data((1u16, 2u16)).await.unwrap();
+            assert!(matches!(tag.tag(), Tag::String(text) if text.len() == 10000));
+            assert!(matches!(region.read_data::<_, NamedTag>((3u16, 4u16)).await, Err(McError::RegionDataNotFound)));
+        });
+        // The blocking RegionFile can read what the AsyncRegionFile wrote.
+        let mut region = RegionFile::open(&path).unwrap();
+        let tag: NamedTag = region.read_data((1u16, 2u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::String(text) if text.len() == 10000));
+    }
+
+    #[test]
+    fn async_external_chunk_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.1.-1.mca");
+        let external_path = dir.path().join("c.34.-29.mcc");
+        // Data that doesn't compress, so that it needs more than 255 sectors.
+        let mut state = 1u32;
+        let noise = (0..256 * 4096).map(|_| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 24) as i8
+        }).collect::<Vec<_>>();
+        let large = NamedTag::new(crate::compound! {
+            ("data", noise),
+        });
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build().unwrap();
+        runtime.block_on(async {
+            let mut region = AsyncRegionFile::create(&path).await.unwrap();
+            let sector = region.write_data((2u16, 3u16), &large).await.unwrap();
+            assert_eq!(sector.sector_count(), 1);
+            assert!(external_path.is_file());
+            let read: NamedTag = region.read_data((2u16, 3u16)).await.unwrap();
+            assert_eq!(read.tag().content_hash(), large.tag().content_hash());
+            // Writing a chunk that fits removes the external chunk file.
+            region.write_data((2u16, 3u16), &NamedTag::new(Tag::Int(0))).await.unwrap();
+            assert!(!external_path.exists());
+            region.write_data((2u16, 3u16), &large).await.unwrap();
+            region.delete_data((2u16, 3u16)).await.unwrap();
+            assert!(!external_path.exists());
+            region.write_data((2u16, 3u16), &large).await.unwrap();
+        });
+        // The blocking RegionFile can read the external chunk.
+        let mut region = RegionFile::open(&path).unwrap();
+        let read: NamedTag = region.read_data((2u16, 3u16)).unwrap();
+        assert_eq!(read.tag().content_hash(), large.tag().content_hash());
+    }
+}
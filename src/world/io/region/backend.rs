@@ -0,0 +1,113 @@
+//! The storage that a [RegionFile](super::RegionFile) reads from and writes to.
+
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+};
+
+use crate::McResult;
+
+/// Where the bytes of a region file are stored.
+#[derive(Debug)]
+pub enum RegionBackend {
+    /// A file on disk.
+    File(File),
+    /// A buffer in memory, for regions that don't come from (or aren't written to) the file system.
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl RegionBackend {
+    /// Creates an in-memory backend containing `data`.
+    pub fn memory(data: Vec<u8>) -> Self {
+        Self::Memory(Cursor::new(data))
+    }
+
+    /// The size of the region in bytes.
+    pub fn len(&self) -> McResult<u64> {
+        match self {
+            RegionBackend::File(file) => Ok(file.metadata()?.len()),
+            RegionBackend::Memory(cursor) => Ok(cursor.get_ref().len() as u64),
+        }
+    }
+
+    /// Returns true if the region has a size of 0.
+    pub fn is_empty(&self) -> McResult<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Truncates or extends the region (with zeroes) to `size` bytes.
+    pub fn set_len(&mut self, size: u64) -> McResult<()> {
+        match self {
+            RegionBackend::File(file) => file.set_len(size)?,
+            RegionBackend::Memory(cursor) => cursor.get_mut().resize(size as usize, 0),
+        }
+        Ok(())
+    }
+
+    /// Waits until the data written to a file has reached the disk. This does nothing for regions in memory.
+    pub fn sync_data(&mut self) -> McResult<()> {
+        if let RegionBackend::File(file) = self {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Reads the whole region into a buffer, or returns the buffer of an in-memory region.
+    pub fn into_bytes(self) -> McResult<Vec<u8>> {
+        match self {
+            RegionBackend::File(mut file) => {
+                let mut data = Vec::new();
+                file.seek(SeekFrom::Start(0))?;
+                file.read_to_end(&mut data)?;
+                Ok(data)
+            }
+            RegionBackend::Memory(cursor) => Ok(cursor.into_inner()),
+        }
+    }
+}
+
+impl Read for RegionBackend {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            RegionBackend::File(file) => file.read(buf),
+            RegionBackend::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Write for RegionBackend {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            RegionBackend::File(file) => file.write(buf),
+            RegionBackend::Memory(cursor) => cursor.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            RegionBackend::File(file) => file.flush(),
+            RegionBackend::Memory(cursor) => cursor.flush(),
+        }
+    }
+}
+
+impl Seek for RegionBackend {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            RegionBackend::File(file) => file.seek(pos),
+            RegionBackend::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+impl From<File> for RegionBackend {
+    fn from(value: File) -> Self {
+        Self::File(value)
+    }
+}
+
+impl From<Vec<u8>> for RegionBackend {
+    fn from(value: Vec<u8>) -> Self {
+        Self::memory(value)
+    }
+}
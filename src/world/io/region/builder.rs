@@ -0,0 +1,202 @@
+//! A builder for creating a new region file from an existing one with some chunks replaced.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use flate2::Compression;
+
+use crate::{
+    McResult,
+    ioext::*,
+    nbt::tag::NamedTag,
+};
+
+use super::prelude::*;
+
+/// Builds a new region file, optionally based on an existing region file.
+/// Chunks that aren't inserted or removed are copied from the source
+/// file verbatim (without being recompressed) along with their timestamps.
+/// The source file is never modified.
+pub struct RegionBuilder {
+    source: Option<PathBuf>,
+    /// `None` means that the chunk is removed.
+    chunks: HashMap<RegionCoord, Option<NamedTag>>,
+    default_timestamp: Option<Timestamp>,
+    preserve_timestamps: bool,
+    compression: Compression,
+    durability: Durability,
+}
+
+impl RegionBuilder {
+    /// Creates a builder for an empty region file.
+    pub fn new() -> Self {
+        Self {
+            source: None,
+            chunks: HashMap::new(),
+            default_timestamp: None,
+            preserve_timestamps: false,
+            compression: Compression::best(),
+            durability: Durability::Never,
+        }
+    }
+
+    /// Creates a builder that copies the chunks of the region file at `path`.
+    pub fn from_region<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            source: Some(path.as_ref().to_owned()),
+            ..Self::new()
+        }
+    }
+
+    /// The timestamp given to inserted chunks.
+    /// If this isn't set, the `utc_now` timestamp at the time of building is used.
+    pub fn default_timestamp<Ts: Into<Timestamp>>(mut self, timestamp: Ts) -> Self {
+        self.default_timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// When `true`, inserted chunks that replace a chunk in the source file keep
+    /// the source chunk's timestamp instead of the default timestamp.
+    /// Copied chunks always keep their timestamp.
+    pub fn preserve_timestamps(mut self, preserve: bool) -> Self {
+        self.preserve_timestamps = preserve;
+        self
+    }
+
+    /// The compression level used for inserted chunks.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Whether the built file is synced to the disk before [RegionBuilder::build] returns.
+    /// See [StreamingRegionWriter::durability].
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Inserts a chunk, replacing the chunk from the source file if there is one.
+    pub fn insert<C: Into<RegionCoord>>(mut self, coord: C, chunk: NamedTag) -> Self {
+        self.chunks.insert(coord.into(), Some(chunk));
+        self
+    }
+
+    /// Removes a chunk so that it isn't copied from the source file.
+    pub fn remove<C: Into<RegionCoord>>(mut self, coord: C) -> Self {
+        self.chunks.insert(coord.into(), None);
+        self
+    }
+
+    /// Writes the region file to `path`, returning an error if it already exists.
+    /// Chunks are written in table order. External chunk files of copied chunks are copied as well,
+    /// see [StreamingRegionWriter::copy_chunk_from_region].
+    pub fn build<P: AsRef<Path>>(self, path: P) -> McResult<()> {
+        let mut source = match &self.source {
+            Some(source) => {
+                let mut reader = BufReader::new(File::open(source)?);
+                let header = RegionHeader::read_from(&mut reader)?;
+                Some((reader, header, source))
+            },
+            None => None,
+        };
+        let default_timestamp = self.default_timestamp.unwrap_or_else(Timestamp::utc_now);
+        let mut writer = StreamingRegionWriter::with_compression(path, self.compression)?;
+        writer.durability = self.durability;
+        for index in 0..1024usize {
+            let coord = RegionCoord::from(index);
+            match (self.chunks.get(&coord), source.as_mut()) {
+                (Some(Some(chunk)), source) => {
+                    let timestamp = match source {
+                        Some((_, header, _)) if self.preserve_timestamps
+                            && header.sectors[coord].sector_count() != 0 => header.timestamps[coord],
+                        _ => default_timestamp,
+                    };
+                    writer.push_timestamped(coord, chunk, timestamp)?;
+                },
+                (Some(None), _) => (),
+                (None, Some((reader, header, source_path))) => {
+                    if header.sectors[coord].sector_count() != 0 {
+                        writer.copy_chunk_from_region(reader, header, source_path, coord)?;
+                    }
+                },
+                (None, None) => (),
+            }
+        }
+        writer.finish()
+    }
+}
+
+impl Default for RegionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{McError, nbt::tag::Tag, world::io::region::external_chunk_path};
+
+    #[test]
+    fn preserve_timestamps_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("source.mca");
+        let mut source = StreamingRegionWriter::create(&source_path).unwrap();
+        source.push_timestamped((0u16, 0u16), &NamedTag::new(Tag::Int(0)), 100).unwrap();
+        source.push_timestamped((1u16, 0u16), &NamedTag::new(Tag::Int(1)), 200).unwrap();
+        source.finish().unwrap();
+
+        let rebuilt = dir.path().join("rebuilt.mca");
+        RegionBuilder::from_region(&source_path)
+            .default_timestamp(500)
+            .insert((1u16, 0u16), NamedTag::new(Tag::Int(2)))
+            .build(&rebuilt).unwrap();
+        let region = RegionFile::open(&rebuilt).unwrap();
+        assert_eq!(u32::from(region.get_timestamp((0u16, 0u16))), 100);
+        assert_eq!(u32::from(region.get_timestamp((1u16, 0u16))), 500);
+
+        let preserved = dir.path().join("preserved.mca");
+        RegionBuilder::from_region(&source_path)
+            .default_timestamp(500)
+            .preserve_timestamps(true)
+            .insert((1u16, 0u16), NamedTag::new(Tag::Int(2)))
+            .insert((2u16, 0u16), NamedTag::new(Tag::Int(3)))
+            .build(&preserved).unwrap();
+        let mut region = RegionFile::open(&preserved).unwrap();
+        assert_eq!(u32::from(region.get_timestamp((0u16, 0u16))), 100);
+        assert_eq!(u32::from(region.get_timestamp((1u16, 0u16))), 200);
+        assert_eq!(u32::from(region.get_timestamp((2u16, 0u16))), 500);
+        let tag: NamedTag = region.read_data((1u16, 0u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::Int(2)));
+    }
+
+    #[test]
+    fn external_chunk_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("r.0.0.mca");
+        let mut source = RegionFile::create(&source_path).unwrap();
+        source.set_compression(Compression::none());
+        let large = NamedTag::new(Tag::ByteArray(vec![1; 256 * 4096]));
+        source.write_data((1u16, 1u16), &large).unwrap();
+        assert!(external_chunk_path(&source_path, RegionCoord::new(1, 1)).unwrap().is_file());
+        drop(source);
+
+        let out_dir = dir.path().join("out");
+        std::fs::create_dir(&out_dir).unwrap();
+        let rebuilt = out_dir.join("r.0.0.mca");
+        RegionBuilder::from_region(&source_path)
+            .insert((0u16, 0u16), NamedTag::new(Tag::Int(0)))
+            .build(&rebuilt).unwrap();
+        let mut region = RegionFile::open(&rebuilt).unwrap();
+        let tag: NamedTag = region.read_data((1u16, 1u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::ByteArray(bytes) if bytes.len() == 256 * 4096));
+        // Without a region file name there's nowhere to put the external file, and the chunk is too large to store inline.
+        let unnamed = RegionBuilder::from_region(&source_path).build(dir.path().join("rebuilt.mca"));
+        assert!(matches!(unnamed, Err(McError::RegionDataTooLarge)));
+    }
+}
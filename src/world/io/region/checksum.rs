@@ -0,0 +1,143 @@
+//! Checksum sidecar files for detecting bit rot in region files.
+//!
+//! A sidecar (`r.<x>.<z>.mca.crc`) holds the length and CRC32 of the stored bytes of every chunk in a
+//! region file: the compression scheme followed by the compressed data (including the contents of
+//! external `.mcc` files). Unlike a [manifest](super::manifest), verifying a sidecar doesn't decompress
+//! or decode any NBT, but the checksums change whenever a chunk is rewritten, even with the same content.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::Crc;
+
+use crate::{McError, McResult, ioext::*};
+
+use super::prelude::*;
+
+/// Identifies a checksum sidecar file.
+const MAGIC: [u8; 4] = *b"RCRC";
+
+/// The path of the checksum sidecar for the region file at `region_path`.
+pub fn checksum_path<P: AsRef<Path>>(region_path: P) -> PathBuf {
+    let mut path = region_path.as_ref().as_os_str().to_owned();
+    path.push(".crc");
+    PathBuf::from(path)
+}
+
+/// The length and CRC32 of the stored bytes of a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChunkChecksum {
+    /// The number of stored bytes, or zero if the chunk isn't present.
+    pub length: u32,
+    pub crc: u32,
+}
+
+impl ChunkChecksum {
+    /// Computes the checksum of the stored bytes of a chunk.
+    pub fn of(bytes: &[u8]) -> Self {
+        let mut crc = Crc::new();
+        crc.update(bytes);
+        Self {
+            length: crc.amount(),
+            crc: crc.sum(),
+        }
+    }
+
+    /// Returns true if the chunk isn't present.
+    pub fn is_empty(self) -> bool {
+        self.length == 0
+    }
+}
+
+/// The checksums of all 1024 chunks in a region file, as stored in a checksum sidecar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumTable {
+    checksums: Box<[ChunkChecksum; 1024]>,
+}
+
+impl Default for ChecksumTable {
+    fn default() -> Self {
+        Self {
+            checksums: Box::new([ChunkChecksum::default(); 1024]),
+        }
+    }
+}
+
+impl ChecksumTable {
+    pub fn get<C: Into<RegionCoord>>(&self, coord: C) -> ChunkChecksum {
+        self.checksums[coord.into().index()]
+    }
+
+    pub fn set<C: Into<RegionCoord>>(&mut self, coord: C, checksum: ChunkChecksum) {
+        self.checksums[coord.into().index()] = checksum;
+    }
+
+    /// Returns the coordinates of the chunks whose checksums differ between the two tables.
+    /// The result is sorted.
+    pub fn differing(&self, other: &ChecksumTable) -> Vec<RegionCoord> {
+        (0..1024u16)
+            .map(RegionCoord::from)
+            .filter(|&coord| self.get(coord) != other.get(coord))
+            .collect()
+    }
+
+    /// Reads a checksum sidecar.
+    pub fn load<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(McError::Custom("Not a region checksum file.".to_owned()));
+        }
+        let mut table = Self::default();
+        for checksum in table.checksums.iter_mut() {
+            checksum.length = reader.read_value()?;
+            checksum.crc = reader.read_value()?;
+        }
+        Ok(table)
+    }
+
+    /// Writes a checksum sidecar.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> McResult<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&MAGIC)?;
+        for checksum in self.checksums.iter() {
+            writer.write_value(checksum.length)?;
+            writer.write_value(checksum.crc)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+
+    #[test]
+    fn checksum_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        region.write_data((0, 0), &NamedTag::new(Tag::Int(1))).unwrap();
+        region.write_data((5, 7), &NamedTag::new(Tag::String("checksum".repeat(100)))).unwrap();
+        region.write_checksums().unwrap();
+        assert!(region.verify_checksums().unwrap().is_empty());
+        let table = ChecksumTable::load(checksum_path(&path)).unwrap();
+        assert!(!table.get((5, 7)).is_empty());
+        assert!(table.get((1, 0)).is_empty());
+        drop(region);
+
+        // Flip a bit in the compressed data of the second chunk.
+        let sector = RegionFile::open(&path).unwrap().get_sector((5, 7));
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[sector.offset() as usize + 10] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.verify_checksums().unwrap(), vec![RegionCoord::from((5, 7))]);
+    }
+}
@@ -1,11 +1,26 @@
 use std::io::{Read, Write};
+use flate2::{
+    Compression,
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
 use crate::{
     McResult, McError,
     ioext::*,
 };
 
+/// The name that Zstd compressed chunks are stored under with the custom (`127`) scheme.
+#[cfg(feature = "zstd")]
+pub const ZSTD_CUSTOM_NAME: &str = "zstd";
+
+/// Set on the compression scheme byte when a chunk is too large for the region file.
+/// The chunk's data is stored in a `c.<x>.<z>.mcc` file next to the region file instead,
+/// and only the compression scheme is left in the region file.
+pub const EXTERNAL_CHUNK_FLAG: u8 = 128;
+
 /// Compression scheme used for writing or reading.
 #[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompressionScheme {
     /// GZip compression is used.
     GZip = 1,
@@ -13,25 +28,126 @@ pub enum CompressionScheme {
     ZLib = 2,
     /// Data is uncompressed.
     Uncompressed = 3,
+    /// LZ4 compression is used (Minecraft 1.20.5+).
+    /// The data is in the block format written by lz4-java's `LZ4BlockOutputStream`.
+    #[cfg(feature = "lz4")]
+    LZ4 = 4,
+    /// Zstd compression is used.
+    /// Minecraft doesn't support Zstd, so it is stored with the custom scheme (`127`),
+    /// which is followed by the name of the compression ([ZSTD_CUSTOM_NAME]).
+    /// Other tools will only be able to read these chunks if they use the same name.
+    #[cfg(feature = "zstd")]
+    Zstd = 127,
 }
 
-impl Writable for CompressionScheme {
-    fn write_to<W: Write>(&self, writer: &mut W) -> McResult<usize> {
+impl CompressionScheme {
+    /// The number of bytes that the compression scheme occupies in a chunk.
+    /// This is included in the chunk's length.
+    pub fn header_len(self) -> u32 {
         match self {
-            CompressionScheme::GZip => writer.write_value(1u8),
-            CompressionScheme::ZLib => writer.write_value(2u8),
-            CompressionScheme::Uncompressed => writer.write_value(3u8),
+            #[cfg(feature = "zstd")]
+            CompressionScheme::Zstd => 1 + 2 + ZSTD_CUSTOM_NAME.len() as u32,
+            _ => 1,
         }
     }
-}
 
-impl Readable for CompressionScheme {
-    fn read_from<R: Read>(reader: &mut R) -> McResult<Self> {
-        match reader.read_value::<u8>()? {
+    /// Compresses `data` with this scheme and writes it to `writer`.
+    /// The `compression` level is used for GZip, ZLib, and Zstd, and is ignored otherwise.
+    pub fn compress<W: Write>(self, data: &[u8], compression: Compression, writer: &mut W) -> McResult<()> {
+        match self {
+            CompressionScheme::GZip => {
+                let mut encoder = GzEncoder::new(writer, compression);
+                encoder.write_all(data)?;
+                encoder.finish()?;
+            },
+            CompressionScheme::ZLib => {
+                let mut encoder = ZlibEncoder::new(writer, compression);
+                encoder.write_all(data)?;
+                encoder.finish()?;
+            },
+            CompressionScheme::Uncompressed => writer.write_all(data)?,
+            #[cfg(feature = "lz4")]
+            CompressionScheme::LZ4 => super::lz4block::compress_lz4_blocks(data, writer)?,
+            #[cfg(feature = "zstd")]
+            CompressionScheme::Zstd => zstd::stream::copy_encode(data, writer, compression.level() as i32)?,
+        }
+        Ok(())
+    }
+
+    /// Wraps `reader` in a decompressor for this scheme.
+    /// `reader` should be positioned after the compression scheme and limited to the chunk's data.
+    pub fn decoder<'a, R: Read + 'a>(self, reader: R) -> McResult<Box<dyn Read + 'a>> {
+        Ok(match self {
+            CompressionScheme::GZip => Box::new(GzDecoder::new(reader)),
+            CompressionScheme::ZLib => Box::new(ZlibDecoder::new(reader)),
+            CompressionScheme::Uncompressed => Box::new(reader),
+            #[cfg(feature = "lz4")]
+            CompressionScheme::LZ4 => Box::new(std::io::Cursor::new(super::lz4block::decompress_lz4_blocks(reader)?)),
+            #[cfg(feature = "zstd")]
+            CompressionScheme::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        })
+    }
+
+    /// Reads the compression scheme and whether [EXTERNAL_CHUNK_FLAG] was set.
+    pub fn read_with_external_flag<R: Read>(reader: &mut R) -> McResult<(Self, bool)> {
+        let id: u8 = reader.read_value()?;
+        if id & EXTERNAL_CHUNK_FLAG != 0 {
+            Ok((Self::read_id(id & !EXTERNAL_CHUNK_FLAG, reader)?, true))
+        } else {
+            Ok((Self::read_id(id, reader)?, false))
+        }
+    }
+
+    /// Writes the compression scheme with [EXTERNAL_CHUNK_FLAG] set.
+    pub fn write_external_to<W: Write>(self, writer: &mut W) -> McResult<usize> {
+        self.write_id(EXTERNAL_CHUNK_FLAG, writer)
+    }
+
+    /// Writes the scheme's id combined with `flags`, followed by the name for custom schemes.
+    fn write_id<W: Write>(self, flags: u8, writer: &mut W) -> McResult<usize> {
+        writer.write_value(self as u8 | flags)?;
+        #[cfg(feature = "zstd")]
+        if self == CompressionScheme::Zstd {
+            writer.write_value(ZSTD_CUSTOM_NAME.len() as u16)?;
+            writer.write_all(ZSTD_CUSTOM_NAME.as_bytes())?;
+        }
+        Ok(self.header_len() as usize)
+    }
+
+    /// Gets the scheme for an id (without flags), reading the name for custom schemes.
+    fn read_id<R: Read>(id: u8, reader: &mut R) -> McResult<Self> {
+        match id {
             1 => Ok(Self::GZip),
             2 => Ok(Self::ZLib),
             3 => Ok(Self::Uncompressed),
+            #[cfg(feature = "lz4")]
+            4 => Ok(Self::LZ4),
+            127 => {
+                // The custom scheme is followed by the name of the compression.
+                let length = reader.read_value::<u16>()?;
+                let mut name = vec![0u8; length as usize];
+                reader.read_exact(&mut name)?;
+                let name = String::from_utf8(name)?;
+                match name.as_str() {
+                    #[cfg(feature = "zstd")]
+                    ZSTD_CUSTOM_NAME => Ok(Self::Zstd),
+                    _ => Err(McError::UnsupportedCustomCompression(name)),
+                }
+            },
             unexpected => Err(McError::InvalidCompressionScheme(unexpected)),
         }
     }
-}
\ No newline at end of file
+}
+
+impl Writable for CompressionScheme {
+    fn write_to<W: Write>(&self, writer: &mut W) -> McResult<usize> {
+        self.write_id(0, writer)
+    }
+}
+
+impl Readable for CompressionScheme {
+    fn read_from<R: Read>(reader: &mut R) -> McResult<Self> {
+        let id: u8 = reader.read_value()?;
+        Self::read_id(id, reader)
+    }
+}
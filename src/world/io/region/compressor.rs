@@ -0,0 +1,119 @@
+//! Compressing chunks for region files with reusable state.
+//!
+//! [RegionFile](super::RegionFile) and [StreamingRegionWriter](super::streaming::StreamingRegionWriter) can be given a
+//! [Compressor] that's used for every chunk they write, which avoids setting up a new encoder for each chunk.
+//!
+//! ZLib and GZip compression use flate2, whose backend is chosen with crate features: the pure Rust
+//! `miniz_oxide` backend is used by default, and the `zlib-rs` feature switches to the faster `zlib-rs` backend.
+
+use flate2::{Compress, Compression, FlushCompress, Status};
+
+use crate::McResult;
+
+use super::CompressionScheme;
+
+/// Compresses the NBT of chunks for a region file.
+pub trait Compressor: Send {
+    /// The scheme of the compressed data, which is written before it in the region file.
+    fn scheme(&self) -> CompressionScheme;
+
+    /// Compresses `data`, appending the compressed bytes to `out`.
+    fn compress(&mut self, data: &[u8], out: &mut Vec<u8>) -> McResult<()>;
+}
+
+/// A ZLib [Compressor] that reuses its compression state between chunks.
+pub struct ZlibCompressor {
+    compress: Compress,
+}
+
+impl ZlibCompressor {
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            compress: Compress::new(compression, true),
+        }
+    }
+}
+
+impl Default for ZlibCompressor {
+    fn default() -> Self {
+        Self::new(Compression::best())
+    }
+}
+
+impl Compressor for ZlibCompressor {
+    fn scheme(&self) -> CompressionScheme {
+        CompressionScheme::ZLib
+    }
+
+    fn compress(&mut self, mut data: &[u8], out: &mut Vec<u8>) -> McResult<()> {
+        self.compress.reset();
+        loop {
+            // Compressed data is only written to the spare capacity of `out`.
+            out.reserve((data.len() / 2).max(4096));
+            let before = self.compress.total_in();
+            let status = self.compress.compress_vec(data, out, FlushCompress::Finish)
+                .map_err(std::io::Error::other)?;
+            data = &data[(self.compress.total_in() - before) as usize..];
+            if status == Status::StreamEnd {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A [Compressor] for any [CompressionScheme], using [CompressionScheme::compress].
+#[derive(Debug, Clone, Copy)]
+pub struct SchemeCompressor {
+    pub scheme: CompressionScheme,
+    pub compression: Compression,
+}
+
+impl SchemeCompressor {
+    pub fn new(scheme: CompressionScheme, compression: Compression) -> Self {
+        Self { scheme, compression }
+    }
+}
+
+impl Compressor for SchemeCompressor {
+    fn scheme(&self) -> CompressionScheme {
+        self.scheme
+    }
+
+    fn compress(&mut self, data: &[u8], out: &mut Vec<u8>) -> McResult<()> {
+        self.scheme.compress(data, self.compression, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+    use crate::world::io::region::{streaming::StreamingRegionWriter, RegionFile};
+
+    #[test]
+    fn compressor_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        region.set_compressor(Some(Box::new(ZlibCompressor::default())));
+        let chunk = |index: i32| NamedTag::new(Tag::String(format!("chunk {index}").repeat(1000)));
+        for index in 0..3 {
+            region.write_data(index as u16, &chunk(index)).unwrap();
+        }
+        region.set_compressor(Some(Box::new(SchemeCompressor::new(CompressionScheme::Uncompressed, Compression::none()))));
+        region.write_data(3u16, &chunk(3)).unwrap();
+        assert!(region.get_sector(3u16).sector_count() > region.get_sector(2u16).sector_count());
+        for index in 0..4 {
+            let read: NamedTag = region.read_data(index as u16).unwrap();
+            assert_eq!(read.tag().content_hash(), chunk(index).tag().content_hash());
+        }
+
+        let streamed = dir.path().join("r.1.0.mca");
+        let mut writer = StreamingRegionWriter::create(&streamed).unwrap();
+        writer.set_compressor(Some(Box::new(ZlibCompressor::new(Compression::fast()))));
+        writer.push((0, 0), &chunk(0)).unwrap();
+        writer.finish().unwrap();
+        let read: NamedTag = RegionFile::open(&streamed).unwrap().read_data((0, 0)).unwrap();
+        assert_eq!(read.tag().content_hash(), chunk(0).tag().content_hash());
+    }
+}
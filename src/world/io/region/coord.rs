@@ -5,7 +5,8 @@ use std::io::SeekFrom;
 /// This struct represents a chunk coordinate within a region file.
 /// The coordinate can be an absolute coordinate and it will be
 /// normalized to relative coordinates.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RegionCoord(u16);
 
 impl RegionCoord {
@@ -50,6 +51,64 @@ impl RegionCoord {
     }
 }
 
+/// Defines how a chunk's (x, z) coordinate within a region file maps to its
+/// index in the header tables.
+/// Vanilla region files use [VanillaLayout] (`x + z * 32`), which is what
+/// [RegionCoord::new] uses. Other layouts are only useful for reading
+/// non-standard region files, such as those produced by some mods.
+pub trait RegionLayout {
+    /// Returns the table index (0..1024) for the relative coordinate.
+    /// `x` and `z` are guaranteed to be less than 32.
+    fn index(x: u16, z: u16) -> u16;
+    /// Returns the relative (x, z) coordinate for the table index.
+    /// `index` is guaranteed to be less than 1024.
+    fn coord(index: u16) -> (u16, u16);
+}
+
+/// The layout used by Minecraft (row-major): `x + z * 32`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct VanillaLayout;
+
+impl RegionLayout for VanillaLayout {
+    fn index(x: u16, z: u16) -> u16 {
+        x | (z << 5)
+    }
+
+    fn coord(index: u16) -> (u16, u16) {
+        (index & 31, index >> 5)
+    }
+}
+
+/// A column-major layout: `z + x * 32`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ColumnMajorLayout;
+
+impl RegionLayout for ColumnMajorLayout {
+    fn index(x: u16, z: u16) -> u16 {
+        z | (x << 5)
+    }
+
+    fn coord(index: u16) -> (u16, u16) {
+        (index >> 5, index & 31)
+    }
+}
+
+impl RegionCoord {
+    /// Create a new RegionCoord using a custom [RegionLayout].
+    /// The resulting coordinate addresses the table slot that `layout` assigns
+    /// to `(x, z)`, so it can be passed to any [RegionFile](super::RegionFile) method.
+    /// The x and z are normalized to relative coordinates like [RegionCoord::new].
+    pub fn with_layout<L: RegionLayout>(x: u16, z: u16) -> Self {
+        Self(L::index(x & 31, z & 31) & 1023)
+    }
+
+    /// Returns the relative (x, z) coordinate of this table slot under a custom [RegionLayout].
+    pub fn layout_coord<L: RegionLayout>(&self) -> (i32, i32) {
+        let (x, z) = L::coord(self.0 & 1023);
+        (x as i32, z as i32)
+    }
+}
+
 macro_rules! __regioncoord_impl {
     ($type:ty) => {
 
@@ -59,9 +118,12 @@ macro_rules! __regioncoord_impl {
             }
         }
 
+        /// Converts a packed table index into a [RegionCoord].
+        /// The index is masked to the valid range (`0..1024`), so an out of range
+        /// index wraps around rather than indexing out of bounds later on.
         impl From<$type> for RegionCoord {
             fn from(value: $type) -> Self {
-                Self(value as u16)
+                Self((value as u16) & 1023)
             }
         }
 
@@ -91,4 +153,21 @@ impl std::fmt::Display for RegionCoord {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({}, {})", self.x(), self.z())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::io::region::header::SectorTable;
+
+    #[test]
+    fn out_of_range_index_test() {
+        let coord = RegionCoord::from(2000u32);
+        assert_eq!(coord.index(), 2000 & 1023);
+        assert_eq!(RegionCoord::from(1024usize), RegionCoord::from(0usize));
+        assert!(RegionCoord::from(-1i32).index() < 1024);
+        // Indexing a table with an out of range index must not panic.
+        let table = SectorTable::default();
+        assert!(table[2000u32].is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+//! Comparing two region files chunk by chunk.
+
+use std::path::Path;
+
+use crate::{
+    McResult,
+    nbt::{diff::diff, tag::NamedTag},
+};
+
+use super::prelude::*;
+
+/// How [diff_regions] decides whether a chunk present in both region files has changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegionCompare {
+    /// Chunks with different timestamps are changed. Only the headers are read.
+    #[default]
+    Timestamp,
+    /// Chunks whose stored (compressed) bytes differ are changed. The same chunk
+    /// compressed with a different scheme or level counts as changed.
+    CompressedBytes,
+    /// Chunks whose decoded NBT differs are changed. This is the slowest, but it
+    /// ignores differences in compression and timestamps.
+    Nbt,
+}
+
+/// The result of [diff_regions]. Every list is ordered by [RegionCoord].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegionDiff {
+    /// Chunks that are only present in the second region file.
+    pub added: Vec<RegionCoord>,
+    /// Chunks that are only present in the first region file.
+    pub removed: Vec<RegionCoord>,
+    /// Chunks that are present in both region files, but differ.
+    pub changed: Vec<RegionCoord>,
+}
+
+impl RegionDiff {
+    /// Returns true if no chunks were added, removed, or changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// Returns true if the chunk at `coord` has data. A chunk with an allocated sector
+/// but a length of zero isn't present.
+fn is_present(region: &mut RegionFile, coord: RegionCoord, compare: RegionCompare) -> McResult<bool> {
+    if region.get_sector(coord).sector_count() == 0 {
+        return Ok(false);
+    }
+    // The timestamp comparison doesn't otherwise read the chunks, so only the header is trusted.
+    if compare == RegionCompare::Timestamp {
+        return Ok(true);
+    }
+    Ok(region.read_stored_bytes(coord)?.is_some())
+}
+
+/// Compares the region file at `old` with the region file at `new`, chunk by chunk,
+/// and reports which chunks were added, removed, or changed going from `old` to `new`.
+pub fn diff_regions<P: AsRef<Path>, Q: AsRef<Path>>(old: P, new: Q, compare: RegionCompare) -> McResult<RegionDiff> {
+    let mut old = RegionFile::open(old)?;
+    let mut new = RegionFile::open(new)?;
+    let mut diff_result = RegionDiff::default();
+    for index in 0..1024u16 {
+        let coord = RegionCoord::from(index);
+        match (is_present(&mut old, coord, compare)?, is_present(&mut new, coord, compare)?) {
+            (false, false) => continue,
+            (false, true) => diff_result.added.push(coord),
+            (true, false) => diff_result.removed.push(coord),
+            (true, true) => {
+                let changed = match compare {
+                    RegionCompare::Timestamp => old.get_timestamp(coord) != new.get_timestamp(coord),
+                    RegionCompare::CompressedBytes => old.read_stored_bytes(coord)? != new.read_stored_bytes(coord)?,
+                    RegionCompare::Nbt => {
+                        let old_chunk: NamedTag = old.read_data(coord)?;
+                        let new_chunk: NamedTag = new.read_data(coord)?;
+                        !diff(old_chunk.tag(), new_chunk.tag()).is_empty()
+                    },
+                };
+                if changed {
+                    diff_result.changed.push(coord);
+                }
+            },
+        }
+    }
+    Ok(diff_result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::Tag;
+    use crate::world::io::region::CompressionScheme;
+
+    #[test]
+    fn diff_regions_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_path = dir.path().join("r.0.0.mca");
+        let new_path = dir.path().join("r.0.0.new.mca");
+        let chunk = |value: i32| NamedTag::new(Tag::Compound(crate::nbt::Map::from([("value".to_owned(), Tag::Int(value))])));
+        {
+            let mut old = RegionFile::create(&old_path).unwrap();
+            let mut new = RegionFile::create(&new_path).unwrap();
+            old.write_data_timestamped((0, 0), &chunk(1), 10).unwrap();
+            new.write_data_timestamped_with_scheme((0, 0), &chunk(1), 20, CompressionScheme::Uncompressed).unwrap();
+            old.write_data_timestamped((1, 0), &chunk(2), 10).unwrap();
+            new.write_data_timestamped((1, 0), &chunk(3), 10).unwrap();
+            old.write_data_timestamped((2, 0), &chunk(4), 10).unwrap();
+            new.write_data_timestamped((3, 0), &chunk(5), 10).unwrap();
+        }
+        let coord = |x: i32, z: i32| RegionCoord::from((x, z));
+        let by_timestamp = diff_regions(&old_path, &new_path, RegionCompare::Timestamp).unwrap();
+        assert_eq!(by_timestamp.added, vec![coord(3, 0)]);
+        assert_eq!(by_timestamp.removed, vec![coord(2, 0)]);
+        assert_eq!(by_timestamp.changed, vec![coord(0, 0)]);
+        let by_bytes = diff_regions(&old_path, &new_path, RegionCompare::CompressedBytes).unwrap();
+        assert_eq!(by_bytes.changed, vec![coord(0, 0), coord(1, 0)]);
+        let by_nbt = diff_regions(&old_path, &new_path, RegionCompare::Nbt).unwrap();
+        assert_eq!(by_nbt.changed, vec![coord(1, 0)]);
+        assert!(diff_regions(&old_path, &old_path, RegionCompare::Nbt).unwrap().is_empty());
+    }
+}
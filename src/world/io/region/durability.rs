@@ -0,0 +1,56 @@
+//! Controlling when region writes are synced to the disk.
+
+/// When a region file waits for its writes to reach the disk (with `sync_data`).
+///
+/// Without syncing, written data may sit in the operating system's cache for a while, so a power loss
+/// can lose writes that had already returned. Syncing makes writes much slower, so bulk imports usually
+/// leave it off and sync once at the end, while servers that write a few chunks at a time can sync every write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Writes are never synced. The operating system writes them to the disk when it chooses.
+    #[default]
+    Never,
+    /// Writes are synced when the region file is flushed (or a region file that's being built is finished).
+    OnFlush,
+    /// Every write is synced before it returns.
+    EveryWrite,
+}
+
+impl Durability {
+    /// Returns true if a flush should be synced.
+    pub fn syncs_on_flush(self) -> bool {
+        self != Durability::Never
+    }
+
+    /// Returns true if every write should be synced.
+    pub fn syncs_every_write(self) -> bool {
+        self == Durability::EveryWrite
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+    use crate::world::io::region::{builder::RegionBuilder, RegionFile};
+
+    #[test]
+    fn durability_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        assert_eq!(region.durability(), Durability::Never);
+        region.set_durability(Durability::EveryWrite);
+        region.write_data_timestamped((0, 0), &NamedTag::new(Tag::Int(1)), 10).unwrap();
+        region.delete_data((0, 0)).unwrap();
+        region.flush().unwrap();
+
+        let built = dir.path().join("r.1.0.mca");
+        RegionBuilder::new()
+            .durability(Durability::OnFlush)
+            .insert((1, 1), NamedTag::new(Tag::Int(2)))
+            .build(&built).unwrap();
+        let tag: NamedTag = RegionFile::open(&built).unwrap().read_data((1, 1)).unwrap();
+        assert!(matches!(tag.tag(), Tag::Int(2)));
+    }
+}
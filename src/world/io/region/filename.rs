@@ -0,0 +1,88 @@
+//! Region file names (`r.<x>.<z>.mca`) and finding the region files in a directory.
+
+use std::path::{Path, PathBuf};
+
+use crate::McResult;
+
+/// The coordinate of a region as it appears in a region file name such as `r.-1.2.mca`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RegionFileName {
+    pub x: i64,
+    pub z: i64,
+}
+
+impl RegionFileName {
+    pub fn new(x: i64, z: i64) -> Self {
+        Self { x, z }
+    }
+
+    /// Parses a region file name such as `r.-1.2.mca`.
+    /// Returns `None` unless the name is exactly what Minecraft would write for a region, so names
+    /// like `r.+1.02.mca` or `r.1.2.mca.bak` are rejected.
+    pub fn parse(name: &str) -> Option<Self> {
+        let (x, z) = name.strip_prefix("r.")?.strip_suffix(".mca")?.split_once('.')?;
+        let file_name = Self::new(x.parse().ok()?, z.parse().ok()?);
+        (file_name.to_string() == name).then_some(file_name)
+    }
+
+    /// Parses the file name of a path. See [RegionFileName::parse].
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        path.as_ref().file_name()?.to_str().and_then(Self::parse)
+    }
+
+    /// The path of the region file within `directory`.
+    pub fn path<P: AsRef<Path>>(self, directory: P) -> PathBuf {
+        directory.as_ref().join(self.to_string())
+    }
+}
+
+impl std::fmt::Display for RegionFileName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "r.{}.{}.mca", self.x, self.z)
+    }
+}
+
+/// Finds the region files in a region directory, returning the region coordinate and path of each.
+/// Files with names that aren't valid region file names (see [RegionFileName::parse]) and directories
+/// are skipped. The regions are sorted by z and then by x. If the directory doesn't exist, there are no regions.
+pub fn discover_regions<P: AsRef<Path>>(dir: P) -> McResult<Vec<(i64, i64, PathBuf)>> {
+    let dir = dir.as_ref();
+    let mut regions = Vec::new();
+    if !dir.is_dir() {
+        return Ok(regions);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        if let Some(name) = entry.file_name().to_str().and_then(RegionFileName::parse) {
+            regions.push((name.x, name.z, entry.path()));
+        }
+    }
+    regions.sort_by_key(|&(x, z, _)| (z, x));
+    Ok(regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discover_regions_test() {
+        assert_eq!(RegionFileName::parse("r.-1.2.mca"), Some(RegionFileName::new(-1, 2)));
+        for invalid in ["r.1.mca", "r.1.2.3.mca", "r.+1.2.mca", "r.01.2.mca", "r.1.2.mca.bak", "r.-0.0.mca", "r.99999999999999999999.0.mca"] {
+            assert_eq!(RegionFileName::parse(invalid), None, "{invalid}");
+        }
+        let dir = tempfile::tempdir().unwrap();
+        assert!(discover_regions(dir.path().join("missing")).unwrap().is_empty());
+        for name in ["r.0.0.mca", "r.-3.7.mca", "r.5.-1.mca", "c.0.0.mcc", "r.a.b.mca"] {
+            std::fs::write(dir.path().join(name), []).unwrap();
+        }
+        std::fs::create_dir(dir.path().join("r.1.1.mca")).unwrap();
+        let regions = discover_regions(dir.path()).unwrap();
+        let coords = regions.iter().map(|&(x, z, _)| (x, z)).collect::<Vec<_>>();
+        assert_eq!(coords, vec![(5, -1), (0, 0), (-3, 7)]);
+        assert_eq!(regions[2].2, dir.path().join("r.-3.7.mca"));
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::Path;
+
+/// The format of a region file.
+/// Both formats share the same header and chunk layout, so a [RegionFile](super::RegionFile)
+/// can read and write either. The difference is the structure of the chunk NBT within.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RegionFormat {
+    /// Anvil (`.mca`), used since Minecraft 1.2.
+    #[default]
+    Anvil,
+    /// McRegion (`.mcr`), used from Beta 1.3 until Minecraft 1.2.
+    /// Chunks store numeric block ids in a 128 block tall `Blocks` array.
+    McRegion,
+}
+
+impl RegionFormat {
+    /// The file extension (without the dot) for region files of this format.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            RegionFormat::Anvil => "mca",
+            RegionFormat::McRegion => "mcr",
+        }
+    }
+
+    /// Gets the format from a file extension (without the dot), ignoring case.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        if extension.eq_ignore_ascii_case("mca") {
+            Some(RegionFormat::Anvil)
+        } else if extension.eq_ignore_ascii_case("mcr") {
+            Some(RegionFormat::McRegion)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the format from the extension of a region file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        path.as_ref().extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Self::from_extension)
+    }
+}
+
+impl std::fmt::Display for RegionFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegionFormat::Anvil => write!(f, "Anvil"),
+            RegionFormat::McRegion => write!(f, "McRegion"),
+        }
+    }
+}
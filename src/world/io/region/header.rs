@@ -11,7 +11,7 @@ use std::{
     fmt::Debug,
     io::{
         Read, Write,
-        SeekFrom,
+        Seek, SeekFrom,
     }, 
     ops::{
         Index, IndexMut,
@@ -64,6 +64,16 @@ pub struct RegionHeader {
     pub timestamps: TimestampTable,
 }
 
+/// Reads the 8KiB region header verbatim from the beginning of the reader.
+/// Unlike reading a [RegionHeader], the bytes are returned exactly as they are on disk.
+/// The reader is left at the end of the header.
+pub fn read_raw_header<R: Read + Seek>(reader: &mut R) -> McResult<Box<[u8; 8192]>> {
+    let mut header = Box::new([0u8; 8192]);
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_exact(header.as_mut_slice())?;
+    Ok(header)
+}
+
 impl<T: RegionTableItem> RegionTable<T> {
     pub const OFFSET: u64 = T::OFFSET;
 
@@ -17,7 +17,7 @@ use std::{
         File,
     },
     io::{
-        BufReader, Seek,
+        BufReader, Read, Seek,
     },
 };
 
@@ -57,16 +57,7 @@ impl RegionFileInfo {
         let metadata = std::fs::metadata(path.as_ref())?;
         let mut reader = BufReader::with_capacity(4096*2, file);
         let header = RegionHeader::read_from(&mut reader)?;
-        let mut bits = RegionBitmask::new();
-        for i in 0..1024 {
-            if !header.sectors[i].is_empty() {
-                reader.seek(header.sectors[i].seeker())?;
-                let length = u32::read_from(&mut reader)?;
-                if length != 0 {
-                    bits.set(i, true);
-                }
-            }
-        }
+        let bits = RegionBitmask::present_in(&header, &mut reader)?;
         Ok(Self {
             path: PathBuf::from(path.as_ref()),
             metadata,
@@ -167,6 +158,49 @@ impl RegionBitmask {
         self.0[sub_index] = self.0[sub_index].set_bit(bit_index, on);
     }
 
+    /// Creates a bitmask of the chunks that have a sector allocated in the
+    /// header of the region file at `path`.
+    /// Only the sector table is read, so this is cheap, but an allocated
+    /// sector doesn't guarantee that the chunk has any data.
+    /// Use [RegionBitmask::present_from] for that.
+    pub fn allocated_from<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(4096, file);
+        let sectors = SectorTable::read_from(&mut reader)?;
+        let mut bits = RegionBitmask::new();
+        sectors.iter()
+            .enumerate()
+            .filter(|(_, sector)| sector.sector_count() != 0)
+            .for_each(|(index, _)| bits.set(index, true));
+        Ok(bits)
+    }
+
+    /// Creates a bitmask of the chunks that have data present in the
+    /// region file at `path`.
+    /// Unlike [RegionBitmask::allocated_from], this reads the length of
+    /// each allocated chunk, and only chunks with a non-zero length are set.
+    pub fn present_from<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut reader = BufReader::with_capacity(4096*2, file);
+        let header = RegionHeader::read_from(&mut reader)?;
+        Self::present_in(&header, &mut reader)
+    }
+
+    /// Checks the length of each allocated chunk in the header.
+    fn present_in<R: Read + Seek>(header: &RegionHeader, reader: &mut R) -> McResult<Self> {
+        let mut bits = RegionBitmask::new();
+        for i in 0..1024 {
+            if header.sectors[i].sector_count() != 0 {
+                reader.seek(header.sectors[i].seeker())?;
+                let length = u32::read_from(reader)?;
+                if length != 0 {
+                    bits.set(i, true);
+                }
+            }
+        }
+        Ok(bits)
+    }
+
     /// Clear all bits (Setting them to 0).
     pub fn clear(&mut self) {
         self.0.iter_mut().for_each(|value| {
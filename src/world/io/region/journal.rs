@@ -0,0 +1,130 @@
+//! The journal that makes [journaled](super::RegionFile::set_journaled) region writes safe to interrupt.
+//!
+//! A journaled write never overwrites the sectors of the chunk it replaces. The new data is written to
+//! free sectors and synced first, then the new sector table entry is written to a journal file next to
+//! the region file (`r.<x>.<z>.mca.journal`) and synced, and only then is the entry written to the
+//! region's header. The journal is removed once the header has been synced.
+//!
+//! If the process dies before the journal is complete, the header still points at the old chunk.
+//! If it dies after, [RegionFile::open](super::RegionFile::open) finds the journal and finishes the write.
+
+use std::{
+    fs::File,
+    io::{Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{McResult, ioext::*};
+
+use super::prelude::*;
+
+/// Identifies a journal file.
+const MAGIC: [u8; 4] = *b"RJNL";
+/// The magic, the index of the chunk (2 bytes), the sector (4 bytes), and the checksum (4 bytes).
+const ENTRY_LEN: usize = 14;
+
+/// The path of the journal for the region file at `region_path`.
+pub fn journal_path<P: AsRef<Path>>(region_path: P) -> PathBuf {
+    let mut path = region_path.as_ref().as_os_str().to_owned();
+    path.push(".journal");
+    PathBuf::from(path)
+}
+
+/// A 32-bit FNV-1a hash, so that a journal that was only partly written isn't replayed.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5u32, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+}
+
+fn encode_entry(coord: RegionCoord, sector: RegionSector) -> McResult<Vec<u8>> {
+    let mut entry = Vec::with_capacity(ENTRY_LEN);
+    entry.extend_from_slice(&MAGIC);
+    entry.write_value(coord.index() as u16)?;
+    entry.write_value(sector)?;
+    let check = checksum(&entry);
+    entry.write_value(check)?;
+    Ok(entry)
+}
+
+fn decode_entry(entry: &[u8]) -> Option<(RegionCoord, RegionSector)> {
+    if entry.len() != ENTRY_LEN || entry[..4] != MAGIC {
+        return None;
+    }
+    let mut reader = &entry[4..];
+    let index: u16 = reader.read_value().ok()?;
+    let sector: RegionSector = reader.read_value().ok()?;
+    let check: u32 = reader.read_value().ok()?;
+    (index < 1024 && check == checksum(&entry[..10])).then(|| (RegionCoord::from(index), sector))
+}
+
+/// Writes the journal for a new sector table entry and waits for it to reach the disk.
+pub(crate) fn write_journal(region_path: &Path, coord: RegionCoord, sector: RegionSector) -> McResult<()> {
+    let mut file = File::create(journal_path(region_path))?;
+    file.write_all(&encode_entry(coord, sector)?)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Removes the journal of a region file, if there is one.
+pub(crate) fn remove_journal(region_path: &Path) -> McResult<()> {
+    match std::fs::remove_file(journal_path(region_path)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Finishes the write recorded in the journal of the region file at `region_path`, if there is one,
+/// then removes the journal. A journal that wasn't completely written is discarded, along with
+/// entries that point past the end of the file. Returns true if an entry was written to the header.
+pub(crate) fn replay_journal(file: &mut File, region_path: &Path) -> McResult<bool> {
+    let entry = match std::fs::read(journal_path(region_path)) {
+        Ok(entry) => entry,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    let file_len = file.metadata()?.len();
+    let replayed = match decode_entry(&entry) {
+        Some((coord, sector)) if sector.end_offset() <= file_len => {
+            file.seek(coord.sector_table_offset())?;
+            file.write_value(sector)?;
+            file.sync_data()?;
+            true
+        }
+        _ => false,
+    };
+    remove_journal(region_path)?;
+    Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+
+    #[test]
+    fn journal_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        region.set_journaled(true);
+        let first = region.write_data((0, 0), &NamedTag::new(Tag::Int(1))).unwrap();
+        // The old sectors aren't reused, so the old chunk is intact until the header changes.
+        let second = region.write_data((0, 0), &NamedTag::new(Tag::Int(2))).unwrap();
+        assert!(!first.intersects(second));
+        assert!(!journal_path(&path).exists());
+        drop(region);
+
+        // A journal left by a write that was interrupted after the data was written is replayed.
+        write_journal(&path, RegionCoord::from((1, 0)), second).unwrap();
+        let mut region = RegionFile::open(&path).unwrap();
+        assert!(!journal_path(&path).exists());
+        let replayed: NamedTag = region.read_data((1, 0)).unwrap();
+        assert!(matches!(replayed.tag(), Tag::Int(2)));
+        drop(region);
+
+        // An incomplete journal is discarded.
+        std::fs::write(journal_path(&path), &encode_entry(RegionCoord::from((2, 0)), second).unwrap()[..9]).unwrap();
+        let region = RegionFile::open(&path).unwrap();
+        assert!(region.get_sector((2, 0)).is_empty());
+        assert!(!journal_path(&path).exists());
+    }
+}
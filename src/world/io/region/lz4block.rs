@@ -0,0 +1,118 @@
+//! The LZ4 block stream format used by Minecraft for LZ4 compressed chunks.
+//!
+//! Minecraft uses lz4-java's `LZ4BlockOutputStream`, which splits the data into
+//! blocks of up to 64KiB. Each block has a 21 byte header:
+//! `"LZ4Block"`, a token (`method | level`), the compressed length, the original
+//! length, and a checksum of the original data (all little-endian `i32`s).
+//! The stream ends with an empty block.
+//! This is not the same as the LZ4 frame format.
+
+use std::io::{Read, Write};
+
+use crate::{McError, McResult};
+
+const MAGIC: &[u8; 8] = b"LZ4Block";
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 4 + 4;
+const METHOD_RAW: u8 = 0x10;
+const METHOD_LZ4: u8 = 0x20;
+const BLOCK_SIZE: usize = 1 << 16;
+/// `log2(BLOCK_SIZE) - 10`
+const COMPRESSION_LEVEL: u8 = 6;
+const CHECKSUM_SEED: u32 = 0x9747b28c;
+
+fn checksum(data: &[u8]) -> u32 {
+    xxhash_rust::xxh32::xxh32(data, CHECKSUM_SEED) & 0x0FFFFFFF
+}
+
+fn write_block<W: Write>(writer: &mut W, method: u8, data: &[u8], original_len: usize, checksum: u32) -> McResult<()> {
+    let mut header = [0u8; HEADER_LEN];
+    header[0..8].copy_from_slice(MAGIC);
+    header[8] = method | COMPRESSION_LEVEL;
+    header[9..13].copy_from_slice(&(data.len() as u32).to_le_bytes());
+    header[13..17].copy_from_slice(&(original_len as u32).to_le_bytes());
+    header[17..21].copy_from_slice(&checksum.to_le_bytes());
+    writer.write_all(&header)?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+/// Compresses `data` into the LZ4 block stream format and writes it to `writer`.
+pub fn compress_lz4_blocks<W: Write>(data: &[u8], writer: &mut W) -> McResult<()> {
+    for block in data.chunks(BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(block);
+        // Like lz4-java, store the block uncompressed if compressing doesn't make it smaller.
+        if compressed.len() < block.len() {
+            write_block(writer, METHOD_LZ4, &compressed, block.len(), checksum(block))?;
+        } else {
+            write_block(writer, METHOD_RAW, block, block.len(), checksum(block))?;
+        }
+    }
+    write_block(writer, METHOD_RAW, &[], 0, 0)
+}
+
+/// Reads an LZ4 block stream from `reader` until the end marker and returns the decompressed data.
+pub fn decompress_lz4_blocks<R: Read>(mut reader: R) -> McResult<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut header = [0u8; HEADER_LEN];
+    let mut compressed = Vec::new();
+    loop {
+        reader.read_exact(&mut header)?;
+        if &header[0..8] != MAGIC {
+            return McError::custom("Invalid LZ4 block magic.");
+        }
+        let method = header[8] & 0xF0;
+        let compressed_len = u32::from_le_bytes(header[9..13].try_into().unwrap()) as usize;
+        let original_len = u32::from_le_bytes(header[13..17].try_into().unwrap()) as usize;
+        let expected_checksum = u32::from_le_bytes(header[17..21].try_into().unwrap());
+        if original_len == 0 {
+            return Ok(output);
+        }
+        if original_len > BLOCK_SIZE << 9 {
+            return McError::custom("LZ4 block is too large.");
+        }
+        let start = output.len();
+        match method {
+            METHOD_RAW => {
+                if compressed_len != original_len {
+                    return McError::custom("Invalid LZ4 block length.");
+                }
+                output.resize(start + original_len, 0);
+                reader.read_exact(&mut output[start..])?;
+            },
+            METHOD_LZ4 => {
+                compressed.resize(compressed_len, 0);
+                reader.read_exact(&mut compressed)?;
+                output.resize(start + original_len, 0);
+                let decompressed = lz4_flex::block::decompress_into(&compressed, &mut output[start..])
+                    .map_err(|err| McError::Custom(format!("Failed to decompress LZ4 block: {err}")))?;
+                if decompressed != original_len {
+                    return McError::custom("Invalid LZ4 block length.");
+                }
+            },
+            _ => return McError::custom("Invalid LZ4 block method."),
+        }
+        if checksum(&output[start..]) != expected_checksum {
+            return McError::custom("LZ4 block checksum mismatch.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lz4_block_round_trip_test() {
+        let data = (0..200000u32).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+        let mut stream = Vec::new();
+        compress_lz4_blocks(&data, &mut stream).unwrap();
+        assert_eq!(&stream[0..8], MAGIC);
+        assert!(stream.len() < data.len());
+        assert_eq!(decompress_lz4_blocks(stream.as_slice()).unwrap(), data);
+        // An empty stream is only the end marker.
+        stream.clear();
+        compress_lz4_blocks(&[], &mut stream).unwrap();
+        assert_eq!(stream.len(), HEADER_LEN);
+        assert!(decompress_lz4_blocks(stream.as_slice()).unwrap().is_empty());
+    }
+}
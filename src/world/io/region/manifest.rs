@@ -0,0 +1,68 @@
+//! Chunk content manifests for verifying region files.
+//!
+//! A manifest is a list of every present chunk in a region file along with
+//! the [content hash](crate::nbt::tag::Tag::content_hash) of its NBT.
+//! Since the hash is computed from the decoded NBT, it does not change when
+//! a chunk is recompressed or moved to another sector, so a manifest taken
+//! before a backup can be used to verify the restored region file.
+//! With the `serde` feature, a manifest can be serialized to be stored
+//! alongside the backup.
+
+use std::{collections::HashMap, path::Path};
+
+use crate::McResult;
+
+use super::prelude::*;
+
+/// A list of present chunks and the content hash of each chunk's NBT.
+pub type RegionManifest = Vec<(RegionCoord, u64)>;
+
+/// Reads every present chunk in the region file and returns the content hash of each.
+/// The entries are ordered by [RegionCoord].
+pub fn region_manifest<P: AsRef<Path>>(path: P) -> McResult<RegionManifest> {
+    RegionFile::open(path)?.content_hashes()
+}
+
+/// Compares the region file at `path` to a previously generated manifest.
+/// Returns the coordinates of the chunks that differ, which includes chunks that are
+/// missing from either the region file or the manifest. The result is sorted.
+pub fn verify_against_manifest<P: AsRef<Path>>(path: P, manifest: &[(RegionCoord, u64)]) -> McResult<Vec<RegionCoord>> {
+    let current = region_manifest(path)?;
+    let mut expected = manifest.iter().copied().collect::<HashMap<RegionCoord, u64>>();
+    let mut differing = Vec::new();
+    for (coord, hash) in current {
+        match expected.remove(&coord) {
+            Some(expected_hash) if expected_hash == hash => (),
+            _ => differing.push(coord),
+        }
+    }
+    // Whatever remains was in the manifest, but not in the region file.
+    differing.extend(expected.into_keys());
+    differing.sort();
+    Ok(differing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+
+    #[test]
+    fn manifest_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        for x in 0..3u16 {
+            region.write_data((x, 0u16), &NamedTag::new(Tag::Int(x as i32))).unwrap();
+        }
+        let manifest = region_manifest(&path).unwrap();
+        assert_eq!(manifest.len(), 3);
+        // Recompressing a chunk doesn't change its content hash.
+        region.write_data_with_scheme((1u16, 0u16), &NamedTag::new(Tag::Int(1)), CompressionScheme::GZip).unwrap();
+        assert!(verify_against_manifest(&path, &manifest).unwrap().is_empty());
+
+        region.write_data((2u16, 0u16), &NamedTag::new(Tag::Int(20))).unwrap();
+        region.delete_data((0u16, 0u16)).unwrap();
+        assert_eq!(verify_against_manifest(&path, &manifest).unwrap(), vec![RegionCoord::new(0, 0), RegionCoord::new(2, 0)]);
+    }
+}
@@ -5,17 +5,45 @@ pub mod timestamp;
 pub use timestamp::Timestamp;
 pub mod coord;
 pub use coord::RegionCoord;
+pub mod filename;
+pub use filename::RegionFileName;
+pub mod format;
+pub use format::RegionFormat;
 pub mod info;
 pub mod compressionscheme;
+#[cfg(feature = "lz4")]
+pub mod lz4block;
 pub use compressionscheme::CompressionScheme;
+pub mod compressor;
+pub use compressor::Compressor;
+pub mod rawchunk;
+pub use rawchunk::RawChunk;
 pub mod managedsector;
 pub use managedsector::ManagedSector;
 pub mod sectormanager;
 pub use sectormanager::*;
+pub mod backend;
+pub mod durability;
+pub use durability::Durability;
+pub use backend::RegionBackend;
 pub mod regionfile;
 pub use regionfile::RegionFile;
+pub mod journal;
+pub mod checksum;
+pub mod manifest;
+pub mod parallel;
+pub mod streaming;
+pub mod builder;
+pub mod sample;
+pub mod recompress;
+#[cfg(feature = "tokio")]
+pub mod asyncregion;
+pub mod verify;
+pub mod diff;
 pub mod prelude;
 
+use std::path::{Path, PathBuf};
+
 /*	╭──────────────────────────────────────────────────────────────────────────────╮
     │ How do Region Files work?                                                    │
     ╰──────────────────────────────────────────────────────────────────────────────╯
@@ -56,12 +84,23 @@ pub mod prelude;
     TODO: Research whether or not Minecraft ever saves a sector offset as
         : occupied while the length at that offset is zero.
 
+    A related edge case is a sector offset that is non-zero while the size is
+    zero (a "degenerate" sector, see RegionSector::is_degenerate). It doesn't
+    occupy any sectors, so it is treated the same as an empty sector: readers
+    report that there is no data without seeking to the offset, and writing to
+    that chunk allocates a new sector.
+
     Following the length is a single byte representing the compression scheme used
     to save that chunk. The possible values are 1 for GZip, 2 for ZLib, and 3 for 
     uncompressed. After the compression scheme are (length - 1) bytes of data that
     represent a chunk within a Minecraft world, which is in NBT format. This chunk
     is a named tag.
 
+    If a chunk needs more than 255 sectors, Minecraft sets the high bit (128) of the
+    compression scheme and writes the compressed data to a `c.<x>.<z>.mcc` file in
+    the same directory instead, where x and z are the chunk's world coordinates.
+    The region file only holds a length of 1 followed by the compression scheme.
+
     After the chunk is some pad bytes (typically zeroes, but I don't think that it
     is a requirement that the pad bytes are zeroes).
 
@@ -69,6 +108,17 @@ pub mod prelude;
     will reject it if it's not.
 */
 
+/// Gets the path of the external chunk file (`c.<x>.<z>.mcc`) for a chunk within
+/// the region file at `region_path`.
+/// Returns `None` if the region file isn't named `r.<x>.<z>.mca`.
+pub fn external_chunk_path<P: AsRef<Path>>(region_path: P, coord: RegionCoord) -> Option<PathBuf> {
+    let region_path = region_path.as_ref();
+    let region = filename::RegionFileName::from_path(region_path)?;
+    let x = region.x * 32 + coord.x() as i64;
+    let z = region.z * 32 + coord.z() as i64;
+    Some(region_path.with_file_name(format!("c.{x}.{z}.mcc")))
+}
+
 /// Tests if a value is a multiple of 4096.
 pub const fn is_multiple_of_4096(n: u64) -> bool {
     (n & 4095) == 0
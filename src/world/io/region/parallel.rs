@@ -0,0 +1,97 @@
+//! Processing every region file in a directory across multiple threads.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Mutex, atomic::{AtomicUsize, Ordering}},
+    thread,
+};
+
+use crate::{
+    McResult, McError, CancellationToken,
+    math::coord::{Dimension, WorldCoord},
+};
+
+use super::{filename::RegionFileName, RegionFile};
+
+/// Guesses the dimension of a region directory from the name of its parent directory.
+/// `DIM-1` is the Nether, `DIM1` is the End, and anything else is the Overworld.
+fn region_directory_dimension(region_dir: &Path) -> Dimension {
+    let parent = region_dir.parent()
+        .and_then(Path::file_name)
+        .and_then(|name| name.to_str())
+        .map(str::to_ascii_uppercase);
+    match parent.as_deref() {
+        Some("DIM-1") => Dimension::Nether,
+        Some("DIM1") => Dimension::TheEnd,
+        _ => Dimension::Overworld,
+    }
+}
+
+/// Runs `f` on every `.mca` region file in `region_dir` using `threads` worker threads
+/// (or the available parallelism if `threads` is `0`).
+/// The [WorldCoord] passed to `f` is the coordinate of the region (not a chunk), parsed
+/// from the file name. Its dimension is guessed from the name of the parent directory.
+/// Files with names that aren't valid region file names are skipped.
+///
+/// An error is only returned if the directory can't be read. Errors from opening
+/// or processing individual region files are collected and returned with the path
+/// of the file that caused them.
+pub fn process_regions_parallel<P, F>(region_dir: P, threads: usize, f: F) -> McResult<Vec<(PathBuf, McError)>>
+where
+    P: AsRef<Path>,
+    F: Fn(WorldCoord, &mut RegionFile) -> McResult<()> + Sync,
+{
+    process_regions_parallel_cancellable(region_dir, threads, &CancellationToken::new(), f)
+}
+
+/// Like [process_regions_parallel], but stops once `cancel` is cancelled. The workers finish the
+/// region files they're processing (`f` can check the token itself to stop sooner), don't start
+/// any others, and [McError::Cancelled] is returned.
+pub fn process_regions_parallel_cancellable<P, F>(region_dir: P, threads: usize, cancel: &CancellationToken, f: F) -> McResult<Vec<(PathBuf, McError)>>
+where
+    P: AsRef<Path>,
+    F: Fn(WorldCoord, &mut RegionFile) -> McResult<()> + Sync,
+{
+    let region_dir = region_dir.as_ref();
+    let dimension = region_directory_dimension(region_dir);
+    let mut regions = Vec::new();
+    for entry in std::fs::read_dir(region_dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Some(name) = RegionFileName::from_path(&path) else {
+            continue;
+        };
+        regions.push((WorldCoord::new(name.x, name.z, dimension), path));
+    }
+    let threads = if threads == 0 {
+        thread::available_parallelism().map(|count| count.get()).unwrap_or(1)
+    } else {
+        threads
+    }.min(regions.len().max(1));
+    let next = AtomicUsize::new(0);
+    let errors = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while !cancel.is_cancelled() {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((coord, path)) = regions.get(index) else {
+                        break;
+                    };
+                    let result = RegionFile::open(path).and_then(|mut region| f(*coord, &mut region));
+                    if let Err(err) = result {
+                        if let Ok(mut errors) = errors.lock() {
+                            errors.push((path.clone(), err));
+                        }
+                    }
+                }
+            });
+        }
+    });
+    cancel.check()?;
+    let mut errors = errors.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+    errors.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(errors)
+}
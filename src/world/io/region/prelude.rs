@@ -6,6 +6,25 @@ pub use super::{
     header::*,
     info::*,
     coord::*,
+    filename::*,
+    format::*,
     compressionscheme::*,
+    compressor::*,
+    rawchunk::*,
+    backend::*,
+    durability::*,
     regionfile::*,
+    manifest::*,
+    checksum::*,
+    parallel::*,
+    streaming::*,
+    builder::*,
+    sample::*,
+    recompress::*,
+    verify::*,
+};
+
+#[cfg(feature = "tokio")]
+pub use super::{
+    asyncregion::*,
 };
\ No newline at end of file
@@ -0,0 +1,60 @@
+//! Chunks as they're stored in a region file, still compressed.
+
+use std::io::Read;
+
+use crate::{McResult, ioext::Readable};
+
+use super::CompressionScheme;
+
+/// The compressed data of a chunk along with its [CompressionScheme], exactly as it's stored in a region file
+/// (or in an external `.mcc` file). Read with [RegionFile::read_raw](super::RegionFile::read_raw) and written with
+/// [RegionFile::write_raw](super::RegionFile::write_raw), so that chunks can be moved between region files
+/// without being decompressed and compressed again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawChunk {
+    pub scheme: CompressionScheme,
+    /// The compressed data, without the length or the compression scheme.
+    pub data: Vec<u8>,
+}
+
+impl RawChunk {
+    pub fn new(scheme: CompressionScheme, data: Vec<u8>) -> Self {
+        Self { scheme, data }
+    }
+
+    /// Decompresses the data, returning the uncompressed NBT bytes.
+    pub fn decompress(&self) -> McResult<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        self.scheme.decoder(self.data.as_slice())?.read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    /// Decompresses and decodes the data.
+    pub fn decode<T: Readable>(&self) -> McResult<T> {
+        T::read_from(&mut self.scheme.decoder(self.data.as_slice())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nbt::tag::{NamedTag, Tag};
+    use crate::world::io::region::RegionFile;
+
+    #[test]
+    fn raw_chunk_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut source = RegionFile::create(dir.path().join("r.0.0.mca")).unwrap();
+        let chunk = NamedTag::new(Tag::String("raw".repeat(100)));
+        source.write_data_timestamped((1, 2), &chunk, 42).unwrap();
+        assert!(matches!(source.read_raw((3, 3)), Err(crate::McError::RegionDataNotFound)));
+
+        let raw = source.read_raw((1, 2)).unwrap();
+        assert_eq!(raw.decode::<NamedTag>().unwrap().tag().content_hash(), chunk.tag().content_hash());
+        let mut destination = RegionFile::create(dir.path().join("r.1.0.mca")).unwrap();
+        destination.write_raw_timestamped((1, 2), &raw, 42).unwrap();
+        assert_eq!(destination.read_raw((1, 2)).unwrap(), raw);
+        assert_eq!(destination.get_timestamp((1, 2)), source.get_timestamp((1, 2)));
+        let read: NamedTag = destination.read_data((1, 2)).unwrap();
+        assert_eq!(read.tag().content_hash(), chunk.tag().content_hash());
+    }
+}
@@ -0,0 +1,72 @@
+//! Rewriting region files with a different compression scheme or level.
+
+use std::{io::Read, path::Path};
+
+use flate2::Compression;
+
+use crate::{McResult, McError};
+
+use super::prelude::*;
+
+/// Rewrites every chunk of the region file at `input` to a new region file at `output`,
+/// compressed with the `target` scheme at the given `level`. Returns an error if `output` already exists.
+///
+/// Chunks are decompressed and recompressed without parsing their NBT, and keep their timestamps.
+/// Chunks stored in external `.mcc` files are read, but are written into the new region file,
+/// so [McError::RegionDataTooLarge] is returned if one of them doesn't fit.
+/// The `level` is ignored for schemes that don't have levels (see [CompressionScheme::compress]).
+///
+/// Returns the number of chunks that were written.
+pub fn recompress_region<P1: AsRef<Path>, P2: AsRef<Path>>(input: P1, output: P2, target: CompressionScheme, level: Compression) -> McResult<usize> {
+    let mut region = RegionFile::open(input)?;
+    let mut writer = StreamingRegionWriter::with_compression(output, level)?;
+    let mut data = Vec::new();
+    let mut count = 0;
+    for index in 0..1024usize {
+        let coord = RegionCoord::from(index);
+        if region.get_sector(coord).sector_count() == 0 {
+            continue;
+        }
+        data.clear();
+        let result = region.read(coord, |mut decoder| {
+            decoder.read_to_end(&mut data)?;
+            Ok(())
+        });
+        match result {
+            Ok(()) => (),
+            Err(McError::RegionDataNotFound) => continue,
+            Err(err) => return Err(err),
+        }
+        writer.push_uncompressed(coord, &data, target, region.get_timestamp(coord))?;
+        count += 1;
+    }
+    writer.finish()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+
+    #[test]
+    fn recompress_region_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("r.0.0.mca");
+        let mut writer = StreamingRegionWriter::with_compression(&input, Compression::fast()).unwrap();
+        writer.push_timestamped((3u16, 4u16), &NamedTag::new(Tag::String("abc".repeat(5000))), 1234).unwrap();
+        writer.finish().unwrap();
+
+        let uncompressed = dir.path().join("uncompressed.mca");
+        assert_eq!(recompress_region(&input, &uncompressed, CompressionScheme::Uncompressed, Compression::none()).unwrap(), 1);
+        let gzip = dir.path().join("gzip.mca");
+        recompress_region(&uncompressed, &gzip, CompressionScheme::GZip, Compression::best()).unwrap();
+        assert!(std::fs::metadata(&gzip).unwrap().len() < std::fs::metadata(&uncompressed).unwrap().len());
+
+        let mut region = RegionFile::open(&gzip).unwrap();
+        assert_eq!(u32::from(region.get_timestamp((3u16, 4u16))), 1234);
+        let tag: NamedTag = region.read_data((3u16, 4u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::String(text) if text.len() == 15000));
+        assert!(recompress_region(&input, &gzip, CompressionScheme::GZip, Compression::best()).is_err());
+    }
+}
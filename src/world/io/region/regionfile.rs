@@ -1,6 +1,7 @@
 // TODO: Remove this when you no longer want to silence the warnings.
 
 use std::{
+    collections::HashMap,
     fs::File, io::{
         BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Take, Write
     }, path::{
@@ -19,15 +20,75 @@ use flate2::{
 };
 
 use crate::{
-    McResult, McError,
+    McResult, McError, ErrorContext, ResultExt,
     ioext::*,
+    nbt::tag::NamedTag,
+    world::{
+        blockregistry::BlockRegistry,
+        blockstate::BlockState,
+        chunk::{Chunk, decode_chunk_for_format},
+        chunkversion::{chunk_nbt_position, is_mcregion_chunk_nbt},
+    },
 };
 
 use super::{
     prelude::*,
-    {required_sectors, pad_size},
+    journal::{remove_journal, replay_journal, write_journal},
+    {required_sectors, pad_size, external_chunk_path},
 };
 
+/// Adds the coordinate of a chunk and the path of its region file to an error reading the chunk.
+/// Region files that live in memory have an empty path, so the path is left out.
+fn chunk_error_context(path: &Path, coord: RegionCoord, err: McError) -> McError {
+    let err = err.with_context(ErrorContext::RegionChunk(coord));
+    if path.as_os_str().is_empty() {
+        err
+    } else {
+        err.with_context(ErrorContext::Path(path.to_owned()))
+    }
+}
+
+/// Writes a file through a temporary file that replaces it once its contents have reached the disk,
+/// so the file is never left partly written.
+fn write_file_synced(path: &Path, data: &[u8]) -> McResult<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Removes an external chunk file if it exists.
+fn remove_external_chunk(path: &Path) -> McResult<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Overwrites an external chunk file with zeroes, if there is one, so that removing it doesn't leave the chunk on the disk.
+fn shred_external_chunk(path: &Path) -> McResult<()> {
+    let mut file = match File::options().write(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let len = file.metadata()?.len();
+    file.write_zeroes(len)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// The number of bytes of `data` that have to be written at `offset` in a sparse file that's `file_len` bytes long.
+/// Trailing zeroes past the end of the file are left out, since extending the file fills them in.
+fn sparse_write_len(offset: u64, data: &[u8], file_len: u64) -> usize {
+    let overwritten = file_len.saturating_sub(offset).min(data.len() as u64) as usize;
+    let nonzero = data.iter().rposition(|&byte| byte != 0).map_or(0, |index| index + 1);
+    overwritten.max(nonzero)
+}
+
 pub trait RegionManager {
     type Sector;
     //	write_data
@@ -49,7 +110,7 @@ pub struct RegionFile {
     header: RegionHeader,
     sector_manager: SectorManager,
     /// This file handle is for both reading and writing.
-    file_handle: File,
+    file_handle: RegionBackend,
     path: PathBuf,
     /// Because the write size of a value sometimes can't quite be known until
     /// after it has been written, it will be helpful to have a buffer to write
@@ -57,13 +118,35 @@ pub struct RegionFile {
     /// many 4KiB blocks are needed to write this data so that a sector can be
     /// allocated.
     write_buf: Cursor<Vec<u8>>,
+    /// Holds the uncompressed NBT of a chunk while it's being written, so that the
+    /// allocation is reused between writes.
+    scratch: Vec<u8>,
+    /// See [RegionFile::set_compressor].
+    compressor: Option<Box<dyn Compressor>>,
+    /// The chunk most recently decoded by [RegionFile::get_block_state_at].
+    cached_chunk: Option<(RegionCoord, Chunk)>,
+    format: RegionFormat,
+    /// See [RegionFile::set_journaled].
+    journaled: bool,
+    /// See [RegionFile::set_sparse].
+    sparse: bool,
+    /// See [RegionFile::set_truncate_on_delete].
+    truncate_on_delete: bool,
+    durability: Durability,
     pub compression: Compression,
 }
 
 pub enum MultiDecoder<'a> {
-    GZip(GzDecoder<Take<BufReader<&'a mut File>>>),
-    ZLib(ZlibDecoder<Take<BufReader<&'a mut File>>>),
-    Uncompressed(Take<BufReader<&'a mut File>>),
+    GZip(GzDecoder<Take<BufReader<&'a mut RegionBackend>>>),
+    ZLib(ZlibDecoder<Take<BufReader<&'a mut RegionBackend>>>),
+    Uncompressed(Take<BufReader<&'a mut RegionBackend>>),
+    /// LZ4 block streams are decompressed all at once.
+    #[cfg(feature = "lz4")]
+    LZ4(Cursor<Vec<u8>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::read::Decoder<'a, BufReader<Take<BufReader<&'a mut RegionBackend>>>>),
+    /// A chunk stored in an external `.mcc` file.
+    External(Box<dyn Read + 'a>),
 }
 
 impl<'a> Read for MultiDecoder<'a> {
@@ -72,11 +155,17 @@ impl<'a> Read for MultiDecoder<'a> {
             MultiDecoder::GZip(reader) => reader.read(buf),
             MultiDecoder::ZLib(reader) => reader.read(buf),
             MultiDecoder::Uncompressed(reader) => reader.read(buf),
+            #[cfg(feature = "lz4")]
+            MultiDecoder::LZ4(reader) => reader.read(buf),
+            #[cfg(feature = "zstd")]
+            MultiDecoder::Zstd(reader) => reader.read(buf),
+            MultiDecoder::External(reader) => reader.read(buf),
         }
     }
 }
 
 impl RegionFile {
+    /// The path of the region file. This is empty for regions that are in memory.
     pub fn path(&self) -> &Path {
         &self.path
     }
@@ -89,10 +178,114 @@ impl RegionFile {
         &self.header.timestamps
     }
 
+    /// The [SectorManager] that tracks the unused sectors of the file.
+    pub fn sector_manager(&self) -> &SectorManager {
+        &self.sector_manager
+    }
+
+    /// Turns journaled writes on or off. Journaled writes are slower, since they wait for the data to reach the disk,
+    /// but a write that's interrupted (by a crash or a power loss) never damages the chunks that were already in the file.
+    /// The chunk being written is either left as it was or is written completely the next time the file is opened.
+    /// See [journal](super::journal) for how this works. Regions in memory are never journaled.
+    pub fn set_journaled(&mut self, journaled: bool) {
+        self.journaled = journaled;
+    }
+
+    pub fn is_journaled(&self) -> bool {
+        self.journaled
+    }
+
+    /// Turns sparse writes on or off. When writing past the end of the file, sparse writes leave out the padding
+    /// after the last chunk and extend the file with `set_len` instead, which file systems that support sparse files
+    /// store as a hole. Regions created with [RegionFile::create_sparse] are sparse.
+    pub fn set_sparse(&mut self, sparse: bool) {
+        self.sparse = sparse;
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        self.sparse
+    }
+
+    /// Turns truncation after deletes on or off. When it's on, [RegionFile::delete_data] calls
+    /// [RegionFile::truncate] so that deleting the chunks at the end of the file shrinks it.
+    pub fn set_truncate_on_delete(&mut self, truncate_on_delete: bool) {
+        self.truncate_on_delete = truncate_on_delete;
+    }
+
+    pub fn truncates_on_delete(&self) -> bool {
+        self.truncate_on_delete
+    }
+
+    /// Sets the [Compressor] used by [RegionFile::write_data] and the functions built on it.
+    /// With `None` (the default), chunks are compressed with ZLib at the [RegionFile::compression] level.
+    /// [RegionFile::write] and [RegionFile::write_data_with_scheme] always use their own compression.
+    pub fn set_compressor(&mut self, compressor: Option<Box<dyn Compressor>>) {
+        self.compressor = compressor;
+    }
+
+    /// Sets when writes are synced to the disk. The default is [Durability::Never].
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
+
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Flushes the file, and waits for the writes to reach the disk unless the [Durability] is [Durability::Never].
+    pub fn flush(&mut self) -> McResult<()> {
+        self.file_handle.flush()?;
+        if self.durability.syncs_on_flush() {
+            self.file_handle.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Syncs a write that just finished if the [Durability] is [Durability::EveryWrite].
+    fn sync_write(&mut self) -> McResult<()> {
+        if self.durability.syncs_every_write() {
+            self.file_handle.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Sets the [AllocationStrategy] used when chunks are written.
+    pub fn set_allocation_strategy(&mut self, strategy: AllocationStrategy) {
+        self.sector_manager.set_strategy(strategy);
+    }
+
     pub fn header(&self) -> &RegionHeader {
         &self.header
     }
 
+    /// Allows direct mutation of the header for repairs that can't be expressed through
+    /// the rest of the API (such as manually fixing a sector entry).
+    /// After `edit` returns, the entire 8KiB header is written to disk and the
+    /// [SectorManager] is rebuilt from the new sector table so that it stays consistent.
+    /// No validation is performed on the edited header.
+    pub fn edit_header<F: FnOnce(&mut RegionHeader)>(&mut self, edit: F) -> McResult<()> {
+        edit(&mut self.header);
+        self.write_header()?;
+        self.sync_write()?;
+        self.sector_manager = SectorManager::from(self.header.sectors.iter())
+            .with_strategy(self.sector_manager.strategy());
+        Ok(())
+    }
+
+    /// Writes the in-memory header to the beginning of the file.
+    fn write_header(&mut self) -> McResult<()> {
+        let mut writer = BufWriter::new(&mut self.file_handle);
+        writer.seek(SeekFrom::Start(0))?;
+        self.header.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads the 8KiB header verbatim from the file without going through the parsed [RegionHeader].
+    pub fn raw_header(&mut self) -> McResult<[u8; 8192]> {
+        Ok(*read_raw_header(&mut self.file_handle)?)
+    }
+
     pub fn get_sector<C: Into<RegionCoord>>(&self, coord: C) -> RegionSector {
         let coord: RegionCoord = coord.into();
         self.header.sectors[coord.index()]
@@ -114,54 +307,191 @@ impl RegionFile {
     }
 
     /// Attempts to open a Minecraft region file at the given path, returning an error if it is not found.
+    /// If a [journaled](RegionFile::set_journaled) write to the file was interrupted, it's finished first.
     pub fn open<P: AsRef<Path>>(path: P) -> McResult<Self> {
         let path = path.as_ref();
         let mut file_handle = File::options()
             // Need to be able to read and write.
             .read(true).write(true)
-            .open(path)?;
-        // Seek to the end to figure out the size of the file.
-        file_handle.seek(SeekFrom::End(0))?;
-        let file_size = file_handle.stream_position()?;
-        if file_size < 8192 {
+            .open(path)
+            .with_path(path)?;
+        replay_journal(&mut file_handle, path).with_path(path)?;
+        Self::from_backend(RegionBackend::File(file_handle), path.to_owned()).with_path(path)
+    }
+
+    /// Opens a region file for reading only. The file is never modified: if a [journaled](RegionFile::set_journaled)
+    /// write was interrupted, it isn't finished, so the chunk it was writing reads as it was before the write.
+    /// Writing to the region returns an error.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let path = path.as_ref();
+        let file_handle = File::open(path).with_path(path)?;
+        Self::from_backend(RegionBackend::File(file_handle), path.to_owned()).with_path(path)
+    }
+
+    /// Opens a region file from its bytes, keeping it in memory.
+    /// Nothing is written to the file system: use [RegionFile::into_bytes] to get the bytes
+    /// after editing it. The region has no path, so chunks that are too large to fit in the
+    /// region can't be written or read (see [RegionFile::path]).
+    pub fn from_bytes(data: Vec<u8>) -> McResult<Self> {
+        Self::from_backend(RegionBackend::memory(data), PathBuf::new())
+    }
+
+    /// Creates an empty region file in memory. See [RegionFile::from_bytes].
+    pub fn new_in_memory() -> Self {
+        Self {
+            file_handle: RegionBackend::memory(vec![0; 4096*2]),
+            compression: Compression::best(),
+            write_buf: Cursor::new(Vec::with_capacity(4096*2)),
+            scratch: Vec::new(),
+            compressor: None,
+            cached_chunk: None,
+            format: RegionFormat::Anvil,
+            journaled: false,
+            sparse: false,
+            truncate_on_delete: false,
+            durability: Durability::Never,
+            header: RegionHeader::default(),
+            sector_manager: SectorManager::new(),
+            path: PathBuf::new(),
+        }
+    }
+
+    /// Returns true if the region is kept in memory rather than in a file.
+    pub fn is_in_memory(&self) -> bool {
+        matches!(self.file_handle, RegionBackend::Memory(_))
+    }
+
+    /// The bytes of the region file. For a region that was opened from a file, the whole file is read.
+    pub fn into_bytes(self) -> McResult<Vec<u8>> {
+        self.file_handle.into_bytes()
+    }
+
+    fn from_backend(mut file_handle: RegionBackend, path: PathBuf) -> McResult<Self> {
+        if file_handle.len()? < 8192 {
             // The size was too small to hold the header, which means it isn't
             // a valid region file.
             return Err(McError::InvalidRegionFile);
         }
         file_handle.seek(SeekFrom::Start(0))?;
-        let header = {				
+        let header = {
             let mut temp_reader = BufReader::new((&mut file_handle).take(4096*2));
             RegionHeader::read_from(&mut temp_reader)?
         };
         let sector_manager = SectorManager::from(header.sectors.iter());
-        Ok(Self {
+        let format = RegionFormat::from_path(&path);
+        let mut region = Self {
             file_handle,
             header,
             compression: Compression::best(),
             sector_manager,
             write_buf: Cursor::new(Vec::with_capacity(4096*2)),
-            path: path.to_owned(),
-        })
+            scratch: Vec::new(),
+            compressor: None,
+            cached_chunk: None,
+            format: RegionFormat::Anvil,
+            journaled: false,
+            sparse: false,
+            truncate_on_delete: false,
+            durability: Durability::Never,
+            path,
+        };
+        region.format = match format {
+            Some(format) => format,
+            None => region.detect_format(),
+        };
+        Ok(region)
+    }
+
+    /// The format of the region file.
+    /// When the region file is opened, this is determined from the file extension (`.mca` or `.mcr`).
+    /// If the extension is unknown, the first present chunk is inspected instead.
+    /// New region files are always [RegionFormat::Anvil].
+    pub fn format(&self) -> RegionFormat {
+        self.format
+    }
+
+    /// Detects the format from the NBT of the first present chunk.
+    /// Defaults to [RegionFormat::Anvil] if there are no chunks, or if the chunk can't be read,
+    /// so that a damaged chunk never keeps the rest of the file from being opened.
+    fn detect_format(&mut self) -> RegionFormat {
+        for index in 0..1024u16 {
+            match self.read_data::<_, NamedTag>(index) {
+                Ok(chunk) if is_mcregion_chunk_nbt(chunk.tag()) => return RegionFormat::McRegion,
+                Err(McError::RegionDataNotFound) => continue,
+                _ => return RegionFormat::Anvil,
+            }
+        }
+        RegionFormat::Anvil
     }
 
     /// Attempts to create a new Minecraft region file at the given path, returning an error if it already exists.
     pub fn create<P: AsRef<Path>>(path: P) -> McResult<Self> {
-        let path = path.as_ref();
+        Self::create_with_capacity(path, 0)
+    }
+
+    /// Attempts to create a new Minecraft region file at the given path with `sectors` pre-zeroed
+    /// 4KiB sectors following the header, returning an error if it already exists.
+    /// The reserved sectors are registered as unused in the [SectorManager] so that
+    /// new writes will fill the reserved space before growing the file.
+    pub fn create_with_capacity<P: AsRef<Path>>(path: P, sectors: u32) -> McResult<Self> {
+        Self::create_with(path.as_ref(), sectors, false)
+    }
+
+    /// Like [RegionFile::create], but the header is allocated with `set_len` rather than written,
+    /// and the region is [sparse](RegionFile::set_sparse). This saves IO when creating many near-empty regions.
+    pub fn create_sparse<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        Self::create_with(path.as_ref(), 0, true)
+    }
+
+    /// Like [RegionFile::create_with_capacity], but the header and the reserved sectors are allocated
+    /// with `set_len` rather than written, and the region is [sparse](RegionFile::set_sparse).
+    pub fn create_sparse_with_capacity<P: AsRef<Path>>(path: P, sectors: u32) -> McResult<Self> {
+        Self::create_with(path.as_ref(), sectors, true)
+    }
+
+    fn create_with(path: &Path, sectors: u32, sparse: bool) -> McResult<Self> {
+        // Sector offsets are 24 bits, so anything beyond that can never be allocated.
+        if sectors > ManagedSector::ACCESSIBLE.end - 2 {
+            return Err(McError::OutOfRange);
+        }
         // Create region file with empty header.
         let mut file_handle = File::options()
             // Need to be able to read and write.
             .read(true).write(true)
             // The file doesn't exist, so we need to create it.
             .create_new(true)
-            .open(path)?;
-        // Write an empty header since this is a new file.
-        file_handle.write_zeroes(4096*2)?;
+            .open(path)
+            .with_path(path)?;
+        // Write an empty header since this is a new file, followed by the reserved sectors.
+        let size = 4096*2 + (sectors as u64) * 4096;
+        if sparse {
+            file_handle.set_len(size).with_path(path)?;
+        } else {
+            file_handle.write_zeroes(size).with_path(path)?;
+        }
+        let sector_manager = if sectors == 0 {
+            SectorManager::new()
+        } else {
+            let reserved = ManagedSector::new(2, 2 + sectors);
+            SectorManager::with_unused(
+                ManagedSector::new(reserved.end, u32::MAX),
+                vec![reserved],
+            )
+        };
         Ok(Self {
-            file_handle,
+            file_handle: RegionBackend::File(file_handle),
             compression: Compression::best(),
             write_buf: Cursor::new(Vec::with_capacity(4096*2)),
+            scratch: Vec::new(),
+            compressor: None,
+            cached_chunk: None,
+            format: RegionFormat::Anvil,
+            journaled: false,
+            sparse,
+            truncate_on_delete: false,
+            durability: Durability::Never,
             header: RegionHeader::default(),
-            sector_manager: SectorManager::new(),
+            sector_manager,
             path: path.to_owned(),
         })
     }
@@ -176,6 +506,17 @@ impl RegionFile {
         }
     }
 
+    /// Creates a new [RegionFile] object, opening a Minecraft region file at the given path, or
+    /// creating it with `sectors` reserved sectors (see [RegionFile::create_with_capacity]) if it doesn't exist.
+    pub fn open_or_create_with_capacity<P: AsRef<Path>>(path: P, sectors: u32) -> McResult<Self> {
+        let path = path.as_ref();
+        if path.is_file() {
+            Self::open(path)
+        } else {
+            Self::create_with_capacity(path, sectors)
+        }
+    }
+
     pub fn write_with_utcnow<C: Into<RegionCoord>, F: FnMut(&mut ZlibEncoder<&mut Cursor<Vec<u8>>>) -> McResult<()>>(&mut self, coord: C, mut write: F) -> McResult<RegionSector> {
         self.write_timestamped(coord, Timestamp::utc_now(), |writer| {
             write(writer)
@@ -188,36 +529,60 @@ impl RegionFile {
         self.write_data_timestamped(coord, value, Timestamp::utc_now())
     }
 
+    /// Reads the chunk at `coord` by passing a decoder for its data to `read`.
+    /// Returns [McError::RegionDataNotFound] if the chunk isn't present. Other errors are
+    /// wrapped with the chunk's coordinate and the region file's path (see [McError::with_context]).
     pub fn read<'a, C: Into<RegionCoord>, R, F: FnMut(MultiDecoder<'a>) -> McResult<R>>(&'a mut self, coord: C, mut read: F) -> McResult<R> {
         let coord: RegionCoord = coord.into();
         let sector = self.header.sectors[coord.index()];
-        if sector.is_empty() {
+        // Degenerate sectors (non-zero offset, zero size) have no data.
+        if sector.sector_count() == 0 {
             return Err(McError::RegionDataNotFound);
         }
+        let region_path = &self.path;
+        let context = |err: McError| chunk_error_context(region_path, coord, err);
         let mut reader = BufReader::new(&mut self.file_handle);
-        reader.seek(SeekFrom::Start(sector.offset()))?;
-        let length: u32 = reader.read_value()?;
+        reader.seek(SeekFrom::Start(sector.offset())).map_err(|err| context(err.into()))?;
+        let length: u32 = reader.read_value().map_err(context)?;
         if length == 0 {
             return Err(McError::RegionDataNotFound);
         }
-        let scheme: CompressionScheme = reader.read_value()?;
-        match scheme {
+        let (scheme, external) = CompressionScheme::read_with_external_flag(&mut reader).map_err(context)?;
+        if external {
+            let path = external_chunk_path(region_path, coord).ok_or(McError::ExternalChunkPathUnknown(coord)).map_err(context)?;
+            let file = File::open(&path).with_path(&path).map_err(context)?;
+            let decoder = scheme.decoder(BufReader::new(file)).map_err(context)?;
+            return read(MultiDecoder::External(decoder)).map_err(context);
+        }
+        // The compression scheme is included in the length.
+        let data = reader.take(length.saturating_sub(scheme.header_len()) as u64);
+        let result = match scheme {
             CompressionScheme::GZip => {
-                // Subtract 1 from length because the compression scheme is included in the length.
-                let decoder = GzDecoder::new(reader.take((length - 1) as u64));
+                let decoder = GzDecoder::new(data);
                 let multi = MultiDecoder::GZip(decoder);
                 read(multi)
             },
             CompressionScheme::ZLib => {
-                let decoder = ZlibDecoder::new(reader.take((length - 1) as u64));
+                let decoder = ZlibDecoder::new(data);
                 let multi = MultiDecoder::ZLib(decoder);
                 read(multi)
             },
             CompressionScheme::Uncompressed => {
-                let multi = MultiDecoder::Uncompressed(reader.take((length - 1) as u64));
+                let multi = MultiDecoder::Uncompressed(data);
                 read(multi)
             },
-        }
+            #[cfg(feature = "lz4")]
+            CompressionScheme::LZ4 => {
+                let decompressed = super::lz4block::decompress_lz4_blocks(data)?;
+                read(MultiDecoder::LZ4(Cursor::new(decompressed)))
+            },
+            #[cfg(feature = "zstd")]
+            CompressionScheme::Zstd => {
+                let decoder = zstd::stream::read::Decoder::new(data)?;
+                read(MultiDecoder::Zstd(decoder))
+            },
+        };
+        result.map_err(context)
     }
 
     pub fn read_data<C: Into<RegionCoord>, T: Readable>(&mut self, coord: C) -> McResult<T> {
@@ -226,10 +591,264 @@ impl RegionFile {
         })
     }
 
+    /// Decompresses the data at `coord` into `buffer`, replacing its contents, and returns the number of bytes read.
+    /// Reusing the same buffer for many chunks avoids allocating for each one, which pairs well with
+    /// [TagRef::read_named](crate::nbt::borrowed::TagRef::read_named) for read-only scans.
+    pub fn read_decompressed_into<C: Into<RegionCoord>>(&mut self, coord: C, buffer: &mut Vec<u8>) -> McResult<usize> {
+        self.read(coord, |mut decoder| {
+            buffer.clear();
+            Ok(decoder.read_to_end(buffer)?)
+        })
+    }
+
+    /// Reads the [BlockState] at a block within a chunk without loading the chunk into a world.
+    /// `local` is the `(x, y, z)` coordinate of the block, where `x` and `z` are relative
+    /// to the chunk (`0..16`) and `y` is the world height.
+    /// Returns `None` if `y` is outside of the chunk's sections or the section has no blocks.
+    ///
+    /// The most recently decoded chunk is cached, so sampling many blocks from the same
+    /// chunk only decodes it once. Block ids in the cached chunk belong to `registry`,
+    /// so the same registry should be passed on every call. Use
+    /// [RegionFile::clear_chunk_cache] before switching registries.
+    pub fn get_block_state_at<C: Into<RegionCoord>>(&mut self, registry: &mut BlockRegistry, chunk_coord: C, local: (u8, i32, u8)) -> McResult<Option<BlockState>> {
+        let coord: RegionCoord = chunk_coord.into();
+        let (x, y, z) = local;
+        McError::range_check(x, 0..16)?;
+        McError::range_check(z, 0..16)?;
+        let chunk = match self.cached_chunk.take() {
+            Some((cached, chunk)) if cached == coord => chunk,
+            _ => {
+                let root: NamedTag = self.read_data(coord)?;
+                decode_chunk_for_format(registry, root.tag, self.format())?
+            },
+        };
+        let sections = &chunk.sections.sections;
+        let state = sections.first()
+            .map(|bottom| y.div_euclid(16) - bottom.y as i32)
+            .filter(|&index| index >= 0 && (index as usize) < sections.len())
+            .and_then(|index| sections[index as usize].get_id(x as i64, y as i64 & 0xf, z as i64))
+            .and_then(|id| registry.get_owned(id));
+        self.cached_chunk = Some((coord, chunk));
+        Ok(state)
+    }
+
+    /// Clears the chunk cached by [RegionFile::get_block_state_at].
+    pub fn clear_chunk_cache(&mut self) {
+        self.cached_chunk = None;
+    }
+
+    /// Clears the cached chunk if it's at `coord`, since its data is about to change.
+    fn invalidate_cached_chunk(&mut self, coord: RegionCoord) {
+        if matches!(&self.cached_chunk, Some((cached, _)) if *cached == coord) {
+            self.cached_chunk = None;
+        }
+    }
+
+    /// Returns every present chunk with the size of its sector in bytes (sector count × 4096),
+    /// sorted from largest to smallest. This only uses the header, so nothing is read from the file.
+    pub fn chunks_by_size(&self) -> Vec<(RegionCoord, u64)> {
+        let mut sizes = (0..1024u16)
+            .map(RegionCoord::from)
+            .map(|coord| (coord, self.get_sector(coord)))
+            .filter(|(_, sector)| sector.sector_count() != 0)
+            .map(|(coord, sector)| (coord, sector.size()))
+            .collect::<Vec<(RegionCoord, u64)>>();
+        sizes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        sizes
+    }
+
+    /// Like [RegionFile::chunks_by_size], but reads each chunk's length prefix to get the
+    /// exact number of bytes it occupies (excluding padding), sorted from largest to smallest.
+    /// The size includes the 4 byte length and the compression scheme.
+    pub fn chunks_by_exact_size(&mut self) -> McResult<Vec<(RegionCoord, u64)>> {
+        let mut sizes = Vec::new();
+        for index in 0..1024u16 {
+            let coord = RegionCoord::from(index);
+            let sector = self.get_sector(coord);
+            if sector.sector_count() == 0 {
+                continue;
+            }
+            self.file_handle.seek(SeekFrom::Start(sector.offset()))?;
+            let length: u32 = self.file_handle.read_value()?;
+            if length == 0 {
+                continue;
+            }
+            sizes.push((coord, length as u64 + 4));
+        }
+        sizes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        Ok(sizes)
+    }
+
+    /// Reads the bytes that are stored for a chunk, without decompressing them: the compression
+    /// scheme followed by the compressed data. For chunks stored in an external `.mcc` file, the
+    /// contents of that file follow the compression scheme.
+    /// Returns `None` if the chunk isn't present.
+    pub fn read_stored_bytes<C: Into<RegionCoord>>(&mut self, coord: C) -> McResult<Option<Vec<u8>>> {
+        let coord: RegionCoord = coord.into();
+        let sector = self.get_sector(coord);
+        if sector.sector_count() == 0 {
+            return Ok(None);
+        }
+        self.file_handle.seek(SeekFrom::Start(sector.offset()))?;
+        let length: u32 = self.file_handle.read_value()?;
+        if length == 0 {
+            return Ok(None);
+        }
+        let mut bytes = Vec::with_capacity(length as usize);
+        (&mut self.file_handle).take(length as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != length as usize {
+            return Err(McError::TruncatedChunk {
+                coord_hint: coord,
+                expected: length as u64,
+                got: bytes.len() as u64,
+            });
+        }
+        let (_, external) = CompressionScheme::read_with_external_flag(&mut bytes.as_slice())?;
+        if external {
+            let path = external_chunk_path(&self.path, coord).ok_or(McError::ExternalChunkPathUnknown(coord))?;
+            File::open(path)?.read_to_end(&mut bytes)?;
+        }
+        Ok(Some(bytes))
+    }
+
+    /// Reads a chunk without decompressing it. Chunks stored in an external `.mcc` file are read from that file.
+    /// Returns [McError::RegionDataNotFound] if the chunk isn't present.
+    pub fn read_raw<C: Into<RegionCoord>>(&mut self, coord: C) -> McResult<RawChunk> {
+        let Some(mut bytes) = self.read_stored_bytes(coord)? else {
+            return Err(McError::RegionDataNotFound);
+        };
+        let mut reader = bytes.as_slice();
+        let (scheme, _) = CompressionScheme::read_with_external_flag(&mut reader)?;
+        let header_len = bytes.len() - reader.len();
+        bytes.drain(..header_len);
+        Ok(RawChunk::new(scheme, bytes))
+    }
+
+    /// Writes a chunk that's already compressed. Like the other writes, chunks that need more than 255 sectors
+    /// are moved to an external `.mcc` file. The timestamp isn't changed.
+    pub fn write_raw<C: Into<RegionCoord>>(&mut self, coord: C, chunk: &RawChunk) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        self.invalidate_cached_chunk(coord);
+        self.write_buf.get_mut().clear();
+        self.write_buf.set_position(0);
+        // Room for the length, which is written in commit_write_buf.
+        self.write_buf.write_all(&[0u8; 4])?;
+        self.write_buf.write_value(chunk.scheme)?;
+        self.write_buf.write_all(&chunk.data)?;
+        self.commit_write_buf(coord, chunk.scheme)
+    }
+
+    /// Like [RegionFile::write_raw], but also sets the timestamp.
+    pub fn write_raw_timestamped<C: Into<RegionCoord>, Ts: Into<Timestamp>>(&mut self, coord: C, chunk: &RawChunk, timestamp: Ts) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        let allocation = self.write_raw(coord, chunk)?;
+        self.write_timestamp(coord, timestamp.into())?;
+        Ok(allocation)
+    }
+
+    /// Computes the [checksum](ChunkChecksum) of the stored bytes of every chunk, without decompressing them.
+    pub fn checksums(&mut self) -> McResult<ChecksumTable> {
+        let mut table = ChecksumTable::default();
+        for index in 0..1024u16 {
+            let coord = RegionCoord::from(index);
+            if let Some(bytes) = self.read_stored_bytes(coord).map_err(|err| chunk_error_context(&self.path, coord, err))? {
+                table.set(coord, ChunkChecksum::of(&bytes));
+            }
+        }
+        Ok(table)
+    }
+
+    /// Writes the checksums of every chunk to the checksum sidecar next to the region file (`r.<x>.<z>.mca.crc`),
+    /// replacing the previous sidecar. Region files that are kept in memory don't have a sidecar.
+    pub fn write_checksums(&mut self) -> McResult<()> {
+        if self.is_in_memory() {
+            return Err(McError::Custom("Region files in memory have no checksum file.".to_owned()));
+        }
+        self.checksums()?.save(checksum_path(&self.path)).with_path(checksum_path(&self.path))
+    }
+
+    /// Compares the checksums of every chunk to the checksum sidecar written by [RegionFile::write_checksums].
+    /// Returns the coordinates of the chunks that differ, which includes chunks that were added or removed
+    /// since the sidecar was written. The result is sorted.
+    pub fn verify_checksums(&mut self) -> McResult<Vec<RegionCoord>> {
+        if self.is_in_memory() {
+            return Err(McError::Custom("Region files in memory have no checksum file.".to_owned()));
+        }
+        let path = checksum_path(&self.path);
+        let expected = ChecksumTable::load(&path).with_path(path)?;
+        Ok(expected.differing(&self.checksums()?))
+    }
+
+    /// Reads every present chunk and returns the [content hash](crate::nbt::tag::Tag::content_hash) of its NBT.
+    /// The entries are ordered by [RegionCoord].
+    pub fn content_hashes(&mut self) -> McResult<Vec<(RegionCoord, u64)>> {
+        let mut hashes = Vec::new();
+        for index in 0..1024u16 {
+            let coord = RegionCoord::from(index);
+            if self.get_sector(coord).sector_count() == 0 {
+                continue;
+            }
+            match self.read_data::<_, NamedTag>(coord) {
+                Ok(chunk) => hashes.push((coord, chunk.tag().content_hash())),
+                // The sector is allocated, but the length is zero.
+                Err(McError::RegionDataNotFound) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(hashes)
+    }
+
+    /// Finds groups of chunks that have identical content (the same decoded NBT).
+    /// Each group contains at least two coordinates. The groups and the coordinates
+    /// within them are sorted. Nothing is modified; this is intended for reporting
+    /// redundancy in regions built from templates or merges.
+    pub fn find_duplicate_chunks(&mut self) -> McResult<Vec<Vec<RegionCoord>>> {
+        let mut groups = HashMap::<u64, Vec<RegionCoord>>::new();
+        for (coord, hash) in self.content_hashes()? {
+            groups.entry(hash).or_default().push(coord);
+        }
+        let mut duplicates = groups.into_values()
+            .filter(|group| group.len() > 1)
+            .collect::<Vec<Vec<RegionCoord>>>();
+        duplicates.sort();
+        Ok(duplicates)
+    }
+
+    /// Reads the `xPos` and `zPos` of every present chunk and compares them to the position
+    /// expected from the chunk's slot, given the region's coordinate (`r.<region_x>.<region_z>.mca`).
+    /// Returns the slots whose stored position disagrees along with the stored position.
+    /// Chunks that don't have an `xPos` and `zPos` are skipped.
+    pub fn find_misplaced_chunks(&mut self, region_x: i32, region_z: i32) -> McResult<Vec<(RegionCoord, (i32, i32))>> {
+        let mut misplaced = Vec::new();
+        for index in 0..1024u16 {
+            let coord = RegionCoord::from(index);
+            if self.get_sector(coord).sector_count() == 0 {
+                continue;
+            }
+            let chunk = match self.read_data::<_, NamedTag>(coord) {
+                Ok(chunk) => chunk,
+                Err(McError::RegionDataNotFound) => continue,
+                Err(err) => return Err(err),
+            };
+            let Some(stored) = chunk_nbt_position(chunk.tag()) else {
+                continue;
+            };
+            let expected = (region_x * 32 + coord.x(), region_z * 32 + coord.z());
+            if stored != expected {
+                misplaced.push((coord, stored));
+            }
+        }
+        Ok(misplaced)
+    }
+
     pub fn write<C: Into<RegionCoord>, F: FnMut(&mut ZlibEncoder<&mut Cursor<Vec<u8>>>) -> McResult<()>>(&mut self, coord: C, mut write: F) -> McResult<RegionSector> {
         let coord: RegionCoord = coord.into();
+        self.invalidate_cached_chunk(coord);
         // Clear the write_buf to prepare it for writing.
+        // The position must be reset as well, otherwise the next write would
+        // begin where the length was written during the previous write.
         self.write_buf.get_mut().clear();
+        self.write_buf.set_position(0);
         // Gotta write 5 bytes to the buffer so that there's room for the length and the compression scheme.
         // To kill two birds with one stone, I'll write all 2s so that I don't have to go back and write the
         // compression scheme after writing the length.
@@ -239,39 +858,219 @@ impl RegionFile {
         // value.write_to(&mut encoder)?;
         write(&mut encoder)?;
         encoder.finish()?;
-        // Get the length of the written data by getting the length of the buffer and subtracting 5 (for
-        // the bytes that were pre-written in a previous step)
-        let length = self.write_buf.get_ref().len() - 5;
-        // Get sectors required to accomodate the buffer.
-        // + 5 because you need to add the (length_bytes + CompressionScheme)
-        let required_sectors = required_sectors((length + 5) as u32);
-        // If there is an overflow, return an error because there's no way to write it to the file.
-        if required_sectors > 255 {
+        self.commit_write_buf(coord, CompressionScheme::ZLib)
+    }
+
+    /// Writes `value` compressed with `scheme` instead of ZLib.
+    /// Chunks written with [CompressionScheme::LZ4] or [CompressionScheme::Zstd]
+    /// can only be read by versions of Minecraft (or other tools) that support them.
+    pub fn write_data_with_scheme<C: Into<RegionCoord>, T: Writable>(&mut self, coord: C, value: &T, scheme: CompressionScheme) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        let size = value.write_size();
+        self.check_write_size(coord, size, scheme)?;
+        self.invalidate_cached_chunk(coord);
+        let mut data = std::mem::take(&mut self.scratch);
+        data.clear();
+        data.reserve(size.unwrap_or_default());
+        let compressed = value.write_to(&mut data).and_then(|_| {
+            self.write_buf.get_mut().clear();
+            self.write_buf.set_position(0);
+            // Room for the length, which is written in commit_write_buf.
+            self.write_buf.write_all(&[0u8; 4])?;
+            self.write_buf.write_value(scheme)?;
+            scheme.compress(&data, self.compression, &mut self.write_buf)
+        });
+        self.scratch = data;
+        compressed?;
+        self.commit_write_buf(coord, scheme)
+    }
+
+    /// Writes `value` with the [Compressor] set with [RegionFile::set_compressor].
+    fn write_data_with_compressor<T: Writable>(&mut self, coord: RegionCoord, value: &T) -> McResult<RegionSector> {
+        self.invalidate_cached_chunk(coord);
+        let Some(scheme) = self.compressor.as_ref().map(|compressor| compressor.scheme()) else {
+            return self.write_data(coord, value);
+        };
+        let size = value.write_size();
+        self.check_write_size(coord, size, scheme)?;
+        self.scratch.clear();
+        self.scratch.reserve(size.unwrap_or_default());
+        value.write_to(&mut self.scratch)?;
+        let Some(compressor) = self.compressor.as_mut() else {
+            return self.write_data(coord, value);
+        };
+        let buffer = self.write_buf.get_mut();
+        buffer.clear();
+        // Room for the length, which is written in commit_write_buf.
+        buffer.extend_from_slice(&[0u8; 4]);
+        buffer.write_value(scheme)?;
+        compressor.compress(&self.scratch, buffer)?;
+        self.commit_write_buf(coord, scheme)
+    }
+
+    /// Like [RegionFile::write_data_with_scheme], but also sets the timestamp.
+    pub fn write_data_timestamped_with_scheme<C: Into<RegionCoord>, T: Writable, Ts: Into<Timestamp>>(&mut self, coord: C, value: &T, timestamp: Ts, scheme: CompressionScheme) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        let allocation = self.write_data_with_scheme(coord, value, scheme)?;
+        self.write_timestamp(coord, timestamp.into())?;
+        Ok(allocation)
+    }
+
+    /// Returns [McError::RegionDataTooLarge] without compressing anything if data of `size` uncompressed bytes
+    /// can't fit in the region file. Compressed data may end up any size, so this is only known when the data
+    /// is stored as it is (uncompressed, or at compression level 0), and only when the chunk can't be moved to
+    /// an external chunk file.
+    fn check_write_size(&self, coord: RegionCoord, size: Option<usize>, scheme: CompressionScheme) -> McResult<()> {
+        let Some(size) = size else {
+            return Ok(());
+        };
+        let stored = match scheme {
+            CompressionScheme::Uncompressed => true,
+            CompressionScheme::GZip | CompressionScheme::ZLib => self.compression.level() == 0,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        };
+        // Stored data takes at least `size` bytes, plus 5 for the length and the compression scheme.
+        let length = size.saturating_add(5).min(u32::MAX as usize) as u32;
+        if stored && required_sectors(length) > 255 && external_chunk_path(&self.path, coord).is_none() {
             return Err(McError::RegionDataTooLarge);
         }
+        Ok(())
+    }
+
+    /// Pads the write_buf, which should hold 4 placeholder bytes for the length followed
+    /// by the compression scheme and the compressed data, writes the length,
+    /// then allocates a sector for the chunk and writes it to the file.
+    /// Chunks that need more than 255 sectors are moved to an external chunk file.
+    fn commit_write_buf(&mut self, coord: RegionCoord, scheme: CompressionScheme) -> McResult<RegionSector> {
+        // The length includes the compression scheme but not the length bytes.
+        let mut length = self.write_buf.get_ref().len() - 4;
+        let journaled = self.journaled && !self.is_in_memory();
+        let external_path = external_chunk_path(&self.path, coord);
+        // A journaled write only removes the old external chunk file once the header no longer points to it.
+        let mut stale_external = None;
+        // + 4 because you need to add the length bytes.
+        if required_sectors((length + 4) as u32) > 255 {
+            // If the external chunk file can't be named, there's no way to write it.
+            let Some(external_path) = external_path else {
+                return Err(McError::RegionDataTooLarge);
+            };
+            // The data goes to the external file, and only the flagged compression scheme stays in the region file.
+            let data_start = 4 + scheme.header_len() as usize;
+            if journaled {
+                write_file_synced(&external_path, &self.write_buf.get_ref()[data_start..])?;
+            } else {
+                std::fs::write(&external_path, &self.write_buf.get_ref()[data_start..])?;
+            }
+            self.write_buf.get_mut().clear();
+            self.write_buf.set_position(0);
+            self.write_buf.write_all(&[0u8; 4])?;
+            scheme.write_external_to(&mut self.write_buf)?;
+            length = scheme.header_len() as usize;
+        } else if let Some(external_path) = external_path {
+            // The chunk may have been stored externally before.
+            if journaled {
+                stale_external = Some(external_path);
+            } else {
+                remove_external_chunk(&external_path)?;
+            }
+        }
+        // Get sectors required to accomodate the buffer.
+        let required_sectors = required_sectors((length + 4) as u32);
         // Write pad zeroes
-        // + 5 because you need to add the (length_bytes + CompressionScheme)
-        let pad_bytes = pad_size((length + 5) as u64);
+        let pad_bytes = pad_size((length + 4) as u64);
+        self.write_buf.set_position((length + 4) as u64);
         self.write_buf.write_zeroes(pad_bytes)?;
         // Seek back to the beginning to write the length.
         self.write_buf.set_position(0);
-        // Add 1 to the length because the specification requires that the compression scheme is included in the length for some reason.
-        self.write_buf.write_value((length + 1) as u32)?;
+        self.write_buf.write_value(length as u32)?;
         // Allocation
         let old_sector = self.header.sectors[coord.index()];
+        if journaled {
+            return self.commit_journaled(coord, old_sector, required_sectors as u8, stale_external);
+        }
         let new_sector = self.sector_manager.reallocate_err(old_sector, required_sectors as u8)?;
         self.header.sectors[coord.index()] = new_sector;
+        let buffer = self.write_buf.get_ref().as_slice();
+        let (write_len, extend_to) = if self.sparse {
+            let file_len = self.file_handle.len()?;
+            (sparse_write_len(new_sector.offset(), buffer, file_len), new_sector.offset() + buffer.len() as u64)
+        } else {
+            (buffer.len(), 0)
+        };
         // Writing to file
         let mut writer = BufWriter::new(&mut self.file_handle);
         writer.seek(SeekFrom::Start(new_sector.offset()))?;
-        writer.write_all(self.write_buf.get_ref().as_slice())?;
+        writer.write_all(&buffer[..write_len])?;
         writer.seek(coord.sector_table_offset())?;
         writer.write_value(new_sector)?;
         writer.flush()?;
+        drop(writer);
+        self.extend_sparse(extend_to)?;
+        self.sync_write()?;
+        Ok(new_sector)
+    }
+
+    /// Grows the file to `size` bytes if it's shorter, filling the sectors that
+    /// [sparse writes](RegionFile::set_sparse) left unwritten.
+    fn extend_sparse(&mut self, size: u64) -> McResult<()> {
+        if self.sparse && self.file_handle.len()? < size {
+            self.file_handle.set_len(size)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the prepared write_buf to newly allocated sectors, leaving `old_sector` untouched until the
+    /// header points to the new sectors. See [journal](super::journal) for the order of the writes.
+    fn commit_journaled(&mut self, coord: RegionCoord, old_sector: RegionSector, required_sectors: u8, stale_external: Option<PathBuf>) -> McResult<RegionSector> {
+        let new_sector = self.sector_manager.allocate_err(required_sectors)?;
+        let written = self.write_journaled(coord, new_sector);
+        if let Err(err) = written {
+            self.sector_manager.deallocate(new_sector);
+            return Err(err);
+        }
+        self.header.sectors[coord.index()] = new_sector;
+        if !old_sector.is_empty() && !old_sector.is_degenerate() {
+            self.sector_manager.deallocate(old_sector);
+        }
+        if let Some(stale_external) = stale_external {
+            remove_external_chunk(&stale_external)?;
+        }
         Ok(new_sector)
     }
 
+    fn write_journaled(&mut self, coord: RegionCoord, new_sector: RegionSector) -> McResult<()> {
+        let mut writer = BufWriter::new(&mut self.file_handle);
+        writer.seek(SeekFrom::Start(new_sector.offset()))?;
+        writer.write_all(self.write_buf.get_ref().as_slice())?;
+        writer.flush()?;
+        drop(writer);
+        self.file_handle.sync_data()?;
+        write_journal(&self.path, coord, new_sector)?;
+        self.file_handle.seek(coord.sector_table_offset())?;
+        self.file_handle.write_value(new_sector)?;
+        self.file_handle.sync_data()?;
+        remove_journal(&self.path)
+    }
+
+    fn write_timestamp(&mut self, coord: RegionCoord, timestamp: Timestamp) -> McResult<()> {
+        self.header.timestamps[coord.index()] = timestamp;
+        // Write the timestamp to the file.
+        let mut writer = BufWriter::new(&mut self.file_handle);
+        writer.seek(coord.timestamp_table_offset())?;
+        writer.write_value(timestamp)?;
+        // I'm pretty sure that flush() doesn't do anything, but I'll put it here just in case.
+        writer.flush()?;
+        drop(writer);
+        self.sync_write()
+    }
+
     pub fn write_data<C: Into<RegionCoord>, T: Writable>(&mut self, coord: C, value: &T) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        if self.compressor.is_some() {
+            return self.write_data_with_compressor(coord, value);
+        }
+        self.check_write_size(coord, value.write_size(), CompressionScheme::ZLib)?;
         self.write(coord, |mut encoder| {
             value.write_to(&mut encoder)?;
             Ok(())
@@ -282,14 +1081,7 @@ impl RegionFile {
         let coord: RegionCoord = coord.into();
         // let allocation = self.write_data(coord, value)?;
         let allocation = self.write(coord, write)?;
-        let timestamp: Timestamp = timestamp.into();
-        self.header.timestamps[coord.index()] = timestamp;
-        // Write the timestamp to the file.
-        let mut writer = BufWriter::new(&mut self.file_handle);
-        writer.seek(coord.timestamp_table_offset())?;
-        writer.write_value(timestamp)?;
-        // I'm pretty sure that flush() doesn't do anything, but I'll put it here just in case.
-        writer.flush()?;
+        self.write_timestamp(coord, timestamp.into())?;
         Ok(allocation)
     }
 
@@ -300,15 +1092,164 @@ impl RegionFile {
         })
     }
 
+    /// Writes many chunks at once, compressed with ZLib. This is much faster than calling
+    /// [RegionFile::write_data] for each chunk: the chunks are compressed into one buffer, sectors are
+    /// allocated for all of them, chunks that end up next to each other in the file are written with a
+    /// single write, and the header is written once at the end. Timestamps aren't changed.
+    ///
+    /// If a coordinate appears more than once, the last value is written. If sectors can't be allocated
+    /// for a chunk, the chunks before it are still written and the error is returned.
+    /// Journaled region files (see [RegionFile::set_journaled]) write the chunks one at a time.
+    /// Returns the sector of each chunk that was written, in the order they were first given.
+    pub fn write_batch<'a, C, T, I>(&mut self, chunks: I) -> McResult<Vec<(RegionCoord, RegionSector)>>
+    where C: Into<RegionCoord>, T: Writable + 'a, I: IntoIterator<Item = (C, &'a T)> {
+        self.write_batch_with_timestamp(chunks, None)
+    }
+
+    /// Like [RegionFile::write_batch], but also sets the timestamp of every chunk.
+    pub fn write_batch_timestamped<'a, C, T, I, Ts>(&mut self, chunks: I, timestamp: Ts) -> McResult<Vec<(RegionCoord, RegionSector)>>
+    where C: Into<RegionCoord>, T: Writable + 'a, I: IntoIterator<Item = (C, &'a T)>, Ts: Into<Timestamp> {
+        self.write_batch_with_timestamp(chunks, Some(timestamp.into()))
+    }
+
+    fn write_batch_with_timestamp<'a, C, T, I>(&mut self, chunks: I, timestamp: Option<Timestamp>) -> McResult<Vec<(RegionCoord, RegionSector)>>
+    where C: Into<RegionCoord>, T: Writable + 'a, I: IntoIterator<Item = (C, &'a T)> {
+        if self.journaled && !self.is_in_memory() {
+            // Journaled writes have to happen in a particular order for each chunk.
+            return chunks.into_iter().map(|(coord, value)| {
+                let coord: RegionCoord = coord.into();
+                let sector = self.write_data(coord, value)?;
+                if let Some(timestamp) = timestamp {
+                    self.write_timestamp(coord, timestamp)?;
+                }
+                Ok((coord, sector))
+            }).collect();
+        }
+        // Each chunk's length, compression scheme, data, and padding, as a range of write_buf.
+        let mut entries = Vec::<(RegionCoord, std::ops::Range<usize>)>::new();
+        let mut entry_indices = HashMap::<RegionCoord, usize>::new();
+        self.write_buf.get_mut().clear();
+        for (coord, value) in chunks {
+            let coord: RegionCoord = coord.into();
+            self.invalidate_cached_chunk(coord);
+            let range = self.compress_into_write_buf(coord, value)?;
+            match entry_indices.get(&coord) {
+                Some(&index) => entries[index].1 = range,
+                None => {
+                    entry_indices.insert(coord, entries.len());
+                    entries.push((coord, range));
+                }
+            }
+        }
+        let mut allocated = Vec::with_capacity(entries.len());
+        let mut allocation_error = None;
+        for (coord, range) in &entries {
+            let old_sector = self.header.sectors[coord.index()];
+            match self.sector_manager.reallocate_err(old_sector, (range.len() / 4096) as u8) {
+                Ok(sector) => {
+                    self.header.sectors[coord.index()] = sector;
+                    if let Some(timestamp) = timestamp {
+                        self.header.timestamps[coord.index()] = timestamp;
+                    }
+                    allocated.push((*coord, sector, range.clone()));
+                }
+                Err(err) => {
+                    allocation_error = Some(err);
+                    break;
+                }
+            }
+        }
+        // Chunks that are next to each other both in the file and in the buffer are written together.
+        let mut writes = allocated.iter()
+            .map(|(_, sector, range)| (sector.offset(), range.clone()))
+            .collect::<Vec<_>>();
+        writes.sort_by_key(|(offset, _)| *offset);
+        let mut runs = Vec::<(u64, std::ops::Range<usize>)>::new();
+        for (offset, range) in writes {
+            match runs.last_mut() {
+                Some((run_offset, run)) if *run_offset + run.len() as u64 == offset && run.end == range.start => run.end = range.end,
+                _ => runs.push((offset, range)),
+            }
+        }
+        let file_len = if self.sparse { self.file_handle.len()? } else { 0 };
+        let mut extend_to = 0;
+        let mut writer = BufWriter::new(&mut self.file_handle);
+        for (offset, range) in runs {
+            let run = &self.write_buf.get_ref()[range];
+            let write_len = if self.sparse {
+                extend_to = extend_to.max(offset + run.len() as u64);
+                sparse_write_len(offset, run, file_len)
+            } else {
+                run.len()
+            };
+            writer.seek(SeekFrom::Start(offset))?;
+            writer.write_all(&run[..write_len])?;
+        }
+        writer.flush()?;
+        drop(writer);
+        self.extend_sparse(extend_to)?;
+        self.write_header()?;
+        self.sync_write()?;
+        match allocation_error {
+            Some(err) => Err(err),
+            None => Ok(allocated.into_iter().map(|(coord, sector, _)| (coord, sector)).collect()),
+        }
+    }
+
+    /// Appends a chunk's length, compression scheme, ZLib compressed data, and padding to write_buf,
+    /// returning the range that it takes up. Like [RegionFile::commit_write_buf], chunks that need more
+    /// than 255 sectors are moved to an external chunk file.
+    fn compress_into_write_buf<T: Writable>(&mut self, coord: RegionCoord, value: &T) -> McResult<std::ops::Range<usize>> {
+        let scheme = CompressionScheme::ZLib;
+        let start = self.write_buf.get_ref().len();
+        self.write_buf.set_position(start as u64);
+        // Room for the length, which is written after the data.
+        self.write_buf.write_all(&[0u8; 4])?;
+        self.write_buf.write_value(scheme)?;
+        let mut encoder = ZlibEncoder::new(&mut self.write_buf, self.compression);
+        value.write_to(&mut encoder)?;
+        encoder.finish()?;
+        // The length includes the compression scheme but not the length bytes.
+        let mut length = self.write_buf.get_ref().len() - start - 4;
+        let external_path = external_chunk_path(&self.path, coord);
+        if required_sectors((length + 4) as u32) > 255 {
+            let Some(external_path) = external_path else {
+                return Err(McError::RegionDataTooLarge);
+            };
+            let data_start = start + 4 + scheme.header_len() as usize;
+            std::fs::write(&external_path, &self.write_buf.get_ref()[data_start..])?;
+            self.write_buf.get_mut().truncate(start + 4);
+            self.write_buf.set_position((start + 4) as u64);
+            scheme.write_external_to(&mut self.write_buf)?;
+            length = scheme.header_len() as usize;
+        } else if let Some(external_path) = external_path {
+            // The chunk may have been stored externally before.
+            remove_external_chunk(&external_path)?;
+        }
+        let end = start + length + 4 + pad_size((length + 4) as u64) as usize;
+        self.write_buf.get_mut().resize(end, 0);
+        self.write_buf.set_position(start as u64);
+        self.write_buf.write_value(length as u32)?;
+        Ok(start..end)
+    }
+
     pub fn delete_data<C: Into<RegionCoord>>(&mut self, coord: C) -> McResult<RegionSector> {
         let coord: RegionCoord = coord.into();
+        self.invalidate_cached_chunk(coord);
         let sector = self.header.sectors[coord.index()];
         if sector.is_empty() {
             return Ok(sector);
         }
-        self.sector_manager.deallocate(sector);
+        // A degenerate sector doesn't occupy any space, so there's nothing to deallocate,
+        // but the header entry is still cleared.
+        if !sector.is_degenerate() {
+            self.sector_manager.deallocate(sector);
+        }
         self.header.sectors[coord.index()] = RegionSector::default();
         self.header.timestamps[coord.index()] = Timestamp::default();
+        if let Some(external_path) = external_chunk_path(&self.path, coord) {
+            remove_external_chunk(&external_path)?;
+        }
         // Clear the sector from the sector table
         let mut writer = BufWriter::new(&mut self.file_handle);
         writer.seek(coord.sector_table_offset())?;
@@ -317,24 +1258,444 @@ impl RegionFile {
         writer.seek(coord.timestamp_table_offset())?;
         writer.write_zeroes(4)?;
         writer.flush()?;
+        drop(writer);
+        self.sync_write()?;
+        if self.truncate_on_delete {
+            self.truncate()?;
+        }
+        Ok(sector)
+    }
+
+    /// Like [RegionFile::delete_data], but the sectors that the chunk occupied are also overwritten with zeroes,
+    /// and its external chunk file is overwritten before it's removed, so the chunk can't be recovered from
+    /// the file. Sectors that another chunk also points to are left as they are.
+    pub fn shred_data<C: Into<RegionCoord>>(&mut self, coord: C) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        if let Some(external_path) = external_chunk_path(&self.path, coord) {
+            shred_external_chunk(&external_path)?;
+        }
+        let sector = self.delete_data(coord)?;
+        if sector.is_degenerate() {
+            return Ok(sector);
+        }
+        // The file may have been truncated by the delete, and the header must never be overwritten.
+        let end = sector.sector_end_offset().min(self.file_handle.len()? / 4096);
+        let in_use = self.header.sectors.iter()
+            .filter(|other| !other.is_empty() && other.intersects(sector))
+            .collect::<Vec<_>>();
+        for offset in sector.sector_offset().max(2)..end {
+            let block = RegionSector::new(offset as u32, 1);
+            if in_use.iter().any(|other| other.intersects(block)) {
+                continue;
+            }
+            self.file_handle.seek(block.seeker())?;
+            self.file_handle.write_zeroes(4096)?;
+        }
+        self.sync_write()?;
         Ok(sector)
     }
 
+    /// Shrinks the file so that it ends at the last used sector, removing the unused sectors at the end
+    /// that deleted or moved chunks left behind. Unlike [RegionFile::optimize], no chunks are moved,
+    /// so unused sectors between chunks are kept. Returns the number of bytes removed.
+    pub fn truncate(&mut self) -> McResult<u64> {
+        let end = self.sector_manager.used_end().max(2) as u64 * 4096;
+        let len = self.file_handle.len()?;
+        if len <= end {
+            return Ok(0);
+        }
+        self.file_handle.set_len(end)?;
+        self.sync_write()?;
+        Ok(len - end)
+    }
+
     ///	Removes all unused sectors from the region file, rearranging it so that it is optimized.
     ///	This is a costly operation, so it should only be performed when a region file reaches a certain threshhold 
     ///	of complexity.
+    ///
+    /// Chunks are moved toward the start of the file in the order that they appear, closing any
+    /// gaps between them. Chunks that were allocated more sectors than they need are shrunk.
+    /// Entries that don't hold any data (a zero length or a [degenerate](RegionSector::is_degenerate)
+    /// sector) are removed. Afterwards the file is truncated and the [SectorManager] is rebuilt,
+    /// so new allocations are appended to the end of the file.
+    ///
+    /// Returns an error without modifying anything if any chunks overlap.
     pub fn optimize(&mut self) -> McResult<()> {
-        //	There is likely an algorithm that can be invented to optimize the file, and as a consequence
-        //	there should be an algorithm that can measure the complexity for solving with the first algorithm.
-        //	Therefore it should be possible to pass a sector table into the complexity measuring algorithm to measure the cost
-        //	of optimization.
-        //		optimization_cost(sector_table)
-        
-        // I had an idea for how I might be able to write the optimization algorithm.
-        // What I can do is I can get information about the sectors:
-        // I would need the gaps, then the upper sectors that need to be moved around to fill in the gaps.
-        
-
-        todo!()
-    }
-}
\ No newline at end of file
+        let mut chunks = Vec::new();
+        let mut removed = Vec::new();
+        for index in 0..1024u16 {
+            let coord = RegionCoord::from(index);
+            let sector = self.header.sectors[coord];
+            if sector.is_empty() {
+                continue;
+            }
+            if sector.is_degenerate() {
+                removed.push(coord);
+                continue;
+            }
+            self.file_handle.seek(sector.seeker())?;
+            let length: u32 = self.file_handle.read_value()?;
+            if length == 0 {
+                removed.push(coord);
+            } else {
+                // + 4 for the length bytes. Never grow a sector past what was allocated.
+                let needed = (required_sectors(length + 4) as u64).min(sector.sector_count());
+                chunks.push((coord, sector, needed));
+            }
+        }
+        chunks.sort_by_key(|(_, sector, _)| sector.sector_offset());
+        if chunks.windows(2).any(|pair| pair[0].1.intersects(pair[1].1)) {
+            return McError::custom("Can't optimize a region file with overlapping sectors.");
+        }
+        for coord in removed {
+            self.header.sectors[coord] = RegionSector::empty();
+            self.header.timestamps[coord] = Timestamp::default();
+        }
+        // Sectors are moved in ascending order, so a chunk is never moved on top of
+        // a chunk that hasn't been moved yet.
+        let mut next_sector = 2u64;
+        let mut buffer = Vec::new();
+        for (coord, sector, needed) in chunks {
+            let new_sector = RegionSector::new(next_sector as u32, needed as u8);
+            if new_sector != sector {
+                buffer.resize((needed * 4096) as usize, 0);
+                self.file_handle.seek(sector.seeker())?;
+                self.file_handle.read_exact(&mut buffer)?;
+                self.file_handle.seek(new_sector.seeker())?;
+                self.file_handle.write_all(&buffer)?;
+                self.header.sectors[coord] = new_sector;
+                // Update the entry right away so that the header on disk
+                // keeps pointing at valid data as chunks are moved.
+                self.file_handle.seek(coord.sector_table_offset())?;
+                self.file_handle.write_value(new_sector)?;
+            }
+            next_sector += needed;
+        }
+        self.write_header()?;
+        self.file_handle.set_len(next_sector * 4096)?;
+        self.sector_manager = SectorManager::from(self.header.sectors.iter())
+            .with_strategy(self.sector_manager.strategy());
+        self.sync_write()
+    }
+
+    /// Like [RegionFile::optimize], but afterwards the bytes after the data of each chunk in its last sector
+    /// are overwritten with zeroes, as are the timestamps of missing chunks. Since optimizing removes every
+    /// unused sector, nothing of deleted chunks is left in the file.
+    pub fn optimize_and_shred(&mut self) -> McResult<()> {
+        self.optimize()?;
+        for index in 0..1024u16 {
+            let coord = RegionCoord::from(index);
+            let sector = self.header.sectors[coord];
+            if sector.sector_count() == 0 {
+                self.header.timestamps[coord] = Timestamp::default();
+                continue;
+            }
+            self.file_handle.seek(sector.seeker())?;
+            let length: u32 = self.file_handle.read_value()?;
+            // + 4 for the length bytes.
+            let data_end = (sector.offset() + 4 + length as u64).min(sector.end_offset());
+            self.file_handle.seek(SeekFrom::Start(data_end))?;
+            self.file_handle.write_zeroes(sector.end_offset() - data_end)?;
+        }
+        self.write_header()?;
+        self.sync_write()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::Tag;
+
+    #[test]
+    fn external_chunk_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.1.-1.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        let large = NamedTag::new(crate::compound! {
+            ("data", vec![1i8; 256 * 4096]),
+        });
+        let external_path = dir.path().join("c.34.-29.mcc");
+        let sector = region.write_data_with_scheme((2u16, 3u16), &large, CompressionScheme::Uncompressed).unwrap();
+        assert_eq!(sector.sector_count(), 1);
+        assert!(external_path.is_file());
+        let read: NamedTag = region.read_data((2u16, 3u16)).unwrap();
+        assert_eq!(read.tag().content_hash(), large.tag().content_hash());
+        assert!(super::super::verify::verify_region_file(&path).unwrap().is_ok());
+        // Writing a chunk that fits removes the external chunk file.
+        region.write_data((2u16, 3u16), &NamedTag::new(Tag::Int(0))).unwrap();
+        assert!(!external_path.exists());
+        region.write_data_with_scheme((2u16, 3u16), &large, CompressionScheme::Uncompressed).unwrap();
+        region.delete_data((2u16, 3u16)).unwrap();
+        assert!(!external_path.exists());
+        // Without coordinates in the file name, there's nowhere to put the external chunk.
+        let mut unnamed = RegionFile::create(dir.path().join("unnamed.mca")).unwrap();
+        assert!(matches!(
+            unnamed.write_data_with_scheme((0u16, 0u16), &large, CompressionScheme::Uncompressed),
+            Err(McError::RegionDataTooLarge)
+        ));
+    }
+
+    #[test]
+    fn compression_scheme_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        let schemes = [
+            CompressionScheme::GZip,
+            CompressionScheme::ZLib,
+            CompressionScheme::Uncompressed,
+            #[cfg(feature = "lz4")]
+            CompressionScheme::LZ4,
+            #[cfg(feature = "zstd")]
+            CompressionScheme::Zstd,
+        ];
+        let chunk = NamedTag::new(crate::compound! {
+            ("xPos", 1),
+            ("data", vec![7i64; 2000]),
+        });
+        for (i, &scheme) in schemes.iter().enumerate() {
+            region.write_data_with_scheme((i as u16, 0u16), &chunk, scheme).unwrap();
+        }
+        drop(region);
+        let mut region = RegionFile::open(&path).unwrap();
+        for i in 0..schemes.len() {
+            let read: NamedTag = region.read_data((i as u16, 0u16)).unwrap();
+            assert_eq!(read.tag().content_hash(), chunk.tag().content_hash());
+        }
+    }
+
+    #[test]
+    fn degenerate_sector_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        let sector = region.write_data((0u16, 0u16), &NamedTag::new(Tag::Int(0))).unwrap();
+        // Points at the chunk at (0, 0), but with a size of zero.
+        let degenerate = RegionSector::new(sector.sector_offset() as u32, 0);
+        assert!(degenerate.is_degenerate());
+        assert!(!RegionSector::empty().is_degenerate());
+        assert!(!sector.is_degenerate());
+        region.edit_header(|header| header.sectors[(1u16, 1u16)] = degenerate).unwrap();
+        assert!(matches!(region.read_data::<_, NamedTag>((1u16, 1u16)), Err(McError::RegionDataNotFound)));
+        assert!(region.chunks_by_size().iter().all(|(coord, _)| *coord != RegionCoord::new(1, 1)));
+        // Writing to the degenerate slot must not overwrite the chunk it points at.
+        let written = region.write_data((1u16, 1u16), &NamedTag::new(Tag::Int(1))).unwrap();
+        assert!(!written.intersects(sector));
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        let tag: NamedTag = region.read_data((0u16, 0u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::Int(0)));
+        let tag: NamedTag = region.read_data((1u16, 1u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::Int(1)));
+    }
+
+    #[test]
+    fn in_memory_region_test() {
+        let mut region = RegionFile::new_in_memory();
+        assert!(region.is_in_memory());
+        region.write_data((1u16, 2u16), &NamedTag::new(Tag::Int(12))).unwrap();
+        region.write_data((3u16, 4u16), &NamedTag::new(Tag::String("ab".repeat(5000)))).unwrap();
+        region.delete_data((1u16, 2u16)).unwrap();
+        region.optimize().unwrap();
+        let bytes = region.into_bytes().unwrap();
+        assert_eq!(bytes.len(), 3 * 4096);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        std::fs::write(&path, &bytes).unwrap();
+        let mut region = RegionFile::open(&path).unwrap();
+        let tag: NamedTag = region.read_data((3u16, 4u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::String(text) if text.len() == 10000));
+        assert_eq!(region.into_bytes().unwrap(), bytes);
+        let region = RegionFile::from_bytes(bytes).unwrap();
+        assert!(region.get_sector((1u16, 2u16)).is_empty());
+        assert!(RegionFile::from_bytes(vec![0; 100]).is_err());
+    }
+
+    #[test]
+    fn write_size_test() {
+        let large = NamedTag::new(crate::compound! {
+            ("data", vec![1i8; 256 * 4096]),
+        });
+        let mut region = RegionFile::new_in_memory();
+        // Without compression, the chunk can't fit, and there's no external file for it.
+        region.set_compression(Compression::none());
+        assert!(matches!(region.write_data((0u16, 0u16), &large), Err(McError::RegionDataTooLarge)));
+        assert!(region.get_sector((0u16, 0u16)).is_empty());
+        region.set_compression(Compression::default());
+        assert!(region.write_data((0u16, 0u16), &large).unwrap().sector_count() < 255);
+    }
+
+    #[test]
+    fn sparse_region_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create_sparse_with_capacity(&path, 2).unwrap();
+        assert!(region.is_sparse());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 4 * 4096);
+        region.write_data((0u16, 0u16), &NamedTag::new(Tag::Int(0))).unwrap();
+        let tags = (1..4u16).map(|x| (x, NamedTag::new(Tag::String("ab".repeat(3000 * x as usize))))).collect::<Vec<_>>();
+        region.write_batch(tags.iter().map(|(x, tag)| ((*x, 0u16), tag))).unwrap();
+        let end = (0..4u16).map(|x| region.get_sector((x, 0u16)).end_offset()).max().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), end);
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        for (x, tag) in &tags {
+            let read: NamedTag = region.read_data((*x, 0u16)).unwrap();
+            assert!(matches!((read.tag(), tag.tag()), (Tag::String(read), Tag::String(text)) if read == text));
+        }
+    }
+
+    #[test]
+    fn truncate_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        for x in 0..3u16 {
+            region.write_data((x, 0u16), &NamedTag::new(Tag::Int(x as i32))).unwrap();
+        }
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 5 * 4096);
+        region.set_truncate_on_delete(true);
+        // Deleting a chunk in the middle leaves a gap, which truncation can't remove.
+        region.delete_data((1u16, 0u16)).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 5 * 4096);
+        // Deleting the last chunk also frees the gap before it.
+        region.delete_data((2u16, 0u16)).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 3 * 4096);
+        assert_eq!(region.truncate().unwrap(), 0);
+        let sector = region.write_data((5u16, 0u16), &NamedTag::new(Tag::Int(5))).unwrap();
+        assert_eq!(sector.sector_offset(), 3);
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        let tag: NamedTag = region.read_data((0u16, 0u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::Int(0)));
+        let tag: NamedTag = region.read_data((5u16, 0u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::Int(5)));
+    }
+
+    #[test]
+    fn shred_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        region.set_compression(Compression::none());
+        for x in 0..3u16 {
+            region.write_data((x, 0u16), &NamedTag::new(Tag::String("secret".repeat(100)))).unwrap();
+            region.write_timestamp(RegionCoord::new(x, 0), Timestamp::from(1234u32)).unwrap();
+        }
+        let contains = |needle: &[u8]| std::fs::read(&path).unwrap().windows(needle.len()).any(|window| window == needle);
+        let sector = region.shred_data((1u16, 0u16)).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes[sector.offset() as usize..sector.end_offset() as usize].iter().all(|&byte| byte == 0));
+        assert!(contains(b"secretsecret"));
+
+        region.delete_data((2u16, 0u16)).unwrap();
+        region.write_data((0u16, 0u16), &NamedTag::new(Tag::String("public".to_owned()))).unwrap();
+        // Leave old data after the chunk in its sector, like another tool might.
+        let sector = region.get_sector((0u16, 0u16));
+        let mut file = File::options().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(sector.end_offset() - 100)).unwrap();
+        file.write_all(b"secret").unwrap();
+        drop(file);
+        region.optimize_and_shred().unwrap();
+        assert!(!contains(b"secret"));
+        assert_eq!(region.get_timestamp((2u16, 0u16)), Timestamp::default());
+        let tag: NamedTag = region.read_data((0u16, 0u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::String(text) if text == "public"));
+    }
+
+    #[test]
+    fn optimize_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        for x in 0..6u16 {
+            region.write_data((x, 0u16), &NamedTag::new(Tag::String("ab".repeat(10)))).unwrap();
+        }
+        region.write_data((2u16, 0u16), &NamedTag::new(Tag::String("small".to_owned()))).unwrap();
+        region.delete_data((1u16, 0u16)).unwrap();
+        region.delete_data((4u16, 0u16)).unwrap();
+        // Claim the freed sector after (3, 0) so that it has a wasted sector.
+        region.edit_header(|header| {
+            let sector = header.sectors[(3u16, 0u16)];
+            header.sectors[(3u16, 0u16)] = RegionSector::new(sector.sector_offset() as u32, 2);
+        }).unwrap();
+        region.optimize().unwrap();
+        let sectors = [0u16, 2, 3, 5].map(|x| region.get_sector((x, 0u16)));
+        assert_eq!(sectors.map(|sector| sector.sector_offset()), [2, 3, 4, 5]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 6 * 4096);
+        // New allocations are appended after the optimized chunks.
+        let sector = region.write_data((9u16, 9u16), &NamedTag::new(Tag::Int(9))).unwrap();
+        assert_eq!(sector.sector_offset(), 6);
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        for x in [0u16, 3, 5] {
+            let tag: NamedTag = region.read_data((x, 0u16)).unwrap();
+            assert!(matches!(tag.tag(), Tag::String(text) if text.len() == 20));
+        }
+        let tag: NamedTag = region.read_data((2u16, 0u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::String(text) if text == "small"));
+        assert!(region.get_sector((1u16, 0u16)).is_empty());
+    }
+
+    #[test]
+    fn format_detection_test() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(RegionFormat::from_path("region/r.0.0.MCR"), Some(RegionFormat::McRegion));
+        assert_eq!(RegionFormat::from_path("region/r.0.0.dat"), None);
+        let level = crate::compound! {
+            ("xPos", 0),
+            ("zPos", 0),
+            ("Blocks", Tag::ByteArray(vec![0; 32768])),
+        };
+        let chunk = NamedTag::new(crate::compound! { ("Level", level) });
+        for (name, format) in [
+            ("r.0.0.mcr", RegionFormat::McRegion),
+            ("r.0.0.mca", RegionFormat::Anvil),
+            ("r.0.0.bak", RegionFormat::McRegion),
+        ] {
+            let path = dir.path().join(name);
+            RegionFile::create(&path).unwrap()
+                .write_data((3u16, 3u16), &chunk).unwrap();
+            assert_eq!(RegionFile::open(&path).unwrap().format(), format);
+        }
+        // A chunk that can't be decoded doesn't keep the file from being opened.
+        let path = dir.path().join("r.0.0.bak");
+        let sector = RegionFile::open(&path).unwrap().get_sector((3u16, 3u16));
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[sector.offset() as usize + 5..sector.offset() as usize + 20].fill(0xFF);
+        std::fs::write(&path, bytes).unwrap();
+        assert_eq!(RegionFile::open(&path).unwrap().format(), RegionFormat::Anvil);
+    }
+
+    #[test]
+    fn write_batch_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        region.write_data((5u16, 0u16), &NamedTag::new(Tag::String("old".repeat(5000)))).unwrap();
+        let chunks = (0..100).map(|index| (RegionCoord::from(index as u16), NamedTag::new(Tag::Int(index)))).collect::<Vec<_>>();
+        let replacement = NamedTag::new(Tag::Int(-1));
+        let written = region.write_batch_timestamped(
+            chunks.iter().map(|(coord, chunk)| (*coord, chunk)).chain([(RegionCoord::from(7u16), &replacement)]),
+            1234,
+        ).unwrap();
+        assert_eq!(written.len(), 100);
+        let sectors = written.iter().map(|(_, sector)| *sector).collect::<Vec<_>>();
+        assert!(sectors.iter().enumerate().all(|(index, a)| sectors[index + 1..].iter().all(|b| !a.intersects(*b))));
+        drop(region);
+        let mut region = RegionFile::open(&path).unwrap();
+        for index in [0, 5, 99] {
+            let chunk: NamedTag = region.read_data(index as u16).unwrap();
+            assert!(matches!(chunk.tag(), Tag::Int(value) if *value == index));
+            assert_eq!(u32::from(region.get_timestamp(index as u16)), 1234);
+        }
+        let replaced: NamedTag = region.read_data(7u16).unwrap();
+        assert!(matches!(replaced.tag(), Tag::Int(-1)));
+        assert!(super::super::verify::verify_region_file(&path).unwrap().is_ok());
+    }
+}
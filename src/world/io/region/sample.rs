@@ -0,0 +1,84 @@
+//! Measuring compression levels against a sample of a region file's chunks.
+
+use std::{
+    io::{Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use flate2::write::ZlibEncoder;
+use rand::seq::SliceRandom;
+
+use crate::{
+    McResult, McError,
+    util::compression::compression_from_level,
+};
+
+use super::prelude::*;
+
+/// Recompresses a random sample of up to `sample_count` chunks from the region file at `path`
+/// at each of the given compression `levels` (`0..=9`).
+/// Returns `(level, total compressed bytes, total time spent compressing)` for each level,
+/// in the same order as `levels`. Chunks are decompressed once up front, so only the time
+/// spent compressing is measured.
+/// This can be used to pick a compression level before recompressing a whole world.
+pub fn sample_compression_tradeoff<P: AsRef<Path>>(path: P, levels: &[u32], sample_count: usize) -> McResult<Vec<(u32, u64, Duration)>> {
+    let levels = levels.iter()
+        .map(|&level| Ok((level, compression_from_level(level)?)))
+        .collect::<McResult<Vec<_>>>()?;
+    let mut region = RegionFile::open(path)?;
+    let present = (0..1024usize)
+        .map(RegionCoord::from)
+        .filter(|&coord| region.get_sector(coord).sector_count() != 0)
+        .collect::<Vec<_>>();
+    let mut samples = Vec::with_capacity(sample_count.min(present.len()));
+    for &coord in present.choose_multiple(&mut rand::thread_rng(), sample_count) {
+        let data = region.read(coord, |mut decoder| {
+            let mut data = Vec::new();
+            decoder.read_to_end(&mut data)?;
+            Ok(data)
+        });
+        match data {
+            Ok(data) => samples.push(data),
+            Err(McError::RegionDataNotFound) => (),
+            Err(err) => return Err(err),
+        }
+    }
+    let mut buffer = Vec::new();
+    levels.into_iter().map(|(level, compression)| {
+        let mut total_size = 0u64;
+        let mut total_time = Duration::ZERO;
+        for data in samples.iter() {
+            buffer.clear();
+            let start = Instant::now();
+            let mut encoder = ZlibEncoder::new(&mut buffer, compression);
+            encoder.write_all(data)?;
+            encoder.finish()?;
+            total_time += start.elapsed();
+            total_size += buffer.len() as u64;
+        }
+        Ok((level, total_size, total_time))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+
+    #[test]
+    fn sample_compression_tradeoff_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut writer = StreamingRegionWriter::create(&path).unwrap();
+        for x in 0..8u16 {
+            writer.push((x, 0u16), &NamedTag::new(Tag::String("abc".repeat(1000)))).unwrap();
+        }
+        writer.finish().unwrap();
+        let results = sample_compression_tradeoff(&path, &[0, 9], 4).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, 0);
+        assert!(results[1].1 < results[0].1);
+        assert!(sample_compression_tradeoff(&path, &[10], 4).is_err());
+    }
+}
@@ -73,6 +73,21 @@ impl RegionSector {
         self.0 == 0
     }
 
+    /// Determines if this sector has a non-zero offset but a sector count of zero.
+    /// A degenerate sector isn't [empty](RegionSector::is_empty), but it doesn't
+    /// occupy any space in the file either. Readers treat any sector with a
+    /// [sector_count](RegionSector::sector_count) of zero (empty or degenerate) as
+    /// having no data, and never seek to it.
+    pub fn is_degenerate(&self) -> bool {
+        self.0 != 0 && self.sector_count() == 0
+    }
+
+    /// Determines if this sector points within a region file of `file_size` bytes.
+    /// A valid sector begins after the 8KiB header and ends at or before the end of the file.
+    pub fn is_valid_for(self, file_size: u64) -> bool {
+        self.offset() >= 8192 && self.end_offset() <= file_size
+    }
+
     /// Tests if two sectors intersect.
     pub fn intersects(self, rhs: Self) -> bool {
         (
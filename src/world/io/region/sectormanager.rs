@@ -10,10 +10,16 @@ use crate::{
 
 use super::prelude::*;
 
+/// Allocates and frees sectors in a region file.
 pub trait SectorAllocator {
+    /// Frees a sector, allowing it to be reused.
     fn deallocate(&mut self, sector: RegionSector);
+    /// Allocates a sector that is `size` 4KiB blocks long.
+    /// Returns `None` if there isn't space.
     #[must_use]
     fn allocate(&mut self, size: u8) -> Option<RegionSector>;
+    /// Allocates a sector that is `new_size` 4KiB blocks long to replace `free`, which is freed.
+    /// Returns `None` if there isn't space, in which case `free` isn't freed.
     #[must_use]
     fn reallocate(&mut self, free: RegionSector, new_size: u8) -> Option<RegionSector>;
 
@@ -30,12 +36,33 @@ pub trait SectorAllocator {
     }
 }
 
-// TODO: Documentation on this sucks.
-/// Manages unused sectors in a region file so that
-/// a [RegionManager] can store chunks in a region file without
-/// intersection issues. Also manages the end-offset so that it can
-/// determine where to start writing new sectors if it runs out of
-/// unused chunks.
+/// How a [SectorManager] chooses where to allocate a sector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AllocationStrategy {
+    /// Use the first unused range (in the order that they're tracked) that is large enough,
+    /// or the end of the file if there isn't one.
+    #[default]
+    FirstFit,
+    /// Use the smallest unused range that is large enough, or the end of the file if there isn't one.
+    /// This leaves larger ranges for larger chunks at the cost of searching every range.
+    BestFit,
+    /// Always allocate at the end of the file, including when a chunk is reallocated to the same
+    /// or a smaller size. Freed sectors are still tracked (even at the end of the file),
+    /// but they're never reused, so data that was freed is never overwritten.
+    AppendOnly,
+}
+
+/// Tracks which 4KiB sectors of a region file are unused so that chunks can be
+/// written without overlapping each other.
+///
+/// Unused space is kept as a list of free ranges between used sectors, along with the
+/// end sector, which is all of the space after the last used sector. Allocations are taken
+/// from the free ranges according to the [AllocationStrategy], and from the end sector when
+/// no free range is large enough. Freed sectors are merged with adjacent free ranges.
+///
+/// A [SectorManager] can be built from a region file's [SectorTable] with [SectorManager::from_table]
+/// or [SectorManager::from_file]. The sectors given to [SectorAllocator::deallocate] should be
+/// sectors that were allocated by the same manager (or that were in the table it was built from).
 pub struct SectorManager {
     /// The unused sectors in a region file.
     /// Expect that this might not be sorted.
@@ -44,6 +71,7 @@ pub struct SectorManager {
     /// used sectors.
     /// This is where new or too large sectors will be allocated.
     pub(super) end_sector: ManagedSector,
+    pub(super) strategy: AllocationStrategy,
 }
 
 impl SectorAllocator for SectorManager {
@@ -147,8 +175,8 @@ impl SectorAllocator for SectorManager {
             _ => ()
         }
         // If the freed sector borders the end_sector, absorb it into
-        // the end_sector
-        if freed_sector.end >= self.end_sector.start {
+        // the end_sector, unless the space must not be allocated again.
+        if freed_sector.end >= self.end_sector.start && self.strategy != AllocationStrategy::AppendOnly {
             self.end_sector.absorb(freed_sector);
         // otherwise add the freed sector to the unused_sectors.
         } else {
@@ -156,38 +184,24 @@ impl SectorAllocator for SectorManager {
         }
     }
 
-    /// Allocate a sector of a specified size.
+    /// Allocate a sector of a specified size, choosing where according to the [AllocationStrategy].
     #[must_use]
     fn allocate(&mut self, size: u8) -> Option<RegionSector> {
-        self.unused_sectors
-            .iter()
-            // Dereference the sector to satisfy borrow checker.
-            .map(|sector| *sector)
-            // We'll need the index of the found sector.
-            .enumerate()
-            // Find a sector that is at least as large as the requested
-            // size.
-            .find(|(_, sector)| sector.size() >= (size as u32))
-            // If a sector is found, we can reduce the size of it by
-            // the requested size (removing it if the size becomes 0).
-            .and_then(|(index, sector)| {
-                let (new_sector, old_sector) = sector.split_left(size as u32).unwrap();
-                if old_sector.is_empty() {
-                    self.unused_sectors.swap_remove(index);
-                } else {
-                    self.unused_sectors[index] = old_sector;
-                }
-                Some(RegionSector::from(new_sector))
-            })
+        let Some(index) = self.find_unused(size as u32) else {
             // If there was no sector found of the appropriate size,
             // create a new sector at the end and move the end_offset
             // to the end of that sector.
-            .or_else(||{
-                // Since we know that the end_sector will have enough
-                // space, we'll just call expect.
-                self.end_sector
-                    .allocate(size)
-            })
+            return self.end_sector.allocate(size);
+        };
+        // Reduce the size of the found sector by the requested size
+        // (removing it if the size becomes 0).
+        let (new_sector, old_sector) = self.unused_sectors[index].split_left(size as u32)?;
+        if old_sector.is_empty() {
+            self.unused_sectors.swap_remove(index);
+        } else {
+            self.unused_sectors[index] = old_sector;
+        }
+        Some(RegionSector::from(new_sector))
     }
 
     /// This will allocate a new sector, and if successful (and necessary), free the old one.
@@ -202,6 +216,10 @@ impl SectorAllocator for SectorManager {
         if new_size == 0 {
             return None;
         }
+        // The chunk can't be rewritten in place, since that would overwrite its old data.
+        if self.strategy == AllocationStrategy::AppendOnly {
+            return self.reallocate_unchecked(free, new_size);
+        }
         // We don't need to do an allocation if our freed sector is big enough to accomodate the new size.
         if free.sector_count() > (new_size as u64) {
             // Use split_left so that when the right side is freed, it can be absorbed
@@ -213,8 +231,8 @@ impl SectorAllocator for SectorManager {
         } else if free.sector_count() == (new_size as u64) {
             Some(free)
         // No need to deallocate.
-        } else if free.is_empty() {
-            // The sector is empty, so there's nothing to free.
+        } else if free.sector_count() == 0 {
+            // The sector is empty (or degenerate), so there's nothing to free.
             self.allocate(new_size)
         } else {
             self.reallocate_unchecked(free, new_size)
@@ -228,6 +246,7 @@ impl SectorManager {
             unused_sectors: Vec::new(),
             // Initialize the end_sector to the accessible range (24-bits).
             end_sector: ManagedSector::new(2, u32::MAX),
+            strategy: AllocationStrategy::default(),
         }
     }
     /// Creates a new [SectorManager] with the specified unused sectors.
@@ -238,6 +257,7 @@ impl SectorManager {
         Self {
             unused_sectors,
             end_sector,
+            strategy: AllocationStrategy::default(),
         }
     }
 
@@ -277,117 +297,74 @@ impl SectorManager {
             .sum()
     }
 
+    /// Sets the [AllocationStrategy] used for future allocations.
+    pub fn with_strategy(mut self, strategy: AllocationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn strategy(&self) -> AllocationStrategy {
+        self.strategy
+    }
+
+    pub fn set_strategy(&mut self, strategy: AllocationStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// The unused ranges of sectors before the end sector, sorted by offset.
+    pub fn free_ranges(&self) -> Vec<ManagedSector> {
+        let mut ranges = self.unused_sectors.clone();
+        ranges.sort();
+        ranges
+    }
+
+    /// The largest unused range of sectors before the end sector.
+    pub fn largest_free_range(&self) -> Option<ManagedSector> {
+        self.unused_sectors.iter()
+            .copied()
+            .max_by_key(|sector| (sector.size(), std::cmp::Reverse(sector.start)))
+    }
+
+    /// The sector offset after the last used sector, which is where the file could be truncated.
+    pub fn used_end(&self) -> u32 {
+        self.end_sector.start
+    }
+
+    /// How fragmented the unused space before the end sector is, from `0.0` to `1.0`.
+    /// This is `1 - largest free range / total free space`, so it's `0.0` when the unused
+    /// space is in a single range (or there is none), and approaches `1.0` as the unused
+    /// space is split into more, smaller ranges.
+    pub fn fragmentation(&self) -> f64 {
+        let total = self.count_unused_blocks();
+        if total == 0 {
+            return 0.0;
+        }
+        let largest = self.largest_free_range().map_or(0, |sector| sector.size());
+        1.0 - largest as f64 / total as f64
+    }
+
     /// This function will only cause the [SectorManager] to change its state if it succeeds in allocating a sector.
     /// Failure is unlikely because you would need a ridiculously large file (which is possible, but unlikely).
     /// This function does not check if the sector being freed is big enough to hold the requested size (hence the `unchecked`).
     #[must_use]
     #[inline(always)]
     fn reallocate_unchecked(&mut self, free: RegionSector, new_size: u8) -> Option<RegionSector> {
-        #[derive(Default)]
-        struct Finder {
-            left: Option<usize>,
-            right: Option<usize>,
-            alloc: Option<usize>,
-        }
-        let mut freed_sector = ManagedSector::from(free);
-        let mut finder = Finder::default();
-        /// Checks that the supplied option is none and that the condition is met.
-        /// If the conditions are met, the option is set to the supplied value.
-        /// Returns the result of the conditions.
-        macro_rules! apply_some_condition {
-            ($opt:expr, $condition:expr, $value:expr) => {
-                if $opt.is_none() && ($condition) {
-                    $opt = Some($value);
-                    true
-                } else {
-                    false
-                }
-            };
-        }
-        self.unused_sectors
-            .iter()
-            .map(|s| *s)
+        // The new sector is allocated before the old one is freed so that they can't overlap.
+        let sector = self.allocate(new_size)?;
+        self.deallocate(free);
+        Some(sector)
+    }
+
+    /// Finds the index of the unused sector to allocate `size` sectors from.
+    fn find_unused(&self, size: u32) -> Option<usize> {
+        let mut candidates = self.unused_sectors.iter()
             .enumerate()
-            .find_map(|(index, sector)| {
-                if apply_some_condition!(finder.alloc,	sector.size() >= (new_size as u32),	index)
-                || apply_some_condition!(finder.left,	sector.end == freed_sector.start,	index)
-                || apply_some_condition!(finder.right,	sector.start == freed_sector.end,	index) {
-                    if let (Some(_), Some(_), Some(_)) = (finder.alloc, finder.left, finder.right) {
-                        return Some(());
-                    }
-                }
-                None
-            });
-        // In order to preserve state upon failure, I've created a temporary enum type to
-        // store values for success actions.
-        enum SuccessAction {
-            /// Replace the sector at index.
-            Replace(usize, ManagedSector),
-            /// Remove sector at index.
-            Remove(usize),
-            /// No action.
-            None,
-        }
-        finder.alloc.map(|index| {
-            let result = self.unused_sectors[index];
-            if result.size() > (new_size as u32) {
-                let (new, old) = result.split_left(new_size as u32).unwrap();
-                (
-                    RegionSector::from(new),
-                    SuccessAction::Replace(index, old)
-                )
-            } else {
-                (
-                    RegionSector::from(result),
-                    SuccessAction::Remove(index)
-                )
-            }
-        })
-        .or_else(|| {
-            self.end_sector
-                .allocate(new_size)
-                .map(|sector| (sector, SuccessAction::None))
-        })
-        .map(|(sector, action)| {
-            match (finder.left, finder.right) {
-                (Some(left), Some(right)) => {
-                    freed_sector.absorb(
-                        self.unused_sectors.swap_remove(right.max(left))
-                    );
-                    freed_sector.absorb(
-                        self.unused_sectors.swap_remove(left.min(right))
-                    );
-                }
-                (Some(index), None) => {
-                    // You do not need to absorb the end sector, that is
-                    // done in the next step.
-                    freed_sector.absorb(
-                        self.unused_sectors.swap_remove(index)
-                    );
-                }
-                (None, Some(index)) => {
-                    freed_sector.absorb(
-                        self.unused_sectors.swap_remove(index)
-                    );
-                }
-                _ => ()
-            }
-            if freed_sector.end >= self.end_sector.start {
-                self.end_sector.absorb(freed_sector);
-            } else {
-                self.unused_sectors.push(freed_sector);
-            }
-            match action {
-                SuccessAction::Replace(index, old) => {
-                    self.unused_sectors[index] = old;
-                }
-                SuccessAction::Remove(index) => {
-                    self.unused_sectors.swap_remove(index);
-                }
-                SuccessAction::None => ()
-            }
-            sector
-        })
+            .filter(|(_, sector)| sector.size() >= size);
+        match self.strategy {
+            AllocationStrategy::FirstFit => candidates.next(),
+            AllocationStrategy::BestFit => candidates.min_by_key(|(_, sector)| (sector.size(), sector.start)),
+            AllocationStrategy::AppendOnly => None,
+        }.map(|(index, _)| index)
     }
 }
 
@@ -475,7 +452,42 @@ impl<'a,T: ManagedSectorIteratorItem, It: IntoIterator<Item = T>> From<It> for S
             });
         Self {
             unused_sectors,
-            end_sector: ManagedSector::end_sector(end_sector.end)
+            end_sector: ManagedSector::end_sector(end_sector.end),
+            strategy: AllocationStrategy::default(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocation_strategy_test() {
+        // Used sectors at 2, 5, and 9..12, leaving 3..5 and 6..9 unused.
+        let table = [RegionSector::new(2, 1), RegionSector::new(5, 1), RegionSector::new(9, 3)];
+        let manager = || SectorManager::from(table.iter());
+        assert_eq!(manager().free_ranges(), vec![ManagedSector::new(3, 5), ManagedSector::new(6, 9)]);
+        assert_eq!(manager().used_end(), 12);
+        assert!((manager().fragmentation() - 0.4).abs() < 1e-9);
+
+        let mut first_fit = manager();
+        assert_eq!(first_fit.allocate(1), Some(RegionSector::new(3, 1)));
+        let mut best_fit = manager().with_strategy(AllocationStrategy::BestFit);
+        assert_eq!(best_fit.allocate(3), Some(RegionSector::new(6, 3)));
+        assert_eq!(best_fit.allocate(1), Some(RegionSector::new(3, 1)));
+        assert_eq!(best_fit.fragmentation(), 0.0);
+        let mut append_only = manager().with_strategy(AllocationStrategy::AppendOnly);
+        assert_eq!(append_only.allocate(1), Some(RegionSector::new(12, 1)));
+        append_only.deallocate(RegionSector::new(2, 1));
+        assert_eq!(append_only.free_ranges()[0], ManagedSector::new(2, 5));
+        assert_eq!(append_only.reallocate(RegionSector::new(5, 1), 2), Some(RegionSector::new(13, 2)));
+        // Rewriting a chunk at the same or a smaller size still appends.
+        assert_eq!(append_only.reallocate(RegionSector::new(13, 2), 2), Some(RegionSector::new(15, 2)));
+        assert_eq!(append_only.reallocate(RegionSector::new(9, 3), 1), Some(RegionSector::new(17, 1)));
+        // Freeing the last sector doesn't let the next allocation reuse it.
+        append_only.deallocate(RegionSector::new(17, 1));
+        assert_eq!(append_only.used_end(), 18);
+        assert_eq!(append_only.allocate(1), Some(RegionSector::new(18, 1)));
+        assert!(append_only.free_ranges().contains(&ManagedSector::new(17, 18)));
+    }
+}
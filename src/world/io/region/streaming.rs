@@ -0,0 +1,322 @@
+//! A writer for creating region files from chunks as they are produced.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::{
+    McResult, McError,
+    ioext::*,
+};
+
+use super::{
+    prelude::*,
+    {external_chunk_path, required_sectors, pad_size},
+};
+
+/// Writes a new region file one chunk at a time.
+/// Chunks can be pushed in any order and their data is written to the file
+/// immediately, one after another. The header is kept in memory and is only
+/// written when [StreamingRegionWriter::finish] is called, so the file is not
+/// a valid region file until then.
+pub struct StreamingRegionWriter {
+    header: RegionHeader,
+    writer: BufWriter<File>,
+    path: PathBuf,
+    /// The 4KiB sector offset where the next chunk will be written.
+    next_sector: u32,
+    write_buf: Cursor<Vec<u8>>,
+    /// Holds the uncompressed NBT of a chunk while it's being compressed by the [Compressor].
+    scratch: Vec<u8>,
+    /// See [StreamingRegionWriter::set_compressor].
+    compressor: Option<Box<dyn Compressor>>,
+    pub compression: Compression,
+    /// Whether [StreamingRegionWriter::finish] waits for the file to reach the disk. Since the file isn't
+    /// a valid region file until it's finished, [Durability::EveryWrite] is the same as [Durability::OnFlush].
+    pub durability: Durability,
+}
+
+impl StreamingRegionWriter {
+    /// Creates a new region file at the given path, returning an error if it already exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        Self::with_compression(path, Compression::best())
+    }
+
+    /// Creates a new region file at the given path that compresses chunks with the given
+    /// [Compression], returning an error if it already exists.
+    pub fn with_compression<P: AsRef<Path>>(path: P, compression: Compression) -> McResult<Self> {
+        let path = path.as_ref();
+        let file = File::options()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        let mut writer = BufWriter::new(file);
+        // Reserve space for the header.
+        writer.write_zeroes(4096*2)?;
+        Ok(Self {
+            header: RegionHeader::default(),
+            writer,
+            path: path.to_owned(),
+            next_sector: 2,
+            write_buf: Cursor::new(Vec::with_capacity(4096*2)),
+            scratch: Vec::new(),
+            compressor: None,
+            compression,
+            durability: Durability::Never,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The header that will be written when the writer is finished.
+    pub fn header(&self) -> &RegionHeader {
+        &self.header
+    }
+
+    /// Returns true if a chunk has already been pushed to the coordinate.
+    pub fn contains<C: Into<RegionCoord>>(&self, coord: C) -> bool {
+        !self.header.sectors[coord.into()].is_empty()
+    }
+
+    /// Sets the [Compressor] used by [StreamingRegionWriter::push] and [StreamingRegionWriter::push_timestamped].
+    /// With `None` (the default), chunks are compressed with ZLib at the [StreamingRegionWriter::compression] level.
+    pub fn set_compressor(&mut self, compressor: Option<Box<dyn Compressor>>) {
+        self.compressor = compressor;
+    }
+
+    /// Writes a chunk with the `utc_now` timestamp and returns the [RegionSector] where it was written.
+    /// Returns [McError::DuplicateRegionCoord] if a chunk was already pushed to this coordinate,
+    /// or [McError::RegionDataTooLarge] if the chunk requires more than 255 sectors.
+    pub fn push<C: Into<RegionCoord>, T: Writable>(&mut self, coord: C, value: &T) -> McResult<RegionSector> {
+        self.push_timestamped(coord, value, Timestamp::utc_now())
+    }
+
+    /// Writes a chunk with the given timestamp and returns the [RegionSector] where it was written.
+    /// See [StreamingRegionWriter::push].
+    pub fn push_timestamped<C: Into<RegionCoord>, T: Writable, Ts: Into<Timestamp>>(&mut self, coord: C, value: &T, timestamp: Ts) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        if self.contains(coord) {
+            return Err(McError::DuplicateRegionCoord(coord));
+        }
+        self.write_buf.get_mut().clear();
+        self.write_buf.set_position(0);
+        // Room for the length.
+        self.write_buf.write_all(&[0u8; 4])?;
+        if let Some(compressor) = self.compressor.as_mut() {
+            self.scratch.clear();
+            value.write_to(&mut self.scratch)?;
+            self.write_buf.write_value(compressor.scheme())?;
+            compressor.compress(&self.scratch, self.write_buf.get_mut())?;
+            return self.write_buffered_chunk(coord, timestamp.into());
+        }
+        self.write_buf.write_value(CompressionScheme::ZLib)?;
+        let mut encoder = ZlibEncoder::new(&mut self.write_buf, self.compression);
+        value.write_to(&mut encoder)?;
+        encoder.finish()?;
+        self.write_buffered_chunk(coord, timestamp.into())
+    }
+
+    /// Compresses `data` (the uncompressed bytes of a chunk's NBT) with `scheme` using this writer's
+    /// [Compression] level, then writes it with the given timestamp. The NBT isn't parsed.
+    /// See [StreamingRegionWriter::push].
+    pub fn push_uncompressed<C: Into<RegionCoord>, Ts: Into<Timestamp>>(&mut self, coord: C, data: &[u8], scheme: CompressionScheme, timestamp: Ts) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        if self.contains(coord) {
+            return Err(McError::DuplicateRegionCoord(coord));
+        }
+        self.write_buf.get_mut().clear();
+        self.write_buf.set_position(0);
+        // Room for the length.
+        self.write_buf.write_all(&[0u8; 4])?;
+        scheme.write_to(&mut self.write_buf)?;
+        scheme.compress(data, self.compression, &mut self.write_buf)?;
+        self.write_buffered_chunk(coord, timestamp.into())
+    }
+
+    /// Writes the chunk in `write_buf`, which starts with 4 bytes of room for the length
+    /// followed by the compression scheme and the compressed data.
+    fn write_buffered_chunk(&mut self, coord: RegionCoord, timestamp: Timestamp) -> McResult<RegionSector> {
+        // The length includes the compression scheme.
+        let length = self.write_buf.get_ref().len() - 4;
+        let sector_count = required_sectors((length + 4) as u32);
+        if sector_count > 255 {
+            return Err(McError::RegionDataTooLarge);
+        }
+        if self.next_sector + sector_count > ManagedSector::ACCESSIBLE.end {
+            return Err(McError::RegionAllocationFailure);
+        }
+        let pad_bytes = pad_size((length + 4) as u64);
+        self.write_buf.seek(SeekFrom::End(0))?;
+        self.write_buf.write_zeroes(pad_bytes)?;
+        self.write_buf.set_position(0);
+        self.write_buf.write_value(length as u32)?;
+        self.writer.write_all(self.write_buf.get_ref().as_slice())?;
+        let sector = RegionSector::new(self.next_sector, sector_count as u8);
+        self.next_sector += sector_count;
+        self.header.sectors[coord] = sector;
+        self.header.timestamps[coord] = timestamp;
+        Ok(sector)
+    }
+
+    /// Copies a chunk verbatim (without recompressing it) from the region file that `reader`
+    /// reads from, using the source file's `header` to locate the chunk. The chunk keeps
+    /// the same coordinate and timestamp.
+    /// Returns [McError::RegionDataNotFound] if the chunk isn't present in the source, or
+    /// [McError::TruncatedChunk] if the source ends before the chunk's declared length.
+    /// Nothing is written if an error is returned.
+    ///
+    /// Only the region file is read, so a chunk that's stored in an external chunk file is copied
+    /// without its data. Use [StreamingRegionWriter::copy_chunk_from_region] to copy those chunks.
+    pub fn copy_chunk_from<R: Read + Seek, C: Into<RegionCoord>>(&mut self, reader: &mut R, header: &RegionHeader, coord: C) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        if self.contains(coord) {
+            return Err(McError::DuplicateRegionCoord(coord));
+        }
+        let source = header.sectors[coord];
+        if source.sector_count() == 0 {
+            return Err(McError::RegionDataNotFound);
+        }
+        reader.seek(source.seeker())?;
+        let length: u32 = reader.read_value()?;
+        if length == 0 {
+            return Err(McError::RegionDataNotFound);
+        }
+        // + 4 for the length bytes.
+        let sector_count = required_sectors(length + 4);
+        if sector_count > 255 {
+            return Err(McError::RegionDataTooLarge);
+        }
+        if self.next_sector + sector_count > ManagedSector::ACCESSIBLE.end {
+            return Err(McError::RegionAllocationFailure);
+        }
+        self.write_buf.get_mut().clear();
+        self.write_buf.set_position(0);
+        self.write_buf.write_value(length)?;
+        // The chunk is buffered so that a truncated chunk doesn't leave partial data in the file.
+        let copied = copy_bytes(reader, &mut self.write_buf, length as u64)?;
+        if copied != length as u64 {
+            return Err(McError::TruncatedChunk {
+                coord_hint: coord,
+                expected: length as u64,
+                got: copied,
+            });
+        }
+        let pad_bytes = pad_size((length + 4) as u64);
+        self.write_buf.write_zeroes(pad_bytes)?;
+        self.writer.write_all(self.write_buf.get_ref().as_slice())?;
+        let sector = RegionSector::new(self.next_sector, sector_count as u8);
+        self.next_sector += sector_count;
+        self.header.sectors[coord] = sector;
+        self.header.timestamps[coord] = header.timestamps[coord];
+        Ok(sector)
+    }
+
+    /// Like [StreamingRegionWriter::copy_chunk_from], but a chunk that's stored in an external chunk file
+    /// next to the region file at `source_path` (see [external_chunk_path]) keeps its data. The external file
+    /// is copied next to this writer's file, or if this file isn't named like a region file and so can't have
+    /// external chunk files, the data is stored in this file instead, which fails with
+    /// [McError::RegionDataTooLarge] if it doesn't fit.
+    pub fn copy_chunk_from_region<R: Read + Seek, C: Into<RegionCoord>>(&mut self, reader: &mut R, header: &RegionHeader, source_path: &Path, coord: C) -> McResult<RegionSector> {
+        let coord: RegionCoord = coord.into();
+        let source = header.sectors[coord];
+        let external = if source.sector_count() == 0 {
+            false
+        } else {
+            // Skip the length to get to the compression scheme.
+            reader.seek(SeekFrom::Start(source.offset() + 4))?;
+            CompressionScheme::read_with_external_flag(reader)?.1
+        };
+        let source_external = external_chunk_path(source_path, coord).filter(|path| external && path.is_file());
+        let Some(source_external) = source_external else {
+            return self.copy_chunk_from(reader, header, coord);
+        };
+        if let Some(external) = external_chunk_path(&self.path, coord) {
+            let sector = self.copy_chunk_from(reader, header, coord)?;
+            std::fs::copy(source_external, external)?;
+            return Ok(sector);
+        }
+        if self.contains(coord) {
+            return Err(McError::DuplicateRegionCoord(coord));
+        }
+        reader.seek(SeekFrom::Start(source.offset() + 4))?;
+        let (scheme, _) = CompressionScheme::read_with_external_flag(reader)?;
+        self.write_buf.get_mut().clear();
+        self.write_buf.set_position(0);
+        // Room for the length.
+        self.write_buf.write_all(&[0u8; 4])?;
+        scheme.write_to(&mut self.write_buf)?;
+        std::io::copy(&mut File::open(source_external)?, &mut self.write_buf)?;
+        self.write_buffered_chunk(coord, header.timestamps[coord])
+    }
+
+    /// Writes the header and flushes the file, syncing it to the disk if the [durability](StreamingRegionWriter::durability) asks for it.
+    pub fn finish(mut self) -> McResult<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.header.write_to(&mut self.writer)?;
+        self.writer.flush()?;
+        if self.durability.syncs_on_flush() {
+            self.writer.get_ref().sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+
+    #[test]
+    fn streaming_writer_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut writer = StreamingRegionWriter::create(&path).unwrap();
+        for (x, z) in [(31u16, 31u16), (0, 0), (4, 7)] {
+            let tag = NamedTag::new(Tag::Int((x * 32 + z) as i32));
+            writer.push((x, z), &tag).unwrap();
+        }
+        let duplicate = writer.push((0u16, 0u16), &NamedTag::new(Tag::Int(0)));
+        assert!(matches!(duplicate, Err(McError::DuplicateRegionCoord(_))));
+        writer.finish().unwrap();
+
+        let mut region = RegionFile::open(&path).unwrap();
+        let tag: NamedTag = region.read_data((4u16, 7u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::Int(135)));
+        assert!(region.get_sector((1u16, 1u16)).is_empty());
+    }
+
+    #[test]
+    fn copy_truncated_chunk_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_path = dir.path().join("r.0.0.mca");
+        let mut source = StreamingRegionWriter::create(&source_path).unwrap();
+        source.push_timestamped((1u16, 1u16), &NamedTag::new(Tag::Int(1)), 1234).unwrap();
+        source.push((2u16, 2u16), &NamedTag::new(Tag::String("a".repeat(10000)))).unwrap();
+        let header = source.header().clone();
+        source.finish().unwrap();
+        // Cut the second chunk short.
+        let file = File::options().write(true).open(&source_path).unwrap();
+        let cut = header.sectors[(2u16, 2u16)].offset() + 16;
+        file.set_len(cut).unwrap();
+
+        let mut reader = File::open(&source_path).unwrap();
+        let mut writer = StreamingRegionWriter::create(dir.path().join("r.1.0.mca")).unwrap();
+        writer.copy_chunk_from(&mut reader, &header, (1u16, 1u16)).unwrap();
+        let truncated = writer.copy_chunk_from(&mut reader, &header, (2u16, 2u16));
+        assert!(matches!(truncated, Err(McError::TruncatedChunk { got: 12, .. })));
+        assert!(!writer.contains((2u16, 2u16)));
+        let path = writer.path().to_owned();
+        writer.finish().unwrap();
+
+        let mut region = RegionFile::open(path).unwrap();
+        assert_eq!(u32::from(region.get_timestamp((1u16, 1u16))), 1234);
+        let tag: NamedTag = region.read_data((1u16, 1u16)).unwrap();
+        assert!(matches!(tag.tag(), Tag::Int(1)));
+    }
+}
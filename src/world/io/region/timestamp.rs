@@ -1,6 +1,6 @@
 
 use std::io::{Read, Write};
-use chrono::{NaiveDateTime, DateTime, Utc, TimeZone};
+use chrono::{NaiveDateTime, DateTime, Local, Utc, TimeZone};
 use crate::{
     McResult,
     for_each_int_type,
@@ -16,6 +16,22 @@ impl Timestamp {
         DateTime::<Utc>::try_from(*self).ok()
     }
 
+    /// Converts the timestamp to the local time zone.
+    pub fn to_local(&self) -> Option<DateTime<Local>> {
+        self.to_datetime().map(|datetime| datetime.with_timezone(&Local))
+    }
+
+    /// Formats the timestamp (in Utc) using a [chrono format string](chrono::format::strftime).
+    /// Returns `None` if the timestamp can't be represented or the format string is invalid.
+    /// For local time, use [Timestamp::to_local] and format the result.
+    pub fn format(&self, fmt: &str) -> Option<String> {
+        use std::fmt::Write;
+        let datetime = self.to_datetime()?;
+        let mut output = String::new();
+        write!(output, "{}", datetime.format(fmt)).ok()?;
+        Some(output)
+    }
+
     /// Get a [Timestamp] for the current time (in Utc).
     pub fn utc_now() -> Timestamp {
         Timestamp(
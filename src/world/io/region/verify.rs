@@ -0,0 +1,201 @@
+//! Integrity checks for region files.
+
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    McResult, McError,
+    ioext::*,
+    nbt::tag::NamedTag,
+    world::chunkversion::chunk_nbt_position,
+};
+
+use super::{
+    prelude::*,
+    is_multiple_of_4096,
+    external_chunk_path,
+    filename::RegionFileName,
+};
+
+/// The result of [verify_region_file].
+/// Every list is ordered by [RegionCoord].
+#[derive(Debug)]
+pub struct RegionIntegrityReport {
+    /// The path to the region file.
+    pub path: PathBuf,
+    /// The size of the region file in bytes.
+    pub file_size: u64,
+    /// Pairs of chunks whose sectors overlap.
+    pub overlapping_sectors: Vec<(RegionCoord, RegionCoord)>,
+    /// Chunks whose sector begins within the header or ends past the end of the file.
+    pub out_of_bounds_sectors: Vec<RegionCoord>,
+    /// Chunks whose timestamp is later than the time of verification.
+    pub future_timestamps: Vec<(RegionCoord, Timestamp)>,
+    /// Chunks whose length prefix doesn't fit within their sector.
+    pub invalid_lengths: Vec<(RegionCoord, u32)>,
+    /// Chunks whose data couldn't be decompressed or parsed as NBT.
+    pub unreadable_chunks: Vec<(RegionCoord, McError)>,
+    /// Chunks whose stored `xPos` and `zPos` don't match their position in the region.
+    /// This is only checked when the region coordinate can be parsed from the file name.
+    pub misplaced_chunks: Vec<(RegionCoord, (i32, i32))>,
+}
+
+impl RegionIntegrityReport {
+    /// Returns true if the file size is a multiple of 4KiB. Minecraft will
+    /// consider the region to be corrupted otherwise.
+    pub fn is_correct_size_multiple(&self) -> bool {
+        is_multiple_of_4096(self.file_size)
+    }
+
+    /// Returns true if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.is_correct_size_multiple()
+        && self.overlapping_sectors.is_empty()
+        && self.out_of_bounds_sectors.is_empty()
+        && self.future_timestamps.is_empty()
+        && self.invalid_lengths.is_empty()
+        && self.unreadable_chunks.is_empty()
+        && self.misplaced_chunks.is_empty()
+    }
+}
+
+/// Reads a region file and checks it for problems, without modifying it.
+/// The checks are:
+/// - The file size is a multiple of 4KiB.
+/// - No two chunks have overlapping sectors.
+/// - Sectors are within the bounds of the file.
+/// - Timestamps aren't in the future.
+/// - Each chunk's length fits within its sector.
+/// - Each chunk can be decompressed and parsed as NBT.
+/// - Each chunk's `xPos` and `zPos` match its position (if the file is named `r.<x>.<z>.mca`).
+///
+/// Returns an error only if the file can't be read or is too small to contain a header.
+pub fn verify_region_file<P: AsRef<Path>>(path: P) -> McResult<RegionIntegrityReport> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    if file_size < 8192 {
+        return Err(McError::InvalidRegionFile);
+    }
+    let mut reader = BufReader::new(file);
+    let header = RegionHeader::read_from(&mut reader)?;
+    let region_position = RegionFileName::from_path(path).map(|name| (name.x, name.z));
+    let now = Timestamp::utc_now();
+    let mut report = RegionIntegrityReport {
+        path: path.to_owned(),
+        file_size,
+        overlapping_sectors: Vec::new(),
+        out_of_bounds_sectors: Vec::new(),
+        future_timestamps: Vec::new(),
+        invalid_lengths: Vec::new(),
+        unreadable_chunks: Vec::new(),
+        misplaced_chunks: Vec::new(),
+    };
+    let present = (0..1024u16)
+        .map(RegionCoord::from)
+        .filter(|&coord| header.sectors[coord].sector_count() != 0)
+        .collect::<Vec<_>>();
+    for (i, &coord) in present.iter().enumerate() {
+        for &other in present[i + 1..].iter() {
+            if header.sectors[coord].intersects(header.sectors[other]) {
+                report.overlapping_sectors.push((coord, other));
+            }
+        }
+    }
+    for coord in present {
+        let sector = header.sectors[coord];
+        let timestamp = header.timestamps[coord];
+        if timestamp > now {
+            report.future_timestamps.push((coord, timestamp));
+        }
+        if !sector.is_valid_for(file_size) {
+            report.out_of_bounds_sectors.push(coord);
+            continue;
+        }
+        reader.seek(sector.seeker())?;
+        let length: u32 = reader.read_value()?;
+        if length == 0 {
+            continue;
+        }
+        // + 4 for the length bytes.
+        if length as u64 + 4 > sector.size() {
+            report.invalid_lengths.push((coord, length));
+            continue;
+        }
+        let chunk = read_chunk(&mut reader, length, path, coord);
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                report.unreadable_chunks.push((coord, err));
+                continue;
+            },
+        };
+        if let (Some((region_x, region_z)), Some(stored)) = (region_position, chunk_nbt_position(chunk.tag())) {
+            let expected = (
+                region_x as i32 * 32 + coord.x(),
+                region_z as i32 * 32 + coord.z(),
+            );
+            if stored != expected {
+                report.misplaced_chunks.push((coord, stored));
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Reads the compression scheme and the chunk that follows it, or the
+/// external chunk file if the chunk is stored externally.
+fn read_chunk<R: Read>(reader: &mut R, length: u32, region_path: &Path, coord: RegionCoord) -> McResult<NamedTag> {
+    let (scheme, external) = CompressionScheme::read_with_external_flag(reader)?;
+    if external {
+        let path = external_chunk_path(region_path, coord).ok_or(McError::ExternalChunkPathUnknown(coord))?;
+        return NamedTag::read_from(&mut scheme.decoder(BufReader::new(File::open(path)?))?);
+    }
+    // The compression scheme is included in the length.
+    let data = reader.take(length.saturating_sub(scheme.header_len()) as u64);
+    NamedTag::read_from(&mut scheme.decoder(data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::Tag;
+
+    #[test]
+    fn verify_region_file_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("r.1.0.mca");
+        let mut region = RegionFile::create(&path).unwrap();
+        let chunk = |x: i32, z: i32| NamedTag::new(crate::compound! {
+            ("xPos", x),
+            ("zPos", z),
+        });
+        region.write_data((0u16, 0u16), &chunk(32, 0)).unwrap();
+        region.write_data((1u16, 0u16), &chunk(0, 0)).unwrap();
+        region.write_data((2u16, 0u16), &NamedTag::new(Tag::Int(0))).unwrap();
+        assert!(verify_region_file(&path).unwrap().misplaced_chunks.len() == 1);
+
+        let sector = region.get_sector((0u16, 0u16));
+        region.edit_header(|header| {
+            header.sectors[(3u16, 0u16)] = sector;
+            header.sectors[(4u16, 0u16)] = RegionSector::new(100, 1);
+            header.timestamps[(2u16, 0u16)] = Timestamp::from(u32::MAX);
+        }).unwrap();
+        drop(region);
+        let report = verify_region_file(&path).unwrap();
+        assert!(!report.is_ok());
+        assert!(report.is_correct_size_multiple());
+        assert_eq!(report.overlapping_sectors, vec![(RegionCoord::new(0, 0), RegionCoord::new(3, 0))]);
+        assert_eq!(report.out_of_bounds_sectors, vec![RegionCoord::new(4, 0)]);
+        assert_eq!(report.future_timestamps.len(), 1);
+        // (3, 0) shares the data of (0, 0), so it's misplaced as well.
+        assert_eq!(report.misplaced_chunks, vec![
+            (RegionCoord::new(1, 0), (0, 0)),
+            (RegionCoord::new(3, 0), (32, 0)),
+        ]);
+        assert!(report.unreadable_chunks.is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+//! Item stacks, and finding them within block entities, entities, and player files.
+//!
+//! Items are found by their shape rather than by a list of known keys: any compound with a String `id` and a
+//! `count` (or `Count` before 24w09a) is an item stack. That covers container contents, equipment, item frames,
+//! dropped items, villager trades, and items nested within other items, such as the contents of a shulker box.
+
+use crate::{
+    math::coord::{BlockCoord, Dimension},
+    nbt::{
+        path::{NbtPath, NbtPathNode},
+        tag::*,
+        Map,
+    },
+    McError, McResult,
+};
+
+/// The DataVersion of 24w09a, where item stacks changed from a Byte `Count` to an Int `count`.
+const INT_COUNT_VERSION: i32 = 3819;
+
+/// An item stack in an inventory.
+#[derive(Debug, Clone)]
+pub struct ItemStack {
+    /// Slot. Items in containers have a slot, but items held by entities (such as in hands or armor) don't.
+    pub slot: Option<i8>,
+    /// id, such as `minecraft:diamond`.
+    pub id: String,
+    /// count, or Count before 24w09a.
+    pub count: i32,
+    /// The rest of the item's compound, such as `components` (or `tag` before 24w09a).
+    pub other: Map,
+}
+
+impl ItemStack {
+    pub fn new<S: Into<String>>(id: S, count: i32) -> Self {
+        Self {
+            slot: None,
+            id: id.into(),
+            count,
+            other: Map::new(),
+        }
+    }
+
+    pub fn with_slot(mut self, slot: i8) -> Self {
+        self.slot = Some(slot);
+        self
+    }
+
+    /// Decodes an item stack from its compound. Both the current and the pre-24w09a counts are read.
+    pub fn decode_map(mut map: Map) -> McResult<Self> {
+        let slot = match map.remove("Slot") {
+            Some(Tag::Byte(slot)) => Some(slot),
+            _ => None,
+        };
+        let Some(Tag::String(id)) = map.remove("id") else {
+            return Err(McError::NotFoundInCompound("id".to_owned()));
+        };
+        let count = match (map.remove("count"), map.remove("Count")) {
+            (Some(Tag::Int(count)), _) => count,
+            (_, Some(Tag::Byte(count))) => count as i32,
+            // Items without a count are a single item.
+            _ => 1,
+        };
+        Ok(Self {
+            slot,
+            id,
+            count,
+            other: map,
+        })
+    }
+
+    /// Encodes the item stack with the count that `data_version` uses.
+    pub fn encode_map(&self, data_version: Option<i32>) -> Map {
+        let mut map = self.other.clone();
+        if let Some(slot) = self.slot {
+            map.insert("Slot".to_owned(), Tag::Byte(slot));
+        }
+        map.insert("id".to_owned(), Tag::String(self.id.clone()));
+        if data_version.is_some_and(|version| version >= INT_COUNT_VERSION) {
+            map.insert("count".to_owned(), Tag::Int(self.count));
+        } else {
+            map.insert("Count".to_owned(), Tag::Byte(self.count.clamp(i8::MIN as i32, i8::MAX as i32) as i8));
+        }
+        map
+    }
+}
+
+/// What an item was found in.
+#[derive(Debug, Clone)]
+pub enum ItemHolder {
+    /// A block entity, such as a chest, a furnace, or a lectern.
+    BlockEntity { coord: BlockCoord, id: String },
+    /// An entity, such as an item frame, a chest minecart, a dropped item, or a mob holding an item.
+    Entity {
+        dimension: Dimension,
+        id: String,
+        pos: Option<(f64, f64, f64)>,
+        uuid: Option<[i32; 4]>,
+    },
+    /// A player's `playerdata` file.
+    Player { uuid: String },
+}
+
+/// An item stack found by [VirtualJavaWorld::find_items](super::world::VirtualJavaWorld::find_items).
+#[derive(Debug, Clone)]
+pub struct FoundItem {
+    pub holder: ItemHolder,
+    /// The path of the item's compound within the holder's compound, such as `Items[3]`, or
+    /// `Inventory[0].components."minecraft:container"[0].item` for an item within a shulker box.
+    pub path: NbtPath,
+    pub item: ItemStack,
+}
+
+/// Whether a compound has the shape of an item stack.
+fn is_item_stack(map: &Map) -> bool {
+    matches!(map.get("id"), Some(Tag::String(_)))
+        && matches!((map.get("count"), map.get("Count")), (Some(Tag::Int(_)), _) | (_, Some(Tag::Byte(_))))
+}
+
+/// Finds every item stack within `map`, including items within other items, with their paths relative to `map`.
+/// Compound keys are visited in sorted order.
+pub fn items_in(map: &Map) -> Vec<(NbtPath, ItemStack)> {
+    let mut found = Vec::new();
+    visit_compound(map, &mut Vec::new(), &mut found);
+    found
+}
+
+fn visit_compound(map: &Map, path: &mut Vec<NbtPathNode>, found: &mut Vec<(NbtPath, ItemStack)>) {
+    if is_item_stack(map) {
+        if let Ok(item) = ItemStack::decode_map(map.clone()) {
+            found.push((NbtPath::new(path.clone()), item));
+        }
+    }
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    for (key, tag) in entries {
+        path.push(NbtPathNode::Key(key.clone()));
+        visit_tag(tag, path, found);
+        path.pop();
+    }
+}
+
+fn visit_tag(tag: &Tag, path: &mut Vec<NbtPathNode>, found: &mut Vec<(NbtPath, ItemStack)>) {
+    match tag {
+        Tag::Compound(map) => visit_compound(map, path, found),
+        Tag::List(ListTag::Compound(maps)) => for (index, map) in maps.iter().enumerate() {
+            path.push(NbtPathNode::Index(index as i64));
+            visit_compound(map, path, found);
+            path.pop();
+        },
+        Tag::List(ListTag::List(lists)) => for (index, list) in lists.iter().enumerate() {
+            path.push(NbtPathNode::Index(index as i64));
+            visit_tag(&Tag::List(list.clone()), path, found);
+            path.pop();
+        },
+        _ => (),
+    }
+}
+
+/// Finds the items in a block entity's compound, as it is stored in a chunk.
+pub fn block_entity_items(map: &Map, dimension: Dimension) -> Vec<FoundItem> {
+    let coord = |key: &str| match map.get(key) {
+        Some(Tag::Int(value)) => *value as i64,
+        _ => 0,
+    };
+    let holder = ItemHolder::BlockEntity {
+        coord: BlockCoord::new(coord("x"), coord("y"), coord("z"), dimension),
+        id: match map.get("id") {
+            Some(Tag::String(id)) => id.clone(),
+            _ => String::new(),
+        },
+    };
+    with_holder(holder, items_in(map))
+}
+
+/// Finds the items in an entity's compound, including the items of its passengers.
+pub fn entity_items(map: &Map, dimension: Dimension) -> Vec<FoundItem> {
+    let entity = super::entity::Entity::from_map(map.clone());
+    let holder = ItemHolder::Entity {
+        dimension,
+        id: entity.id().unwrap_or_default().to_owned(),
+        pos: entity.pos(),
+        uuid: entity.uuid(),
+    };
+    with_holder(holder, items_in(map))
+}
+
+/// Finds the items in a player's data, such as the inventory and ender chest.
+pub fn player_items(uuid: &str, map: &Map) -> Vec<FoundItem> {
+    with_holder(ItemHolder::Player { uuid: uuid.to_owned() }, items_in(map))
+}
+
+/// Finds the items in the block entities and entities of a chunk's NBT.
+/// This works for chunks from the `region` folder (both before and after 1.18) and from the `entities` folder.
+pub fn chunk_items(root: &Tag, dimension: Dimension) -> Vec<FoundItem> {
+    let Tag::Compound(root) = root else {
+        return Vec::new();
+    };
+    let level = match root.get("Level") {
+        Some(Tag::Compound(level)) => Some(level),
+        _ => None,
+    };
+    fn compounds(tag: Option<&Tag>) -> &[Map] {
+        match tag {
+            Some(Tag::List(ListTag::Compound(maps))) => maps.as_slice(),
+            _ => &[],
+        }
+    }
+    let block_entities = compounds(root.get("block_entities")).iter()
+        .chain(compounds(level.and_then(|level| level.get("TileEntities"))));
+    let entities = compounds(root.get("Entities")).iter()
+        .chain(compounds(level.and_then(|level| level.get("Entities"))));
+    block_entities.flat_map(|map| block_entity_items(map, dimension))
+        .chain(entities.flat_map(|map| entity_items(map, dimension)))
+        .collect()
+}
+
+fn with_holder(holder: ItemHolder, items: Vec<(NbtPath, ItemStack)>) -> Vec<FoundItem> {
+    items.into_iter().map(|(path, item)| FoundItem { holder: holder.clone(), path, item }).collect()
+}
@@ -0,0 +1,143 @@
+//! Iterators over the region files and chunks that are present on disk.
+//!
+//! These read from the region files directly, so they don't reflect changes
+//! to chunks that are loaded in a [VirtualJavaWorld](super::world::VirtualJavaWorld)
+//! but haven't been saved yet.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    McError, McResult,
+    math::coord::{Dimension, WorldCoord},
+    nbt::tag::NamedTag,
+};
+
+use super::{
+    chunkstatus::{chunk_nbt_has_status, ChunkStatus},
+    io::region::{filename::discover_regions, RegionCoord, RegionFile},
+};
+
+/// Iterates the coordinates of the region files in a region directory.
+/// The coordinates are those of the regions (not chunks), in sorted order.
+pub struct RegionIter {
+    directory: PathBuf,
+    dimension: Dimension,
+    coords: std::vec::IntoIter<(i64, i64)>,
+}
+
+impl RegionIter {
+    /// Lists the region files in `region_dir`. Files with names that aren't valid
+    /// region file names are skipped. If the directory doesn't exist, there are no regions.
+    pub fn new<P: AsRef<Path>>(region_dir: P, dimension: Dimension) -> McResult<Self> {
+        let directory = region_dir.as_ref().to_owned();
+        let coords = discover_regions(&directory)?.into_iter()
+            .map(|(x, z, _)| (x, z))
+            .collect::<Vec<_>>();
+        Ok(Self {
+            directory,
+            dimension,
+            coords: coords.into_iter(),
+        })
+    }
+
+    /// The path of the region file for a region coordinate.
+    pub fn region_path(&self, coord: WorldCoord) -> PathBuf {
+        self.directory.join(coord.region_file_name())
+    }
+}
+
+impl Iterator for RegionIter {
+    type Item = WorldCoord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.coords.next().map(|(x, z)| WorldCoord::new(x, z, self.dimension))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.coords.size_hint()
+    }
+}
+
+/// Iterates the NBT of every chunk that is present in the region files of a region directory.
+/// Each region file is opened when the iterator reaches it, so only one is open at a time.
+///
+/// The [WorldCoord] is the coordinate of the chunk. Errors from opening region files or
+/// reading chunks are yielded, and iteration continues after them.
+pub struct ChunkIter {
+    regions: RegionIter,
+    current: Option<(WorldCoord, RegionFile)>,
+    index: usize,
+    read_only: bool,
+    min_status: Option<ChunkStatus>,
+}
+
+impl ChunkIter {
+    pub fn new(regions: RegionIter) -> Self {
+        Self {
+            regions,
+            current: None,
+            index: 0,
+            read_only: false,
+            min_status: None,
+        }
+    }
+
+    /// Skips chunks whose [status](ChunkStatus) is lower than `min_status`, such as chunks that
+    /// haven't finished generating when `min_status` is [ChunkStatus::Full].
+    /// Chunks without a known status are skipped as well.
+    pub fn with_min_status(self, min_status: ChunkStatus) -> Self {
+        Self {
+            min_status: Some(min_status),
+            ..self
+        }
+    }
+
+    /// Like [ChunkIter::new], but the region files are opened with [RegionFile::open_read_only].
+    pub fn read_only(regions: RegionIter) -> Self {
+        Self {
+            read_only: true,
+            ..Self::new(regions)
+        }
+    }
+}
+
+impl Iterator for ChunkIter {
+    type Item = McResult<(WorldCoord, NamedTag)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some((region_coord, region)) = &mut self.current else {
+                let coord = self.regions.next()?;
+                let path = self.regions.region_path(coord);
+                let opened = if self.read_only {
+                    RegionFile::open_read_only(path)
+                } else {
+                    RegionFile::open(path)
+                };
+                match opened {
+                    Ok(region) => {
+                        self.current = Some((coord, region));
+                        self.index = 0;
+                        continue;
+                    }
+                    Err(err) => return Some(Err(err)),
+                }
+            };
+            while self.index < 1024 {
+                let coord = RegionCoord::from(self.index as u16);
+                self.index += 1;
+                if region.get_sector(coord).sector_count() == 0 {
+                    continue;
+                }
+                let chunk_coord = region_coord.chunk_in_region(coord);
+                match region.read_data::<_, NamedTag>(coord) {
+                    Ok(tag) if self.min_status.is_some_and(|min_status| !chunk_nbt_has_status(tag.tag(), min_status)) => continue,
+                    Ok(tag) => return Some(Ok((chunk_coord, tag))),
+                    Err(McError::RegionDataNotFound) => continue,
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+            self.current = None;
+        }
+    }
+}
@@ -21,40 +21,21 @@
 // C	Player
 //
 
-use std::{fs::File, io::{BufReader, BufWriter, Read, Seek, SeekFrom}, path::Path};
+use std::{fs::File, io::{BufReader, BufWriter, Write}, path::Path};
 
 use crate::{
-    ioext::ReadExt, nbt::{io::write_named_tag, tag::*, Map}, McError, McResult
+    nbt::{io::{read_nbt_auto, write_named_tag}, tag::*, Map}, McError, McResult,
+    math::{bounds::Bounds3, coord::Dimension},
 };
-use flate2::{read::GzDecoder, read::ZlibDecoder, Compression};
+use super::selection::{ChunkSelection, Selection};
+use flate2::Compression;
 use flate2::write::GzEncoder;
 
 pub fn read_level_from_file<P: AsRef<Path>>(path: P) -> McResult<Level> {
-    let mut file = File::open(path)?;
-    let mut buffer: [u8; 1] = [0];
-    file.read_exact(&mut buffer)?;
-    file.seek(SeekFrom::Start(0))?;
-    let mut reader = BufReader::new(file);
-    // Gzip magic number.
-    match buffer[0] {
-        // GZip
-        0x1f => {
-            let mut decoder = GzDecoder::new(reader);
-            let root: NamedTag = decoder.read_value()?;
-            Level::decode_nbt(root.take_tag())
-        }
-        // ZLib
-        0x78 => {
-            let mut decoder = ZlibDecoder::new(reader);
-            let root: NamedTag = decoder.read_value()?;
-            Level::decode_nbt(root.take_tag())
-        }
-        // No Compression (hopefully)
-        _ => {
-            let root: NamedTag = reader.read_value()?;
-            Level::decode_nbt(root.take_tag())
-        }
-    }
+    let mut reader = BufReader::new(File::open(path)?);
+    // level.dat is normally GZip compressed, but ZLib and raw NBT are accepted as well.
+    let root = read_nbt_auto(&mut reader)?;
+    Level::decode_nbt(root.take_tag())
 }
 
 pub fn write_level_to_file<P: AsRef<Path>>(path: P, level: &Level, compression: Compression) -> McResult<usize> {
@@ -336,4 +317,471 @@ impl DecodeNbt for Level {
             return Err(McError::NbtDecodeError);
         }
     }
-}
\ No newline at end of file
+}
+/// The `Version` compound of `level.dat` (Minecraft 1.9+).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelVersion {
+    /// Id (the DataVersion of the game that last saved the world)
+    pub id: i32,
+    /// Name (such as `1.20.4`)
+    pub name: String,
+    /// Snapshot
+    pub snapshot: bool,
+    /// Series (`main` unless the world was saved by an experimental version), added in 1.18.
+    pub series: Option<String>,
+}
+
+impl LevelVersion {
+    fn decode_map(mut map: Map) -> McResult<Self> {
+        Ok(Self {
+            id: map_decoder!(map; "Id" -> i32),
+            name: map_decoder!(map; "Name" -> String),
+            snapshot: map_decoder!(map; "Snapshot" -> Option<i8>).is_some_and(|snapshot| snapshot != 0),
+            series: map_decoder!(map; "Series" -> Option<String>),
+        })
+    }
+
+    fn encode_map(&self) -> Map {
+        let mut map = Map::new();
+        map_encoder!(map;
+            "Id" = self.id;
+            "Name" = self.name;
+            "Snapshot" = (self.snapshot as i8);
+        );
+        if let Some(series) = &self.series {
+            map_encoder!(map; "Series" = series);
+        }
+        map
+    }
+}
+
+/// The contents of a world's `level.dat`.
+///
+/// Unlike [Level], only commonly used values are typed, and they are only required
+/// if every version of Minecraft writes them. Everything else in the `Data` compound
+/// is kept in [LevelData::other] so that saving doesn't discard anything.
+#[derive(Debug, Clone)]
+pub struct LevelData {
+    /// LevelName
+    pub level_name: String,
+    /// DataVersion (1.9+)
+    pub data_version: Option<i32>,
+    /// Version (1.9+)
+    pub version: Option<LevelVersion>,
+    /// SpawnX
+    pub spawn_x: i32,
+    /// SpawnY
+    pub spawn_y: i32,
+    /// SpawnZ
+    pub spawn_z: i32,
+    /// SpawnAngle (1.16+)
+    pub spawn_angle: Option<f32>,
+    /// BorderCenterX, BorderSize, and the other `Border` values.
+    pub border: WorldBorder,
+    /// GameType
+    pub game_type: i32,
+    /// hardcore
+    pub hardcore: bool,
+    /// Difficulty (1.14+, previously stored in `options.txt`)
+    pub difficulty: Option<i8>,
+    /// allowCommands
+    pub allow_commands: bool,
+    /// Time
+    pub time: i64,
+    /// DayTime
+    pub day_time: i64,
+    /// LastPlayed
+    pub last_played: i64,
+    /// raining
+    pub raining: bool,
+    /// thundering
+    pub thundering: bool,
+    /// GameRules
+    /// Game rules are stored as strings, such as `"true"` or `"3"`.
+    pub game_rules: Map,
+    /// The rest of the `Data` compound.
+    pub other: Map,
+}
+
+impl LevelData {
+    /// Reads `level.dat` from a file.
+    /// The file is normally GZip compressed, but ZLib and raw NBT are accepted as well.
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let root = read_nbt_auto(&mut reader)?;
+        Self::decode_nbt(root.take_tag())
+    }
+
+    /// Writes `level.dat` to a file with GZip compression, overwriting the file if it exists.
+    /// Use [LevelData::save] to keep a backup.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> McResult<usize> {
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        let size = write_named_tag(&mut encoder, &self.encode_nbt(), "")?;
+        encoder.finish()?.flush()?;
+        Ok(size)
+    }
+
+    /// Saves `level.dat` the same way that Minecraft does.
+    /// The new data is written to `level.dat_new` first. Then the existing file
+    /// is moved to `level.dat_old` as a backup, and `level.dat_new` replaces it.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> McResult<()> {
+        let path = path.as_ref();
+        let file_name = path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| McError::Custom(format!("Invalid level.dat path: {}", path.display())))?;
+        let new_path = path.with_file_name(format!("{file_name}_new"));
+        let old_path = path.with_file_name(format!("{file_name}_old"));
+        self.write_to_file(&new_path)?;
+        if path.is_file() {
+            std::fs::rename(path, old_path)?;
+        }
+        std::fs::rename(new_path, path)?;
+        Ok(())
+    }
+
+    /// The spawn position as `(x, y, z)`.
+    pub fn spawn(&self) -> (i32, i32, i32) {
+        (self.spawn_x, self.spawn_y, self.spawn_z)
+    }
+
+    /// Sets the spawn position.
+    pub fn set_spawn(&mut self, (x, y, z): (i32, i32, i32)) {
+        self.spawn_x = x;
+        self.spawn_y = y;
+        self.spawn_z = z;
+    }
+
+    /// The chunks around the spawn point that the game keeps loaded, which trimming should usually keep.
+    /// The radius is the `spawnChunkRadius` game rule (1.20.5+), or 11 chunks for older versions.
+    /// Spawn chunks are always in the Overworld.
+    pub fn spawn_chunks(&self) -> ChunkSelection {
+        let radius = self.game_rule("spawnChunkRadius")
+            .and_then(|radius| radius.parse::<i64>().ok())
+            .unwrap_or(11);
+        let center = ((self.spawn_x as i64).div_euclid(16), (self.spawn_z as i64).div_euclid(16));
+        ChunkSelection::area(((center.0 - radius, center.1 - radius), (center.0 + radius, center.1 + radius)))
+    }
+
+    /// The world seed.
+    /// Since 1.16 the seed is stored in `WorldGenSettings`, and before that it was `RandomSeed`.
+    pub fn seed(&self) -> Option<i64> {
+        match self.other.get("WorldGenSettings") {
+            Some(Tag::Compound(settings)) => match settings.get("seed") {
+                Some(Tag::Long(seed)) => Some(*seed),
+                _ => None,
+            },
+            _ => match self.other.get("RandomSeed") {
+                Some(Tag::Long(seed)) => Some(*seed),
+                _ => None,
+            },
+        }
+    }
+
+    /// Sets the world seed wherever it is stored for this version (see [LevelData::seed]).
+    pub fn set_seed(&mut self, seed: i64) {
+        match self.other.get_mut("WorldGenSettings") {
+            Some(Tag::Compound(settings)) => {
+                settings.insert("seed".to_owned(), Tag::Long(seed));
+            },
+            _ => {
+                self.other.insert("RandomSeed".to_owned(), Tag::Long(seed));
+            },
+        }
+    }
+
+    /// Gets the value of a game rule.
+    pub fn game_rule<S: AsRef<str>>(&self, name: S) -> Option<&str> {
+        match self.game_rules.get(name.as_ref()) {
+            Some(Tag::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Sets the value of a game rule.
+    pub fn set_game_rule<S: Into<String>, V: Into<String>>(&mut self, name: S, value: V) {
+        self.game_rules.insert(name.into(), Tag::String(value.into()));
+    }
+
+    pub fn encode_nbt(&self) -> Tag {
+        let mut data = self.other.clone();
+        map_encoder!(data;
+            "LevelName" = self.level_name;
+            "SpawnX" = self.spawn_x;
+            "SpawnY" = self.spawn_y;
+            "SpawnZ" = self.spawn_z;
+            "GameType" = self.game_type;
+            "hardcore" = (self.hardcore as i8);
+            "allowCommands" = (self.allow_commands as i8);
+            "Time" = self.time;
+            "DayTime" = self.day_time;
+            "LastPlayed" = self.last_played;
+            "raining" = (self.raining as i8);
+            "thundering" = (self.thundering as i8);
+            "GameRules" = self.game_rules;
+        );
+        if let Some(data_version) = self.data_version {
+            map_encoder!(data; "DataVersion" = data_version);
+        }
+        if let Some(version) = &self.version {
+            map_encoder!(data; "Version" = version.encode_map());
+        }
+        if let Some(spawn_angle) = self.spawn_angle {
+            map_encoder!(data; "SpawnAngle" = spawn_angle);
+        }
+        let border = &self.border;
+        map_encoder!(data;
+            "BorderCenterX" = border.center_x;
+            "BorderCenterZ" = border.center_z;
+            "BorderSize" = border.size;
+            "BorderSafeZone" = border.safe_zone;
+            "BorderDamagePerBlock" = border.damage_per_block;
+            "BorderWarningBlocks" = border.warning_blocks;
+            "BorderWarningTime" = border.warning_time;
+            "BorderSizeLerpTarget" = border.size_lerp_target;
+            "BorderSizeLerpTime" = border.size_lerp_time;
+        );
+        if let Some(difficulty) = self.difficulty {
+            map_encoder!(data; "Difficulty" = difficulty);
+        }
+        Tag::Compound(Map::from_iter([("Data".to_owned(), Tag::Compound(data))]))
+    }
+}
+
+/// The world border, stored in `level.dat`.
+///
+/// A [WorldBorder] is also a [Selection] of the chunk columns within it (in the Overworld), so it can be
+/// passed to [trim_world](super::trim::trim_world). Use [WorldBorder::for_dimension] for the Nether.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldBorder {
+    /// BorderCenterX
+    pub center_x: f64,
+    /// BorderCenterZ
+    pub center_z: f64,
+    /// BorderSize, the width of the border in blocks.
+    pub size: f64,
+    /// BorderSafeZone
+    pub safe_zone: f64,
+    /// BorderDamagePerBlock
+    pub damage_per_block: f64,
+    /// BorderWarningBlocks
+    pub warning_blocks: f64,
+    /// BorderWarningTime
+    pub warning_time: f64,
+    /// BorderSizeLerpTarget, the size that the border is moving towards.
+    pub size_lerp_target: f64,
+    /// BorderSizeLerpTime, the milliseconds until the border reaches its target size.
+    pub size_lerp_time: i64,
+}
+
+impl Default for WorldBorder {
+    /// The border of a new world, which is as large as it can be.
+    fn default() -> Self {
+        Self {
+            center_x: 0.0,
+            center_z: 0.0,
+            size: 59999968.0,
+            safe_zone: 5.0,
+            damage_per_block: 0.2,
+            warning_blocks: 5.0,
+            warning_time: 15.0,
+            size_lerp_target: 59999968.0,
+            size_lerp_time: 0,
+        }
+    }
+}
+
+impl WorldBorder {
+    /// Takes the `Border` values out of the `Data` compound of `level.dat`.
+    /// Values that are missing keep their defaults.
+    fn decode_map(data: &mut Map) -> Self {
+        let mut border = Self::default();
+        let mut take = |name: &str, value: &mut f64| {
+            if let Some(Tag::Double(found)) = data.remove(name) {
+                *value = found;
+            }
+        };
+        take("BorderCenterX", &mut border.center_x);
+        take("BorderCenterZ", &mut border.center_z);
+        take("BorderSize", &mut border.size);
+        take("BorderSafeZone", &mut border.safe_zone);
+        take("BorderDamagePerBlock", &mut border.damage_per_block);
+        take("BorderWarningBlocks", &mut border.warning_blocks);
+        take("BorderWarningTime", &mut border.warning_time);
+        take("BorderSizeLerpTarget", &mut border.size_lerp_target);
+        if let Some(Tag::Long(time)) = data.remove("BorderSizeLerpTime") {
+            border.size_lerp_time = time;
+        }
+        border
+    }
+
+    /// The border as it is in a dimension. The Nether's coordinates are scaled down by 8, so its border is too.
+    pub fn for_dimension(self, dimension: Dimension) -> Self {
+        match dimension {
+            Dimension::Nether => Self {
+                center_x: self.center_x / 8.0,
+                center_z: self.center_z / 8.0,
+                size: self.size / 8.0,
+                size_lerp_target: self.size_lerp_target / 8.0,
+                ..self
+            },
+            _ => self,
+        }
+    }
+
+    /// The lowest `(x, z)` block coordinate that is at least partly within the border.
+    pub fn min_block(&self) -> (i64, i64) {
+        let half = self.size / 2.0;
+        ((self.center_x - half).floor() as i64, (self.center_z - half).floor() as i64)
+    }
+
+    /// The highest `(x, z)` block coordinate that is at least partly within the border.
+    pub fn max_block(&self) -> (i64, i64) {
+        let half = self.size / 2.0;
+        ((self.center_x + half).ceil() as i64 - 1, (self.center_z + half).ceil() as i64 - 1)
+    }
+
+    /// Returns true if the block column at `(x, z)` is at least partly within the border.
+    pub fn contains_block(&self, x: i64, z: i64) -> bool {
+        let (min, max) = (self.min_block(), self.max_block());
+        (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&z)
+    }
+
+    /// Returns true if any part of the chunk column is within the border.
+    pub fn contains_chunk(&self, (x, z): (i64, i64)) -> bool {
+        let (min, max) = (self.min_block(), self.max_block());
+        (min.0.div_euclid(16)..=max.0.div_euclid(16)).contains(&x)
+            && (min.1.div_euclid(16)..=max.1.div_euclid(16)).contains(&z)
+    }
+}
+
+impl Selection for WorldBorder {
+    fn contains(&self, (x, _, z): (i64, i64, i64)) -> bool {
+        self.contains_block(x, z)
+    }
+
+    fn bounds(&self) -> Option<Bounds3> {
+        let (min, max) = (self.min_block(), self.max_block());
+        (min.0 <= max.0 && min.1 <= max.1).then(|| Bounds3::new((min.0, i64::MIN, min.1), (max.0, i64::MAX, max.1)))
+    }
+
+    fn intersects_chunk(&self, chunk: (i64, i64)) -> bool {
+        self.contains_chunk(chunk)
+    }
+}
+
+impl DecodeNbt for LevelData {
+    fn decode_nbt(nbt: Tag) -> McResult<Self> {
+        let Tag::Compound(mut map) = nbt else {
+            return Err(McError::NbtDecodeError);
+        };
+        let mut data: Map = map_decoder!(map; "Data" -> Map);
+        let version = map_decoder!(data; "Version" -> Option<Map>);
+        Ok(LevelData {
+            level_name: map_decoder!(data; "LevelName" -> String),
+            data_version: map_decoder!(data; "DataVersion" -> Option<i32>),
+            version: version.map(LevelVersion::decode_map).transpose()?,
+            spawn_x: map_decoder!(data; "SpawnX" -> i32),
+            spawn_y: map_decoder!(data; "SpawnY" -> i32),
+            spawn_z: map_decoder!(data; "SpawnZ" -> i32),
+            spawn_angle: map_decoder!(data; "SpawnAngle" -> Option<f32>),
+            border: WorldBorder::decode_map(&mut data),
+            game_type: map_decoder!(data; "GameType" -> Option<i32>).unwrap_or_default(),
+            hardcore: map_decoder!(data; "hardcore" -> Option<i8>).is_some_and(|hardcore| hardcore != 0),
+            difficulty: map_decoder!(data; "Difficulty" -> Option<i8>),
+            allow_commands: map_decoder!(data; "allowCommands" -> Option<i8>).is_some_and(|allow| allow != 0),
+            time: map_decoder!(data; "Time" -> Option<i64>).unwrap_or_default(),
+            day_time: map_decoder!(data; "DayTime" -> Option<i64>).unwrap_or_default(),
+            last_played: map_decoder!(data; "LastPlayed" -> Option<i64>).unwrap_or_default(),
+            raining: map_decoder!(data; "raining" -> Option<i8>).is_some_and(|raining| raining != 0),
+            thundering: map_decoder!(data; "thundering" -> Option<i8>).is_some_and(|thundering| thundering != 0),
+            game_rules: map_decoder!(data; "GameRules" -> Option<Map>).unwrap_or_default(),
+            other: data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_data_save_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("level.dat");
+        let root = crate::compound! {
+            ("Data", crate::compound! {
+                ("LevelName", "Test World"),
+                ("SpawnX", 10),
+                ("SpawnY", 64),
+                ("SpawnZ", -10),
+                ("DataVersion", 3700),
+                ("Version", crate::compound! {
+                    ("Id", 3700),
+                    ("Name", "1.20.4"),
+                    ("Snapshot", 0i8),
+                }),
+                ("GameRules", crate::compound! {
+                    ("keepInventory", "false"),
+                }),
+                ("WorldGenSettings", crate::compound! {
+                    ("seed", 1234i64),
+                }),
+                ("WanderingTraderSpawnChance", 25),
+            }),
+        };
+        let mut level = LevelData::decode_nbt(root).unwrap();
+        assert_eq!(level.spawn(), (10, 64, -10));
+        assert_eq!(level.seed(), Some(1234));
+        assert_eq!(level.game_rule("keepInventory"), Some("false"));
+        assert_eq!(level.version.as_ref().unwrap().name, "1.20.4");
+        level.save(&path).unwrap();
+        assert!(!dir.path().join("level.dat_old").exists());
+
+        level.set_spawn((0, 70, 0));
+        level.set_seed(-5);
+        level.set_game_rule("keepInventory", "true");
+        level.save(&path).unwrap();
+        let old = LevelData::read_from_file(dir.path().join("level.dat_old")).unwrap();
+        assert_eq!(old.spawn(), (10, 64, -10));
+        let saved = LevelData::read_from_file(&path).unwrap();
+        assert_eq!(saved.spawn(), (0, 70, 0));
+        assert_eq!(saved.seed(), Some(-5));
+        assert_eq!(saved.game_rule("keepInventory"), Some("true"));
+        assert!(matches!(saved.other.get("WanderingTraderSpawnChance"), Some(Tag::Int(25))));
+        assert!(!dir.path().join("level.dat_new").exists());
+    }
+
+    #[test]
+    fn world_border_test() {
+        let root = crate::compound! {
+            ("Data", crate::compound! {
+                ("LevelName", "Border"),
+                ("SpawnX", 100),
+                ("SpawnY", 64),
+                ("SpawnZ", -20),
+                ("BorderCenterX", 8.0),
+                ("BorderCenterZ", 0.0),
+                ("BorderSize", 64.0),
+                ("GameRules", crate::compound! {
+                    ("spawnChunkRadius", "1"),
+                }),
+            }),
+        };
+        let level = LevelData::decode_nbt(root).unwrap();
+        assert!(!level.other.contains_key("BorderSize"));
+        let border = level.border;
+        assert_eq!((border.min_block(), border.max_block()), ((-24, -32), (39, 31)));
+        assert!(border.contains_chunk((-2, 1)) && border.contains_chunk((2, -2)));
+        assert!(!border.contains_chunk((3, 0)) && !border.contains_chunk((0, 2)));
+        assert!(border.contains((39, 300, 31)) && !border.contains((40, 0, 0)));
+        assert_eq!(border.for_dimension(Dimension::Nether).size, 8.0);
+        assert_eq!(level.spawn_chunks().chunks(), ChunkSelection::area(((5, -3), (7, -1))).chunks());
+
+        let decoded = LevelData::decode_nbt(level.encode_nbt()).unwrap();
+        assert_eq!(decoded.border, border);
+        assert_eq!(LevelData::decode_nbt(crate::compound! {
+            ("Data", crate::compound! { ("LevelName", "Default"), ("SpawnX", 0), ("SpawnY", 0), ("SpawnZ", 0) }),
+        }).unwrap().border, WorldBorder::default());
+    }
+}
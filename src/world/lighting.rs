@@ -0,0 +1,417 @@
+//! Block light and sky light calculation.
+//!
+//! Light is stored for each chunk section in the `BlockLight` and `SkyLight` arrays.
+//! Editing blocks doesn't update these arrays, so after light sources or opaque blocks
+//! are placed or removed, the light should be recalculated with [relight_chunk] or
+//! [VirtualJavaWorld::relight_chunks](super::world::VirtualJavaWorld::relight_chunks).
+//!
+//! Light spreads from a block to its six neighbors and loses at least one level for each
+//! block that it passes through. Sky light is the exception: it travels straight down from
+//! the top of the world without losing any levels until it reaches a block that absorbs light.
+//!
+//! How much light a block emits and absorbs isn't stored in its [BlockState], so
+//! [vanilla_light_properties] approximates the vanilla values from block names and properties.
+//! The `_with` variants of the relighting functions accept a function that provides the values instead.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::{
+    blockregistry::BlockRegistry,
+    blockstate::BlockState,
+    chunk::{Chunk, Lighting},
+};
+
+/// How a block interacts with light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LightProperties {
+    /// The light level that the block emits (`0..=15`).
+    pub emission: u8,
+    /// The number of light levels that the block absorbs (`0..=15`).
+    /// Light always loses at least one level when it enters a block, except for sky light going straight down.
+    pub opacity: u8,
+}
+
+impl LightProperties {
+    pub const TRANSPARENT: Self = Self::new(0, 0);
+    pub const OPAQUE: Self = Self::new(0, 15);
+
+    pub const fn new(emission: u8, opacity: u8) -> Self {
+        Self {
+            emission,
+            opacity,
+        }
+    }
+}
+
+/// Approximates the vanilla light emission and opacity of a block.
+///
+/// Blocks that are known to emit light or let light through are matched by name, and
+/// everything else is treated as an opaque full block. Blocks that only occlude light
+/// with part of their shape (such as slabs and stairs) are treated as transparent.
+pub fn vanilla_light_properties(state: &BlockState) -> LightProperties {
+    let name = state.name().strip_prefix("minecraft:").unwrap_or(state.name());
+    let property = |key: &str| state.get_property(key);
+    // Blocks without a `lit` property are always lit.
+    let lit = property("lit").is_none_or(|lit| lit == "true");
+    let count = |key: &str| property(key).and_then(|value| value.parse::<u8>().ok()).unwrap_or(1);
+    let unwaxed = name.strip_prefix("waxed_").unwrap_or(name);
+    let emission = match name {
+        "beacon" | "conduit" | "end_gateway" | "end_portal" | "fire" | "glowstone"
+        | "jack_o_lantern" | "lantern" | "lava" | "sea_lantern" | "shroomlight"
+        | "ochre_froglight" | "verdant_froglight" | "pearlescent_froglight" => 15,
+        "campfire" | "redstone_lamp" if lit => 15,
+        "torch" | "wall_torch" | "end_rod" => 14,
+        "cave_vines" | "cave_vines_plant" if property("berries") == Some("true") => 14,
+        "furnace" | "blast_furnace" | "smoker" if lit => 13,
+        "nether_portal" => 11,
+        "soul_torch" | "soul_wall_torch" | "soul_lantern" | "soul_fire" | "crying_obsidian" => 10,
+        "soul_campfire" if lit => 10,
+        "redstone_ore" | "deepslate_redstone_ore" if lit => 9,
+        "redstone_torch" | "redstone_wall_torch" if lit => 7,
+        "enchanting_table" | "ender_chest" | "glow_lichen" => 7,
+        "sculk_catalyst" => 6,
+        "amethyst_cluster" => 5,
+        "large_amethyst_bud" => 4,
+        "magma_block" => 3,
+        "medium_amethyst_bud" => 2,
+        "small_amethyst_bud" | "brewing_stand" | "brown_mushroom" | "dragon_egg"
+        | "end_portal_frame" | "sculk_sensor" | "calibrated_sculk_sensor" => 1,
+        "light" => count("level"),
+        "respawn_anchor" => [0, 3, 7, 11, 15][count("charges").min(4) as usize],
+        "sea_pickle" if property("waterlogged") == Some("true") => 3 + 3 * count("pickles"),
+        _ if name.ends_with("candle") && property("lit") == Some("true") => 3 * count("candles"),
+        _ if unwaxed.ends_with("copper_bulb") && property("lit") == Some("true") => match unwaxed {
+            "exposed_copper_bulb" => 12,
+            "weathered_copper_bulb" => 8,
+            "oxidized_copper_bulb" => 4,
+            _ => 15,
+        },
+        _ => 0,
+    };
+    let opacity = if property("waterlogged") == Some("true") {
+        1
+    } else {
+        vanilla_opacity(name)
+    };
+    LightProperties::new(emission.min(15), opacity)
+}
+
+fn vanilla_opacity(name: &str) -> u8 {
+    const OPAQUE_EXCEPTIONS: &[&str] = &[
+        "tinted_glass", "grass_block", "snow_block", "mushroom_stem", "packed_ice", "blue_ice",
+    ];
+    const TRANSPARENT_NAMES: &[&str] = &[
+        "air", "cave_air", "void_air", "barrier", "structure_void", "light", "fire", "soul_fire",
+        "nether_portal", "end_portal", "end_gateway", "beacon", "conduit", "cactus", "snow",
+        "scaffolding", "ladder", "vine", "lever", "redstone_wire", "repeater", "comparator",
+        "tripwire", "tripwire_hook", "chain", "iron_bars", "end_rod", "lightning_rod", "cauldron",
+        "hopper", "bell", "anvil", "enchanting_table", "brewing_stand", "lectern", "grindstone",
+        "stonecutter", "dragon_egg", "turtle_egg", "frogspawn", "sugar_cane", "kelp", "kelp_plant",
+        "seagrass", "tall_seagrass", "bamboo", "nether_wart", "wheat", "carrots", "potatoes",
+        "beetroots", "cocoa", "sweet_berry_bush", "spore_blossom", "glow_lichen", "sculk_vein",
+        "pink_petals", "hanging_roots", "pointed_dripstone", "big_dripleaf", "big_dripleaf_stem",
+        "small_dripleaf", "short_grass", "grass", "tall_grass", "fern", "large_fern", "dead_bush",
+        "dandelion", "poppy", "blue_orchid", "allium", "azure_bluet", "oxeye_daisy", "cornflower",
+        "lily_of_the_valley", "wither_rose", "sunflower", "lilac", "rose_bush", "peony",
+        "torchflower", "pitcher_plant", "lily_pad", "azalea", "flowering_azalea", "sea_pickle",
+        "brown_mushroom", "red_mushroom", "crimson_fungus", "warped_fungus", "flower_pot",
+    ];
+    const TRANSPARENT_SUFFIXES: &[&str] = &[
+        "glass", "glass_pane", "torch", "lantern", "sign", "banner", "button", "pressure_plate",
+        "rail", "carpet", "door", "trapdoor", "fence", "fence_gate", "_wall", "slab", "stairs",
+        "sapling", "tulip", "bed", "chest", "head", "skull", "candle", "campfire", "roots",
+        "sprouts", "vines", "vines_plant", "stem", "amethyst_bud", "amethyst_cluster", "coral",
+        "coral_fan", "propagule", "cake",
+    ];
+    if OPAQUE_EXCEPTIONS.contains(&name) {
+        15
+    } else if TRANSPARENT_NAMES.contains(&name)
+        || name.starts_with("potted_")
+        || TRANSPARENT_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+        0
+    } else if name == "water" || name == "bubble_column" || name == "ice" || name == "frosted_ice"
+        || name == "cobweb" || name == "slime_block" || name == "honey_block" || name.ends_with("leaves") {
+        1
+    } else {
+        15
+    }
+}
+
+/// Recalculates the block light of a chunk, and the sky light if `has_skylight` is true
+/// (it's false for the Nether and the End), using [vanilla_light_properties].
+/// Light from neighboring chunks isn't considered, so light near the edges of the chunk may be
+/// darker than it should be. Use [VirtualJavaWorld::relight_chunks](super::world::VirtualJavaWorld::relight_chunks)
+/// to include neighboring chunks.
+pub fn relight_chunk(chunk: &mut Chunk, registry: &BlockRegistry, has_skylight: bool) {
+    relight_chunk_with(chunk, registry, has_skylight, vanilla_light_properties);
+}
+
+/// Like [relight_chunk], but the light properties of blocks are provided by `properties`.
+pub fn relight_chunk_with<F: Fn(&BlockState) -> LightProperties>(chunk: &mut Chunk, registry: &BlockRegistry, has_skylight: bool, properties: F) {
+    let mut volume = LightVolume::new(registry, properties);
+    volume.insert(chunk, true);
+    volume.relight(has_skylight);
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LightKind {
+    Block,
+    Sky,
+}
+
+/// The location of a block within a [LightVolume].
+#[derive(Clone, Copy)]
+struct Cell {
+    chunk: (i64, i64),
+    section: usize,
+    x: i64,
+    y: i64,
+    z: i64,
+}
+
+struct VolumeChunk<'a> {
+    chunk: &'a mut Chunk,
+    writable: bool,
+}
+
+/// A group of chunks that light is calculated for.
+/// Only the light of writable chunks is changed. The light in the other chunks
+/// is treated as a source that spreads into the writable chunks.
+pub(crate) struct LightVolume<'a> {
+    chunks: HashMap<(i64, i64), VolumeChunk<'a>>,
+    /// Light properties indexed by block id.
+    properties: Vec<LightProperties>,
+}
+
+const NEIGHBORS: [(i64, i64, i64); 6] = [
+    (1, 0, 0), (-1, 0, 0),
+    (0, 1, 0), (0, -1, 0),
+    (0, 0, 1), (0, 0, -1),
+];
+
+impl<'a> LightVolume<'a> {
+    pub(crate) fn new<F: Fn(&BlockState) -> LightProperties>(registry: &BlockRegistry, properties: F) -> Self {
+        Self {
+            chunks: HashMap::new(),
+            properties: (0..registry.len() as u32)
+                .map(|id| registry.get(id).map(&properties).unwrap_or_default())
+                .collect(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, chunk: &'a mut Chunk, writable: bool) {
+        let key = (chunk.x as i64, chunk.z as i64);
+        self.chunks.insert(key, VolumeChunk { chunk, writable });
+    }
+
+    fn cell(&self, x: i64, y: i64, z: i64) -> Option<Cell> {
+        let key = (x.div_euclid(16), z.div_euclid(16));
+        let sections = &self.chunks.get(&key)?.chunk.sections.sections;
+        let section_y = y.div_euclid(16);
+        let first = sections.first()?.y as i64;
+        // Sections are normally contiguous, but search for the section if they aren't.
+        let section = match usize::try_from(section_y - first).ok().and_then(|index| sections.get(index).map(|section| (index, section))) {
+            Some((index, section)) if section.y as i64 == section_y => index,
+            _ => sections.iter().position(|section| section.y as i64 == section_y)?,
+        };
+        Some(Cell {
+            chunk: key,
+            section,
+            x: x & 0xf,
+            y: y & 0xf,
+            z: z & 0xf,
+        })
+    }
+
+    fn is_writable(&self, cell: Cell) -> bool {
+        self.chunks[&cell.chunk].writable
+    }
+
+    fn properties(&self, cell: Cell) -> LightProperties {
+        let section = &self.chunks[&cell.chunk].chunk.sections.sections[cell.section];
+        section.get_id(cell.x, cell.y, cell.z)
+            .and_then(|id| self.properties.get(id as usize).copied())
+            .unwrap_or_default()
+    }
+
+    fn light(&self, kind: LightKind, cell: Cell) -> u8 {
+        let section = &self.chunks[&cell.chunk].chunk.sections.sections[cell.section];
+        match kind {
+            LightKind::Block => section.blocklight(cell.x, cell.y, cell.z),
+            LightKind::Sky => section.skylight(cell.x, cell.y, cell.z),
+        }
+    }
+
+    fn set_light(&mut self, kind: LightKind, cell: Cell, level: u8) {
+        let Some(volume_chunk) = self.chunks.get_mut(&cell.chunk) else {
+            return;
+        };
+        let section = &mut volume_chunk.chunk.sections.sections[cell.section];
+        match kind {
+            LightKind::Block => section.set_blocklight(cell.x, cell.y, cell.z, level),
+            LightKind::Sky => section.set_skylight(cell.x, cell.y, cell.z, level),
+        };
+    }
+
+    fn writable_chunks(&self) -> Vec<(i64, i64)> {
+        self.chunks.iter()
+            .filter(|(_, volume_chunk)| volume_chunk.writable)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Recalculates the light of the writable chunks.
+    pub(crate) fn relight(&mut self, has_skylight: bool) {
+        let writable = self.writable_chunks();
+        for key in writable.iter() {
+            let chunk = &mut self.chunks.get_mut(key).unwrap().chunk;
+            for section in chunk.sections.sections.iter_mut() {
+                section.blocklight = Some(Lighting::new());
+                if has_skylight {
+                    section.skylight = Some(Lighting::new());
+                }
+            }
+        }
+        let mut queue = VecDeque::new();
+        // Block light begins at the blocks that emit light.
+        for key in writable.iter() {
+            let chunk = &self.chunks[key].chunk;
+            let mut sources = Vec::new();
+            for section in chunk.sections.sections.iter() {
+                let Some(blocks) = &section.blocks else {
+                    continue;
+                };
+                for (index, &id) in blocks.iter().enumerate() {
+                    let emission = self.properties.get(id as usize).map_or(0, |properties| properties.emission);
+                    if emission > 0 {
+                        let x = key.0 * 16 + (index & 0xf) as i64;
+                        let y = section.y as i64 * 16 + (index >> 8) as i64;
+                        let z = key.1 * 16 + ((index >> 4) & 0xf) as i64;
+                        sources.push((x, y, z, emission));
+                    }
+                }
+            }
+            for (x, y, z, emission) in sources {
+                if let Some(cell) = self.cell(x, y, z) {
+                    self.set_light(LightKind::Block, cell, emission);
+                    queue.push_back((x, y, z, emission));
+                }
+            }
+        }
+        self.push_border_light(LightKind::Block, &writable, &mut queue);
+        self.propagate(LightKind::Block, queue);
+        if !has_skylight {
+            return;
+        }
+        // Sky light travels straight down until it is absorbed, then spreads out from there.
+        let mut queue = VecDeque::new();
+        for key in writable.iter() {
+            let range = self.chunks[key].chunk.height_range();
+            for z in key.1 * 16..key.1 * 16 + 16 {
+                for x in key.0 * 16..key.0 * 16 + 16 {
+                    let mut level = 15u8;
+                    for y in range.clone().rev() {
+                        let Some(cell) = self.cell(x, y, z) else {
+                            continue;
+                        };
+                        level = level.saturating_sub(self.properties(cell).opacity);
+                        if level == 0 {
+                            break;
+                        }
+                        self.set_light(LightKind::Sky, cell, level);
+                        if level > 1 {
+                            queue.push_back((x, y, z, level));
+                        }
+                    }
+                }
+            }
+        }
+        self.push_border_light(LightKind::Sky, &writable, &mut queue);
+        self.propagate(LightKind::Sky, queue);
+    }
+
+    /// Queues the light along the edges of read-only chunks that border writable chunks.
+    fn push_border_light(&self, kind: LightKind, writable: &[(i64, i64)], queue: &mut VecDeque<(i64, i64, i64, u8)>) {
+        for &(chunk_x, chunk_z) in writable {
+            let edges = [
+                ((chunk_x - 1, chunk_z), (15, 0), (0, 1)),
+                ((chunk_x + 1, chunk_z), (0, 0), (0, 1)),
+                ((chunk_x, chunk_z - 1), (0, 15), (1, 0)),
+                ((chunk_x, chunk_z + 1), (0, 0), (1, 0)),
+            ];
+            for (key, (start_x, start_z), (step_x, step_z)) in edges {
+                let Some(neighbor) = self.chunks.get(&key) else {
+                    continue;
+                };
+                if neighbor.writable {
+                    continue;
+                }
+                for y in neighbor.chunk.height_range() {
+                    for i in 0..16 {
+                        let x = key.0 * 16 + start_x + step_x * i;
+                        let z = key.1 * 16 + start_z + step_z * i;
+                        let Some(cell) = self.cell(x, y, z) else {
+                            continue;
+                        };
+                        let level = self.light(kind, cell);
+                        if level > 1 {
+                            queue.push_back((x, y, z, level));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Spreads light outward from the queued blocks into the writable chunks.
+    fn propagate(&mut self, kind: LightKind, mut queue: VecDeque<(i64, i64, i64, u8)>) {
+        while let Some((x, y, z, level)) = queue.pop_front() {
+            for (dx, dy, dz) in NEIGHBORS {
+                let (nx, ny, nz) = (x + dx, y + dy, z + dz);
+                let Some(cell) = self.cell(nx, ny, nz) else {
+                    continue;
+                };
+                if !self.is_writable(cell) {
+                    continue;
+                }
+                let new_level = level.saturating_sub(self.properties(cell).opacity.max(1));
+                if new_level > self.light(kind, cell) {
+                    self.set_light(kind, cell, new_level);
+                    if new_level > 1 {
+                        queue.push_back((nx, ny, nz, new_level));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::tests::empty_chunk;
+
+    #[test]
+    fn relight_chunk_test() {
+        let mut registry = BlockRegistry::with_air();
+        let stone = registry.register(BlockState::from("minecraft:stone"));
+        let torch = registry.register(BlockState::from("minecraft:torch"));
+        let mut chunk = empty_chunk(0, 0);
+        // A stone floor at y = 8 with a torch under it.
+        for z in 0..16 {
+            for x in 0..16 {
+                chunk.set_id((x, 8, z), stone);
+            }
+        }
+        chunk.set_id((4, 4, 4), torch);
+        relight_chunk(&mut chunk, &registry, true);
+        assert_eq!(chunk.blocklight((4, 4, 4)), 14);
+        assert_eq!(chunk.blocklight((5, 4, 4)), 13);
+        assert_eq!(chunk.blocklight((4, 2, 6)), 10);
+        assert_eq!(chunk.blocklight((4, 8, 4)), 0);
+        assert_eq!(chunk.skylight((3, 31, 3)), 15);
+        assert_eq!(chunk.skylight((3, 9, 3)), 15);
+        assert_eq!(chunk.skylight((3, 8, 3)), 0);
+        assert_eq!(chunk.skylight((3, 7, 3)), 0);
+    }
+}
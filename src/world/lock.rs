@@ -0,0 +1,147 @@
+//! Holding a world's `session.lock`, so that a world isn't edited while the game or a server is using it.
+//!
+//! Minecraft writes a snowman (`☃`) into `session.lock` and holds an exclusive lock on the file
+//! for as long as the world is open. Editing region files while the game has them open corrupts
+//! them, so tools should acquire the lock before writing to a world.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use crate::{McError, McResult};
+
+/// The contents that Minecraft writes to `session.lock`.
+const SESSION_LOCK_CONTENTS: &str = "☃";
+
+/// The `session.lock` files that are locked by this process.
+///
+/// On Unix, closing any handle to a file releases every lock that the process holds on it,
+/// so a file that this process has locked must not be opened (and closed) again until the lock is released.
+fn held_locks() -> &'static Mutex<HashSet<PathBuf>> {
+    static HELD: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+    HELD.get_or_init(Default::default)
+}
+
+/// An exclusive lock on a world's `session.lock`. The lock is released when this is dropped.
+#[derive(Debug)]
+pub struct WorldLock {
+    file: Option<File>,
+    path: PathBuf,
+}
+
+impl WorldLock {
+    /// Locks the `session.lock` of the world at `world_dir`, creating it if it doesn't exist.
+    /// Returns [McError::WorldLocked] if the game, a server, or another [WorldLock] holds the lock.
+    pub fn acquire<P: AsRef<Path>>(world_dir: P) -> McResult<Self> {
+        let world_dir = world_dir.as_ref();
+        if !world_dir.is_dir() {
+            return Err(McError::WorldDirectoryNotFound(world_dir.to_owned()));
+        }
+        let path = world_dir.canonicalize()?.join("session.lock");
+        let Ok(mut held) = held_locks().lock() else {
+            return McError::custom("Failed to lock the set of held world locks.");
+        };
+        if !held.insert(path.clone()) {
+            return Err(McError::WorldLocked(world_dir.to_owned()));
+        }
+        match lock_file(&path) {
+            Ok(Some(file)) => Ok(Self {
+                file: Some(file),
+                path,
+            }),
+            result => {
+                held.remove(&path);
+                match result {
+                    Err(err) => Err(err),
+                    _ => Err(McError::WorldLocked(world_dir.to_owned())),
+                }
+            }
+        }
+    }
+
+    /// The path of the `session.lock` file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for WorldLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock. It has to be closed before the path is forgotten
+        // so that another lock on the same world can't be taken while this one is still open.
+        drop(self.file.take());
+        if let Ok(mut held) = held_locks().lock() {
+            held.remove(&self.path);
+        }
+    }
+}
+
+/// Opens and locks `session.lock`, then writes the snowman into it.
+/// Returns `None` if another process holds the lock.
+fn lock_file(path: &Path) -> McResult<Option<File>> {
+    // The file isn't truncated until the lock is held, since the game might be using it.
+    let mut file = File::options().read(true).write(true).create(true).truncate(false).open(path)?;
+    if !try_lock(&file)? {
+        return Ok(None);
+    }
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(SESSION_LOCK_CONTENTS.as_bytes())?;
+    file.flush()?;
+    Ok(Some(file))
+}
+
+/// Takes the kind of lock that Java's `FileChannel::tryLock` takes, so that the game sees the world as locked
+/// (and so that the game's lock is seen). On Unix, Java uses `fcntl` record locks, which don't interact with
+/// the `flock` locks that [File::try_lock] uses. Returns false if another process holds the lock.
+#[cfg(unix)]
+fn try_lock(file: &File) -> McResult<bool> {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: `flock` is a plain C struct, so all zeroes is a valid value.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    // A start and length of 0 locks the whole file, which is what Java does for `tryLock()`.
+    // SAFETY: The file descriptor is open for as long as `file` is, and `lock` outlives the call.
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLK, &lock) } == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EACCES | libc::EAGAIN) => Ok(false),
+        _ => Err(err.into()),
+    }
+}
+
+/// Java uses `LockFileEx` on Windows, which is what [File::try_lock] uses.
+#[cfg(not(unix))]
+fn try_lock(file: &File) -> McResult<bool> {
+    match file.try_lock() {
+        Ok(()) => Ok(true),
+        Err(std::fs::TryLockError::WouldBlock) => Ok(false),
+        Err(std::fs::TryLockError::Error(err)) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_lock_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = WorldLock::acquire(dir.path()).unwrap();
+        assert!(matches!(WorldLock::acquire(dir.path()), Err(McError::WorldLocked(_))));
+        assert!(matches!(crate::world::world::VirtualJavaWorld::open_locked(dir.path()), Err(McError::WorldLocked(_))));
+        let path = lock.path().to_owned();
+        drop(lock);
+        // Reading the file while it's locked would release the lock on Unix.
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "☃");
+        assert!(crate::world::world::VirtualJavaWorld::open_locked(dir.path()).unwrap().is_locked());
+        assert!(matches!(WorldLock::acquire(dir.path().join("missing")), Err(McError::WorldDirectoryNotFound(_))));
+    }
+}
@@ -0,0 +1,445 @@
+//! Map items: `data/map_<id>.dat` and `data/idcounts.dat`.
+//!
+//! Each map is GZip compressed NBT holding a 128x128 grid of color indices. A color index is a base color
+//! (`index / 4`) and a shade (`index % 4`). Base color 0 is transparent. [MapItemData::render_rgba]
+//! converts the grid into an RGBA image that can be handed to an image encoder.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{
+    nbt::{
+        io::{read_nbt_auto, write_named_tag},
+        tag::*,
+        Map,
+    },
+    McError, McResult,
+};
+
+/// The width and height of a map in pixels.
+pub const MAP_SIZE: usize = 128;
+
+/// The DataVersion of 20w21a, where `dimension` changed from a number to a String.
+const NAMED_DIMENSION_VERSION: i32 = 2554;
+/// The DataVersion of 1.20.5, where banner markers changed to lowercase keys with a `pos` IntArray.
+const BANNER_CODEC_VERSION: i32 = 3837;
+
+/// The RGB values of the base map colors, indexed by base color. Base color 0 is transparent.
+const BASE_COLORS: [u32; 62] = [
+    0x000000, 0x7FB238, 0xF7E9A3, 0xC7C7C7, 0xFF0000, 0xA0A0FF, 0xA7A7A7, 0x007C00,
+    0xFFFFFF, 0xA4A8B8, 0x976D4D, 0x707070, 0x4040FF, 0x8F7748, 0xFFFCF5, 0xD87F33,
+    0xB24CD8, 0x6699D8, 0xE5E533, 0x7FCC19, 0xF27FA5, 0x4C4C4C, 0x999999, 0x4C7F99,
+    0x7F3FB2, 0x334CB2, 0x664C33, 0x667F33, 0x993333, 0x191919, 0xFAEE4D, 0x5CDBD5,
+    0x4A80FF, 0x00D93A, 0x815631, 0x700200, 0xD1B1A1, 0x9F5224, 0x95576C, 0x706C8A,
+    0xBA8524, 0x677535, 0xA04D4E, 0x392923, 0x876B62, 0x575C5C, 0x7A4958, 0x4C3E5C,
+    0x4C3223, 0x4C522A, 0x8E3C2E, 0x251610, 0xBD3031, 0x943F61, 0x5C191D, 0x167E86,
+    0x3A8E8C, 0x562C3E, 0x14B485, 0x646464, 0xD8AF93, 0x7FA796,
+];
+
+/// The brightness of each shade, out of 255.
+const SHADES: [u32; 4] = [180, 220, 255, 135];
+
+/// Converts a map color index into RGBA. Transparent and unknown colors are `[0, 0, 0, 0]`.
+pub fn map_color_rgba(color: u8) -> [u8; 4] {
+    let base = (color / 4) as usize;
+    if base == 0 || base >= BASE_COLORS.len() {
+        return [0, 0, 0, 0];
+    }
+    let rgb = BASE_COLORS[base];
+    let shade = SHADES[(color % 4) as usize];
+    let channel = |offset: u32| (((rgb >> offset) & 0xFF) * shade / 255) as u8;
+    [channel(16), channel(8), channel(0), 255]
+}
+
+/// The path of a map's data within a world directory.
+pub fn map_data_path<P: AsRef<Path>>(world_dir: P, id: i32) -> PathBuf {
+    world_dir.as_ref().join("data").join(format!("map_{id}.dat"))
+}
+
+/// The path of `idcounts.dat` within a world directory.
+pub fn idcounts_path<P: AsRef<Path>>(world_dir: P) -> PathBuf {
+    world_dir.as_ref().join("data").join("idcounts.dat")
+}
+
+/// The ids of the maps in the world's `data` directory, sorted.
+/// Returns an empty list if the world has no `data` directory.
+pub fn map_ids<P: AsRef<Path>>(world_dir: P) -> McResult<Vec<i32>> {
+    let directory = world_dir.as_ref().join("data");
+    if !directory.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut ids = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let name = entry?.file_name();
+        let id = name.to_str()
+            .and_then(|name| name.strip_prefix("map_"))
+            .and_then(|name| name.strip_suffix(".dat"))
+            .and_then(|id| id.parse().ok());
+        if let Some(id) = id {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+fn read_root<P: AsRef<Path>>(path: P) -> McResult<Map> {
+    let mut reader = BufReader::new(File::open(path)?);
+    match read_nbt_auto(&mut reader)?.take_tag() {
+        Tag::Compound(map) => Ok(map),
+        _ => Err(McError::NbtDecodeError),
+    }
+}
+
+fn write_root<P: AsRef<Path>>(path: P, root: Map) -> McResult<usize> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    let size = write_named_tag(&mut encoder, &Tag::Compound(root), "")?;
+    encoder.finish()?.flush()?;
+    Ok(size)
+}
+
+/// A banner marker on a map.
+#[derive(Debug, Clone)]
+pub struct MapBanner {
+    /// Pos, or pos since 1.20.5.
+    pub pos: (i32, i32, i32),
+    /// Color, such as `white`.
+    pub color: String,
+    /// Name: the banner's custom name as a text component, if it has one.
+    pub name: Option<Tag>,
+}
+
+impl MapBanner {
+    /// Decodes a banner from its compound. Both the current and the pre-1.20.5 keys are read.
+    pub fn decode_map(mut map: Map) -> McResult<Self> {
+        let pos = match (map.remove("pos"), map.remove("Pos")) {
+            (Some(Tag::IntArray(pos)), _) if pos.len() == 3 => (pos[0], pos[1], pos[2]),
+            (_, Some(Tag::Compound(pos))) => {
+                let axis = |key: &str| match pos.get(key) {
+                    Some(Tag::Int(value)) => Ok(*value),
+                    _ => Err(McError::NotFoundInCompound(key.to_owned())),
+                };
+                (axis("X")?, axis("Y")?, axis("Z")?)
+            }
+            _ => return Err(McError::NotFoundInCompound("pos".to_owned())),
+        };
+        let color = match (map.remove("color"), map.remove("Color")) {
+            (Some(Tag::String(color)), _) | (_, Some(Tag::String(color))) => color,
+            _ => "white".to_owned(),
+        };
+        let name = map.remove("name").or_else(|| map.remove("Name"));
+        Ok(Self { pos, color, name })
+    }
+
+    /// Encodes the banner with the keys that `data_version` uses.
+    pub fn encode_map(&self, data_version: Option<i32>) -> Map {
+        let mut map = Map::new();
+        let (x, y, z) = self.pos;
+        if data_version.is_some_and(|version| version >= BANNER_CODEC_VERSION) {
+            map.insert("pos".to_owned(), Tag::IntArray(vec![x, y, z]));
+            map.insert("color".to_owned(), Tag::String(self.color.clone()));
+            if let Some(name) = &self.name {
+                map.insert("name".to_owned(), name.clone());
+            }
+        } else {
+            let mut pos = Map::new();
+            pos.insert("X".to_owned(), Tag::Int(x));
+            pos.insert("Y".to_owned(), Tag::Int(y));
+            pos.insert("Z".to_owned(), Tag::Int(z));
+            map.insert("Pos".to_owned(), Tag::Compound(pos));
+            map.insert("Color".to_owned(), Tag::String(self.color.clone()));
+            if let Some(name) = &self.name {
+                map.insert("Name".to_owned(), name.clone());
+            }
+        }
+        map
+    }
+}
+
+/// The contents of a `map_<id>.dat` file.
+#[derive(Debug, Clone)]
+pub struct MapItemData {
+    /// DataVersion (1.9+)
+    pub data_version: Option<i32>,
+    /// scale, from 0 (1 block per pixel) to 4 (16 blocks per pixel).
+    pub scale: i8,
+    /// dimension, such as `minecraft:overworld`.
+    /// Before 20w21a this was a number, which is converted to and from the name.
+    pub dimension: String,
+    /// `(xCenter, zCenter)`
+    pub center: (i32, i32),
+    /// locked: whether the map was locked in a cartography table.
+    pub locked: bool,
+    /// trackingPosition
+    pub tracking_position: bool,
+    /// unlimitedTracking
+    pub unlimited_tracking: bool,
+    /// colors: the color index of each pixel, in rows from north to south.
+    pub colors: Vec<u8>,
+    /// banners
+    pub banners: Vec<MapBanner>,
+    /// The rest of the map's `data` compound, such as `frames`.
+    pub other: Map,
+}
+
+impl MapItemData {
+    /// Creates a blank map.
+    pub fn new<S: Into<String>>(dimension: S, center: (i32, i32), scale: i8) -> Self {
+        Self {
+            data_version: None,
+            scale,
+            dimension: dimension.into(),
+            center,
+            locked: false,
+            tracking_position: true,
+            unlimited_tracking: false,
+            colors: vec![0; MAP_SIZE * MAP_SIZE],
+            banners: Vec::new(),
+            other: Map::new(),
+        }
+    }
+
+    /// Reads map data from a file.
+    /// The file is normally GZip compressed, but ZLib and raw NBT are accepted as well.
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        Self::decode_nbt(Tag::Compound(read_root(path)?))
+    }
+
+    /// Writes map data to a file with GZip compression, creating the parent directory if needed.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> McResult<usize> {
+        let Tag::Compound(root) = self.encode_nbt() else {
+            unreachable!("Map data is encoded as a compound.");
+        };
+        write_root(path, root)
+    }
+
+    /// Reads the map with the given id from a world directory.
+    pub fn load<P: AsRef<Path>>(world_dir: P, id: i32) -> McResult<Self> {
+        Self::read_from_file(map_data_path(world_dir, id))
+    }
+
+    /// Writes the map with the given id into a world directory.
+    pub fn save_to_world<P: AsRef<Path>>(&self, world_dir: P, id: i32) -> McResult<usize> {
+        self.write_to_file(map_data_path(world_dir, id))
+    }
+
+    /// The color index of the pixel at `(x, z)`, or `None` if it's outside of the map.
+    pub fn color(&self, x: usize, z: usize) -> Option<u8> {
+        if x >= MAP_SIZE {
+            return None;
+        }
+        self.colors.get(z * MAP_SIZE + x).copied()
+    }
+
+    /// Sets the color index of the pixel at `(x, z)`. Returns the previous color, or `None` if it's outside of the map.
+    pub fn set_color(&mut self, x: usize, z: usize, color: u8) -> Option<u8> {
+        if x >= MAP_SIZE {
+            return None;
+        }
+        self.colors.get_mut(z * MAP_SIZE + x).map(|pixel| std::mem::replace(pixel, color))
+    }
+
+    /// Renders the map's colors into a 128x128 RGBA buffer, in rows from north to south.
+    pub fn render_rgba(&self) -> Vec<u8> {
+        let mut rgba = vec![0; MAP_SIZE * MAP_SIZE * 4];
+        for (pixel, &color) in rgba.chunks_exact_mut(4).zip(&self.colors) {
+            pixel.copy_from_slice(&map_color_rgba(color));
+        }
+        rgba
+    }
+
+    pub fn decode_nbt(nbt: Tag) -> McResult<Self> {
+        let Tag::Compound(mut root) = nbt else {
+            return Err(McError::NbtDecodeError);
+        };
+        let data_version = match root.remove("DataVersion") {
+            Some(Tag::Int(version)) => Some(version),
+            _ => None,
+        };
+        let Some(Tag::Compound(mut map)) = root.remove("data") else {
+            return Err(McError::NotFoundInCompound("data".to_owned()));
+        };
+        let dimension = match map.remove("dimension") {
+            Some(Tag::String(dimension)) => dimension,
+            Some(Tag::Int(-1) | Tag::Byte(-1)) => "minecraft:the_nether".to_owned(),
+            Some(Tag::Int(1) | Tag::Byte(1)) => "minecraft:the_end".to_owned(),
+            _ => "minecraft:overworld".to_owned(),
+        };
+        let colors = match map.remove("colors") {
+            Some(Tag::ByteArray(colors)) if colors.len() == MAP_SIZE * MAP_SIZE => colors.into_iter().map(|color| color as u8).collect(),
+            _ => vec![0; MAP_SIZE * MAP_SIZE],
+        };
+        let banners = match map.remove("banners") {
+            Some(Tag::List(ListTag::Compound(banners))) => banners.into_iter().map(MapBanner::decode_map).collect::<McResult<_>>()?,
+            _ => Vec::new(),
+        };
+        macro_rules! take {
+            ($key:literal, $variant:ident) => {
+                match map.remove($key) {
+                    Some(Tag::$variant(value)) => Some(value),
+                    _ => None,
+                }
+            };
+        }
+        Ok(Self {
+            data_version,
+            scale: take!("scale", Byte).unwrap_or_default(),
+            dimension,
+            center: (take!("xCenter", Int).unwrap_or_default(), take!("zCenter", Int).unwrap_or_default()),
+            locked: take!("locked", Byte).is_some_and(|locked| locked != 0),
+            tracking_position: take!("trackingPosition", Byte).map_or(true, |tracking| tracking != 0),
+            unlimited_tracking: take!("unlimitedTracking", Byte).is_some_and(|unlimited| unlimited != 0),
+            colors,
+            banners,
+            other: map,
+        })
+    }
+
+    pub fn encode_nbt(&self) -> Tag {
+        let mut map = self.other.clone();
+        map.insert("scale".to_owned(), Tag::Byte(self.scale));
+        let legacy_dimension = match self.dimension.as_str() {
+            _ if self.data_version.is_some_and(|version| version >= NAMED_DIMENSION_VERSION) => None,
+            "minecraft:the_nether" => Some(-1),
+            "minecraft:overworld" => Some(0),
+            "minecraft:the_end" => Some(1),
+            _ => None,
+        };
+        map.insert("dimension".to_owned(), match legacy_dimension {
+            Some(dimension) => Tag::Int(dimension),
+            None => Tag::String(self.dimension.clone()),
+        });
+        map.insert("xCenter".to_owned(), Tag::Int(self.center.0));
+        map.insert("zCenter".to_owned(), Tag::Int(self.center.1));
+        map.insert("locked".to_owned(), Tag::Byte(self.locked as i8));
+        map.insert("trackingPosition".to_owned(), Tag::Byte(self.tracking_position as i8));
+        map.insert("unlimitedTracking".to_owned(), Tag::Byte(self.unlimited_tracking as i8));
+        map.insert("colors".to_owned(), Tag::ByteArray(self.colors.iter().map(|&color| color as i8).collect()));
+        let banners = self.banners.iter().map(|banner| banner.encode_map(self.data_version)).collect();
+        map.insert("banners".to_owned(), Tag::List(ListTag::Compound(banners)));
+        let mut root = Map::new();
+        if let Some(data_version) = self.data_version {
+            root.insert("DataVersion".to_owned(), Tag::Int(data_version));
+        }
+        root.insert("data".to_owned(), Tag::Compound(map));
+        Tag::Compound(root)
+    }
+}
+
+/// The contents of `idcounts.dat`, which tracks the last map id that was used.
+#[derive(Debug, Clone)]
+pub struct MapIdCounts {
+    /// DataVersion (1.9+)
+    pub data_version: Option<i32>,
+    /// map: the last map id that was used, or `-1` if no maps have been created.
+    pub map: i32,
+}
+
+impl Default for MapIdCounts {
+    fn default() -> Self {
+        Self {
+            data_version: None,
+            map: -1,
+        }
+    }
+}
+
+impl MapIdCounts {
+    /// Reads `idcounts.dat` from a file. Both the current layout (`data.map`) and the pre-1.13 layout
+    /// (a Short `map` at the root) are read.
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let mut root = read_root(path)?;
+        let data_version = match root.remove("DataVersion") {
+            Some(Tag::Int(version)) => Some(version),
+            _ => None,
+        };
+        let map = match root.get("data") {
+            Some(Tag::Compound(data)) => data.get("map"),
+            _ => root.get("map"),
+        };
+        let map = match map {
+            Some(Tag::Int(map)) => *map,
+            Some(Tag::Short(map)) => *map as i32,
+            _ => return Err(McError::NotFoundInCompound("map".to_owned())),
+        };
+        Ok(Self { data_version, map })
+    }
+
+    /// Writes `idcounts.dat` to a file with GZip compression, creating the parent directory if needed.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> McResult<usize> {
+        let mut data = Map::new();
+        data.insert("map".to_owned(), Tag::Int(self.map));
+        let mut root = Map::new();
+        if let Some(data_version) = self.data_version {
+            root.insert("DataVersion".to_owned(), Tag::Int(data_version));
+        }
+        root.insert("data".to_owned(), Tag::Compound(data));
+        write_root(path, root)
+    }
+
+    /// Reads `idcounts.dat` from a world directory.
+    /// Returns the default (no maps created) if the world doesn't have one.
+    pub fn load<P: AsRef<Path>>(world_dir: P) -> McResult<Self> {
+        let path = idcounts_path(world_dir);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        Self::read_from_file(path)
+    }
+
+    /// Writes `idcounts.dat` into a world directory.
+    pub fn save_to_world<P: AsRef<Path>>(&self, world_dir: P) -> McResult<usize> {
+        self.write_to_file(idcounts_path(world_dir))
+    }
+
+    /// Reserves the next map id and returns it. Save the counts afterwards so that the game doesn't reuse the id.
+    pub fn next_id(&mut self) -> i32 {
+        self.map += 1;
+        self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_item_data_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut counts = MapIdCounts::load(dir.path()).unwrap();
+        assert_eq!(counts.next_id(), 0);
+        assert_eq!(counts.next_id(), 1);
+        counts.save_to_world(dir.path()).unwrap();
+        assert_eq!(MapIdCounts::load(dir.path()).unwrap().map, 1);
+
+        let mut map = MapItemData::new("minecraft:the_nether", (64, -64), 2);
+        map.data_version = Some(3953);
+        // Grass (base color 1) with the brightest shade, and water (base color 12) with the darkest shade.
+        assert_eq!(map.set_color(0, 0, 6), Some(0));
+        map.set_color(127, 127, 12 * 4 + 3);
+        assert_eq!(map.set_color(128, 0, 1), None);
+        map.banners.push(MapBanner { pos: (70, 64, -60), color: "red".to_owned(), name: None });
+        map.save_to_world(dir.path(), 1).unwrap();
+        assert_eq!(map_ids(dir.path()).unwrap(), [1]);
+
+        let loaded = MapItemData::load(dir.path(), 1).unwrap();
+        assert_eq!(loaded.dimension, "minecraft:the_nether");
+        assert_eq!((loaded.center, loaded.scale), ((64, -64), 2));
+        assert_eq!(loaded.banners[0].pos, (70, 64, -60));
+        let rgba = loaded.render_rgba();
+        assert_eq!(rgba.len(), MAP_SIZE * MAP_SIZE * 4);
+        assert_eq!(&rgba[..4], &[0x7F, 0xB2, 0x38, 255]);
+        assert_eq!(&rgba[4..8], &[0, 0, 0, 0]);
+        assert_eq!(&rgba[rgba.len() - 4..], &[33, 33, 135, 255]);
+    }
+}
@@ -0,0 +1,125 @@
+//! Copying chunk columns between worlds.
+
+use std::path::PathBuf;
+
+use crate::{
+    McError, McResult,
+    math::coord::{Dimension, WorldCoord},
+    nbt::tag::NamedTag,
+};
+
+use super::{
+    io::region::RegionFile,
+    selection::Selection,
+    world::VirtualJavaWorld,
+};
+
+/// Writes a chunk's NBT to a region folder that doesn't always have a region file for every region.
+/// If `tag` is `None`, the chunk's entry is removed (if the region file exists).
+fn replace_optional_chunk(directory: PathBuf, coord: WorldCoord, tag: Option<&NamedTag>) -> McResult<()> {
+    let region_coord = coord.region_coord();
+    let path = directory.join(region_coord.region_file_name());
+    match tag {
+        Some(tag) => {
+            std::fs::create_dir_all(&directory)?;
+            RegionFile::open_or_create(path)?.write_data_with_utcnow(coord.xz(), tag)?;
+        }
+        None if path.is_file() => {
+            RegionFile::open(path)?.delete_data(coord.xz())?;
+        }
+        None => (),
+    }
+    Ok(())
+}
+
+/// Reads the NBT of a chunk from `world`. Loaded chunks are encoded with the world's
+/// registry, so unsaved changes are included. Other chunks are read from disk without being loaded.
+/// Returns `None` if the chunk hasn't been generated.
+fn read_chunk_nbt(world: &mut VirtualJavaWorld, coord: WorldCoord) -> McResult<Option<NamedTag>> {
+    if let Some(slot) = world.get_chunk(coord) {
+        let Ok(slot) = slot.lock() else {
+            return McError::custom("Failed to lock chunk.");
+        };
+        return Ok(Some(NamedTag::new(slot.chunk.to_nbt(&world.block_registry))));
+    }
+    let region_coord = coord.region_coord();
+    if !world.get_region_directory(coord.dimension)?.join(region_coord.region_file_name()).is_file() {
+        return Ok(None);
+    }
+    let region = world.get_or_load_region(region_coord)?;
+    let Ok(mut region) = region.lock() else {
+        return McError::custom("Failed to lock region file.");
+    };
+    match region.region.read_data(coord.xz()) {
+        Ok(tag) => Ok(Some(tag)),
+        Err(McError::RegionDataNotFound) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Copies the chunk columns that a [Selection] intersects in a dimension from `src` to `dst`,
+/// including their entities and POI. The copied chunks replace the chunks in `dst`, and the entities and POI
+/// of the destination chunks are replaced with those of the source (or removed if the source has none).
+/// Chunks that haven't been generated in `src` are skipped.
+///
+/// Blocks are copied by their state rather than by their id, so the worlds' block registries don't need to match.
+/// Chunks that are loaded in `src` are copied with their unsaved changes. Chunks that are loaded in `dst`
+/// are reloaded from the copy, discarding their unsaved changes.
+/// Returns the number of chunks that were copied.
+pub fn copy_chunks<S: Selection + ?Sized>(src: &mut VirtualJavaWorld, dst: &mut VirtualJavaWorld, dimension: Dimension, selection: &S) -> McResult<usize> {
+    let mut copied = 0;
+    for (x, z) in selection.chunks() {
+        let coord = WorldCoord::new(x, z, dimension);
+        let Some(chunk) = read_chunk_nbt(src, coord)? else {
+            continue;
+        };
+        {
+            let region = dst.get_or_load_region(coord.region_coord())?;
+            let Ok(mut region) = region.lock() else {
+                return McError::custom("Failed to lock region file.");
+            };
+            region.region.write_data_with_utcnow(coord.xz(), &chunk)?;
+        }
+        // Reloading decodes the chunk with the destination's registry.
+        if dst.is_chunk_loaded(coord) {
+            dst.load_chunk(coord)?;
+        }
+        replace_optional_chunk(dst.get_entities_directory(dimension)?, coord, src.load_entities(coord)?.as_ref())?;
+        replace_optional_chunk(dst.get_poi_directory(dimension)?, coord, src.load_poi(coord)?.as_ref())?;
+        copied += 1;
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{blockstate::{BlockProperties, BlockState}, chunk::tests::empty_chunk, selection::ChunkSelection, entity::{Entity, EntityChunk}};
+    use crate::math::coord::BlockCoord;
+
+    #[test]
+    fn copy_chunks_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut src = VirtualJavaWorld::open(dir.path().join("src"));
+        let mut dst = VirtualJavaWorld::open(dir.path().join("dst"));
+        for world in [&src, &dst] {
+            std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+            let mut region = RegionFile::create(world.get_region_directory(Dimension::Overworld).unwrap().join("r.0.0.mca")).unwrap();
+            region.write_data((1, 1), &NamedTag::new(empty_chunk(1, 1).to_nbt(&world.block_registry))).unwrap();
+        }
+        // Registering a different state first gives the worlds different ids for the same state.
+        dst.block_registry.register(BlockState::new("minecraft:dirt", BlockProperties::none()));
+        let stone = BlockState::new("minecraft:stone", BlockProperties::none());
+        let coord = BlockCoord::overworld(20, 0, 20);
+        src.load_chunk(WorldCoord::overworld(1, 1)).unwrap();
+        src.set_state(coord, &stone);
+        src.save_entity_chunk(WorldCoord::overworld(1, 1), EntityChunk {
+            entities: vec![Entity::new("minecraft:pig", (20.5, 0.0, 20.5))],
+            ..EntityChunk::new(3465, 1, 1)
+        }).unwrap();
+        dst.load_chunk(WorldCoord::overworld(1, 1)).unwrap();
+        assert_eq!(copy_chunks(&mut src, &mut dst, Dimension::Overworld, &ChunkSelection::area(((0, 0), (2, 2)))).unwrap(), 1);
+        assert_eq!(dst.get_state(coord).map(BlockState::name), Some("minecraft:stone"));
+        assert_eq!(dst.load_entity_chunk(WorldCoord::overworld(1, 1)).unwrap().unwrap().entities.len(), 1);
+    }
+}
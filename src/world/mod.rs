@@ -5,4 +5,35 @@ pub mod chunk;
 pub mod world;
 pub mod container;
 pub mod block;
-pub mod level;
\ No newline at end of file
+pub mod level;
+pub mod dimension;
+pub mod lock;
+pub mod chunkversion;
+pub mod chunkstatus;
+pub mod lighting;
+pub mod entity;
+pub mod player;
+pub mod item;
+pub mod map;
+pub mod heightmap;
+pub mod iter;
+pub mod schematic;
+pub mod structure;
+pub mod structurestart;
+pub mod datapack;
+pub mod sync;
+pub mod merge;
+pub mod selection;
+pub mod session;
+pub mod shared;
+pub mod view;
+pub mod trim;
+pub mod index;
+pub mod stats;
+pub mod transform;
+#[cfg(feature = "vanilla-blocks")]
+pub mod vanilla;
+#[cfg(feature = "backup")]
+pub mod backup;
+#[cfg(feature = "render")]
+pub mod render;
\ No newline at end of file
@@ -0,0 +1,447 @@
+//! Player files: `playerdata/<uuid>.dat`, and with the `json` feature, `advancements/<uuid>.json` and `stats/<uuid>.json`.
+//!
+//! Player data is GZip compressed NBT. Like [LevelData](super::level::LevelData), only the commonly used values of
+//! [PlayerData] are typed, and everything else is kept in [PlayerData::other] so that saving doesn't discard anything.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{
+    nbt::{
+        io::{read_nbt_auto, write_named_tag},
+        tag::*,
+        Map,
+    },
+    McError, McResult,
+};
+
+// Re-exported since ItemStack was first added to this module.
+pub use super::item::ItemStack;
+
+/// The DataVersion of 20w21a, where `Dimension` changed from an Int to a String.
+const NAMED_DIMENSION_VERSION: i32 = 2554;
+
+/// The path of a player's data within a world directory.
+pub fn player_data_path<P: AsRef<Path>>(world_dir: P, uuid: &str) -> PathBuf {
+    world_dir.as_ref().join("playerdata").join(format!("{uuid}.dat"))
+}
+
+/// The path of a player's advancements within a world directory.
+pub fn advancements_path<P: AsRef<Path>>(world_dir: P, uuid: &str) -> PathBuf {
+    world_dir.as_ref().join("advancements").join(format!("{uuid}.json"))
+}
+
+/// The path of a player's statistics within a world directory.
+pub fn stats_path<P: AsRef<Path>>(world_dir: P, uuid: &str) -> PathBuf {
+    world_dir.as_ref().join("stats").join(format!("{uuid}.json"))
+}
+
+/// The UUIDs of the players that have data in `playerdata`, sorted.
+/// Backups (`.dat_old`) are skipped. Returns an empty list if the world has no `playerdata` directory.
+pub fn player_uuids<P: AsRef<Path>>(world_dir: P) -> McResult<Vec<String>> {
+    let directory = world_dir.as_ref().join("playerdata");
+    if !directory.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut uuids = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "dat") {
+            if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                uuids.push(stem.to_owned());
+            }
+        }
+    }
+    uuids.sort();
+    Ok(uuids)
+}
+
+/// The contents of a player's `.dat` file.
+#[derive(Debug, Clone)]
+pub struct PlayerData {
+    /// DataVersion (1.9+)
+    pub data_version: Option<i32>,
+    /// Pos
+    pub pos: (f64, f64, f64),
+    /// Rotation as `(yaw, pitch)`
+    pub rotation: (f32, f32),
+    /// Dimension, such as `minecraft:overworld`.
+    /// Before 20w21a this was an Int (`-1` for the Nether, `0` for the Overworld, and `1` for the End),
+    /// which is converted to and from the name.
+    pub dimension: String,
+    /// playerGameType
+    pub game_type: i32,
+    /// Health
+    pub health: f32,
+    /// foodLevel
+    pub food_level: i32,
+    /// XpLevel
+    pub xp_level: i32,
+    /// XpP: the progress towards the next level, from 0 to 1.
+    pub xp_progress: f32,
+    /// XpTotal
+    pub xp_total: i32,
+    /// Inventory
+    pub inventory: Vec<ItemStack>,
+    /// EnderItems
+    pub ender_items: Vec<ItemStack>,
+    /// The rest of the player's compound.
+    pub other: Map,
+}
+
+impl PlayerData {
+    /// Reads player data from a file.
+    /// The file is normally GZip compressed, but ZLib and raw NBT are accepted as well.
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let root = read_nbt_auto(&mut reader)?;
+        Self::decode_nbt(root.take_tag())
+    }
+
+    /// Writes player data to a file with GZip compression, overwriting the file if it exists.
+    /// Use [PlayerData::save] to keep a backup.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> McResult<usize> {
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        let size = write_named_tag(&mut encoder, &self.encode_nbt(), "")?;
+        encoder.finish()?.flush()?;
+        Ok(size)
+    }
+
+    /// Reads the data of the player with the given UUID from a world directory.
+    pub fn load<P: AsRef<Path>>(world_dir: P, uuid: &str) -> McResult<Self> {
+        Self::read_from_file(player_data_path(world_dir, uuid))
+    }
+
+    /// Saves player data the same way that Minecraft does.
+    /// The new data is written to `<uuid>.dat_new` first. Then the existing file
+    /// is moved to `<uuid>.dat_old` as a backup, and `<uuid>.dat_new` replaces it.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> McResult<()> {
+        let path = path.as_ref();
+        let file_name = path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| McError::Custom(format!("Invalid player data path: {}", path.display())))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let new_path = path.with_file_name(format!("{file_name}_new"));
+        let old_path = path.with_file_name(format!("{file_name}_old"));
+        self.write_to_file(&new_path)?;
+        if path.is_file() {
+            std::fs::rename(path, old_path)?;
+        }
+        std::fs::rename(new_path, path)?;
+        Ok(())
+    }
+
+    /// Saves the data of the player with the given UUID into a world directory with [PlayerData::save].
+    pub fn save_to_world<P: AsRef<Path>>(&self, world_dir: P, uuid: &str) -> McResult<()> {
+        self.save(player_data_path(world_dir, uuid))
+    }
+
+    /// The coordinate of the block that the player is in.
+    pub fn block_coord(&self) -> (i64, i64, i64) {
+        (self.pos.0.floor() as i64, self.pos.1.floor() as i64, self.pos.2.floor() as i64)
+    }
+
+    pub fn decode_nbt(nbt: Tag) -> McResult<Self> {
+        let Tag::Compound(mut map) = nbt else {
+            return Err(McError::NbtDecodeError);
+        };
+        let pos = match map.remove("Pos") {
+            Some(Tag::List(ListTag::Double(pos))) if pos.len() == 3 => (pos[0], pos[1], pos[2]),
+            _ => return Err(McError::NotFoundInCompound("Pos".to_owned())),
+        };
+        let rotation = match map.remove("Rotation") {
+            Some(Tag::List(ListTag::Float(rotation))) if rotation.len() == 2 => (rotation[0], rotation[1]),
+            _ => (0.0, 0.0),
+        };
+        let dimension = match map.remove("Dimension") {
+            Some(Tag::String(dimension)) => dimension,
+            Some(Tag::Int(-1)) => "minecraft:the_nether".to_owned(),
+            Some(Tag::Int(1)) => "minecraft:the_end".to_owned(),
+            _ => "minecraft:overworld".to_owned(),
+        };
+        let mut items = |key: &str| -> McResult<Vec<ItemStack>> {
+            match map.remove(key) {
+                Some(Tag::List(ListTag::Compound(items))) => items.into_iter().map(ItemStack::decode_map).collect(),
+                _ => Ok(Vec::new()),
+            }
+        };
+        let inventory = items("Inventory")?;
+        let ender_items = items("EnderItems")?;
+        macro_rules! take {
+            ($key:literal, $variant:ident) => {
+                match map.remove($key) {
+                    Some(Tag::$variant(value)) => Some(value),
+                    _ => None,
+                }
+            };
+        }
+        Ok(Self {
+            data_version: take!("DataVersion", Int),
+            pos,
+            rotation,
+            dimension,
+            inventory,
+            ender_items,
+            game_type: take!("playerGameType", Int).unwrap_or_default(),
+            health: take!("Health", Float).unwrap_or(20.0),
+            food_level: take!("foodLevel", Int).unwrap_or(20),
+            xp_level: take!("XpLevel", Int).unwrap_or_default(),
+            xp_progress: take!("XpP", Float).unwrap_or_default(),
+            xp_total: take!("XpTotal", Int).unwrap_or_default(),
+            other: map,
+        })
+    }
+
+    pub fn encode_nbt(&self) -> Tag {
+        let mut map = self.other.clone();
+        let items = |items: &[ItemStack]| {
+            Tag::List(ListTag::Compound(items.iter().map(|item| item.encode_map(self.data_version)).collect()))
+        };
+        if let Some(data_version) = self.data_version {
+            map.insert("DataVersion".to_owned(), Tag::Int(data_version));
+        }
+        map.insert("Pos".to_owned(), Tag::List(ListTag::Double(vec![self.pos.0, self.pos.1, self.pos.2])));
+        map.insert("Rotation".to_owned(), Tag::List(ListTag::Float(vec![self.rotation.0, self.rotation.1])));
+        let legacy_dimension = match self.dimension.as_str() {
+            _ if self.data_version.is_some_and(|version| version >= NAMED_DIMENSION_VERSION) => None,
+            "minecraft:the_nether" => Some(-1),
+            "minecraft:overworld" => Some(0),
+            "minecraft:the_end" => Some(1),
+            _ => None,
+        };
+        map.insert("Dimension".to_owned(), match legacy_dimension {
+            Some(dimension) => Tag::Int(dimension),
+            None => Tag::String(self.dimension.clone()),
+        });
+        map.insert("playerGameType".to_owned(), Tag::Int(self.game_type));
+        map.insert("Health".to_owned(), Tag::Float(self.health));
+        map.insert("foodLevel".to_owned(), Tag::Int(self.food_level));
+        map.insert("XpLevel".to_owned(), Tag::Int(self.xp_level));
+        map.insert("XpP".to_owned(), Tag::Float(self.xp_progress));
+        map.insert("XpTotal".to_owned(), Tag::Int(self.xp_total));
+        map.insert("Inventory".to_owned(), items(&self.inventory));
+        map.insert("EnderItems".to_owned(), items(&self.ender_items));
+        Tag::Compound(map)
+    }
+}
+
+#[cfg(feature = "json")]
+pub use json::*;
+
+#[cfg(feature = "json")]
+mod json {
+    use std::{collections::BTreeMap, path::Path};
+
+    use serde_json::Value;
+
+    use crate::{McError, McResult};
+
+    fn read_json(path: &Path) -> McResult<serde_json::Map<String, Value>> {
+        match serde_json::from_slice(&std::fs::read(path)?)? {
+            Value::Object(object) => Ok(object),
+            _ => McError::custom(format!("Expected a JSON object in {}", path.display())),
+        }
+    }
+
+    /// Writes JSON the way that Minecraft does, with two space indentation.
+    fn write_json(path: &Path, object: serde_json::Map<String, Value>) -> McResult<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&Value::Object(object))?)?;
+        Ok(())
+    }
+
+    /// The progress of a single advancement (or recipe unlock, which is stored as an advancement).
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct AdvancementProgress {
+        /// The criteria that have been met, with the time that they were met (such as `2024-01-01 12:00:00 +0000`).
+        pub criteria: BTreeMap<String, String>,
+        pub done: bool,
+    }
+
+    /// The contents of a player's `advancements/<uuid>.json`.
+    #[derive(Debug, Clone, Default)]
+    pub struct PlayerAdvancements {
+        pub data_version: Option<i32>,
+        /// Advancements by id, such as `minecraft:story/mine_stone`.
+        pub advancements: BTreeMap<String, AdvancementProgress>,
+    }
+
+    impl PlayerAdvancements {
+        pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+            let mut advancements = Self::default();
+            for (key, value) in read_json(path.as_ref())? {
+                match value {
+                    Value::Number(version) if key == "DataVersion" => advancements.data_version = version.as_i64().map(|version| version as i32),
+                    Value::Object(mut progress) => {
+                        let criteria = match progress.remove("criteria") {
+                            Some(Value::Object(criteria)) => criteria.into_iter()
+                                .filter_map(|(name, time)| Some((name, time.as_str()?.to_owned())))
+                                .collect(),
+                            _ => BTreeMap::new(),
+                        };
+                        let done = progress.get("done").and_then(Value::as_bool).unwrap_or_default();
+                        advancements.advancements.insert(key, AdvancementProgress { criteria, done });
+                    }
+                    _ => (),
+                }
+            }
+            Ok(advancements)
+        }
+
+        pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> McResult<()> {
+            let mut object = serde_json::Map::new();
+            for (id, progress) in &self.advancements {
+                let criteria = progress.criteria.iter()
+                    .map(|(name, time)| (name.clone(), Value::String(time.clone())))
+                    .collect();
+                object.insert(id.clone(), serde_json::json!({ "criteria": Value::Object(criteria), "done": progress.done }));
+            }
+            if let Some(data_version) = self.data_version {
+                object.insert("DataVersion".to_owned(), data_version.into());
+            }
+            write_json(path.as_ref(), object)
+        }
+
+        pub fn is_done(&self, id: &str) -> bool {
+            self.advancements.get(id).is_some_and(|progress| progress.done)
+        }
+
+        /// The ids of the advancements that are done.
+        pub fn completed(&self) -> impl Iterator<Item = &str> {
+            self.advancements.iter().filter(|(_, progress)| progress.done).map(|(id, _)| id.as_str())
+        }
+    }
+
+    /// The contents of a player's `stats/<uuid>.json` (1.13+).
+    #[derive(Debug, Clone, Default)]
+    pub struct PlayerStats {
+        pub data_version: Option<i32>,
+        /// Statistics by category (such as `minecraft:mined`), then by statistic (such as `minecraft:stone`).
+        pub stats: BTreeMap<String, BTreeMap<String, i64>>,
+    }
+
+    impl PlayerStats {
+        pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+            let mut object = read_json(path.as_ref())?;
+            let stats = match object.remove("stats") {
+                Some(Value::Object(categories)) => categories.into_iter()
+                    .filter_map(|(category, values)| match values {
+                        Value::Object(values) => Some((category, values.into_iter()
+                            .filter_map(|(name, value)| Some((name, value.as_i64()?)))
+                            .collect())),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => BTreeMap::new(),
+            };
+            Ok(Self {
+                data_version: object.get("DataVersion").and_then(Value::as_i64).map(|version| version as i32),
+                stats,
+            })
+        }
+
+        pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> McResult<()> {
+            let mut object = serde_json::Map::new();
+            object.insert("stats".to_owned(), serde_json::to_value(&self.stats)?);
+            if let Some(data_version) = self.data_version {
+                object.insert("DataVersion".to_owned(), data_version.into());
+            }
+            write_json(path.as_ref(), object)
+        }
+
+        /// Gets a statistic, which is 0 if it isn't stored.
+        pub fn get(&self, category: &str, name: &str) -> i64 {
+            self.stats.get(category).and_then(|values| values.get(name)).copied().unwrap_or_default()
+        }
+
+        pub fn set<C: Into<String>, N: Into<String>>(&mut self, category: C, name: N, value: i64) {
+            self.stats.entry(category.into()).or_default().insert(name.into(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_data_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+        let tag = Tag::parse(r#"{
+            DataVersion: 3465,
+            Pos: [1.5d, 64.0d, -2.5d],
+            Rotation: [90.0f, 0.0f],
+            Dimension: "minecraft:the_nether",
+            XpLevel: 30,
+            XpP: 0.5f,
+            XpTotal: 1395,
+            Inventory: [{ Slot: 0b, id: "minecraft:diamond_sword", Count: 1b, tag: { Damage: 5 } }, { Slot: 1b, id: "minecraft:stone", Count: 64b }],
+            EnderItems: [{ Slot: 26b, id: "minecraft:elytra", Count: 1b }],
+            Brain: { memories: {} }
+        }"#).unwrap();
+        let mut player = PlayerData::decode_nbt(tag).unwrap();
+        assert_eq!(player.block_coord(), (1, 64, -3));
+        assert_eq!(player.dimension, "minecraft:the_nether");
+        assert_eq!(player.inventory[1].count, 64);
+        assert!(player.inventory[0].other.contains_key("tag"));
+        player.xp_level += 1;
+        player.ender_items.push(ItemStack::new("minecraft:diamond", 3).with_slot(0));
+        player.save_to_world(dir.path(), uuid).unwrap();
+        player.save_to_world(dir.path(), uuid).unwrap();
+        assert!(dir.path().join("playerdata").join(format!("{uuid}.dat_old")).is_file());
+        assert_eq!(player_uuids(dir.path()).unwrap(), [uuid]);
+
+        let loaded = PlayerData::load(dir.path(), uuid).unwrap();
+        assert_eq!(loaded.xp_level, 31);
+        assert_eq!(loaded.ender_items.len(), 2);
+        assert!(loaded.other.contains_key("Brain"));
+        let Tag::Compound(encoded) = loaded.encode_nbt() else { panic!() };
+        assert!(matches!(encoded.get("Dimension"), Some(Tag::String(_))));
+        assert!(matches!(&encoded["Inventory"], Tag::List(ListTag::Compound(items)) if matches!(items[1].get("Count"), Some(Tag::Byte(64)))));
+
+        // Before 20w21a, the dimension is an Int.
+        let legacy = PlayerData::decode_nbt(Tag::parse("{ DataVersion: 1343, Pos: [0.0d, 0.0d, 0.0d], Dimension: 1 }").unwrap()).unwrap();
+        assert_eq!(legacy.dimension, "minecraft:the_end");
+        assert!(matches!(legacy.encode_nbt(), Tag::Compound(map) if matches!(map.get("Dimension"), Some(Tag::Int(1)))));
+        assert!(player_uuids(dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn player_json_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = advancements_path(dir.path(), "uuid");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, r#"{
+            "minecraft:story/mine_stone": { "criteria": { "get_stone": "2024-01-01 12:00:00 +0000" }, "done": true },
+            "minecraft:story/smelt_iron": { "criteria": {}, "done": false },
+            "DataVersion": 3465
+        }"#).unwrap();
+        let mut advancements = PlayerAdvancements::read_from_file(&path).unwrap();
+        assert!(advancements.is_done("minecraft:story/mine_stone"));
+        assert_eq!(advancements.completed().count(), 1);
+        advancements.advancements.remove("minecraft:story/mine_stone");
+        advancements.write_to_file(&path).unwrap();
+        let advancements = PlayerAdvancements::read_from_file(&path).unwrap();
+        assert_eq!((advancements.advancements.len(), advancements.data_version), (1, Some(3465)));
+
+        let path = stats_path(dir.path(), "uuid");
+        let mut stats = PlayerStats::default();
+        stats.set("minecraft:mined", "minecraft:stone", 12);
+        stats.write_to_file(&path).unwrap();
+        let stats = PlayerStats::read_from_file(&path).unwrap();
+        assert_eq!(stats.get("minecraft:mined", "minecraft:stone"), 12);
+        assert_eq!(stats.get("minecraft:mined", "minecraft:dirt"), 0);
+    }
+}
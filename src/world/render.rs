@@ -0,0 +1,313 @@
+//! Top-down color maps of regions and worlds.
+//!
+//! Each pixel is the color of the highest block in its column that has a color in the [BlockPalette]. Blocks
+//! without a color are see-through, so a palette without grass shows the dirt underneath it. With height shading,
+//! each pixel is brightened or darkened by comparing its height with the pixel to the north of it, the same way
+//! that in-game maps are shaded.
+
+use std::{collections::HashMap, path::Path};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{math::coord::*, nbt::tag::NamedTag, McError, McResult};
+
+use super::{
+    blockregistry::BlockRegistry,
+    blockstate::BlockState,
+    chunk::{decode_versioned_chunk, Chunk},
+    io::region::{coord::RegionCoord, RegionFile},
+    world::VirtualJavaWorld,
+};
+
+/// The width and height of a region's image in pixels.
+pub const REGION_IMAGE_SIZE: u32 = 512;
+
+/// Colors for common terrain blocks, used by [BlockPalette::basic].
+const BASIC_COLORS: &[(&str, u32)] = &[
+    ("minecraft:grass_block", 0x7FB238),
+    ("minecraft:short_grass", 0x6E9A30),
+    ("minecraft:tall_grass", 0x6E9A30),
+    ("minecraft:dirt", 0x976D4D),
+    ("minecraft:coarse_dirt", 0x77553B),
+    ("minecraft:podzol", 0x815631),
+    ("minecraft:mycelium", 0x7F3FB2),
+    ("minecraft:sand", 0xF7E9A3),
+    ("minecraft:red_sand", 0xD87F33),
+    ("minecraft:sandstone", 0xE3DBB0),
+    ("minecraft:gravel", 0x888383),
+    ("minecraft:clay", 0xA4A8B8),
+    ("minecraft:stone", 0x707070),
+    ("minecraft:deepslate", 0x646464),
+    ("minecraft:andesite", 0x888888),
+    ("minecraft:diorite", 0xBCBCBC),
+    ("minecraft:granite", 0x9A6B57),
+    ("minecraft:bedrock", 0x333333),
+    ("minecraft:water", 0x4040FF),
+    ("minecraft:lava", 0xFF5A00),
+    ("minecraft:ice", 0xA0A0FF),
+    ("minecraft:packed_ice", 0x8DB4FA),
+    ("minecraft:snow", 0xFFFFFF),
+    ("minecraft:snow_block", 0xFFFFFF),
+    ("minecraft:oak_leaves", 0x007C00),
+    ("minecraft:birch_leaves", 0x5C8A3A),
+    ("minecraft:spruce_leaves", 0x3D6140),
+    ("minecraft:jungle_leaves", 0x1E8A12),
+    ("minecraft:acacia_leaves", 0x4F8A1E),
+    ("minecraft:dark_oak_leaves", 0x2B6B12),
+    ("minecraft:oak_log", 0x8F7748),
+    ("minecraft:spruce_log", 0x664C33),
+    ("minecraft:birch_log", 0xD7CB8D),
+    ("minecraft:netherrack", 0x700200),
+    ("minecraft:soul_sand", 0x664C33),
+    ("minecraft:end_stone", 0xDBDE9E),
+    ("minecraft:terracotta", 0x985E43),
+];
+
+/// The colors of block states, by block name.
+#[derive(Debug, Clone, Default)]
+pub struct BlockPalette {
+    colors: HashMap<String, [u8; 4]>,
+    fallback: Option<[u8; 4]>,
+}
+
+impl BlockPalette {
+    /// Creates an empty palette.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A small palette of common terrain blocks. Blocks that aren't in it are see-through.
+    pub fn basic() -> Self {
+        let mut palette = Self::new();
+        for &(name, rgb) in BASIC_COLORS {
+            palette.insert(name, rgba_from_hex(rgb, 255));
+        }
+        palette
+    }
+
+    /// Parses a palette from text with one block per line: a block name followed by an `RRGGBB` or `RRGGBBAA`
+    /// color, optionally prefixed with `#`. Blank lines and lines starting with `//` are skipped.
+    pub fn parse(text: &str) -> McResult<Self> {
+        let mut palette = Self::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let invalid = || McError::Custom(format!("Invalid palette line: {line}"));
+            let mut parts = line.split_whitespace();
+            let (Some(name), Some(color), None) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(invalid());
+            };
+            let hex = color.strip_prefix('#').unwrap_or(color);
+            let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+            let rgba = match hex.len() {
+                6 => rgba_from_hex(value, 255),
+                8 => value.to_be_bytes(),
+                _ => return Err(invalid()),
+            };
+            palette.insert(name, rgba);
+        }
+        Ok(palette)
+    }
+
+    /// Reads a palette from a file in the format that [BlockPalette::parse] accepts.
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn with_color<S: Into<String>>(mut self, name: S, rgba: [u8; 4]) -> Self {
+        self.insert(name, rgba);
+        self
+    }
+
+    /// The color of blocks that aren't in the palette. Air is always see-through.
+    pub fn with_fallback(mut self, rgba: [u8; 4]) -> Self {
+        self.fallback = Some(rgba);
+        self
+    }
+
+    pub fn insert<S: Into<String>>(&mut self, name: S, rgba: [u8; 4]) -> Option<[u8; 4]> {
+        self.colors.insert(name.into(), rgba)
+    }
+
+    /// The color of a block state, or `None` if it's see-through.
+    pub fn color(&self, state: &BlockState) -> Option<[u8; 4]> {
+        match state.name() {
+            "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air" => None,
+            name => self.colors.get(name).copied().or(self.fallback),
+        }
+    }
+}
+
+fn rgba_from_hex(rgb: u32, alpha: u8) -> [u8; 4] {
+    let [_, r, g, b] = rgb.to_be_bytes();
+    [r, g, b, alpha]
+}
+
+/// Options for rendering.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    height_shading: bool,
+}
+
+impl RenderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shades each pixel by comparing its height with the pixel to the north, like in-game maps.
+    pub fn with_height_shading(mut self, height_shading: bool) -> Self {
+        self.height_shading = height_shading;
+        self
+    }
+}
+
+/// The color and height of the top block of each column.
+struct TopDown {
+    width: u32,
+    height: u32,
+    pixels: Vec<Option<([u8; 4], i64)>>,
+}
+
+impl TopDown {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![None; (width * height) as usize],
+        }
+    }
+
+    /// Draws a chunk with its north-west corner at `offset`.
+    fn draw_chunk(&mut self, offset: (u32, u32), chunk: &Chunk, registry: &BlockRegistry, palette: &BlockPalette) {
+        let mut sections = chunk.sections.sections.iter()
+            .filter(|section| section.blocks.is_some())
+            .collect::<Vec<_>>();
+        sections.sort_by_key(|section| std::cmp::Reverse(section.y));
+        for z in 0..16 {
+            for x in 0..16 {
+                let top = sections.iter().find_map(|section| (0..16).rev().find_map(|y| {
+                    let state = registry.get(section.get_id(x, y, z)?)?;
+                    let color = palette.color(state).filter(|color| color[3] > 0)?;
+                    Some((color, section.y as i64 * 16 + y))
+                }));
+                let index = (offset.1 + z as u32) * self.width + offset.0 + x as u32;
+                self.pixels[index as usize] = top;
+            }
+        }
+    }
+
+    fn into_image(self, options: &RenderOptions) -> RgbaImage {
+        RgbaImage::from_fn(self.width, self.height, |x, z| {
+            let Some((color, height)) = self.pixels[(z * self.width + x) as usize] else {
+                return Rgba([0, 0, 0, 0]);
+            };
+            if !options.height_shading {
+                return Rgba(color);
+            }
+            let north = z.checked_sub(1).and_then(|north| self.pixels[(north * self.width + x) as usize]);
+            let shade = match north {
+                Some((_, north)) if height > north => 255,
+                Some((_, north)) if height < north => 180,
+                _ => 220,
+            };
+            let [r, g, b, a] = color;
+            let channel = |value: u8| (value as u32 * shade / 255) as u8;
+            Rgba([channel(r), channel(g), channel(b), a])
+        })
+    }
+}
+
+/// Renders a region file into a 512x512 image with [RenderOptions::default].
+/// Each pixel is a block column, with the region's north-west corner at `(0, 0)`. Missing chunks are transparent.
+pub fn render_region_to_image<P: AsRef<Path>>(region_path: P, palette: &BlockPalette) -> McResult<RgbaImage> {
+    render_region_with(region_path, palette, &RenderOptions::default())
+}
+
+/// Renders a region file into a 512x512 image.
+pub fn render_region_with<P: AsRef<Path>>(region_path: P, palette: &BlockPalette, options: &RenderOptions) -> McResult<RgbaImage> {
+    render_region(region_path.as_ref(), palette, options, None)
+}
+
+/// Renders a region, drawing the chunks that are loaded in `world` from memory instead of from the region file.
+fn render_region(path: &Path, palette: &BlockPalette, options: &RenderOptions, world: Option<(&VirtualJavaWorld, WorldCoord)>) -> McResult<RgbaImage> {
+    let mut region = RegionFile::open(path)?;
+    let mut top_down = TopDown::new(REGION_IMAGE_SIZE, REGION_IMAGE_SIZE);
+    let mut registry = BlockRegistry::with_air();
+    for index in 0..1024u16 {
+        let coord = RegionCoord::from(index);
+        let offset = (coord.x() as u32 * 16, coord.z() as u32 * 16);
+        if let Some((world, region_coord)) = world {
+            let chunk_coord = region_coord.chunk_in_region(coord);
+            if let Some(slot) = world.chunks.get(&chunk_coord) {
+                let slot = slot.lock().map_err(|_| McError::Custom("Failed to lock chunk.".to_owned()))?;
+                top_down.draw_chunk(offset, &slot.chunk, &world.block_registry, palette);
+                continue;
+            }
+        }
+        if region.get_sector(coord).is_empty() {
+            continue;
+        }
+        let root: NamedTag = match region.read_data(coord) {
+            Ok(root) => root,
+            Err(McError::RegionDataNotFound) => continue,
+            Err(err) => return Err(err),
+        };
+        let chunk = decode_versioned_chunk(&mut registry, root.take_tag())?;
+        top_down.draw_chunk(offset, &chunk, &registry, palette);
+    }
+    Ok(top_down.into_image(options))
+}
+
+impl VirtualJavaWorld {
+    /// Renders each region file of a dimension into a 512x512 tile, yielding the region coordinate with each tile.
+    /// Regions are rendered one at a time when the iterator is advanced. Loaded chunks are drawn from memory,
+    /// so unsaved edits are included.
+    pub fn render_tiles<'a>(&'a self, dimension: Dimension, palette: &'a BlockPalette, options: RenderOptions) -> McResult<impl Iterator<Item = McResult<(WorldCoord, RgbaImage)>> + 'a> {
+        let directory = self.get_region_directory(dimension)?;
+        Ok(self.iter_regions(dimension)?.map(move |coord| {
+            let path = directory.join(coord.region_file_name());
+            render_region(&path, palette, &options, Some((self, coord))).map(|image| (coord, image))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{chunk::tests::empty_chunk, world::ChunkSlot};
+
+    #[test]
+    fn render_test() {
+        let palette = BlockPalette::parse("// Terrain\nminecraft:stone #707070\nminecraft:glass 00000000\n").unwrap()
+            .with_color("minecraft:gold_block", [250, 238, 77, 255]);
+        assert!(BlockPalette::parse("minecraft:stone 7070").is_err());
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        world.chunks.insert(WorldCoord::overworld(0, 0), ChunkSlot::arc_new(empty_chunk(0, 0)));
+        world.set_state(BlockCoord::overworld(0, 0, 0), BlockState::from("minecraft:stone"));
+        world.set_state(BlockCoord::overworld(0, 5, 0), BlockState::from("minecraft:glass"));
+        world.set_state(BlockCoord::overworld(0, 2, 1), BlockState::from("minecraft:gold_block"));
+        world.save_all().unwrap();
+
+        let region_path = world.get_region_directory(Dimension::Overworld).unwrap().join("r.0.0.mca");
+        let image = render_region_to_image(&region_path, &palette).unwrap();
+        assert_eq!(image.dimensions(), (REGION_IMAGE_SIZE, REGION_IMAGE_SIZE));
+        // Glass is see-through, so the stone underneath is drawn.
+        assert_eq!(image.get_pixel(0, 0), &Rgba([0x70, 0x70, 0x70, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([0, 0, 0, 0]));
+
+        // The gold block is higher than the stone to its north, so it's drawn at full brightness.
+        world.set_state(BlockCoord::overworld(0, 0, 2), BlockState::from("minecraft:stone"));
+        let options = RenderOptions::new().with_height_shading(true);
+        let tiles = world.render_tiles(Dimension::Overworld, &palette, options).unwrap()
+            .collect::<McResult<Vec<_>>>().unwrap();
+        assert_eq!(tiles.len(), 1);
+        let (coord, image) = &tiles[0];
+        assert_eq!(*coord, WorldCoord::overworld(0, 0));
+        assert_eq!(image.get_pixel(0, 0), &Rgba([96, 96, 96, 255]));
+        assert_eq!(image.get_pixel(0, 1), &Rgba([250, 238, 77, 255]));
+        assert_eq!(image.get_pixel(0, 2), &Rgba([79, 79, 79, 255]));
+    }
+}
@@ -0,0 +1,266 @@
+//! The MCEdit schematic format (`.schematic`) from before 1.13.
+//!
+//! Blocks are stored as numeric block ids and data values, so they are converted to and
+//! from block states with a table of the 1.12 blocks. The conversion is approximate:
+//! variants that are stored in data values (such as wool colors, wood types, and slab halves)
+//! are converted, but properties such as `facing` are left as their defaults when reading
+//! and are ignored when writing. Blocks that were added after 1.12 are written as air.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use super::*;
+
+const COLORS: [&str; 16] = [
+    "white", "orange", "magenta", "light_blue", "yellow", "lime", "pink", "gray",
+    "light_gray", "cyan", "purple", "blue", "brown", "green", "red", "black",
+];
+
+const WOODS: [&str; 6] = ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"];
+
+const STONE_SLABS: [&str; 8] = [
+    "smooth_stone_slab", "sandstone_slab", "petrified_oak_slab", "cobblestone_slab",
+    "brick_slab", "stone_brick_slab", "nether_brick_slab", "quartz_slab",
+];
+
+/// The block state of each 1.12 block id with a data value of 0.
+/// Unused ids are empty.
+const LEGACY_BLOCKS: [&str; 256] = [
+    "air", "stone", "grass_block", "dirt", "cobblestone", "oak_planks", "oak_sapling", "bedrock",
+    "water", "water", "lava", "lava", "sand", "gravel", "gold_ore", "iron_ore",
+    "coal_ore", "oak_log", "oak_leaves", "sponge", "glass", "lapis_ore", "lapis_block", "dispenser",
+    "sandstone", "note_block", "red_bed", "powered_rail", "detector_rail", "sticky_piston", "cobweb", "dead_bush",
+    "dead_bush", "piston", "piston_head", "white_wool", "moving_piston", "dandelion", "poppy", "brown_mushroom",
+    "red_mushroom", "gold_block", "iron_block", "smooth_stone_slab[type=double]", "smooth_stone_slab", "bricks", "tnt", "bookshelf",
+    "mossy_cobblestone", "obsidian", "torch", "fire", "spawner", "oak_stairs", "chest", "redstone_wire",
+    "diamond_ore", "diamond_block", "crafting_table", "wheat", "farmland", "furnace", "furnace[lit=true]", "oak_sign",
+    "oak_door", "ladder", "rail", "cobblestone_stairs", "oak_wall_sign", "lever", "stone_pressure_plate", "iron_door",
+    "oak_pressure_plate", "redstone_ore", "redstone_ore[lit=true]", "redstone_torch[lit=false]", "redstone_torch", "stone_button", "snow", "ice",
+    "snow_block", "cactus", "clay", "sugar_cane", "jukebox", "oak_fence", "carved_pumpkin", "netherrack",
+    "soul_sand", "glowstone", "nether_portal", "jack_o_lantern", "cake", "repeater", "repeater[powered=true]", "white_stained_glass",
+    "oak_trapdoor", "infested_stone", "stone_bricks", "brown_mushroom_block", "red_mushroom_block", "iron_bars", "glass_pane", "melon",
+    "pumpkin_stem", "melon_stem", "vine", "oak_fence_gate", "brick_stairs", "stone_brick_stairs", "mycelium", "lily_pad",
+    "nether_bricks", "nether_brick_fence", "nether_brick_stairs", "nether_wart", "enchanting_table", "brewing_stand", "cauldron", "end_portal",
+    "end_portal_frame", "end_stone", "dragon_egg", "redstone_lamp", "redstone_lamp[lit=true]", "oak_slab[type=double]", "oak_slab", "cocoa",
+    "sandstone_stairs", "emerald_ore", "ender_chest", "tripwire_hook", "tripwire", "emerald_block", "spruce_stairs", "birch_stairs",
+    "jungle_stairs", "command_block", "beacon", "cobblestone_wall", "flower_pot", "carrots", "potatoes", "oak_button",
+    "skeleton_skull", "anvil", "trapped_chest", "light_weighted_pressure_plate", "heavy_weighted_pressure_plate", "comparator", "comparator[powered=true]", "daylight_detector",
+    "redstone_block", "nether_quartz_ore", "hopper", "quartz_block", "quartz_stairs", "activator_rail", "dropper", "white_terracotta",
+    "white_stained_glass_pane", "acacia_leaves", "acacia_log", "acacia_stairs", "dark_oak_stairs", "slime_block", "barrier", "iron_trapdoor",
+    "prismarine", "sea_lantern", "hay_block", "white_carpet", "terracotta", "coal_block", "packed_ice", "sunflower",
+    "white_banner", "white_wall_banner", "daylight_detector[inverted=true]", "red_sandstone", "red_sandstone_stairs", "red_sandstone_slab[type=double]", "red_sandstone_slab", "spruce_fence_gate",
+    "birch_fence_gate", "jungle_fence_gate", "dark_oak_fence_gate", "acacia_fence_gate", "spruce_fence", "birch_fence", "jungle_fence", "dark_oak_fence",
+    "acacia_fence", "spruce_door", "birch_door", "jungle_door", "acacia_door", "dark_oak_door", "end_rod", "chorus_plant",
+    "chorus_flower", "purpur_block", "purpur_pillar", "purpur_stairs", "purpur_slab[type=double]", "purpur_slab", "end_stone_bricks", "beetroots",
+    "dirt_path", "end_gateway", "repeating_command_block", "chain_command_block", "frosted_ice", "magma_block", "nether_wart_block", "red_nether_bricks",
+    "bone_block", "structure_void", "observer", "white_shulker_box", "orange_shulker_box", "magenta_shulker_box", "light_blue_shulker_box", "yellow_shulker_box",
+    "lime_shulker_box", "pink_shulker_box", "gray_shulker_box", "light_gray_shulker_box", "cyan_shulker_box", "purple_shulker_box", "blue_shulker_box", "brown_shulker_box",
+    "green_shulker_box", "red_shulker_box", "black_shulker_box", "white_glazed_terracotta", "orange_glazed_terracotta", "magenta_glazed_terracotta", "light_blue_glazed_terracotta", "yellow_glazed_terracotta",
+    "lime_glazed_terracotta", "pink_glazed_terracotta", "gray_glazed_terracotta", "light_gray_glazed_terracotta", "cyan_glazed_terracotta", "purple_glazed_terracotta", "blue_glazed_terracotta", "brown_glazed_terracotta",
+    "green_glazed_terracotta", "red_glazed_terracotta", "black_glazed_terracotta", "white_concrete", "white_concrete_powder", "", "", "structure_block",
+];
+
+/// Converts a 1.12 block id and data value to a block state string without the namespace.
+/// Returns `None` for unused ids.
+pub fn legacy_block_state_string(id: u8, data: u8) -> Option<String> {
+    let data = data & 15;
+    let pick = |names: &[&str], index: u8| names.get(index as usize).map(|name| (*name).to_owned());
+    let half = if data & 8 == 0 { "bottom" } else { "top" };
+    let variant = match id {
+        1 => pick(&["stone", "granite", "polished_granite", "diorite", "polished_diorite", "andesite", "polished_andesite"], data),
+        3 => pick(&["dirt", "coarse_dirt", "podzol"], data),
+        5 => WOODS.get(data as usize).map(|wood| format!("{wood}_planks")),
+        6 => WOODS.get((data & 7) as usize).map(|wood| format!("{wood}_sapling")),
+        12 => pick(&["sand", "red_sand"], data),
+        17 => Some(format!("{}_log", WOODS[(data & 3) as usize])),
+        18 => Some(format!("{}_leaves", WOODS[(data & 3) as usize])),
+        19 => pick(&["sponge", "wet_sponge"], data),
+        24 => pick(&["sandstone", "chiseled_sandstone", "cut_sandstone"], data),
+        31 => pick(&["dead_bush", "grass", "fern"], data),
+        35 => Some(format!("{}_wool", COLORS[data as usize])),
+        38 => pick(&["poppy", "blue_orchid", "allium", "azure_bluet", "red_tulip", "orange_tulip", "white_tulip", "pink_tulip", "oxeye_daisy"], data),
+        43 => Some(format!("{}[type=double]", STONE_SLABS[(data & 7) as usize])),
+        44 => Some(format!("{}[type={half}]", STONE_SLABS[(data & 7) as usize])),
+        95 => Some(format!("{}_stained_glass", COLORS[data as usize])),
+        97 => pick(&["infested_stone", "infested_cobblestone", "infested_stone_bricks", "infested_mossy_stone_bricks", "infested_cracked_stone_bricks", "infested_chiseled_stone_bricks"], data),
+        98 => pick(&["stone_bricks", "mossy_stone_bricks", "cracked_stone_bricks", "chiseled_stone_bricks"], data),
+        125 => WOODS.get((data & 7) as usize).map(|wood| format!("{wood}_slab[type=double]")),
+        126 => WOODS.get((data & 7) as usize).map(|wood| format!("{wood}_slab[type={half}]")),
+        139 => pick(&["cobblestone_wall", "mossy_cobblestone_wall"], data),
+        145 => pick(&["anvil", "chipped_anvil", "damaged_anvil"], data >> 2),
+        155 => pick(&["quartz_block", "chiseled_quartz_block", "quartz_pillar"], data.min(2)),
+        159 => Some(format!("{}_terracotta", COLORS[data as usize])),
+        160 => Some(format!("{}_stained_glass_pane", COLORS[data as usize])),
+        161 => pick(&["acacia_leaves", "dark_oak_leaves"], data & 1),
+        162 => pick(&["acacia_log", "dark_oak_log"], data & 1),
+        168 => pick(&["prismarine", "prismarine_bricks", "dark_prismarine"], data),
+        171 => Some(format!("{}_carpet", COLORS[data as usize])),
+        175 => pick(&["sunflower", "lilac", "tall_grass", "large_fern", "rose_bush", "peony"], data & 7),
+        179 => pick(&["red_sandstone", "chiseled_red_sandstone", "cut_red_sandstone"], data),
+        182 => Some(format!("red_sandstone_slab[type={half}]")),
+        205 => Some(format!("purpur_slab[type={half}]")),
+        251 => Some(format!("{}_concrete", COLORS[data as usize])),
+        252 => Some(format!("{}_concrete_powder", COLORS[data as usize])),
+        _ => None,
+    };
+    variant.or_else(|| {
+        let base = LEGACY_BLOCKS[id as usize];
+        (!base.is_empty()).then(|| base.to_owned())
+    })
+}
+
+/// Converts a 1.12 block id and data value to a [BlockState].
+pub fn legacy_block_state(id: u8, data: u8) -> Option<BlockState> {
+    legacy_block_state_string(id, data)
+        .and_then(|state| BlockState::parse(format!("minecraft:{state}")).ok())
+}
+
+type LegacyCandidates = HashMap<String, Vec<(Vec<(String, String)>, u8, u8)>>;
+
+/// Block names mapped to the legacy ids that convert to them, along with the properties of the conversion.
+fn legacy_candidates() -> &'static LegacyCandidates {
+    static CANDIDATES: OnceLock<LegacyCandidates> = OnceLock::new();
+    CANDIDATES.get_or_init(|| {
+        let mut candidates = LegacyCandidates::new();
+        for id in 0..=255u8 {
+            for data in 0..16u8 {
+                let Some(state) = legacy_block_state(id, data) else {
+                    continue;
+                };
+                let properties = state.properties().unwrap_or_default().iter()
+                    .map(|property| (property.name().to_owned(), property.value().to_owned()))
+                    .collect::<Vec<_>>();
+                let entry = candidates.entry(state.name().to_owned()).or_default();
+                if !entry.iter().any(|(existing, _, _)| *existing == properties) {
+                    entry.push((properties, id, data));
+                }
+            }
+        }
+        candidates
+    })
+}
+
+/// Finds the 1.12 block id and data value of a block state.
+/// When several match, the one that has the most properties in common with `state` is used.
+pub fn find_legacy_id(state: &BlockState) -> Option<(u8, u8)> {
+    let candidates = legacy_candidates().get(state.name())?;
+    candidates.iter()
+        .filter(|(properties, _, _)| {
+            properties.iter().all(|(name, value)| state.get_property(name) == Some(value.as_str()))
+        })
+        .max_by_key(|(properties, _, _)| properties.len())
+        .or(candidates.first())
+        .map(|&(_, id, data)| (id, data))
+}
+
+/// Decodes the root compound of an MCEdit schematic.
+pub(crate) fn decode(mut map: Map) -> McResult<Schematic> {
+    let size = (
+        map_decoder!(map; "Width" -> i16) as u16,
+        map_decoder!(map; "Height" -> i16) as u16,
+        map_decoder!(map; "Length" -> i16) as u16,
+    );
+    let mut schematic = Schematic::new(size);
+    let blocks = map_decoder!(map; "Blocks" -> Vec<i8>);
+    let data = map_decoder!(map; "Data" -> Vec<i8>);
+    let add_blocks = map_decoder!(map; "AddBlocks" -> Option<Vec<i8>>);
+    if blocks.len() < schematic.blocks.blocks.len() || data.len() < schematic.blocks.blocks.len() {
+        return Err(McError::NbtDecodeError);
+    }
+    let mut ids = HashMap::<(u16, u8), u32>::new();
+    for (index, block) in schematic.blocks.blocks.iter_mut().enumerate() {
+        // AddBlocks holds the upper 4 bits of ids above 255, two blocks per byte.
+        let add = add_blocks.as_ref().and_then(|add| add.get(index >> 1)).map_or(0u16, |&add| {
+            let add = add as u8 as u16;
+            if index & 1 == 0 { (add & 0x0f) << 8 } else { (add & 0xf0) << 4 }
+        });
+        let id = add | blocks[index] as u8 as u16;
+        let data = data[index] as u8 & 15;
+        let registry = &mut schematic.blocks.block_registry;
+        *block = *ids.entry((id, data)).or_insert_with(|| {
+            u8::try_from(id).ok()
+                .and_then(|id| legacy_block_state(id, data))
+                .map_or(0, |state| registry.register(state))
+        });
+    }
+    if let Some(ListTag::Compound(block_entities)) = map_decoder!(map; "TileEntities" -> Option<ListTag>) {
+        for block_entity in block_entities {
+            schematic.block_entities.push(BlockEntity::try_from_map(with_keep_packed(block_entity))?);
+        }
+    }
+    if let Some(ListTag::Compound(entities)) = map_decoder!(map; "Entities" -> Option<ListTag>) {
+        schematic.entities = entities.into_iter().map(Entity::from_map).collect();
+    }
+    schematic.offset = (
+        map_decoder!(map; "WEOffsetX" -> Option<i32>).unwrap_or_default(),
+        map_decoder!(map; "WEOffsetY" -> Option<i32>).unwrap_or_default(),
+        map_decoder!(map; "WEOffsetZ" -> Option<i32>).unwrap_or_default(),
+    );
+    Ok(schematic)
+}
+
+/// MCEdit block entities don't have `keepPacked`, which [BlockEntity] requires.
+fn with_keep_packed(mut map: Map) -> Map {
+    map.entry("keepPacked".to_owned()).or_insert(Tag::Byte(0));
+    map
+}
+
+/// Encodes a schematic as the root compound of an MCEdit schematic.
+pub(crate) fn encode(schematic: &Schematic) -> McResult<Tag> {
+    let registry = &schematic.blocks.block_registry;
+    let legacy_ids = (0..registry.len() as u32)
+        .map(|id| registry.get(id).and_then(find_legacy_id).unwrap_or((0, 0)))
+        .collect::<Vec<(u8, u8)>>();
+    let (blocks, data): (Vec<i8>, Vec<i8>) = schematic.blocks.blocks.iter()
+        .map(|&id| {
+            let (id, data) = legacy_ids.get(id as usize).copied().unwrap_or_default();
+            (id as i8, data as i8)
+        })
+        .unzip();
+    let block_entities = schematic.block_entities.iter()
+        .map(|entity| entity.clone().to_map())
+        .collect::<Vec<Map>>();
+    let entities = schematic.entities.iter()
+        .map(|entity| entity.nbt().clone())
+        .collect::<Vec<Map>>();
+    let (width, height, length) = schematic.size();
+    let (x, y, z) = schematic.offset;
+    Ok(Tag::Compound(Map::from([
+        ("Width".to_owned(), Tag::Short(width as i16)),
+        ("Height".to_owned(), Tag::Short(height as i16)),
+        ("Length".to_owned(), Tag::Short(length as i16)),
+        ("Materials".to_owned(), Tag::string("Alpha")),
+        ("Blocks".to_owned(), Tag::ByteArray(blocks)),
+        ("Data".to_owned(), Tag::ByteArray(data)),
+        ("TileEntities".to_owned(), Tag::List(ListTag::Compound(block_entities))),
+        ("Entities".to_owned(), Tag::List(ListTag::Compound(entities))),
+        ("WEOffsetX".to_owned(), Tag::Int(x)),
+        ("WEOffsetY".to_owned(), Tag::Int(y)),
+        ("WEOffsetZ".to_owned(), Tag::Int(z)),
+    ])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_round_trip_test() {
+        let mut schematic = Schematic::new((2, 2, 2));
+        let top_slab = BlockState::parse("minecraft:oak_slab[type=top,waterlogged=false]").unwrap();
+        schematic.set_block_state(0, 0, 0, BlockState::from("minecraft:red_wool"));
+        schematic.set_block_state(1, 0, 0, &top_slab);
+        schematic.set_block_state(0, 1, 0, BlockState::from("minecraft:mangrove_planks"));
+        schematic.set_block_state(1, 1, 1, BlockState::parse("minecraft:furnace[facing=north,lit=true]").unwrap());
+        let root = schematic.to_nbt(SchematicFormat::McEdit).unwrap();
+        let decoded = Schematic::from_nbt(root.take_tag()).unwrap();
+        assert_eq!(decoded.get_block_state(0, 0, 0).map(BlockState::name), Some("minecraft:red_wool"));
+        let slab = decoded.get_block_state(1, 0, 0).unwrap();
+        assert_eq!(slab.name(), "minecraft:oak_slab");
+        assert_eq!(slab.get_property("type"), Some("top"));
+        // Mangrove planks didn't exist in 1.12.
+        assert_eq!(decoded.get_block_state(0, 1, 0).map(BlockState::name), Some("minecraft:air"));
+        assert_eq!(decoded.get_block_state(1, 1, 1).and_then(|state| state.get_property("lit")), Some("true"));
+        assert_eq!(find_legacy_id(&BlockState::from("minecraft:stone")), Some((1, 0)));
+        assert_eq!(find_legacy_id(&BlockState::from("minecraft:smooth_stone_slab")), Some((43, 0)));
+    }
+}
@@ -0,0 +1,463 @@
+//! The Litematica schematic format (`.litematic`).
+//!
+//! A litematic holds one or more named regions. Each region has its own position relative to the
+//! litematic's origin, and its own palette of block state compounds. The palette indices are packed
+//! into a long array with a fixed number of bits per block, and unlike chunk sections, an index may
+//! span two longs. Blocks are stored in YZX order.
+//!
+//! The size of a region may be negative on any axis, which means that the region extends in the
+//! negative direction from its position. Regions are always written with positive sizes.
+
+use std::collections::HashMap;
+
+use super::*;
+
+/// The litematic format version that is written.
+pub const LITEMATIC_VERSION: i32 = 6;
+
+/// A region of a [Litematic].
+pub struct LitematicRegion {
+    pub name: String,
+    /// The minimum corner of the region, relative to the litematic's origin.
+    pub position: (i32, i32, i32),
+    /// The blocks, block entities, and entities of the region.
+    /// Positions within the schematic are relative to the region's minimum corner.
+    pub schematic: Schematic,
+    /// All other unknown tags, such as `PendingBlockTicks`.
+    pub other: Map,
+}
+
+impl LitematicRegion {
+    pub fn new<S: AsRef<str>>(name: S, position: (i32, i32, i32), schematic: Schematic) -> Self {
+        Self {
+            name: name.as_ref().to_owned(),
+            position,
+            schematic,
+            other: Map::new(),
+        }
+    }
+}
+
+/// A Litematica schematic.
+pub struct Litematic {
+    /// MinecraftDataVersion
+    pub data_version: i32,
+    /// Metadata.Name
+    pub name: String,
+    /// Metadata.Author
+    pub author: String,
+    /// Metadata.Description
+    pub description: String,
+    /// Metadata.TimeCreated, in milliseconds since the Unix epoch.
+    pub time_created: i64,
+    /// Metadata.TimeModified, in milliseconds since the Unix epoch.
+    pub time_modified: i64,
+    pub regions: Vec<LitematicRegion>,
+    /// All other unknown metadata tags, such as `PreviewImageData`.
+    /// The tags that are computed from the regions (such as `TotalBlocks`) are replaced when encoding.
+    pub other_metadata: Map,
+}
+
+impl Litematic {
+    /// Creates a litematic without any regions.
+    pub fn new<S: AsRef<str>>(name: S) -> Self {
+        Self {
+            data_version: 0,
+            name: name.as_ref().to_owned(),
+            author: String::new(),
+            description: String::new(),
+            time_created: 0,
+            time_modified: 0,
+            regions: Vec::new(),
+            other_metadata: Map::new(),
+        }
+    }
+
+    /// Creates a litematic with a single region that holds `schematic`.
+    pub fn from_schematic<S: AsRef<str>>(name: S, schematic: Schematic) -> Self {
+        let mut litematic = Self::new(name.as_ref());
+        litematic.data_version = schematic.data_version;
+        litematic.regions.push(LitematicRegion::new(name, (0, 0, 0), schematic));
+        litematic
+    }
+
+    /// The minimum corner and size of the box that encloses all of the regions.
+    pub fn enclosing_box(&self) -> ((i32, i32, i32), (u16, u16, u16)) {
+        let Some(min) = self.regions.iter()
+            .map(|region| region.position)
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2))) else {
+            return ((0, 0, 0), (0, 0, 0));
+        };
+        let max = self.regions.iter()
+            .map(|region| {
+                let (x, y, z) = region.position;
+                let (width, height, length) = region.schematic.size();
+                (x + width as i32, y + height as i32, z + length as i32)
+            })
+            .fold(min, |a, b| (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)));
+        (min, ((max.0 - min.0) as u16, (max.1 - min.1) as u16, (max.2 - min.2) as u16))
+    }
+
+    /// Merges the regions into a single schematic that covers the [enclosing box](Self::enclosing_box).
+    /// Where regions overlap, later regions replace the blocks of earlier ones.
+    /// The offset of the schematic is the minimum corner of the enclosing box.
+    pub fn to_schematic(&self) -> Schematic {
+        let (min, size) = self.enclosing_box();
+        let mut schematic = Schematic::new(size);
+        schematic.offset = min;
+        schematic.data_version = self.data_version;
+        schematic.metadata.insert("Name".to_owned(), Tag::string(&self.name));
+        schematic.metadata.insert("Author".to_owned(), Tag::string(&self.author));
+        for region in self.regions.iter() {
+            let offset = (
+                (region.position.0 - min.0) as i64,
+                (region.position.1 - min.1) as i64,
+                (region.position.2 - min.2) as i64,
+            );
+            let registry = &region.schematic.blocks.block_registry;
+            let ids = (0..registry.len() as u32)
+                .map(|id| registry.get(id).map_or(0, |state| schematic.blocks.block_registry.register(state)))
+                .collect::<Vec<u32>>();
+            let (width, height, length) = region.schematic.size();
+            for y in 0..height as i64 {
+                for z in 0..length as i64 {
+                    for x in 0..width as i64 {
+                        let id = region.schematic.blocks.get_block_id(x, y, z).unwrap_or_default();
+                        schematic.blocks.set_block_id(x + offset.0, y + offset.1, z + offset.2, ids[id as usize]);
+                    }
+                }
+            }
+            schematic.block_entities.extend(region.schematic.block_entities.iter().map(|entity| {
+                let mut entity = entity.clone();
+                entity.x += offset.0 as i32;
+                entity.y += offset.1 as i32;
+                entity.z += offset.2 as i32;
+                entity
+            }));
+            schematic.entities.extend(region.schematic.entities.iter().map(|entity| {
+                let mut entity = entity.clone();
+                if let Some((x, y, z)) = entity.pos() {
+                    entity.set_pos((x + offset.0 as f64, y + offset.1 as f64, z + offset.2 as f64));
+                }
+                entity
+            }));
+        }
+        schematic
+    }
+
+    /// Reads a litematic file. The file may be GZip compressed (which is normal), ZLib compressed, or uncompressed.
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Self::from_nbt(read_nbt_auto(&mut reader)?.take_tag())
+    }
+
+    /// Writes the litematic to a file with GZip compression.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> McResult<usize> {
+        let root = self.to_nbt();
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        let size = write_named_tag(&mut encoder, root.tag(), root.name())?;
+        encoder.finish()?.flush()?;
+        Ok(size)
+    }
+
+    /// Decodes the root tag of a litematic file.
+    pub fn from_nbt(nbt: Tag) -> McResult<Self> {
+        let Tag::Compound(mut map) = nbt else {
+            return Err(McError::NbtDecodeError);
+        };
+        let version = map_decoder!(map; "Version" -> i32);
+        if version < 2 {
+            return McError::custom(format!("Unsupported litematic version: {version}"));
+        }
+        let mut metadata = map_decoder!(map; "Metadata" -> Option<Map>).unwrap_or_default();
+        let mut litematic = Self::new(map_decoder!(metadata; "Name" -> Option<String>).unwrap_or_default());
+        litematic.data_version = map_decoder!(map; "MinecraftDataVersion" -> Option<i32>).unwrap_or_default();
+        litematic.author = map_decoder!(metadata; "Author" -> Option<String>).unwrap_or_default();
+        litematic.description = map_decoder!(metadata; "Description" -> Option<String>).unwrap_or_default();
+        litematic.time_created = map_decoder!(metadata; "TimeCreated" -> Option<i64>).unwrap_or_default();
+        litematic.time_modified = map_decoder!(metadata; "TimeModified" -> Option<i64>).unwrap_or_default();
+        for computed in ["RegionCount", "TotalBlocks", "TotalVolume", "EnclosingSize"] {
+            metadata.remove(computed);
+        }
+        litematic.other_metadata = metadata;
+        for (name, region) in map_decoder!(map; "Regions" -> Map) {
+            let Tag::Compound(region) = region else {
+                return Err(McError::NbtDecodeError);
+            };
+            let mut region = decode_region(region)?;
+            region.name = name;
+            region.schematic.data_version = litematic.data_version;
+            litematic.regions.push(region);
+        }
+        Ok(litematic)
+    }
+
+    /// Encodes the litematic as the root tag of a litematic file.
+    pub fn to_nbt(&self) -> NamedTag {
+        let (_, (width, height, length)) = self.enclosing_box();
+        let mut total_blocks = 0i64;
+        let mut total_volume = 0i64;
+        let mut regions = Map::new();
+        for region in self.regions.iter() {
+            let (tag, blocks) = encode_region(region.position, &region.schematic, &region.other);
+            let (x, y, z) = region.schematic.size();
+            total_blocks += blocks;
+            total_volume += x as i64 * y as i64 * z as i64;
+            regions.insert(region.name.clone(), tag);
+        }
+        let mut metadata = self.other_metadata.clone();
+        metadata.extend([
+            ("Name".to_owned(), Tag::string(&self.name)),
+            ("Author".to_owned(), Tag::string(&self.author)),
+            ("Description".to_owned(), Tag::string(&self.description)),
+            ("TimeCreated".to_owned(), Tag::Long(self.time_created)),
+            ("TimeModified".to_owned(), Tag::Long(self.time_modified)),
+            ("RegionCount".to_owned(), Tag::Int(self.regions.len() as i32)),
+            ("TotalBlocks".to_owned(), Tag::Int(total_blocks as i32)),
+            ("TotalVolume".to_owned(), Tag::Int(total_volume as i32)),
+            ("EnclosingSize".to_owned(), vec3(width as i32, height as i32, length as i32)),
+        ]);
+        NamedTag::new(Tag::Compound(Map::from([
+            ("MinecraftDataVersion".to_owned(), Tag::Int(self.data_version)),
+            ("Version".to_owned(), Tag::Int(LITEMATIC_VERSION)),
+            ("SubVersion".to_owned(), Tag::Int(1)),
+            ("Metadata".to_owned(), Tag::Compound(metadata)),
+            ("Regions".to_owned(), Tag::Compound(regions)),
+        ])))
+    }
+}
+
+/// Encodes a schematic as a litematic with a single region, without cloning the schematic.
+pub(crate) fn encode_schematic(schematic: &Schematic) -> NamedTag {
+    let name = match schematic.metadata.get("Name") {
+        Some(Tag::String(name)) => name.clone(),
+        _ => "Unnamed".to_owned(),
+    };
+    let mut litematic = Litematic::new(&name);
+    litematic.data_version = schematic.data_version;
+    if let Some(Tag::String(author)) = schematic.metadata.get("Author") {
+        litematic.author = author.clone();
+    }
+    let mut root = litematic.to_nbt().take_tag();
+    let (tag, blocks) = encode_region((0, 0, 0), schematic, &Map::new());
+    let (width, height, length) = schematic.size();
+    if let Tag::Compound(root) = &mut root {
+        if let Some(Tag::Compound(metadata)) = root.get_mut("Metadata") {
+            metadata.insert("RegionCount".to_owned(), Tag::Int(1));
+            metadata.insert("TotalBlocks".to_owned(), Tag::Int(blocks as i32));
+            metadata.insert("TotalVolume".to_owned(), Tag::Int(width as i32 * height as i32 * length as i32));
+            metadata.insert("EnclosingSize".to_owned(), vec3(width as i32, height as i32, length as i32));
+        }
+        root.insert("Regions".to_owned(), Tag::Compound(Map::from([(name, tag)])));
+    }
+    NamedTag::new(root)
+}
+
+fn vec3(x: i32, y: i32, z: i32) -> Tag {
+    Tag::Compound(Map::from([
+        ("x".to_owned(), Tag::Int(x)),
+        ("y".to_owned(), Tag::Int(y)),
+        ("z".to_owned(), Tag::Int(z)),
+    ]))
+}
+
+fn decode_vec3(tag: Tag) -> McResult<(i32, i32, i32)> {
+    let Tag::Compound(mut map) = tag else {
+        return Err(McError::NbtDecodeError);
+    };
+    Ok((
+        map_decoder!(map; "x" -> i32),
+        map_decoder!(map; "y" -> i32),
+        map_decoder!(map; "z" -> i32),
+    ))
+}
+
+/// The number of bits that each palette index takes in the packed block array.
+fn bits_per_block(palette_len: usize) -> u32 {
+    (usize::BITS - palette_len.saturating_sub(1).leading_zeros()).max(2)
+}
+
+fn decode_region(mut map: Map) -> McResult<LitematicRegion> {
+    let position = decode_vec3(map.remove("Position").ok_or(McError::NotFoundInCompound("Position".to_owned()))?)?;
+    let size = decode_vec3(map.remove("Size").ok_or(McError::NotFoundInCompound("Size".to_owned()))?)?;
+    // A negative size extends from the position in the negative direction.
+    let min_axis = |pos: i32, size: i32| if size < 0 { pos + size + 1 } else { pos };
+    let min = (
+        min_axis(position.0, size.0),
+        min_axis(position.1, size.1),
+        min_axis(position.2, size.2),
+    );
+    let abs_size = (
+        u16::try_from(size.0.unsigned_abs()).map_err(|_| McError::OutOfRange)?,
+        u16::try_from(size.1.unsigned_abs()).map_err(|_| McError::OutOfRange)?,
+        u16::try_from(size.2.unsigned_abs()).map_err(|_| McError::OutOfRange)?,
+    );
+    let mut schematic = Schematic::new(abs_size);
+    let palette = match map_decoder!(map; "BlockStatePalette" -> ListTag) {
+        ListTag::Compound(palette) => palette,
+        ListTag::Empty => Vec::new(),
+        _ => return Err(McError::NbtDecodeError),
+    };
+    let ids = palette.iter()
+        .map(|state| BlockState::try_from_map(state).map(|state| schematic.blocks.block_registry.register(state)))
+        .collect::<McResult<Vec<u32>>>()?;
+    let states = map_decoder!(map; "BlockStates" -> Vec<i64>);
+    let bits = bits_per_block(ids.len());
+    let mask = (1u64 << bits) - 1;
+    if (states.len() as u64 * 64) < schematic.blocks.blocks.len() as u64 * bits as u64 {
+        return Err(McError::NbtDecodeError);
+    }
+    for (index, block) in schematic.blocks.blocks.iter_mut().enumerate() {
+        let start = index as u64 * bits as u64;
+        let long = (start >> 6) as usize;
+        let offset = start & 63;
+        let mut value = (states[long] as u64) >> offset;
+        if offset + bits as u64 > 64 {
+            value |= (states[long + 1] as u64) << (64 - offset);
+        }
+        *block = *ids.get((value & mask) as usize).ok_or(McError::NbtDecodeError)?;
+    }
+    if let Some(ListTag::Compound(block_entities)) = map_decoder!(map; "TileEntities" -> Option<ListTag>) {
+        for mut entity in block_entities {
+            let coord = (
+                map_decoder!(entity; "x" -> i32) as i64,
+                map_decoder!(entity; "y" -> i32) as i64,
+                map_decoder!(entity; "z" -> i32) as i64,
+            );
+            // Older versions of Litematica don't save the id, so it's assumed to be the block's name.
+            let id = match map_decoder!(entity; "id" -> Option<String>) {
+                Some(id) => id,
+                None => schematic.get_block_state(coord.0, coord.1, coord.2)
+                    .map(|state| state.name().to_owned())
+                    .unwrap_or_default(),
+            };
+            schematic.block_entities.push(BlockEntity::new(id, coord, entity));
+        }
+    }
+    if let Some(ListTag::Compound(entities)) = map_decoder!(map; "Entities" -> Option<ListTag>) {
+        // Entity positions are relative to the region's position rather than its minimum corner.
+        let shift = (
+            (position.0 - min.0) as f64,
+            (position.1 - min.1) as f64,
+            (position.2 - min.2) as f64,
+        );
+        schematic.entities = entities.into_iter().map(|entity| {
+            let mut entity = Entity::from_map(entity);
+            if let Some((x, y, z)) = entity.pos() {
+                entity.set_pos((x + shift.0, y + shift.1, z + shift.2));
+            }
+            entity
+        }).collect();
+    }
+    Ok(LitematicRegion {
+        name: String::new(),
+        position: min,
+        schematic,
+        other: map,
+    })
+}
+
+/// Encodes a region, returning the tag and the number of blocks that aren't air.
+fn encode_region(position: (i32, i32, i32), schematic: &Schematic, other: &Map) -> (Tag, i64) {
+    let registry = &schematic.blocks.block_registry;
+    // Litematica expects air to be the first entry of the palette.
+    let air = BlockState::air();
+    let mut palette = vec![air.clone().to_nbt()];
+    let mut indices = HashMap::<u32, u64>::new();
+    let mut non_air = 0i64;
+    let values = schematic.blocks.blocks.iter().map(|&id| {
+        let state = registry.get(id).unwrap_or(&air);
+        if state.name() == "minecraft:air" {
+            return 0;
+        }
+        non_air += 1;
+        *indices.entry(id).or_insert_with(|| {
+            palette.push(state.clone().to_nbt());
+            palette.len() as u64 - 1
+        })
+    }).collect::<Vec<u64>>();
+    let bits = bits_per_block(palette.len()) as u64;
+    let mut states = vec![0u64; (values.len() as u64 * bits).div_ceil(64) as usize];
+    for (index, value) in values.into_iter().enumerate() {
+        let start = index as u64 * bits;
+        let long = (start >> 6) as usize;
+        let offset = start & 63;
+        states[long] |= value << offset;
+        if offset + bits > 64 {
+            states[long + 1] |= value >> (64 - offset);
+        }
+    }
+    let block_entities = schematic.block_entities.iter().map(|entity| {
+        let mut map = entity.data.clone();
+        map.insert("id".to_owned(), Tag::String(entity.id.clone()));
+        map.insert("x".to_owned(), Tag::Int(entity.x));
+        map.insert("y".to_owned(), Tag::Int(entity.y));
+        map.insert("z".to_owned(), Tag::Int(entity.z));
+        map
+    }).collect::<Vec<Map>>();
+    let entities = schematic.entities.iter()
+        .map(|entity| entity.nbt().clone())
+        .collect::<Vec<Map>>();
+    let (width, height, length) = schematic.size();
+    let mut map = other.clone();
+    map.extend([
+        ("Position".to_owned(), vec3(position.0, position.1, position.2)),
+        ("Size".to_owned(), vec3(width as i32, height as i32, length as i32)),
+        ("BlockStatePalette".to_owned(), Tag::List(ListTag::Compound(palette))),
+        ("BlockStates".to_owned(), Tag::LongArray(states.into_iter().map(|long| long as i64).collect())),
+        ("TileEntities".to_owned(), Tag::List(ListTag::Compound(block_entities))),
+        ("Entities".to_owned(), Tag::List(ListTag::Compound(entities))),
+    ]);
+    for ticks in ["PendingBlockTicks", "PendingFluidTicks"] {
+        map.entry(ticks.to_owned()).or_insert(Tag::List(ListTag::Empty));
+    }
+    (Tag::Compound(map), non_air)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn litematic_round_trip_test() {
+        // 5 states need 3 bits per block, so some indices span two longs.
+        let mut schematic = Schematic::new((7, 3, 5));
+        for (i, name) in ["stone", "dirt", "oak_log", "glass"].into_iter().enumerate() {
+            schematic.set_block_state(i as i64, 1, 4, BlockState::from(format!("minecraft:{name}")));
+        }
+        schematic.set_block_state(6, 2, 4, BlockState::from("minecraft:chest"));
+        schematic.block_entities.push(BlockEntity::new("minecraft:chest", (6, 2, 4), Map::new()));
+        let mut litematic = Litematic::from_schematic("Test", schematic);
+        litematic.regions.push(LitematicRegion::new("Other", (-2, 0, 0), Schematic::new((1, 1, 1))));
+        let decoded = Litematic::from_nbt(litematic.to_nbt().take_tag()).unwrap();
+        assert_eq!(decoded.name, "Test");
+        assert_eq!(decoded.enclosing_box(), ((-2, 0, 0), (9, 3, 5)));
+        let region = decoded.regions.iter().find(|region| region.name == "Test").unwrap();
+        assert_eq!(region.schematic.get_block_state(3, 1, 4).map(BlockState::name), Some("minecraft:glass"));
+        assert_eq!(region.schematic.get_block_state(6, 2, 4).map(BlockState::name), Some("minecraft:chest"));
+        assert_eq!(region.schematic.get_block_state(5, 1, 4).map(BlockState::name), Some("minecraft:air"));
+        assert_eq!(region.schematic.block_entities[0].coord(), (6, 2, 4));
+        let merged = decoded.to_schematic();
+        assert_eq!(merged.offset, (-2, 0, 0));
+        assert_eq!(merged.get_block_state(2, 1, 4).map(BlockState::name), Some("minecraft:stone"));
+        assert_eq!(merged.block_entities[0].coord(), (8, 2, 4));
+        let single = Schematic::from_nbt(merged.to_nbt(SchematicFormat::Litematica).unwrap().take_tag()).unwrap();
+        assert_eq!(single.size(), (9, 3, 5));
+        assert_eq!(single.get_block_state(2, 1, 4).map(BlockState::name), Some("minecraft:stone"));
+    }
+
+    #[test]
+    fn negative_size_test() {
+        let mut schematic = Schematic::new((2, 1, 1));
+        schematic.set_block_state(0, 0, 0, BlockState::from("minecraft:stone"));
+        let Tag::Compound(mut region) = encode_region((0, 0, 0), &schematic, &Map::new()).0 else {
+            panic!("Region isn't a compound.");
+        };
+        region.insert("Position".to_owned(), vec3(5, 0, 0));
+        region.insert("Size".to_owned(), vec3(-2, 1, 1));
+        let region = decode_region(region).unwrap();
+        assert_eq!(region.position, (4, 0, 0));
+        assert_eq!(region.schematic.size(), (2, 1, 1));
+    }
+}
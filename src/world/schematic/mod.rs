@@ -0,0 +1,325 @@
+//! Schematics are cuboids of blocks that can be saved to a file, then pasted into a world.
+//!
+//! The following formats are supported:
+//! - Sponge schematics (`.schem`), versions 1 through 3. See [sponge].
+//! - Legacy MCEdit schematics (`.schematic`), which use numeric block ids. See [legacy].
+//! - Litematica schematics (`.litematic`), which may have several regions. See [litematic].
+//!
+//! Biomes aren't read from or written to schematics.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{
+    math::coord::{BlockCoord, WorldCoord},
+    nbt::{
+        io::{read_nbt_auto, write_named_tag},
+        tag::*,
+        Map,
+    },
+    McError, McResult,
+};
+
+use super::{
+    blockstate::BlockState,
+    chunk::BlockEntity,
+    container::BlockContainer,
+    entity::{Entity, EntityChunk},
+    world::VirtualJavaWorld,
+};
+
+macro_rules! map_decoder {
+    ($map:expr; $name:literal -> Option<$type:ty>) => {
+        if let Some(tag) = $map.remove($name) {
+            Some(<$type>::decode_nbt(tag)?)
+        } else {
+            None
+        }
+    };
+    ($map:expr; $name:literal -> $type:ty) => {
+        <$type>::decode_nbt($map.remove($name).ok_or(McError::NotFoundInCompound($name.to_owned()))?)?
+    };
+}
+
+pub mod sponge;
+pub mod legacy;
+pub mod litematic;
+
+/// The file formats that a [Schematic] can be written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SchematicFormat {
+    /// Sponge schematic version 2 (`.schem`), used by WorldEdit for 1.13 through 1.20.
+    SpongeV2,
+    /// Sponge schematic version 3 (`.schem`), used by WorldEdit since 1.20.
+    SpongeV3,
+    /// The MCEdit format (`.schematic`) from before 1.13.
+    McEdit,
+    /// The Litematica format (`.litematic`), written with a single region.
+    Litematica,
+}
+
+/// A cuboid of blocks, along with the block entities and entities within it.
+/// All positions are relative to the minimum corner of the schematic.
+pub struct Schematic {
+    /// The blocks of the schematic. The size of the container is the size of the schematic.
+    pub blocks: BlockContainer,
+    /// The offset of the minimum corner from the point that the schematic was copied relative to.
+    /// This is the `Offset` of Sponge schematics, or the `WEOffset` of MCEdit schematics.
+    /// It isn't applied when pasting.
+    pub offset: (i32, i32, i32),
+    /// The data version of the Minecraft version that the schematic was created with.
+    pub data_version: i32,
+    pub block_entities: Vec<BlockEntity>,
+    pub entities: Vec<Entity>,
+    /// Extra information about the schematic, such as `Name` and `Author`.
+    pub metadata: Map,
+}
+
+impl Schematic {
+    /// Creates a schematic of the given size that is filled with air.
+    pub fn new(size: (u16, u16, u16)) -> Self {
+        Self {
+            blocks: BlockContainer::new(size),
+            offset: (0, 0, 0),
+            data_version: 0,
+            block_entities: Vec::new(),
+            entities: Vec::new(),
+            metadata: Map::new(),
+        }
+    }
+
+    /// The size of the schematic as `(width, height, length)`, which are the sizes along the x, y, and z axes.
+    pub fn size(&self) -> (u16, u16, u16) {
+        self.blocks.size
+    }
+
+    pub fn get_block_state(&self, x: i64, y: i64, z: i64) -> Option<&BlockState> {
+        self.blocks.get_block_state(x, y, z)
+    }
+
+    /// Sets a block, returning the old block. Returns `None` if the position is outside of the schematic.
+    pub fn set_block_state<T: std::borrow::Borrow<BlockState>>(&mut self, x: i64, y: i64, z: i64, state: T) -> Option<BlockState> {
+        self.blocks.set_block_state(x, y, z, state).cloned()
+    }
+
+    /// Reads a schematic file of any of the supported formats.
+    /// The file may be GZip compressed (which is normal), ZLib compressed, or uncompressed.
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Self::from_nbt(read_nbt_auto(&mut reader)?.take_tag())
+    }
+
+    /// Writes the schematic to a file with GZip compression.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P, format: SchematicFormat) -> McResult<usize> {
+        let root = self.to_nbt(format)?;
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        let size = write_named_tag(&mut encoder, root.tag(), root.name())?;
+        encoder.finish()?.flush()?;
+        Ok(size)
+    }
+
+    /// Decodes the root tag of a schematic file, detecting which format it's in.
+    pub fn from_nbt(nbt: Tag) -> McResult<Self> {
+        let Tag::Compound(mut root) = nbt else {
+            return Err(McError::NbtDecodeError);
+        };
+        // Sponge version 3 wraps the schematic in a `Schematic` compound.
+        if let Some(Tag::Compound(schematic)) = root.remove("Schematic") {
+            sponge::decode(schematic)
+        } else if matches!(root.get("Regions"), Some(Tag::Compound(_))) {
+            // The regions of a litematic are merged.
+            litematic::Litematic::from_nbt(Tag::Compound(root)).map(|litematic| litematic.to_schematic())
+        } else if matches!(root.get("Blocks"), Some(Tag::ByteArray(_))) {
+            legacy::decode(root)
+        } else {
+            sponge::decode(root)
+        }
+    }
+
+    /// Encodes the schematic as the root tag of a schematic file.
+    /// Returns an error if the schematic can't be represented in the format.
+    pub fn to_nbt(&self, format: SchematicFormat) -> McResult<NamedTag> {
+        match format {
+            SchematicFormat::SpongeV2 => Ok(NamedTag::with_name("Schematic", sponge::encode(self, 2))),
+            SchematicFormat::SpongeV3 => Ok(NamedTag::new(Tag::Compound(Map::from([
+                ("Schematic".to_owned(), sponge::encode(self, 3)),
+            ])))),
+            SchematicFormat::McEdit => Ok(NamedTag::with_name("Schematic", legacy::encode(self)?)),
+            SchematicFormat::Litematica => Ok(litematic::encode_schematic(self)),
+        }
+    }
+
+    /// Copies the blocks, block entities, and entities from `min` to `max` (inclusive) into a new schematic,
+    /// loading chunks as needed. Blocks above or below the chunks' sections are left as air.
+    pub fn copy_from_world(world: &mut VirtualJavaWorld, min: BlockCoord, max: BlockCoord) -> McResult<Self> {
+        let low = (min.x.min(max.x), min.y.min(max.y), min.z.min(max.z));
+        let high = (min.x.max(max.x), min.y.max(max.y), min.z.max(max.z));
+        let size = (
+            u16::try_from(high.0 - low.0 + 1).map_err(|_| McError::OutOfRange)?,
+            u16::try_from(high.1 - low.1 + 1).map_err(|_| McError::OutOfRange)?,
+            u16::try_from(high.2 - low.2 + 1).map_err(|_| McError::OutOfRange)?,
+        );
+        let mut schematic = Self::new(size);
+        // The world's registry can't be used while its chunks are being edited, so the
+        // world's block ids are copied first and remapped afterwards.
+        let mut world_ids = vec![0u32; schematic.blocks.blocks.len()];
+        let mut data_version = None;
+        let index = |(x, y, z): (i64, i64, i64)| {
+            (((y - low.1) * size.2 as i64 + (z - low.2)) * size.0 as i64 + (x - low.0)) as usize
+        };
+        let mut block_entities = Vec::new();
+        world.edit_chunks_in_box(min, max, |chunk, chunk_low, chunk_high| {
+            data_version.get_or_insert(chunk.data_version);
+            for y in chunk_low.1..=chunk_high.1 {
+                for z in chunk_low.2..=chunk_high.2 {
+                    for x in chunk_low.0..=chunk_high.0 {
+                        if let Some(id) = chunk.get_id((x, y, z)) {
+                            world_ids[index((x, y, z))] = id;
+                        }
+                    }
+                }
+            }
+            block_entities.extend(chunk.block_entities.iter()
+                .filter(|entity| {
+                    let (x, y, z) = entity.coord();
+                    (chunk_low.0..=chunk_high.0).contains(&x)
+                        && (chunk_low.1..=chunk_high.1).contains(&y)
+                        && (chunk_low.2..=chunk_high.2).contains(&z)
+                })
+                .cloned());
+            Ok(false)
+        })?;
+        let mut remap = HashMap::<u32, u32>::new();
+        for (block, world_id) in schematic.blocks.blocks.iter_mut().zip(world_ids) {
+            *block = *remap.entry(world_id).or_insert_with(|| {
+                world.block_registry.get(world_id)
+                    .map_or(0, |state| schematic.blocks.block_registry.register(state))
+            });
+        }
+        schematic.block_entities = block_entities.into_iter().map(|mut entity| {
+            entity.x -= low.0 as i32;
+            entity.y -= low.1 as i32;
+            entity.z -= low.2 as i32;
+            entity
+        }).collect();
+        schematic.entities = world.entities_in_box(min, max)?.into_iter().map(|mut entity| {
+            if let Some((x, y, z)) = entity.pos() {
+                entity.set_pos((x - low.0 as f64, y - low.1 as f64, z - low.2 as f64));
+            }
+            entity
+        }).collect();
+        schematic.data_version = data_version.unwrap_or_default();
+        Ok(schematic)
+    }
+
+    /// Pastes the schematic into a world with its minimum corner at `origin`, loading chunks as needed.
+    /// If `skip_air` is true, air in the schematic doesn't replace blocks in the world.
+    /// Blocks that would be above or below the chunks' sections are skipped.
+    ///
+    /// Entities are added to the world's entity chunks without their `UUID`s so that
+    /// the game gives them new ones.
+    pub fn paste(&self, world: &mut VirtualJavaWorld, origin: BlockCoord, skip_air: bool) -> McResult<()> {
+        let size = self.size();
+        if size.0 == 0 || size.1 == 0 || size.2 == 0 {
+            return Ok(());
+        }
+        let registry = &self.blocks.block_registry;
+        let ids = (0..registry.len() as u32)
+            .map(|id| registry.get(id).map_or(0, |state| world.block_registry.register(state)))
+            .collect::<Vec<u32>>();
+        let air = (0..registry.len() as u32)
+            .map(|id| registry.get(id).is_some_and(|state| state.name() == "minecraft:air"))
+            .collect::<Vec<bool>>();
+        let max = BlockCoord::new(
+            origin.x + size.0 as i64 - 1,
+            origin.y + size.1 as i64 - 1,
+            origin.z + size.2 as i64 - 1,
+            origin.dimension,
+        );
+        world.edit_chunks_in_box(origin, max, |chunk, low, high| {
+            for y in low.1..=high.1 {
+                for z in low.2..=high.2 {
+                    for x in low.0..=high.0 {
+                        let Some(id) = self.blocks.get_block_id(x - origin.x, y - origin.y, z - origin.z) else {
+                            continue;
+                        };
+                        if skip_air && air[id as usize] {
+                            continue;
+                        }
+                        chunk.set_id((x, y, z), ids[id as usize]);
+                        chunk.remove_block_entity((x, y, z));
+                    }
+                }
+            }
+            for entity in self.block_entities.iter() {
+                let mut entity = entity.clone();
+                entity.x += origin.x as i32;
+                entity.y += origin.y as i32;
+                entity.z += origin.z as i32;
+                if chunk.contains_coord(entity.coord()) {
+                    chunk.set_block_entity(entity)?;
+                }
+            }
+            Ok(true)
+        })?;
+        let mut entity_chunks = HashMap::<WorldCoord, Vec<Entity>>::new();
+        for entity in self.entities.iter() {
+            let Some((x, y, z)) = entity.pos() else {
+                continue;
+            };
+            let mut entity = entity.clone();
+            let pos = (x + origin.x as f64, y + origin.y as f64, z + origin.z as f64);
+            entity.set_pos(pos);
+            entity.nbt_mut().remove("UUID");
+            let coord = WorldCoord::new((pos.0.floor() as i64).div_euclid(16), (pos.2.floor() as i64).div_euclid(16), origin.dimension);
+            entity_chunks.entry(coord).or_default().push(entity);
+        }
+        for (coord, entities) in entity_chunks {
+            let mut chunk = world.load_entity_chunk(coord)?
+                .unwrap_or_else(|| EntityChunk::new(self.data_version, coord.x as i32, coord.z as i32));
+            chunk.entities.extend(entities);
+            world.save_entity_chunk(coord, chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::coord::Dimension;
+
+    #[test]
+    fn schematic_paste_and_copy_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        for z in -1..=0 {
+            for x in -1..=0 {
+                let chunk = crate::world::chunk::tests::empty_chunk(x, z);
+                world.chunks.insert(WorldCoord::overworld(x as i64, z as i64), crate::world::world::ChunkSlot::arc_new(chunk));
+            }
+        }
+        let stairs = BlockState::parse("minecraft:oak_stairs[facing=east,half=top]").unwrap();
+        let mut schematic = Schematic::new((3, 2, 3));
+        schematic.set_block_state(0, 0, 0, &stairs);
+        schematic.set_block_state(2, 1, 2, BlockState::from("minecraft:chest"));
+        schematic.block_entities.push(BlockEntity::new("minecraft:chest", (2, 1, 2), Map::new()));
+        schematic.paste(&mut world, BlockCoord::overworld(-1, 4, -1), true).unwrap();
+        assert_eq!(world.get_state(BlockCoord::overworld(-1, 4, -1)), Some(&stairs));
+        let copy = Schematic::copy_from_world(&mut world, BlockCoord::overworld(-1, 4, -1), BlockCoord::new(1, 5, 1, Dimension::Overworld)).unwrap();
+        assert_eq!(copy.size(), (3, 2, 3));
+        assert_eq!(copy.get_block_state(0, 0, 0), Some(&stairs));
+        assert_eq!(copy.get_block_state(2, 1, 2).map(BlockState::name), Some("minecraft:chest"));
+        assert_eq!(copy.get_block_state(1, 1, 1).map(BlockState::name), Some("minecraft:air"));
+        assert_eq!(copy.block_entities.len(), 1);
+        assert_eq!(copy.block_entities[0].coord(), (2, 1, 2));
+        assert_eq!(copy.data_version, 3465);
+    }
+}
@@ -0,0 +1,230 @@
+//! The Sponge schematic format (`.schem`).
+//!
+//! Blocks are stored as a palette of block state strings (such as `minecraft:oak_stairs[facing=east,half=top]`)
+//! and a byte array of palette indices encoded as varints, in YZX order.
+//!
+//! Version 3 moved the palette, block data, and block entities into a `Blocks` compound, and
+//! nests the extra tags of block entities and entities in a `Data` compound.
+//! <https://github.com/SpongePowered/Schematic-Specification>
+
+use std::collections::HashMap;
+
+use super::*;
+
+/// Decodes the `Schematic` compound of a Sponge schematic of any version.
+pub(crate) fn decode(mut map: Map) -> McResult<Schematic> {
+    let version = map_decoder!(map; "Version" -> i32);
+    let size = (
+        map_decoder!(map; "Width" -> i16) as u16,
+        map_decoder!(map; "Height" -> i16) as u16,
+        map_decoder!(map; "Length" -> i16) as u16,
+    );
+    let mut schematic = Schematic::new(size);
+    if let Some(offset) = map_decoder!(map; "Offset" -> Option<Vec<i32>>) {
+        let [x, y, z] = offset.as_slice() else {
+            return Err(McError::NbtDecodeError);
+        };
+        schematic.offset = (*x, *y, *z);
+    }
+    schematic.data_version = map_decoder!(map; "DataVersion" -> Option<i32>).unwrap_or_default();
+    schematic.metadata = map_decoder!(map; "Metadata" -> Option<Map>).unwrap_or_default();
+    let (palette, data, block_entities) = if version >= 3 {
+        match map_decoder!(map; "Blocks" -> Option<Map>) {
+            Some(mut blocks) => (
+                map_decoder!(blocks; "Palette" -> Map),
+                map_decoder!(blocks; "Data" -> Vec<i8>),
+                map_decoder!(blocks; "BlockEntities" -> Option<ListTag>),
+            ),
+            // A schematic without blocks is all air.
+            None => (Map::new(), Vec::new(), None),
+        }
+    } else {
+        let block_entities = match map_decoder!(map; "BlockEntities" -> Option<ListTag>) {
+            Some(block_entities) => Some(block_entities),
+            // Version 1 calls them tile entities.
+            None => map_decoder!(map; "TileEntities" -> Option<ListTag>),
+        };
+        (
+            map_decoder!(map; "Palette" -> Map),
+            map_decoder!(map; "BlockData" -> Vec<i8>),
+            block_entities,
+        )
+    };
+    decode_blocks(&mut schematic, palette, &data)?;
+    for mut entity in compounds(block_entities)? {
+        let pos = map_decoder!(entity; "Pos" -> Vec<i32>);
+        let [x, y, z] = pos.as_slice() else {
+            return Err(McError::NbtDecodeError);
+        };
+        let id = match map_decoder!(entity; "Id" -> Option<String>) {
+            Some(id) => id,
+            None => map_decoder!(entity; "id" -> String),
+        };
+        let data = if version >= 3 {
+            map_decoder!(entity; "Data" -> Option<Map>).unwrap_or_default()
+        } else {
+            entity
+        };
+        schematic.block_entities.push(BlockEntity::new(id, (*x as i64, *y as i64, *z as i64), data));
+    }
+    for mut entity in compounds(map_decoder!(map; "Entities" -> Option<ListTag>))? {
+        let pos = map_decoder!(entity; "Pos" -> ListTag);
+        let id = map_decoder!(entity; "Id" -> String);
+        let mut data = if version >= 3 {
+            map_decoder!(entity; "Data" -> Option<Map>).unwrap_or_default()
+        } else {
+            entity
+        };
+        data.insert("id".to_owned(), Tag::String(id));
+        data.insert("Pos".to_owned(), Tag::List(pos));
+        schematic.entities.push(Entity::from_map(data));
+    }
+    Ok(schematic)
+}
+
+/// Encodes a schematic as the `Schematic` compound of a Sponge schematic.
+/// `version` should be 2 or 3.
+pub(crate) fn encode(schematic: &Schematic, version: i32) -> Tag {
+    let (palette, data) = encode_blocks(schematic);
+    let block_entities = schematic.block_entities.iter().map(|entity| {
+        let mut map = if version >= 3 {
+            Map::from([("Data".to_owned(), Tag::Compound(entity.data.clone()))])
+        } else {
+            entity.data.clone()
+        };
+        map.insert("Pos".to_owned(), Tag::IntArray(vec![entity.x, entity.y, entity.z]));
+        map.insert("Id".to_owned(), Tag::String(entity.id.clone()));
+        map
+    }).collect::<Vec<Map>>();
+    let entities = schematic.entities.iter().filter_map(|entity| {
+        let mut data = entity.nbt().clone();
+        let Some(Tag::String(id)) = data.remove("id") else {
+            return None;
+        };
+        let pos = data.remove("Pos")?;
+        let mut map = if version >= 3 {
+            Map::from([("Data".to_owned(), Tag::Compound(data))])
+        } else {
+            data
+        };
+        map.insert("Pos".to_owned(), pos);
+        map.insert("Id".to_owned(), Tag::String(id));
+        Some(map)
+    }).collect::<Vec<Map>>();
+    let (width, height, length) = schematic.size();
+    let (x, y, z) = schematic.offset;
+    let mut map = Map::from([
+        ("Version".to_owned(), Tag::Int(version)),
+        ("DataVersion".to_owned(), Tag::Int(schematic.data_version)),
+        ("Width".to_owned(), Tag::Short(width as i16)),
+        ("Height".to_owned(), Tag::Short(height as i16)),
+        ("Length".to_owned(), Tag::Short(length as i16)),
+        ("Offset".to_owned(), Tag::IntArray(vec![x, y, z])),
+        ("Metadata".to_owned(), Tag::Compound(schematic.metadata.clone())),
+        ("Entities".to_owned(), Tag::List(ListTag::Compound(entities))),
+    ]);
+    let block_entities = Tag::List(ListTag::Compound(block_entities));
+    if version >= 3 {
+        map.insert("Blocks".to_owned(), Tag::Compound(Map::from([
+            ("Palette".to_owned(), Tag::Compound(palette)),
+            ("Data".to_owned(), Tag::ByteArray(data)),
+            ("BlockEntities".to_owned(), block_entities),
+        ])));
+    } else {
+        map.insert("PaletteMax".to_owned(), Tag::Int(palette.len() as i32));
+        map.insert("Palette".to_owned(), Tag::Compound(palette));
+        map.insert("BlockData".to_owned(), Tag::ByteArray(data));
+        map.insert("BlockEntities".to_owned(), block_entities);
+    }
+    Tag::Compound(map)
+}
+
+fn compounds(list: Option<ListTag>) -> McResult<Vec<Map>> {
+    match list {
+        Some(ListTag::Compound(compounds)) => Ok(compounds),
+        Some(ListTag::Empty) | None => Ok(Vec::new()),
+        Some(_) => Err(McError::NbtDecodeError),
+    }
+}
+
+fn decode_blocks(schematic: &mut Schematic, palette: Map, data: &[i8]) -> McResult<()> {
+    let registry = &mut schematic.blocks.block_registry;
+    let mut ids = HashMap::<i32, u32>::new();
+    for (state, index) in palette {
+        let Tag::Int(index) = index else {
+            return Err(McError::NbtDecodeError);
+        };
+        ids.insert(index, registry.register(BlockState::parse(&state)?));
+    }
+    let mut bytes = data.iter().map(|&byte| byte as u8);
+    for block in schematic.blocks.blocks.iter_mut() {
+        // A varint stores 7 bits per byte, and the high bit is set on all but the last byte.
+        let mut index = 0i32;
+        let mut shift = 0;
+        loop {
+            let Some(byte) = bytes.next() else {
+                return Err(McError::NbtDecodeError);
+            };
+            index |= ((byte & 0x7f) as i32) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift > 28 {
+                return Err(McError::NbtDecodeError);
+            }
+        }
+        *block = *ids.get(&index).ok_or(McError::NbtDecodeError)?;
+    }
+    Ok(())
+}
+
+fn encode_blocks(schematic: &Schematic) -> (Map, Vec<i8>) {
+    let registry = &schematic.blocks.block_registry;
+    let mut indices = HashMap::<u32, i32>::new();
+    let mut palette = Map::new();
+    let mut data = Vec::with_capacity(schematic.blocks.blocks.len());
+    for &id in schematic.blocks.blocks.iter() {
+        let index = *indices.entry(id).or_insert_with(|| {
+            let index = palette.len() as i32;
+            let state = registry.get(id).map_or_else(|| "minecraft:air".to_owned(), BlockState::to_string);
+            palette.insert(state, Tag::Int(index));
+            index
+        });
+        let mut value = index as u32;
+        while value >= 0x80 {
+            data.push(((value & 0x7f) | 0x80) as u8 as i8);
+            value >>= 7;
+        }
+        data.push(value as u8 as i8);
+    }
+    (palette, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sponge_round_trip_test() {
+        let mut schematic = Schematic::new((200, 1, 2));
+        // Enough states to need two byte varints.
+        for x in 0..200 {
+            schematic.set_block_state(x, 0, 1, BlockState::from(format!("minecraft:test_{x}")));
+        }
+        schematic.offset = (-1, 2, -3);
+        schematic.block_entities.push(BlockEntity::new("minecraft:sign", (3, 0, 1), Map::new()));
+        schematic.entities.push(Entity::new("minecraft:pig", (1.5, 0.0, 0.5)));
+        for format in [SchematicFormat::SpongeV2, SchematicFormat::SpongeV3] {
+            let root = schematic.to_nbt(format).unwrap();
+            let decoded = Schematic::from_nbt(root.take_tag()).unwrap();
+            assert_eq!(decoded.size(), (200, 1, 2));
+            assert_eq!(decoded.offset, (-1, 2, -3));
+            assert_eq!(decoded.get_block_state(199, 0, 1).map(BlockState::name), Some("minecraft:test_199"));
+            assert_eq!(decoded.get_block_state(199, 0, 0).map(BlockState::name), Some("minecraft:air"));
+            assert_eq!(decoded.block_entities[0].coord(), (3, 0, 1));
+            assert_eq!(decoded.entities[0].id(), Some("minecraft:pig"));
+            assert_eq!(decoded.entities[0].pos(), Some((1.5, 0.0, 0.5)));
+        }
+    }
+}
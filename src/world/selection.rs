@@ -0,0 +1,345 @@
+//! Selections of blocks that bulk operations on a [VirtualJavaWorld](super::world::VirtualJavaWorld) accept,
+//! such as [fill_selection](super::world::VirtualJavaWorld::fill_selection) and
+//! [prune_chunks](super::world::VirtualJavaWorld::prune_chunks).
+//!
+//! Selections don't have a dimension; the dimension is passed to the operation instead.
+//! They can be combined with [Selection::union] and [Selection::intersection].
+
+use std::collections::BTreeSet;
+
+use glam::{I64Vec3, i64vec3};
+
+use crate::math::bounds::{Bounds2, Bounds3};
+
+/// A set of blocks.
+pub trait Selection {
+    /// Returns true if the block at `(x, y, z)` is selected.
+    fn contains(&self, coord: (i64, i64, i64)) -> bool;
+
+    /// The smallest box that contains every selected block, or `None` if nothing is selected.
+    /// Selections of whole chunk columns extend from `i64::MIN` to `i64::MAX` on the y axis.
+    fn bounds(&self) -> Option<Bounds3>;
+
+    /// Returns true if any block within the chunk column may be selected.
+    fn intersects_chunk(&self, chunk: (i64, i64)) -> bool {
+        self.bounds().is_some_and(|bounds| {
+            (bounds.min.x.div_euclid(16)..=bounds.max.x.div_euclid(16)).contains(&chunk.0)
+                && (bounds.min.z.div_euclid(16)..=bounds.max.z.div_euclid(16)).contains(&chunk.1)
+        })
+    }
+
+    /// The chunk columns that may have selected blocks, sorted by z and then by x.
+    fn chunks(&self) -> Vec<(i64, i64)> {
+        let Some(bounds) = self.bounds() else {
+            return Vec::new();
+        };
+        let mut chunks = Vec::new();
+        for z in bounds.min.z.div_euclid(16)..=bounds.max.z.div_euclid(16) {
+            for x in bounds.min.x.div_euclid(16)..=bounds.max.x.div_euclid(16) {
+                if self.intersects_chunk((x, z)) {
+                    chunks.push((x, z));
+                }
+            }
+        }
+        chunks
+    }
+
+    /// Selects the blocks that are in either selection.
+    fn union<S: Selection>(self, other: S) -> Union<Self, S>
+    where Self: Sized {
+        Union(self, other)
+    }
+
+    /// Selects the blocks that are in both selections.
+    fn intersection<S: Selection>(self, other: S) -> Intersection<Self, S>
+    where Self: Sized {
+        Intersection(self, other)
+    }
+}
+
+impl<T: Selection + ?Sized> Selection for &T {
+    fn contains(&self, coord: (i64, i64, i64)) -> bool {
+        (**self).contains(coord)
+    }
+
+    fn bounds(&self) -> Option<Bounds3> {
+        (**self).bounds()
+    }
+
+    fn intersects_chunk(&self, chunk: (i64, i64)) -> bool {
+        (**self).intersects_chunk(chunk)
+    }
+
+    fn chunks(&self) -> Vec<(i64, i64)> {
+        (**self).chunks()
+    }
+}
+
+/// The squared distance from `center` to the nearest point of the chunk column on the x/z plane.
+fn chunk_distance_squared(center: (i64, i64), chunk: (i64, i64)) -> i64 {
+    let dx = center.0 - center.0.clamp(chunk.0 * 16, chunk.0 * 16 + 15);
+    let dz = center.1 - center.1.clamp(chunk.1 * 16, chunk.1 * 16 + 15);
+    dx * dx + dz * dz
+}
+
+/// Selects the blocks from one corner to another (inclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoxSelection {
+    pub bounds: Bounds3,
+}
+
+impl BoxSelection {
+    pub fn new<T: Into<I64Vec3>>(a: T, b: T) -> Self {
+        Self { bounds: Bounds3::new(a, b) }
+    }
+}
+
+impl From<Bounds3> for BoxSelection {
+    fn from(bounds: Bounds3) -> Self {
+        Self { bounds }
+    }
+}
+
+impl Selection for BoxSelection {
+    fn contains(&self, (x, y, z): (i64, i64, i64)) -> bool {
+        (self.bounds.min.x..=self.bounds.max.x).contains(&x)
+            && (self.bounds.min.y..=self.bounds.max.y).contains(&y)
+            && (self.bounds.min.z..=self.bounds.max.z).contains(&z)
+    }
+
+    fn bounds(&self) -> Option<Bounds3> {
+        Some(self.bounds)
+    }
+}
+
+/// Selects the blocks whose distance from the center block is at most `radius`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SphereSelection {
+    pub center: (i64, i64, i64),
+    pub radius: f64,
+}
+
+impl SphereSelection {
+    pub fn new(center: (i64, i64, i64), radius: f64) -> Self {
+        Self { center, radius }
+    }
+}
+
+impl Selection for SphereSelection {
+    fn contains(&self, (x, y, z): (i64, i64, i64)) -> bool {
+        let (dx, dy, dz) = (x - self.center.0, y - self.center.1, z - self.center.2);
+        ((dx * dx + dy * dy + dz * dz) as f64) <= self.radius * self.radius
+    }
+
+    fn bounds(&self) -> Option<Bounds3> {
+        if self.radius < 0.0 {
+            return None;
+        }
+        let r = self.radius.floor() as i64;
+        let center = i64vec3(self.center.0, self.center.1, self.center.2);
+        Some(Bounds3::new(center - r, center + r))
+    }
+
+    fn intersects_chunk(&self, chunk: (i64, i64)) -> bool {
+        self.radius >= 0.0
+            && chunk_distance_squared((self.center.0, self.center.2), chunk) as f64 <= self.radius * self.radius
+    }
+}
+
+/// Selects the blocks in a vertical cylinder: those from `min_y` to `max_y` (inclusive) whose horizontal
+/// distance from the center column is at most `radius`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CylinderSelection {
+    /// The x and z coordinates of the center column.
+    pub center: (i64, i64),
+    pub radius: f64,
+    pub min_y: i64,
+    pub max_y: i64,
+}
+
+impl CylinderSelection {
+    pub fn new(center: (i64, i64), radius: f64, min_y: i64, max_y: i64) -> Self {
+        Self {
+            center,
+            radius,
+            min_y: min_y.min(max_y),
+            max_y: min_y.max(max_y),
+        }
+    }
+}
+
+impl Selection for CylinderSelection {
+    fn contains(&self, (x, y, z): (i64, i64, i64)) -> bool {
+        let (dx, dz) = (x - self.center.0, z - self.center.1);
+        (self.min_y..=self.max_y).contains(&y)
+            && ((dx * dx + dz * dz) as f64) <= self.radius * self.radius
+    }
+
+    fn bounds(&self) -> Option<Bounds3> {
+        if self.radius < 0.0 {
+            return None;
+        }
+        let r = self.radius.floor() as i64;
+        Some(Bounds3::new(
+            (self.center.0 - r, self.min_y, self.center.1 - r),
+            (self.center.0 + r, self.max_y, self.center.1 + r),
+        ))
+    }
+
+    fn intersects_chunk(&self, chunk: (i64, i64)) -> bool {
+        self.radius >= 0.0
+            && chunk_distance_squared(self.center, chunk) as f64 <= self.radius * self.radius
+    }
+}
+
+/// Selects every block in a set of chunk columns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkSelection {
+    chunks: BTreeSet<(i64, i64)>,
+}
+
+impl ChunkSelection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Selects the chunks from one corner to another (inclusive chunk coordinates).
+    pub fn area<T: Into<Bounds2>>(bounds: T) -> Self {
+        let bounds: Bounds2 = bounds.into();
+        (bounds.min.y..=bounds.max.y)
+            .flat_map(|z| (bounds.min.x..=bounds.max.x).map(move |x| (x, z)))
+            .collect()
+    }
+
+    /// Adds a chunk column, returning false if it was already selected.
+    pub fn insert(&mut self, chunk: (i64, i64)) -> bool {
+        self.chunks.insert(chunk)
+    }
+
+    /// Removes a chunk column, returning false if it wasn't selected.
+    pub fn remove(&mut self, chunk: (i64, i64)) -> bool {
+        self.chunks.remove(&chunk)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+impl FromIterator<(i64, i64)> for ChunkSelection {
+    fn from_iter<T: IntoIterator<Item = (i64, i64)>>(iter: T) -> Self {
+        Self { chunks: iter.into_iter().collect() }
+    }
+}
+
+impl Selection for ChunkSelection {
+    fn contains(&self, (x, _, z): (i64, i64, i64)) -> bool {
+        self.chunks.contains(&(x.div_euclid(16), z.div_euclid(16)))
+    }
+
+    fn bounds(&self) -> Option<Bounds3> {
+        // The chunks are ordered by x, so only the x range is known without looking at every chunk.
+        let min_x = self.chunks.first()?.0;
+        let max_x = self.chunks.last()?.0;
+        let min_z = self.chunks.iter().map(|&(_, z)| z).min()?;
+        let max_z = self.chunks.iter().map(|&(_, z)| z).max()?;
+        Some(Bounds3::new((min_x * 16, i64::MIN, min_z * 16), (max_x * 16 + 15, i64::MAX, max_z * 16 + 15)))
+    }
+
+    fn intersects_chunk(&self, chunk: (i64, i64)) -> bool {
+        self.chunks.contains(&chunk)
+    }
+
+    fn chunks(&self) -> Vec<(i64, i64)> {
+        let mut chunks = self.chunks.iter().copied().collect::<Vec<_>>();
+        chunks.sort_by_key(|&(x, z)| (z, x));
+        chunks
+    }
+}
+
+/// The blocks that are in either of two selections. See [Selection::union].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Union<A, B>(pub A, pub B);
+
+impl<A: Selection, B: Selection> Selection for Union<A, B> {
+    fn contains(&self, coord: (i64, i64, i64)) -> bool {
+        self.0.contains(coord) || self.1.contains(coord)
+    }
+
+    fn bounds(&self) -> Option<Bounds3> {
+        match (self.0.bounds(), self.1.bounds()) {
+            (Some(a), Some(b)) => Some(Bounds3 { min: a.min.min(b.min), max: a.max.max(b.max) }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn intersects_chunk(&self, chunk: (i64, i64)) -> bool {
+        self.0.intersects_chunk(chunk) || self.1.intersects_chunk(chunk)
+    }
+
+    fn chunks(&self) -> Vec<(i64, i64)> {
+        let mut chunks = self.0.chunks();
+        chunks.extend(self.1.chunks());
+        chunks.sort_by_key(|&(x, z)| (z, x));
+        chunks.dedup();
+        chunks
+    }
+}
+
+/// The blocks that are in both of two selections. See [Selection::intersection].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Intersection<A, B>(pub A, pub B);
+
+impl<A: Selection, B: Selection> Selection for Intersection<A, B> {
+    fn contains(&self, coord: (i64, i64, i64)) -> bool {
+        self.0.contains(coord) && self.1.contains(coord)
+    }
+
+    fn bounds(&self) -> Option<Bounds3> {
+        let (a, b) = (self.0.bounds()?, self.1.bounds()?);
+        let (min, max) = (a.min.max(b.min), a.max.min(b.max));
+        (min.cmple(max).all()).then_some(Bounds3 { min, max })
+    }
+
+    fn intersects_chunk(&self, chunk: (i64, i64)) -> bool {
+        self.0.intersects_chunk(chunk) && self.1.intersects_chunk(chunk)
+    }
+
+    fn chunks(&self) -> Vec<(i64, i64)> {
+        if self.bounds().is_none() {
+            return Vec::new();
+        }
+        self.0.chunks()
+            .into_iter()
+            .filter(|&chunk| self.1.intersects_chunk(chunk))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_test() {
+        let sphere = SphereSelection::new((0, 64, 0), 3.0);
+        assert!(sphere.contains((3, 64, 0)) && !sphere.contains((3, 65, 1)));
+        assert_eq!(sphere.chunks(), vec![(-1, -1), (0, -1), (-1, 0), (0, 0)]);
+        let cylinder = CylinderSelection::new((8, 8), 4.0, 70, 60);
+        assert!(cylinder.contains((8, 70, 12)) && !cylinder.contains((8, 71, 12)));
+        assert_eq!(cylinder.chunks(), vec![(0, 0)]);
+        let chunks = ChunkSelection::area(((0, 0), (1, 0)));
+        assert!(chunks.contains((31, -1000, 15)) && !chunks.contains((32, 0, 0)));
+        let both = BoxSelection::new((-5, 0, -5), (40, 10, 5)).intersection(&chunks);
+        assert_eq!(both.chunks(), vec![(0, 0), (1, 0)]);
+        assert_eq!(both.bounds(), Some(Bounds3::new((0, 0, 0), (31, 10, 5))));
+        let either = sphere.union(cylinder);
+        assert!(either.contains((-2, 64, -2)) && either.contains((10, 65, 10)));
+        assert_eq!(either.chunks(), vec![(-1, -1), (0, -1), (-1, 0), (0, 0)]);
+        assert!(BoxSelection::new((0, 0, 0), (1, 1, 1)).intersection(BoxSelection::new((5, 5, 5), (6, 6, 6))).chunks().is_empty());
+    }
+}
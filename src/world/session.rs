@@ -0,0 +1,363 @@
+//! Edit sessions: groups of changes to a world that are saved together or thrown away together.
+//!
+//! An [EditSession] remembers every chunk as it was before the session first changed it. Committing
+//! the session saves its chunks one region file at a time, writing each region to a temporary copy
+//! that then replaces the original, so a crash leaves each region file either as it was or with all
+//! of the session's changes. Rolling back (or dropping the session) restores the remembered chunks.
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    McError, McResult,
+    math::coord::{BlockCoord, WorldCoord},
+    nbt::tag::NamedTag,
+};
+
+use super::{
+    blockstate::BlockState,
+    chunk::Chunk,
+    io::region::RegionFile,
+    world::VirtualJavaWorld,
+};
+
+/// The name of the directory (within a region directory) where regions are written before they replace the originals.
+const TEMP_DIRECTORY: &str = "edit-session.tmp";
+
+/// A block that was changed by an [EditSession].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockChange {
+    pub coord: BlockCoord,
+    /// The state before the change, or `None` if the block wasn't stored (such as in a section without blocks).
+    pub old: Option<BlockState>,
+    pub new: BlockState,
+}
+
+/// A chunk as it was before an [EditSession] changed it.
+struct Original {
+    chunk: Chunk,
+    dirty: bool,
+}
+
+/// The chunks of a world as they were before an [EditSession] changed them.
+/// Saving the record with [UndoRecord::save] creates a chunk set that undoes the session when it's applied
+/// with [VirtualJavaWorld::apply_chunk_set].
+#[derive(Debug, Clone, Default)]
+pub struct UndoRecord {
+    /// The original NBT of each chunk, ordered by coordinate.
+    pub chunks: BTreeMap<WorldCoord, NamedTag>,
+}
+
+impl UndoRecord {
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Writes the record as a chunk set at `out_dir`, laid out like `world`
+    /// (see [VirtualJavaWorld::export_changed_chunks]). Region files in `out_dir` are replaced.
+    pub fn save<P: AsRef<Path>>(&self, world: &VirtualJavaWorld, out_dir: P) -> McResult<()> {
+        let out_dir = out_dir.as_ref();
+        let mut regions = BTreeMap::<WorldCoord, Vec<(WorldCoord, &NamedTag)>>::new();
+        for (coord, chunk) in &self.chunks {
+            regions.entry(coord.region_coord()).or_default().push((*coord, chunk));
+        }
+        for (region_coord, chunks) in regions {
+            let directory = out_dir.join(world.relative_dimension_directory(region_coord.dimension)?).join("region");
+            std::fs::create_dir_all(&directory)?;
+            let path = directory.join(region_coord.region_file_name());
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+            let mut region = RegionFile::create(path)?;
+            for (coord, chunk) in chunks {
+                region.write_data_with_utcnow(coord.xz(), chunk)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A group of changes to a [VirtualJavaWorld] that can be committed or rolled back.
+/// Dropping a session without committing it rolls it back.
+///
+/// While the session is open, the world's chunk limit is lifted so that the session's chunks aren't
+/// saved by being evicted. The limit is restored when the session ends.
+pub struct EditSession<'a> {
+    world: &'a mut VirtualJavaWorld,
+    originals: HashMap<WorldCoord, Original>,
+    changes: Vec<BlockChange>,
+    chunk_limit: Option<usize>,
+    finished: bool,
+}
+
+impl<'a> EditSession<'a> {
+    pub fn new(world: &'a mut VirtualJavaWorld) -> Self {
+        let chunk_limit = world.chunk_limit();
+        // Lifting the limit never evicts chunks, so this can't fail.
+        let _ = world.set_chunk_limit(None);
+        Self {
+            world,
+            originals: HashMap::new(),
+            changes: Vec::new(),
+            chunk_limit,
+            finished: false,
+        }
+    }
+
+    /// The world, for reading. Changes must be made through the session so that they can be rolled back.
+    pub fn world(&self) -> &VirtualJavaWorld {
+        self.world
+    }
+
+    /// The blocks changed with [EditSession::set_block_state], in the order they were changed.
+    pub fn changes(&self) -> &[BlockChange] {
+        &self.changes
+    }
+
+    /// The chunks that the session has changed, ordered by coordinate.
+    pub fn modified_chunks(&self) -> Vec<WorldCoord> {
+        let mut chunks = self.originals.keys().copied().collect::<Vec<_>>();
+        chunks.sort();
+        chunks
+    }
+
+    /// Loads a chunk if it isn't loaded, and remembers it as it is if the session hasn't changed it yet.
+    fn remember_chunk(&mut self, coord: WorldCoord) -> McResult<()> {
+        let slot = self.world.get_or_load_chunk(coord)?;
+        if self.originals.contains_key(&coord) {
+            return Ok(());
+        }
+        let Ok(slot) = slot.lock() else {
+            return McError::custom("Failed to lock chunk.");
+        };
+        self.originals.insert(coord, Original {
+            chunk: slot.chunk.clone(),
+            dirty: slot.dirty,
+        });
+        Ok(())
+    }
+
+    /// Sets the block state at a coordinate, loading the chunk if it isn't loaded.
+    /// Returns the old block state. See [VirtualJavaWorld::set_block_state_loaded].
+    pub fn set_block_state<T: Borrow<BlockState>>(&mut self, coord: BlockCoord, state: T) -> McResult<Option<BlockState>> {
+        let state = state.borrow();
+        self.remember_chunk(coord.chunk_coord())?;
+        let old = self.world.set_block_state_loaded(coord, state)?;
+        if old.as_ref() != Some(state) {
+            self.changes.push(BlockChange {
+                coord,
+                old: old.clone(),
+                new: state.clone(),
+            });
+        }
+        Ok(old)
+    }
+
+    /// Calls `edit` with a chunk, loading it if it isn't loaded, and marks the chunk dirty.
+    /// Changes made this way aren't listed in [EditSession::changes], but they're rolled back with the rest.
+    /// Block states must be registered in the world's registry, which is passed to `edit` along with the chunk.
+    pub fn edit_chunk<R, F>(&mut self, coord: WorldCoord, edit: F) -> McResult<R>
+    where F: FnOnce(&mut Chunk, &mut super::blockregistry::BlockRegistry) -> McResult<R> {
+        self.remember_chunk(coord)?;
+        let Some(slot) = self.world.get_chunk(coord) else {
+            return McError::custom("Chunk was unloaded during the edit session.");
+        };
+        let Ok(mut slot) = slot.lock() else {
+            return McError::custom("Failed to lock chunk.");
+        };
+        slot.mark_dirty();
+        edit(&mut slot.chunk, &mut self.world.block_registry)
+    }
+
+    /// The chunks as they were before the session changed them.
+    pub fn undo_record(&self) -> UndoRecord {
+        let chunks = self.originals.iter()
+            .map(|(coord, original)| (*coord, NamedTag::new(original.chunk.to_nbt(&self.world.block_registry))))
+            .collect();
+        UndoRecord { chunks }
+    }
+
+    /// Saves the chunks that the session changed, one region file at a time, and ends the session.
+    ///
+    /// Each region file is copied into a temporary directory next to it, the chunks are written to the copy,
+    /// and the copy is renamed over the original. If saving a region fails, the regions that were already
+    /// saved keep the changes, the others are left as they were, and the changed chunks stay loaded.
+    pub fn commit(mut self) -> McResult<()> {
+        self.finished = true;
+        let mut regions = BTreeMap::<WorldCoord, Vec<WorldCoord>>::new();
+        for coord in self.originals.keys() {
+            regions.entry(coord.region_coord()).or_default().push(*coord);
+        }
+        let result = regions.into_iter().try_for_each(|(region_coord, chunks)| {
+            self.commit_region(region_coord, &chunks)
+        });
+        self.originals.clear();
+        // The chunk limit is restored even if saving failed.
+        let restored = self.world.set_chunk_limit(self.chunk_limit);
+        result.and(restored)
+    }
+
+    fn commit_region(&mut self, region_coord: WorldCoord, chunks: &[WorldCoord]) -> McResult<()> {
+        let directory = self.world.get_region_directory(region_coord.dimension)?;
+        let temp_directory = directory.join(TEMP_DIRECTORY);
+        std::fs::create_dir_all(&temp_directory)?;
+        let file_name = region_coord.region_file_name();
+        let path = directory.join(&file_name);
+        let temp_path = temp_directory.join(&file_name);
+        let result = self.write_region_copy(&path, &temp_path, chunks)
+            .and_then(|()| move_external_chunks(&temp_directory, &directory))
+            .and_then(|()| Ok(std::fs::rename(&temp_path, &path)?));
+        let _ = std::fs::remove_dir_all(&temp_directory);
+        result?;
+        // The world's handle still refers to the file that was replaced.
+        if let Some(slot) = self.world.regions.get(&region_coord) {
+            let Ok(mut slot) = slot.lock() else {
+                return McError::custom("Failed to lock region.");
+            };
+            slot.region = RegionFile::open(&path)?;
+        }
+        for coord in chunks {
+            if let Some(slot) = self.world.get_chunk(*coord) {
+                if let Ok(mut slot) = slot.lock() {
+                    slot.dirty = false;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the region file at `path` (if it exists) to `temp_path` and writes `chunks` to the copy.
+    fn write_region_copy(&self, path: &Path, temp_path: &Path, chunks: &[WorldCoord]) -> McResult<()> {
+        if path.is_file() {
+            std::fs::copy(path, temp_path)?;
+        }
+        let mut region = RegionFile::open_or_create(temp_path)?;
+        for coord in chunks {
+            let Some(slot) = self.world.get_chunk(*coord) else {
+                return McError::custom("Chunk was unloaded during the edit session.");
+            };
+            let Ok(slot) = slot.lock() else {
+                return McError::custom("Failed to lock chunk.");
+            };
+            let root = NamedTag::new(slot.chunk.to_nbt(&self.world.block_registry));
+            region.write_data_with_utcnow(coord.xz(), &root)?;
+        }
+        Ok(())
+    }
+
+    /// Restores the chunks that the session changed and ends the session.
+    pub fn rollback(mut self) -> McResult<()> {
+        self.restore()
+    }
+
+    fn restore(&mut self) -> McResult<()> {
+        self.finished = true;
+        let mut result = Ok(());
+        for (coord, original) in self.originals.drain() {
+            let Some(slot) = self.world.get_chunk(coord) else {
+                continue;
+            };
+            let Ok(mut slot) = slot.lock() else {
+                result = McError::custom("Failed to lock chunk.");
+                continue;
+            };
+            slot.chunk = original.chunk;
+            slot.dirty = original.dirty;
+        }
+        self.changes.clear();
+        let restored = self.world.set_chunk_limit(self.chunk_limit);
+        result.and(restored)
+    }
+}
+
+impl Drop for EditSession<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.restore();
+        }
+    }
+}
+
+/// Moves the external chunk files (`c.<x>.<z>.mcc`) written next to a temporary region file into the region directory.
+fn move_external_chunks(temp_directory: &Path, directory: &Path) -> McResult<()> {
+    for entry in std::fs::read_dir(temp_directory)? {
+        let path: PathBuf = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "mcc") {
+            if let Some(name) = path.file_name() {
+                std::fs::rename(&path, directory.join(name))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl VirtualJavaWorld {
+    /// Starts an [EditSession] on the world.
+    pub fn edit_session(&mut self) -> EditSession<'_> {
+        EditSession::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::coord::Dimension;
+    use crate::world::{blockstate::BlockProperties, chunk::tests::empty_chunk};
+
+    #[test]
+    fn edit_session_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        {
+            let mut region = RegionFile::create(world.get_region_directory(Dimension::Overworld).unwrap().join("r.0.0.mca")).unwrap();
+            region.write_data((0, 0), &NamedTag::new(empty_chunk(0, 0).to_nbt(&world.block_registry))).unwrap();
+        }
+        let stone = BlockState::new("minecraft:stone", BlockProperties::none());
+        let coord = BlockCoord::overworld(1, 2, 3);
+
+        let mut session = world.edit_session();
+        session.set_block_state(coord, &stone).unwrap();
+        assert_eq!(session.changes().len(), 1);
+        assert_eq!(session.world().get_state(coord).map(BlockState::name), Some("minecraft:stone"));
+        session.rollback().unwrap();
+        assert_ne!(world.get_state(coord).map(BlockState::name), Some("minecraft:stone"));
+
+        let mut session = world.edit_session();
+        session.set_block_state(coord, &stone).unwrap();
+        let undo = session.undo_record();
+        session.commit().unwrap();
+        world.unload_all();
+        world.load_chunk(WorldCoord::overworld(0, 0)).unwrap();
+        assert_eq!(world.get_state(coord).map(BlockState::name), Some("minecraft:stone"));
+        assert!(!world.get_region_directory(Dimension::Overworld).unwrap().join(TEMP_DIRECTORY).exists());
+
+        let undo_dir = dir.path().join("undo");
+        undo.save(&world, &undo_dir).unwrap();
+        assert_eq!(world.apply_chunk_set(&undo_dir).unwrap(), 1);
+        world.load_chunk(WorldCoord::overworld(0, 0)).unwrap();
+        assert_ne!(world.get_state(coord).map(BlockState::name), Some("minecraft:stone"));
+    }
+
+    #[test]
+    fn failed_commit_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        let region_dir = world.get_region_directory(Dimension::Overworld).unwrap();
+        // A directory where the region file should be makes saving the region fail.
+        std::fs::create_dir_all(region_dir.join("r.0.0.mca")).unwrap();
+        world.chunks.insert(WorldCoord::overworld(0, 0), crate::world::world::ChunkSlot::arc_new(empty_chunk(0, 0)));
+        world.set_chunk_limit(Some(3)).unwrap();
+        let mut session = world.edit_session();
+        session.set_block_state(BlockCoord::overworld(1, 2, 3), BlockState::new("minecraft:stone", BlockProperties::none())).unwrap();
+        assert!(session.commit().is_err());
+        assert_eq!(world.chunk_limit(), Some(3));
+    }
+}
@@ -0,0 +1,458 @@
+//! A world that can be shared between threads, so that disjoint chunks can be loaded, edited, and saved
+//! concurrently without an external lock.
+//!
+//! [SharedWorld] splits its loaded chunks and region files into shards, each behind its own lock, so threads
+//! working on different chunks rarely wait for each other. The block registry is a [SharedBlockRegistry],
+//! where looking up states that are already registered only takes a read lock.
+//!
+//! Lock ordering (in addition to the chunk before region order of [VirtualJavaWorld]):
+//! a chunk shard lock is never held while a chunk is locked or loaded from disk, and nothing else
+//! is locked while the block registry is locked.
+//! A region shard lock is held while a region file in that shard is opened, and while its chunk count
+//! is changed, so that a region can't be closed as another thread starts using it. A region shard lock
+//! is never acquired while a chunk or region is locked.
+
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    path::{Path, PathBuf},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{
+    McError, McResult, ResultExt,
+    math::coord::{BlockCoord, Dimension, WorldCoord},
+    nbt::tag::NamedTag,
+};
+
+use super::{
+    blockregistry::BlockRegistry,
+    blockstate::BlockState,
+    chunk::{Chunk, decode_chunk_for_format},
+    dimension::DimensionRegistry,
+    io::region::RegionFile,
+    level::LevelData,
+    lock::WorldLock,
+    world::{ArcChunkSlot, ArcRegionSlot, ChunkSlot, RegionSlot, VirtualJavaWorld},
+};
+
+/// The number of shards that a [SharedWorld] splits its chunks and regions into.
+const SHARD_COUNT: usize = 32;
+
+/// A [BlockRegistry] that can be shared between threads.
+#[derive(Default)]
+pub struct SharedBlockRegistry {
+    registry: RwLock<BlockRegistry>,
+}
+
+impl SharedBlockRegistry {
+    pub fn new(registry: BlockRegistry) -> Self {
+        Self {
+            registry: RwLock::new(registry),
+        }
+    }
+
+    /// Locks the registry for reading.
+    pub fn read(&self) -> McResult<RwLockReadGuard<'_, BlockRegistry>> {
+        self.registry.read().map_err(|_| McError::Custom("Failed to lock block registry.".to_owned()))
+    }
+
+    /// Locks the registry for writing.
+    pub fn write(&self) -> McResult<RwLockWriteGuard<'_, BlockRegistry>> {
+        self.registry.write().map_err(|_| McError::Custom("Failed to lock block registry.".to_owned()))
+    }
+
+    /// Gets the id of a block state, registering it if needed (see [BlockRegistry::try_register]).
+    /// States that are already registered are found without waiting for writers.
+    pub fn try_register<T: Borrow<BlockState>>(&self, state: T) -> McResult<u32> {
+        if let Some(id) = self.read()?.find(state.borrow()) {
+            return Ok(id);
+        }
+        self.write()?.try_register(state)
+    }
+
+    /// Gets a copy of the block state with the given id.
+    pub fn get_owned(&self, id: u32) -> Option<BlockState> {
+        self.read().ok()?.get_owned(id)
+    }
+
+    pub fn into_inner(self) -> BlockRegistry {
+        self.registry.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A map from coordinates to slots that is split into shards with their own locks.
+struct ShardedMap<V> {
+    hasher: RandomState,
+    shards: Box<[RwLock<HashMap<WorldCoord, V>>]>,
+}
+
+impl<V: Clone> ShardedMap<V> {
+    fn new() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            shards: (0..SHARD_COUNT).map(|_| RwLock::default()).collect(),
+        }
+    }
+
+    fn from_map(map: HashMap<WorldCoord, V>) -> Self {
+        let sharded = Self::new();
+        for (coord, value) in map {
+            if let Ok(mut shard) = sharded.shard(coord).write() {
+                shard.insert(coord, value);
+            }
+        }
+        sharded
+    }
+
+    fn shard(&self, coord: WorldCoord) -> &RwLock<HashMap<WorldCoord, V>> {
+        &self.shards[self.hasher.hash_one(coord) as usize % self.shards.len()]
+    }
+
+    fn write(&self, coord: WorldCoord) -> McResult<RwLockWriteGuard<'_, HashMap<WorldCoord, V>>> {
+        self.shard(coord).write().map_err(|_| McError::Custom("Failed to lock shard.".to_owned()))
+    }
+
+    fn get(&self, coord: WorldCoord) -> Option<V> {
+        self.shard(coord).read().ok()?.get(&coord).cloned()
+    }
+
+    fn remove(&self, coord: WorldCoord) -> Option<V> {
+        self.write(coord).ok()?.remove(&coord)
+    }
+
+    /// A snapshot of the coordinates in every shard.
+    fn keys(&self) -> Vec<WorldCoord> {
+        self.shards.iter()
+            .filter_map(|shard| shard.read().ok())
+            .flat_map(|shard| shard.keys().copied().collect::<Vec<_>>())
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.shards.iter()
+            .filter_map(|shard| shard.read().ok())
+            .map(|shard| shard.len())
+            .sum()
+    }
+
+    fn into_map(self) -> HashMap<WorldCoord, V> {
+        self.shards.into_vec()
+            .into_iter()
+            .flat_map(|shard| shard.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()))
+            .collect()
+    }
+}
+
+/// A [VirtualJavaWorld] that can be shared between threads. Every method takes `&self`, so the world can be
+/// used from several threads at once (for example with [std::thread::scope]) to work on different chunks.
+///
+/// Unlike [VirtualJavaWorld], a shared world has no chunk limit. Convert it back with [SharedWorld::into_world]
+/// to use the rest of the world's operations.
+pub struct SharedWorld {
+    pub directory: PathBuf,
+    pub block_registry: SharedBlockRegistry,
+    /// The contents of `level.dat`, if it was loaded.
+    pub level_data: Option<LevelData>,
+    /// The custom dimensions that [Dimension::Other] ids refer to.
+    pub dimensions: DimensionRegistry,
+    chunks: ShardedMap<ArcChunkSlot>,
+    regions: ShardedMap<ArcRegionSlot>,
+    lock: Option<WorldLock>,
+}
+
+impl SharedWorld {
+    /// Opens the world at `directory`, like [VirtualJavaWorld::open].
+    pub fn open(directory: impl AsRef<Path>) -> Self {
+        Self::from(VirtualJavaWorld::open(directory))
+    }
+
+    /// Opens the world at `directory` while holding its `session.lock`, like [VirtualJavaWorld::open_locked].
+    pub fn open_locked(directory: impl AsRef<Path>) -> McResult<Self> {
+        VirtualJavaWorld::open_locked(directory).map(Self::from)
+    }
+
+    /// Turns the shared world back into a [VirtualJavaWorld], keeping the loaded chunks and regions.
+    pub fn into_world(self) -> VirtualJavaWorld {
+        VirtualJavaWorld::from_shared_parts(
+            self.directory,
+            self.block_registry.into_inner(),
+            self.level_data,
+            self.dimensions,
+            self.chunks.into_map(),
+            self.regions.into_map(),
+            self.lock,
+        )
+    }
+
+    /// The directory that the region files of a dimension are in.
+    /// Returns [McError::UnknownDimension] for a [Dimension::Other] that isn't registered.
+    pub fn get_region_directory(&self, dimension: Dimension) -> McResult<PathBuf> {
+        self.dimensions.directory(&self.directory, dimension)
+            .map(|directory| directory.join("region"))
+            .ok_or(McError::UnknownDimension(dimension))
+    }
+
+    /// The number of loaded chunks.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// The coordinates of the loaded chunks, in no particular order.
+    pub fn loaded_chunks(&self) -> Vec<WorldCoord> {
+        self.chunks.keys()
+    }
+
+    /// Gets the region file at a region coordinate, opening (or creating) it if it isn't open.
+    pub fn get_or_load_region(&self, coord: WorldCoord) -> McResult<ArcRegionSlot> {
+        if let Some(slot) = self.regions.get(coord) {
+            return Ok(slot);
+        }
+        let mut shard = self.regions.write(coord)?;
+        self.region_in_shard(&mut shard, coord)
+    }
+
+    /// Gets the region file at a region coordinate from its locked shard, opening (or creating) it if it isn't open.
+    /// Opening under the shard lock keeps two threads from both trying to create the same file.
+    fn region_in_shard(&self, shard: &mut HashMap<WorldCoord, ArcRegionSlot>, coord: WorldCoord) -> McResult<ArcRegionSlot> {
+        if let Some(slot) = shard.get(&coord) {
+            return Ok(slot.clone());
+        }
+        let path = self.get_region_directory(coord.dimension)?.join(coord.region_file_name());
+        let region = RegionFile::open_or_create(&path).with_path(path)?;
+        Ok(shard.entry(coord).or_insert(RegionSlot::arc_new(region)).clone())
+    }
+
+    /// Reads and decodes a chunk from its region file without adding it to the world.
+    fn read_chunk(&self, coord: WorldCoord) -> McResult<Chunk> {
+        let region = self.get_or_load_region(coord.region_coord())?;
+        let (root, format) = {
+            let Ok(mut region) = region.lock() else {
+                return McError::custom("Failed to lock region file.");
+            };
+            (region.region.read_data::<_, NamedTag>(coord.xz())?, region.region.format())
+        };
+        decode_chunk_for_format(&mut *self.block_registry.write()?, root.tag, format).with_chunk(coord)
+    }
+
+    /// Keeps the region of a newly added chunk open while the chunk is loaded.
+    fn count_chunk(&self, coord: WorldCoord) -> McResult<()> {
+        let region_coord = coord.region_coord();
+        // The shard stays locked so that the region can't be closed before it's counted.
+        let mut shard = self.regions.write(region_coord)?;
+        let region = self.region_in_shard(&mut shard, region_coord)?;
+        let Ok(mut region) = region.lock() else {
+            return McError::custom("Failed to lock region file.");
+        };
+        region.increment();
+        Ok(())
+    }
+
+    /// Loads a chunk, replacing it if it was already loaded.
+    pub fn load_chunk(&self, coord: WorldCoord) -> McResult<ArcChunkSlot> {
+        let slot = ChunkSlot::arc_new(self.read_chunk(coord)?);
+        let old = self.chunks.write(coord)?.insert(coord, slot.clone());
+        if old.is_none() {
+            self.count_chunk(coord)?;
+        }
+        Ok(slot)
+    }
+
+    /// Gets a loaded chunk.
+    pub fn get_chunk(&self, coord: WorldCoord) -> Option<ArcChunkSlot> {
+        self.chunks.get(coord)
+    }
+
+    /// Gets a chunk, loading it if it isn't loaded. If two threads load the same chunk at once,
+    /// both get the same slot.
+    pub fn get_or_load_chunk(&self, coord: WorldCoord) -> McResult<ArcChunkSlot> {
+        if let Some(slot) = self.chunks.get(coord) {
+            return Ok(slot);
+        }
+        let loaded = ChunkSlot::arc_new(self.read_chunk(coord)?);
+        let (slot, inserted) = {
+            let mut shard = self.chunks.write(coord)?;
+            match shard.get(&coord) {
+                Some(existing) => (existing.clone(), false),
+                None => {
+                    shard.insert(coord, loaded.clone());
+                    (loaded, true)
+                }
+            }
+        };
+        if inserted {
+            self.count_chunk(coord)?;
+        }
+        Ok(slot)
+    }
+
+    /// Unloads a chunk without saving it. The region file is closed once none of its chunks are loaded.
+    pub fn unload_chunk(&self, coord: WorldCoord) -> Option<ArcChunkSlot> {
+        let removed = self.chunks.remove(coord)?;
+        let region_coord = coord.region_coord();
+        // The shard stays locked from the decrement until the region is removed, so no other
+        // thread can start using the region in between.
+        if let Ok(mut shard) = self.regions.write(region_coord) {
+            let close = shard.get(&region_coord)
+                .and_then(|region| region.lock().ok().map(|mut region| region.decrement()))
+                .unwrap_or(false);
+            if close {
+                shard.remove(&region_coord);
+            }
+        }
+        Some(removed)
+    }
+
+    /// Gets the block state at a coordinate, if the chunk is loaded.
+    pub fn get_block_state(&self, coord: BlockCoord) -> Option<BlockState> {
+        let slot = self.get_chunk(coord.chunk_coord())?;
+        let id = slot.lock().ok()?.chunk.get_id(coord.xyz())?;
+        self.block_registry.get_owned(id)
+    }
+
+    /// Sets the block state at a coordinate, loading the chunk if it isn't loaded.
+    /// The chunk is marked dirty if the block changed. Returns the old block state.
+    pub fn set_block_state<T: Borrow<BlockState>>(&self, coord: BlockCoord, state: T) -> McResult<Option<BlockState>> {
+        let id = self.block_registry.try_register(state)?;
+        let slot = self.get_or_load_chunk(coord.chunk_coord())?;
+        let old_id = {
+            let Ok(mut slot) = slot.lock() else {
+                return McError::custom("Failed to lock chunk.");
+            };
+            let old_id = slot.chunk.set_id(coord.xyz(), id);
+            if old_id != Some(id) {
+                slot.mark_dirty();
+            }
+            old_id
+        };
+        Ok(old_id.and_then(|old_id| self.block_registry.get_owned(old_id)))
+    }
+
+    /// Saves a loaded chunk if it's dirty.
+    pub fn save_chunk(&self, coord: WorldCoord) -> McResult<()> {
+        let Some(slot) = self.get_chunk(coord) else {
+            return Ok(());
+        };
+        let Ok(mut slot) = slot.lock() else {
+            return Err(McError::FailedToSaveChunk);
+        };
+        if !slot.dirty {
+            return Ok(());
+        }
+        let registry = self.block_registry.read()?;
+        let root = NamedTag::new(slot.chunk.to_nbt(&registry));
+        drop(registry);
+        let region = self.get_or_load_region(coord.region_coord())?;
+        let Ok(mut region) = region.lock() else {
+            return Err(McError::FailedToSaveChunk);
+        };
+        region.region.write_data_with_utcnow(coord.xz(), &root)?;
+        slot.dirty = false;
+        Ok(())
+    }
+
+    /// Saves every dirty chunk.
+    pub fn save_dirty(&self) -> McResult<()> {
+        self.chunks.keys()
+            .into_iter()
+            .try_for_each(|coord| self.save_chunk(coord))
+    }
+}
+
+impl From<VirtualJavaWorld> for SharedWorld {
+    fn from(mut world: VirtualJavaWorld) -> Self {
+        let lock = world.take_lock();
+        Self {
+            directory: world.directory,
+            block_registry: SharedBlockRegistry::new(world.block_registry),
+            level_data: world.level_data,
+            dimensions: world.dimensions,
+            chunks: ShardedMap::from_map(world.chunks),
+            regions: ShardedMap::from_map(world.regions),
+            lock,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{blockstate::BlockProperties, chunk::tests::empty_chunk};
+
+    #[test]
+    fn shared_world_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        let mut region = RegionFile::create(world.get_region_directory(Dimension::Overworld).unwrap().join("r.0.0.mca")).unwrap();
+        for x in 0..8 {
+            region.write_data((x, 0), &NamedTag::new(empty_chunk(x, 0).to_nbt(&world.block_registry))).unwrap();
+        }
+        drop(region);
+
+        let shared = SharedWorld::from(world);
+        std::thread::scope(|scope| {
+            for x in 0..8i64 {
+                let shared = &shared;
+                scope.spawn(move || {
+                    let state = BlockState::new("minecraft:stone", BlockProperties::none());
+                    shared.set_block_state(BlockCoord::new(x * 16, 0, 0, Dimension::Overworld), state).unwrap();
+                    // Every thread also loads the first chunk.
+                    shared.get_or_load_chunk(WorldCoord::overworld(0, 0)).unwrap();
+                });
+            }
+        });
+        assert_eq!(shared.chunk_count(), 8);
+        shared.save_dirty().unwrap();
+
+        let mut world = shared.into_world();
+        for x in 0..8 {
+            world.unload_chunk(WorldCoord::overworld(x, 0));
+        }
+        assert!(world.regions.is_empty());
+        world.load_chunk(WorldCoord::overworld(7, 0)).unwrap();
+        let state = world.get_state(BlockCoord::new(7 * 16, 0, 0, Dimension::Overworld)).unwrap();
+        assert_eq!(state.name(), "minecraft:stone");
+    }
+
+    #[test]
+    fn concurrent_region_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        let shared = SharedWorld::from(world);
+        // Every thread tries to create the same region file at once.
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                let shared = &shared;
+                scope.spawn(move || {
+                    shared.get_or_load_region(WorldCoord::overworld(0, 0)).unwrap();
+                });
+            }
+        });
+        {
+            let region = shared.get_or_load_region(WorldCoord::overworld(0, 0)).unwrap();
+            let mut region = region.lock().unwrap();
+            for x in 0..8 {
+                region.region.write_data((x, 0), &NamedTag::new(empty_chunk(x, 0).to_nbt(&shared.block_registry.write().unwrap()))).unwrap();
+            }
+        }
+        shared.regions.remove(WorldCoord::overworld(0, 0));
+        // Loading and unloading chunks of the same region must never leave it closed while a chunk is loaded.
+        std::thread::scope(|scope| {
+            for x in 0..8i64 {
+                let shared = &shared;
+                scope.spawn(move || {
+                    for _ in 0..50 {
+                        shared.get_or_load_chunk(WorldCoord::overworld(x, 0)).unwrap();
+                        assert!(shared.regions.get(WorldCoord::overworld(0, 0)).is_some());
+                        shared.unload_chunk(WorldCoord::overworld(x, 0));
+                    }
+                });
+            }
+        });
+        assert_eq!(shared.chunk_count(), 0);
+        assert_eq!(shared.regions.len(), 0);
+    }
+}
@@ -0,0 +1,340 @@
+//! Statistics about a whole world, such as how many of each block there are
+//! and how much space the region files waste.
+
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use crate::{
+    math::coord::{Dimension, WorldCoord},
+    nbt::tag::{DecodeNbt, NamedTag},
+    CancellationToken, McError, McResult,
+};
+
+use super::{
+    blockstate::BlockState,
+    chunk::{decode_chunk_for_format, Chunk},
+    chunkstatus::ChunkStatus,
+    entity::{Entity, EntityChunk},
+    io::region::{RegionCoord, RegionFile, Timestamp},
+    iter::RegionIter,
+    selection::Selection,
+    world::VirtualJavaWorld,
+};
+
+/// Statistics about a region file in a `region` folder.
+#[derive(Debug, Clone)]
+pub struct RegionStats {
+    /// The coordinate of the region (not of a chunk).
+    pub coord: WorldCoord,
+    /// The size of the file in bytes.
+    pub file_size: u64,
+    /// The number of chunks that have sectors allocated.
+    pub chunk_count: u32,
+    /// The number of 4KiB sectors that are allocated to chunks.
+    pub used_sectors: u64,
+    /// The number of 4KiB sectors after the header that aren't allocated to any chunk.
+    /// These can be reclaimed by [RegionFile::optimize].
+    pub wasted_sectors: u64,
+}
+
+/// Statistics about every chunk in a world, collected by [WorldStats::collect].
+#[derive(Debug, Default)]
+pub struct WorldStats {
+    /// The number of chunks in each dimension.
+    pub chunks: HashMap<Dimension, u64>,
+    /// The number of each block state in all chunk sections.
+    /// Sections that aren't saved aren't counted, but sections that are saved without
+    /// blocks are counted as air.
+    pub blocks: HashMap<BlockState, u64>,
+    /// The number of entities of each id, from entity chunks as well as the `Entities` list of older chunks.
+    pub entities: HashMap<String, u64>,
+    /// Every region file in the `region` folders, in the order that they were read.
+    pub regions: Vec<RegionStats>,
+    /// The earliest time that a chunk was saved.
+    pub oldest_chunk: Option<Timestamp>,
+    /// The latest time that a chunk was saved.
+    pub newest_chunk: Option<Timestamp>,
+    /// Chunks (and entity chunks) that couldn't be read or decoded. They aren't included in the other statistics.
+    /// If a whole region file couldn't be opened, the coordinate is that of the region.
+    pub unreadable_chunks: Vec<(WorldCoord, McError)>,
+}
+
+impl WorldStats {
+    /// Reads every chunk of the Overworld, the Nether, the End, and the custom dimensions in [VirtualJavaWorld::dimensions].
+    ///
+    /// Chunks that are loaded in `world` are counted as they are in memory rather than as
+    /// they are on disk, so unsaved changes are included. Timestamps and region file sizes
+    /// always come from disk. Block states are registered in the world's block registry.
+    pub fn collect(world: &mut VirtualJavaWorld) -> McResult<Self> {
+        Self::collect_cancellable(world, &CancellationToken::new())
+    }
+
+    /// Like [WorldStats::collect], but returns [McError::Cancelled] once `cancel` is cancelled.
+    /// The token is checked before each region file is read.
+    pub fn collect_cancellable(world: &mut VirtualJavaWorld, cancel: &CancellationToken) -> McResult<Self> {
+        Self::collect_filtered(world, None, cancel)
+    }
+
+    /// Like [WorldStats::collect_cancellable], but chunks whose [status](Chunk::status) is lower than
+    /// `min_status` (or unknown) aren't counted, so that [ChunkStatus::Full] skips chunks that haven't
+    /// finished generating. Their blocks aren't counted either, but region statistics still include them.
+    pub fn collect_with_min_status(world: &mut VirtualJavaWorld, min_status: ChunkStatus, cancel: &CancellationToken) -> McResult<Self> {
+        Self::collect_filtered(world, Some(min_status), cancel)
+    }
+
+    fn collect_filtered(world: &mut VirtualJavaWorld, min_status: Option<ChunkStatus>, cancel: &CancellationToken) -> McResult<Self> {
+        let has_status = |chunk: &Chunk| min_status.is_none_or(|min_status| chunk.status().is_some_and(|status| status >= min_status));
+        let mut stats = Self::default();
+        let mut block_ids = HashMap::<u32, u64>::new();
+        for dimension in world.dimensions.dimensions() {
+            let mut seen = HashSet::<WorldCoord>::new();
+            for region_coord in world.iter_regions(dimension)? {
+                cancel.check()?;
+                let path = world.get_region_directory(dimension)?.join(region_coord.region_file_name());
+                let file_size = std::fs::metadata(&path)?.len();
+                let mut region = match RegionFile::open(&path) {
+                    Ok(region) => region,
+                    Err(err) => {
+                        stats.unreadable_chunks.push((region_coord, err));
+                        continue;
+                    }
+                };
+                let mut region_stats = RegionStats {
+                    coord: region_coord,
+                    file_size,
+                    chunk_count: 0,
+                    used_sectors: 0,
+                    wasted_sectors: 0,
+                };
+                let format = region.format();
+                for index in 0..1024u16 {
+                    let coord = RegionCoord::from(index);
+                    let sector = region.get_sector(coord);
+                    if sector.sector_count() == 0 {
+                        continue;
+                    }
+                    region_stats.chunk_count += 1;
+                    region_stats.used_sectors += sector.sector_count();
+                    let timestamp = region.get_timestamp(coord);
+                    if timestamp != Timestamp::default() {
+                        stats.oldest_chunk = Some(stats.oldest_chunk.map_or(timestamp, |oldest| oldest.min(timestamp)));
+                        stats.newest_chunk = Some(stats.newest_chunk.map_or(timestamp, |newest| newest.max(timestamp)));
+                    }
+                    let chunk_coord = region_coord.chunk_in_region(coord);
+                    seen.insert(chunk_coord);
+                    if let Some(slot) = world.get_chunk(chunk_coord) {
+                        let slot = slot.lock().map_err(|_| McError::Custom("Failed to lock chunk.".to_owned()))?;
+                        if has_status(&slot.chunk) {
+                            stats.count_chunk(dimension, &slot.chunk, &mut block_ids);
+                        }
+                        continue;
+                    }
+                    let chunk = region.read_data::<_, NamedTag>(coord)
+                        .and_then(|root| decode_chunk_for_format(&mut world.block_registry, root.take_tag(), format));
+                    match chunk {
+                        Ok(chunk) if has_status(&chunk) => stats.count_chunk(dimension, &chunk, &mut block_ids),
+                        Ok(_) | Err(McError::RegionDataNotFound) => (),
+                        Err(err) => stats.unreadable_chunks.push((chunk_coord, err)),
+                    }
+                }
+                // The first two sectors are the header.
+                region_stats.wasted_sectors = (file_size / 4096).saturating_sub(2 + region_stats.used_sectors);
+                stats.regions.push(region_stats);
+            }
+            // Chunks that have been created in memory but haven't been saved yet.
+            for (coord, slot) in world.chunks.iter() {
+                if coord.dimension != dimension || seen.contains(coord) {
+                    continue;
+                }
+                let slot = slot.lock().map_err(|_| McError::Custom("Failed to lock chunk.".to_owned()))?;
+                if has_status(&slot.chunk) {
+                    stats.count_chunk(dimension, &slot.chunk, &mut block_ids);
+                }
+            }
+            let entities_directory = world.get_entities_directory(dimension)?;
+            for region_coord in RegionIter::new(&entities_directory, dimension)? {
+                cancel.check()?;
+                let path = entities_directory.join(region_coord.region_file_name());
+                let mut region = match RegionFile::open(&path) {
+                    Ok(region) => region,
+                    Err(err) => {
+                        stats.unreadable_chunks.push((region_coord, err));
+                        continue;
+                    }
+                };
+                for index in 0..1024u16 {
+                    let coord = RegionCoord::from(index);
+                    if region.get_sector(coord).sector_count() == 0 {
+                        continue;
+                    }
+                    let chunk = region.read_data::<_, NamedTag>(coord)
+                        .and_then(|root| EntityChunk::decode_nbt(root.take_tag()));
+                    match chunk {
+                        Ok(chunk) => stats.count_entities(chunk.entities.iter()),
+                        Err(McError::RegionDataNotFound) => (),
+                        Err(err) => {
+                            let chunk_coord = region_coord.chunk_in_region(coord);
+                            stats.unreadable_chunks.push((chunk_coord, err));
+                        }
+                    }
+                }
+            }
+        }
+        for (id, count) in block_ids {
+            if let Some(state) = world.block_registry.get(id) {
+                *stats.blocks.entry(state.clone()).or_default() += count;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Like [WorldStats::collect], but only counts the blocks and entities within a [Selection] of one dimension.
+    /// The chunk count and timestamps are of the chunks that the selection intersects, and no region
+    /// statistics are collected. Chunks that aren't loaded are read from disk without being loaded.
+    pub fn collect_selection<S: Selection + ?Sized>(world: &mut VirtualJavaWorld, dimension: Dimension, selection: &S) -> McResult<Self> {
+        let mut stats = Self::default();
+        let mut block_ids = HashMap::<u32, u64>::new();
+        let Some(bounds) = selection.bounds() else {
+            return Ok(stats);
+        };
+        let mut regions = HashMap::<WorldCoord, Option<RegionFile>>::new();
+        for (chunk_x, chunk_z) in selection.chunks() {
+            let chunk_coord = WorldCoord::new(chunk_x, chunk_z, dimension);
+            let region_coord = chunk_coord.region_coord();
+            let region = match regions.entry(region_coord) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let path = world.get_region_directory(dimension)?.join(region_coord.region_file_name());
+                    entry.insert(if path.is_file() { Some(RegionFile::open(path)?) } else { None })
+                }
+            };
+            if let Some(region) = region.as_ref() {
+                let timestamp = region.get_timestamp(chunk_coord.xz());
+                if timestamp != Timestamp::default() {
+                    stats.oldest_chunk = Some(stats.oldest_chunk.map_or(timestamp, |oldest| oldest.min(timestamp)));
+                    stats.newest_chunk = Some(stats.newest_chunk.map_or(timestamp, |newest| newest.max(timestamp)));
+                }
+            }
+            let loaded = world.get_chunk(chunk_coord);
+            let read;
+            let slot;
+            let chunk = if let Some(loaded) = loaded.as_ref() {
+                slot = loaded.lock().map_err(|_| McError::Custom("Failed to lock chunk.".to_owned()))?;
+                &slot.chunk
+            } else {
+                let Some(region) = region.as_mut() else {
+                    continue;
+                };
+                let format = region.format();
+                let chunk = region.read_data::<_, NamedTag>(chunk_coord.xz())
+                    .and_then(|root| decode_chunk_for_format(&mut world.block_registry, root.take_tag(), format));
+                read = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(McError::RegionDataNotFound) => continue,
+                    Err(err) => {
+                        stats.unreadable_chunks.push((chunk_coord, err));
+                        continue;
+                    }
+                };
+                &read
+            };
+            *stats.chunks.entry(dimension).or_default() += 1;
+            let height = chunk.height_range();
+            for y in bounds.min.y.max(height.start)..=bounds.max.y.min(height.end - 1) {
+                for z in chunk_z * 16..chunk_z * 16 + 16 {
+                    for x in chunk_x * 16..chunk_x * 16 + 16 {
+                        if selection.contains((x, y, z)) {
+                            if let Some(id) = chunk.get_id((x, y, z)) {
+                                *block_ids.entry(id).or_default() += 1;
+                            }
+                        }
+                    }
+                }
+            }
+            let in_selection = |entity: &&Entity| entity.block_coord().is_some_and(|coord| selection.contains(coord));
+            stats.count_entities(chunk.get_entities().iter().filter(in_selection));
+            match world.load_entity_chunk(chunk_coord) {
+                Ok(Some(entities)) => stats.count_entities(entities.entities.iter().filter(in_selection)),
+                Ok(None) => (),
+                Err(err) => stats.unreadable_chunks.push((chunk_coord, err)),
+            }
+        }
+        for (id, count) in block_ids {
+            if let Some(state) = world.block_registry.get(id) {
+                *stats.blocks.entry(state.clone()).or_default() += count;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// The number of chunks in every dimension.
+    pub fn total_chunks(&self) -> u64 {
+        self.chunks.values().sum()
+    }
+
+    /// The combined size of every region file in bytes.
+    pub fn total_region_size(&self) -> u64 {
+        self.regions.iter().map(|region| region.file_size).sum()
+    }
+
+    /// The number of unallocated sectors in every region file.
+    pub fn total_wasted_sectors(&self) -> u64 {
+        self.regions.iter().map(|region| region.wasted_sectors).sum()
+    }
+
+    /// Block states sorted by how many there are, most common first.
+    pub fn blocks_by_count(&self) -> Vec<(&BlockState, u64)> {
+        let mut blocks = self.blocks.iter().map(|(state, &count)| (state, count)).collect::<Vec<_>>();
+        blocks.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        blocks
+    }
+
+    fn count_chunk(&mut self, dimension: Dimension, chunk: &Chunk, block_ids: &mut HashMap<u32, u64>) {
+        *self.chunks.entry(dimension).or_default() += 1;
+        for section in chunk.sections.sections.iter() {
+            match &section.blocks {
+                Some(blocks) => blocks.iter().for_each(|&id| *block_ids.entry(id).or_default() += 1),
+                None => *block_ids.entry(0).or_default() += 4096,
+            }
+        }
+        self.count_entities(chunk.get_entities().iter());
+    }
+
+    fn count_entities<'a, It: Iterator<Item = &'a Entity>>(&mut self, entities: It) {
+        for entity in entities {
+            if let Some(id) = entity.id() {
+                *self.entities.entry(id.to_owned()).or_default() += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{chunk::tests::empty_chunk, world::{ChunkSlot, SaveOptions}};
+
+    #[test]
+    fn world_stats_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        for x in 0..2 {
+            world.chunks.insert(WorldCoord::overworld(x, 0), ChunkSlot::arc_new(empty_chunk(x as i32, 0)));
+        }
+        world.set_block_state_loaded(crate::math::coord::BlockCoord::overworld(1, 3, 1), BlockState::from("minecraft:stone")).unwrap();
+        world.save_chunk(WorldCoord::overworld(0, 0), &SaveOptions::new()).unwrap();
+        let mut entities = EntityChunk::new(3465, 0, 0);
+        entities.entities.push(Entity::new("minecraft:pig", (1.0, 2.0, 3.0)));
+        world.save_entity_chunk(WorldCoord::overworld(0, 0), entities).unwrap();
+        let stats = WorldStats::collect(&mut world).unwrap();
+        assert_eq!(stats.chunks.get(&Dimension::Overworld), Some(&2));
+        assert_eq!(stats.total_chunks(), 2);
+        assert_eq!(stats.blocks.get(&BlockState::from("minecraft:stone")), Some(&1));
+        assert_eq!(stats.blocks_by_count()[0].0.name(), "minecraft:air");
+        assert_eq!(stats.entities.get("minecraft:pig"), Some(&1));
+        assert_eq!(stats.regions.len(), 1);
+        assert_eq!(stats.regions[0].chunk_count, 1);
+        assert!(stats.oldest_chunk.is_some());
+        assert!(stats.unreadable_chunks.is_empty());
+    }
+}
@@ -0,0 +1,437 @@
+//! Structure templates: the `.nbt` files that structure blocks save and load, and that data packs use
+//! for jigsaw structures.
+//!
+//! Unlike [schematics](super::schematic), a template only lists the blocks that it places, so positions that held
+//! structure void when the template was saved are left alone when it's placed. A template may have several
+//! palettes (such as the shipwreck variants), of which one is chosen when the template is placed.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{
+    math::coord::{BlockCoord, WorldCoord},
+    nbt::{
+        io::{read_nbt_auto, write_named_tag},
+        tag::*,
+        Map,
+    },
+    McError, McResult,
+};
+
+use super::{
+    blockstate::BlockState,
+    chunk::BlockEntity,
+    entity::{Entity, EntityChunk},
+    schematic::Schematic,
+    world::VirtualJavaWorld,
+};
+
+/// The block that marks positions that a template doesn't place a block at.
+const STRUCTURE_VOID: &str = "minecraft:structure_void";
+
+/// A block placed by a [StructureTemplate].
+#[derive(Debug, Clone)]
+pub struct StructureBlock {
+    /// pos, relative to the template's minimum corner.
+    pub pos: (i32, i32, i32),
+    /// state: the index of the block state in the palette.
+    pub state: u32,
+    /// nbt: the block entity's tags, without its position.
+    pub nbt: Option<Map>,
+}
+
+/// An entity placed by a [StructureTemplate].
+#[derive(Debug, Clone)]
+pub struct StructureEntity {
+    /// pos, relative to the template's minimum corner.
+    pub pos: (f64, f64, f64),
+    /// blockPos: the block that the entity is in, relative to the template's minimum corner.
+    pub block_pos: (i32, i32, i32),
+    /// nbt: the entity's tags. Its position and `UUID` are replaced when it's placed.
+    pub nbt: Map,
+}
+
+/// A structure block template.
+#[derive(Debug, Clone)]
+pub struct StructureTemplate {
+    /// DataVersion
+    pub data_version: i32,
+    /// size as `(x, y, z)`
+    pub size: (i32, i32, i32),
+    /// `palette`, or `palettes` for templates with more than one. Every template has at least one palette,
+    /// and every palette has the same length.
+    pub palettes: Vec<Vec<BlockState>>,
+    pub blocks: Vec<StructureBlock>,
+    pub entities: Vec<StructureEntity>,
+    /// The rest of the root compound, such as `author` in old templates.
+    pub other: Map,
+}
+
+fn int_triple(tag: Option<Tag>, key: &str) -> McResult<(i32, i32, i32)> {
+    match tag {
+        Some(Tag::List(ListTag::Int(values))) if values.len() == 3 => Ok((values[0], values[1], values[2])),
+        _ => Err(McError::NotFoundInCompound(key.to_owned())),
+    }
+}
+
+fn decode_palette(tag: Tag) -> McResult<Vec<BlockState>> {
+    match tag {
+        Tag::List(ListTag::Compound(states)) => states.iter().map(BlockState::try_from_map).collect(),
+        Tag::List(ListTag::Empty) => Ok(Vec::new()),
+        _ => Err(McError::NbtDecodeError),
+    }
+}
+
+fn encode_palette(palette: &[BlockState]) -> Tag {
+    Tag::List(ListTag::Compound(palette.iter().map(|state| {
+        let mut map = state.clone().to_nbt();
+        // Vanilla leaves out the properties of blocks that don't have any.
+        if matches!(map.get("Properties"), Some(Tag::Compound(properties)) if properties.is_empty()) {
+            map.remove("Properties");
+        }
+        map
+    }).collect()))
+}
+
+impl StructureTemplate {
+    /// Creates an empty template of the given size.
+    pub fn new(size: (i32, i32, i32)) -> Self {
+        Self {
+            data_version: 0,
+            size,
+            palettes: vec![Vec::new()],
+            blocks: Vec::new(),
+            entities: Vec::new(),
+            other: Map::new(),
+        }
+    }
+
+    /// The first palette, which is the only palette of most templates.
+    pub fn palette(&self) -> &[BlockState] {
+        self.palettes.first().map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds a block to the template, adding its state to the palette if needed.
+    /// If the template has more than one palette, the state is added to all of them.
+    /// A block that was already at `pos` is replaced.
+    pub fn add_block(&mut self, pos: (i32, i32, i32), state: &BlockState, nbt: Option<Map>) {
+        let state = self.palette_index(state);
+        self.blocks.retain(|block| block.pos != pos);
+        self.blocks.push(StructureBlock { pos, state, nbt });
+    }
+
+    fn palette_index(&mut self, state: &BlockState) -> u32 {
+        match self.palette().iter().position(|existing| existing == state) {
+            Some(index) => index as u32,
+            None => {
+                for palette in self.palettes.iter_mut() {
+                    palette.push(state.clone());
+                }
+                (self.palette().len() - 1) as u32
+            }
+        }
+    }
+
+    /// Reads a template from a file. Structure block templates are normally GZip compressed.
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> McResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Self::decode_nbt(read_nbt_auto(&mut reader)?.take_tag())
+    }
+
+    /// Writes the template to a file with GZip compression.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> McResult<usize> {
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        let size = write_named_tag(&mut encoder, &self.encode_nbt(), "")?;
+        encoder.finish()?.flush()?;
+        Ok(size)
+    }
+
+    pub fn decode_nbt(nbt: Tag) -> McResult<Self> {
+        let Tag::Compound(mut map) = nbt else {
+            return Err(McError::NbtDecodeError);
+        };
+        let data_version = match map.remove("DataVersion") {
+            Some(Tag::Int(version)) => version,
+            _ => 0,
+        };
+        let size = int_triple(map.remove("size"), "size")?;
+        let palettes = match (map.remove("palette"), map.remove("palettes")) {
+            (Some(palette), _) => vec![decode_palette(palette)?],
+            (None, Some(Tag::List(ListTag::List(palettes)))) => palettes.into_iter()
+                .map(|palette| decode_palette(Tag::List(palette)))
+                .collect::<McResult<Vec<_>>>()?,
+            _ => return Err(McError::NotFoundInCompound("palette".to_owned())),
+        };
+        let blocks = match map.remove("blocks") {
+            Some(Tag::List(ListTag::Compound(blocks))) => blocks.into_iter().map(|mut block| {
+                let pos = int_triple(block.remove("pos"), "pos")?;
+                let Some(Tag::Int(state)) = block.remove("state") else {
+                    return Err(McError::NotFoundInCompound("state".to_owned()));
+                };
+                let nbt = match block.remove("nbt") {
+                    Some(Tag::Compound(nbt)) => Some(nbt),
+                    _ => None,
+                };
+                Ok(StructureBlock { pos, state: state as u32, nbt })
+            }).collect::<McResult<Vec<_>>>()?,
+            _ => Vec::new(),
+        };
+        let entities = match map.remove("entities") {
+            Some(Tag::List(ListTag::Compound(entities))) => entities.into_iter().map(|mut entity| {
+                let pos = match entity.remove("pos") {
+                    Some(Tag::List(ListTag::Double(pos))) if pos.len() == 3 => (pos[0], pos[1], pos[2]),
+                    _ => return Err(McError::NotFoundInCompound("pos".to_owned())),
+                };
+                let block_pos = int_triple(entity.remove("blockPos"), "blockPos")?;
+                let nbt = match entity.remove("nbt") {
+                    Some(Tag::Compound(nbt)) => nbt,
+                    _ => Map::new(),
+                };
+                Ok(StructureEntity { pos, block_pos, nbt })
+            }).collect::<McResult<Vec<_>>>()?,
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            data_version,
+            size,
+            palettes,
+            blocks,
+            entities,
+            other: map,
+        })
+    }
+
+    pub fn encode_nbt(&self) -> Tag {
+        let ints = |(x, y, z): (i32, i32, i32)| Tag::List(ListTag::Int(vec![x, y, z]));
+        let mut map = self.other.clone();
+        map.insert("DataVersion".to_owned(), Tag::Int(self.data_version));
+        map.insert("size".to_owned(), ints(self.size));
+        if let [palette] = self.palettes.as_slice() {
+            map.insert("palette".to_owned(), encode_palette(palette));
+        } else {
+            let palettes = self.palettes.iter().map(|palette| match encode_palette(palette) {
+                Tag::List(list) => list,
+                _ => unreachable!("Palettes are encoded as lists."),
+            }).collect();
+            map.insert("palettes".to_owned(), Tag::List(ListTag::List(palettes)));
+        }
+        map.insert("blocks".to_owned(), Tag::List(ListTag::Compound(self.blocks.iter().map(|block| {
+            let mut compound = Map::from([
+                ("pos".to_owned(), ints(block.pos)),
+                ("state".to_owned(), Tag::Int(block.state as i32)),
+            ]);
+            if let Some(nbt) = &block.nbt {
+                compound.insert("nbt".to_owned(), Tag::Compound(nbt.clone()));
+            }
+            compound
+        }).collect())));
+        map.insert("entities".to_owned(), Tag::List(ListTag::Compound(self.entities.iter().map(|entity| Map::from([
+            ("pos".to_owned(), Tag::List(ListTag::Double(vec![entity.pos.0, entity.pos.1, entity.pos.2]))),
+            ("blockPos".to_owned(), ints(entity.block_pos)),
+            ("nbt".to_owned(), Tag::Compound(entity.nbt.clone())),
+        ])).collect())));
+        Tag::Compound(map)
+    }
+
+    /// Creates a template from a schematic. Structure void in the schematic isn't placed by the template.
+    pub fn from_schematic(schematic: &Schematic) -> McResult<Self> {
+        let (width, height, length) = schematic.size();
+        let mut template = Self::new((width as i32, height as i32, length as i32));
+        template.data_version = schematic.data_version;
+        let block_entities = schematic.block_entities.iter()
+            .map(|entity| (entity.coord(), entity))
+            .collect::<HashMap<_, _>>();
+        for y in 0..height as i64 {
+            for z in 0..length as i64 {
+                for x in 0..width as i64 {
+                    let Some(state) = schematic.get_block_state(x, y, z) else {
+                        continue;
+                    };
+                    if state.name() == STRUCTURE_VOID {
+                        continue;
+                    }
+                    let nbt = block_entities.get(&(x, y, z)).map(|entity| {
+                        let mut nbt = (*entity).clone().to_map();
+                        for key in ["x", "y", "z"] {
+                            nbt.remove(key);
+                        }
+                        nbt
+                    });
+                    let state = template.palette_index(state);
+                    template.blocks.push(StructureBlock { pos: (x as i32, y as i32, z as i32), state, nbt });
+                }
+            }
+        }
+        template.entities = schematic.entities.iter().filter_map(|entity| {
+            let pos = entity.pos()?;
+            let mut nbt = entity.nbt().clone();
+            nbt.remove("UUID");
+            let block_pos = (pos.0.floor() as i32, pos.1.floor() as i32, pos.2.floor() as i32);
+            Some(StructureEntity { pos, block_pos, nbt })
+        }).collect();
+        Ok(template)
+    }
+
+    /// Creates a schematic from one of the template's palettes. Positions that the template doesn't place
+    /// a block at are filled with structure void.
+    pub fn to_schematic(&self, palette_index: usize) -> McResult<Schematic> {
+        let palette = self.palettes.get(palette_index).ok_or(McError::OutOfRange)?;
+        let size = |value: i32| u16::try_from(value).map_err(|_| McError::OutOfRange);
+        let mut schematic = Schematic::new((size(self.size.0)?, size(self.size.1)?, size(self.size.2)?));
+        schematic.data_version = self.data_version;
+        let void = BlockState::from(STRUCTURE_VOID);
+        for y in 0..self.size.1 as i64 {
+            for z in 0..self.size.2 as i64 {
+                for x in 0..self.size.0 as i64 {
+                    schematic.set_block_state(x, y, z, &void);
+                }
+            }
+        }
+        for block in self.blocks.iter() {
+            let state = palette.get(block.state as usize).ok_or(McError::OutOfRange)?;
+            let (x, y, z) = block.pos;
+            schematic.set_block_state(x as i64, y as i64, z as i64, state);
+            if let Some(nbt) = &block.nbt {
+                schematic.block_entities.push(block_entity_at(nbt, (x as i64, y as i64, z as i64))?);
+            }
+        }
+        schematic.entities = self.entities.iter().map(|entity| {
+            let mut placed = Entity::from_map(entity.nbt.clone());
+            placed.set_pos(entity.pos);
+            placed
+        }).collect();
+        Ok(schematic)
+    }
+
+    /// Copies the blocks, block entities, and entities from `min` to `max` (inclusive) into a new template,
+    /// loading chunks as needed. Structure void in the world isn't placed by the template.
+    pub fn copy_from_world(world: &mut VirtualJavaWorld, min: BlockCoord, max: BlockCoord) -> McResult<Self> {
+        Self::from_schematic(&Schematic::copy_from_world(world, min, max)?)
+    }
+
+    /// Places the template into a world with its minimum corner at `origin`, using one of its palettes.
+    /// Chunks are loaded as needed, and blocks that would be above or below the chunks' sections are skipped.
+    ///
+    /// Entities are added to the world's entity chunks without their `UUID`s so that the game gives them new ones.
+    pub fn paste(&self, world: &mut VirtualJavaWorld, origin: BlockCoord, palette_index: usize) -> McResult<()> {
+        let palette = self.palettes.get(palette_index).ok_or(McError::OutOfRange)?;
+        if self.size.0 <= 0 || self.size.1 <= 0 || self.size.2 <= 0 {
+            return Ok(());
+        }
+        let ids = palette.iter().map(|state| world.block_registry.register(state)).collect::<Vec<u32>>();
+        let mut by_chunk = HashMap::<(i64, i64), Vec<&StructureBlock>>::new();
+        for block in self.blocks.iter() {
+            let x = origin.x + block.pos.0 as i64;
+            let z = origin.z + block.pos.2 as i64;
+            by_chunk.entry((x.div_euclid(16), z.div_euclid(16))).or_default().push(block);
+        }
+        let max = BlockCoord::new(
+            origin.x + self.size.0 as i64 - 1,
+            origin.y + self.size.1 as i64 - 1,
+            origin.z + self.size.2 as i64 - 1,
+            origin.dimension,
+        );
+        world.edit_chunks_in_box(origin, max, |chunk, low, high| {
+            let Some(blocks) = by_chunk.get(&(chunk.x as i64, chunk.z as i64)) else {
+                return Ok(false);
+            };
+            for block in blocks {
+                let coord = (origin.x + block.pos.0 as i64, origin.y + block.pos.1 as i64, origin.z + block.pos.2 as i64);
+                if coord.1 < low.1 || coord.1 > high.1 {
+                    continue;
+                }
+                let id = *ids.get(block.state as usize).ok_or(McError::OutOfRange)?;
+                chunk.set_id(coord, id);
+                chunk.remove_block_entity(coord);
+                if let Some(nbt) = &block.nbt {
+                    chunk.set_block_entity(block_entity_at(nbt, coord)?)?;
+                }
+            }
+            Ok(true)
+        })?;
+        let mut entity_chunks = HashMap::<WorldCoord, Vec<Entity>>::new();
+        for entity in self.entities.iter() {
+            let pos = (entity.pos.0 + origin.x as f64, entity.pos.1 + origin.y as f64, entity.pos.2 + origin.z as f64);
+            let mut placed = Entity::from_map(entity.nbt.clone());
+            placed.set_pos(pos);
+            placed.nbt_mut().remove("UUID");
+            let coord = WorldCoord::new((pos.0.floor() as i64).div_euclid(16), (pos.2.floor() as i64).div_euclid(16), origin.dimension);
+            entity_chunks.entry(coord).or_default().push(placed);
+        }
+        for (coord, entities) in entity_chunks {
+            let mut chunk = world.load_entity_chunk(coord)?
+                .unwrap_or_else(|| EntityChunk::new(self.data_version, coord.x as i32, coord.z as i32));
+            chunk.entities.extend(entities);
+            world.save_entity_chunk(coord, chunk)?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates a block entity from a template's block `nbt` at a block coordinate.
+fn block_entity_at(nbt: &Map, coord: (i64, i64, i64)) -> McResult<BlockEntity> {
+    let mut map = nbt.clone();
+    map.insert("x".to_owned(), Tag::Int(coord.0 as i32));
+    map.insert("y".to_owned(), Tag::Int(coord.1 as i32));
+    map.insert("z".to_owned(), Tag::Int(coord.2 as i32));
+    map.entry("keepPacked".to_owned()).or_insert(Tag::Byte(0));
+    BlockEntity::try_from_map(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{chunk::tests::empty_chunk, world::ChunkSlot};
+
+    #[test]
+    fn structure_template_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        world.chunks.insert(WorldCoord::overworld(0, 0), ChunkSlot::arc_new(empty_chunk(0, 0)));
+        let stone = BlockState::from("minecraft:stone");
+        world.set_state(BlockCoord::overworld(2, 0, 2), &stone);
+        world.set_state(BlockCoord::overworld(2, 1, 2), &stone);
+
+        let mut template = StructureTemplate::new((2, 2, 1));
+        template.data_version = 3465;
+        template.add_block((0, 0, 0), &BlockState::from("minecraft:chest"), Some(Map::from([
+            ("id".to_owned(), Tag::String("minecraft:chest".to_owned())),
+            ("Items".to_owned(), Tag::List(ListTag::Empty)),
+        ])));
+        template.add_block((1, 1, 0), &BlockState::from("minecraft:air"), None);
+        template.entities.push(StructureEntity {
+            pos: (0.5, 1.0, 0.5),
+            block_pos: (0, 1, 0),
+            nbt: Entity::new("minecraft:armor_stand", (0.0, 0.0, 0.0)).into_map(),
+        });
+        let path = dir.path().join("house.nbt");
+        template.write_to_file(&path).unwrap();
+        let template = StructureTemplate::read_from_file(&path).unwrap();
+        assert_eq!(template.size, (2, 2, 1));
+        assert_eq!(template.palette().len(), 2);
+        assert!(template.blocks[0].nbt.is_some());
+
+        // (2, 0, 2) isn't placed by the template, so the stone stays, while the air replaces the stone above it.
+        template.paste(&mut world, BlockCoord::overworld(1, 0, 2), 0).unwrap();
+        assert_eq!(world.get_state(BlockCoord::overworld(1, 0, 2)).map(BlockState::name), Some("minecraft:chest"));
+        assert_eq!(world.get_state(BlockCoord::overworld(2, 0, 2)), Some(&stone));
+        assert_eq!(world.get_state(BlockCoord::overworld(2, 1, 2)).map(BlockState::name), Some("minecraft:air"));
+        let entities = world.load_entity_chunk(WorldCoord::overworld(0, 0)).unwrap().unwrap().entities;
+        assert_eq!(entities[0].pos(), Some((1.5, 1.0, 2.5)));
+
+        world.set_state(BlockCoord::overworld(2, 0, 2), BlockState::from(STRUCTURE_VOID));
+        let copy = StructureTemplate::copy_from_world(&mut world, BlockCoord::overworld(1, 0, 2), BlockCoord::overworld(2, 1, 2)).unwrap();
+        assert_eq!(copy.blocks.len(), 3);
+        let chest = copy.blocks.iter().find(|block| block.pos == (0, 0, 0)).unwrap();
+        assert!(chest.nbt.as_ref().is_some_and(|nbt| nbt.contains_key("Items") && !nbt.contains_key("x")));
+        assert_eq!(copy.entities.len(), 1);
+        assert_eq!(copy.to_schematic(0).unwrap().get_block_state(1, 0, 0).map(BlockState::name), Some(STRUCTURE_VOID));
+    }
+}
@@ -0,0 +1,203 @@
+//! The structures that chunks record in their `structures` compound: the structure starts, which
+//! describe every piece of a generated structure, and the references that point from every chunk that
+//! a structure overlaps to the chunk that it starts in.
+//!
+//! Structure names changed over time (`Village` in 1.13, `village` in 1.16, and `minecraft:village_plains`
+//! since 1.18.2), so names are compared with [structure_matches].
+
+use glam::i64vec3;
+
+use crate::{
+    math::bounds::Bounds3,
+    nbt::{tag::{ListTag, Tag}, Map},
+};
+
+/// A piece of a structure, such as a single house of a village.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructurePiece {
+    /// The id of the piece, such as `minecraft:jigsaw`. Empty if the piece doesn't have one.
+    pub id: String,
+    /// The blocks that the piece occupies.
+    pub bounds: Bounds3,
+}
+
+/// A structure that starts in a chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructureStart {
+    /// The name of the structure, which is its key in the `starts` compound.
+    pub name: String,
+    /// The chunk that the structure starts in.
+    pub chunk: (i32, i32),
+    /// The blocks that the structure occupies: the union of the bounds of its pieces.
+    pub bounds: Bounds3,
+    pub pieces: Vec<StructurePiece>,
+}
+
+/// The structure starts and references of a chunk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkStructures {
+    /// The structures that start in the chunk. Starts that didn't generate a structure (`INVALID` starts) are skipped.
+    pub starts: Vec<StructureStart>,
+    /// For each structure name, the chunks that structures of that name which overlap this chunk start in.
+    pub references: Vec<(String, Vec<(i32, i32)>)>,
+}
+
+impl ChunkStructures {
+    /// Decodes the `structures` compound of a 1.18+ chunk, or the `Structures` compound of an older one.
+    /// Anything that can't be decoded is skipped.
+    pub fn from_map(structures: &Map) -> Self {
+        let starts = match structures.get("starts").or_else(|| structures.get("Starts")) {
+            Some(Tag::Compound(starts)) => starts.iter()
+                .filter_map(|(name, start)| match start {
+                    Tag::Compound(start) => decode_start(name, start),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let references = match structures.get("References") {
+            Some(Tag::Compound(references)) => references.iter()
+                .filter_map(|(name, chunks)| match chunks {
+                    Tag::LongArray(chunks) => Some((name.clone(), chunks.iter().map(|&packed| unpack_chunk(packed)).collect())),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Self {
+            starts,
+            references,
+        }
+    }
+
+    /// Reads the structures from chunk NBT of either layout, without decoding the chunk.
+    pub fn from_chunk_nbt(nbt: &Tag) -> Self {
+        let Tag::Compound(root) = nbt else {
+            return Self::default();
+        };
+        let root = match root.get("Level") {
+            Some(Tag::Compound(level)) => level,
+            _ => root,
+        };
+        match root.get("structures").or_else(|| root.get("Structures")) {
+            Some(Tag::Compound(structures)) => Self::from_map(structures),
+            _ => Self::default(),
+        }
+    }
+}
+
+/// Returns true if the structure name `name` is of the kind `kind`. The `minecraft:` namespace and case are ignored,
+/// and a kind also matches its variants, so `village` matches `minecraft:village_plains` as well as `Village`.
+pub fn structure_matches(name: &str, kind: &str) -> bool {
+    let normalize = |name: &str| name.strip_prefix("minecraft:").unwrap_or(name).to_ascii_lowercase();
+    let (name, kind) = (normalize(name), normalize(kind));
+    name == kind || name.strip_prefix(&kind).is_some_and(|variant| variant.starts_with('_'))
+}
+
+/// Chunk coordinates in structure references are packed with x in the low 32 bits and z in the high 32 bits.
+fn unpack_chunk(packed: i64) -> (i32, i32) {
+    (packed as i32, (packed >> 32) as i32)
+}
+
+fn decode_bounds(tag: Option<&Tag>) -> Option<Bounds3> {
+    match tag {
+        Some(Tag::IntArray(bb)) if bb.len() == 6 => {
+            let bb = bb.iter().map(|&value| value as i64).collect::<Vec<i64>>();
+            Some(Bounds3::new(i64vec3(bb[0], bb[1], bb[2]), i64vec3(bb[3], bb[4], bb[5])))
+        }
+        _ => None,
+    }
+}
+
+fn decode_start(name: &str, start: &Map) -> Option<StructureStart> {
+    let id = match start.get("id") {
+        Some(Tag::String(id)) => id.as_str(),
+        _ => return None,
+    };
+    if id == "INVALID" {
+        return None;
+    }
+    let (Some(Tag::Int(chunk_x)), Some(Tag::Int(chunk_z))) = (start.get("ChunkX"), start.get("ChunkZ")) else {
+        return None;
+    };
+    let pieces = match start.get("Children") {
+        Some(Tag::List(ListTag::Compound(children))) => children.iter()
+            .filter_map(|child| Some(StructurePiece {
+                id: match child.get("id") {
+                    Some(Tag::String(id)) => id.clone(),
+                    _ => String::new(),
+                },
+                bounds: decode_bounds(child.get("BB"))?,
+            }))
+            .collect::<Vec<_>>(),
+        _ => Vec::new(),
+    };
+    // Before 1.18, starts also saved the bounds of the whole structure.
+    let bounds = pieces.iter()
+        .map(|piece| piece.bounds)
+        .chain(decode_bounds(start.get("BB")))
+        .reduce(|a, b| Bounds3::new(a.min.min(b.min), a.max.max(b.max)))?;
+    Some(StructureStart {
+        name: name.to_owned(),
+        chunk: (*chunk_x, *chunk_z),
+        bounds,
+        pieces,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::coord::{Dimension, WorldCoord};
+    use crate::nbt::tag::NamedTag;
+    use crate::world::{chunk::tests::empty_chunk, io::region::RegionFile, world::VirtualJavaWorld};
+
+    #[test]
+    fn structure_start_test() {
+        let piece = |bb: [i32; 6]| Map::from([
+            ("id".to_owned(), Tag::string("minecraft:jigsaw")),
+            ("BB".to_owned(), Tag::IntArray(bb.to_vec())),
+        ]);
+        let village = Map::from([
+            ("id".to_owned(), Tag::string("minecraft:village_plains")),
+            ("ChunkX".to_owned(), Tag::Int(2)),
+            ("ChunkZ".to_owned(), Tag::Int(-1)),
+            ("Children".to_owned(), Tag::List(ListTag::Compound(vec![
+                piece([32, 60, -16, 40, 70, -8]),
+                piece([28, 64, -20, 34, 68, -12]),
+            ]))),
+        ]);
+        let structures = Map::from([
+            ("starts".to_owned(), Tag::Compound(Map::from([
+                ("minecraft:village_plains".to_owned(), Tag::Compound(village)),
+                ("minecraft:stronghold".to_owned(), Tag::Compound(Map::from([("id".to_owned(), Tag::string("INVALID"))]))),
+            ]))),
+            ("References".to_owned(), Tag::Compound(Map::from([
+                ("minecraft:village_plains".to_owned(), Tag::LongArray(vec![(-1i64 << 32) | 2])),
+            ]))),
+        ]);
+        let decoded = ChunkStructures::from_map(&structures);
+        assert_eq!(decoded.starts.len(), 1);
+        assert_eq!(decoded.starts[0].bounds, Bounds3::new(i64vec3(28, 60, -20), i64vec3(40, 70, -8)));
+        assert_eq!(decoded.references, vec![("minecraft:village_plains".to_owned(), vec![(2, -1)])]);
+        assert!(structure_matches("minecraft:village_plains", "village"));
+        assert!(structure_matches("Village", "minecraft:village"));
+        assert!(!structure_matches("minecraft:villages", "village"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        let region_dir = world.get_region_directory(Dimension::Overworld).unwrap();
+        std::fs::create_dir_all(&region_dir).unwrap();
+        let mut chunk = empty_chunk(2, -1);
+        chunk.structures = structures;
+        assert_eq!(chunk.structures(), decoded);
+        RegionFile::create(region_dir.join("r.0.-1.mca")).unwrap()
+            .write_data((2, 31), &NamedTag::new(chunk.to_nbt(&world.block_registry))).unwrap();
+        let found = world.find_structures(Dimension::Overworld, "village").unwrap().collect::<Vec<_>>();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].as_ref().unwrap().chunk, (2, -1));
+        world.load_chunk(WorldCoord::new(2, -1, Dimension::Overworld)).unwrap();
+        assert_eq!(world.find_structures(Dimension::Overworld, "minecraft:village_plains").unwrap().count(), 1);
+        assert_eq!(world.find_structures(Dimension::Overworld, "stronghold").unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,239 @@
+//! Incremental exports of the chunks that changed after a point in time, for backups and mirroring.
+//!
+//! A chunk set is a directory laid out like a world, with only the region files (`region`,
+//! `entities`, and `poi`) that have chunks in the set. Each region file in the set only has
+//! the chunks that are in the set, with the timestamps they had in the source world.
+
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    McError, McResult, CancellationToken,
+    math::coord::{Dimension, WorldCoord},
+};
+
+use super::{
+    io::region::{
+        external_chunk_path,
+        streaming::StreamingRegionWriter,
+        RegionCoord, RegionFile, Timestamp,
+    },
+    dimension::DimensionRegistry,
+    iter::RegionIter,
+    world::VirtualJavaWorld,
+};
+
+/// The folders of a dimension that have region files.
+const REGION_FOLDERS: [&str; 3] = ["region", "entities", "poi"];
+
+/// The path of the region file for a region coordinate within `directory`.
+fn region_path(directory: &Path, region_coord: WorldCoord) -> PathBuf {
+    directory.join(region_coord.region_file_name())
+}
+
+/// The coordinates of the chunks that are present in a region file.
+fn present_chunks(region: &RegionFile) -> impl Iterator<Item = RegionCoord> + '_ {
+    (0..1024u16)
+        .map(RegionCoord::from)
+        .filter(|&coord| region.get_sector(coord).sector_count() != 0)
+}
+
+/// Copies every chunk in `source` to `destination` without recompressing them, keeping their timestamps.
+/// Returns the number of chunks copied.
+fn copy_region_chunks(source: &mut RegionFile, destination: &mut RegionFile) -> McResult<usize> {
+    let mut copied = 0;
+    for coord in present_chunks(source).collect::<Vec<_>>() {
+        let chunk = match source.read_raw(coord) {
+            Ok(chunk) => chunk,
+            // The sector is allocated, but the length is zero.
+            Err(McError::RegionDataNotFound) => continue,
+            Err(err) => return Err(err),
+        };
+        destination.write_raw_timestamped(coord, &chunk, source.get_timestamp(coord))?;
+        copied += 1;
+    }
+    Ok(copied)
+}
+
+/// Removes a region file that was only partly written, along with the external chunk files that were copied for it.
+fn remove_partial_region(path: &Path, externals: &[PathBuf]) -> McResult<()> {
+    for file in externals.iter().map(PathBuf::as_path).chain([path]) {
+        match std::fs::remove_file(file) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+impl VirtualJavaWorld {
+    /// The directory of a dimension relative to the world directory.
+    pub(crate) fn relative_dimension_directory(&self, dimension: Dimension) -> McResult<PathBuf> {
+        let directory = self.get_dimension_directory(dimension)?;
+        Ok(directory.strip_prefix(&self.directory)
+            .map(Path::to_path_buf)
+            .unwrap_or(directory))
+    }
+
+    /// Exports the chunks whose timestamp is later than `since` to a chunk set at `out_dir`,
+    /// which can be merged into another world with [VirtualJavaWorld::apply_chunk_set].
+    /// Chunks are copied without being decompressed, along with their external `.mcc` files.
+    /// Region files in `out_dir` that have changed chunks are replaced.
+    ///
+    /// Chunks are read from disk, so loaded chunks should be saved first.
+    /// Returns the number of chunks that were exported.
+    pub fn export_changed_chunks<P: AsRef<Path>>(&self, since: Timestamp, out_dir: P) -> McResult<usize> {
+        self.export_changed_chunks_cancellable(since, out_dir, &CancellationToken::new())
+    }
+
+    /// Like [VirtualJavaWorld::export_changed_chunks], but returns [McError::Cancelled] once `cancel` is cancelled.
+    /// The region file that was being written when the export was cancelled is removed along with its
+    /// external chunk files, so `out_dir` only has complete region files.
+    pub fn export_changed_chunks_cancellable<P: AsRef<Path>>(&self, since: Timestamp, out_dir: P, cancel: &CancellationToken) -> McResult<usize> {
+        let out_dir = out_dir.as_ref();
+        let mut exported = 0;
+        for dimension in self.dimensions.dimensions() {
+            let relative = self.relative_dimension_directory(dimension)?;
+            for folder in REGION_FOLDERS {
+                let mut regions = RegionIter::new(self.get_dimension_directory(dimension)?.join(folder), dimension)?;
+                let out_folder = out_dir.join(&relative).join(folder);
+                for region_coord in regions.by_ref().collect::<Vec<_>>() {
+                    cancel.check()?;
+                    let source_path = regions.region_path(region_coord);
+                    let region = RegionFile::open(&source_path)?;
+                    let changed = present_chunks(&region)
+                        .filter(|&coord| region.get_timestamp(coord) > since)
+                        .collect::<Vec<_>>();
+                    if changed.is_empty() {
+                        continue;
+                    }
+                    std::fs::create_dir_all(&out_folder)?;
+                    let out_path = region_path(&out_folder, region_coord);
+                    if out_path.exists() {
+                        std::fs::remove_file(&out_path)?;
+                    }
+                    let mut writer = StreamingRegionWriter::create(&out_path)?;
+                    let mut reader = BufReader::new(File::open(&source_path)?);
+                    let mut copied_externals = Vec::new();
+                    for coord in changed {
+                        if cancel.is_cancelled() {
+                            drop(writer);
+                            remove_partial_region(&out_path, &copied_externals)?;
+                            return Err(McError::Cancelled);
+                        }
+                        match writer.copy_chunk_from(&mut reader, region.header(), coord) {
+                            Ok(_) => exported += 1,
+                            Err(McError::RegionDataNotFound) => continue,
+                            Err(err) => return Err(err),
+                        }
+                        let external = external_chunk_path(&source_path, coord)
+                            .filter(|path| path.is_file());
+                        if let (Some(external), Some(out_external)) = (external, external_chunk_path(&out_path, coord)) {
+                            std::fs::copy(external, &out_external)?;
+                            copied_externals.push(out_external);
+                        }
+                    }
+                    writer.finish()?;
+                }
+            }
+        }
+        Ok(exported)
+    }
+
+    /// Merges a chunk set created by [VirtualJavaWorld::export_changed_chunks] into this world.
+    /// Every chunk in the set replaces the chunk at the same coordinate, keeping the timestamp
+    /// from the set. Loaded chunks that are replaced are unloaded without being saved.
+    /// Returns the number of chunks that were applied.
+    pub fn apply_chunk_set<P: AsRef<Path>>(&mut self, set_dir: P) -> McResult<usize> {
+        let set_dir = set_dir.as_ref();
+        let mut applied = 0;
+        // Custom dimensions in the set are registered in this world by name, since their ids may differ.
+        let set_dimensions = DimensionRegistry::discover(set_dir)?;
+        let mut dimensions = self.dimensions.dimensions();
+        for (_, name) in set_dimensions.custom() {
+            let dimension = self.dimensions.register(name);
+            if !dimensions.contains(&dimension) {
+                dimensions.push(dimension);
+            }
+        }
+        for dimension in dimensions {
+            let relative = self.relative_dimension_directory(dimension)?;
+            for folder in REGION_FOLDERS {
+                let mut regions = RegionIter::new(set_dir.join(&relative).join(folder), dimension)?;
+                for region_coord in regions.by_ref().collect::<Vec<_>>() {
+                    let mut source = RegionFile::open(regions.region_path(region_coord))?;
+                    if folder != "region" {
+                        let directory = self.get_dimension_directory(dimension)?.join(folder);
+                        std::fs::create_dir_all(&directory)?;
+                        let mut destination = RegionFile::open_or_create(region_path(&directory, region_coord))?;
+                        applied += copy_region_chunks(&mut source, &mut destination)?;
+                        continue;
+                    }
+                    for coord in present_chunks(&source).collect::<Vec<_>>() {
+                        let chunk_coord = region_coord.chunk_in_region(coord);
+                        self.unload_chunk(chunk_coord);
+                    }
+                    // The world keeps loaded region files open, so those are written through the world
+                    // so that its copy of the header stays up to date.
+                    if let Some(slot) = self.regions.get(&region_coord) {
+                        let Ok(mut slot) = slot.lock() else {
+                            return McError::custom("Failed to lock region.");
+                        };
+                        applied += copy_region_chunks(&mut source, &mut slot.region)?;
+                    } else {
+                        let directory = self.get_region_directory(dimension)?;
+                        std::fs::create_dir_all(&directory)?;
+                        let mut destination = RegionFile::open_or_create(region_path(&directory, region_coord))?;
+                        applied += copy_region_chunks(&mut source, &mut destination)?;
+                    }
+                }
+            }
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+
+    #[test]
+    fn chunk_set_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = VirtualJavaWorld::open(dir.path().join("source"));
+        let chunk = |value: i32| NamedTag::new(Tag::Compound(crate::nbt::Map::from([("value".to_owned(), Tag::Int(value))])));
+        std::fs::create_dir_all(source.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        std::fs::create_dir_all(source.get_entities_directory(Dimension::Nether).unwrap()).unwrap();
+        {
+            let mut region = RegionFile::create(source.get_region_directory(Dimension::Overworld).unwrap().join("r.-1.0.mca")).unwrap();
+            region.write_data_timestamped((0, 0), &chunk(1), 100).unwrap();
+            region.write_data_timestamped((1, 0), &chunk(2), 300).unwrap();
+            let mut entities = RegionFile::create(source.get_entities_directory(Dimension::Nether).unwrap().join("r.0.0.mca")).unwrap();
+            entities.write_data_timestamped((2, 3), &chunk(3), 400).unwrap();
+        }
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let cancelled_dir = dir.path().join("cancelled");
+        assert!(matches!(source.export_changed_chunks_cancellable(Timestamp::from(200), &cancelled_dir, &cancel), Err(McError::Cancelled)));
+        assert!(!cancelled_dir.join("region/r.-1.0.mca").exists());
+        let set_dir = dir.path().join("set");
+        assert_eq!(source.export_changed_chunks(Timestamp::from(200), &set_dir).unwrap(), 2);
+        let exported = RegionFile::open(set_dir.join("region/r.-1.0.mca")).unwrap();
+        assert!(exported.get_sector((0, 0)).is_empty());
+        assert_eq!(exported.get_timestamp((1, 0)), Timestamp::from(300));
+
+        let mut mirror = VirtualJavaWorld::open(dir.path().join("mirror"));
+        assert_eq!(mirror.apply_chunk_set(&set_dir).unwrap(), 2);
+        let mut region = RegionFile::open(mirror.get_region_directory(Dimension::Overworld).unwrap().join("r.-1.0.mca")).unwrap();
+        assert!(region.get_sector((0, 0)).is_empty());
+        let applied: NamedTag = region.read_data((1, 0)).unwrap();
+        assert!(matches!(applied.tag(), Tag::Compound(map) if matches!(map.get("value"), Some(Tag::Int(2)))));
+        assert_eq!(region.get_timestamp((1, 0)), Timestamp::from(300));
+        let mut entities = RegionFile::open(mirror.get_entities_directory(Dimension::Nether).unwrap().join("r.0.0.mca")).unwrap();
+        assert!(entities.read_data::<_, NamedTag>((2, 3)).is_ok());
+    }
+}
@@ -0,0 +1,406 @@
+//! Rotating and mirroring blocks, schematics, and cuboid selections of a world.
+//!
+//! Rotations are around the Y axis, and are clockwise when viewed from above.
+//! Block states are transformed along with their positions, so that properties holding a direction
+//! (such as `facing`, `axis`, `rotation`, and the `north`/`east`/`south`/`west` connections of fences)
+//! still point the same way relative to the rest of the blocks.
+
+use crate::{
+    math::coord::BlockCoord,
+    nbt::tag::*,
+    McResult,
+};
+
+use super::{
+    block::CubeDirection,
+    blockstate::{BlockState, PropertyValue},
+    container::BlockContainer,
+    schematic::Schematic,
+    world::VirtualJavaWorld,
+};
+
+/// A rotation around the Y axis, clockwise when viewed from above.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Counterclockwise90,
+}
+
+impl Rotation {
+    /// The number of clockwise quarter turns.
+    pub fn quarter_turns(self) -> u8 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 1,
+            Rotation::Clockwise180 => 2,
+            Rotation::Counterclockwise90 => 3,
+        }
+    }
+}
+
+/// A mirror that flips coordinates along an axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mirror {
+    /// Flips along the X axis, swapping east and west.
+    X,
+    /// Flips along the Y axis, swapping up and down.
+    Y,
+    /// Flips along the Z axis, swapping north and south.
+    Z,
+}
+
+/// A rotation or a mirror of a cuboid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transform {
+    Rotate(Rotation),
+    Mirror(Mirror),
+}
+
+impl From<Rotation> for Transform {
+    fn from(value: Rotation) -> Self {
+        Self::Rotate(value)
+    }
+}
+
+impl From<Mirror> for Transform {
+    fn from(value: Mirror) -> Self {
+        Self::Mirror(value)
+    }
+}
+
+impl Transform {
+    /// Returns true if the transform swaps the X and Z axes.
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Transform::Rotate(rotation) if rotation.quarter_turns() % 2 == 1)
+    }
+
+    /// Returns true for mirrors that flip the cuboid horizontally, which swaps the handedness of blocks such as stairs and doors.
+    fn is_horizontal_mirror(self) -> bool {
+        matches!(self, Transform::Mirror(Mirror::X | Mirror::Z))
+    }
+
+    /// Transforms a direction, such as the `facing` of a block.
+    pub fn direction(self, direction: CubeDirection) -> CubeDirection {
+        use CubeDirection::*;
+        match self {
+            Transform::Rotate(rotation) => (0..rotation.quarter_turns()).fold(direction, |direction, _| match direction {
+                North => East,
+                East => South,
+                South => West,
+                West => North,
+                vertical => vertical,
+            }),
+            Transform::Mirror(Mirror::X) => match direction {
+                East => West,
+                West => East,
+                other => other,
+            },
+            Transform::Mirror(Mirror::Y) => match direction {
+                Up => Down,
+                Down => Up,
+                other => other,
+            },
+            Transform::Mirror(Mirror::Z) => match direction {
+                North => South,
+                South => North,
+                other => other,
+            },
+        }
+    }
+
+    /// The size of a cuboid of `size` after it's transformed.
+    pub fn size(self, size: (u16, u16, u16)) -> (u16, u16, u16) {
+        if self.swaps_axes() {
+            (size.2, size.1, size.0)
+        } else {
+            size
+        }
+    }
+
+    /// Transforms a block coordinate relative to the minimum corner of a cuboid of `size`.
+    /// The result is relative to the minimum corner of the transformed cuboid.
+    pub fn block_coord(self, coord: (i64, i64, i64), size: (u16, u16, u16)) -> (i64, i64, i64) {
+        let (x, y, z) = coord;
+        let (max_x, max_y, max_z) = (size.0 as i64 - 1, size.1 as i64 - 1, size.2 as i64 - 1);
+        match self {
+            Transform::Rotate(Rotation::None) => (x, y, z),
+            Transform::Rotate(Rotation::Clockwise90) => (max_z - z, y, x),
+            Transform::Rotate(Rotation::Clockwise180) => (max_x - x, y, max_z - z),
+            Transform::Rotate(Rotation::Counterclockwise90) => (z, y, max_x - x),
+            Transform::Mirror(Mirror::X) => (max_x - x, y, z),
+            Transform::Mirror(Mirror::Y) => (x, max_y - y, z),
+            Transform::Mirror(Mirror::Z) => (x, y, max_z - z),
+        }
+    }
+
+    /// Transforms a position (such as the `Pos` of an entity) relative to the minimum corner of a cuboid of `size`.
+    pub fn position(self, pos: (f64, f64, f64), size: (u16, u16, u16)) -> (f64, f64, f64) {
+        let (x, y, z) = pos;
+        let (size_x, size_y, size_z) = (size.0 as f64, size.1 as f64, size.2 as f64);
+        match self {
+            Transform::Rotate(Rotation::None) => (x, y, z),
+            Transform::Rotate(Rotation::Clockwise90) => (size_z - z, y, x),
+            Transform::Rotate(Rotation::Clockwise180) => (size_x - x, y, size_z - z),
+            Transform::Rotate(Rotation::Counterclockwise90) => (z, y, size_x - x),
+            Transform::Mirror(Mirror::X) => (size_x - x, y, z),
+            Transform::Mirror(Mirror::Y) => (x, size_y - y, z),
+            Transform::Mirror(Mirror::Z) => (x, y, size_z - z),
+        }
+    }
+
+    /// Transforms the yaw and pitch of an entity's `Rotation`, in degrees.
+    /// A yaw of 0 faces south, and the yaw increases clockwise.
+    pub fn yaw_pitch(self, yaw: f32, pitch: f32) -> (f32, f32) {
+        match self {
+            Transform::Rotate(rotation) => ((yaw + 90.0 * rotation.quarter_turns() as f32).rem_euclid(360.0), pitch),
+            Transform::Mirror(Mirror::X) => ((-yaw).rem_euclid(360.0), pitch),
+            Transform::Mirror(Mirror::Y) => (yaw, -pitch),
+            Transform::Mirror(Mirror::Z) => ((180.0 - yaw).rem_euclid(360.0), pitch),
+        }
+    }
+
+    /// Transforms the direction-bearing properties of a block state.
+    /// Properties that aren't recognized are left unchanged.
+    pub fn block_state(self, state: &BlockState) -> BlockState {
+        let mut result = state.clone();
+        for property in state.properties().unwrap_or_default() {
+            let (name, value) = (property.name(), property.value());
+            // Connections such as the sides of fences move to the property of the transformed side,
+            // as long as the block has that property (vines have `up`, but not `down`).
+            if let Some(direction) = CubeDirection::from_property(name) {
+                let target = self.direction(direction).to_property();
+                if state.get_property(&target).is_some() {
+                    result.set_property(target, value);
+                }
+                continue;
+            }
+            let transformed = match name {
+                "facing" => CubeDirection::from_property(value).map(|direction| self.direction(direction).to_property()),
+                "axis" if self.swaps_axes() => match value {
+                    "x" => Some("z".to_owned()),
+                    "z" => Some("x".to_owned()),
+                    _ => None,
+                },
+                "rotation" => state.get_int(name).map(|rotation| self.rotation(rotation).to_string()),
+                "shape" => self.shape(value),
+                "orientation" => value.split('_')
+                    .map(|part| CubeDirection::from_property(part).map(|direction| self.direction(direction).to_property()))
+                    .collect::<Option<Vec<_>>>()
+                    .map(|parts| parts.join("_")),
+                "half" | "type" if self == Transform::Mirror(Mirror::Y) => swap(value, [("top", "bottom"), ("upper", "lower")]),
+                "face" | "attachment" if self == Transform::Mirror(Mirror::Y) => swap(value, [("floor", "ceiling")]),
+                "hinge" | "type" if self.is_horizontal_mirror() => swap(value, [("left", "right")]),
+                _ => None,
+            };
+            if let Some(transformed) = transformed {
+                result.set_property(name, transformed);
+            }
+        }
+        result
+    }
+
+    /// Transforms the 16 directions of the `rotation` property of signs, banners, and heads,
+    /// where 0 faces south and the rotation increases clockwise.
+    fn rotation(self, rotation: i32) -> i32 {
+        match self {
+            Transform::Rotate(turns) => (rotation + 4 * turns.quarter_turns() as i32).rem_euclid(16),
+            Transform::Mirror(Mirror::X) => (16 - rotation).rem_euclid(16),
+            Transform::Mirror(Mirror::Y) => rotation,
+            Transform::Mirror(Mirror::Z) => (8 - rotation).rem_euclid(16),
+        }
+    }
+
+    /// Transforms the `shape` of stairs and rails.
+    fn shape(self, shape: &str) -> Option<String> {
+        if let Some(side) = ["inner_", "outer_"].iter().find_map(|prefix| shape.strip_prefix(prefix)) {
+            if !self.is_horizontal_mirror() {
+                return None;
+            }
+            let prefix = &shape[..shape.len() - side.len()];
+            return swap(side, [("left", "right")]).map(|side| format!("{prefix}{side}"));
+        }
+        let rail = |part: &str| CubeDirection::from_property(part).map(|direction| self.direction(direction));
+        if let Some(direction) = shape.strip_prefix("ascending_") {
+            return rail(direction).map(|direction| format!("ascending_{}", direction.to_property()));
+        }
+        let (first, second) = shape.split_once('_')?;
+        let (mut first, mut second) = (rail(first)?, rail(second)?);
+        // Rail shapes name the north or south end first, and straight rails are `north_south` or `east_west`.
+        let east_west = |direction| matches!(direction, CubeDirection::East | CubeDirection::West);
+        if (east_west(first) && !east_west(second))
+            || matches!((first, second), (CubeDirection::West, CubeDirection::East) | (CubeDirection::South, CubeDirection::North)) {
+            std::mem::swap(&mut first, &mut second);
+        }
+        Some(format!("{}_{}", first.to_property(), second.to_property()))
+    }
+}
+
+/// Returns the other value of the pair that `value` is in.
+fn swap<const N: usize>(value: &str, pairs: [(&str, &str); N]) -> Option<String> {
+    pairs.iter().find_map(|&(a, b)| {
+        if value == a {
+            Some(b.to_owned())
+        } else if value == b {
+            Some(a.to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+/// Transforms the blocks of a container, returning a new container.
+///
+/// Each state in the container's registry is transformed once. If the container's registry
+/// rejects a transformed state (see [BlockRegistry::validate](super::blockregistry::BlockRegistry::validate)),
+/// such as a hopper that would face up, the original state is kept.
+pub fn transform_blocks<T: Into<Transform>>(blocks: &BlockContainer, transform: T) -> BlockContainer {
+    let transform = transform.into();
+    let size = blocks.size;
+    let mut result = BlockContainer::new(transform.size(size));
+    let registry = &blocks.block_registry;
+    let ids = (0..registry.len() as u32)
+        .map(|id| registry.get(id).map_or(0, |state| {
+            let transformed = transform.block_state(state);
+            if registry.validate(&transformed).is_ok() {
+                result.block_registry.register(transformed)
+            } else {
+                result.block_registry.register(state)
+            }
+        }))
+        .collect::<Vec<u32>>();
+    for y in 0..size.1 as i64 {
+        for z in 0..size.2 as i64 {
+            for x in 0..size.0 as i64 {
+                let Some(id) = blocks.get_block_id(x, y, z) else {
+                    continue;
+                };
+                let (x, y, z) = transform.block_coord((x, y, z), size);
+                result.set_block_id(x, y, z, ids[id as usize]);
+            }
+        }
+    }
+    result
+}
+
+/// Transforms a schematic, returning a new schematic.
+///
+/// Block entities are moved to their transformed positions, and entities have their `Pos` and `Rotation` transformed.
+/// The offset is changed so that the point the schematic was copied relative to is transformed along with the blocks.
+pub fn transform_schematic<T: Into<Transform>>(schematic: &Schematic, transform: T) -> Schematic {
+    let transform = transform.into();
+    let size = schematic.size();
+    let origin = transform.block_coord((-schematic.offset.0 as i64, -schematic.offset.1 as i64, -schematic.offset.2 as i64), size);
+    Schematic {
+        blocks: transform_blocks(&schematic.blocks, transform),
+        offset: (-origin.0 as i32, -origin.1 as i32, -origin.2 as i32),
+        data_version: schematic.data_version,
+        block_entities: schematic.block_entities.iter().map(|entity| {
+            let mut entity = entity.clone();
+            let (x, y, z) = transform.block_coord(entity.coord(), size);
+            (entity.x, entity.y, entity.z) = (x as i32, y as i32, z as i32);
+            entity
+        }).collect(),
+        entities: schematic.entities.iter().map(|entity| {
+            let mut entity = entity.clone();
+            if let Some(pos) = entity.pos() {
+                entity.set_pos(transform.position(pos, size));
+            }
+            if let Some(Tag::List(ListTag::Float(rotation))) = entity.nbt_mut().get_mut("Rotation") {
+                if let [yaw, pitch] = rotation.as_mut_slice() {
+                    (*yaw, *pitch) = transform.yaw_pitch(*yaw, *pitch);
+                }
+            }
+            entity
+        }).collect(),
+        metadata: schematic.metadata.clone(),
+    }
+}
+
+/// Transforms the blocks, block entities, and entities from `min` to `max` (inclusive) in place, loading chunks as needed.
+///
+/// The selection is rotated around its center. If the center is between two blocks and the rotation
+/// changes the size of the selection, the result is shifted towards the minimum corner.
+/// The selection is cleared to air before the transformed blocks are written, and moved entities are
+/// given new UUIDs by the game (see [Schematic::paste]).
+///
+/// Returns the minimum and maximum corners of the transformed selection.
+pub fn transform_selection<T: Into<Transform>>(world: &mut VirtualJavaWorld, min: BlockCoord, max: BlockCoord, transform: T) -> McResult<(BlockCoord, BlockCoord)> {
+    let transform = transform.into();
+    let schematic = Schematic::copy_from_world(world, min, max)?;
+    let transformed = transform_schematic(&schematic, transform);
+    let air = world.block_registry.register(BlockState::air());
+    world.edit_chunks_in_box(min, max, |chunk, low, high| {
+        let mut changed = false;
+        for y in low.1..=high.1 {
+            for z in low.2..=high.2 {
+                for x in low.0..=high.0 {
+                    changed |= chunk.set_id((x, y, z), air) != Some(air);
+                    changed |= chunk.remove_block_entity((x, y, z)).is_some();
+                }
+            }
+        }
+        Ok(changed)
+    })?;
+    world.remove_entities_in_box(min, max, |_| true)?;
+    let (size, new_size) = (schematic.size(), transformed.size());
+    let low = BlockCoord::new(
+        min.x.min(max.x) + (size.0 as i64 - new_size.0 as i64).div_euclid(2),
+        min.y.min(max.y),
+        min.z.min(max.z) + (size.2 as i64 - new_size.2 as i64).div_euclid(2),
+        min.dimension,
+    );
+    let high = BlockCoord::new(
+        low.x + new_size.0 as i64 - 1,
+        low.y + new_size.1 as i64 - 1,
+        low.z + new_size.2 as i64 - 1,
+        min.dimension,
+    );
+    transformed.paste(world, low, false)?;
+    Ok((low, high))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{blockstate::blockstate, chunk::BlockEntity};
+    use crate::nbt::Map;
+
+    #[test]
+    fn transform_test() {
+        let clockwise = Transform::from(Rotation::Clockwise90);
+        assert_eq!(clockwise.block_state(&blockstate!(oak_stairs[facing = east, half = top])), blockstate!(oak_stairs[facing = south, half = top]));
+        assert_eq!(clockwise.block_state(&blockstate!(oak_log[axis = x])), blockstate!(oak_log[axis = z]));
+        assert_eq!(
+            clockwise.block_state(&blockstate!(oak_fence[north = "true", east = "false", south = "false", west = "false"])),
+            blockstate!(oak_fence[north = "false", east = "true", south = "false", west = "false"]),
+        );
+        assert_eq!(clockwise.block_state(&blockstate!(rail[shape = north_east])), blockstate!(rail[shape = south_east]));
+        assert_eq!(clockwise.block_state(&blockstate!(rail[shape = ascending_west])), blockstate!(rail[shape = ascending_north]));
+        assert_eq!(clockwise.block_state(&blockstate!(rail[shape = north_south])), blockstate!(rail[shape = east_west]));
+        assert_eq!(clockwise.block_state(&blockstate!(oak_sign[rotation = 14])), blockstate!(oak_sign[rotation = 2]));
+        assert_eq!(
+            Transform::from(Mirror::Z).block_state(&blockstate!(oak_stairs[facing = north, shape = inner_left])),
+            blockstate!(oak_stairs[facing = south, shape = inner_right]),
+        );
+        assert_eq!(Transform::from(Mirror::X).block_state(&blockstate!(oak_sign[rotation = 4])), blockstate!(oak_sign[rotation = 12]));
+        assert_eq!(Transform::from(Mirror::Y).block_state(&blockstate!(oak_slab[type = top])), blockstate!(oak_slab[type = bottom]));
+        assert_eq!(Transform::from(Mirror::X).block_state(&blockstate!(chest[type = left])), blockstate!(chest[type = right]));
+
+        let mut schematic = Schematic::new((3, 1, 2));
+        schematic.set_block_state(0, 0, 0, blockstate!(chest[facing = north]));
+        schematic.block_entities.push(BlockEntity::new("minecraft:chest", (0, 0, 0), Map::new()));
+        let rotated = transform_schematic(&schematic, Rotation::Clockwise90);
+        assert_eq!(rotated.size(), (2, 1, 3));
+        assert_eq!(rotated.get_block_state(1, 0, 0), Some(&blockstate!(chest[facing = east])));
+        assert_eq!(rotated.block_entities[0].coord(), (1, 0, 0));
+        assert_eq!(rotated.offset, (-1, 0, 0));
+        let turned = [Rotation::Clockwise90, Rotation::Clockwise180].iter()
+            .fold(rotated, |schematic, &rotation| transform_schematic(&schematic, rotation));
+        assert_eq!(turned.size(), (3, 1, 2));
+        assert_eq!(turned.get_block_state(0, 0, 0), schematic.get_block_state(0, 0, 0));
+        assert_eq!(turned.offset, (0, 0, 0));
+    }
+}
@@ -0,0 +1,163 @@
+//! Shrinking a world to a [Selection] by deleting every chunk outside of it, such as trimming
+//! a server's map down to its world border.
+
+use std::path::{Path, PathBuf};
+
+use crate::{McError, McResult, ResultExt, math::coord::Dimension};
+
+use super::{
+    io::region::{external_chunk_path, RegionCoord, RegionFile},
+    dimension::DimensionRegistry,
+    iter::RegionIter,
+    level::LevelData,
+    lock::WorldLock,
+    selection::{ChunkSelection, Selection},
+};
+
+/// The folders of a dimension that hold region files, all of which are trimmed.
+const REGION_FOLDERS: [&str; 3] = ["region", "entities", "poi"];
+
+/// The result of [trim_world].
+#[derive(Debug, Clone, Default)]
+pub struct TrimReport {
+    /// The number of chunks deleted from the `region` folder.
+    /// Entity and POI chunks are deleted as well, but aren't counted.
+    pub chunks_deleted: u64,
+    /// Region files (from any of the folders) that were removed because none of their chunks are kept.
+    pub removed_regions: Vec<PathBuf>,
+    /// The number of bytes that were reclaimed, including external `.mcc` files.
+    /// For a dry run, this is the space taken by the chunks that would be deleted.
+    pub reclaimed_bytes: u64,
+}
+
+/// Deletes every chunk column of a dimension that `keep` doesn't intersect from the `region`, `entities`,
+/// and `poi` folders. Region files that end up without any chunks are removed, and the rest are
+/// [optimized](RegionFile::optimize) so that the space is given back. Applying the trim acquires the world's
+/// [WorldLock], so it fails while the game or a [VirtualJavaWorld](super::world::VirtualJavaWorld) has the world open.
+///
+/// With `dry_run`, nothing is modified and the report describes what would be deleted. Since optimizing
+/// also reclaims sectors that were already unused, an applied trim may reclaim more than its dry run reported.
+pub fn trim_world<P: AsRef<Path>, S: Selection + ?Sized>(world_dir: P, dimension: Dimension, keep: &S, dry_run: bool) -> McResult<TrimReport> {
+    let world_dir = world_dir.as_ref();
+    let _lock = if dry_run {
+        None
+    } else {
+        Some(WorldLock::acquire(world_dir)?)
+    };
+    let mut report = TrimReport::default();
+    let dimension_dir = DimensionRegistry::discover(world_dir)?
+        .directory(world_dir, dimension)
+        .ok_or(McError::UnknownDimension(dimension))?;
+    for folder in REGION_FOLDERS {
+        let directory = dimension_dir.join(folder);
+        for region_coord in RegionIter::new(&directory, dimension)? {
+            let path = directory.join(region_coord.region_file_name());
+            let deleted = trim_region(&path, region_coord.xz(), keep, dry_run, &mut report).with_path(&path)?;
+            if folder == "region" {
+                report.chunks_deleted += deleted;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Trims a dimension to the world border in the world's `level.dat` with [trim_world].
+/// In the Overworld, the [spawn chunks](LevelData::spawn_chunks) are kept even if they're outside of the border.
+pub fn trim_world_to_border<P: AsRef<Path>>(world_dir: P, dimension: Dimension, dry_run: bool) -> McResult<TrimReport> {
+    let world_dir = world_dir.as_ref();
+    let level_path = world_dir.join("level.dat");
+    let level_data = LevelData::read_from_file(&level_path).with_path(level_path)?;
+    let spawn_chunks = match dimension {
+        Dimension::Overworld => level_data.spawn_chunks(),
+        _ => ChunkSelection::new(),
+    };
+    let keep = level_data.border.for_dimension(dimension).union(spawn_chunks);
+    trim_world(world_dir, dimension, &keep, dry_run)
+}
+
+/// Trims a single region file, returning the number of chunks deleted.
+fn trim_region<S: Selection + ?Sized>(path: &Path, (region_x, region_z): (i64, i64), keep: &S, dry_run: bool, report: &mut TrimReport) -> McResult<u64> {
+    let file_size = std::fs::metadata(path)?.len();
+    let mut region = RegionFile::open(path)?;
+    let mut deleted = Vec::new();
+    let mut kept = 0;
+    for index in 0..1024u16 {
+        let coord = RegionCoord::from(index);
+        if region.get_sector(coord).sector_count() == 0 {
+            continue;
+        }
+        let chunk = (region_x * 32 + coord.x() as i64, region_z * 32 + coord.z() as i64);
+        if keep.intersects_chunk(chunk) {
+            kept += 1;
+        } else {
+            deleted.push(coord);
+        }
+    }
+    if deleted.is_empty() {
+        return Ok(0);
+    }
+    let external_size = deleted.iter()
+        .filter_map(|&coord| external_chunk_path(path, coord))
+        .filter_map(|external| external.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum::<u64>();
+    if kept == 0 {
+        report.removed_regions.push(path.to_owned());
+        report.reclaimed_bytes += file_size + external_size;
+        if !dry_run {
+            // Deleting the chunks removes their external files.
+            for &coord in &deleted {
+                region.delete_data(coord)?;
+            }
+            drop(region);
+            std::fs::remove_file(path)?;
+        }
+    } else if dry_run {
+        report.reclaimed_bytes += deleted.iter()
+            .map(|&coord| region.get_sector(coord).sector_count() * 4096)
+            .sum::<u64>() + external_size;
+    } else {
+        for &coord in &deleted {
+            region.delete_data(coord)?;
+        }
+        region.optimize()?;
+        drop(region);
+        report.reclaimed_bytes += file_size.saturating_sub(std::fs::metadata(path)?.len()) + external_size;
+    }
+    Ok(deleted.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tag::{NamedTag, Tag};
+    use crate::world::selection::ChunkSelection;
+
+    #[test]
+    fn trim_world_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let region_dir = dir.path().join("region");
+        std::fs::create_dir_all(&region_dir).unwrap();
+        let chunk = NamedTag::new(Tag::String("trim".repeat(2000)));
+        let mut region = RegionFile::create(region_dir.join("r.0.0.mca")).unwrap();
+        for index in 0..4u16 {
+            region.write_data(index, &chunk).unwrap();
+        }
+        drop(region);
+        RegionFile::create(region_dir.join("r.1.0.mca")).unwrap().write_data((0, 0), &chunk).unwrap();
+        let keep = ChunkSelection::area(((0, 0), (1, 0)));
+
+        let planned = trim_world(dir.path(), Dimension::Overworld, &keep, true).unwrap();
+        assert_eq!(planned.chunks_deleted, 3);
+        assert_eq!(planned.removed_regions, vec![region_dir.join("r.1.0.mca")]);
+        assert!(region_dir.join("r.1.0.mca").exists());
+
+        let applied = trim_world(dir.path(), Dimension::Overworld, &keep, false).unwrap();
+        assert_eq!(applied.chunks_deleted, 3);
+        assert!(applied.reclaimed_bytes >= planned.reclaimed_bytes);
+        assert!(!region_dir.join("r.1.0.mca").exists());
+        let region = RegionFile::open(region_dir.join("r.0.0.mca")).unwrap();
+        assert!(!region.get_sector((1, 0)).is_empty());
+        assert!(region.get_sector((2, 0)).is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+//! Lists of the blocks in vanilla Minecraft, used to catch block states that
+//! the game wouldn't recognize (such as typos in block names or property values)
+//! before they're written into chunks.
+//!
+//! The lists are embedded in the crate, so this module is behind the `vanilla-blocks` feature.
+
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    sync::OnceLock,
+};
+
+use crate::{McError, McResult};
+
+use super::blockstate::BlockState;
+
+/// An embedded block list and the DataVersions that it applies to.
+struct BlockList {
+    version_name: &'static str,
+    data_versions: RangeInclusive<i32>,
+    source: &'static str,
+}
+
+/// Every embedded block list, oldest first.
+const BLOCK_LISTS: [BlockList; 1] = [
+    BlockList {
+        version_name: "1.20",
+        data_versions: 3463..=3578,
+        source: include_str!("vanilla/blocks_1_20.txt"),
+    },
+];
+
+/// The blocks of a Minecraft version, and the values that each of their properties can have.
+#[derive(Debug, Clone)]
+pub struct VanillaBlocks {
+    version_name: &'static str,
+    data_versions: RangeInclusive<i32>,
+    /// Block names (without the namespace) mapped to their properties.
+    blocks: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+impl VanillaBlocks {
+    /// Gets the blocks of the Minecraft version with the given DataVersion.
+    /// The list is parsed the first time that it's requested.
+    /// Returns [McError::UnsupportedDataVersion] if no list is embedded for the version.
+    pub fn for_data_version(data_version: i32) -> McResult<&'static VanillaBlocks> {
+        static LISTS: OnceLock<Vec<VanillaBlocks>> = OnceLock::new();
+        let lists = LISTS.get_or_init(|| BLOCK_LISTS.iter().map(Self::parse).collect());
+        lists.iter()
+            .find(|list| list.data_versions.contains(&data_version))
+            .ok_or(McError::UnsupportedDataVersion(data_version))
+    }
+
+    /// The name of the Minecraft version, such as `1.20`.
+    pub fn version_name(&self) -> &str {
+        self.version_name
+    }
+
+    /// The DataVersions that the list applies to.
+    pub fn data_versions(&self) -> RangeInclusive<i32> {
+        self.data_versions.clone()
+    }
+
+    /// The number of blocks in the list.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns true if the list has no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Returns true if `name` is a vanilla block. The namespace may be left out.
+    pub fn contains(&self, name: &str) -> bool {
+        vanilla_name(name).is_some_and(|name| self.blocks.contains_key(name))
+    }
+
+    /// The values that a property of a block can have, or `None` if the block or property doesn't exist.
+    pub fn property_values(&self, name: &str, property: &str) -> Option<&[String]> {
+        let properties = self.blocks.get(vanilla_name(name)?)?;
+        properties.get(property).map(Vec::as_slice)
+    }
+
+    /// Checks that the state is a vanilla block, and that each of its properties exists and has a valid value.
+    /// Properties that are left out are allowed, since Minecraft gives them their default values.
+    /// Returns [McError::InvalidBlockState] describing the first problem that was found.
+    pub fn validate(&self, state: &BlockState) -> McResult<()> {
+        let properties = vanilla_name(state.name())
+            .and_then(|name| self.blocks.get(name))
+            .ok_or_else(|| McError::InvalidBlockState(format!("{} is not a block in Minecraft {}", state.name(), self.version_name)))?;
+        for property in state.properties().unwrap_or_default() {
+            let Some(values) = properties.get(property.name()) else {
+                return Err(McError::InvalidBlockState(format!("{} has no property named \"{}\"", state.name(), property.name())));
+            };
+            if !values.iter().any(|value| value == property.value()) {
+                return Err(McError::InvalidBlockState(format!(
+                    "\"{}\" is not a valid value for the \"{}\" property of {} (expected one of {})",
+                    property.value(),
+                    property.name(),
+                    state.name(),
+                    values.join(", "),
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the syntax described at the top of the embedded lists.
+    /// The lists are part of the crate, so mistakes in them panic.
+    fn parse(list: &BlockList) -> Self {
+        let mut sets = HashMap::<&str, Vec<&str>>::new();
+        let mut templates = HashMap::<&str, Vec<(String, Vec<String>)>>::new();
+        let mut blocks = HashMap::new();
+        for line in list.source.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut tokens = line.split_whitespace();
+            let head = tokens.next().unwrap();
+            if let Some(name) = head.strip_prefix('$').filter(|name| !name.starts_with('{')) {
+                sets.insert(name, tokens.collect());
+                continue;
+            }
+            let mut properties = Vec::new();
+            for token in tokens {
+                if let Some(template) = token.strip_prefix('@') {
+                    let template = templates.get(template)
+                        .unwrap_or_else(|| panic!("Unknown template @{template} in block list {}.", list.version_name));
+                    properties.extend(template.iter().cloned());
+                } else {
+                    let (name, values) = token.split_once('=')
+                        .unwrap_or_else(|| panic!("Invalid property {token} in block list {}.", list.version_name));
+                    properties.push((name.to_owned(), parse_values(values)));
+                }
+            }
+            if let Some(name) = head.strip_prefix('@') {
+                templates.insert(name, properties);
+                continue;
+            }
+            let names = match head.split_once("${").and_then(|(prefix, rest)| Some((prefix, rest.split_once('}')?))) {
+                Some((prefix, (set, suffix))) => sets.get(set)
+                    .unwrap_or_else(|| panic!("Unknown set ${set} in block list {}.", list.version_name))
+                    .iter()
+                    .map(|name| format!("{prefix}{name}{suffix}"))
+                    .collect(),
+                None => vec![head.to_owned()],
+            };
+            for name in names {
+                blocks.insert(name, properties.iter().cloned().collect::<HashMap<_, _>>());
+            }
+        }
+        Self {
+            version_name: list.version_name,
+            data_versions: list.data_versions.clone(),
+            blocks,
+        }
+    }
+}
+
+/// Parses a comma separated list of values, where `a..b` is a range of integers.
+fn parse_values(values: &str) -> Vec<String> {
+    values.split(',').flat_map(|value| {
+        match value.split_once("..").and_then(|(start, end)| Some((start.parse::<i32>().ok()?, end.parse::<i32>().ok()?))) {
+            Some((start, end)) => (start..=end).map(|value| value.to_string()).collect(),
+            None => vec![value.to_owned()],
+        }
+    }).collect()
+}
+
+/// Strips the `minecraft` namespace from a block name.
+/// Returns `None` if the name has a different namespace.
+fn vanilla_name(name: &str) -> Option<&str> {
+    match name.split_once(':') {
+        Some(("minecraft", name)) => Some(name),
+        Some(_) => None,
+        None => Some(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::blockstate::blockstate;
+
+    #[test]
+    fn vanilla_blocks_test() {
+        let blocks = VanillaBlocks::for_data_version(3465).unwrap();
+        assert_eq!(blocks.version_name(), "1.20");
+        assert!(blocks.contains("minecraft:cherry_hanging_sign"));
+        assert!(blocks.contains("waxed_oxidized_cut_copper_stairs"));
+        assert!(!blocks.contains("minecraft:stnoe"));
+        assert_eq!(blocks.property_values("minecraft:oak_sign", "rotation").unwrap().len(), 16);
+        assert!(blocks.validate(&blockstate!(oak_stairs[facing = east, half = top])).is_ok());
+        assert!(blocks.validate(&blockstate!(stone)).is_ok());
+        assert!(matches!(blocks.validate(&blockstate!(oak_stairs[facing = up])), Err(McError::InvalidBlockState(_))));
+        assert!(matches!(blocks.validate(&blockstate!(stone[color = red])), Err(McError::InvalidBlockState(_))));
+        assert!(matches!(blocks.validate(&BlockState::from("mymod:stone")), Err(McError::InvalidBlockState(_))));
+        assert!(matches!(VanillaBlocks::for_data_version(2730), Err(McError::UnsupportedDataVersion(2730))));
+    }
+}
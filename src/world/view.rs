@@ -0,0 +1,172 @@
+//! Reading a world without any chance of modifying it.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    McError, McResult, ResultExt,
+    math::coord::{BlockCoord, Dimension, WorldCoord},
+    nbt::tag::NamedTag,
+};
+
+use super::{
+    blockregistry::BlockRegistry,
+    blockstate::BlockState,
+    chunk::{Chunk, decode_chunk_for_format},
+    dimension::DimensionRegistry,
+    io::region::RegionFile,
+    iter::{ChunkIter, RegionIter},
+    level::LevelData,
+};
+
+/// A world that is opened strictly for reading, for tools that only inspect a world.
+///
+/// Unlike [VirtualJavaWorld](super::world::VirtualJavaWorld), a view never creates region files (chunks in
+/// missing regions are simply absent), doesn't take the world's `session.lock`, and opens region files with
+/// [RegionFile::open_read_only], so it can be used on a world that the game has open. Chunks are decoded
+/// when they're first queried and kept until [WorldView::clear_cache] is called.
+pub struct WorldView {
+    pub directory: PathBuf,
+    pub block_registry: BlockRegistry,
+    /// The contents of `level.dat`, if it could be read.
+    pub level_data: Option<LevelData>,
+    /// The custom dimensions that [Dimension::Other] ids refer to.
+    pub dimensions: DimensionRegistry,
+    regions: HashMap<WorldCoord, Option<RegionFile>>,
+    chunks: HashMap<WorldCoord, Option<Chunk>>,
+}
+
+impl WorldView {
+    /// Opens the world at `directory` for reading.
+    /// Returns [McError::WorldDirectoryNotFound] if the directory doesn't exist.
+    pub fn open<P: AsRef<Path>>(directory: P) -> McResult<Self> {
+        let directory = directory.as_ref().to_owned();
+        if !directory.is_dir() {
+            return Err(McError::WorldDirectoryNotFound(directory));
+        }
+        Ok(Self {
+            block_registry: BlockRegistry::with_air(),
+            level_data: LevelData::read_from_file(directory.join("level.dat")).ok(),
+            dimensions: DimensionRegistry::discover(&directory)?,
+            regions: HashMap::new(),
+            chunks: HashMap::new(),
+            directory,
+        })
+    }
+
+    /// The directory that the region files of a dimension are in.
+    /// Returns [McError::UnknownDimension] for a [Dimension::Other] that isn't registered.
+    pub fn get_region_directory(&self, dimension: Dimension) -> McResult<PathBuf> {
+        self.dimensions.directory(&self.directory, dimension)
+            .map(|directory| directory.join("region"))
+            .ok_or(McError::UnknownDimension(dimension))
+    }
+
+    /// Finds the region files of a dimension, yielding their region coordinates.
+    pub fn iter_regions(&self, dimension: Dimension) -> McResult<RegionIter> {
+        RegionIter::new(self.get_region_directory(dimension)?, dimension)
+    }
+
+    /// Reads every chunk of a dimension from its region files, one region at a time.
+    /// The NBT can be decoded with [decode_chunk_for_format].
+    pub fn iter_chunks(&self, dimension: Dimension) -> McResult<ChunkIter> {
+        self.iter_regions(dimension).map(ChunkIter::read_only)
+    }
+
+    /// Gets the region file at a region coordinate, or `None` if it doesn't exist.
+    fn get_region(&mut self, coord: WorldCoord) -> McResult<Option<&mut RegionFile>> {
+        if !self.regions.contains_key(&coord) {
+            let path = self.get_region_directory(coord.dimension)?.join(coord.region_file_name());
+            let region = if path.is_file() {
+                Some(RegionFile::open_read_only(&path).with_path(path)?)
+            } else {
+                None
+            };
+            self.regions.insert(coord, region);
+        }
+        Ok(self.regions.get_mut(&coord).and_then(Option::as_mut))
+    }
+
+    /// Reads the NBT of a chunk. Returns `None` if the chunk or its region file doesn't exist.
+    pub fn read_chunk_nbt(&mut self, coord: WorldCoord) -> McResult<Option<NamedTag>> {
+        let Some(region) = self.get_region(coord.region_coord())? else {
+            return Ok(None);
+        };
+        match region.read_data(coord.xz()) {
+            Ok(tag) => Ok(Some(tag)),
+            Err(McError::RegionDataNotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Gets a chunk, decoding it if it hasn't been queried yet. Returns `None` if the chunk doesn't exist.
+    pub fn get_chunk(&mut self, coord: WorldCoord) -> McResult<Option<&Chunk>> {
+        if !self.chunks.contains_key(&coord) {
+            let chunk = match self.read_chunk_nbt(coord)? {
+                Some(root) => {
+                    let format = self.get_region(coord.region_coord())?
+                        .map(|region| region.format())
+                        .unwrap_or_default();
+                    Some(decode_chunk_for_format(&mut self.block_registry, root.tag, format).with_chunk(coord)?)
+                }
+                None => None,
+            };
+            self.chunks.insert(coord, chunk);
+        }
+        Ok(self.chunks.get(&coord).and_then(Option::as_ref))
+    }
+
+    /// Gets the block state at a coordinate. Returns `None` if the chunk doesn't exist or the
+    /// block is outside of the chunk's sections.
+    pub fn get_block_state(&mut self, coord: BlockCoord) -> McResult<Option<&BlockState>> {
+        let id = self.get_chunk(coord.chunk_coord())?.and_then(|chunk| chunk.get_id(coord.xyz()));
+        Ok(id.and_then(|id| self.block_registry.get(id)))
+    }
+
+    /// Gets the biome at a coordinate. Returns `None` if the chunk doesn't exist or the section doesn't have biomes.
+    pub fn get_biome(&mut self, coord: BlockCoord) -> McResult<Option<String>> {
+        Ok(self.get_chunk(coord.chunk_coord())?
+            .and_then(|chunk| chunk.get_biome(coord.xyz()))
+            .map(str::to_owned))
+    }
+
+    /// Forgets the decoded chunks and closes the region files, so that later queries read the world again.
+    pub fn clear_cache(&mut self) {
+        self.chunks.clear();
+        self.regions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{blockstate::BlockProperties, world::VirtualJavaWorld};
+
+    #[test]
+    fn world_view_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        let chunk = crate::world::chunk::tests::empty_chunk(0, 0);
+        RegionFile::create(world.get_region_directory(Dimension::Overworld).unwrap().join("r.0.0.mca")).unwrap()
+            .write_data((0, 0), &NamedTag::new(chunk.to_nbt(&world.block_registry))).unwrap();
+        world.set_block_state_loaded(BlockCoord::new(1, 2, 3, Dimension::Overworld), BlockState::new("minecraft:stone", BlockProperties::none())).unwrap();
+        world.save_all().unwrap();
+        drop(world);
+        let region_path = dir.path().join("region/r.0.0.mca");
+        let modified = std::fs::metadata(&region_path).unwrap().modified().unwrap();
+
+        let mut view = WorldView::open(dir.path()).unwrap();
+        let state = view.get_block_state(BlockCoord::new(1, 2, 3, Dimension::Overworld)).unwrap().unwrap();
+        assert_eq!(state.name(), "minecraft:stone");
+        assert!(view.get_block_state(BlockCoord::new(600, 2, 3, Dimension::Overworld)).unwrap().is_none());
+        assert_eq!(view.iter_chunks(Dimension::Overworld).unwrap().count(), 1);
+        // Nothing was created or modified.
+        assert!(!dir.path().join("region/r.1.0.mca").exists());
+        assert!(!dir.path().join("session.lock").exists());
+        assert_eq!(std::fs::metadata(&region_path).unwrap().modified().unwrap(), modified);
+        assert!(matches!(WorldView::open(dir.path().join("missing")), Err(McError::WorldDirectoryNotFound(_))));
+    }
+}
@@ -3,20 +3,33 @@
 */
 #![allow(unused)]
 
-use std::{collections::HashMap, path::{PathBuf, Path}, marker::PhantomData, sync::{Arc, Mutex}, ops::Rem, borrow::Borrow};
+use std::{collections::{HashMap, BTreeMap}, path::{PathBuf, Path}, marker::PhantomData, sync::{Arc, Mutex}, ops::Rem, borrow::Borrow};
 
 use glam::I64Vec3;
 
-use crate::{McResult, McError, nbt::tag::NamedTag, math::bounds::{Bounds2, Bounds3}};
+use crate::{McResult, McError, ResultExt, CancellationToken, nbt::{io::read_nbt_auto, tag::{NamedTag, Tag, ListTag, DecodeNbt, EncodeNbt}}, math::bounds::{Bounds2, Bounds3}};
 use super::container::*;
 
 use super::{
     blockregistry::BlockRegistry,
     blockstate::*,
-    chunk::{Chunk, decode_chunk},
+    chunk::{BlockEntity, Chunk, decode_chunk_for_format, decode_versioned_chunk},
+    chunkversion::ChunkLayout,
+    chunkstatus::{chunk_nbt_has_status, ChunkStatus},
+    dimension::DimensionRegistry,
+    level::{LevelData, WorldBorder},
+    lock::WorldLock,
+    item::{FoundItem, ItemStack, block_entity_items, chunk_items, entity_items, player_items},
+    player::{player_data_path, player_uuids},
+    entity::{Entity, EntityChunk},
+    iter::{ChunkIter, RegionIter},
+    selection::{ChunkSelection, Selection},
+    structurestart::{structure_matches, ChunkStructures, StructureStart},
+    lighting::{LightProperties, LightVolume, vanilla_light_properties},
     io::region::{
         RegionFile,
         coord::RegionCoord,
+        filename::RegionFileName,
         regionfile::{
             RegionManager,
         },
@@ -75,7 +88,7 @@ impl<T> CubeNeighbors<T> {
 // }
 
 pub struct RegionSlot {
-    region: RegionFile,
+    pub(crate) region: RegionFile,
     load_count: usize,
 }
 
@@ -126,8 +139,108 @@ impl ChunkSlot {
     }
 }
 
-type ArcChunkSlot = Arc<Mutex<ChunkSlot>>;
-type ArcRegionSlot = Arc<Mutex<RegionSlot>>;
+/// Tracks the order in which chunks were last used so that the least
+/// recently used chunk can be evicted.
+#[derive(Debug, Default)]
+struct ChunkUsage {
+    tick: u64,
+    ticks: HashMap<WorldCoord, u64>,
+    order: BTreeMap<u64, WorldCoord>,
+}
+
+impl ChunkUsage {
+    fn touch(&mut self, coord: WorldCoord) {
+        self.tick += 1;
+        if let Some(old_tick) = self.ticks.insert(coord, self.tick) {
+            self.order.remove(&old_tick);
+        }
+        self.order.insert(self.tick, coord);
+    }
+
+    fn remove(&mut self, coord: WorldCoord) {
+        if let Some(tick) = self.ticks.remove(&coord) {
+            self.order.remove(&tick);
+        }
+    }
+
+    /// The least recently used chunk that isn't `except`.
+    fn least_recent(&self, except: WorldCoord) -> Option<WorldCoord> {
+        self.order.values()
+            .copied()
+            .find(|&coord| coord != except)
+    }
+}
+
+// Lock ordering:
+// A chunk lock may be held while acquiring a region lock (see `save_chunk`),
+// but a chunk lock must never be acquired while a region lock is held.
+// Following this order prevents deadlocks between the two kinds of mutexes.
+pub(crate) type ArcChunkSlot = Arc<Mutex<ChunkSlot>>;
+pub(crate) type ArcRegionSlot = Arc<Mutex<RegionSlot>>;
+
+/// Controls the status fields that [VirtualJavaWorld::save_chunk] writes, which tell the game
+/// what it needs to recalculate when the chunk is loaded.
+/// Other ways of saving use the world's [default options](VirtualJavaWorld::set_save_options).
+///
+/// The default leaves the fields unchanged. [SaveOptions::edited] is meant for chunks whose blocks were edited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveOptions {
+    /// Replaces `Status`. A status other than `minecraft:full` makes the game continue generating the chunk.
+    pub status: Option<String>,
+    /// Sets `isLightOn`. If it's false, the game recalculates the chunk's light when it's loaded.
+    pub light_on: Option<bool>,
+    /// Removes `below_zero_retrogen`, so that the game doesn't generate terrain below the old
+    /// bottom of the world in chunks that were upgraded from before 1.18.
+    pub clear_below_zero_retrogen: bool,
+}
+
+impl SaveOptions {
+    /// Options that leave the status fields unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options for chunks with edited blocks: the chunk is marked as fully generated, its light
+    /// is recalculated by the game, and blocks below the old bottom of the world aren't generated.
+    pub fn edited() -> Self {
+        Self {
+            status: Some("minecraft:full".to_owned()),
+            light_on: Some(false),
+            clear_below_zero_retrogen: true,
+        }
+    }
+
+    /// Replaces `Status` with `status`.
+    pub fn with_status<S: AsRef<str>>(mut self, status: S) -> Self {
+        self.status = Some(status.as_ref().to_owned());
+        self
+    }
+
+    /// Sets `isLightOn` to `light_on`.
+    pub fn with_light_on(mut self, light_on: bool) -> Self {
+        self.light_on = Some(light_on);
+        self
+    }
+
+    /// Removes `below_zero_retrogen` if `clear` is true.
+    pub fn with_below_zero_retrogen_cleared(mut self, clear: bool) -> Self {
+        self.clear_below_zero_retrogen = clear;
+        self
+    }
+
+    /// Applies the options to a chunk.
+    pub fn apply(&self, chunk: &mut Chunk) {
+        if let Some(status) = &self.status {
+            chunk.status = status.clone();
+        }
+        if let Some(light_on) = self.light_on {
+            chunk.other.insert("isLightOn".to_owned(), Tag::Byte(light_on as i8));
+        }
+        if self.clear_below_zero_retrogen {
+            chunk.other.remove("below_zero_retrogen");
+        }
+    }
+}
 
 /*
 VirtualJavaWorld is for testing purposes. I plan on rewriting the entire
@@ -138,6 +251,17 @@ pub struct VirtualJavaWorld {
     pub chunks: HashMap<WorldCoord, ArcChunkSlot>,
     pub regions: HashMap<WorldCoord, ArcRegionSlot>,
     pub directory: PathBuf,
+    /// The contents of `level.dat`, if it was loaded.
+    pub level_data: Option<LevelData>,
+    /// The custom dimensions that [Dimension::Other] ids refer to.
+    pub dimensions: DimensionRegistry,
+    /// The maximum number of chunks that can be loaded at once.
+    chunk_limit: Option<usize>,
+    chunk_usage: Mutex<ChunkUsage>,
+    /// The options used when chunks are saved without [VirtualJavaWorld::save_chunk].
+    save_options: SaveOptions,
+    /// The world's `session.lock`, if the world was opened with [VirtualJavaWorld::open_locked].
+    lock: Option<WorldLock>,
 }
 
 // I would like to implement a system where I keep track of
@@ -145,23 +269,345 @@ pub struct VirtualJavaWorld {
 // a region when there are no more chunks.
 
 impl VirtualJavaWorld {
+    /// Opens the world at `directory`.
+    /// If the world has a `level.dat` that can be read, [VirtualJavaWorld::level_data] is populated.
+    /// Use [VirtualJavaWorld::load_level_data] to find out why it couldn't be read.
+    /// The custom dimensions in the `dimensions` directory are [discovered](DimensionRegistry::discover)
+    /// and registered in [VirtualJavaWorld::dimensions].
     pub fn open(directory: impl AsRef<Path>) -> Self {
+        let directory = directory.as_ref().to_owned();
+        let level_data = LevelData::read_from_file(directory.join("level.dat")).ok();
+        let dimensions = DimensionRegistry::discover(&directory).unwrap_or_default();
         Self {
             block_registry: BlockRegistry::with_air(),
             chunks: HashMap::new(),
             regions: HashMap::new(),
-            directory: directory.as_ref().to_owned(),
+            directory,
+            level_data,
+            dimensions,
+            chunk_limit: None,
+            chunk_usage: Mutex::new(ChunkUsage::default()),
+            save_options: SaveOptions::default(),
+            lock: None,
+        }
+    }
+
+    /// Puts a world back together from a [SharedWorld](super::shared::SharedWorld).
+    /// The chunks are used in an arbitrary order.
+    pub(crate) fn from_shared_parts(
+        directory: PathBuf,
+        block_registry: BlockRegistry,
+        level_data: Option<LevelData>,
+        dimensions: DimensionRegistry,
+        chunks: HashMap<WorldCoord, ArcChunkSlot>,
+        regions: HashMap<WorldCoord, ArcRegionSlot>,
+        lock: Option<WorldLock>,
+    ) -> Self {
+        let mut usage = ChunkUsage::default();
+        chunks.keys().for_each(|&coord| usage.touch(coord));
+        Self {
+            block_registry,
+            chunks,
+            regions,
+            directory,
+            level_data,
+            dimensions,
+            chunk_limit: None,
+            chunk_usage: Mutex::new(usage),
+            save_options: SaveOptions::default(),
+            lock,
+        }
+    }
+
+    /// Takes the world's `session.lock` so that it can be moved to a [SharedWorld](super::shared::SharedWorld).
+    pub(crate) fn take_lock(&mut self) -> Option<WorldLock> {
+        self.lock.take()
+    }
+
+    /// Opens the world at `directory` like [VirtualJavaWorld::open], but first acquires the world's
+    /// `session.lock` so that the game can't open the world while it's being edited.
+    /// The lock is held until the world is dropped.
+    /// Returns [McError::WorldLocked] if the game, a server, or another tool is using the world.
+    pub fn open_locked(directory: impl AsRef<Path>) -> McResult<Self> {
+        let lock = WorldLock::acquire(directory.as_ref())?;
+        let mut world = Self::open(directory);
+        world.lock = Some(lock);
+        Ok(world)
+    }
+
+    /// Returns true if the world's `session.lock` is held (see [VirtualJavaWorld::open_locked]).
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    /// Get the path of the world's `level.dat`.
+    pub fn get_level_data_path(&self) -> PathBuf {
+        self.directory.join("level.dat")
+    }
+
+    /// Reads `level.dat`, replacing [VirtualJavaWorld::level_data].
+    pub fn load_level_data(&mut self) -> McResult<&mut LevelData> {
+        let level_data = LevelData::read_from_file(self.get_level_data_path())?;
+        Ok(self.level_data.insert(level_data))
+    }
+
+    /// Saves [VirtualJavaWorld::level_data] to `level.dat`, backing up the previous file
+    /// to `level.dat_old`. Does nothing if there is no level data.
+    pub fn save_level_data(&self) -> McResult<()> {
+        if let Some(level_data) = &self.level_data {
+            level_data.save(self.get_level_data_path())?;
         }
+        Ok(())
+    }
+
+    /// The world border of a dimension, from [VirtualJavaWorld::level_data].
+    /// Returns `None` if there is no level data.
+    pub fn world_border(&self, dimension: Dimension) -> Option<WorldBorder> {
+        self.level_data.as_ref().map(|level_data| level_data.border.for_dimension(dimension))
+    }
+
+    /// Returns true if any part of the chunk is within the world border of its dimension.
+    /// Without level data, every chunk is considered to be within the border.
+    pub fn is_within_border(&self, coord: WorldCoord) -> bool {
+        self.world_border(coord.dimension)
+            .is_none_or(|border| border.contains_chunk(coord.xz()))
+    }
+
+    /// The Overworld chunks that the game keeps loaded around the spawn point (see [LevelData::spawn_chunks]).
+    /// Empty if there is no level data.
+    pub fn spawn_chunks(&self) -> ChunkSelection {
+        self.level_data.as_ref()
+            .map(LevelData::spawn_chunks)
+            .unwrap_or_default()
+    }
+
+    /// Limits the number of chunks that can be loaded at once (`None` for no limit).
+    /// When loading a chunk would exceed the limit, the least recently used chunks
+    /// are saved (if dirty) and unloaded. Chunks are used when they are loaded or
+    /// retrieved with [VirtualJavaWorld::get_chunk], which includes getting and
+    /// setting blocks.
+    /// If the limit is lowered below the number of loaded chunks, chunks are evicted immediately.
+    pub fn set_chunk_limit(&mut self, limit: Option<usize>) -> McResult<()> {
+        self.chunk_limit = limit;
+        self.evict_chunks(None)
+    }
+
+    /// The maximum number of chunks that can be loaded at once.
+    pub fn chunk_limit(&self) -> Option<usize> {
+        self.chunk_limit
+    }
+
+    /// Sets the options applied to chunks saved by [VirtualJavaWorld::save_all], [VirtualJavaWorld::save_dirty],
+    /// [VirtualJavaWorld::save_area], and chunk eviction. The default leaves the status fields unchanged.
+    pub fn set_save_options(&mut self, options: SaveOptions) {
+        self.save_options = options;
+    }
+
+    /// The options applied to chunks saved without [VirtualJavaWorld::save_chunk].
+    pub fn save_options(&self) -> &SaveOptions {
+        &self.save_options
+    }
+
+    /// Saves and unloads least recently used chunks until the chunk limit is satisfied.
+    /// The chunk at `keep` is never evicted.
+    fn evict_chunks(&mut self, keep: Option<WorldCoord>) -> McResult<()> {
+        let Some(limit) = self.chunk_limit else {
+            return Ok(());
+        };
+        while self.chunks.len() > limit {
+            let oldest = {
+                let Ok(mut usage) = self.chunk_usage.lock() else {
+                    return McError::custom("Failed to lock chunk usage.");
+                };
+                let oldest = match keep {
+                    Some(keep) => usage.least_recent(keep),
+                    None => usage.order.values().next().copied(),
+                };
+                // Usage entries for chunks that are no longer loaded are stale, so drop them.
+                match oldest {
+                    Some(coord) if !self.chunks.contains_key(&coord) => {
+                        usage.remove(coord);
+                        continue;
+                    }
+                    oldest => oldest,
+                }
+            };
+            let Some(oldest) = oldest else {
+                break;
+            };
+            let options = self.save_options.clone();
+            self.save_chunk(oldest, &options)?;
+            self.unload_chunk(oldest);
+        }
+        Ok(())
+    }
+
+    fn touch_chunk(&self, coord: WorldCoord) {
+        if let Ok(mut usage) = self.chunk_usage.lock() {
+            usage.touch(coord);
+        }
+    }
+
+    /// Get the root directory of each dimension.
+    /// Custom dimensions are in `dimensions/<namespace>/<name>`.
+    ///
+    /// Returns [McError::UnknownDimension] for a [Dimension::Other] that isn't registered in [VirtualJavaWorld::dimensions].
+    pub fn get_dimension_directory(&self, dimension: Dimension) -> McResult<PathBuf> {
+        self.dimensions.directory(&self.directory, dimension)
+            .ok_or(McError::UnknownDimension(dimension))
     }
 
     /// Get the directory that the region files are located at for each dimension.
-    pub fn get_region_directory(&self, dimension: Dimension) -> PathBuf {
-        self.directory.join(match dimension {
-            Dimension::Overworld => "region",
-            Dimension::Nether => "Dim-1/region",
-            Dimension::TheEnd => "Dim1/region",
-            Dimension::Other(_) => todo!(),
-        })
+    pub fn get_region_directory(&self, dimension: Dimension) -> McResult<PathBuf> {
+        Ok(self.get_dimension_directory(dimension)?.join("region"))
+    }
+
+    /// Finds the region files of a dimension, yielding their region coordinates.
+    pub fn iter_regions(&self, dimension: Dimension) -> McResult<RegionIter> {
+        RegionIter::new(self.get_region_directory(dimension)?, dimension)
+    }
+
+    /// Reads every chunk of a dimension from its region files, one region at a time.
+    /// Chunks are read from disk, so unsaved changes to loaded chunks aren't included.
+    /// The NBT can be decoded with [decode_chunk_for_format].
+    pub fn iter_chunks(&self, dimension: Dimension) -> McResult<ChunkIter> {
+        self.iter_regions(dimension).map(ChunkIter::new)
+    }
+
+    /// Get the directory that the POI (point of interest) region files are located at for each dimension.
+    pub fn get_poi_directory(&self, dimension: Dimension) -> McResult<PathBuf> {
+        Ok(self.get_dimension_directory(dimension)?.join("poi"))
+    }
+
+    /// Opens the POI region file for the region coordinate.
+    /// Unlike block regions, the file is not created if it doesn't exist.
+    pub fn read_poi_region(&self, coord: WorldCoord) -> McResult<RegionFile> {
+        let regname = coord.region_file_name();
+        RegionFile::open(self.get_poi_directory(coord.dimension)?.join(regname))
+    }
+
+    /// Reads the POI data for a chunk.
+    pub fn read_chunk_poi(&self, coord: WorldCoord) -> McResult<NamedTag> {
+        let mut region = self.read_poi_region(coord.region_coord())?;
+        region.read_data(coord.xz())
+    }
+
+    /// Get the directory that the entity region files are located at for each dimension.
+    /// Entities have been stored separately from chunks since Minecraft 1.17.
+    pub fn get_entities_directory(&self, dimension: Dimension) -> McResult<PathBuf> {
+        Ok(self.get_dimension_directory(dimension)?.join("entities"))
+    }
+
+    /// Opens the entities region file for the region coordinate.
+    /// Unlike block regions, the file is not created if it doesn't exist.
+    pub fn read_entities_region(&self, coord: WorldCoord) -> McResult<RegionFile> {
+        let regname = coord.region_file_name();
+        RegionFile::open(self.get_entities_directory(coord.dimension)?.join(regname))
+    }
+
+    /// Loads the entities of a chunk from the `entities` region folder.
+    /// Returns `None` if the region file doesn't exist or has no entry for the chunk.
+    pub fn load_entities(&self, coord: WorldCoord) -> McResult<Option<NamedTag>> {
+        Self::load_optional_chunk(self.get_entities_directory(coord.dimension)?, coord)
+    }
+
+    /// Loads and decodes the entities of a chunk from the `entities` region folder.
+    /// Returns `None` if the chunk has no entity data.
+    pub fn load_entity_chunk(&self, coord: WorldCoord) -> McResult<Option<EntityChunk>> {
+        self.load_entities(coord)?
+            .map(|tag| EntityChunk::decode_nbt(tag.take_tag()))
+            .transpose()
+    }
+
+    /// Writes the entities of a chunk to the `entities` region folder.
+    pub fn save_entity_chunk(&self, coord: WorldCoord, chunk: EntityChunk) -> McResult<()> {
+        let directory = self.get_entities_directory(coord.dimension)?;
+        std::fs::create_dir_all(&directory)?;
+        let region_coord = coord.region_coord();
+        let mut region = RegionFile::open_or_create(directory.join(region_coord.region_file_name()))?;
+        region.write_data(coord.xz(), &NamedTag::new(chunk.encode_nbt()))?;
+        Ok(())
+    }
+
+    /// Finds the entities in the `entities` region folder whose positions are within the
+    /// blocks from `min` to `max` (inclusive). The dimension of `min` is used.
+    pub fn entities_in_box(&self, min: BlockCoord, max: BlockCoord) -> McResult<Vec<Entity>> {
+        let mut found = Vec::new();
+        self.for_each_entity_chunk_in_box(min, max, |_, chunk| {
+            found.extend(chunk.entities.iter()
+                .filter(|entity| entity_in_box(entity, min, max))
+                .cloned());
+            Ok(false)
+        })?;
+        Ok(found)
+    }
+
+    /// Removes the entities within the blocks from `min` to `max` (inclusive) that `remove`
+    /// returns true for, writing the changed chunks back to the `entities` region folder.
+    /// Returns the number of entities that were removed.
+    pub fn remove_entities_in_box<F: FnMut(&Entity) -> bool>(&self, min: BlockCoord, max: BlockCoord, mut remove: F) -> McResult<usize> {
+        let mut removed = 0;
+        self.for_each_entity_chunk_in_box(min, max, |_, chunk| {
+            let count = chunk.entities.len();
+            chunk.entities.retain(|entity| !(entity_in_box(entity, min, max) && remove(entity)));
+            removed += count - chunk.entities.len();
+            Ok(count != chunk.entities.len())
+        })?;
+        Ok(removed)
+    }
+
+    /// Calls `f` with each entity chunk that overlaps the blocks from `min` to `max`.
+    /// If `f` returns true, the chunk is written back to its region file.
+    fn for_each_entity_chunk_in_box<F>(&self, min: BlockCoord, max: BlockCoord, mut f: F) -> McResult<()>
+    where F: FnMut(WorldCoord, &mut EntityChunk) -> McResult<bool> {
+        let directory = self.get_entities_directory(min.dimension)?;
+        let (min_x, max_x) = (min.x.min(max.x).div_euclid(16), min.x.max(max.x).div_euclid(16));
+        let (min_z, max_z) = (min.z.min(max.z).div_euclid(16), min.z.max(max.z).div_euclid(16));
+        for region_z in min_z.div_euclid(32)..=max_z.div_euclid(32) {
+            for region_x in min_x.div_euclid(32)..=max_x.div_euclid(32) {
+                let path = RegionFileName::new(region_x, region_z).path(&directory);
+                if !path.is_file() {
+                    continue;
+                }
+                let mut region = RegionFile::open(path)?;
+                for z in min_z.max(region_z * 32)..=max_z.min(region_z * 32 + 31) {
+                    for x in min_x.max(region_x * 32)..=max_x.min(region_x * 32 + 31) {
+                        let coord = WorldCoord::new(x, z, min.dimension);
+                        let tag: NamedTag = match region.read_data(coord.xz()) {
+                            Ok(tag) => tag,
+                            Err(McError::RegionDataNotFound) => continue,
+                            Err(err) => return Err(err),
+                        };
+                        let mut chunk = EntityChunk::decode_nbt(tag.take_tag())?;
+                        if f(coord, &mut chunk)? {
+                            region.write_data(coord.xz(), &NamedTag::new(chunk.encode_nbt()))?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the POI data of a chunk from the `poi` region folder.
+    /// Returns `None` if the region file doesn't exist or has no entry for the chunk.
+    pub fn load_poi(&self, coord: WorldCoord) -> McResult<Option<NamedTag>> {
+        Self::load_optional_chunk(self.get_poi_directory(coord.dimension)?, coord)
+    }
+
+    /// Reads a chunk's NBT from a region folder that doesn't always have a region file for every region.
+    fn load_optional_chunk(directory: PathBuf, coord: WorldCoord) -> McResult<Option<NamedTag>> {
+        let region_coord = coord.region_coord();
+        let path = directory.join(region_coord.region_file_name());
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let mut region = RegionFile::open(path)?;
+        match region.read_data(coord.xz()) {
+            Ok(tag) => Ok(Some(tag)),
+            Err(McError::RegionDataNotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
     }
 
     /// Loads a region file into memory so that it IO can be performed.
@@ -169,8 +615,8 @@ impl VirtualJavaWorld {
         if let Some(slot) = self.regions.get(&coord) {
             Ok(slot.clone())
         } else {
-            let regiondir = self.get_region_directory(coord.dimension);
-            let regname = format!("r.{}.{}.mca", coord.x, coord.z);
+            let regiondir = self.get_region_directory(coord.dimension)?;
+            let regname = coord.region_file_name();
             let regfilepath = regiondir.join(regname);
             let regionfile = RegionFile::open_or_create(regfilepath)?;
             let slot = RegionSlot::arc_new(regionfile);
@@ -187,7 +633,8 @@ impl VirtualJavaWorld {
         let reglock = region.lock();
         if let Ok(mut regionlock) = reglock {
             let root = regionlock.region.read_data::<_, NamedTag>(coord.xz())?;
-            let chunk = decode_chunk(&mut self.block_registry, root.tag)?;
+            let format = regionlock.region.format();
+            let chunk = decode_chunk_for_format(&mut self.block_registry, root.tag, format).with_chunk(coord)?;
             let slot = ChunkSlot::arc_new(chunk);
             let old = self.chunks.insert(coord, slot.clone());
             // If there was already a chunk loaded at this coord, there's no need
@@ -195,6 +642,9 @@ impl VirtualJavaWorld {
             if old.is_none() {
                 regionlock.increment();
             }
+            drop(regionlock);
+            self.touch_chunk(coord);
+            self.evict_chunks(Some(coord))?;
             Ok(slot)
         } else {
             McError::custom("Failed to lock region file.")
@@ -224,16 +674,22 @@ impl VirtualJavaWorld {
 
     /// Get a chunk (if it has been loaded).
     pub fn get_chunk(&self, coord: WorldCoord) -> Option<ArcChunkSlot> {
-        self.chunks.get(&coord).map(|slot| slot.clone())
+        let slot = self.chunks.get(&coord).cloned();
+        if slot.is_some() {
+            self.touch_chunk(coord);
+        }
+        slot
     }
 
-    /// Attempts to save a chunk (assuming the chunk has already been loaded)
-    pub fn save_chunk(&mut self, coord: WorldCoord) -> McResult<()> {
+    /// Attempts to save a chunk (assuming the chunk has already been loaded).
+    /// The `options` are applied to the loaded chunk before it's written. Chunks that aren't dirty aren't saved.
+    pub fn save_chunk(&mut self, coord: WorldCoord, options: &SaveOptions) -> McResult<()> {
         if let Some(slot) = self.get_chunk(coord) {
             if let Ok(mut slot) = slot.lock() {
                 if !slot.dirty {
                     return Ok(());
                 }
+                options.apply(&mut slot.chunk);
                 let region = self.get_or_load_region(coord.region_coord())?;
                 let reglock = region.lock();
                 if let Ok(mut region) = reglock {
@@ -251,24 +707,101 @@ impl VirtualJavaWorld {
 
     pub fn save_area<T: Into<Bounds2>>(&mut self, dimension: Dimension, bounds: T) -> McResult<()> {
         let bounds: Bounds2 = bounds.into();
+        let options = self.save_options.clone();
         (bounds.min.y..=bounds.max.y).try_for_each(|y| {
             (bounds.min.x..=bounds.max.x).try_for_each(|x| {
-                self.save_chunk(WorldCoord::new(x, y, dimension))?;
+                self.save_chunk(WorldCoord::new(x, y, dimension), &options)?;
                 McResult::Ok(())
             })
         })
     }
 
+    /// Saves every loaded chunk, whether or not it has been modified.
+    /// See [VirtualJavaWorld::save_dirty] for how chunks are written.
     pub fn save_all(&mut self) -> McResult<()> {
-        let keys_clone = self.chunks.keys().map(|c| *c).collect::<Box<[WorldCoord]>>();
-        keys_clone.into_iter().try_for_each(|coord| {
-            self.save_chunk(*coord)
-        })
+        self.save_chunks(false)
+    }
+
+    /// Saves all dirty chunks.
+    ///
+    /// This happens in two phases so that no chunk lock is held while a region lock is held:
+    /// 1. Each dirty chunk is locked, encoded, and marked clean, then its lock is released.
+    /// 2. The encoded chunks are grouped by region, and each group is written under a single
+    ///    region lock.
+    ///
+    /// If a write fails, the chunks that were not written are marked dirty again (after the region
+    /// lock is released) and the error is returned.
+    /// A chunk whose lock is poisoned is skipped, the rest are still saved, and
+    /// [McError::FailedToSaveChunk] is returned.
+    pub fn save_dirty(&mut self) -> McResult<()> {
+        self.save_chunks(true)
+    }
+
+    fn save_chunks(&mut self, dirty_only: bool) -> McResult<()> {
+        // Phase 1: Collect chunks.
+        // A chunk whose lock is poisoned is skipped (and left as it is) rather than
+        // aborting the save, since chunks collected before it have already been marked clean.
+        let mut result = Ok(());
+        let mut groups = HashMap::<WorldCoord, Vec<(WorldCoord, NamedTag)>>::new();
+        for (coord, slot) in self.chunks.iter() {
+            let Ok(mut slot) = slot.lock() else {
+                result = Err(McError::FailedToSaveChunk);
+                continue;
+            };
+            if dirty_only && !slot.dirty {
+                continue;
+            }
+            self.save_options.apply(&mut slot.chunk);
+            let root = NamedTag::new(slot.chunk.to_nbt(&self.block_registry));
+            // Marking the chunk clean now means that any edits made after this point
+            // will mark it dirty again.
+            slot.dirty = false;
+            groups.entry(coord.region_coord()).or_default().push((*coord, root));
+        }
+        // Phase 2: Write each group under a single region lock.
+        let mut unsaved = Vec::<WorldCoord>::new();
+        let mut write_failed = false;
+        for (region_coord, chunks) in groups {
+            if write_failed {
+                unsaved.extend(chunks.into_iter().map(|(coord, _)| coord));
+                continue;
+            }
+            let written = match self.get_or_load_region(region_coord) {
+                Ok(region) => {
+                    if let Ok(mut region) = region.lock() {
+                        chunks.iter().enumerate().try_for_each(|(index, (coord, root))| {
+                            region.region.write_data_with_utcnow(coord.xz(), root)
+                                .map(|_| ())
+                                .map_err(|err| (index, err))
+                        })
+                    } else {
+                        Err((0, McError::FailedToSaveChunk))
+                    }
+                }
+                Err(err) => Err((0, err)),
+            };
+            if let Err((index, err)) = written {
+                unsaved.extend(chunks[index..].iter().map(|(coord, _)| *coord));
+                write_failed = true;
+                result = Err(err);
+            }
+        }
+        // The region locks have been released, so it's safe to lock chunks again.
+        for coord in unsaved {
+            if let Some(slot) = self.get_chunk(coord) {
+                if let Ok(mut slot) = slot.lock() {
+                    slot.mark_dirty();
+                }
+            }
+        }
+        result
     }
 
     /// Remove a chunk from internal storage.
     pub fn unload_chunk(&mut self, coord: WorldCoord) -> Option<ArcChunkSlot> {
-        
+        if let Ok(mut usage) = self.chunk_usage.lock() {
+            usage.remove(coord);
+        }
         if !self.chunks.contains_key(&coord) {
             return None;
         }
@@ -302,6 +835,9 @@ impl VirtualJavaWorld {
     pub fn unload_all(&mut self) {
         self.chunks.clear();
         self.regions.clear();
+        if let Ok(mut usage) = self.chunk_usage.lock() {
+            *usage = ChunkUsage::default();
+        }
     }
 
     /// Get a block id at the given coordinate.
@@ -323,6 +859,29 @@ impl VirtualJavaWorld {
         }
     }
 
+    /// Get the biome at the given coordinate.
+    /// Returns `None` if the chunk isn't loaded or the section doesn't have biomes.
+    pub fn get_biome(&self, coord: BlockCoord) -> Option<String> {
+        let slot = self.get_chunk(coord.chunk_coord())?;
+        let slot = slot.lock().ok()?;
+        slot.chunk.get_biome(coord.xyz()).map(str::to_owned)
+    }
+
+    /// Set the biome at a coordinate, loading the chunk first if it isn't loaded.
+    /// Biomes are stored in 4x4x4 cells, so this changes the biome of the whole cell.
+    /// The chunk is marked dirty if the biome changed. This will return the old biome.
+    pub fn set_biome<S: AsRef<str>>(&mut self, coord: BlockCoord, biome: S) -> McResult<Option<String>> {
+        let slot = self.get_or_load_chunk(coord.chunk_coord())?;
+        let Ok(mut slot) = slot.lock() else {
+            return McError::custom("Failed to lock chunk.");
+        };
+        let old = slot.chunk.set_biome(coord.xyz(), biome.as_ref());
+        if old.as_deref() != Some(biome.as_ref()) {
+            slot.mark_dirty();
+        }
+        Ok(old)
+    }
+
     /// Set a block id, returning the old block id.
     /// (This function does not check that the ids are the same)
     pub fn set_id(&mut self, coord: BlockCoord, id: u32) -> Option<u32> {
@@ -344,13 +903,580 @@ impl VirtualJavaWorld {
     }
 
     /// Set the block state at a coordinate. This will return the old block state.
+    /// The chunk must already be loaded, otherwise nothing happens and `None` is returned.
+    /// Nothing happens either if the block registry rejects the state (see [BlockRegistry::try_register]).
+    /// Prefer [VirtualJavaWorld::set_block_state_loaded], which loads the chunk if needed.
     pub fn set_state<T: Borrow<BlockState>>(&mut self, coord: BlockCoord, state: T) -> Option<&BlockState> {
-        let id = self.block_registry.register(state.borrow());
+        let id = self.block_registry.try_register(state.borrow()).ok()?;
         self.set_id(coord, id).and_then(|id| {
             self.block_registry.get(id)
         })
     }
 
+    /// Set the block state at a coordinate, loading the chunk first if it isn't loaded.
+    /// The chunk is marked dirty if the block changed. This will return the old block state.
+    /// This is the recommended way to set blocks.
+    /// Light isn't updated, so use [VirtualJavaWorld::relight_chunks] after placing or removing light sources.
+    /// If the block registry validates states (see [BlockRegistry::try_register]), invalid states return an error.
+    pub fn set_block_state_loaded<T: Borrow<BlockState>>(&mut self, coord: BlockCoord, state: T) -> McResult<Option<BlockState>> {
+        let id = self.block_registry.try_register(state.borrow())?;
+        let slot = self.get_or_load_chunk(coord.chunk_coord())?;
+        let Ok(mut slot) = slot.lock() else {
+            return McError::custom("Failed to lock chunk.");
+        };
+        let old_id = slot.chunk.set_id(coord.xyz(), id);
+        if old_id != Some(id) {
+            slot.mark_dirty();
+        }
+        Ok(old_id.and_then(|old_id| self.block_registry.get(old_id)).cloned())
+    }
+
+    /// Recalculates the light of the loaded chunks at `coords` and their loaded neighbors
+    /// using [vanilla_light_properties]. Light from other loaded chunks that border those
+    /// chunks spreads into them, but the light of those chunks isn't changed.
+    /// Relit chunks are marked dirty. Chunks that aren't loaded are ignored.
+    pub fn relight_chunks(&mut self, coords: &[WorldCoord]) -> McResult<()> {
+        self.relight_chunks_with(coords, vanilla_light_properties)
+    }
+
+    /// Like [VirtualJavaWorld::relight_chunks], but the light properties of blocks are provided by `properties`.
+    pub fn relight_chunks_with<F: Fn(&BlockState) -> LightProperties>(&mut self, coords: &[WorldCoord], properties: F) -> McResult<()> {
+        // Light is calculated separately for each dimension.
+        let mut dimensions: HashMap<Dimension, Vec<WorldCoord>> = HashMap::new();
+        for coord in coords {
+            dimensions.entry(coord.dimension).or_default().push(*coord);
+        }
+        for (dimension, coords) in dimensions {
+            let mut targets = Vec::new();
+            for coord in coords {
+                for z in coord.z - 1..=coord.z + 1 {
+                    for x in coord.x - 1..=coord.x + 1 {
+                        let target = WorldCoord::new(x, z, dimension);
+                        if self.chunks.contains_key(&target) && !targets.contains(&target) {
+                            targets.push(target);
+                        }
+                    }
+                }
+            }
+            let mut borders = Vec::new();
+            for target in targets.iter() {
+                for (x, z) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                    let border = WorldCoord::new(target.x + x, target.z + z, dimension);
+                    if self.chunks.contains_key(&border) && !targets.contains(&border) && !borders.contains(&border) {
+                        borders.push(border);
+                    }
+                }
+            }
+            let slots = targets.iter().map(|coord| (&self.chunks[coord], true))
+                .chain(borders.iter().map(|coord| (&self.chunks[coord], false)))
+                .collect::<Vec<_>>();
+            let mut guards = Vec::with_capacity(slots.len());
+            for (slot, writable) in slots {
+                let Ok(guard) = slot.lock() else {
+                    return McError::custom("Failed to lock chunk.");
+                };
+                guards.push((guard, writable));
+            }
+            let mut volume = LightVolume::new(&self.block_registry, &properties);
+            for (guard, writable) in guards.iter_mut() {
+                volume.insert(&mut guard.chunk, *writable);
+            }
+            volume.relight(!matches!(dimension, Dimension::Nether | Dimension::TheEnd));
+            drop(volume);
+            for (guard, writable) in guards.iter_mut() {
+                if *writable {
+                    guard.mark_dirty();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills the blocks from `min` to `max` (inclusive) with `state`, loading chunks as needed.
+    /// The dimension of `min` is used, and blocks above or below the chunks' sections are skipped.
+    /// Chunks with changed blocks are marked dirty. Returns the number of blocks that changed.
+    pub fn fill<T: Borrow<BlockState>>(&mut self, min: BlockCoord, max: BlockCoord, state: T) -> McResult<u64> {
+        let id = self.block_registry.register(state.borrow());
+        let mut changed = 0;
+        self.edit_chunks_in_box(min, max, |chunk, low, high| {
+            let before = changed;
+            for y in low.1..=high.1 {
+                for z in low.2..=high.2 {
+                    for x in low.0..=high.0 {
+                        if chunk.set_id((x, y, z), id) != Some(id) {
+                            changed += 1;
+                        }
+                    }
+                }
+            }
+            Ok(changed != before)
+        })?;
+        Ok(changed)
+    }
+
+    /// Fills the blocks of a [Selection] with `state`, loading chunks as needed.
+    /// Blocks above or below the chunks' sections are skipped.
+    /// Chunks with changed blocks are marked dirty. Returns the number of blocks that changed.
+    pub fn fill_selection<S: Selection + ?Sized, T: Borrow<BlockState>>(&mut self, dimension: Dimension, selection: &S, state: T) -> McResult<u64> {
+        let Some(bounds) = selection.bounds() else {
+            return Ok(0);
+        };
+        let id = self.block_registry.register(state.borrow());
+        let mut changed = 0;
+        for (chunk_x, chunk_z) in selection.chunks() {
+            let min = BlockCoord::new(chunk_x * 16, bounds.min.y, chunk_z * 16, dimension);
+            let max = BlockCoord::new(chunk_x * 16 + 15, bounds.max.y, chunk_z * 16 + 15, dimension);
+            self.edit_chunks_in_box(min, max, |chunk, low, high| {
+                let before = changed;
+                for y in low.1..=high.1 {
+                    for z in low.2..=high.2 {
+                        for x in low.0..=high.0 {
+                            if selection.contains((x, y, z)) && chunk.set_id((x, y, z), id) != Some(id) {
+                                changed += 1;
+                            }
+                        }
+                    }
+                }
+                Ok(changed != before)
+            })?;
+        }
+        Ok(changed)
+    }
+
+    /// Replaces the blocks of a [Selection] that `filter` returns true for with `replacement`, loading chunks
+    /// as needed and removing the block entities of the replaced blocks.
+    /// Chunks with replaced blocks are marked dirty. Returns the number of blocks that were replaced.
+    pub fn replace_in_selection<S, F, T>(&mut self, dimension: Dimension, selection: &S, filter: F, replacement: T) -> McResult<u64>
+    where S: Selection + ?Sized, F: Fn(&BlockState) -> bool, T: Borrow<BlockState> {
+        let Some(bounds) = selection.bounds() else {
+            return Ok(0);
+        };
+        let replacement = replacement.borrow();
+        let replacement_id = self.block_registry.register(replacement);
+        let mut matches = Vec::<bool>::new();
+        let mut replaced = 0;
+        for (chunk_x, chunk_z) in selection.chunks() {
+            let slot = self.get_or_load_chunk(WorldCoord::new(chunk_x, chunk_z, dimension))?;
+            // Loading the chunk may have registered new states.
+            matches.extend((matches.len() as u32..self.block_registry.len() as u32)
+                .map(|id| self.block_registry.get(id).is_some_and(|state| state != replacement && filter(state))));
+            let Ok(mut slot) = slot.lock() else {
+                return McError::custom("Failed to lock chunk.");
+            };
+            let height = slot.chunk.height_range();
+            let before = replaced;
+            for y in bounds.min.y.max(height.start)..=bounds.max.y.min(height.end - 1) {
+                for z in chunk_z * 16..chunk_z * 16 + 16 {
+                    for x in chunk_x * 16..chunk_x * 16 + 16 {
+                        if !selection.contains((x, y, z)) {
+                            continue;
+                        }
+                        let Some(id) = slot.chunk.get_id((x, y, z)) else {
+                            continue;
+                        };
+                        if matches.get(id as usize).copied().unwrap_or_default() {
+                            slot.chunk.set_id((x, y, z), replacement_id);
+                            slot.chunk.remove_block_entity((x, y, z));
+                            replaced += 1;
+                        }
+                    }
+                }
+            }
+            if replaced != before {
+                slot.mark_dirty();
+            }
+        }
+        Ok(replaced)
+    }
+
+    /// Deletes the chunk columns that a [Selection] intersects from the dimension's `region`, `entities`,
+    /// and `poi` folders, so that the game generates them again. Loaded chunks that are deleted are unloaded
+    /// without being saved. Use a [ChunkSelection](super::selection::ChunkSelection) to delete exact chunks.
+    /// Returns the number of chunks that were deleted from the `region` folder.
+    pub fn prune_chunks<S: Selection + ?Sized>(&mut self, dimension: Dimension, selection: &S) -> McResult<usize> {
+        let mut pruned = 0;
+        for (chunk_x, chunk_z) in selection.chunks() {
+            let coord = WorldCoord::new(chunk_x, chunk_z, dimension);
+            self.unload_chunk(coord);
+            let region_coord = coord.region_coord();
+            let region_name = region_coord.region_file_name();
+            // A region that the world has open has to be written through the world's handle.
+            if let Some(slot) = self.regions.get(&region_coord) {
+                let Ok(mut slot) = slot.lock() else {
+                    return McError::custom("Failed to lock region.");
+                };
+                pruned += !slot.region.delete_data(coord.xz())?.is_empty() as usize;
+            } else {
+                let path = self.get_region_directory(dimension)?.join(&region_name);
+                if path.is_file() {
+                    pruned += !RegionFile::open(path)?.delete_data(coord.xz())?.is_empty() as usize;
+                }
+            }
+            for directory in [self.get_entities_directory(dimension)?, self.get_poi_directory(dimension)?] {
+                let path = directory.join(&region_name);
+                if path.is_file() {
+                    RegionFile::open(path)?.delete_data(coord.xz())?;
+                }
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Deletes the chunks of a dimension that haven't finished generating (proto-chunks whose
+    /// [status](Chunk::status) isn't [ChunkStatus::Full], or is unknown) with [VirtualJavaWorld::prune_chunks].
+    /// Loaded chunks are judged by their status in memory. Returns the number of chunks that were deleted.
+    pub fn prune_unfinished_chunks(&mut self, dimension: Dimension) -> McResult<usize> {
+        let mut unfinished = ChunkSelection::new();
+        for region_coord in self.iter_regions(dimension)? {
+            let path = self.get_region_directory(dimension)?.join(region_coord.region_file_name());
+            let mut region = RegionFile::open(&path).with_path(&path)?;
+            for index in 0..1024u16 {
+                let coord = RegionCoord::from(index);
+                if region.get_sector(coord).sector_count() == 0 {
+                    continue;
+                }
+                let chunk_coord = region_coord.chunk_in_region(coord);
+                let finished = match self.chunks.get(&chunk_coord) {
+                    Some(slot) => {
+                        let Ok(slot) = slot.lock() else {
+                            return McError::custom("Failed to lock chunk.");
+                        };
+                        slot.chunk.is_fully_generated()
+                    }
+                    None => {
+                        let root = region.read_data::<_, NamedTag>(coord).with_chunk(chunk_coord)?;
+                        chunk_nbt_has_status(root.tag(), ChunkStatus::Full)
+                    }
+                };
+                if !finished {
+                    unfinished.insert(chunk_coord.xz());
+                }
+            }
+        }
+        self.prune_chunks(dimension, &unfinished)
+    }
+
+    /// Copies the blocks and block entities from `src_min` to `src_max` (inclusive) so that
+    /// `src_min` is copied to `dst`, loading chunks as needed. The source and destination
+    /// may overlap. Block entities at the destination are replaced by the copied ones.
+    /// Blocks outside of the chunks' sections are skipped.
+    /// Chunks with changed blocks are marked dirty. Returns the number of blocks that were copied.
+    pub fn clone_region(&mut self, src_min: BlockCoord, src_max: BlockCoord, dst: BlockCoord) -> McResult<u64> {
+        let low = (src_min.x.min(src_max.x), src_min.y.min(src_max.y), src_min.z.min(src_max.z));
+        let high = (src_min.x.max(src_max.x), src_min.y.max(src_max.y), src_min.z.max(src_max.z));
+        let size = (high.0 - low.0 + 1, high.1 - low.1 + 1, high.2 - low.2 + 1);
+        let index = |(x, y, z): (i64, i64, i64)| {
+            (((y - low.1) * size.2 + (z - low.2)) * size.0 + (x - low.0)) as usize
+        };
+        // The source is read before anything is written so that overlapping regions copy correctly.
+        let mut blocks: Vec<Option<u32>> = vec![None; (size.0 * size.1 * size.2) as usize];
+        let mut block_entities = Vec::new();
+        self.edit_chunks_in_box(src_min, src_max, |chunk, chunk_low, chunk_high| {
+            for y in chunk_low.1..=chunk_high.1 {
+                for z in chunk_low.2..=chunk_high.2 {
+                    for x in chunk_low.0..=chunk_high.0 {
+                        blocks[index((x, y, z))] = chunk.get_id((x, y, z));
+                    }
+                }
+            }
+            let in_box = |(x, y, z): (i64, i64, i64)| {
+                (chunk_low.0..=chunk_high.0).contains(&x)
+                    && (chunk_low.1..=chunk_high.1).contains(&y)
+                    && (chunk_low.2..=chunk_high.2).contains(&z)
+            };
+            block_entities.extend(chunk.block_entities.iter()
+                .filter(|entity| in_box(entity.coord()))
+                .cloned());
+            Ok(false)
+        })?;
+        let offset = (dst.x - low.0, dst.y - low.1, dst.z - low.2);
+        let dst_min = BlockCoord::new(dst.x, dst.y, dst.z, dst.dimension);
+        let dst_max = BlockCoord::new(dst.x + size.0 - 1, dst.y + size.1 - 1, dst.z + size.2 - 1, dst.dimension);
+        let mut copied = 0;
+        self.edit_chunks_in_box(dst_min, dst_max, |chunk, chunk_low, chunk_high| {
+            let mut changed = false;
+            for y in chunk_low.1..=chunk_high.1 {
+                for z in chunk_low.2..=chunk_high.2 {
+                    for x in chunk_low.0..=chunk_high.0 {
+                        let Some(id) = blocks[index((x - offset.0, y - offset.1, z - offset.2))] else {
+                            continue;
+                        };
+                        copied += 1;
+                        changed |= chunk.set_id((x, y, z), id) != Some(id);
+                        changed |= chunk.remove_block_entity((x, y, z)).is_some();
+                    }
+                }
+            }
+            for entity in block_entities.iter() {
+                let (x, y, z) = entity.coord();
+                let mut entity = entity.clone();
+                entity.x = (x + offset.0) as i32;
+                entity.y = (y + offset.1) as i32;
+                entity.z = (z + offset.2) as i32;
+                if chunk.contains_coord(entity.coord()) {
+                    chunk.set_block_entity(entity)?;
+                    changed = true;
+                }
+            }
+            Ok(changed)
+        })?;
+        Ok(copied)
+    }
+
+    /// Finds the blocks in a dimension that `filter` returns true for, yielding their coordinates
+    /// one chunk at a time. Loaded chunks are searched first, then the chunks in the dimension's
+    /// region files that aren't loaded.
+    ///
+    /// Chunks are read from disk one at a time and aren't loaded into the world. A chunk is only
+    /// decoded if one of its section palettes contains a matching block state.
+    pub fn find_blocks<'a, F>(&'a self, dimension: Dimension, filter: F) -> McResult<impl Iterator<Item = McResult<BlockCoord>> + 'a>
+    where F: Fn(&BlockState) -> bool + 'a {
+        let filter = std::rc::Rc::new(filter);
+        let loaded_matches = (0..self.block_registry.len() as u32)
+            .map(|id| self.block_registry.get(id).is_some_and(|state| filter(state)))
+            .collect::<Vec<bool>>();
+        let air_matches = filter(&BlockState::air());
+        let loaded = self.chunks.iter()
+            .filter(|(coord, _)| coord.dimension == dimension)
+            .map(|(coord, slot)| (*coord, slot.clone()))
+            .collect::<Vec<_>>();
+        let loaded_coords = loaded.iter().map(|(coord, _)| *coord).collect::<std::collections::HashSet<_>>();
+        let in_loaded = loaded.into_iter().flat_map(move |(_, slot)| {
+            match slot.lock() {
+                Ok(slot) => find_matching(&slot.chunk, &loaded_matches, air_matches, dimension).into_iter().map(Ok).collect(),
+                Err(_) => vec![McError::custom("Failed to lock chunk.")],
+            }
+        });
+        let disk_filter = filter.clone();
+        let on_disk = self.iter_chunks(dimension)?.flat_map(move |chunk| {
+            let (coord, root) = match chunk {
+                Ok(chunk) => chunk,
+                Err(err) => return vec![Err(err)],
+            };
+            if loaded_coords.contains(&coord) || !palette_may_match(root.tag(), &*disk_filter) {
+                return Vec::new();
+            }
+            let mut registry = BlockRegistry::with_air();
+            let chunk = match decode_versioned_chunk(&mut registry, root.take_tag()) {
+                Ok(chunk) => chunk,
+                Err(err) => return vec![Err(err)],
+            };
+            let matches = (0..registry.len() as u32)
+                .map(|id| registry.get(id).is_some_and(|state| disk_filter(state)))
+                .collect::<Vec<bool>>();
+            find_matching(&chunk, &matches, air_matches, dimension).into_iter().map(Ok).collect()
+        });
+        Ok(in_loaded.chain(on_disk))
+    }
+
+    /// Finds the structures of a kind (such as `village` or `minecraft:stronghold`, see [structure_matches]) in a dimension
+    /// by their structure starts, yielding each structure once along with its bounding box. Loaded chunks are searched
+    /// first, then the chunks in the dimension's region files that aren't loaded, which are read without being loaded.
+    pub fn find_structures<'a>(&'a self, dimension: Dimension, kind: &'a str) -> McResult<impl Iterator<Item = McResult<StructureStart>> + 'a> {
+        let loaded = self.chunks.iter()
+            .filter(|(coord, _)| coord.dimension == dimension)
+            .map(|(coord, slot)| (*coord, slot.clone()))
+            .collect::<Vec<_>>();
+        let loaded_coords = loaded.iter().map(|(coord, _)| *coord).collect::<std::collections::HashSet<_>>();
+        let in_loaded = loaded.into_iter().flat_map(move |(_, slot)| {
+            match slot.lock() {
+                Ok(slot) => slot.chunk.structures().starts.into_iter().map(Ok).collect(),
+                Err(_) => vec![McError::custom("Failed to lock chunk.")],
+            }
+        });
+        let on_disk = self.iter_chunks(dimension)?.flat_map(move |chunk| match chunk {
+            Ok((coord, _)) if loaded_coords.contains(&coord) => Vec::new(),
+            Ok((_, root)) => ChunkStructures::from_chunk_nbt(root.tag()).starts.into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err)],
+        });
+        Ok(in_loaded.chain(on_disk)
+            .filter(move |start| start.as_ref().map_or(true, |start| structure_matches(&start.name, kind))))
+    }
+
+    /// Searches the world for item stacks that `predicate` returns true for, lazily yielding where each was found.
+    /// Player files are searched first, then each dimension's block entities and entities, including items
+    /// nested within other items (such as the contents of a shulker box).
+    ///
+    /// Loaded chunks are searched in memory, while chunks that aren't loaded, and the `entities` region folder,
+    /// are read from disk one chunk at a time without being loaded into the world.
+    pub fn find_items<'a, F>(&'a self, predicate: F) -> McResult<impl Iterator<Item = McResult<FoundItem>> + 'a>
+    where F: Fn(&ItemStack) -> bool + 'a {
+        let predicate = std::rc::Rc::new(predicate);
+        let players = player_uuids(&self.directory)?.into_iter().flat_map(move |uuid| {
+            let read = || -> McResult<Vec<FoundItem>> {
+                let mut reader = std::io::BufReader::new(std::fs::File::open(player_data_path(&self.directory, &uuid))?);
+                match read_nbt_auto(&mut reader)?.tag() {
+                    Tag::Compound(root) => Ok(player_items(&uuid, root)),
+                    _ => Ok(Vec::new()),
+                }
+            };
+            match read() {
+                Ok(found) => found.into_iter().map(Ok).collect(),
+                Err(err) => vec![Err(err)],
+            }
+        });
+        let mut dimensions: Vec<Box<dyn Iterator<Item = McResult<FoundItem>> + 'a>> = Vec::new();
+        for dimension in self.dimensions.dimensions() {
+            let loaded = self.chunks.iter()
+                .filter(|(coord, _)| coord.dimension == dimension)
+                .map(|(coord, slot)| (*coord, slot.clone()))
+                .collect::<Vec<_>>();
+            let loaded_coords = loaded.iter().map(|(coord, _)| *coord).collect::<std::collections::HashSet<_>>();
+            let in_loaded = loaded.into_iter().flat_map(move |(_, slot)| {
+                let Ok(slot) = slot.lock() else {
+                    return vec![McError::custom("Failed to lock chunk.")];
+                };
+                let block_entities = slot.chunk.block_entities.iter()
+                    .flat_map(|block_entity| block_entity_items(&block_entity.clone().to_map(), dimension));
+                let entities = match &slot.chunk.entities {
+                    Some(ListTag::Compound(entities)) => entities.as_slice(),
+                    _ => &[],
+                };
+                block_entities.chain(entities.iter().flat_map(|entity| entity_items(entity, dimension)))
+                    .map(Ok)
+                    .collect()
+            });
+            let on_disk = self.iter_chunks(dimension)?
+                .filter(move |chunk| !matches!(chunk, Ok((coord, _)) if loaded_coords.contains(coord)));
+            let entity_chunks = ChunkIter::new(RegionIter::new(self.get_entities_directory(dimension)?, dimension)?);
+            let from_disk = on_disk.chain(entity_chunks).flat_map(move |chunk| match chunk {
+                Ok((_, root)) => chunk_items(root.tag(), dimension).into_iter().map(Ok).collect(),
+                Err(err) => vec![Err(err)],
+            });
+            dimensions.push(Box::new(in_loaded.chain(from_disk)));
+        }
+        Ok(players.chain(dimensions.into_iter().flatten())
+            .filter(move |found| found.as_ref().map_or(true, |found| predicate(&found.item))))
+    }
+
+    /// Replaces the blocks in a dimension that `filter` returns true for with `replacement`,
+    /// removing the block entities of the replaced blocks. Returns the number of blocks that were replaced.
+    ///
+    /// Loaded chunks are edited in memory and marked dirty. Chunks that aren't loaded are read from
+    /// the region files one at a time, and written back with `isLightOn` cleared so that the game
+    /// recalculates their light. A chunk is only decoded if one of its section palettes contains a matching block state.
+    /// Chunks from before 1.18 aren't rewritten; [McError::UnsupportedDataVersion] is returned if one of them
+    /// contains a matching block state.
+    pub fn replace_blocks<F, T>(&mut self, dimension: Dimension, filter: F, replacement: T) -> McResult<u64>
+    where F: Fn(&BlockState) -> bool, T: Borrow<BlockState> {
+        self.replace_blocks_cancellable(dimension, filter, replacement, &CancellationToken::new())
+    }
+
+    /// Like [VirtualJavaWorld::replace_blocks], but returns [McError::Cancelled] once `cancel` is cancelled.
+    /// The changes to each region file are written after all of its chunks have been read, so a region
+    /// file is either completely updated or left as it was. Regions that were finished before the
+    /// cancellation (and loaded chunks) keep their replaced blocks.
+    pub fn replace_blocks_cancellable<F, T>(&mut self, dimension: Dimension, filter: F, replacement: T, cancel: &CancellationToken) -> McResult<u64>
+    where F: Fn(&BlockState) -> bool, T: Borrow<BlockState> {
+        cancel.check()?;
+        let replacement = replacement.borrow();
+        // Blocks that are already the replacement are left alone so that they aren't counted.
+        let filter = |state: &BlockState| state != replacement && filter(state);
+        let air_matches = filter(&BlockState::air());
+        let replacement_id = self.block_registry.register(replacement);
+        let matches = (0..self.block_registry.len() as u32)
+            .map(|id| self.block_registry.get(id).is_some_and(filter))
+            .collect::<Vec<bool>>();
+        let mut replaced = 0;
+        for (coord, slot) in self.chunks.iter() {
+            if coord.dimension != dimension {
+                continue;
+            }
+            let Ok(mut slot) = slot.lock() else {
+                return McError::custom("Failed to lock chunk.");
+            };
+            let count = replace_matching(&mut slot.chunk, &matches, air_matches, replacement_id);
+            if count > 0 {
+                slot.mark_dirty();
+                replaced += count;
+            }
+        }
+        for region_coord in self.iter_regions(dimension)? {
+            cancel.check()?;
+            let path = self.get_region_directory(dimension)?.join(region_coord.region_file_name());
+            // A region that the world has open has to be written through the world's handle.
+            let slot = self.regions.get(&region_coord).cloned();
+            let mut region_lock = slot.as_ref().map(|slot| slot.lock());
+            let mut opened = None;
+            let region = match region_lock.as_mut() {
+                Some(Ok(slot)) => &mut slot.region,
+                Some(Err(_)) => return McError::custom("Failed to lock region."),
+                None => opened.insert(RegionFile::open(&path)?),
+            };
+            let mut edited = Vec::new();
+            for index in 0..1024usize {
+                cancel.check()?;
+                let local = RegionCoord::from(index);
+                let coord = region_coord.chunk_in_region(local);
+                if self.chunks.contains_key(&coord) {
+                    continue;
+                }
+                let root: NamedTag = match region.read_data(local) {
+                    Ok(root) => root,
+                    Err(McError::RegionDataNotFound) => continue,
+                    Err(err) => return Err(err),
+                };
+                if !palette_may_match(root.tag(), &filter) {
+                    continue;
+                }
+                if ChunkLayout::of_chunk_nbt(root.tag())? != ChunkLayout::Flattened {
+                    let data_version = match root.tag() {
+                        Tag::Compound(map) => match map.get("DataVersion") {
+                            Some(Tag::Int(data_version)) => *data_version,
+                            _ => 0,
+                        },
+                        _ => 0,
+                    };
+                    return Err(McError::UnsupportedDataVersion(data_version));
+                }
+                let mut registry = BlockRegistry::with_air();
+                let mut chunk = decode_versioned_chunk(&mut registry, root.take_tag())?;
+                let local_replacement = registry.register(replacement);
+                let matches = (0..registry.len() as u32)
+                    .map(|id| registry.get(id).is_some_and(filter))
+                    .collect::<Vec<bool>>();
+                let count = replace_matching(&mut chunk, &matches, air_matches, local_replacement);
+                if count > 0 {
+                    SaveOptions::new().with_light_on(false).apply(&mut chunk);
+                    edited.push((local, NamedTag::new(chunk.to_nbt(&registry)), count));
+                }
+            }
+            for (local, root, count) in edited {
+                region.write_data_with_utcnow(local, &root)?;
+                replaced += count;
+            }
+        }
+        Ok(replaced)
+    }
+
+    /// Calls `f` with each chunk that overlaps the blocks from `min` to `max` (inclusive),
+    /// loading chunks as needed, along with the corners of the part of the box within that chunk.
+    /// The box is clipped to the height of the chunk's sections.
+    /// If `f` returns true, the chunk is marked dirty.
+    pub(crate) fn edit_chunks_in_box<F>(&mut self, min: BlockCoord, max: BlockCoord, mut f: F) -> McResult<()>
+    where F: FnMut(&mut Chunk, (i64, i64, i64), (i64, i64, i64)) -> McResult<bool> {
+        let low = (min.x.min(max.x), min.y.min(max.y), min.z.min(max.z));
+        let high = (min.x.max(max.x), min.y.max(max.y), min.z.max(max.z));
+        for chunk_z in low.2.div_euclid(16)..=high.2.div_euclid(16) {
+            for chunk_x in low.0.div_euclid(16)..=high.0.div_euclid(16) {
+                let slot = self.get_or_load_chunk(WorldCoord::new(chunk_x, chunk_z, min.dimension))?;
+                let Ok(mut slot) = slot.lock() else {
+                    return McError::custom("Failed to lock chunk.");
+                };
+                let height = slot.chunk.height_range();
+                let chunk_low = (low.0.max(chunk_x * 16), low.1.max(height.start), low.2.max(chunk_z * 16));
+                let chunk_high = (high.0.min(chunk_x * 16 + 15), high.1.min(height.end - 1), high.2.min(chunk_z * 16 + 15));
+                if chunk_low.1 > chunk_high.1 {
+                    continue;
+                }
+                if f(&mut slot.chunk, chunk_low, chunk_high)? {
+                    slot.mark_dirty();
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn query_neighbor_ids(&self, coord: BlockCoord) -> CubeNeighbors<u32> {
         macro_rules! get_neighbor {
             ($x:expr, $y:expr, $z:expr) => {
@@ -441,4 +1567,365 @@ the user wants to get/set a block, the chunk is automatically loaded if it hasn'
 The chunk is then stored in a queue. The once the queue reaches a certain size, the oldest element is
 pulled out of the queue and it is saved and unloaded. Any time a chunk that is already in the queue is
 edited, it goes to the back of the queue.
-*/
\ No newline at end of file
+*/
+
+/// Tests if an entity's position is within the blocks from `min` to `max` (inclusive).
+fn entity_in_box(entity: &Entity, min: BlockCoord, max: BlockCoord) -> bool {
+    entity.block_coord().is_some_and(|(x, y, z)| {
+        (min.x.min(max.x)..=min.x.max(max.x)).contains(&x)
+            && (min.y.min(max.y)..=min.y.max(max.y)).contains(&y)
+            && (min.z.min(max.z)..=min.z.max(max.z)).contains(&z)
+    })
+}
+
+/// Returns false if none of the chunk's section palettes contain a block state that `filter` returns true for.
+/// Chunks with a layout that has no palettes may match.
+fn palette_may_match<F: Fn(&BlockState) -> bool>(nbt: &Tag, filter: &F) -> bool {
+    if filter(&BlockState::air()) {
+        return true;
+    }
+    let palette_matches = |palette: Option<&Tag>| match palette {
+        Some(Tag::List(ListTag::Compound(palette))) => palette.iter()
+            .any(|entry| BlockState::try_from_map(entry).map_or(true, |state| filter(&state))),
+        _ => true,
+    };
+    let Tag::Compound(root) = nbt else {
+        return true;
+    };
+    if let Some(Tag::List(ListTag::Compound(sections))) = root.get("sections") {
+        return sections.iter().any(|section| match section.get("block_states") {
+            Some(Tag::Compound(block_states)) => palette_matches(block_states.get("palette")),
+            _ => false,
+        });
+    }
+    match root.get("Level") {
+        Some(Tag::Compound(level)) => match level.get("Sections") {
+            Some(Tag::List(ListTag::Compound(sections))) => sections.iter().any(|section| palette_matches(section.get("Palette"))),
+            _ => false,
+        },
+        _ => true,
+    }
+}
+
+/// The coordinates of the blocks in a chunk with ids that are true in `matches`.
+/// Empty sections are air, so they match if `air_matches` is true.
+fn find_matching(chunk: &Chunk, matches: &[bool], air_matches: bool, dimension: Dimension) -> Vec<BlockCoord> {
+    let mut found = Vec::new();
+    for section in chunk.sections.sections.iter() {
+        let base = (chunk.x as i64 * 16, section.y as i64 * 16, chunk.z as i64 * 16);
+        for index in 0..4096usize {
+            let is_match = match &section.blocks {
+                Some(blocks) => matches.get(blocks[index] as usize).copied().unwrap_or(false),
+                None => air_matches,
+            };
+            if is_match {
+                let (x, y, z) = (index as i64 & 15, index as i64 >> 8, (index as i64 >> 4) & 15);
+                found.push(BlockCoord::new(base.0 + x, base.1 + y, base.2 + z, dimension));
+            }
+        }
+    }
+    found
+}
+
+/// Replaces the blocks in a chunk with ids that are true in `matches`, and removes their block entities.
+/// Returns the number of blocks that were replaced.
+fn replace_matching(chunk: &mut Chunk, matches: &[bool], air_matches: bool, replacement: u32) -> u64 {
+    let is_match = |id: u32| matches.get(id as usize).copied().unwrap_or(false);
+    let replaced_entities = chunk.block_entities.iter()
+        .map(BlockEntity::coord)
+        .filter(|&coord| chunk.contains_coord(coord) && chunk.get_id(coord).map_or(air_matches, is_match))
+        .collect::<Vec<_>>();
+    for coord in replaced_entities {
+        chunk.remove_block_entity(coord);
+    }
+    let mut replaced = 0;
+    for section in chunk.sections.sections.iter_mut() {
+        match &mut section.blocks {
+            Some(blocks) => for id in blocks.iter_mut().filter(|id| is_match(**id)) {
+                *id = replacement;
+                replaced += 1;
+            },
+            None if air_matches => {
+                section.blocks = Some(vec![replacement; 4096].into_boxed_slice());
+                replaced += 4096;
+            }
+            None => (),
+        }
+    }
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_entities_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = VirtualJavaWorld::open(dir.path());
+        let coord = WorldCoord::nether(-1, 33);
+        assert!(world.load_entities(coord).unwrap().is_none());
+        let entities_dir = world.get_entities_directory(Dimension::Nether).unwrap();
+        std::fs::create_dir_all(&entities_dir).unwrap();
+        let mut region = RegionFile::create(entities_dir.join("r.-1.1.mca")).unwrap();
+        region.write_data(coord.xz(), &NamedTag::new(crate::compound! {
+            ("Position", vec![-1i32, 33]),
+        })).unwrap();
+        drop(region);
+        assert!(world.load_entities(coord).unwrap().is_some());
+        assert!(world.load_entities(WorldCoord::nether(0, 33)).unwrap().is_none());
+        assert!(world.load_poi(coord).unwrap().is_none());
+    }
+
+    #[test]
+    fn entities_in_box_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = VirtualJavaWorld::open(dir.path());
+        let coord = WorldCoord::overworld(-1, 0);
+        let mut chunk = EntityChunk::new(3465, -1, 0);
+        chunk.entities.push(Entity::new("minecraft:zombie", (-10.5, 64.0, 3.5)));
+        chunk.entities.push(Entity::new("minecraft:cow", (-3.5, 70.0, 3.5)));
+        world.save_entity_chunk(coord, chunk).unwrap();
+        let min = BlockCoord::overworld(-16, 0, 0);
+        let max = BlockCoord::overworld(16, 100, 16);
+        assert_eq!(world.entities_in_box(min, max).unwrap().len(), 2);
+        let zombies = world.entities_in_box(min, BlockCoord::overworld(-5, 100, 16)).unwrap();
+        assert_eq!(zombies.len(), 1);
+        assert_eq!(zombies[0].id(), Some("minecraft:zombie"));
+        let removed = world.remove_entities_in_box(min, max, |entity| entity.id() == Some("minecraft:zombie")).unwrap();
+        assert_eq!(removed, 1);
+        let chunk = world.load_entity_chunk(coord).unwrap().unwrap();
+        assert_eq!(chunk.entities.len(), 1);
+        assert_eq!(chunk.entities[0].id(), Some("minecraft:cow"));
+    }
+
+    #[test]
+    fn iter_chunks_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = VirtualJavaWorld::open(dir.path());
+        assert_eq!(world.iter_regions(Dimension::Overworld).unwrap().count(), 0);
+        let region_dir = world.get_region_directory(Dimension::Overworld).unwrap();
+        std::fs::create_dir_all(&region_dir).unwrap();
+        std::fs::write(region_dir.join("notes.txt"), "not a region").unwrap();
+        for (x, z) in [(0, 0), (-1, 2)] {
+            let mut region = RegionFile::create(RegionFileName::new(x, z).path(&region_dir)).unwrap();
+            region.write_data((5, 6), &NamedTag::new(crate::compound! {
+                ("xPos", x * 32 + 5),
+            })).unwrap();
+        }
+        let regions = world.iter_regions(Dimension::Overworld).unwrap().collect::<Vec<_>>();
+        assert_eq!(regions, vec![WorldCoord::overworld(0, 0), WorldCoord::overworld(-1, 2)]);
+        let chunks = world.iter_chunks(Dimension::Overworld).unwrap()
+            .map(|chunk| chunk.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(chunks, vec![WorldCoord::overworld(5, 6), WorldCoord::overworld(-27, 70)]);
+    }
+
+    #[test]
+    fn fill_and_clone_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        for z in -1..=1 {
+            for x in -1..=1 {
+                let chunk = crate::world::chunk::tests::empty_chunk(x, z);
+                world.chunks.insert(WorldCoord::overworld(x as i64, z as i64), ChunkSlot::arc_new(chunk));
+            }
+        }
+        let stone = BlockState::from("minecraft:stone");
+        // The box crosses chunk borders and extends below the bottom section.
+        let changed = world.fill(BlockCoord::overworld(-2, -5, -2), BlockCoord::overworld(1, 1, 1), &stone).unwrap();
+        assert_eq!(changed, 4 * 2 * 4);
+        assert_eq!(world.get_state(BlockCoord::overworld(-2, 0, 1)), Some(&stone));
+        assert_eq!(world.get_state(BlockCoord::overworld(2, 0, 1)).map(BlockState::name), Some("minecraft:air"));
+        assert!(world.chunks.values().filter(|slot| slot.lock().unwrap().dirty).count() == 4);
+        {
+            let slot = world.get_chunk(WorldCoord::overworld(-1, -1)).unwrap();
+            let mut slot = slot.lock().unwrap();
+            slot.chunk.set_block_entity(crate::world::chunk::BlockEntity::new("minecraft:chest", (-1, 1, -1), crate::nbt::Map::new())).unwrap();
+        }
+        // Overlapping copy, shifted by one block.
+        let copied = world.clone_region(BlockCoord::overworld(-2, 0, -2), BlockCoord::overworld(1, 1, 1), BlockCoord::overworld(-1, 0, -1)).unwrap();
+        assert_eq!(copied, 32);
+        assert_eq!(world.get_state(BlockCoord::overworld(2, 1, 2)), Some(&stone));
+        assert_eq!(world.get_state(BlockCoord::overworld(-2, 1, -2)), Some(&stone));
+        let slot = world.get_chunk(WorldCoord::overworld(0, 0)).unwrap();
+        let slot = slot.lock().unwrap();
+        assert!(slot.chunk.get_block_entity((0, 1, 0)).is_some());
+    }
+
+    #[test]
+    fn find_and_replace_blocks_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        let tnt = BlockState::from("minecraft:tnt");
+        for x in 0..3 {
+            let coord = WorldCoord::overworld(x, 0);
+            world.chunks.insert(coord, ChunkSlot::arc_new(crate::world::chunk::tests::empty_chunk(x as i32, 0)));
+            world.set_state(BlockCoord::overworld(x * 16 + 1, 2, 3), &tnt);
+        }
+        world.set_state(BlockCoord::overworld(0, 0, 0), BlockState::from("minecraft:stone"));
+        world.save_all().unwrap();
+        // Chunk 2 has no TNT on disk, but its loaded copy does.
+        world.unload_chunk(WorldCoord::overworld(0, 0));
+        world.unload_chunk(WorldCoord::overworld(1, 0));
+        world.set_state(BlockCoord::overworld(35, 4, 5), &tnt);
+        let is_tnt = |state: &BlockState| state.name() == "minecraft:tnt";
+        let mut found = world.find_blocks(Dimension::Overworld, is_tnt).unwrap()
+            .collect::<McResult<Vec<_>>>().unwrap();
+        found.sort();
+        assert_eq!(found, vec![
+            BlockCoord::overworld(1, 2, 3),
+            BlockCoord::overworld(17, 2, 3),
+            BlockCoord::overworld(33, 2, 3),
+            BlockCoord::overworld(35, 4, 5),
+        ]);
+        assert_eq!(world.replace_blocks(Dimension::Overworld, is_tnt, BlockState::from("minecraft:sand")).unwrap(), 4);
+        assert_eq!(world.find_blocks(Dimension::Overworld, is_tnt).unwrap().count(), 0);
+        let sand = world.find_blocks(Dimension::Overworld, |state| state.name() == "minecraft:sand").unwrap().count();
+        assert_eq!(sand, 4);
+        world.load_chunk(WorldCoord::overworld(0, 0)).unwrap();
+        assert_eq!(world.get_state(BlockCoord::overworld(0, 0, 0)).map(BlockState::name), Some("minecraft:stone"));
+    }
+
+    #[test]
+    fn selection_test() {
+        use crate::world::{selection::*, stats::WorldStats};
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        for x in 0..2 {
+            world.chunks.insert(WorldCoord::overworld(x, 0), ChunkSlot::arc_new(crate::world::chunk::tests::empty_chunk(x as i32, 0)));
+        }
+        let sphere = SphereSelection::new((16, 4, 8), 2.0);
+        assert_eq!(world.fill_selection(Dimension::Overworld, &sphere, BlockState::from("minecraft:stone")).unwrap(), 33);
+        let cylinder = CylinderSelection::new((16, 8), 1.0, 4, 4);
+        let is_stone = |state: &BlockState| state.name() == "minecraft:stone";
+        assert_eq!(world.replace_in_selection(Dimension::Overworld, &cylinder, is_stone, BlockState::from("minecraft:sand")).unwrap(), 5);
+        let stats = WorldStats::collect_selection(&mut world, Dimension::Overworld, &sphere).unwrap();
+        assert_eq!(stats.total_chunks(), 2);
+        assert_eq!(stats.blocks.get(&BlockState::from("minecraft:stone")), Some(&28));
+        assert_eq!(stats.blocks.get(&BlockState::from("minecraft:sand")), Some(&5));
+        world.save_all().unwrap();
+        assert_eq!(world.prune_chunks(Dimension::Overworld, &ChunkSelection::from_iter([(1, 0), (5, 5)])).unwrap(), 1);
+        assert!(!world.is_chunk_loaded(WorldCoord::overworld(1, 0)));
+        assert!(world.load_chunk(WorldCoord::overworld(1, 0)).is_err());
+        assert!(world.load_chunk(WorldCoord::overworld(0, 0)).is_ok());
+    }
+
+    #[test]
+    fn find_items_test() {
+        use crate::world::item::ItemHolder;
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        let is_diamond = |item: &ItemStack| item.id == "minecraft:diamond";
+        assert_eq!(world.find_items(is_diamond).unwrap().count(), 0);
+        let Tag::Compound(chest) = Tag::parse(r#"{ Items: [
+            { Slot: 0b, id: "minecraft:diamond", count: 2 },
+            { Slot: 1b, id: "minecraft:shulker_box", count: 1, components: { "minecraft:container": [{ slot: 0, item: { id: "minecraft:diamond", count: 5 } }] } }
+        ] }"#).unwrap() else {
+            panic!("Chest is not a compound.");
+        };
+        let mut chunk = crate::world::chunk::tests::empty_chunk(0, 0);
+        chunk.set_block_entity(BlockEntity::new("minecraft:chest", (1, 2, 3), chest)).unwrap();
+        world.chunks.insert(WorldCoord::overworld(0, 0), ChunkSlot::arc_new(chunk));
+        let mut entities = EntityChunk::new(3953, 0, 0);
+        let mut frame = Entity::new("minecraft:item_frame", (4.5, 70.0, 4.5));
+        frame.nbt_mut().insert("Item".to_owned(), Tag::Compound(ItemStack::new("minecraft:diamond", 1).encode_map(Some(3953))));
+        entities.entities.push(frame);
+        world.save_entity_chunk(WorldCoord::nether(0, 0), entities).unwrap();
+        let mut player = crate::world::player::PlayerData::decode_nbt(Tag::parse(r#"{ DataVersion: 3465, Pos: [0.5d, 64.0d, 0.5d], Rotation: [0.0f, 0.0f], Dimension: "minecraft:overworld" }"#).unwrap()).unwrap();
+        player.inventory.push(ItemStack::new("minecraft:diamond", 64).with_slot(8));
+        player.inventory.push(ItemStack::new("minecraft:stone", 64).with_slot(9));
+        player.save_to_world(dir.path(), "069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap();
+
+        let found = world.find_items(is_diamond).unwrap().collect::<McResult<Vec<_>>>().unwrap();
+        let found = found.iter()
+            .map(|found| (found.path.to_string(), found.item.count, &found.holder))
+            .collect::<Vec<_>>();
+        assert_eq!(found.len(), 4);
+        assert!(matches!(found[0], (ref path, 64, ItemHolder::Player { .. }) if path == "Inventory[0]"));
+        assert!(matches!(found[1], (ref path, 2, ItemHolder::BlockEntity { coord, .. }) if path == "Items[0]" && *coord == BlockCoord::overworld(1, 2, 3)));
+        assert!(matches!(found[2], (ref path, 5, ItemHolder::BlockEntity { .. }) if path == "Items[1].components.\"minecraft:container\"[0].item"));
+        assert!(matches!(found[3], (ref path, 1, ItemHolder::Entity { dimension: Dimension::Nether, ref id, .. }) if path == "Item" && id == "minecraft:item_frame"));
+    }
+
+    #[test]
+    fn save_options_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        let coord = WorldCoord::overworld(0, 0);
+        let mut chunk = crate::world::chunk::tests::empty_chunk(0, 0);
+        chunk.status = "minecraft:features".to_owned();
+        chunk.other.insert("isLightOn".to_owned(), Tag::Byte(1));
+        chunk.other.insert("below_zero_retrogen".to_owned(), Tag::Compound(crate::nbt::Map::new()));
+        world.chunks.insert(coord, ChunkSlot::arc_new(chunk));
+        world.set_state(BlockCoord::overworld(0, 0, 0), BlockState::from("minecraft:stone"));
+        world.save_chunk(coord, &SaveOptions::edited()).unwrap();
+        let mut region = RegionFile::open(world.get_region_directory(Dimension::Overworld).unwrap().join("r.0.0.mca")).unwrap();
+        let root: NamedTag = region.read_data(coord.xz()).unwrap();
+        let Tag::Compound(map) = root.tag() else {
+            panic!("Chunk is not a compound.");
+        };
+        assert!(matches!(map.get("Status"), Some(Tag::String(status)) if status == "minecraft:full"));
+        assert!(matches!(map.get("isLightOn"), Some(Tag::Byte(0))));
+        assert!(map.get("below_zero_retrogen").is_none());
+
+        // The world's options are used by save_dirty.
+        world.set_save_options(SaveOptions::new().with_status("minecraft:light"));
+        world.set_state(BlockCoord::overworld(0, 1, 0), BlockState::from("minecraft:stone"));
+        world.save_dirty().unwrap();
+        let mut region = RegionFile::open(world.get_region_directory(Dimension::Overworld).unwrap().join("r.0.0.mca")).unwrap();
+        let root: NamedTag = region.read_data(coord.xz()).unwrap();
+        let Tag::Compound(map) = root.tag() else {
+            panic!("Chunk is not a compound.");
+        };
+        assert!(matches!(map.get("Status"), Some(Tag::String(status)) if status == "minecraft:light"));
+    }
+
+    #[test]
+    fn chunk_limit_after_unload_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        let insert = |world: &mut VirtualJavaWorld, x: i32| {
+            let coord = WorldCoord::overworld(x as i64, 0);
+            world.chunks.insert(coord, ChunkSlot::arc_new(crate::world::chunk::tests::empty_chunk(x, 0)));
+            world.touch_chunk(coord);
+        };
+        insert(&mut world, 0);
+        world.unload_all();
+        assert!(world.chunk_usage.lock().unwrap().order.is_empty());
+        // A usage entry for a chunk that isn't loaded must not stall eviction.
+        world.touch_chunk(WorldCoord::overworld(5, 0));
+        insert(&mut world, 1);
+        insert(&mut world, 2);
+        world.set_chunk_limit(Some(1)).unwrap();
+        assert_eq!(world.chunks.len(), 1);
+        assert!(world.chunks.contains_key(&WorldCoord::overworld(2, 0)));
+        assert_eq!(world.chunk_usage.lock().unwrap().order.len(), 1);
+    }
+
+    #[test]
+    fn save_poisoned_chunk_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut world = VirtualJavaWorld::open(dir.path());
+        std::fs::create_dir_all(world.get_region_directory(Dimension::Overworld).unwrap()).unwrap();
+        for x in 0..2 {
+            let slot = ChunkSlot::arc_new(crate::world::chunk::tests::empty_chunk(x, 0));
+            slot.lock().unwrap().mark_dirty();
+            world.chunks.insert(WorldCoord::overworld(x as i64, 0), slot);
+        }
+        let poisoned = world.get_chunk(WorldCoord::overworld(1, 0)).unwrap();
+        let _ = std::thread::spawn(move || {
+            let _lock = poisoned.lock().unwrap();
+            panic!("Poisoning the chunk lock.");
+        }).join();
+        assert!(matches!(world.save_dirty(), Err(McError::FailedToSaveChunk)));
+        let slot = world.get_chunk(WorldCoord::overworld(0, 0)).unwrap();
+        assert!(!slot.lock().unwrap().dirty);
+        let mut region = RegionFile::open(world.get_region_directory(Dimension::Overworld).unwrap().join("r.0.0.mca")).unwrap();
+        assert!(region.read_data::<_, NamedTag>((0, 0)).is_ok());
+    }
+}